@@ -0,0 +1,166 @@
+// Copyright 2015 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! A connection between a sandboxed child and its parent.
+//!
+//! `SandboxMethods::start_with_channel()` starts the child with one end of a connected pair of
+//! Unix stream sockets and returns the other end as a `SandboxChannel`. The child picks its end
+//! up with `ChildSandboxChannel::from_environment()`. Either side can use its end as a plain
+//! stream, through `Read` and `Write`, or exchange whole messages with `send_message()` and
+//! `recv_message()`, which frame each with its length in four little-endian bytes.
+
+use std::env;
+use std::io::{self, Read, Write};
+use std::os::unix::io::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+use std::os::unix::net::UnixStream;
+
+/// The environment variable through which the parent tells the child which descriptor is its end
+/// of the channel.
+pub(crate) const CHANNEL_FD_ENV_VAR: &str = "GAOL_CHANNEL_FD";
+
+/// The longest message `recv_message()` accepts, so that a peer can't make this process allocate
+/// whatever it claims to be sending.
+pub const MAX_MESSAGE_SIZE: usize = 16 * 1024 * 1024;
+
+/// The parent's end of the channel to a child started with `SandboxMethods::start_with_channel()`.
+pub struct SandboxChannel {
+    stream: UnixStream,
+}
+
+impl SandboxChannel {
+    /// Creates a channel, returning this process's end and the descriptor for the child's, both
+    /// close-on-exec.
+    pub(crate) fn pair() -> io::Result<(SandboxChannel, OwnedFd)> {
+        let (stream, child_stream) = UnixStream::pair()?;
+        Ok((SandboxChannel {
+            stream,
+        }, OwnedFd::from(child_stream)))
+    }
+
+    /// Sends `message` to the child in one piece.
+    pub fn send_message(&mut self, message: &[u8]) -> io::Result<()> {
+        send_message(&mut self.stream, message)
+    }
+
+    /// Waits for the next message from the child. Fails with `UnexpectedEof` if the child closed
+    /// its end, and with `InvalidData` if the message is longer than `MAX_MESSAGE_SIZE`.
+    pub fn recv_message(&mut self) -> io::Result<Vec<u8>> {
+        recv_message(&mut self.stream)
+    }
+}
+
+impl Read for SandboxChannel {
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        self.stream.read(buffer)
+    }
+}
+
+impl Write for SandboxChannel {
+    fn write(&mut self, buffer: &[u8]) -> io::Result<usize> {
+        self.stream.write(buffer)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.stream.flush()
+    }
+}
+
+impl AsRawFd for SandboxChannel {
+    fn as_raw_fd(&self) -> RawFd {
+        self.stream.as_raw_fd()
+    }
+}
+
+/// The child's end of the channel to its parent.
+///
+/// Every profile allows reading and writing descriptors the process already has, including with
+/// `recvfrom()` and `sendto()`, so the channel keeps working after the sandbox is activated.
+pub struct ChildSandboxChannel {
+    stream: UnixStream,
+}
+
+impl ChildSandboxChannel {
+    /// Picks up the end of the channel that `SandboxMethods::start_with_channel()` gave this
+    /// process, and makes it close-on-exec, so that programs this process runs don't inherit it.
+    /// Fails with `NotFound` if this process wasn't started with a channel.
+    ///
+    /// Call this before activating the sandbox, which clears the environment.
+    pub fn from_environment() -> io::Result<ChildSandboxChannel> {
+        let fd: RawFd = match env::var(CHANNEL_FD_ENV_VAR).ok().and_then(|fd| fd.parse().ok()) {
+            Some(fd) => fd,
+            None => {
+                return Err(io::Error::new(io::ErrorKind::NotFound,
+                                          "this process wasn't started with a channel"))
+            }
+        };
+        env::remove_var(CHANNEL_FD_ENV_VAR);
+        if unsafe { libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC) } != 0 {
+            return Err(io::Error::last_os_error())
+        }
+        Ok(ChildSandboxChannel {
+            stream: unsafe { UnixStream::from_raw_fd(fd) },
+        })
+    }
+
+    /// Sends `message` to the parent in one piece.
+    pub fn send_message(&mut self, message: &[u8]) -> io::Result<()> {
+        send_message(&mut self.stream, message)
+    }
+
+    /// Waits for the next message from the parent. Fails as `SandboxChannel::recv_message()`
+    /// does.
+    pub fn recv_message(&mut self) -> io::Result<Vec<u8>> {
+        recv_message(&mut self.stream)
+    }
+}
+
+impl Read for ChildSandboxChannel {
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        self.stream.read(buffer)
+    }
+}
+
+impl Write for ChildSandboxChannel {
+    fn write(&mut self, buffer: &[u8]) -> io::Result<usize> {
+        self.stream.write(buffer)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.stream.flush()
+    }
+}
+
+impl AsRawFd for ChildSandboxChannel {
+    fn as_raw_fd(&self) -> RawFd {
+        self.stream.as_raw_fd()
+    }
+}
+
+fn send_message(stream: &mut UnixStream, message: &[u8]) -> io::Result<()> {
+    if message.len() > MAX_MESSAGE_SIZE {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "message too long"))
+    }
+    let mut framed = Vec::with_capacity(4 + message.len());
+    framed.extend_from_slice(&(message.len() as u32).to_le_bytes());
+    framed.extend_from_slice(message);
+    stream.write_all(&framed)
+}
+
+fn recv_message(stream: &mut UnixStream) -> io::Result<Vec<u8>> {
+    let mut length = [0; 4];
+    stream.read_exact(&mut length)?;
+    let length = u32::from_le_bytes(length) as usize;
+    if length > MAX_MESSAGE_SIZE {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "message too long"))
+    }
+    let mut message = vec![0; length];
+    stream.read_exact(&mut message)?;
+    Ok(message)
+}
@@ -0,0 +1,119 @@
+// Copyright 2015 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Why the operating system refused to apply a sandbox's restrictions.
+
+use crate::profile::{Describe, Operation};
+
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+/// Which restriction couldn't be applied, and the error the operating system gave.
+///
+/// `ActivationError::RestrictionFailed` carries one of these. Errors that don't belong to any
+/// mechanism below, and all errors on platforms other than Linux, are `PlatformError`.
+#[derive(Debug)]
+pub enum SandboxError {
+    /// The jail's filesystem couldn't be set up in the process's mount namespace. This is what
+    /// happens when the process wasn't started in namespaces of its own by `Sandbox::start()`.
+    NamespaceFailed(io::Error),
+    /// This process may not create user namespaces, so `Sandbox::start()` can't start children
+    /// in namespaces of their own. The error says whether a sysctl forbids them or what `errno`
+    /// creating one failed with. `SandboxOptions::disable_namespaces()` starts children confined
+    /// by their seccomp filter alone instead.
+    UserNamespacesUnavailable(io::Error),
+    /// The seccomp filter couldn't be installed, or its notification descriptor couldn't be sent
+    /// to the supervisor.
+    SeccompFailed(io::Error),
+    /// A path couldn't be bound into the `chroot` jail, or the jail couldn't be entered.
+    ChrootFailed(io::Error),
+    /// The path the profile allows couldn't be bound into the `chroot` jail.
+    MountFailed {
+        /// The path outside the jail.
+        path: PathBuf,
+        /// Why it couldn't be bound.
+        error: io::Error,
+    },
+    /// The capabilities the process holds in its user namespace couldn't be dropped.
+    CapabilityDropFailed(io::Error),
+    /// One of the `ResourceLimits` couldn't be set with `setrlimit()`.
+    ResourceLimitFailed(io::Error),
+    /// The profile allows an operation that this platform can't enforce.
+    UnsupportedOperation(Operation),
+    /// Anything else, described for humans.
+    PlatformError(String),
+}
+
+impl SandboxError {
+    /// Returns the operating system's error number, if there is one.
+    pub fn raw_os_error(&self) -> Option<i32> {
+        self.io_error().and_then(io::Error::raw_os_error)
+    }
+
+    fn io_error(&self) -> Option<&io::Error> {
+        match *self {
+            SandboxError::NamespaceFailed(ref error) |
+            SandboxError::UserNamespacesUnavailable(ref error) |
+            SandboxError::SeccompFailed(ref error) |
+            SandboxError::ChrootFailed(ref error) |
+            SandboxError::CapabilityDropFailed(ref error) |
+            SandboxError::ResourceLimitFailed(ref error) |
+            SandboxError::MountFailed { ref error, .. } => Some(error),
+            SandboxError::UnsupportedOperation(_) | SandboxError::PlatformError(_) => None,
+        }
+    }
+}
+
+impl fmt::Display for SandboxError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SandboxError::NamespaceFailed(ref error) => {
+                write!(f, "the jail couldn't be set up in the mount namespace: {}", error)
+            }
+            SandboxError::UserNamespacesUnavailable(ref error) => {
+                write!(f, "user namespaces are unavailable: {}", error)
+            }
+            SandboxError::SeccompFailed(ref error) => {
+                write!(f, "the seccomp filter couldn't be installed: {}", error)
+            }
+            SandboxError::ChrootFailed(ref error) => {
+                write!(f, "the chroot jail couldn't be set up: {}", error)
+            }
+            SandboxError::MountFailed { ref path, ref error } => {
+                write!(f, "{} couldn't be bound into the chroot jail: {}", path.display(), error)
+            }
+            SandboxError::CapabilityDropFailed(ref error) => {
+                write!(f, "the capabilities couldn't be dropped: {}", error)
+            }
+            SandboxError::ResourceLimitFailed(ref error) => {
+                write!(f, "the resource limits couldn't be set: {}", error)
+            }
+            SandboxError::UnsupportedOperation(ref operation) => {
+                write!(f, "the operation to {} can't be enforced on this platform",
+                       operation.describe())
+            }
+            SandboxError::PlatformError(ref message) => f.write_str(message),
+        }
+    }
+}
+
+impl Error for SandboxError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        self.io_error().map(|error| error as &(dyn Error + 'static))
+    }
+}
+
+impl From<io::Error> for SandboxError {
+    fn from(error: io::Error) -> SandboxError {
+        SandboxError::PlatformError(error.to_string())
+    }
+}
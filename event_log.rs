@@ -0,0 +1,276 @@
+// Copyright 2015 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Machine-readable logs of sandbox lifecycle events and violations.
+//!
+//! With the `serde` feature enabled, `SandboxOptions::event_log()` makes the parent side of a
+//! sandbox write one JSON object per line to a caller-provided file descriptor for every event in
+//! the life of the child. Each record is written with a single `write` call and is never longer
+//! than `MAX_RECORD_SIZE`, so several sandboxes can share one pipe without interleaving.
+//!
+//! The schema is `EventRecord`. Fields that don't apply to an event are `null`.
+
+#[cfg(feature = "serde")]
+use std::io;
+#[cfg(all(feature = "serde", unix))]
+use std::os::fd::{AsRawFd, OwnedFd};
+#[cfg(all(feature = "serde", windows))]
+use std::fs::File;
+#[cfg(all(feature = "serde", windows))]
+use std::io::Write;
+#[cfg(all(feature = "serde", windows))]
+use std::os::windows::io::OwnedHandle;
+#[cfg(feature = "serde")]
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[cfg(unix)]
+use libc::pid_t;
+#[cfg(feature = "serde")]
+use log::error;
+
+#[cfg(windows)]
+use crate::platform::process::pid_t;
+use crate::platform::process::ExitStatus;
+use crate::sandbox::ActivationError;
+use crate::telemetry::{Mechanism, Stage};
+
+/// The longest record that will be written, including the trailing newline. This is the smallest
+/// `PIPE_BUF` POSIX allows, so writes of whole records to pipes are atomic everywhere.
+pub const MAX_RECORD_SIZE: usize = 512;
+
+/// The kind of event a record describes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum EventKind {
+    /// The sandboxed child was started.
+    Started,
+    /// The sandboxed child couldn't be started. `errno` says why.
+    StartFailed,
+    /// The sandboxed child exited normally or was killed by a signal other than a violation.
+    Exited,
+    /// The sandboxed child was killed for performing a prohibited operation.
+    Violation,
+    /// The sandboxed child exited during activation because its pre-activation hook panicked.
+    HookPanicked,
+    /// The sandboxed child was killed for running past its wall-clock limit.
+    TimedOut,
+}
+
+/// One line of the event log.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct EventRecord {
+    /// Milliseconds since the Unix epoch at which the parent observed the event.
+    pub timestamp_ms: u64,
+    /// The process ID of the sandboxed child, as seen by the parent.
+    pub pid: Option<pid_t>,
+    /// What happened.
+    pub event: EventKind,
+    /// The name of the system call responsible for a violation, when it is known.
+    pub syscall: Option<String>,
+    /// The stage of the sandbox lifecycle the event happened in, for example `start` or `wait`.
+    pub stage: Option<String>,
+    /// The error number of a failure.
+    pub errno: Option<i32>,
+    /// The exit code of a child that exited normally.
+    pub exit_code: Option<i32>,
+    /// The signal that terminated the child.
+    pub signal: Option<i32>,
+    /// The mechanisms turned off by the sandbox options, if any were, for example with
+    /// `SandboxOptions::disable_chroot()`.
+    pub disabled: Option<Vec<Mechanism>>,
+    /// How long each stage of starting the child and activating its sandbox took, in
+    /// microseconds, for a `started` event. See `ActivationStatus::timings()`.
+    pub timings_us: Option<Vec<(Stage, u64)>>,
+}
+
+impl EventRecord {
+    /// Creates a record of the given kind, timestamped now, with no other fields set.
+    pub fn new(event: EventKind) -> EventRecord {
+        let timestamp_ms = SystemTime::now().duration_since(UNIX_EPOCH).map(|duration| {
+            duration.as_millis() as u64
+        }).unwrap_or(0);
+        EventRecord {
+            timestamp_ms,
+            pid: None,
+            event,
+            syscall: None,
+            stage: None,
+            errno: None,
+            exit_code: None,
+            signal: None,
+            disabled: None,
+            timings_us: None,
+        }
+    }
+
+    /// Creates the record describing how a child exited.
+    pub fn for_exit(pid: pid_t, status: ExitStatus) -> EventRecord {
+        let mut record = match status {
+            ExitStatus::Code(code) => {
+                let kind = match status.activation_error() {
+                    Some(ActivationError::HookPanicked) => EventKind::HookPanicked,
+                    _ => EventKind::Exited,
+                };
+                let mut record = EventRecord::new(kind);
+                record.exit_code = Some(code);
+                record
+            }
+            ExitStatus::Signal(signal) => {
+                let kind = if status.is_violation() {
+                    EventKind::Violation
+                } else {
+                    EventKind::Exited
+                };
+                let mut record = EventRecord::new(kind);
+                record.signal = Some(signal);
+                record
+            }
+            ExitStatus::TimedOut => EventRecord::new(EventKind::TimedOut),
+        };
+        record.pid = Some(pid);
+        record.stage = Some("wait".to_owned());
+        record
+    }
+
+    /// Serializes this record as one line of JSON, trailing newline included.
+    ///
+    /// If the record would be longer than `MAX_RECORD_SIZE`, the free-form `syscall` and `stage`
+    /// fields and the timings are dropped. Returns `None` if it is still too long.
+    #[cfg(feature = "serde")]
+    pub fn to_line(&self) -> Option<Vec<u8>> {
+        let mut line = serde_json::to_vec(self).ok()?;
+        if line.len() >= MAX_RECORD_SIZE {
+            let truncated = EventRecord {
+                syscall: None,
+                stage: None,
+                timings_us: None,
+                ..self.clone()
+            };
+            line = serde_json::to_vec(&truncated).ok()?;
+        }
+        if line.len() >= MAX_RECORD_SIZE {
+            return None
+        }
+        line.push(b'\n');
+        Some(line)
+    }
+}
+
+/// A file descriptor that event records are written to.
+#[cfg(feature = "serde")]
+pub struct EventLog {
+    #[cfg(unix)]
+    fd: OwnedFd,
+    #[cfg(windows)]
+    file: File,
+}
+
+#[cfg(all(feature = "serde", unix))]
+impl EventLog {
+    /// Creates a log that writes to `fd`. The descriptor is marked close-on-exec so that
+    /// sandboxed children can't write forged records to it.
+    pub fn new(fd: OwnedFd) -> EventLog {
+        unsafe {
+            let flags = libc::fcntl(fd.as_raw_fd(), libc::F_GETFD);
+            if flags >= 0 {
+                libc::fcntl(fd.as_raw_fd(), libc::F_SETFD, flags | libc::FD_CLOEXEC);
+            }
+        }
+        EventLog {
+            fd,
+        }
+    }
+
+    /// Writes one record with a single `write` call.
+    pub fn write(&self, record: &EventRecord) -> io::Result<()> {
+        let line = match record.to_line() {
+            Some(line) => line,
+            None => return Err(io::Error::new(io::ErrorKind::InvalidData, "event record too long")),
+        };
+        let result = unsafe {
+            libc::write(self.fd.as_raw_fd(), line.as_ptr() as *const libc::c_void, line.len())
+        };
+        if result < 0 {
+            Err(io::Error::last_os_error())
+        } else if result as usize != line.len() {
+            Err(io::Error::new(io::ErrorKind::WriteZero, "short write of event record"))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+#[cfg(all(feature = "serde", windows))]
+impl EventLog {
+    /// Creates a log that writes to `handle`. Sandboxed children inherit no handles, so they
+    /// can't write forged records to it.
+    pub fn new(handle: OwnedHandle) -> EventLog {
+        EventLog {
+            file: File::from(handle),
+        }
+    }
+
+    /// Writes one record with a single `WriteFile` call.
+    pub fn write(&self, record: &EventRecord) -> io::Result<()> {
+        let line = match record.to_line() {
+            Some(line) => line,
+            None => return Err(io::Error::new(io::ErrorKind::InvalidData, "event record too long")),
+        };
+        if (&self.file).write(&line)? != line.len() {
+            Err(io::Error::new(io::ErrorKind::WriteZero, "short write of event record"))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Where a sandbox and the processes it starts report events. Does nothing unless an event log
+/// was configured.
+#[derive(Clone, Default)]
+pub(crate) struct EventSink {
+    #[cfg(feature = "serde")]
+    log: Option<Arc<EventLog>>,
+}
+
+impl EventSink {
+    #[cfg(feature = "serde")]
+    pub fn new(log: Option<Arc<EventLog>>) -> EventSink {
+        EventSink {
+            log,
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    pub fn record(&self, record: EventRecord) {
+        if let Some(ref log) = self.log {
+            if let Err(err) = log.write(&record) {
+                error!("failed to write sandbox event: {}", err)
+            }
+        }
+    }
+
+    #[cfg(not(feature = "serde"))]
+    pub fn record(&self, _: EventRecord) {}
+
+    /// Whether records are written anywhere, so that callers can skip building them.
+    pub fn is_enabled(&self) -> bool {
+        #[cfg(feature = "serde")]
+        {
+            self.log.is_some()
+        }
+        #[cfg(not(feature = "serde"))]
+        {
+            false
+        }
+    }
+}
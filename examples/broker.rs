@@ -0,0 +1,279 @@
+// Copyright 2015 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! The broker pattern: a sandboxed child with no filesystem access at all asks its unsandboxed
+//! parent to open files on its behalf. The parent checks each request against its own policy and
+//! passes back an open file descriptor over a Unix socket, so the child never needs a profile
+//! that allows `open`.
+//!
+//! The protocol over the socket is deliberately small:
+//!
+//!  * The child sends a request: a native-endian `u32` length followed by that many bytes of path.
+//!
+//!  * The parent replies with a native-endian `i32`: zero if the file was opened, in which case
+//!    the descriptor rides along as `SCM_RIGHTS` ancillary data, or an `errno` value otherwise.
+//!
+//! Run with `cargo run --example broker [PATH...]`.
+
+extern crate gaol;
+extern crate libc;
+
+use gaol::platform::process::ExitStatus;
+use gaol::profile::Profile;
+use gaol::sandbox::{ChildSandbox, ChildSandboxMethods, Command, Sandbox, SandboxMethods};
+use libc::{c_int, c_void};
+use std::env;
+use std::ffi::{CString, OsStr};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::mem;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::io::{AsRawFd, FromRawFd, RawFd};
+use std::os::unix::net::UnixStream;
+use std::path::Path;
+use std::process;
+use std::ptr;
+
+/// The environment variable through which the child learns its end of the broker socket.
+const BROKER_FD_VAR: &str = "GAOL_BROKER_FD";
+
+/// The files the parent is willing to open for the child.
+const ALLOWED_PATHS: [&str; 2] = ["/etc/hostname", "/etc/os-release"];
+
+/// The files the child asks for if none are given on the command line. The second is refused.
+const DEFAULT_REQUESTS: [&str; 2] = ["/etc/os-release", "/etc/passwd"];
+
+/// The longest path the parent will accept, to bound the size of a request.
+const MAX_PATH_LENGTH: u32 = 4096;
+
+fn main() {
+    match env::var(BROKER_FD_VAR) {
+        Ok(fd) => child(fd.parse().expect("invalid broker socket")),
+        Err(_) => parent(),
+    }
+}
+
+// Parent side
+
+fn parent() {
+    // On Linux the sandbox is built from unprivileged user namespaces, which some distributions
+    // turn off. Say so rather than failing obscurely.
+    if cfg!(any(target_os="android", target_os="linux")) &&
+            !gaol::platform::capabilities().user_namespaces {
+        eprintln!("This kernel doesn't allow unprivileged user namespaces, so gaol can't create a \
+                   sandbox here. (On Ubuntu, check `sysctl kernel.unprivileged_userns_clone` \
+                   and `kernel.apparmor_restrict_unprivileged_userns`.)");
+        process::exit(2)
+    }
+
+    let (broker, child_end) = UnixStream::pair().expect("couldn't create the broker socket");
+    // `UnixStream` sockets are close-on-exec; the child's end must survive `exec`.
+    set_inheritable(child_end.as_raw_fd());
+
+    let mut command = Command::me().expect("couldn't find the current executable");
+    command.env(BROKER_FD_VAR, child_end.as_raw_fd().to_string());
+    for path in env::args_os().skip(1) {
+        command.arg(path);
+    }
+
+    // The child gets nothing at all: no files, no network.
+    let profile = Profile::new(Vec::new()).unwrap();
+    let child = match Sandbox::new(profile).start(&mut command) {
+        Ok(child) => child,
+        Err(err) => {
+            eprintln!("couldn't start the sandboxed child: {}", err);
+            process::exit(2)
+        }
+    };
+    drop(child_end);
+
+    if let Err(err) = serve(broker) {
+        eprintln!("broker: lost the connection to the child: {}", err)
+    }
+
+    match child.wait() {
+        Ok(ExitStatus::Code(0)) => {}
+        Ok(ExitStatus::Code(code)) => {
+            eprintln!("the child exited with status {}", code);
+            process::exit(1)
+        }
+        Ok(ExitStatus::Signal(signal)) if signal == libc::SIGSYS => {
+            eprintln!("the sandbox killed the child for making a prohibited system call");
+            process::exit(1)
+        }
+        Ok(ExitStatus::Signal(signal)) => {
+            eprintln!("the child was killed by signal {}", signal);
+            process::exit(1)
+        }
+        Ok(ExitStatus::TimedOut) => {
+            eprintln!("the child ran out of time");
+            process::exit(1)
+        }
+        Err(err) => {
+            eprintln!("couldn't wait for the child: {}", err);
+            process::exit(1)
+        }
+    }
+}
+
+/// Answers requests until the child closes its end of the socket.
+fn serve(mut broker: UnixStream) -> io::Result<()> {
+    loop {
+        let mut length = [0; 4];
+        match broker.read_exact(&mut length) {
+            Ok(()) => {}
+            Err(ref err) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(()),
+            Err(err) => return Err(err),
+        }
+        let length = u32::from_ne_bytes(length);
+        if length > MAX_PATH_LENGTH {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "request too long"))
+        }
+        let mut path = vec![0; length as usize];
+        broker.read_exact(&mut path)?;
+
+        match open_for_child(&path) {
+            Ok(file) => {
+                println!("broker: granted {}", String::from_utf8_lossy(&path));
+                send_reply(&broker, 0, Some(file.as_raw_fd()))?
+            }
+            Err(errno) => {
+                println!("broker: refused {}", String::from_utf8_lossy(&path));
+                send_reply(&broker, errno, None)?
+            }
+        }
+    }
+}
+
+/// Applies the parent's policy to a request, returning an open file or an `errno`.
+fn open_for_child(path: &[u8]) -> Result<File, i32> {
+    if !ALLOWED_PATHS.iter().any(|allowed| allowed.as_bytes() == path) {
+        return Err(libc::EACCES)
+    }
+    let path = CString::new(path).map_err(|_| libc::EINVAL)?;
+    let fd = unsafe {
+        libc::open(path.as_ptr(), libc::O_RDONLY | libc::O_CLOEXEC)
+    };
+    if fd < 0 {
+        return Err(io::Error::last_os_error().raw_os_error().unwrap_or(libc::EIO))
+    }
+    Ok(unsafe { File::from_raw_fd(fd) })
+}
+
+fn set_inheritable(fd: RawFd) {
+    unsafe {
+        let flags = libc::fcntl(fd, libc::F_GETFD);
+        assert!(flags >= 0 && libc::fcntl(fd, libc::F_SETFD, flags & !libc::FD_CLOEXEC) == 0);
+    }
+}
+
+/// Sends a reply, with `fd` attached as `SCM_RIGHTS` ancillary data if present.
+fn send_reply(socket: &UnixStream, status: i32, fd: Option<RawFd>) -> io::Result<()> {
+    let mut payload = status.to_ne_bytes();
+    let mut iov = libc::iovec {
+        iov_base: payload.as_mut_ptr() as *mut c_void,
+        iov_len: payload.len(),
+    };
+    // `u64`s keep the buffer aligned for `cmsghdr`.
+    let mut control = [0u64; 4];
+    let mut message: libc::msghdr = unsafe { mem::zeroed() };
+    message.msg_iov = &mut iov;
+    message.msg_iovlen = 1;
+    if let Some(fd) = fd {
+        unsafe {
+            message.msg_control = control.as_mut_ptr() as *mut c_void;
+            message.msg_controllen = libc::CMSG_SPACE(mem::size_of::<c_int>() as u32) as _;
+            let header = libc::CMSG_FIRSTHDR(&message);
+            (*header).cmsg_level = libc::SOL_SOCKET;
+            (*header).cmsg_type = libc::SCM_RIGHTS;
+            (*header).cmsg_len = libc::CMSG_LEN(mem::size_of::<c_int>() as u32) as _;
+            ptr::write_unaligned(libc::CMSG_DATA(header) as *mut c_int, fd);
+        }
+    }
+    if unsafe { libc::sendmsg(socket.as_raw_fd(), &message, 0) } < 0 {
+        return Err(io::Error::last_os_error())
+    }
+    Ok(())
+}
+
+// Child side
+
+fn child(broker_fd: RawFd) {
+    let mut broker = unsafe { UnixStream::from_raw_fd(broker_fd) };
+
+    // Lock down before touching anything. From here on, the only way to reach the filesystem is
+    // through the broker.
+    if let Err(err) = ChildSandbox::new(Profile::new(Vec::new()).unwrap()).activate() {
+        eprintln!("child: couldn't activate the sandbox: {}", err);
+        process::exit(2)
+    }
+
+    let mut requests: Vec<Vec<u8>> =
+        env::args_os().skip(1).map(|path| path.as_bytes().to_vec()).collect();
+    if requests.is_empty() {
+        requests = DEFAULT_REQUESTS.iter().map(|path| path.as_bytes().to_vec()).collect();
+    }
+
+    for path in requests {
+        let display = Path::new(OsStr::from_bytes(&path)).display().to_string();
+        match request_file(&mut broker, &path) {
+            Ok(file) => {
+                let mut first_line = String::new();
+                match BufReader::new(file).read_line(&mut first_line) {
+                    Ok(_) => println!("child: {}: {}", display, first_line.trim_end()),
+                    Err(err) => println!("child: {}: read failed: {}", display, err),
+                }
+            }
+            Err(err) => println!("child: {}: {}", display, err),
+        }
+    }
+}
+
+/// Asks the broker to open `path`.
+fn request_file(broker: &mut UnixStream, path: &[u8]) -> io::Result<File> {
+    broker.write_all(&(path.len() as u32).to_ne_bytes())?;
+    broker.write_all(path)?;
+
+    let mut payload = [0; 4];
+    let mut iov = libc::iovec {
+        iov_base: payload.as_mut_ptr() as *mut c_void,
+        iov_len: payload.len(),
+    };
+    let mut control = [0u64; 4];
+    let mut message: libc::msghdr = unsafe { mem::zeroed() };
+    message.msg_iov = &mut iov;
+    message.msg_iovlen = 1;
+    message.msg_control = control.as_mut_ptr() as *mut c_void;
+    message.msg_controllen = mem::size_of_val(&control) as _;
+    let received = unsafe {
+        libc::recvmsg(broker.as_raw_fd(), &mut message, libc::MSG_WAITALL)
+    };
+    if received < 0 {
+        return Err(io::Error::last_os_error())
+    }
+    if received as usize != payload.len() {
+        return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "truncated reply"))
+    }
+
+    let status = i32::from_ne_bytes(payload);
+    if status != 0 {
+        return Err(io::Error::from_raw_os_error(status))
+    }
+    unsafe {
+        let header = libc::CMSG_FIRSTHDR(&message);
+        if header.is_null() ||
+                (*header).cmsg_level != libc::SOL_SOCKET ||
+                (*header).cmsg_type != libc::SCM_RIGHTS {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "reply carried no descriptor"))
+        }
+        let fd = ptr::read_unaligned(libc::CMSG_DATA(header) as *const c_int);
+        Ok(File::from_raw_fd(fd))
+    }
+}
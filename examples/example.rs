@@ -31,21 +31,19 @@ fn profile() -> Profile {
     // fail.)
     operations.retain(|operation| {
         println!("{:?}: {:?}", operation, operation.support());
-        match operation.support() {
-            OperationSupportLevel::NeverAllowed | OperationSupportLevel::CanBeAllowed => true,
-            _ => false,
-        }
+        matches!(operation.support(),
+                 OperationSupportLevel::NeverAllowed | OperationSupportLevel::CanBeAllowed)
     });
 
     Profile::new(operations).unwrap()
 }
 
 fn main() {
-    match env::args().skip(1).next() {
+    match env::args().nth(1) {
         Some(ref arg) if arg == "child" => {
             // This is the child process.
-            ChildSandbox::new(profile()).activate().unwrap();
-            match File::open(&PathBuf::from("/bin/sh")) {
+            ChildSandbox::from_environment().unwrap().activate().unwrap();
+            match File::open(PathBuf::from("/bin/sh")) {
                 Err(error) => println!("{:?}", error),
                 Ok(_) => panic!("could access /bin/sh"),
             }
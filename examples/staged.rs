@@ -0,0 +1,207 @@
+// Copyright 2015 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Staged lockdown: a child does its trusted setup first, then locks itself down before it
+//! touches untrusted input.
+//!
+//! Stage one loads "plugins" (rewrite rules read from `*.rules` files in a directory) while the
+//! child can still read the filesystem. Stage two activates a sandbox with an empty profile, after
+//! which the child can only compute and use the descriptors it already has, and only then does it
+//! read lines from standard input, rewrite them with the plugins, and print them.
+//!
+//! Run with `echo "fyi, my favourite colour" | cargo run --example staged [PLUGIN_DIR]`.
+
+extern crate gaol;
+extern crate libc;
+
+use gaol::platform::process::ExitStatus;
+use gaol::profile::Profile;
+use gaol::sandbox::{ChildSandbox, ChildSandboxMethods, Command, Sandbox, SandboxMethods};
+use std::env;
+use std::ffi::OsStr;
+use std::fmt;
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+
+/// The environment variable through which the child learns where its plugins are.
+const PLUGIN_DIR_VAR: &str = "GAOL_STAGED_PLUGIN_DIR";
+
+/// A plugin: an ordered list of literal find-and-replace rules.
+struct Plugin {
+    name: String,
+    rules: Vec<(String, String)>,
+}
+
+/// Why a plugin couldn't be loaded.
+enum PluginError {
+    Io(PathBuf, io::Error),
+    Syntax { path: PathBuf, line: usize },
+}
+
+impl fmt::Display for PluginError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            PluginError::Io(ref path, ref err) => write!(f, "{}: {}", path.display(), err),
+            PluginError::Syntax { ref path, line } => {
+                write!(f, "{}:{}: expected `find<TAB>replace`", path.display(), line)
+            }
+        }
+    }
+}
+
+impl Plugin {
+    fn load(path: &Path) -> Result<Plugin, PluginError> {
+        let contents = fs::read_to_string(path).map_err(|err| {
+            PluginError::Io(path.to_path_buf(), err)
+        })?;
+        let mut rules = Vec::new();
+        for (index, line) in contents.lines().enumerate() {
+            if line.is_empty() || line.starts_with('#') {
+                continue
+            }
+            match line.split_once('\t') {
+                Some((find, replace)) if !find.is_empty() => {
+                    rules.push((find.to_owned(), replace.to_owned()))
+                }
+                _ => {
+                    return Err(PluginError::Syntax {
+                        path: path.to_path_buf(),
+                        line: index + 1,
+                    })
+                }
+            }
+        }
+        let name = path.file_stem().unwrap_or_default().to_string_lossy().into_owned();
+        Ok(Plugin {
+            name,
+            rules,
+        })
+    }
+
+    fn apply(&self, text: &str) -> String {
+        self.rules.iter().fold(text.to_owned(), |text, (find, replace)| {
+            text.replace(find.as_str(), replace)
+        })
+    }
+}
+
+/// Loads every `*.rules` file in `directory`, in name order so that output is deterministic.
+fn load_plugins(directory: &Path) -> Result<Vec<Plugin>, PluginError> {
+    let entries = fs::read_dir(directory).map_err(|err| {
+        PluginError::Io(directory.to_path_buf(), err)
+    })?;
+    let mut paths = Vec::new();
+    for entry in entries {
+        let path = entry.map_err(|err| PluginError::Io(directory.to_path_buf(), err))?.path();
+        if path.extension() == Some(OsStr::new("rules")) {
+            paths.push(path)
+        }
+    }
+    paths.sort();
+    paths.iter().map(|path| Plugin::load(path)).collect()
+}
+
+fn main() {
+    match env::var_os(PLUGIN_DIR_VAR) {
+        Some(directory) => child(Path::new(&directory)),
+        None => parent(),
+    }
+}
+
+fn parent() {
+    let directory = match env::args_os().nth(1) {
+        Some(directory) => PathBuf::from(directory),
+        None => Path::new(env!("CARGO_MANIFEST_DIR")).join("examples").join("plugins"),
+    };
+    let directory = match directory.canonicalize() {
+        Ok(directory) => directory,
+        Err(err) => {
+            eprintln!("{}: {}", directory.display(), err);
+            process::exit(2)
+        }
+    };
+
+    if cfg!(any(target_os="android", target_os="linux")) &&
+            !gaol::platform::capabilities().user_namespaces {
+        eprintln!("This kernel doesn't allow unprivileged user namespaces, so gaol can't create a \
+                   sandbox here.");
+        process::exit(2)
+    }
+
+    let mut command = Command::me().expect("couldn't find the current executable");
+    command.env(PLUGIN_DIR_VAR, &directory);
+
+    // The parent's sandbox only sets the child up; the child activates its own, later.
+    let profile = Profile::new(Vec::new()).unwrap();
+    let child = match Sandbox::new(profile).start(&mut command) {
+        Ok(child) => child,
+        Err(err) => {
+            eprintln!("couldn't start the sandboxed child: {}", err);
+            process::exit(2)
+        }
+    };
+    match child.wait() {
+        Ok(ExitStatus::Code(code)) => process::exit(code),
+        Ok(ExitStatus::Signal(signal)) if signal == libc::SIGSYS => {
+            eprintln!("the sandbox killed the child for making a prohibited system call");
+            process::exit(1)
+        }
+        Ok(ExitStatus::Signal(signal)) => {
+            eprintln!("the child was killed by signal {}", signal);
+            process::exit(1)
+        }
+        Ok(ExitStatus::TimedOut) => {
+            eprintln!("the child ran out of time");
+            process::exit(1)
+        }
+        Err(err) => {
+            eprintln!("couldn't wait for the child: {}", err);
+            process::exit(1)
+        }
+    }
+}
+
+fn child(directory: &Path) {
+    // Stage one: trusted setup, with the filesystem still reachable.
+    let plugins = match load_plugins(directory) {
+        Ok(plugins) => plugins,
+        Err(err) => {
+            eprintln!("child: couldn't load plugins: {}", err);
+            process::exit(2)
+        }
+    };
+    let stdin = io::stdin();
+    let stdout = io::stdout();
+
+    // Stage two: lock down. Nothing below can open files or connect anywhere.
+    if let Err(err) = ChildSandbox::new(Profile::new(Vec::new()).unwrap()).activate() {
+        eprintln!("child: couldn't activate the sandbox: {}", err);
+        process::exit(2)
+    }
+    eprintln!("child: loaded {} and locked down",
+              plugins.iter().map(|plugin| plugin.name.as_str()).collect::<Vec<_>>().join(", "));
+
+    let mut output = stdout.lock();
+    for line in stdin.lock().lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(err) => {
+                eprintln!("child: couldn't read input: {}", err);
+                process::exit(1)
+            }
+        };
+        let rewritten = plugins.iter().fold(line, |text, plugin| plugin.apply(&text));
+        if writeln!(output, "{}", rewritten).is_err() {
+            process::exit(1)
+        }
+    }
+}
@@ -8,31 +8,67 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
-#[macro_use]
-extern crate log;
-
-extern crate libc;
-
+#[cfg(unix)]
+pub mod channel;
+pub mod error;
+pub mod event_log;
 pub mod profile;
 pub mod sandbox;
+pub mod telemetry;
+#[doc(hidden)]
+pub mod test_harness;
 
 pub mod platform {
+    pub use self::capabilities::{capabilities, PlatformCapabilities};
+    #[cfg(any(target_os="android", target_os="linux"))]
+    pub use self::linux::{ChildSandbox, Operation, Sandbox};
+    #[cfg(any(target_os="android", target_os="linux"))]
+    pub(crate) use self::linux::{mechanisms, unbindable_filesystem, unsupported_core_pattern};
     #[cfg(any(target_os="android", target_os="linux"))]
-    pub use platform::linux::{ChildSandbox, Operation, Sandbox};
+    pub(crate) use self::linux::OPERATION_ENCODING_ID;
     #[cfg(target_os="macos")]
-    pub use platform::macos::{ChildSandbox, Operation, Sandbox};
+    pub use self::macos::{ChildSandbox, Operation, Sandbox};
+    #[cfg(target_os="macos")]
+    pub(crate) use self::macos::{mechanisms, unbindable_filesystem, unsupported_core_pattern};
+    #[cfg(target_os="macos")]
+    pub(crate) use self::macos::OPERATION_ENCODING_ID;
+    #[cfg(target_os="freebsd")]
+    pub use self::freebsd::{ChildSandbox, Operation, Sandbox};
+    #[cfg(target_os="freebsd")]
+    pub(crate) use self::freebsd::{mechanisms, unbindable_filesystem, unsupported_core_pattern};
     #[cfg(target_os="freebsd")]
-    pub use platform::freebsd::{ChildSandbox, Operation, Sandbox};
-    #[cfg(any(target_os="android", target_os="linux", target_os="macos", target_os="freebsd"))]
-    pub use platform::unix::process::{self, Process};
+    pub(crate) use self::freebsd::OPERATION_ENCODING_ID;
+    #[cfg(target_os="openbsd")]
+    pub use self::openbsd::{ChildSandbox, Operation, Sandbox};
+    #[cfg(target_os="openbsd")]
+    pub(crate) use self::openbsd::{mechanisms, unbindable_filesystem, unsupported_core_pattern};
+    #[cfg(target_os="openbsd")]
+    pub(crate) use self::openbsd::OPERATION_ENCODING_ID;
+    #[cfg(target_os="windows")]
+    pub use self::windows::{ChildSandbox, Operation, Sandbox};
+    #[cfg(target_os="windows")]
+    pub(crate) use self::windows::{mechanisms, unbindable_filesystem, unsupported_core_pattern};
+    #[cfg(target_os="windows")]
+    pub(crate) use self::windows::OPERATION_ENCODING_ID;
+    #[cfg(any(target_os="android", target_os="linux", target_os="macos", target_os="freebsd",
+              target_os="openbsd"))]
+    pub use self::unix::process::{self, Process};
+    #[cfg(target_os="windows")]
+    pub use self::windows::process::{self, Process};
 
+    pub mod capabilities;
     #[cfg(any(target_os="android", target_os="linux"))]
     pub mod linux;
     #[cfg(target_os="macos")]
     pub mod macos;
     #[cfg(target_os="freebsd")]
     pub mod freebsd;
-    #[cfg(any(target_os="android", target_os="linux", target_os="macos", target_os="freebsd"))]
+    #[cfg(target_os="openbsd")]
+    pub mod openbsd;
+    #[cfg(target_os="windows")]
+    pub mod windows;
+    #[cfg(any(target_os="android", target_os="linux", target_os="macos", target_os="freebsd",
+              target_os="openbsd"))]
     pub mod unix;
 }
 
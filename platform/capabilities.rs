@@ -0,0 +1,353 @@
+// Copyright 2015 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Runtime detection of the sandboxing features this machine offers.
+//!
+//! Whether a sandbox will be effective depends on more than the operating system: kernels can be
+//! built without seccomp, distributions can disable unprivileged user namespaces, and newer
+//! mechanisms like Landlock only exist on recent kernels. `capabilities()` probes for all of this
+//! once and reports the results, so embedders can decide at startup whether to proceed.
+//!
+//! The probes never change the state of the calling process: anything that could (such as
+//! entering a user namespace) is attempted in a short-lived forked child instead.
+
+use std::io;
+use std::sync::OnceLock;
+
+/// The sandboxing features available on this machine, as detected at runtime.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PlatformCapabilities {
+    /// Whether an unprivileged process can create a user namespace (Linux).
+    pub user_namespaces: bool,
+    /// Whether a filesystem can be mounted inside a fresh user and mount namespace (Linux).
+    pub mount_in_user_namespace: bool,
+    /// Whether seccomp filter mode is available (Linux).
+    pub seccomp_filter: bool,
+    /// Whether a seccomp filter can be synchronized across all threads with
+    /// `SECCOMP_FILTER_FLAG_TSYNC` (Linux 3.17+).
+    pub seccomp_tsync: bool,
+    /// Whether a user namespace can have a time namespace of its own, whose clocks can be offset
+    /// (Linux 5.6+).
+    pub time_namespaces: bool,
+    /// Whether a user namespace can have a cgroup namespace of its own, which shows the process
+    /// its own cgroup as the root of the hierarchy (Linux 4.6+).
+    pub cgroup_namespaces: bool,
+    /// Whether the `SECCOMP_RET_LOG` action is available (Linux 4.14+).
+    pub seccomp_ret_log: bool,
+    /// Whether the `SECCOMP_RET_USER_NOTIF` action is available (Linux 5.0+).
+    pub seccomp_user_notif: bool,
+    /// The Landlock ABI version, if Landlock is present and enabled (Linux 5.13+).
+    pub landlock_abi: Option<u32>,
+    /// The product version of the operating system, for example `14.2.1` (Mac OS X).
+    pub macos_version: Option<String>,
+}
+
+/// The raw results of the probes from which `PlatformCapabilities` is derived.
+///
+/// This is separate from `PlatformCapabilities` so that the derivation can be tested with
+/// injected results. Errors are represented as `errno` values. The default is the result of
+/// running no probes at all.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ProbeResults {
+    /// The contents of `/proc/sys/kernel/unprivileged_userns_clone`, if that file exists.
+    pub unprivileged_userns_clone: Option<String>,
+    /// The contents of `/proc/sys/user/max_user_namespaces`, if that file exists.
+    pub max_user_namespaces: Option<String>,
+    /// The result of entering a user namespace in a forked child, or `None` if the child
+    /// couldn't be run.
+    pub user_namespace: Option<Result<(), i32>>,
+    /// The result of mounting a `tmpfs` inside that user namespace, or `None` if it wasn't
+    /// attempted.
+    pub mount_in_user_namespace: Option<Result<(), i32>>,
+    /// Whether `/proc/self/ns/time` exists.
+    pub time_namespace: bool,
+    /// Whether `/proc/self/ns/cgroup` exists.
+    pub cgroup_namespace: bool,
+    /// The result of `prctl(PR_GET_SECCOMP)`.
+    pub seccomp_mode: Result<i32, i32>,
+    /// The result of calling `seccomp(SECCOMP_SET_MODE_FILTER, SECCOMP_FILTER_FLAG_TSYNC)` with
+    /// a null program. The kernel rejects unknown flags with `EINVAL` before it looks at the
+    /// program, which it then fails to read with `EFAULT`.
+    pub seccomp_tsync: Result<(), i32>,
+    /// The result of `seccomp(SECCOMP_GET_ACTION_AVAIL)` for `SECCOMP_RET_LOG`.
+    pub seccomp_ret_log: Result<(), i32>,
+    /// The result of `seccomp(SECCOMP_GET_ACTION_AVAIL)` for `SECCOMP_RET_USER_NOTIF`.
+    pub seccomp_user_notif: Result<(), i32>,
+    /// The result of `landlock_create_ruleset(NULL, 0, LANDLOCK_CREATE_RULESET_VERSION)`.
+    pub landlock_abi: Result<i32, i32>,
+    /// The value of the `kern.osproductversion` sysctl.
+    pub macos_version: Option<String>,
+}
+
+impl Default for ProbeResults {
+    fn default() -> ProbeResults {
+        ProbeResults {
+            unprivileged_userns_clone: None,
+            max_user_namespaces: None,
+            user_namespace: None,
+            mount_in_user_namespace: None,
+            time_namespace: false,
+            cgroup_namespace: false,
+            seccomp_mode: Err(libc::ENOSYS),
+            seccomp_tsync: Err(libc::ENOSYS),
+            seccomp_ret_log: Err(libc::ENOSYS),
+            seccomp_user_notif: Err(libc::ENOSYS),
+            landlock_abi: Err(libc::ENOSYS),
+            macos_version: None,
+        }
+    }
+}
+
+impl ProbeResults {
+    /// Returns why an unprivileged process can't create a user namespace, or `None` if it can:
+    /// the sysctl that forbids them, or else the `errno` the probe child got trying (Linux).
+    pub fn user_namespace_error(&self) -> Option<io::Error> {
+        let is_zero = |value: &Option<String>| {
+            value.as_ref().is_some_and(|value| value.trim() == "0")
+        };
+        if is_zero(&self.unprivileged_userns_clone) {
+            return Some(io::Error::new(io::ErrorKind::PermissionDenied,
+                                       "the kernel.unprivileged_userns_clone sysctl is 0"))
+        }
+        if is_zero(&self.max_user_namespaces) {
+            return Some(io::Error::new(io::ErrorKind::PermissionDenied,
+                                       "the user.max_user_namespaces sysctl is 0"))
+        }
+        // Otherwise something else, such as a security module, refuses them.
+        match self.user_namespace {
+            Some(Ok(())) => None,
+            Some(Err(errno)) => Some(io::Error::from_raw_os_error(errno)),
+            None => Some(io::Error::other("the user namespace probe couldn't be run")),
+        }
+    }
+}
+
+impl PlatformCapabilities {
+    /// Derives the capabilities from raw probe results.
+    pub fn from_probes(probes: &ProbeResults) -> PlatformCapabilities {
+        let user_namespaces = probes.user_namespace_error().is_none();
+        let seccomp_filter = probes.seccomp_mode.is_ok();
+        PlatformCapabilities {
+            user_namespaces,
+            mount_in_user_namespace: user_namespaces &&
+                probes.mount_in_user_namespace == Some(Ok(())),
+            seccomp_filter,
+            time_namespaces: user_namespaces && probes.time_namespace,
+            cgroup_namespaces: user_namespaces && probes.cgroup_namespace,
+            seccomp_tsync: seccomp_filter && probes.seccomp_tsync == Err(libc::EFAULT),
+            seccomp_ret_log: seccomp_filter && probes.seccomp_ret_log.is_ok(),
+            seccomp_user_notif: seccomp_filter && probes.seccomp_user_notif.is_ok(),
+            landlock_abi: match probes.landlock_abi {
+                Ok(version) if version > 0 => Some(version as u32),
+                _ => None,
+            },
+            macos_version: probes.macos_version.clone(),
+        }
+    }
+}
+
+/// Returns the sandboxing features available on this machine.
+///
+/// The probes run the first time this is called; later calls return the same results.
+pub fn capabilities() -> &'static PlatformCapabilities {
+    static CAPABILITIES: OnceLock<PlatformCapabilities> = OnceLock::new();
+    CAPABILITIES.get_or_init(|| PlatformCapabilities::from_probes(probe_results()))
+}
+
+/// The probe results `capabilities()` is derived from, once they are known.
+static PROBE_RESULTS: OnceLock<ProbeResults> = OnceLock::new();
+
+/// Returns the results of the probes behind `capabilities()`, running them the first time.
+pub(crate) fn probe_results() -> &'static ProbeResults {
+    PROBE_RESULTS.get_or_init(probe)
+}
+
+/// Makes `capabilities()` report what `probes` says instead of running the probes. Returns false
+/// if the probes have already run.
+pub(crate) fn set_probe_results(probes: ProbeResults) -> bool {
+    PROBE_RESULTS.set(probes).is_ok()
+}
+
+/// Runs every probe for this platform.
+pub fn probe() -> ProbeResults {
+    let mut probes = ProbeResults::default();
+    probe_platform(&mut probes);
+    probes
+}
+
+#[cfg(any(target_os="android", target_os="linux"))]
+fn probe_platform(probes: &mut ProbeResults) {
+    use std::fs;
+    use std::path::Path;
+
+    probes.unprivileged_userns_clone =
+        fs::read_to_string("/proc/sys/kernel/unprivileged_userns_clone").ok();
+    probes.max_user_namespaces = fs::read_to_string("/proc/sys/user/max_user_namespaces").ok();
+    linux::probe_user_namespace(probes);
+    probes.time_namespace = Path::new("/proc/self/ns/time").exists();
+    probes.cgroup_namespace = Path::new("/proc/self/ns/cgroup").exists();
+    probes.seccomp_mode = linux::probe_seccomp_mode();
+    probes.seccomp_tsync = linux::probe_seccomp_tsync();
+    probes.seccomp_ret_log = linux::probe_seccomp_action(libc::SECCOMP_RET_LOG);
+    probes.seccomp_user_notif = linux::probe_seccomp_action(libc::SECCOMP_RET_USER_NOTIF);
+    probes.landlock_abi = linux::probe_landlock_abi();
+}
+
+#[cfg(target_os="macos")]
+fn probe_platform(probes: &mut ProbeResults) {
+    use std::ffi::CString;
+    use std::ptr;
+
+    let name = CString::new("kern.osproductversion").unwrap();
+    let mut value = [0u8; 32];
+    let mut length = value.len();
+    let result = unsafe {
+        libc::sysctlbyname(name.as_ptr(),
+                           value.as_mut_ptr() as *mut libc::c_void,
+                           &mut length,
+                           ptr::null_mut(),
+                           0)
+    };
+    if result == 0 {
+        let end = value[..length].iter().position(|&byte| byte == 0).unwrap_or(length);
+        probes.macos_version = Some(String::from_utf8_lossy(&value[..end]).into_owned());
+    }
+}
+
+#[cfg(not(any(target_os="android", target_os="linux", target_os="macos")))]
+fn probe_platform(_: &mut ProbeResults) {}
+
+#[cfg(any(target_os="android", target_os="linux"))]
+pub(crate) mod linux {
+    use super::ProbeResults;
+
+    use libc::{self, c_int, c_long, c_uint, c_void};
+    use std::ffi::CString;
+    use std::io;
+    use std::ptr;
+
+    /// `landlock_create_ruleset` flag requesting the highest supported ABI version.
+    const LANDLOCK_CREATE_RULESET_VERSION: c_uint = 1;
+
+    /// The exit code of the probe child when `unshare` succeeded but `mount` failed.
+    const PROBE_MOUNT_FAILED: c_int = 2;
+    /// The probe child exits with this plus the `errno` when `unshare` failed.
+    const PROBE_UNSHARE_FAILED: c_int = 64;
+
+    fn errno() -> i32 {
+        io::Error::last_os_error().raw_os_error().unwrap_or(0)
+    }
+
+    /// Enters a user and mount namespace in a forked child and tries to mount a `tmpfs` there.
+    pub fn probe_user_namespace(probes: &mut ProbeResults) {
+        // Allocate everything up front: only async-signal-safe calls are allowed after `fork()`.
+        let tmpfs = CString::new("tmpfs").unwrap();
+        let target = CString::new("/").unwrap();
+
+        unsafe {
+            let pid = libc::fork();
+            if pid < 0 {
+                return
+            }
+            if pid == 0 {
+                if libc::unshare(libc::CLONE_NEWUSER | libc::CLONE_NEWNS) != 0 {
+                    libc::_exit(PROBE_UNSHARE_FAILED + errno())
+                }
+                // Mount over `/` in the private mount namespace; the mount disappears with it.
+                if libc::mount(tmpfs.as_ptr(),
+                               target.as_ptr(),
+                               tmpfs.as_ptr(),
+                               0,
+                               ptr::null()) != 0 {
+                    libc::_exit(PROBE_MOUNT_FAILED)
+                }
+                libc::_exit(0)
+            }
+
+            let mut status = 0;
+            while libc::waitpid(pid, &mut status, 0) < 0 {
+                if errno() != libc::EINTR {
+                    return
+                }
+            }
+            if !libc::WIFEXITED(status) {
+                return
+            }
+            match libc::WEXITSTATUS(status) {
+                0 => {
+                    probes.user_namespace = Some(Ok(()));
+                    probes.mount_in_user_namespace = Some(Ok(()));
+                }
+                PROBE_MOUNT_FAILED => {
+                    probes.user_namespace = Some(Ok(()));
+                    probes.mount_in_user_namespace = Some(Err(libc::EPERM));
+                }
+                status if status > PROBE_UNSHARE_FAILED => {
+                    probes.user_namespace = Some(Err(status - PROBE_UNSHARE_FAILED))
+                }
+                _ => probes.user_namespace = Some(Err(libc::EPERM)),
+            }
+        }
+    }
+
+    pub fn probe_seccomp_mode() -> Result<i32, i32> {
+        let result = unsafe {
+            libc::prctl(libc::PR_GET_SECCOMP, 0, 0, 0, 0)
+        };
+        if result < 0 {
+            Err(errno())
+        } else {
+            Ok(result)
+        }
+    }
+
+    pub fn probe_seccomp_tsync() -> Result<(), i32> {
+        let result = unsafe {
+            libc::syscall(libc::SYS_seccomp,
+                          libc::SECCOMP_SET_MODE_FILTER,
+                          libc::SECCOMP_FILTER_FLAG_TSYNC,
+                          ptr::null::<c_void>())
+        };
+        if result < 0 {
+            Err(errno())
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn probe_seccomp_action(action: c_uint) -> Result<(), i32> {
+        let result = unsafe {
+            libc::syscall(libc::SYS_seccomp,
+                          libc::SECCOMP_GET_ACTION_AVAIL,
+                          0,
+                          &action as *const c_uint)
+        };
+        if result < 0 {
+            Err(errno())
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn probe_landlock_abi() -> Result<i32, i32> {
+        let result: c_long = unsafe {
+            libc::syscall(libc::SYS_landlock_create_ruleset,
+                          ptr::null::<c_void>(),
+                          0,
+                          LANDLOCK_CREATE_RULESET_VERSION)
+        };
+        if result < 0 {
+            Err(errno())
+        } else {
+            Ok(result as i32)
+        }
+    }
+}
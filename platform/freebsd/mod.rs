@@ -9,17 +9,42 @@
 // except according to those terms.
 
 //! Sandboxing on FreeBSD via Capsicum.
+//!
+//! In capability mode a process can open nothing by path and connect to nothing by address, so a
+//! child reaches the files and sockets its profile allows only through descriptors it opened
+//! before activating, with `ChildSandbox::open_path()`, `ChildSandbox::limit_socket()`, or
+//! `CapsicumPre`. Each is limited to the rights its operation needs, so that a descriptor for
+//! reading can't be written to even though the child still holds it.
 
-use platform::unix::process::Process;
-use profile::{self, OperationSupport, OperationSupportLevel, Profile};
-use sandbox::{ChildSandboxMethods, Command, SandboxMethods};
+use crate::error::SandboxError;
+use crate::platform::unix::process::Process;
+use crate::platform::unix::verify;
+use crate::profile::{self, AddressPattern, DecodeError, Decoder, Describe, OperationSupport};
+use crate::profile::{OperationSupportLevel, PathPattern, Profile};
+use crate::sandbox::{self, ActivationError, ChildSandboxMethods, ChildSandboxOptions, Command};
+use crate::sandbox::{PreflightIssue, ProbeStatus, ProfilePayload, SandboxMethods};
+use crate::sandbox::{ResourceLimits, SandboxLimits, SandboxOptions, VerificationReport};
+use crate::telemetry::Mechanism;
 
-use libc::c_int;
+use libc::{self, c_int, c_uint};
+use log::error;
+use std::ffi::CString;
+use std::fs::File;
 use std::io;
+use std::mem;
+use std::os::unix::prelude::{AsRawFd, FromRawFd, OsStrExt, RawFd};
+use std::path::Path;
 
 impl OperationSupport for profile::Operation {
     fn support(&self) -> OperationSupportLevel {
         match *self {
+            // Through descriptors opened before activation; see `ChildSandbox::open_path()` and
+            // `ChildSandbox::limit_socket()`.
+            profile::Operation::FileReadAll(PathPattern::Literal(_)) |
+            profile::Operation::FileReadAll(PathPattern::Subpath(_)) |
+            profile::Operation::NetworkOutbound(AddressPattern::All) => {
+                OperationSupportLevel::CanBeAllowed
+            }
             profile::Operation::SystemInfoRead =>
                 OperationSupportLevel::AlwaysAllowed,
             _ => OperationSupportLevel::NeverAllowed
@@ -27,19 +52,144 @@ impl OperationSupport for profile::Operation {
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Operation { }
 
+/// Identifies FreeBSD-specific operations in encoded profiles.
+pub(crate) const OPERATION_ENCODING_ID: u8 = 3;
+
+impl Operation {
+    pub(crate) fn encode(&self, _: &mut Vec<u8>) {
+        match *self {}
+    }
+
+    /// There are no FreeBSD-specific operations to decode.
+    pub(crate) fn decode(_: &mut Decoder) -> Result<Operation,DecodeError> {
+        Err(DecodeError::Malformed)
+    }
+}
+
+impl Describe for Operation {
+    fn describe(&self) -> String {
+        match *self {}
+    }
+}
+
+/// Returns the mechanisms that confine a child started with a profile, and those of them that
+/// this machine lacks. None of the mechanisms that can be disabled are used here.
+pub(crate) fn mechanisms(_: &Profile, _: &[Mechanism]) -> (Vec<Mechanism>, Vec<Mechanism>) {
+    (vec![Mechanism::Capsicum], Vec::new())
+}
+
+/// Returns the filesystem `path` is on if it can't be made available to a child. Nothing is
+/// bind-mounted here, so every filesystem can.
+pub(crate) fn unbindable_filesystem(_: &Path) -> Option<&'static str> {
+    None
+}
+
+/// Returns the kernel's core dump pattern if it would keep children from dumping core into a
+/// `CoreDumps::EnabledTo` directory. Core dumps aren't redirected here, so no pattern does.
+pub(crate) fn unsupported_core_pattern() -> Option<String> {
+    None
+}
+
+/// Returns the rights, as `libc::CAP_*` constants, that a descriptor needs for `operation`, or
+/// `None` if no descriptor can allow it.
+///
+/// Reading beneath a directory needs `CAP_LOOKUP` on it as well, so that files can be opened
+/// relative to it with `openat()`; they get the directory's rights.
+pub fn rights_for(operation: &profile::Operation) -> Option<Vec<u64>> {
+    match *operation {
+        profile::Operation::FileReadAll(PathPattern::Literal(_)) => {
+            Some(vec![libc::CAP_READ, libc::CAP_SEEK])
+        }
+        profile::Operation::FileReadAll(PathPattern::Subpath(_)) => {
+            Some(vec![libc::CAP_READ, libc::CAP_SEEK, libc::CAP_LOOKUP])
+        }
+        profile::Operation::NetworkOutbound(AddressPattern::All) => {
+            Some(vec![libc::CAP_SEND, libc::CAP_CONNECT])
+        }
+        _ => None,
+    }
+}
+
+/// Opens descriptors for use in capability mode, where nothing can be opened by path.
+///
+/// These must be called before `ChildSandbox::activate()`; afterwards opening fails with
+/// `ECAPMODE`. Unlike the methods on `ChildSandbox`, they don't consult the profile.
+pub struct CapsicumPre;
+
+impl CapsicumPre {
+    /// Opens `path` and limits the descriptor to `rights`, a list of `libc::CAP_*` constants.
+    /// The file is opened for writing too if `rights` include `CAP_WRITE`.
+    pub fn open_path(path: &Path, rights: &[u64]) -> io::Result<File> {
+        let c_path = CString::new(path.as_os_str().as_bytes())?;
+        let access = if rights.contains(&libc::CAP_WRITE) {
+            libc::O_RDWR
+        } else {
+            libc::O_RDONLY
+        };
+        let fd = unsafe {
+            libc::open(c_path.as_ptr(), access | libc::O_CLOEXEC)
+        };
+        if fd < 0 {
+            return Err(io::Error::last_os_error())
+        }
+        let file = unsafe {
+            File::from_raw_fd(fd)
+        };
+        CapsicumPre::limit(file.as_raw_fd(), rights)?;
+        Ok(file)
+    }
+
+    /// Limits the open descriptor `fd`, such as a connected socket, to `rights`. Rights can only
+    /// ever be taken away from a descriptor, never given back.
+    pub fn limit(fd: RawFd, rights: &[u64]) -> io::Result<()> {
+        unsafe {
+            let mut set: libc::cap_rights_t = mem::zeroed();
+            libc::__cap_rights_init(libc::CAP_RIGHTS_VERSION, &mut set, 0u64);
+            for &right in rights {
+                libc::__cap_rights_set(&mut set, right, 0u64);
+            }
+            if libc::cap_rights_limit(fd, &set) != 0 {
+                return Err(io::Error::last_os_error())
+            }
+        }
+        Ok(())
+    }
+}
+
 pub struct Sandbox {
     profile: Profile,
+    options: SandboxOptions,
 }
 
 impl Sandbox {
     pub fn new(profile: Profile) -> Sandbox {
+        Sandbox::with_options(profile, SandboxOptions::new())
+    }
+
+    pub fn with_options(profile: Profile, options: SandboxOptions) -> Sandbox {
         Sandbox {
-            profile: profile,
+            profile,
+            options,
         }
     }
+
+    /// Resource limits need cgroup v2, so children of a sandbox given them fail to start here,
+    /// with `Unsupported`.
+    pub fn with_limits(mut self, limits: SandboxLimits) -> Sandbox {
+        self.options.limits(limits);
+        self
+    }
+
+    /// Per-process resource limits are only set on Linux, so children of a sandbox given them
+    /// fail to start here, with `Unsupported`.
+    pub fn with_resource_limits(mut self, limits: ResourceLimits) -> Sandbox {
+        self.options.process_limits(limits);
+        self
+    }
 }
 
 impl SandboxMethods for Sandbox {
@@ -48,31 +198,123 @@ impl SandboxMethods for Sandbox {
     }
 
     fn start(&self, command: &mut Command) -> io::Result<Process> {
-        command.env("GAOL_CHILD_PROCESS", "1").spawn()
+        if self.options.resource_limits().is_some() {
+            let err = io::Error::new(io::ErrorKind::Unsupported, "resource limits need cgroup v2");
+            return self.options.record_start(&self.profile, Err(err))
+        }
+        if self.options.per_process_limits().is_some() ||
+                self.profile.resource_limits().is_some() {
+            let err = io::Error::new(io::ErrorKind::Unsupported,
+                                     "per-process resource limits are only set on Linux");
+            return self.options.record_start(&self.profile, Err(err))
+        }
+        let result = ProfilePayload::new(&self.profile).and_then(|payload| {
+            payload.export(command);
+            command.env("GAOL_CHILD_PROCESS", "1").spawn()
+        });
+        self.options.record_start(&self.profile, result)
+    }
+
+    fn preflight(&self) -> Result<(), Vec<PreflightIssue>> {
+        self.options.preflight(&self.profile)
     }
 }
 
 pub struct ChildSandbox {
+    profile: Profile,
 }
 
 impl ChildSandbox {
-    pub fn new(_profile: Profile) -> ChildSandbox {
+    pub fn new(profile: Profile) -> ChildSandbox {
+        ChildSandbox::with_options(profile, ChildSandboxOptions::new())
+    }
+
+    /// Creates a sandbox for the profile that `Sandbox::start()` passed to this process, so that
+    /// the child needn't rebuild it. Fails with `InvalidData` if the profile was damaged in
+    /// transit, wrapping a `DecodeError`.
+    pub fn from_environment() -> io::Result<ChildSandbox> {
+        ChildSandbox::from_environment_with_options(ChildSandboxOptions::new())
+    }
+
+    /// Like `from_environment()`, with options.
+    pub fn from_environment_with_options(options: ChildSandboxOptions)
+                                         -> io::Result<ChildSandbox> {
+        Ok(ChildSandbox::with_options(sandbox::profile_from_environment()?, options))
+    }
+
+    /// Capability mode confines the whole process, so none of the options make a difference here.
+    pub fn with_options(profile: Profile, _: ChildSandboxOptions) -> ChildSandbox {
         ChildSandbox {
+            profile,
+        }
+    }
+
+    /// Opens `path` for reading in capability mode, limited to the rights that the profile's
+    /// `FileReadAll` operation for it needs. The path must be one that operation names: the file
+    /// of a `Literal` pattern or the directory of a `Subpath` one.
+    ///
+    /// Must be called before `activate()`. Fails with `PermissionDenied` if the profile doesn't
+    /// allow reading `path`.
+    pub fn open_path(&self, path: &Path) -> io::Result<File> {
+        for operation in self.profile.allowed_operations() {
+            match *operation {
+                profile::Operation::FileReadAll(PathPattern::Literal(ref pattern)) |
+                profile::Operation::FileReadAll(PathPattern::Subpath(ref pattern))
+                        if pattern == path => {
+                    let rights = rights_for(operation).unwrap();
+                    return CapsicumPre::open_path(path, &rights)
+                }
+                _ => {}
+            }
+        }
+        Err(io::Error::new(io::ErrorKind::PermissionDenied, "the profile doesn't allow reading it"))
+    }
+
+    /// Limits `fd`, a socket, to sending, if the profile allows outbound network access. In
+    /// capability mode sockets can't be connected by address, so connect it before activating.
+    ///
+    /// Must be called before `activate()`. Fails with `PermissionDenied` if the profile doesn't
+    /// allow `NetworkOutbound(AddressPattern::All)`.
+    pub fn limit_socket(&self, fd: RawFd) -> io::Result<()> {
+        let operation = profile::Operation::NetworkOutbound(AddressPattern::All);
+        if !self.profile.allowed_operations().contains(&operation) {
+            return Err(io::Error::new(io::ErrorKind::PermissionDenied,
+                                      "the profile doesn't allow outbound network access"))
         }
+        CapsicumPre::limit(fd, &rights_for(&operation).unwrap())
     }
 }
 
 impl ChildSandboxMethods for ChildSandbox {
-    fn activate(&self) -> Result<(),()> {
+    #[inline(never)]
+    fn activate_with_hook<F>(&self, hook: F) -> Result<(),ActivationError> where F: FnOnce() {
+        sandbox::run_activation_hook(hook);
         if unsafe { cap_enter() } == 0 {
             Ok(())
         } else {
-            error!("Failed to init sandbox");
-            Err(())
+            let err = io::Error::last_os_error();
+            error!("Failed to init sandbox: {}", err);
+            Err(ActivationError::RestrictionFailed(SandboxError::from(err)))
         }
     }
+
+    fn verify(&self) -> VerificationReport {
+        let mut report = VerificationReport::default();
+        let mut mode = 0;
+        let status = if unsafe { cap_getmode(&mut mode) } == 0 && mode != 0 {
+            ProbeStatus::Passed
+        } else {
+            ProbeStatus::Failed
+        };
+        report.push("capability-mode", status, format!("cap_getmode reported {}", mode));
+        // Capability mode grants nothing, whatever the profile says.
+        verify::probe_file_access(&mut report);
+        verify::probe_network_connect(&mut report);
+        report
+    }
 }
 
-extern {
+extern "C" {
     fn cap_enter() -> c_int;
+    fn cap_getmode(mode: *mut c_uint) -> c_int;
 }
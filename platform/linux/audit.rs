@@ -0,0 +1,82 @@
+// Copyright 2015 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Sandboxes that log the system calls their profile prohibits instead of enforcing it, for
+//! trying a profile out on a real workload before turning it on.
+//!
+//! The parent tells the child through its environment, and the child's activation then installs
+//! the profile's filter with `SECCOMP_RET_LOG` as its final action. Prohibited system calls show
+//! up in the kernel's audit log (with `auditd`, in `/var/log/audit/audit.log`; otherwise in the
+//! kernel log) as `SECCOMP` records naming the system call number and the process.
+//!
+//! Only the seccomp filter is relaxed. The namespaces and the `chroot` jail still apply, so files
+//! outside the profile still can't be found, and without `NetworkOutbound` there is still no
+//! network to connect to.
+
+use crate::platform;
+use crate::platform::linux::Sandbox;
+use crate::platform::unix::process::Process;
+use crate::sandbox::{Command, SandboxMethods};
+use crate::telemetry::Mechanism;
+
+use log::warn;
+use std::env;
+use std::io;
+
+/// The environment variable through which the parent tells the child to audit instead of
+/// enforcing.
+const AUDIT_ENV_VAR: &str = "GAOL_AUDIT_ONLY";
+
+/// A sandbox whose child logs the system calls its profile doesn't allow and then makes them
+/// anyway, instead of having them handled by the profile's `ViolationAction`.
+///
+/// This confines the child much less than `Sandbox` does, and is a separate type so that it
+/// can't be started by mistake where enforcement was meant. Use it to find out what a profile is
+/// missing, never to run untrusted code.
+pub struct AuditSandbox {
+    sandbox: Sandbox,
+}
+
+impl AuditSandbox {
+    pub fn new(sandbox: Sandbox) -> AuditSandbox {
+        AuditSandbox {
+            sandbox,
+        }
+    }
+
+    /// Returns the sandbox that this one starts children in.
+    pub fn sandbox(&self) -> &Sandbox {
+        &self.sandbox
+    }
+
+    /// Starts `command` in the sandbox, auditing its profile instead of enforcing it.
+    ///
+    /// Before Linux 4.14 the kernel can't log system calls, so prohibited ones are allowed
+    /// silently; a warning is logged. This fails with `InvalidInput` if the sandbox's options
+    /// disable the seccomp filter.
+    pub fn start(&self, command: &mut Command) -> io::Result<Process> {
+        if self.sandbox.options.disabled_mechanisms()?.contains(&Mechanism::SeccompFilter) {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                      "auditing needs the seccomp filter"))
+        }
+        if !platform::capabilities().seccomp_ret_log {
+            warn!("this kernel can't log system calls; prohibited ones will be allowed silently")
+        }
+        command.env(AUDIT_ENV_VAR, "1");
+        self.sandbox.start(command)
+    }
+}
+
+/// Returns true if the parent asked this child to audit its profile instead of enforcing it.
+pub(crate) fn audit_only() -> bool {
+    let audit_only = env::var_os(AUDIT_ENV_VAR).is_some();
+    env::remove_var(AUDIT_ENV_VAR);
+    audit_only
+}
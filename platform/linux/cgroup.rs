@@ -0,0 +1,185 @@
+// Copyright 2015 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Resource limits for sandboxed children, with cgroup v2.
+//!
+//! The parent creates a group named `gaol.<pid>.<token>` for each child started with
+//! `SandboxLimits`, under `/sys/fs/cgroup` or the group `SandboxOptions::cgroup_parent()` names,
+//! writes the limits into it, and opens its `cgroup.procs`. The child writes itself into that
+//! file right after it is forked, before it makes any process of its own, so that nothing it
+//! starts escapes the limits.
+//!
+//! `Process::wait()` removes the group of the child it waited for. Groups whose creator died
+//! before it could do so are removed by `cleanup_stale_cgroups()`.
+
+use crate::platform::linux::jail;
+use crate::sandbox::SandboxLimits;
+
+use libc::{self, pid_t};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Duration;
+
+/// Where the cgroup v2 hierarchy is mounted, under which groups are created unless another
+/// parent is configured.
+pub const DEFAULT_CGROUP_PARENT: &str = "/sys/fs/cgroup";
+
+/// The prefix of the name of every group.
+const CGROUP_PREFIX: &str = "gaol.";
+
+/// How many times to try removing a group whose last processes are still exiting.
+const REMOVE_ATTEMPTS: u32 = 50;
+
+/// A cgroup holding one sandboxed child, which `remove()` deletes. Dropping it leaves the group in
+/// place.
+#[derive(Debug)]
+pub struct Cgroup {
+    path: PathBuf,
+    procs: File,
+}
+
+impl Cgroup {
+    /// Creates a group confined to `limits` under `parent`. Fails with `Unsupported` if `parent`
+    /// isn't a cgroup v2 group, if this process may not create groups in it, or if a controller a
+    /// limit needs isn't enabled for them.
+    pub fn create(parent: &Path, limits: &SandboxLimits) -> io::Result<Cgroup> {
+        if !parent.join("cgroup.controllers").exists() {
+            return Err(unsupported(format!("resource limits need a cgroup v2 group at {}",
+                                           parent.display())))
+        }
+        let pid = unsafe {
+            libc::getpid()
+        };
+        let path = parent.join(format!("{}{}.{:016x}", CGROUP_PREFIX, pid, jail::random_token()));
+        if let Err(err) = fs::create_dir(&path) {
+            return Err(if is_denied(&err) {
+                unsupported(format!("can't create groups in {}: {}", parent.display(), err))
+            } else {
+                err
+            })
+        }
+        let cgroup = Cgroup::configure(&path, limits).map(|procs| {
+            Cgroup {
+                path: path.clone(),
+                procs,
+            }
+        });
+        if cgroup.is_err() {
+            drop(fs::remove_dir(&path));
+        }
+        cgroup
+    }
+
+    /// Writes `limits` into the group at `path` and opens its `cgroup.procs` for the child.
+    fn configure(path: &Path, limits: &SandboxLimits) -> io::Result<File> {
+        let settings = [
+            ("memory.max", limits.memory_bytes.map(|bytes| bytes.to_string())),
+            ("cpu.weight", limits.cpu_shares.map(|shares| shares.to_string())),
+            ("pids.max", limits.pids_max.map(|pids| pids.to_string())),
+        ];
+        for &(file, ref value) in settings.iter() {
+            if let Some(ref value) = *value {
+                OpenOptions::new().write(true).open(path.join(file)).and_then(|mut file| {
+                    file.write_all(value.as_bytes())
+                }).map_err(|err| {
+                    // The file only exists if the parent enables the controller for its children.
+                    if err.kind() == io::ErrorKind::NotFound || is_denied(&err) {
+                        let controller = file.split('.').next().unwrap();
+                        unsupported(format!("can't set {}; is the {} controller enabled in {}? {}",
+                                            file,
+                                            controller,
+                                            path.parent().unwrap().display(),
+                                            err))
+                    } else {
+                        io::Error::new(err.kind(), format!("couldn't set {}: {}", file, err))
+                    }
+                })?;
+            }
+        }
+        OpenOptions::new().write(true).open(path.join("cgroup.procs"))
+    }
+
+    /// Returns the path of this group.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Deletes this group. The kernel only allows that once every process in it has exited, which
+    /// can take a moment after the child itself has been waited for.
+    pub fn remove(&self) -> io::Result<()> {
+        let mut attempts = 0;
+        loop {
+            match fs::remove_dir(&self.path) {
+                Err(ref err) if err.raw_os_error() == Some(libc::EBUSY) &&
+                        attempts < REMOVE_ATTEMPTS => {
+                    attempts += 1;
+                    thread::sleep(Duration::from_millis(10))
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+/// Removes every group under `parent` whose creator is no longer running and in which no process
+/// is left. Returns the number of groups removed.
+///
+/// A group that still has processes in it, such as the descendants of a sandboxed child whose
+/// parent crashed, is left alone, since the kernel won't remove it. Killing them lets a later
+/// cleanup remove it.
+pub fn cleanup_stale_cgroups(parent: &Path) -> io::Result<usize> {
+    let mut removed = 0;
+    for entry in fs::read_dir(parent)? {
+        let entry = entry?;
+        let owner = match creator(entry.file_name().as_bytes()) {
+            Some(owner) => owner,
+            None => continue,
+        };
+        if !entry.file_type()?.is_dir() || jail::process_exists(owner) {
+            continue
+        }
+        match fs::remove_dir(entry.path()) {
+            Ok(()) => removed += 1,
+            // Another process cleaned it up first.
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => {}
+            Err(ref err) if err.raw_os_error() == Some(libc::EBUSY) => {}
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(removed)
+}
+
+/// Returns the process that created the group named `name`, if it is one of ours.
+fn creator(name: &[u8]) -> Option<pid_t> {
+    let rest = name.strip_prefix(CGROUP_PREFIX.as_bytes())?;
+    let pid = rest.split(|&byte| byte == b'.').next()?;
+    std::str::from_utf8(pid).ok()?.parse().ok()
+}
+
+/// Whether `err` means this process isn't allowed to change the hierarchy there.
+fn is_denied(err: &io::Error) -> bool {
+    matches!(err.raw_os_error(), Some(libc::EACCES) | Some(libc::EPERM) | Some(libc::EROFS))
+}
+
+fn unsupported(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::Unsupported, message)
+}
+
+impl AsRawFd for Cgroup {
+    /// Returns the descriptor of this group's `cgroup.procs`, which a process joins the group by
+    /// writing `0` to.
+    fn as_raw_fd(&self) -> RawFd {
+        self.procs.as_raw_fd()
+    }
+}
@@ -0,0 +1,136 @@
+// Copyright 2015 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Killing children that run past `ResourceLimits::max_wall_clock_seconds`.
+//!
+//! A thread watches each such child and kills it with `SIGKILL` once its time is up. The child is
+//! the first process of its own PID namespace, so its descendants die with it. With `pidfd_open()`
+//! (Linux 5.3+) the thread waits on a descriptor for the child and signals through it, so it can
+//! never signal another process that reused the child's ID. Without it, the thread signals the
+//! ID, but only while `Process::wait()` hasn't reaped the child, which keeps the ID from being
+//! reused.
+
+use libc::{self, c_int, pid_t};
+use log::warn;
+use std::io;
+use std::ptr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// The deadline of one child.
+pub struct Deadline {
+    state: Arc<DeadlineState>,
+}
+
+struct DeadlineState {
+    /// Whether `Process::wait()` is about to reap the child, after which it must not be signaled
+    /// by its ID.
+    reaped: Mutex<bool>,
+    /// Notified when `reaped` is set.
+    exited: Condvar,
+    /// Whether the watcher killed the child.
+    expired: AtomicBool,
+}
+
+impl Deadline {
+    /// Starts a thread that kills the child `pid` once `limit` has passed.
+    pub fn watch(pid: pid_t, limit: Duration) -> io::Result<Deadline> {
+        let state = Arc::new(DeadlineState {
+            reaped: Mutex::new(false),
+            exited: Condvar::new(),
+            expired: AtomicBool::new(false),
+        });
+        let watcher_state = state.clone();
+        let deadline = Instant::now() + limit;
+        thread::Builder::new().name(format!("gaol-deadline-{}", pid)).spawn(move || {
+            watch(pid, deadline, &watcher_state)
+        })?;
+        Ok(Deadline {
+            state,
+        })
+    }
+
+    /// Returns true if the child was killed for running out of time.
+    pub fn expired(&self) -> bool {
+        self.state.expired.load(Ordering::SeqCst)
+    }
+
+    /// Tells the watcher that the child has exited and is about to be reaped.
+    pub fn child_exited(&self) {
+        *self.state.reaped.lock().unwrap() = true;
+        self.state.exited.notify_all()
+    }
+}
+
+fn watch(pid: pid_t, deadline: Instant, state: &DeadlineState) {
+    let pidfd = unsafe {
+        libc::syscall(libc::SYS_pidfd_open, pid, 0) as c_int
+    };
+    if pidfd >= 0 {
+        if wait_for_exit(pidfd, deadline) {
+            state.expired.store(true, Ordering::SeqCst);
+            let result = unsafe {
+                libc::syscall(libc::SYS_pidfd_send_signal,
+                              pidfd,
+                              libc::SIGKILL,
+                              ptr::null::<libc::siginfo_t>(),
+                              0)
+            };
+            if result != 0 {
+                warn!("failed to kill child {} at its deadline: {}",
+                      pid,
+                      io::Error::last_os_error())
+            }
+        }
+        unsafe {
+            libc::close(pidfd);
+        }
+        return
+    }
+
+    let reaped = state.reaped.lock().unwrap();
+    let timeout = deadline.saturating_duration_since(Instant::now());
+    let (reaped, _) = state.exited.wait_timeout_while(reaped, timeout, |reaped| !*reaped).unwrap();
+    if !*reaped {
+        state.expired.store(true, Ordering::SeqCst);
+        if unsafe { libc::kill(pid, libc::SIGKILL) } != 0 {
+            warn!("failed to kill child {} at its deadline: {}", pid, io::Error::last_os_error())
+        }
+    }
+}
+
+/// Waits for the process behind `pidfd` to exit, returning true if `deadline` passed first.
+fn wait_for_exit(pidfd: c_int, deadline: Instant) -> bool {
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining == Duration::ZERO {
+            return true
+        }
+        let timeout = remaining.as_millis().clamp(1, c_int::MAX as u128) as c_int;
+        let mut pollfd = libc::pollfd {
+            fd: pidfd,
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        match unsafe { libc::poll(&mut pollfd, 1, timeout) } {
+            0 => {}
+            result if result > 0 => return false,
+            _ => {
+                let err = io::Error::last_os_error();
+                if err.kind() != io::ErrorKind::Interrupted {
+                    warn!("failed to wait for a child's deadline: {}", err);
+                    return false
+                }
+            }
+        }
+    }
+}
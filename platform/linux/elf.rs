@@ -0,0 +1,268 @@
+// Copyright 2015 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Finding the dynamic loader and shared libraries that a program needs, so that they can be bound
+//! into the jail alongside it.
+//!
+//! This reads just enough of 64-bit little-endian ELF files to follow `PT_INTERP`, `DT_NEEDED`,
+//! `DT_RPATH`, and `DT_RUNPATH`, and searches the directories the loader searches when it has no
+//! `/etc/ld.so.cache`, which the jail doesn't hold. Libraries loaded with `dlopen()` aren't found.
+
+use std::ffi::OsStr;
+use std::fs::File;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::FileExt;
+use std::path::{Path, PathBuf};
+
+const PT_LOAD: u32 = 1;
+const PT_DYNAMIC: u32 = 2;
+const PT_INTERP: u32 = 3;
+
+const DT_NULL: u64 = 0;
+const DT_NEEDED: u64 = 1;
+const DT_STRTAB: u64 = 5;
+const DT_RPATH: u64 = 15;
+const DT_RUNPATH: u64 = 29;
+
+/// The directories the loader searches after those a library names itself.
+#[cfg(target_arch="x86_64")]
+static DEFAULT_LIBRARY_DIRECTORIES: [&str; 6] = [
+    "/lib/x86_64-linux-gnu",
+    "/usr/lib/x86_64-linux-gnu",
+    "/lib64",
+    "/usr/lib64",
+    "/lib",
+    "/usr/lib",
+];
+#[cfg(target_arch="aarch64")]
+static DEFAULT_LIBRARY_DIRECTORIES: [&str; 6] = [
+    "/lib/aarch64-linux-gnu",
+    "/usr/lib/aarch64-linux-gnu",
+    "/lib64",
+    "/usr/lib64",
+    "/lib",
+    "/usr/lib",
+];
+#[cfg(target_arch="riscv64")]
+static DEFAULT_LIBRARY_DIRECTORIES: [&str; 6] = [
+    "/lib/riscv64-linux-gnu",
+    "/usr/lib/riscv64-linux-gnu",
+    "/lib64",
+    "/usr/lib64",
+    "/lib",
+    "/usr/lib",
+];
+#[cfg(not(any(target_arch="x86_64", target_arch="aarch64", target_arch="riscv64")))]
+static DEFAULT_LIBRARY_DIRECTORIES: [&str; 4] = [
+    "/lib64",
+    "/usr/lib64",
+    "/lib",
+    "/usr/lib",
+];
+
+/// Returns the dynamic loader and shared libraries that `program` needs, directly or through
+/// other libraries, each once. A statically linked program, or a file that isn't an ELF file this
+/// can read, needs nothing.
+pub(crate) fn dependencies(program: &Path) -> Vec<PathBuf> {
+    let mut dependencies: Vec<PathBuf> = Vec::new();
+    let mut unvisited = vec![program.to_path_buf()];
+    while let Some(path) = unvisited.pop() {
+        let object = match Object::open(&path) {
+            Some(object) => object,
+            None => continue,
+        };
+        let mut found = Vec::new();
+        if let Some(interpreter) = object.interpreter() {
+            found.push(interpreter)
+        }
+        let search_path = object.search_path(&path);
+        for name in object.needed() {
+            if let Some(library) = find_library(&name, &search_path, &object) {
+                found.push(library)
+            }
+        }
+        for path in found {
+            if path != program && !dependencies.contains(&path) {
+                dependencies.push(path.clone());
+                unvisited.push(path)
+            }
+        }
+    }
+    dependencies
+}
+
+/// Returns where the loader would find the library `name` for `object`, if anywhere.
+fn find_library(name: &[u8], search_path: &[PathBuf], object: &Object) -> Option<PathBuf> {
+    if name.contains(&b'/') {
+        let path = PathBuf::from(OsStr::from_bytes(name));
+        return if path.is_absolute() { Some(path) } else { None }
+    }
+    let default_directories = DEFAULT_LIBRARY_DIRECTORIES.iter().map(PathBuf::from);
+    search_path.iter().cloned().chain(default_directories).map(|directory| {
+        directory.join(OsStr::from_bytes(name))
+    }).find(|path| {
+        // The loader skips libraries built for another architecture.
+        Object::open(path).is_some_and(|library| library.machine == object.machine)
+    })
+}
+
+/// An ELF file opened for reading its headers.
+struct Object {
+    file: File,
+    machine: u16,
+    /// Each program header's type, file offset, virtual address, and size in the file.
+    segments: Vec<(u32, u64, u64, u64)>,
+}
+
+impl Object {
+    /// Opens the ELF file at `path`, if it is one this can read.
+    fn open(path: &Path) -> Option<Object> {
+        let file = File::open(path).ok()?;
+        let mut header = [0; 64];
+        file.read_exact_at(&mut header, 0).ok()?;
+        // The magic number, `ELFCLASS64`, and `ELFDATA2LSB`.
+        if header[0..6] != *b"\x7fELF\x02\x01" {
+            return None
+        }
+        let machine = u16::from_le_bytes([header[18], header[19]]);
+        let program_header_offset = u64_at(&header, 32);
+        let program_header_size = u16::from_le_bytes([header[54], header[55]]) as u64;
+        let program_header_count = u16::from_le_bytes([header[56], header[57]]) as u64;
+        if program_header_size < 40 {
+            return None
+        }
+        let mut segments = Vec::new();
+        for index in 0..program_header_count {
+            let mut program_header = [0; 40];
+            file.read_exact_at(&mut program_header,
+                               program_header_offset + index * program_header_size).ok()?;
+            let kind = u32::from_le_bytes([program_header[0], program_header[1],
+                                           program_header[2], program_header[3]]);
+            segments.push((kind,
+                           u64_at(&program_header, 8),
+                           u64_at(&program_header, 16),
+                           u64_at(&program_header, 32)));
+        }
+        Some(Object {
+            file,
+            machine,
+            segments,
+        })
+    }
+
+    /// Returns the path of the dynamic loader this object names, if any.
+    fn interpreter(&self) -> Option<PathBuf> {
+        let &(_, offset, _, _) = self.segments.iter().find(|segment| segment.0 == PT_INTERP)?;
+        let interpreter = self.string_at(offset)?;
+        Some(PathBuf::from(OsStr::from_bytes(&interpreter)))
+    }
+
+    /// Returns the names of the libraries this object needs.
+    fn needed(&self) -> Vec<Vec<u8>> {
+        self.dynamic_strings(DT_NEEDED)
+    }
+
+    /// Returns the directories this object asks for its libraries to be looked for in, with
+    /// `$ORIGIN` standing for the directory that `path`, this object, is in. `DT_RPATH` is
+    /// ignored if there is a `DT_RUNPATH`, as the loader does.
+    fn search_path(&self, path: &Path) -> Vec<PathBuf> {
+        let mut search_path = self.dynamic_strings(DT_RUNPATH);
+        if search_path.is_empty() {
+            search_path = self.dynamic_strings(DT_RPATH)
+        }
+        let origin = path.parent().unwrap_or_else(|| Path::new("/")).as_os_str().as_bytes();
+        search_path.iter().flat_map(|directories| directories.split(|&byte| byte == b':'))
+                   .filter(|directory| !directory.is_empty())
+                   .map(|directory| {
+                       let directory = replace(directory, b"${ORIGIN}", origin);
+                       let directory = replace(&directory, b"$ORIGIN", origin);
+                       PathBuf::from(OsStr::from_bytes(&directory))
+                   })
+                   .filter(|directory| directory.is_absolute())
+                   .collect()
+    }
+
+    /// Returns the strings that the dynamic section's entries tagged `tag` point at.
+    fn dynamic_strings(&self, tag: u64) -> Vec<Vec<u8>> {
+        let &(_, offset, _, size) = match self.segments.iter().find(|segment| {
+            segment.0 == PT_DYNAMIC
+        }) {
+            Some(segment) => segment,
+            None => return vec![],
+        };
+        let mut entries = Vec::new();
+        let mut string_table = None;
+        for index in 0..size / 16 {
+            let mut entry = [0; 16];
+            if self.file.read_exact_at(&mut entry, offset + index * 16).is_err() {
+                break
+            }
+            match u64_at(&entry, 0) {
+                DT_NULL => break,
+                DT_STRTAB => string_table = self.file_offset(u64_at(&entry, 8)),
+                entry_tag if entry_tag == tag => entries.push(u64_at(&entry, 8)),
+                _ => {}
+            }
+        }
+        let string_table = match string_table {
+            Some(string_table) => string_table,
+            None => return vec![],
+        };
+        entries.into_iter().filter_map(|entry| self.string_at(string_table + entry)).collect()
+    }
+
+    /// Returns where in the file the loaded segment holding `address` keeps it.
+    fn file_offset(&self, address: u64) -> Option<u64> {
+        self.segments.iter().find(|&&(kind, _, segment_address, size)| {
+            kind == PT_LOAD && segment_address <= address && address - segment_address < size
+        }).map(|&(_, offset, segment_address, _)| offset + address - segment_address)
+    }
+
+    /// Reads the NUL-terminated string at `offset`.
+    fn string_at(&self, offset: u64) -> Option<Vec<u8>> {
+        let mut string = Vec::new();
+        let mut chunk = [0; 256];
+        loop {
+            let length = self.file.read_at(&mut chunk, offset + string.len() as u64).ok()?;
+            if length == 0 {
+                return None
+            }
+            match chunk[..length].iter().position(|&byte| byte == 0) {
+                Some(end) => {
+                    string.extend_from_slice(&chunk[..end]);
+                    return Some(string)
+                }
+                None => string.extend_from_slice(&chunk[..length]),
+            }
+        }
+    }
+}
+
+fn u64_at(bytes: &[u8], offset: usize) -> u64 {
+    let mut word = [0; 8];
+    word.copy_from_slice(&bytes[offset..offset + 8]);
+    u64::from_le_bytes(word)
+}
+
+/// Returns `bytes` with every `pattern` in it replaced with `replacement`.
+fn replace(bytes: &[u8], pattern: &[u8], replacement: &[u8]) -> Vec<u8> {
+    let mut replaced = Vec::new();
+    let mut rest = bytes;
+    while !rest.is_empty() {
+        if rest.starts_with(pattern) {
+            replaced.extend_from_slice(replacement);
+            rest = &rest[pattern.len()..]
+        } else {
+            replaced.push(rest[0]);
+            rest = &rest[1..]
+        }
+    }
+    replaced
+}
@@ -0,0 +1,310 @@
+// Copyright 2015 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Confining a child's network traffic to address ranges, for `AddressPattern::IpCidr`.
+//!
+//! The seccomp filter can't look at the addresses a child connects to, so a profile that allows
+//! outbound traffic to address ranges keeps the child in a network namespace of its own. Once the
+//! child has entered it, the parent links it to the host with a pair of virtual Ethernet devices
+//! and has an `IpFilterBackend` install packet filter rules in it that drop every outbound packet
+//! the profile doesn't allow. The child can't change the rules, since it drops its capabilities
+//! when it activates its sandbox.
+//!
+//! The parent needs `CAP_NET_ADMIN` and `CAP_SYS_ADMIN` to do this, and `ip` from iproute2 on its
+//! `PATH`. Each link gets a `/30` from `100.64.0.0/10` and a `/112` from `fd67:616f:6c00::/48`,
+//! picked at random; the host end is the child's default route. Traffic bound beyond this
+//! machine only gets there if the host forwards and masquerades it, as it would for a container.
+
+use crate::platform::linux::jail;
+use crate::profile::{AddressPattern, Operation, Profile};
+
+use libc::{self, pid_t};
+use std::fs::File;
+use std::io::{self, Write};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::os::unix::io::AsRawFd;
+use std::os::unix::process::CommandExt;
+use std::process::{self, Stdio};
+
+/// The name of the child's end of the link, inside its network namespace.
+const CHILD_INTERFACE: &str = "eth0";
+
+/// Installs packet filter rules in a sandboxed child's network namespace. See the module
+/// documentation.
+pub trait IpFilterBackend: Send + Sync {
+    /// Returns a short name for this backend, for errors and logs.
+    fn name(&self) -> &str;
+
+    /// Returns true if the tools this backend needs are installed.
+    fn is_available(&self) -> bool;
+
+    /// Installs rules in `namespace`, an open `/proc/<pid>/ns/net`, that drop every outbound
+    /// packet except those leaving through the loopback interface, those the link needs for IPv6
+    /// neighbor discovery, and those `allowed` matches. Only `AddressPattern::IpCidr` and
+    /// `AddressPattern::Udp` patterns are passed.
+    fn install(&self, namespace: &File, allowed: &[AddressPattern]) -> io::Result<()>;
+}
+
+/// Installs the rules with `nft`, in a table named `gaol`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Nftables;
+
+impl IpFilterBackend for Nftables {
+    fn name(&self) -> &str {
+        "nftables"
+    }
+
+    fn is_available(&self) -> bool {
+        is_installed("nft")
+    }
+
+    fn install(&self, namespace: &File, allowed: &[AddressPattern]) -> io::Result<()> {
+        let ruleset = nftables_ruleset(allowed);
+        run(process::Command::new("nft").args(["-f", "-"]), Some(namespace), Some(&ruleset))
+    }
+}
+
+/// Installs the rules with `iptables` and `ip6tables`, in their `OUTPUT` chains.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Iptables;
+
+impl IpFilterBackend for Iptables {
+    fn name(&self) -> &str {
+        "iptables"
+    }
+
+    fn is_available(&self) -> bool {
+        is_installed("iptables") && is_installed("ip6tables")
+    }
+
+    fn install(&self, namespace: &File, allowed: &[AddressPattern]) -> io::Result<()> {
+        for (program, rules) in iptables_rules(allowed) {
+            for rule in rules {
+                run(process::Command::new(program).arg("-w").args(&rule), Some(namespace), None)?
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Returns `Nftables` if `nft` is installed, and otherwise `Iptables` if `iptables` is.
+pub fn default_backend() -> Option<&'static dyn IpFilterBackend> {
+    [&Nftables as &'static dyn IpFilterBackend, &Iptables].iter().cloned().find(|backend| {
+        backend.is_available()
+    })
+}
+
+/// Returns the patterns the rules for `profile` allow, or `None` if its child doesn't need them:
+/// it needs them if it allows outbound traffic to an address range and no operation lets it use
+/// the host's network as it is.
+pub(crate) fn filtered_addresses(profile: &Profile) -> Option<Vec<AddressPattern>> {
+    let mut allowed = Vec::new();
+    let mut has_range = false;
+    for operation in profile.allowed_operations() {
+        match *operation {
+            Operation::NetworkOutbound(ref pattern @ AddressPattern::IpCidr { .. }) => {
+                has_range = true;
+                allowed.push(pattern.clone())
+            }
+            Operation::NetworkOutbound(ref pattern @ AddressPattern::Udp(_)) => {
+                allowed.push(pattern.clone())
+            }
+            Operation::NetworkOutbound(_) | Operation::NetworkInbound(_) => return None,
+            _ => {}
+        }
+    }
+    if has_range {
+        Some(allowed)
+    } else {
+        None
+    }
+}
+
+/// Links the network namespace of the child `pid` to the host and has `backend` install rules in
+/// it allowing `allowed`.
+pub(crate) fn set_up(pid: pid_t, allowed: &[AddressPattern], backend: &dyn IpFilterBackend)
+                     -> io::Result<()> {
+    let namespace = File::open(format!("/proc/{}/ns/net", pid))?;
+    let token = jail::random_token();
+    let host_interface = format!("gaol{:08x}", token as u32);
+    let subnet = (token >> 32) as u32 & 0x3f_ffff;
+    let host_v4 = Ipv4Addr::from(0x6440_0000 | subnet << 2 | 1);
+    let child_v4 = Ipv4Addr::from(0x6440_0000 | subnet << 2 | 2);
+    let v6_network = 0xfd67_616f_6c00_0000u128 << 64 | (subnet as u128) << 16;
+    let host_v6 = Ipv6Addr::from(v6_network | 1);
+    let child_v6 = Ipv6Addr::from(v6_network | 2);
+
+    ip(&["link", "add", &host_interface, "type", "veth", "peer", "name", CHILD_INTERFACE,
+         "netns", &pid.to_string()], None)?;
+    ip(&["addr", "add", &format!("{}/30", host_v4), "dev", &host_interface], None)?;
+    // IPv6 may be disabled on this host, in which case the link only carries IPv4.
+    let has_v6 = ip(&["-6", "addr", "add", &format!("{}/112", host_v6), "dev", &host_interface,
+                      "nodad"], None).is_ok();
+    ip(&["link", "set", &host_interface, "up"], None)?;
+
+    ip(&["addr", "add", &format!("{}/30", child_v4), "dev", CHILD_INTERFACE], Some(&namespace))?;
+    if has_v6 {
+        ip(&["-6", "addr", "add", &format!("{}/112", child_v6), "dev", CHILD_INTERFACE, "nodad"],
+           Some(&namespace))?
+    }
+    ip(&["link", "set", CHILD_INTERFACE, "up"], Some(&namespace))?;
+    ip(&["route", "add", "default", "via", &host_v4.to_string()], Some(&namespace))?;
+    if has_v6 {
+        ip(&["-6", "route", "add", "default", "via", &host_v6.to_string()], Some(&namespace))?
+    }
+
+    backend.install(&namespace, allowed).map_err(|err| {
+        let message = format!("{} couldn't install the rules: {}", backend.name(), err);
+        io::Error::new(err.kind(), message)
+    })
+}
+
+/// Returns the nftables ruleset allowing `allowed`.
+pub fn nftables_ruleset(allowed: &[AddressPattern]) -> String {
+    let mut rules = vec![
+        "oifname \"lo\" accept".to_owned(),
+        "icmpv6 type { nd-neighbor-solicit, nd-neighbor-advert, nd-router-solicit } accept"
+            .to_owned(),
+    ];
+    for pattern in allowed {
+        match *pattern {
+            AddressPattern::IpCidr { addr, prefix_len, port } => {
+                let family = if addr.is_ipv4() { "ip" } else { "ip6" };
+                let network = network(addr, prefix_len);
+                match port {
+                    None => {
+                        rules.push(format!("{} daddr {}/{} accept", family, network, prefix_len))
+                    }
+                    Some(port) => {
+                        for protocol in &["tcp", "udp"] {
+                            rules.push(format!("{} daddr {}/{} {} dport {} accept",
+                                               family, network, prefix_len, protocol, port))
+                        }
+                    }
+                }
+            }
+            AddressPattern::Udp(port) => rules.push(format!("udp dport {} accept", port)),
+            _ => {}
+        }
+    }
+    let mut ruleset = "table inet gaol {\n\tchain output {\n\
+                       \t\ttype filter hook output priority 0; policy drop;\n".to_owned();
+    for rule in rules {
+        ruleset.push_str(&format!("\t\t{}\n", rule));
+    }
+    ruleset.push_str("\t}\n}\n");
+    ruleset
+}
+
+/// Returns the `iptables` and `ip6tables` arguments allowing `allowed`, for each program in
+/// order.
+pub fn iptables_rules(allowed: &[AddressPattern]) -> Vec<(&'static str, Vec<Vec<String>>)> {
+    let mut v4 = vec![rule(&["-A", "OUTPUT", "-o", "lo", "-j", "ACCEPT"])];
+    let mut v6 = v4.clone();
+    for icmp_type in &["neighbour-solicitation", "neighbour-advertisement", "router-solicitation"] {
+        v6.push(rule(&["-A", "OUTPUT", "-p", "icmpv6", "--icmpv6-type", icmp_type, "-j", "ACCEPT"]))
+    }
+    for pattern in allowed {
+        match *pattern {
+            AddressPattern::IpCidr { addr, prefix_len, port } => {
+                let rules = if addr.is_ipv4() { &mut v4 } else { &mut v6 };
+                let destination = format!("{}/{}", network(addr, prefix_len), prefix_len);
+                match port {
+                    None => {
+                        rules.push(rule(&["-A", "OUTPUT", "-d", &destination, "-j", "ACCEPT"]))
+                    }
+                    Some(port) => {
+                        for protocol in &["tcp", "udp"] {
+                            rules.push(rule(&["-A", "OUTPUT", "-d", &destination, "-p", protocol,
+                                              "--dport", &port.to_string(), "-j", "ACCEPT"]))
+                        }
+                    }
+                }
+            }
+            AddressPattern::Udp(port) => {
+                for rules in &mut [&mut v4, &mut v6] {
+                    rules.push(rule(&["-A", "OUTPUT", "-p", "udp", "--dport", &port.to_string(),
+                                      "-j", "ACCEPT"]))
+                }
+            }
+            _ => {}
+        }
+    }
+    for rules in &mut [&mut v4, &mut v6] {
+        rules.push(rule(&["-P", "OUTPUT", "DROP"]))
+    }
+    vec![("iptables", v4), ("ip6tables", v6)]
+}
+
+fn rule(arguments: &[&str]) -> Vec<String> {
+    arguments.iter().map(|argument| argument.to_string()).collect()
+}
+
+/// Returns `addr` with all but its first `prefix_len` bits cleared.
+fn network(addr: IpAddr, prefix_len: u8) -> IpAddr {
+    match addr {
+        IpAddr::V4(addr) => {
+            let mask = u32::MAX.checked_shl(32 - prefix_len.min(32) as u32).unwrap_or(0);
+            IpAddr::V4(Ipv4Addr::from(u32::from(addr) & mask))
+        }
+        IpAddr::V6(addr) => {
+            let mask = u128::MAX.checked_shl(128 - prefix_len.min(128) as u32).unwrap_or(0);
+            IpAddr::V6(Ipv6Addr::from(u128::from(addr) & mask))
+        }
+    }
+}
+
+/// Returns true if `program` can be run from the `PATH`.
+fn is_installed(program: &str) -> bool {
+    process::Command::new(program).arg("--version")
+                                  .stdin(Stdio::null())
+                                  .stdout(Stdio::null())
+                                  .stderr(Stdio::null())
+                                  .status()
+                                  .is_ok()
+}
+
+/// Runs `ip` with `arguments`, in `namespace` if given.
+fn ip(arguments: &[&str], namespace: Option<&File>) -> io::Result<()> {
+    run(process::Command::new("ip").args(arguments), namespace, None)
+}
+
+/// Runs `command` to completion, in the network namespace `namespace` if given, with `input` as
+/// its standard input. Fails with what it wrote to its standard error if it fails.
+fn run(command: &mut process::Command, namespace: Option<&File>, input: Option<&str>)
+       -> io::Result<()> {
+    if let Some(namespace) = namespace {
+        let fd = namespace.as_raw_fd();
+        unsafe {
+            command.pre_exec(move || {
+                if libc::setns(fd, libc::CLONE_NEWNET) == 0 {
+                    Ok(())
+                } else {
+                    Err(io::Error::last_os_error())
+                }
+            });
+        }
+    }
+    let mut child = command.stdin(if input.is_some() { Stdio::piped() } else { Stdio::null() })
+                           .stdout(Stdio::null())
+                           .stderr(Stdio::piped())
+                           .spawn()?;
+    if let Some(input) = input {
+        child.stdin.take().unwrap().write_all(input.as_bytes())?
+    }
+    let output = child.wait_with_output()?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(io::Error::other(format!("{:?} failed: {}",
+                                     command,
+                                     String::from_utf8_lossy(&output.stderr).trim_end())))
+    }
+}
@@ -0,0 +1,147 @@
+// Copyright 2015 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! The directories that `chroot` jails are built in.
+//!
+//! The parent creates each jail directory before starting the child, under the jail root (by
+//! default `/tmp`; see `SandboxOptions::jail_root()`), and passes its path to the child in the
+//! environment. Directories are named `gaol.<pid>.<token>`, where `pid` is the creating process
+//! and `token` is random, and hold a marker file recording the creating process. The child mounts
+//! a `tmpfs` over the directory in its own mount namespace, so neither is visible to it.
+//!
+//! `Process::wait()` removes the jail of the child it waited for. Jails whose creator died before
+//! it could do so are removed by `cleanup_stale_jails()`.
+
+use libc::{self, pid_t};
+use std::collections::hash_map::RandomState;
+use std::ffi::CString;
+use std::fs::{self, File};
+use std::hash::{BuildHasher, Hasher};
+use std::io::{self, Read, Write};
+use std::os::unix::fs::DirBuilderExt;
+use std::os::unix::prelude::OsStrExt;
+use std::path::{Path, PathBuf};
+
+/// The directory jails are created under unless another is configured.
+pub const DEFAULT_JAIL_ROOT: &str = "/tmp";
+
+/// The environment variable through which the parent tells the child where its jail is.
+pub const JAIL_ENV_VAR: &str = "GAOL_JAIL_DIRECTORY";
+
+/// The prefix of the name of every jail directory.
+const JAIL_PREFIX: &str = "gaol.";
+
+/// The file in a jail directory that records the process that created it.
+const OWNER_FILE: &str = ".gaol-owner";
+
+/// How many names to try before giving up on creating a jail directory.
+const MAX_ATTEMPTS: u32 = 16;
+
+/// A jail directory, which `remove()` deletes. Dropping it leaves the directory in place.
+#[derive(Debug)]
+pub struct JailDirectory {
+    path: PathBuf,
+}
+
+impl JailDirectory {
+    /// Creates a new, uniquely named jail directory under `root`, owned by this process.
+    pub fn create(root: &Path) -> io::Result<JailDirectory> {
+        let pid = unsafe {
+            libc::getpid()
+        };
+        for _ in 0..MAX_ATTEMPTS {
+            let path = root.join(format!("{}{}.{:016x}", JAIL_PREFIX, pid, random_token()));
+            match fs::DirBuilder::new().mode(0o700).create(&path) {
+                Ok(()) => {}
+                Err(ref err) if err.kind() == io::ErrorKind::AlreadyExists => continue,
+                Err(err) => return Err(err),
+            }
+            let jail = JailDirectory {
+                path,
+            };
+            if let Err(err) = File::create(jail.path.join(OWNER_FILE)).and_then(|mut file| {
+                write!(file, "{}", pid)
+            }) {
+                drop(jail.remove());
+                return Err(err)
+            }
+            return Ok(jail)
+        }
+        Err(io::Error::new(io::ErrorKind::AlreadyExists, "couldn't find an unused jail name"))
+    }
+
+    /// Returns the path of this jail directory.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Unmounts anything still mounted on this jail directory and deletes it.
+    pub fn remove(&self) -> io::Result<()> {
+        remove_jail(&self.path)
+    }
+}
+
+/// Removes every jail directory under `root` whose creator is no longer running, unmounting
+/// anything still mounted on it first. Returns the number of directories removed.
+///
+/// Directories without an owner marker, such as those left by older versions, are left alone.
+pub fn cleanup_stale_jails(root: &Path) -> io::Result<usize> {
+    let mut removed = 0;
+    for entry in fs::read_dir(root)? {
+        let entry = entry?;
+        if !entry.file_name().as_bytes().starts_with(JAIL_PREFIX.as_bytes()) ||
+                !entry.file_type()?.is_dir() {
+            continue
+        }
+        let owner = match read_owner(&entry.path()) {
+            Some(owner) => owner,
+            None => continue,
+        };
+        if process_exists(owner) {
+            continue
+        }
+        match remove_jail(&entry.path()) {
+            Ok(()) => removed += 1,
+            // Another process cleaned it up first.
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => {}
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(removed)
+}
+
+fn remove_jail(path: &Path) -> io::Result<()> {
+    // Normally nothing is mounted here outside the child's mount namespace, so failure is fine.
+    let c_path = CString::new(path.as_os_str().as_bytes()).unwrap();
+    unsafe {
+        libc::umount2(c_path.as_ptr(), libc::MNT_DETACH);
+    }
+    fs::remove_dir_all(path)
+}
+
+fn read_owner(path: &Path) -> Option<pid_t> {
+    let mut contents = String::new();
+    File::open(path.join(OWNER_FILE)).ok()?.read_to_string(&mut contents).ok()?;
+    contents.trim().parse().ok()
+}
+
+/// Whether the process `pid` is running, or at least might be.
+pub(crate) fn process_exists(pid: pid_t) -> bool {
+    let result = unsafe {
+        libc::kill(pid, 0)
+    };
+    result == 0 || io::Error::last_os_error().raw_os_error() != Some(libc::ESRCH)
+}
+
+/// Returns a random number for a jail or cgroup name. It need not be unpredictable, only unlikely
+/// to collide, since the directory is created exclusively.
+pub(crate) fn random_token() -> u64 {
+    RandomState::new().build_hasher().finish()
+}
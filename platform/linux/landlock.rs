@@ -0,0 +1,266 @@
+// Copyright 2015 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Sandboxing on Linux via the Landlock LSM (Linux 5.13+).
+//!
+//! Landlock restricts which files a process can open, by the file hierarchies it was given rules
+//! for. It complements the namespaces and the `chroot` jail rather than replacing them: the jail
+//! decides what a child can see, and Landlock what it may do with it, checked by the kernel on
+//! every access even if the child gets out of the jail. Where Landlock is missing or disabled,
+//! children are confined by the namespaces, the jail, and the seccomp filter alone.
+//!
+//! Landlock is only used for profiles whose file operations it can express: `FileReadAll`,
+//! `FileReadMetadata`, and `FileWriteAll`. It has no right for reading metadata, which every
+//! process may do wherever it can look. Profiles that run programs, append to files, change
+//! metadata, or read system information go without it, because the jail gives their children
+//! files the profile doesn't name.
+
+use crate::error::SandboxError;
+use crate::platform;
+use crate::platform::capabilities::linux::probe_landlock_abi;
+use crate::profile::{Operation, PathPattern, Profile};
+
+use libc::{self, c_int, c_long, c_void};
+use std::ffi::CString;
+use std::io;
+use std::mem;
+use std::os::unix::prelude::OsStrExt;
+use std::path::{Path, PathBuf};
+
+const LANDLOCK_RULE_PATH_BENEATH: c_int = 1;
+
+const LANDLOCK_ACCESS_FS_EXECUTE: u64 = 1 << 0;
+const LANDLOCK_ACCESS_FS_WRITE_FILE: u64 = 1 << 1;
+const LANDLOCK_ACCESS_FS_READ_FILE: u64 = 1 << 2;
+const LANDLOCK_ACCESS_FS_READ_DIR: u64 = 1 << 3;
+const LANDLOCK_ACCESS_FS_REMOVE_DIR: u64 = 1 << 4;
+const LANDLOCK_ACCESS_FS_REMOVE_FILE: u64 = 1 << 5;
+const LANDLOCK_ACCESS_FS_MAKE_CHAR: u64 = 1 << 6;
+const LANDLOCK_ACCESS_FS_MAKE_DIR: u64 = 1 << 7;
+const LANDLOCK_ACCESS_FS_MAKE_REG: u64 = 1 << 8;
+const LANDLOCK_ACCESS_FS_MAKE_SOCK: u64 = 1 << 9;
+const LANDLOCK_ACCESS_FS_MAKE_FIFO: u64 = 1 << 10;
+const LANDLOCK_ACCESS_FS_MAKE_BLOCK: u64 = 1 << 11;
+const LANDLOCK_ACCESS_FS_MAKE_SYM: u64 = 1 << 12;
+/// Moving and linking files between directories (ABI 2).
+const LANDLOCK_ACCESS_FS_REFER: u64 = 1 << 13;
+/// Truncating files (ABI 3).
+const LANDLOCK_ACCESS_FS_TRUNCATE: u64 = 1 << 14;
+/// `ioctl`s on character and block devices (ABI 5).
+const LANDLOCK_ACCESS_FS_IOCTL_DEV: u64 = 1 << 15;
+
+/// The rights that apply to files other than directories. Rules for them may grant no others.
+const FILE_ACCESS: u64 = LANDLOCK_ACCESS_FS_EXECUTE | LANDLOCK_ACCESS_FS_WRITE_FILE |
+    LANDLOCK_ACCESS_FS_READ_FILE | LANDLOCK_ACCESS_FS_TRUNCATE | LANDLOCK_ACCESS_FS_IOCTL_DEV;
+
+/// What `FileReadAll` allows.
+const READ_ACCESS: u64 = LANDLOCK_ACCESS_FS_READ_FILE | LANDLOCK_ACCESS_FS_READ_DIR;
+
+/// What `FileWriteAll` allows: everything the seccomp filter lets through for it.
+const WRITE_ACCESS: u64 = READ_ACCESS | LANDLOCK_ACCESS_FS_WRITE_FILE |
+    LANDLOCK_ACCESS_FS_REMOVE_DIR | LANDLOCK_ACCESS_FS_REMOVE_FILE | LANDLOCK_ACCESS_FS_MAKE_DIR |
+    LANDLOCK_ACCESS_FS_MAKE_REG | LANDLOCK_ACCESS_FS_MAKE_SYM | LANDLOCK_ACCESS_FS_REFER |
+    LANDLOCK_ACCESS_FS_TRUNCATE;
+
+/// The version of the Landlock ABI this kernel offers. Each version handles more rights than the
+/// one before; version 0 means Landlock is missing or disabled.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct LandlockAbi(pub u32);
+
+impl LandlockAbi {
+    /// Returns true if Landlock can be used at all.
+    pub fn is_supported(&self) -> bool {
+        self.0 > 0
+    }
+
+    /// Returns the file system rights that rulesets can handle under this version.
+    pub fn handled_access_fs(&self) -> u64 {
+        let mut access = match self.0 {
+            0 => return 0,
+            _ => {
+                LANDLOCK_ACCESS_FS_EXECUTE | LANDLOCK_ACCESS_FS_WRITE_FILE |
+                    LANDLOCK_ACCESS_FS_READ_FILE | LANDLOCK_ACCESS_FS_READ_DIR |
+                    LANDLOCK_ACCESS_FS_REMOVE_DIR | LANDLOCK_ACCESS_FS_REMOVE_FILE |
+                    LANDLOCK_ACCESS_FS_MAKE_CHAR | LANDLOCK_ACCESS_FS_MAKE_DIR |
+                    LANDLOCK_ACCESS_FS_MAKE_REG | LANDLOCK_ACCESS_FS_MAKE_SOCK |
+                    LANDLOCK_ACCESS_FS_MAKE_FIFO | LANDLOCK_ACCESS_FS_MAKE_BLOCK |
+                    LANDLOCK_ACCESS_FS_MAKE_SYM
+            }
+        };
+        if self.0 >= 2 {
+            access |= LANDLOCK_ACCESS_FS_REFER
+        }
+        if self.0 >= 3 {
+            access |= LANDLOCK_ACCESS_FS_TRUNCATE
+        }
+        if self.0 >= 5 {
+            access |= LANDLOCK_ACCESS_FS_IOCTL_DEV
+        }
+        access
+    }
+}
+
+/// Detects Landlock support.
+pub struct LandlockSupport;
+
+impl LandlockSupport {
+    /// Returns the highest Landlock ABI version this kernel offers, as probed once by
+    /// `platform::capabilities()`.
+    pub fn detect() -> LandlockAbi {
+        LandlockAbi(platform::capabilities().landlock_abi.unwrap_or(0))
+    }
+}
+
+#[repr(C)]
+struct landlock_ruleset_attr {
+    handled_access_fs: u64,
+}
+
+#[repr(C, packed)]
+struct landlock_path_beneath_attr {
+    allowed_access: u64,
+    parent_fd: c_int,
+}
+
+/// Returns true if Landlock can express the profile's file operations.
+pub(crate) fn applies_to(profile: &Profile) -> bool {
+    profile.allowed_operations().iter().all(|operation| {
+        !matches!(*operation,
+                  Operation::FileWriteMetadata(_) |
+                  Operation::FileAppend(_) |
+                  Operation::ProcessExec(_) |
+                  Operation::SystemInfoRead |
+                  Operation::StandardDevices |
+                  Operation::PlatformSpecific(platform::Operation::MountProc))
+    })
+}
+
+/// Restricts this process to the files the profile allows, with the paths the profile's globs
+/// match among `glob_matches`, and `core_dump_directory`, writable, if there is one.
+///
+/// The paths are resolved as this process sees them, so this must come after entering the jail.
+/// Nothing is restricted, successfully, if this kernel lacks Landlock. Paths that don't exist are
+/// skipped.
+#[inline(never)]
+pub(crate) fn activate(profile: &Profile,
+                       glob_matches: &[PathBuf],
+                       core_dump_directory: Option<&Path>)
+                       -> Result<(),SandboxError> {
+    // Probe here rather than through `platform::capabilities()`, whose other probes fork.
+    let abi = LandlockAbi(probe_landlock_abi().map_or(0, |version| version.max(0) as u32));
+    if !abi.is_supported() {
+        return Ok(())
+    }
+    let handled = abi.handled_access_fs();
+
+    let mut rules: Vec<(&Path, u64)> = Vec::new();
+    for operation in profile.allowed_operations() {
+        let (pattern, access) = match *operation {
+            Operation::FileReadAll(ref pattern) => (pattern, READ_ACCESS),
+            Operation::FileWriteAll(ref pattern) => (pattern, WRITE_ACCESS),
+            _ => continue,
+        };
+        match *pattern {
+            PathPattern::Literal(ref path) | PathPattern::Subpath(ref path) => {
+                rules.push((path, access))
+            }
+            PathPattern::Glob(_) => {
+                for path in glob_matches {
+                    if pattern.matches(path) {
+                        rules.push((path, access))
+                    }
+                }
+            }
+        }
+    }
+    if let Some(core_dump_directory) = core_dump_directory {
+        rules.push((core_dump_directory, WRITE_ACCESS))
+    }
+
+    let attr = landlock_ruleset_attr {
+        handled_access_fs: handled,
+    };
+    let ruleset = unsafe {
+        libc::syscall(libc::SYS_landlock_create_ruleset,
+                      &attr as *const landlock_ruleset_attr,
+                      mem::size_of::<landlock_ruleset_attr>(),
+                      0u32)
+    };
+    if ruleset < 0 {
+        return Err(landlock_error(io::Error::last_os_error()))
+    }
+    let ruleset = ruleset as c_int;
+
+    let result = add_rules(ruleset, &rules, handled).and_then(|()| restrict_self(ruleset));
+    unsafe {
+        libc::close(ruleset);
+    }
+    result.map_err(landlock_error)
+}
+
+fn landlock_error(err: io::Error) -> SandboxError {
+    SandboxError::PlatformError(format!("Landlock couldn't be enabled: {}", err))
+}
+
+fn add_rules(ruleset: c_int, rules: &[(&Path, u64)], handled: u64) -> io::Result<()> {
+    for &(path, access) in rules {
+        let c_path = match CString::new(path.as_os_str().as_bytes()) {
+            Ok(c_path) => c_path,
+            Err(_) => continue,
+        };
+        let fd = unsafe {
+            libc::open(c_path.as_ptr(), libc::O_PATH | libc::O_CLOEXEC)
+        };
+        if fd < 0 {
+            continue
+        }
+        let mut stat: libc::stat = unsafe {
+            mem::zeroed()
+        };
+        let mut access = access & handled;
+        let is_directory = unsafe {
+            libc::fstat(fd, &mut stat) == 0 && stat.st_mode & libc::S_IFMT == libc::S_IFDIR
+        };
+        if !is_directory {
+            access &= FILE_ACCESS
+        }
+        let attr = landlock_path_beneath_attr {
+            allowed_access: access,
+            parent_fd: fd,
+        };
+        let result: c_long = unsafe {
+            libc::syscall(libc::SYS_landlock_add_rule,
+                          ruleset,
+                          LANDLOCK_RULE_PATH_BENEATH,
+                          &attr as *const landlock_path_beneath_attr as *const c_void,
+                          0u32)
+        };
+        let err = io::Error::last_os_error();
+        unsafe {
+            libc::close(fd);
+        }
+        if result != 0 {
+            return Err(err)
+        }
+    }
+    Ok(())
+}
+
+fn restrict_self(ruleset: c_int) -> io::Result<()> {
+    unsafe {
+        // Landlock needs this, as the seccomp filter does, unless the process has
+        // `CAP_SYS_ADMIN`, which by now it has dropped.
+        if libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0) != 0 ||
+                libc::syscall(libc::SYS_landlock_restrict_self, ruleset, 0u32) != 0 {
+            return Err(io::Error::last_os_error())
+        }
+    }
+    Ok(())
+}
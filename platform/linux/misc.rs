@@ -10,20 +10,60 @@
 
 //! Sandboxing on Linux via miscellaneous kernel features.
 
+use crate::error::SandboxError;
+use crate::profile::{Operation, Profile};
+use crate::sandbox::{CoreDumps, ResourceLimits};
+
 use libc;
+use std::env;
+use std::fs;
 use std::io;
 
-pub fn activate() -> Result<(), libc::c_int> {
-    // Disable writing by setting the write limit to zero.
-    let rlimit = libc::rlimit {
-        rlim_cur: 0,
-        rlim_max: 0,
-    };
-    let result = unsafe {
-         libc::setrlimit(libc::RLIMIT_FSIZE, &rlimit)
-    };
-    if result != 0 {
-        return Err(result)
+/// Sets each of `limits` that is set as both the soft and the hard limit, so that the process
+/// can't raise it again. The hard CPU time limit is a second past the soft one, so that a
+/// process handling `SIGXCPU` gets it a second before `SIGKILL`.
+pub fn set_resource_limits(limits: &ResourceLimits) -> Result<(), SandboxError> {
+    for &(resource, limit) in [
+        (libc::RLIMIT_AS, limits.max_memory_bytes),
+        (libc::RLIMIT_CPU, limits.max_cpu_seconds),
+        (libc::RLIMIT_NOFILE, limits.max_open_files),
+        (libc::RLIMIT_NPROC, limits.max_processes),
+        (libc::RLIMIT_FSIZE, limits.max_file_size_bytes),
+    ].iter() {
+        let limit = match limit {
+            Some(limit) => limit,
+            None => continue,
+        };
+        // `setrlimit64()` takes limits past 4 GiB even where `rlim_t` is 32 bits.
+        let rlimit = libc::rlimit64 {
+            rlim_cur: limit,
+            rlim_max: if resource == libc::RLIMIT_CPU { limit.saturating_add(1) } else { limit },
+        };
+        if unsafe { libc::setrlimit64(resource, &rlimit) } != 0 {
+            return Err(SandboxError::ResourceLimitFailed(io::Error::last_os_error()))
+        }
+    }
+    Ok(())
+}
+
+#[inline(never)]
+pub fn activate(profile: &Profile, core_dumps: &CoreDumps) -> Result<(), SandboxError> {
+    // Disable writing by setting the write limit to zero, unless the profile allows writing or
+    // appending somewhere or core dumps are to be written. (The limit truncates core dumps too.)
+    let dumps_core = matches!(*core_dumps, CoreDumps::EnabledTo(_));
+    if !dumps_core && !profile.allowed_operations().iter().any(|operation| {
+        matches!(*operation, Operation::FileWriteAll(_) | Operation::FileAppend(_))
+    }) {
+        let rlimit = libc::rlimit {
+            rlim_cur: 0,
+            rlim_max: 0,
+        };
+        let result = unsafe {
+             libc::setrlimit(libc::RLIMIT_FSIZE, &rlimit)
+        };
+        if result != 0 {
+            return Err(last_error())
+        }
     }
 
     // Set a restrictive `umask` so that even if files happened to get written it'd be hard to do
@@ -32,21 +72,57 @@ pub fn activate() -> Result<(), libc::c_int> {
         libc::umask(0);
     }
 
-    // Disable core dumps and debugging via `PTRACE_ATTACH`.
-    let result = unsafe {
-        libc::prctl(libc::PR_SET_DUMPABLE, 0, 0, 0, 0)
-    };
-    if result != 0 {
-        return Err(result)
+    match *core_dumps {
+        CoreDumps::Disabled => {
+            // Disable core dumps and debugging via `PTRACE_ATTACH`.
+            let rlimit = libc::rlimit {
+                rlim_cur: 0,
+                rlim_max: 0,
+            };
+            let result = unsafe {
+                libc::setrlimit(libc::RLIMIT_CORE, &rlimit)
+            };
+            if result != 0 {
+                return Err(last_error())
+            }
+            let result = unsafe {
+                libc::prctl(libc::PR_SET_DUMPABLE, 0, 0, 0, 0)
+            };
+            if result != 0 {
+                return Err(last_error())
+            }
+        }
+        CoreDumps::Default => {}
+        CoreDumps::EnabledTo(ref directory) => {
+            // Dump as much as we may, into the directory, which `core_pattern` is relative to.
+            let mut rlimit = libc::rlimit {
+                rlim_cur: 0,
+                rlim_max: 0,
+            };
+            let result = unsafe {
+                libc::getrlimit(libc::RLIMIT_CORE, &mut rlimit)
+            };
+            if result != 0 {
+                return Err(last_error())
+            }
+            rlimit.rlim_cur = rlimit.rlim_max;
+            let result = unsafe {
+                libc::setrlimit(libc::RLIMIT_CORE, &rlimit)
+            };
+            if result != 0 {
+                return Err(last_error())
+            }
+            env::set_current_dir(directory)?
+        }
     }
 
     // Enter a new session group. (This can fail with -EPERM if we're already the session leader,
     // which is OK.)
     unsafe {
         if libc::setsid() < 0 {
-            let result = io::Error::last_os_error().raw_os_error().unwrap() as i32;
-            if result != libc::EPERM {
-                return Err(result)
+            let err = io::Error::last_os_error();
+            if err.raw_os_error() != Some(libc::EPERM) {
+                return Err(SandboxError::from(err))
             }
         }
     }
@@ -58,6 +134,15 @@ pub fn activate() -> Result<(), libc::c_int> {
     if result == 0 {
         Ok(())
     } else {
-        Err(result)
+        Err(last_error())
     }
 }
+
+fn last_error() -> SandboxError {
+    SandboxError::from(io::Error::last_os_error())
+}
+
+/// Returns the number of threads in this process.
+pub fn thread_count() -> io::Result<usize> {
+    Ok(fs::read_dir("/proc/self/task")?.count())
+}
@@ -8,54 +8,380 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
-use platform::linux::seccomp::Filter;
-use platform::unix::process::Process;
-use profile::{self, AddressPattern, OperationSupport, OperationSupportLevel, Profile};
-use sandbox::{ChildSandboxMethods, Command, SandboxMethods};
+use crate::error::SandboxError;
+use crate::platform;
+use crate::platform::capabilities;
+use crate::platform::linux::cgroup::Cgroup;
+use crate::platform::linux::deadline::Deadline;
+use crate::platform::linux::jail::JailDirectory;
+use crate::platform::linux::namespace::NamespaceOptions;
+use crate::platform::linux::seccomp::Filter;
+use crate::platform::linux::trace::TraceReport;
+use crate::platform::unix::process::Process;
+use crate::profile::{self, AddressPattern, OperationSupport, OperationSupportLevel, PathPattern};
+use crate::profile::{Profile, ViolationAction};
+use crate::profile::{DecodeError, Decoder, Describe};
+use crate::sandbox::{self, ActivationError, ChildSandboxMethods, ChildSandboxOptions, Command};
+use crate::sandbox::{CoreDumps, MountOptions, PreflightIssue, ProfilePayload, SandboxMethods};
+use crate::sandbox::SandboxOptions;
+use crate::sandbox::{ResourceLimits, SandboxLimits, VerificationReport};
+use crate::telemetry::{Mechanism, Stage, StageTimings};
 
+use libc::c_int;
+use log::warn;
+use std::env;
+use std::ffi::{CString, OsStr};
+use std::fs;
 use std::io;
+use std::mem;
+use std::os::unix::prelude::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
 
+pub use self::audit::AuditSandbox;
+pub use self::ip_filter::{IpFilterBackend, Iptables, Nftables};
+pub use self::cgroup::cleanup_stale_cgroups;
+pub use self::jail::cleanup_stale_jails;
+pub use self::landlock::{LandlockAbi, LandlockSupport};
+pub use self::sigsys::SysViolationInfo;
+pub use self::supervisor::{SeccompNotif, SeccompNotifyFd, SupervisedSandbox, SupervisorResponse};
+
+pub mod audit;
+pub(crate) mod cgroup;
+pub(crate) mod deadline;
+mod elf;
+pub mod ip_filter;
+pub mod jail;
+pub mod landlock;
 pub mod misc;
 pub mod namespace;
 pub mod seccomp;
+mod sigsys;
+pub mod supervisor;
+pub mod syscalls;
+pub mod trace;
+pub mod verify;
 
 #[allow(missing_copy_implementations)]
-#[derive(Clone, Debug)]
-pub struct Operation;
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Operation {
+    /// The system call with this number may be made, with any arguments. The seccomp filter lets
+    /// it through; nothing else about the sandbox changes, so it may still fail, say, for want of
+    /// a path in the jail. `syscalls::syscall_nr()` finds the number for a name, and
+    /// `ProfileBuilder::allow_raw_syscall()` does both at once.
+    Syscall(u32),
+    /// A new `proc` filesystem is mounted read-only at `/proc` in the jail, so that, for example,
+    /// `/proc/self/maps`, `/proc/self/status`, and `/proc/cpuinfo` can be read. It is an instance
+    /// of the child's own, never the host's: it shows only the processes in the child's PID
+    /// namespace, and each only to its own user (`hidepid=2`). The rest of it still describes the
+    /// machine: the kernel's version and settings under `/proc/sys`, its load and uptime, its
+    /// CPUs and memory, and the like, which the profile can't otherwise reveal.
+    MountProc,
+}
+
+impl Describe for Operation {
+    fn describe(&self) -> String {
+        match *self {
+            Operation::Syscall(number) => {
+                match syscalls::syscall_name(number) {
+                    Some(name) => format!("make the system call {}", name),
+                    None => format!("make system call number {}", number),
+                }
+            }
+            Operation::MountProc => "read a /proc of its own".to_owned(),
+        }
+    }
+}
+
+/// Identifies Linux-specific operations in encoded profiles.
+pub(crate) const OPERATION_ENCODING_ID: u8 = 1;
+
+impl Operation {
+    pub(crate) fn encode(&self, bytes: &mut Vec<u8>) {
+        match *self {
+            Operation::Syscall(number) => {
+                bytes.push(1);
+                bytes.extend_from_slice(&number.to_le_bytes())
+            }
+            Operation::MountProc => bytes.push(2),
+        }
+    }
+
+    pub(crate) fn decode(decoder: &mut Decoder) -> Result<Operation,DecodeError> {
+        match decoder.u8()? {
+            1 => Ok(Operation::Syscall(decoder.u32()?)),
+            2 => Ok(Operation::MountProc),
+            _ => Err(DecodeError::Malformed),
+        }
+    }
+}
 
 impl OperationSupport for profile::Operation {
     fn support(&self) -> OperationSupportLevel {
         match *self {
             profile::Operation::FileReadAll(_) |
-            profile::Operation::NetworkOutbound(AddressPattern::All) => {
+            profile::Operation::FileWriteAll(_) |
+            profile::Operation::FileWriteMetadata(_) |
+            profile::Operation::FileAppend(_) |
+            profile::Operation::ProcessExec(_) |
+            profile::Operation::SystemInfoRead |
+            profile::Operation::StandardDevices |
+            profile::Operation::NetworkOutbound(AddressPattern::All) |
+            profile::Operation::NetworkInbound(AddressPattern::All) => {
+                OperationSupportLevel::CanBeAllowed
+            }
+            // The port isn't checked, but the filter does keep UDP from allowing TCP, and no
+            // coarser operation allows UDP alone.
+            profile::Operation::NetworkOutbound(AddressPattern::Udp(_)) => {
+                OperationSupportLevel::CanBeAllowed
+            }
+            // Packet filter rules in the child's network namespace check these. See `ip_filter`.
+            profile::Operation::NetworkOutbound(AddressPattern::IpCidr { .. }) => {
                 OperationSupportLevel::CanBeAllowed
             }
             profile::Operation::FileReadMetadata(_) |
             profile::Operation::NetworkOutbound(AddressPattern::Tcp(_)) |
-            profile::Operation::NetworkOutbound(AddressPattern::LocalSocket(_)) => {
+            profile::Operation::NetworkOutbound(AddressPattern::LocalSocket(_)) |
+            profile::Operation::NetworkInbound(AddressPattern::Tcp(_)) |
+            profile::Operation::NetworkInbound(AddressPattern::Udp(_)) |
+            profile::Operation::NetworkInbound(AddressPattern::LocalSocket(_)) |
+            profile::Operation::NetworkInbound(AddressPattern::IpCidr { .. }) => {
                 OperationSupportLevel::CannotBeAllowedPrecisely
             }
-            profile::Operation::SystemInfoRead |
-            profile::Operation::PlatformSpecific(_) => OperationSupportLevel::NeverAllowed,
+            profile::Operation::PlatformSpecific(Operation::Syscall(_)) |
+            profile::Operation::PlatformSpecific(Operation::MountProc) => {
+                OperationSupportLevel::CanBeAllowed
+            }
+        }
+    }
+}
+
+/// The environment variable through which the parent tells the child which mechanisms
+/// `SandboxOptions` disabled, as a comma-separated list of `Mechanism::name()`s.
+const DISABLED_ENV_VAR: &str = "GAOL_DISABLED_MECHANISMS";
+
+/// The environment variable through which the parent tells the child what to do with core dumps:
+/// `default` for `CoreDumps::Default` or the directory for `CoreDumps::EnabledTo`. Without it,
+/// core dumps are disabled.
+const CORE_DUMPS_ENV_VAR: &str = "GAOL_CORE_DUMPS";
+
+/// The environment variable through which the parent tells the child which of the
+/// `MountOptions` flags it turned off, as a comma-separated list of `exec`, `suid`, and `dev`.
+/// Without it, the jail gets them all.
+const MOUNT_OPTIONS_ENV_VAR: &str = "GAOL_MOUNT_OPTIONS";
+
+/// The longest hostname the kernel takes.
+const HOST_NAME_MAX: usize = 64;
+
+/// The environment variable through which the parent tells the child the size limit, in bytes,
+/// of the `tmpfs` its jail is built on. Without it, the `tmpfs` gets the kernel's default.
+const TMPFS_SIZE_ENV_VAR: &str = "GAOL_TMPFS_SIZE";
+
+/// The environment variable through which the parent tells the child its `ResourceLimits`, as
+/// comma-separated `name=value` pairs for the limits that are set, with the names `memory`,
+/// `cpu`, `files`, `processes` and `file_size`.
+const RESOURCE_LIMITS_ENV_VAR: &str = "GAOL_RESOURCE_LIMITS";
+
+/// The environment variable through which the parent tells the child that the kernel couldn't
+/// give it a cgroup namespace, so that activation can report that.
+const NO_CGROUP_NAMESPACE_ENV_VAR: &str = "GAOL_NO_CGROUP_NAMESPACE";
+
+/// The environment variable through which the parent tells the child which paths the globs in
+/// its profile matched when it was started, separated by newlines. A path with a newline in it
+/// comes through as paths that no glob matches, so it just stays out of the jail.
+const GLOB_MATCHES_ENV_VAR: &str = "GAOL_GLOB_MATCHES";
+
+/// Returns the paths that the globs in the file operations of `profile` match now, or `None` if
+/// there are no globs.
+fn glob_matches(profile: &Profile) -> Option<Vec<PathBuf>> {
+    let globs: Vec<&PathPattern> = profile.allowed_operations().iter().filter_map(|operation| {
+        match *operation {
+            profile::Operation::FileReadAll(ref pattern @ PathPattern::Glob(_)) |
+            profile::Operation::FileWriteAll(ref pattern @ PathPattern::Glob(_)) |
+            profile::Operation::FileWriteMetadata(ref pattern @ PathPattern::Glob(_)) |
+            profile::Operation::FileAppend(ref pattern @ PathPattern::Glob(_)) |
+            profile::Operation::ProcessExec(ref pattern @ PathPattern::Glob(_)) => {
+                Some(pattern)
+            }
+            _ => None,
         }
+    }).collect();
+    if globs.is_empty() {
+        return None
     }
+    let mut matches: Vec<PathBuf> = globs.iter().flat_map(|glob| glob.expand()).collect();
+    matches.sort();
+    matches.dedup();
+    Some(matches)
 }
 
+/// Returns the first symbolic link, with its contents, that leads nowhere on the way to a path
+/// that the file operations of `profile` would bind into the jail.
+fn dangling_symlink(profile: &Profile) -> Option<(PathBuf, PathBuf)> {
+    profile.allowed_operations().iter().filter_map(|operation| {
+        match *operation {
+            profile::Operation::FileReadAll(ref pattern) |
+            profile::Operation::FileWriteAll(ref pattern) |
+            profile::Operation::FileWriteMetadata(ref pattern) |
+            profile::Operation::FileAppend(ref pattern) |
+            profile::Operation::ProcessExec(ref pattern) => Some(pattern),
+            _ => None,
+        }
+    }).filter_map(|pattern| {
+        match *pattern {
+            PathPattern::Literal(ref path) | PathPattern::Subpath(ref path) => Some(path),
+            PathPattern::Glob(_) => None,
+        }
+    }).filter(|path| fs::metadata(path).is_err()).find_map(|path| {
+        path.ancestors().find_map(|ancestor| {
+            let metadata = fs::symlink_metadata(ancestor).ok()?;
+            if !metadata.file_type().is_symlink() || fs::metadata(ancestor).is_ok() {
+                return None
+            }
+            Some((ancestor.to_path_buf(), fs::read_link(ancestor).ok()?))
+        })
+    })
+}
+
+/// Returns the mechanisms that confine a child started with `profile`, leaving out `disabled`,
+/// and those of them that this machine lacks.
+pub(crate) fn mechanisms(profile: &Profile, disabled: &[Mechanism])
+                         -> (Vec<Mechanism>, Vec<Mechanism>) {
+    let mut mechanisms = vec![Mechanism::Namespaces];
+    // A child allowed only address ranges gets a namespace of its own too, linked to the host.
+    if ip_filter::filtered_addresses(profile).is_some() ||
+            !profile.allowed_operations().iter().any(|operation| {
+        matches!(*operation,
+                 profile::Operation::NetworkOutbound(_) | profile::Operation::NetworkInbound(_))
+    }) {
+        mechanisms.push(Mechanism::NetworkNamespace)
+    }
+    mechanisms.push(Mechanism::ChrootJail);
+    mechanisms.push(Mechanism::SeccompFilter);
+    // Landlock is an extra layer where there is one, so it is never missing.
+    let capabilities = platform::capabilities();
+    if capabilities.landlock_abi.is_some() && landlock::applies_to(profile) {
+        mechanisms.push(Mechanism::Landlock)
+    }
+    mechanisms.retain(|mechanism| !disabled.contains(mechanism));
+
+    let missing = mechanisms.iter().cloned().filter(|mechanism| {
+        match *mechanism {
+            Mechanism::Namespaces | Mechanism::NetworkNamespace => !capabilities.user_namespaces,
+            Mechanism::ChrootJail => !capabilities.mount_in_user_namespace,
+            Mechanism::SeccompFilter => !capabilities.seccomp_filter,
+            _ => false,
+        }
+    }).collect();
+    (mechanisms, missing)
+}
+
+/// The `statfs` magic numbers of the filesystems that `unbindable_filesystem()` rejects.
+const PROC_SUPER_MAGIC: i64 = 0x9fa0;
+const SYSFS_MAGIC: i64 = 0x6265_6572;
+
+/// Returns the filesystem `path` is on if binding it into the jail wouldn't work as intended.
+///
+/// The contents of `proc` and `sysfs` depend on the namespaces of whoever mounted them, so a
+/// child in new PID and network namespaces would see the host's processes and devices through
+/// them rather than its own.
+pub(crate) fn unbindable_filesystem(path: &Path) -> Option<&'static str> {
+    let c_path = CString::new(path.as_os_str().as_bytes()).ok()?;
+    let mut statfs: libc::statfs = unsafe {
+        mem::zeroed()
+    };
+    if unsafe { libc::statfs(c_path.as_ptr(), &mut statfs) } != 0 {
+        return None
+    }
+    match statfs.f_type as i64 {
+        PROC_SUPER_MAGIC => Some("proc"),
+        SYSFS_MAGIC => Some("sysfs"),
+        _ => None,
+    }
+}
+
+/// Returns the kernel's core dump pattern if it would keep children from dumping core into a
+/// `CoreDumps::EnabledTo` directory.
+///
+/// A pattern starting with `|` pipes dumps to a helper on the host, and an absolute one is
+/// resolved inside the jail, so only a plain file name ends up in the child's working directory.
+pub(crate) fn unsupported_core_pattern() -> Option<String> {
+    let pattern = fs::read_to_string("/proc/sys/kernel/core_pattern").ok()?;
+    let pattern = pattern.trim_end();
+    if pattern.starts_with('|') || pattern.starts_with('/') {
+        Some(pattern.to_owned())
+    } else {
+        None
+    }
+}
+
+/// Starts sandboxed children. Each child is the first process, PID 1, of a PID namespace of its
+/// own, in user, mount, IPC and UTS namespaces of its own too, so that it can't see or signal
+/// processes outside them whatever its profile allows. The parent reaps the child with
+/// `Process::wait()`, and the child reaps its own children, as any PID 1 does.
 pub struct Sandbox {
     profile: Profile,
+    options: SandboxOptions,
+    ip_filter_backend: Option<Arc<dyn IpFilterBackend>>,
 }
 
 impl Sandbox {
     pub fn new(profile: Profile) -> Sandbox {
+        Sandbox::with_options(profile, SandboxOptions::new())
+    }
+
+    pub fn with_options(profile: Profile, options: SandboxOptions) -> Sandbox {
         Sandbox {
-            profile: profile,
+            profile,
+            options,
+            ip_filter_backend: None,
+        }
+    }
+
+    /// Installs the packet filter rules for `AddressPattern::IpCidr` with `backend`, instead of
+    /// with `ip_filter::default_backend()`.
+    pub fn with_ip_filter_backend(mut self, backend: Arc<dyn IpFilterBackend>) -> Sandbox {
+        self.ip_filter_backend = Some(backend);
+        self
+    }
+
+    /// Confines children started from now on to `limits`, with cgroup v2. Starting them fails
+    /// with `Unsupported` if groups can't be made where `SandboxOptions::cgroup_parent()` says.
+    pub fn with_limits(mut self, limits: SandboxLimits) -> Sandbox {
+        self.options.limits(limits);
+        self
+    }
+
+    /// Limits each child started from now on to `limits` with `setrlimit()`. See
+    /// `ResourceLimits`.
+    pub fn with_resource_limits(mut self, limits: ResourceLimits) -> Sandbox {
+        self.options.process_limits(limits);
+        self
+    }
+
+    /// Runs `command` **with no confinement at all** and records the system calls it and its
+    /// descendants make, suggesting the operations a profile would need to allow them. This is
+    /// for writing profiles for existing programs, and must never be used to run untrusted code.
+    ///
+    /// The profile is ignored. Because nothing is confined, this fails with `InvalidInput` unless
+    /// the options were given `SandboxOptions::acknowledge_weakened_sandbox()`. It needs
+    /// `PTRACE_GET_SYSCALL_INFO` (Linux 5.3+), and waits for any child of this process, so other
+    /// children must not be waited for until it returns.
+    pub fn trace(&self, command: &mut Command) -> io::Result<TraceReport> {
+        if !self.options.weakening_acknowledged() {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                      "tracing runs the child unconfined and needs \
+                                       acknowledge_weakened_sandbox()"))
         }
+        warn!("tracing a child with no sandbox; it is not confined");
+        trace::trace(command)
     }
 
     #[cfg(dump_bpf_sockets)]
     fn dump_filter(&self) {
-        let filter = Filter::new(&self.profile);
-        filter.dump();
+        Filter::cached(&self.profile).dump();
     }
 
     #[cfg(not(dump_bpf_sockets))]
@@ -68,35 +394,571 @@ impl SandboxMethods for Sandbox {
     }
 
     fn start(&self, command: &mut Command) -> io::Result<Process> {
+        // Without seccomp filter mode the child could never activate its sandbox.
+        if !platform::capabilities().seccomp_filter {
+            return Err(io::Error::other("seccomp filter mode is unavailable"))
+        }
         self.dump_filter();
-        namespace::start(&self.profile, command)
+        // The kernel would only refuse it once the child is half made.
+        if self.options.child_hostname().is_some_and(|hostname| hostname.len() > HOST_NAME_MAX) {
+            let err = io::Error::new(io::ErrorKind::InvalidInput, "the hostname is too long");
+            return self.options.record_start(&self.profile, Err(err))
+        }
+        let payload = match ProfilePayload::new(&self.profile) {
+            Ok(payload) => payload,
+            Err(err) => return self.options.record_start(&self.profile, Err(err)),
+        };
+        payload.export(command);
+        let disabled = match self.options.disabled_mechanisms() {
+            Ok(disabled) => disabled,
+            Err(err) => return self.options.record_start(&self.profile, Err(err)),
+        };
+        let (mechanisms, _) = mechanisms(&self.profile, disabled);
+        // Let the caller choose between giving up and `SandboxOptions::disable_namespaces()`.
+        if mechanisms.contains(&Mechanism::Namespaces) {
+            if let Some(err) = capabilities::probe_results().user_namespace_error() {
+                let err = SandboxError::UserNamespacesUnavailable(err);
+                let err = io::Error::new(io::ErrorKind::Unsupported, err);
+                return self.options.record_start(&self.profile, Err(err))
+            }
+        }
+        // The jail would just go without it, and leave the child to wonder why.
+        if mechanisms.contains(&Mechanism::ChrootJail) {
+            if let Some((link, target)) = dangling_symlink(&self.profile) {
+                let err = io::Error::new(io::ErrorKind::InvalidInput,
+                                         format!("{} is a symbolic link to {}, which doesn't exist",
+                                                 link.display(),
+                                                 target.display()));
+                return self.options.record_start(&self.profile, Err(err))
+            }
+        }
+        if !disabled.is_empty() {
+            let names: Vec<&str> = disabled.iter().map(|mechanism| mechanism.name()).collect();
+            command.env(DISABLED_ENV_VAR, names.join(","));
+        }
+        let mount_options = self.options.jail_mount_options();
+        if mount_options != MountOptions::default() {
+            let relaxed: Vec<&str> = [
+                (mount_options.noexec, "exec"),
+                (mount_options.nosuid, "suid"),
+                (mount_options.nodev, "dev"),
+            ].iter().filter(|&&(on, _)| !on).map(|&(_, name)| name).collect();
+            command.env(MOUNT_OPTIONS_ENV_VAR, relaxed.join(","));
+        }
+        if let Some(size) = self.options.jail_tmpfs_size() {
+            command.env(TMPFS_SIZE_ENV_VAR, size.to_string());
+        }
+        match *self.options.core_dump_behavior() {
+            CoreDumps::Disabled => {}
+            CoreDumps::Default => {
+                command.env(CORE_DUMPS_ENV_VAR, "default");
+            }
+            CoreDumps::EnabledTo(ref directory) => {
+                if !directory.is_absolute() {
+                    let err = io::Error::new(io::ErrorKind::InvalidInput,
+                                             "the core dump directory must be absolute");
+                    return self.options.record_start(&self.profile, Err(err))
+                }
+                if let Some(pattern) = unsupported_core_pattern() {
+                    let issue = PreflightIssue::UnsupportedCorePattern(pattern);
+                    let err = io::Error::new(io::ErrorKind::InvalidInput, issue.to_string());
+                    return self.options.record_start(&self.profile, Err(err))
+                }
+                command.env(CORE_DUMPS_ENV_VAR, directory);
+            }
+        }
+
+        let encoded_limits = self.options.per_process_limits().map(encode_resource_limits);
+        if let Some(encoded_limits) = encoded_limits.filter(|limits| !limits.is_empty()) {
+            command.env(RESOURCE_LIMITS_ENV_VAR, encoded_limits);
+        }
+
+        // Expand the globs now, so that paths created later stay out of the jail.
+        if let Some(matches) = glob_matches(&self.profile) {
+            let matches: Vec<&OsStr> = matches.iter().map(|path| path.as_os_str()).collect();
+            command.env(GLOB_MATCHES_ENV_VAR, matches.join(OsStr::new("\n")));
+        }
+
+        // Without namespaces, the child runs beside its parent as it would on other platforms,
+        // confined by its filter alone.
+        if !mechanisms.contains(&Mechanism::Namespaces) {
+            let result = if self.options.resource_limits().is_some() {
+                Err(io::Error::new(io::ErrorKind::Unsupported,
+                                   "sandbox limits need namespaces"))
+            } else if self.options.child_hostname().is_some() {
+                Err(io::Error::new(io::ErrorKind::Unsupported, "hostnames need namespaces"))
+            } else {
+                warn!("namespaces are disabled; the child's filesystem isn't confined");
+                command.spawn().and_then(|process| self.watch_wall_clock(process))
+            };
+            return self.options.record_start(&self.profile, result)
+        }
+
+        // Find a backend for the packet filter rules of address ranges before creating anything
+        // that would need cleaning up.
+        let filtered_addresses = if mechanisms.contains(&Mechanism::NetworkNamespace) {
+            ip_filter::filtered_addresses(&self.profile)
+        } else {
+            None
+        };
+        let ip_filter = match filtered_addresses {
+            None => None,
+            Some(allowed) => {
+                let backend = match self.ip_filter_backend {
+                    Some(ref backend) => Some(&**backend),
+                    None => ip_filter::default_backend(),
+                };
+                match backend {
+                    Some(backend) => Some((allowed, backend)),
+                    None => {
+                        let err = io::Error::new(io::ErrorKind::Unsupported,
+                                                 "address ranges need nft or iptables");
+                        return self.options.record_start(&self.profile, Err(err))
+                    }
+                }
+            }
+        };
+        let configure_network = ip_filter.as_ref().map(|&(ref allowed, backend)| {
+            move |pid| ip_filter::set_up(pid, allowed, backend)
+        });
+
+        let jail = if mechanisms.contains(&Mechanism::ChrootJail) {
+            match JailDirectory::create(self.options.jail_root_path()) {
+                Ok(jail) => {
+                    command.env(jail::JAIL_ENV_VAR, jail.path());
+                    Some(jail)
+                }
+                Err(err) => return self.options.record_start(&self.profile, Err(err)),
+            }
+        } else {
+            None
+        };
+        let cgroup_parent = self.options.cgroup_parent_path();
+        let cgroup = match self.options.resource_limits().map(|limits| {
+            Cgroup::create(cgroup_parent, limits)
+        }) {
+            None => None,
+            Some(Ok(cgroup)) => Some(cgroup),
+            Some(Err(err)) => {
+                if let Some(jail) = jail {
+                    drop(jail.remove());
+                }
+                return self.options.record_start(&self.profile, Err(err))
+            }
+        };
+        let isolate_time = self.options.isolates_time() && platform::capabilities().time_namespaces;
+        if self.options.isolates_time() && !isolate_time {
+            warn!("time namespaces are unavailable; the child will see the host's clocks")
+        }
+        let isolate_cgroups = platform::capabilities().cgroup_namespaces;
+        if !isolate_cgroups {
+            warn!("cgroup namespaces are unavailable; the child will see the host's cgroups");
+            command.env(NO_CGROUP_NAMESPACE_ENV_VAR, "1");
+        }
+        let result = match namespace::start(&mechanisms,
+                                             command,
+                                             cgroup.as_ref(),
+                                             NamespaceOptions {
+                                                 isolate_cgroups,
+                                                 isolate_time,
+                                                 user_mapping: self.options.user_mapping_mode(),
+                                                 hostname: self.options.child_hostname(),
+                                             },
+                                             configure_network.as_ref().map(|configure| {
+                                                 configure as &dyn Fn(c_int) -> io::Result<()>
+                                             }),
+                                             self.options.reports_outcomes()) {
+            Ok(mut process) => {
+                process.jail = jail;
+                process.cgroup = cgroup;
+                self.watch_wall_clock(process)
+            }
+            Err(err) => {
+                if let Some(jail) = jail {
+                    drop(jail.remove());
+                }
+                if let Some(cgroup) = cgroup {
+                    drop(cgroup.remove());
+                }
+                Err(err)
+            }
+        };
+        self.options.record_start(&self.profile, result)
+    }
+
+    fn preflight(&self) -> Result<(), Vec<PreflightIssue>> {
+        self.options.preflight(&self.profile)
     }
 }
 
+impl Sandbox {
+    /// Has the parent kill `process` once it has run past the wall-clock limit it was given, if
+    /// any. The child sets its other limits itself.
+    fn watch_wall_clock(&self, mut process: Process) -> io::Result<Process> {
+        let wall_clock_limits = [self.options.per_process_limits(), self.profile.resource_limits()];
+        let seconds = match wall_clock_limits.iter().filter_map(|limits| {
+            limits.and_then(|limits| limits.max_wall_clock_seconds)
+        }).min() {
+            None => return Ok(process),
+            Some(seconds) => seconds,
+        };
+        match Deadline::watch(process.pid, Duration::from_secs(seconds)) {
+            Ok(deadline) => {
+                process.deadline = Some(deadline);
+                Ok(process)
+            }
+            Err(err) => {
+                // Nothing would stop the child, so don't let it run.
+                unsafe {
+                    libc::kill(process.pid, libc::SIGKILL);
+                }
+                drop(process.wait());
+                Err(err)
+            }
+        }
+    }
+}
+
+/// The sandbox a child activates on itself. It relies on the namespaces `Sandbox::start()`
+/// created for the child: a process started some other way can't move itself into a PID namespace
+/// of its own, so it stays visible to, and able to signal, the rest of the host's processes.
 pub struct ChildSandbox {
     profile: Profile,
+    options: ChildSandboxOptions,
+    violation_handler: Option<fn(&SysViolationInfo)>,
 }
 
 impl ChildSandbox {
     pub fn new(profile: Profile) -> ChildSandbox {
+        ChildSandbox::with_options(profile, ChildSandboxOptions::new())
+    }
+
+    /// Creates a sandbox for the profile that `Sandbox::start()` passed to this process, so that
+    /// the child needn't rebuild it. Fails with `InvalidData` if the profile was damaged in
+    /// transit, wrapping a `DecodeError`.
+    pub fn from_environment() -> io::Result<ChildSandbox> {
+        ChildSandbox::from_environment_with_options(ChildSandboxOptions::new())
+    }
+
+    /// Like `from_environment()`, with options.
+    pub fn from_environment_with_options(options: ChildSandboxOptions)
+                                         -> io::Result<ChildSandbox> {
+        Ok(ChildSandbox::with_options(sandbox::profile_from_environment()?, options))
+    }
+
+    pub fn with_options(profile: Profile, options: ChildSandboxOptions) -> ChildSandbox {
         ChildSandbox {
-            profile: profile,
+            profile,
+            options,
+            violation_handler: None,
+        }
+    }
+
+    /// Makes activation install a `SIGSYS` handler that calls `handler` with each system call the
+    /// profile prohibits, instead of handling it as the profile's `ViolationAction` says, as
+    /// `ViolationAction::Trap` does. The process dies of `SIGSYS` once `handler` returns.
+    ///
+    /// `handler` runs in a signal handler, under the filter: it may only make system calls the
+    /// profile allows, and must not allocate, take locks, or panic. The same caveats as for
+    /// `activate_with_violation_reporting()` apply.
+    pub fn with_violation_handler(mut self, handler: fn(&SysViolationInfo)) -> ChildSandbox {
+        self.violation_handler = Some(handler);
+        self
+    }
+
+    /// Checks, without changing anything about this process, that activation could confine it as
+    /// its profile asks, so that problems surface here rather than as an `ActivationError`. Every
+    /// issue found is returned.
+    ///
+    /// Besides the paths that `SandboxMethods::preflight()` checks, this checks that the process
+    /// may build the jail, that the kernel has seccomp filter mode, that the profile's filter
+    /// isn't too long for the kernel, and that no threads would escape activation. The namespaces
+    /// are the parent's to check, since `Sandbox::start()` created them already.
+    pub fn preflight(&self) -> Result<(), Vec<PreflightIssue>> {
+        let mut issues = Vec::new();
+        sandbox::check_paths(self.profile.required_host_paths(), &mut issues);
+        let disabled = disabled_mechanisms();
+        if !disabled.contains(&Mechanism::ChrootJail) && !may_mount() {
+            issues.push(PreflightIssue::CannotMount)
+        }
+        if !disabled.contains(&Mechanism::SeccompFilter) {
+            if !platform::capabilities().seccomp_filter {
+                issues.push(PreflightIssue::MechanismUnavailable(Mechanism::SeccompFilter))
+            }
+            let length = Filter::new(&self.profile).instructions().len();
+            if length > seccomp::MAX_INSTRUCTIONS {
+                issues.push(PreflightIssue::FilterTooLong(length))
+            }
+        }
+        match misc::thread_count() {
+            Ok(thread_count) if thread_count > 1 && !self.options.synchronizes_threads() => {
+                issues.push(PreflightIssue::MultithreadedProcess {
+                    thread_count,
+                })
+            }
+            Ok(_) | Err(_) => {}
+        }
+        if issues.is_empty() {
+            Ok(())
+        } else {
+            Err(issues)
         }
     }
+
+    /// Like `activate()`, but instead of handling a system call the profile prohibits as its
+    /// `ViolationAction` says, reports it and then kills the process with `SIGSYS`.
+    ///
+    /// The filter raises `SIGSYS` in the process for the prohibited system call, with
+    /// `SECCOMP_RET_TRAP`, and a handler installed here writes one line naming the system call
+    /// number, the architecture, and the first three arguments to `fd` (pass
+    /// `libc::STDERR_FILENO` for standard error), which must stay open. The process then dies of
+    /// `SIGSYS` as it would under `ViolationAction::Kill`. If the profile allows no writing, the
+    /// file size limit keeps reports out of regular files, so use a pipe or terminal then. A
+    /// handler given to `with_violation_handler()` gets the reports instead.
+    ///
+    /// This is a debugging facility for finding out what a profile is missing, not a hardening
+    /// improvement: the process can replace the handler, or block `SIGSYS` and carry on after a
+    /// prohibited call fails with `ENOSYS`. Don't use it for untrusted code. Under a
+    /// `SupervisedSandbox`, the supervisor sees prohibited system calls instead.
+    #[inline(never)]
+    pub fn activate_with_violation_reporting(&self, fd: c_int) -> Result<(),ActivationError> {
+        self.activate_with(|| {}, Some(fd))
+    }
+
+    #[inline(never)]
+    fn activate_with<F>(&self, hook: F, report_fd: Option<c_int>) -> Result<(),ActivationError>
+                        where F: FnOnce() {
+        // Namespaces, the `chroot` jail, and capabilities only bind the calling thread.
+        let thread_count = match misc::thread_count() {
+            Ok(thread_count) => thread_count,
+            Err(err) => return Err(ActivationError::RestrictionFailed(SandboxError::from(err))),
+        };
+        let synchronize_threads = thread_count > 1;
+        if synchronize_threads && !self.options.synchronizes_threads() {
+            return Err(ActivationError::MultithreadedProcess {
+                thread_count,
+            })
+        }
+
+        // Read these before `misc::activate()` clears the environment.
+        let disabled = disabled_mechanisms();
+        let core_dumps = core_dumps();
+        let mount_options = mount_options();
+        let tmpfs_size = env::var(TMPFS_SIZE_ENV_VAR).ok().and_then(|size| size.parse().ok());
+        let resource_limits = match self.profile.resource_limits() {
+            Some(profile_limits) => resource_limits().combined_with(profile_limits),
+            None => resource_limits(),
+        };
+        let timings_fd = timings_fd();
+        let cgroup_namespace = env::var_os(NO_CGROUP_NAMESPACE_ENV_VAR).is_none();
+        let notify_socket = supervisor::notify_socket();
+        let audit_only = audit::audit_only();
+        let glob_matches = match env::var_os(GLOB_MATCHES_ENV_VAR) {
+            Some(glob_matches) => {
+                glob_matches.as_bytes().split(|&byte| byte == b'\n').map(|path| {
+                    PathBuf::from(OsStr::from_bytes(path))
+                }).collect()
+            }
+            None => glob_matches(&self.profile).unwrap_or_default(),
+        };
+        let mut timings = StageTimings::default();
+
+        // Look the filter up (and perhaps compile it) while allocation is still allowed.
+        let reporting = report_fd.is_some() || self.violation_handler.is_some();
+        let filter = match notify_socket {
+            Some(socket) => Arc::new(Filter::supervised(&self.profile, socket)),
+            None if audit_only => Arc::new(Filter::auditing(&self.profile)),
+            None if reporting => Arc::new(Filter::reporting(&self.profile)),
+            None => Filter::cached(&self.profile),
+        };
+        let chroot = !disabled.contains(&Mechanism::ChrootJail);
+        let core_dump_directory = match core_dumps {
+            CoreDumps::EnabledTo(ref directory) => Some(&**directory),
+            CoreDumps::Disabled | CoreDumps::Default => None,
+        };
+        namespace::activate(&self.profile,
+                            chroot,
+                            &glob_matches,
+                            core_dump_directory,
+                            mount_options,
+                            tmpfs_size,
+                            &mut timings).map_err(ActivationError::RestrictionFailed)?;
+        // Before `misc::activate()`, which may lower the file size limit past this one.
+        misc::set_resource_limits(&resource_limits).map_err(ActivationError::RestrictionFailed)?;
+        misc::activate(&self.profile, &core_dumps).map_err(ActivationError::RestrictionFailed)?;
+        sandbox::run_activation_hook(hook);
+        if !disabled.contains(&Mechanism::Landlock) && landlock::applies_to(&self.profile) {
+            landlock::activate(&self.profile, &glob_matches, core_dump_directory)
+                .map_err(ActivationError::RestrictionFailed)?
+        }
+        if disabled.contains(&Mechanism::SeccompFilter) {
+            if let Some(socket) = notify_socket {
+                unsafe {
+                    libc::close(socket);
+                }
+            }
+            send_timings(timings_fd, &timings);
+            return cgroup_namespace_result(cgroup_namespace)
+        }
+        let traps = reporting || self.profile.violation_action() == ViolationAction::Trap;
+        if traps && notify_socket.is_none() && !audit_only {
+            let fd = report_fd.unwrap_or(libc::STDERR_FILENO);
+            sigsys::install(fd, self.violation_handler).map_err(|err| {
+                ActivationError::RestrictionFailed(SandboxError::from(err))
+            })?
+        }
+        let mut unsynchronized = false;
+        let result = timings.time(Stage::FilterInstall, || {
+            match notify_socket {
+                Some(socket) => {
+                    filter.activate_with_listener(synchronize_threads).and_then(|listener| {
+                        supervisor::send_listener(socket, listener)
+                    })
+                }
+                // Without `SECCOMP_FILTER_FLAG_TSYNC`, confine at least this thread.
+                None if synchronize_threads => {
+                    match filter.activate_on_all_threads() {
+                        Err(ref err) if err.raw_os_error() == Some(libc::EINVAL) => {
+                            unsynchronized = true;
+                            filter.activate()
+                        }
+                        result => result,
+                    }
+                }
+                None => filter.activate(),
+            }
+        });
+        result.map_err(ActivationError::RestrictionFailed)?;
+        // If the cache is disabled this is the only reference, and freeing the program now could
+        // make a system call the filter doesn't allow.
+        mem::forget(filter);
+        send_timings(timings_fd, &timings);
+        if unsynchronized {
+            return Err(ActivationError::ThreadsNotSynchronized {
+                thread_count,
+            })
+        }
+        cgroup_namespace_result(cgroup_namespace)
+    }
 }
 
 impl ChildSandboxMethods for ChildSandbox {
-    fn activate(&self) -> Result<(),()> {
-        if namespace::activate(&self.profile).is_err() {
-            return Err(())
-        }
-        if misc::activate().is_err() {
-            return Err(())
+    #[inline(never)]
+    fn activate_with_hook<F>(&self, hook: F) -> Result<(),ActivationError> where F: FnOnce() {
+        self.activate_with(hook, None)
+    }
+
+    fn verify(&self) -> VerificationReport {
+        verify::verify(&self.profile)
+    }
+}
+
+/// Returns the mechanisms the parent disabled for this child.
+fn disabled_mechanisms() -> Vec<Mechanism> {
+    match env::var(DISABLED_ENV_VAR) {
+        Ok(names) => names.split(',').filter_map(Mechanism::from_name).collect(),
+        Err(_) => Vec::new(),
+    }
+}
+
+/// Reports whether this process holds `CAP_SYS_ADMIN`, as a child of `Sandbox::start()` does in
+/// its user namespace, so that it may mount the jail.
+fn may_mount() -> bool {
+    const CAP_SYS_ADMIN: u32 = 21;
+    let status = match fs::read_to_string("/proc/self/status") {
+        Ok(status) => status,
+        Err(_) => return false,
+    };
+    status.lines()
+          .filter_map(|line| line.strip_prefix("CapEff:"))
+          .filter_map(|capabilities| u64::from_str_radix(capabilities.trim(), 16).ok())
+          .any(|capabilities| capabilities & (1 << CAP_SYS_ADMIN) != 0)
+}
+
+/// Reports whether the child got the cgroup namespace `Sandbox::start()` tries to give it, after
+/// everything else has been applied.
+fn cgroup_namespace_result(cgroup_namespace: bool) -> Result<(),ActivationError> {
+    if cgroup_namespace {
+        Ok(())
+    } else {
+        Err(ActivationError::NoCgroupNamespace)
+    }
+}
+
+/// Returns the descriptor the parent asked this child to send its stage timings through, if it
+/// did.
+fn timings_fd() -> Option<c_int> {
+    let fd = env::var(namespace::TIMINGS_FD_ENV_VAR).ok().and_then(|fd| fd.parse().ok());
+    env::remove_var(namespace::TIMINGS_FD_ENV_VAR);
+    fd
+}
+
+/// Sends `timings` to the parent, if it asked for them, and closes the descriptor so that it
+/// stops waiting.
+fn send_timings(fd: Option<c_int>, timings: &StageTimings) {
+    if let Some(fd) = fd {
+        namespace::send_timings(fd, timings);
+        unsafe {
+            libc::close(fd);
         }
-        match Filter::new(&self.profile).activate() {
-            Ok(_) => Ok(()),
-            Err(_) => Err(()),
+    }
+}
+
+/// Encodes `limits` for `RESOURCE_LIMITS_ENV_VAR`.
+fn encode_resource_limits(limits: &ResourceLimits) -> String {
+    let limits = [
+        ("memory", limits.max_memory_bytes),
+        ("cpu", limits.max_cpu_seconds),
+        ("files", limits.max_open_files),
+        ("processes", limits.max_processes),
+        ("file_size", limits.max_file_size_bytes),
+    ];
+    let pairs: Vec<String> = limits.iter().filter_map(|&(name, limit)| {
+        limit.map(|limit| format!("{}={}", name, limit))
+    }).collect();
+    pairs.join(",")
+}
+
+/// Returns the limits the parent asked this child to set, skipping anything unrecognized.
+fn resource_limits() -> ResourceLimits {
+    let mut limits = ResourceLimits::default();
+    let value = match env::var(RESOURCE_LIMITS_ENV_VAR) {
+        Ok(value) => value,
+        Err(_) => return limits,
+    };
+    for pair in value.split(',') {
+        let (name, limit) = match pair.split_once('=') {
+            Some((name, limit)) => (name, limit.parse().ok()),
+            None => continue,
+        };
+        match name {
+            "memory" => limits.max_memory_bytes = limit,
+            "cpu" => limits.max_cpu_seconds = limit,
+            "files" => limits.max_open_files = limit,
+            "processes" => limits.max_processes = limit,
+            "file_size" => limits.max_file_size_bytes = limit,
+            _ => {}
         }
     }
+    limits
 }
 
+/// Returns the flags the parent asked this child to mount the jail's paths with.
+fn mount_options() -> MountOptions {
+    let relaxed = env::var(MOUNT_OPTIONS_ENV_VAR).unwrap_or_default();
+    let relaxed: Vec<&str> = relaxed.split(',').collect();
+    MountOptions {
+        noexec: !relaxed.contains(&"exec"),
+        nosuid: !relaxed.contains(&"suid"),
+        nodev: !relaxed.contains(&"dev"),
+    }
+}
+
+/// Returns what the parent asked this child to do with core dumps.
+fn core_dumps() -> CoreDumps {
+    match env::var_os(CORE_DUMPS_ENV_VAR) {
+        Some(ref value) if value == "default" => CoreDumps::Default,
+        Some(directory) => CoreDumps::EnabledTo(PathBuf::from(directory)),
+        None => CoreDumps::Disabled,
+    }
+}
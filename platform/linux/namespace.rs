@@ -10,130 +10,282 @@
 
 //! Sandboxing on Linux via namespaces.
 
-use platform::linux::seccomp;
-use platform::unix::process::Process;
-use platform::unix;
-use profile::{Operation, PathPattern, Profile};
-use sandbox::Command;
+use crate::error::SandboxError;
+use crate::event_log::EventSink;
+use crate::platform::linux::cgroup::Cgroup;
+use crate::platform::linux::elf;
+use crate::platform::linux::jail::{JailDirectory, DEFAULT_JAIL_ROOT, JAIL_ENV_VAR};
+use crate::platform::linux::Operation as PlatformOperation;
+use crate::platform::unix::process::Process;
+use crate::platform::unix;
+use crate::profile::{Operation, PathPattern, Profile, STANDARD_DEVICES};
+use crate::sandbox::{Command, MountOptions, UserMapping};
+use crate::telemetry::{Mechanism, Stage, StageTimings, TelemetrySink};
 
 use libc::{self, c_char, c_int, c_ulong, c_void, gid_t, pid_t, size_t, ssize_t, uid_t};
+use log::{error, warn};
 use std::env;
 use std::ffi::{CString, OsStr, OsString};
 use std::fs::{self, File};
-use std::io::{self, Write};
-use std::iter;
+use std::io::{self, Read, Write};
 use std::mem;
-use std::os::unix::prelude::OsStrExt;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::symlink;
+use std::os::unix::io::{AsRawFd, FromRawFd};
 use std::path::{Path, PathBuf};
+use std::process;
 use std::ptr;
 
-/// Creates a namespace and sets up a chroot jail.
-pub fn activate(profile: &Profile) -> Result<(),c_int> {
-    let jail = try!(ChrootJail::new(profile));
-    try!(jail.enter());
+/// The environment variable through which `start()` tells the child which inherited file
+/// descriptor to send its stage timings through, when the parent wants them.
+pub const TIMINGS_FD_ENV_VAR: &str = "GAOL_TIMINGS_FD";
+
+/// The files in `/proc` that `Operation::SystemInfoRead` puts in the jail. Unlike most of `/proc`,
+/// they describe the machine rather than the namespaces of whoever mounted it.
+const SYSTEM_INFO_PATHS: [&str; 2] = ["/proc/cpuinfo", "/proc/meminfo"];
+
+/// Sets up a chroot jail, unless `chroot` is false, and drops capabilities. The jail includes
+/// the paths among `glob_matches` that the profile's globs match, and `core_dump_directory`,
+/// writable, if there is one, bound with the flags `mount_options` asks for, on a `tmpfs` of at
+/// most `tmpfs_size` bytes if that is set. The jail's stages are timed in `timings`.
+#[inline(never)]
+pub(crate) fn activate(profile: &Profile,
+                       chroot: bool,
+                       glob_matches: &[PathBuf],
+                       core_dump_directory: Option<&Path>,
+                       mount_options: MountOptions,
+                       tmpfs_size: Option<u64>,
+                       timings: &mut StageTimings)
+                       -> Result<(),SandboxError> {
+    if chroot {
+        let jail = ChrootJail::new(profile,
+                                   glob_matches,
+                                   core_dump_directory,
+                                   mount_options,
+                                   tmpfs_size,
+                                   timings)?;
+        timings.time(Stage::Chroot, || jail.enter())?;
+    }
     drop_capabilities()
 }
 
-/// A `chroot` jail with a restricted view of the filesystem inside it.
+/// A jail with a restricted view of the filesystem, which becomes the root of the process.
+///
+/// The jail starts out empty, without even `/dev/null` or `/proc/self`, and only gets what the
+/// profile allows: `Operation::StandardDevices` for the usual devices and
+/// `PlatformOperation::MountProc` for a `/proc`. Neither is put in unconditionally. `mknod()`
+/// always fails in a user namespace, so the devices have to be bound from the host, and a bound
+/// host `/proc/self` would describe the process as the host's PID namespace sees it.
 struct ChrootJail {
     directory: PathBuf,
 }
 
 impl ChrootJail {
-    /// Creates a new `chroot` jail.
-    fn new(profile: &Profile) -> Result<ChrootJail,c_int> {
-        let prefix = CString::new("/tmp/gaol.XXXXXX").unwrap();
-        let mut prefix: Vec<u8> = prefix.as_bytes_with_nul().iter().map(|x| *x).collect();
-        unsafe {
-            if libc::mkdtemp(prefix.as_mut_ptr() as *mut c_char).is_null() {
-                return Err(-1)
+    /// Creates a new `chroot` jail in the directory the parent created for it, or in a new one
+    /// if this process wasn't started by `Sandbox::start()`.
+    fn new(profile: &Profile,
+           glob_matches: &[PathBuf],
+           core_dump_directory: Option<&Path>,
+           mount_options: MountOptions,
+           tmpfs_size: Option<u64>,
+           timings: &mut StageTimings)
+           -> Result<ChrootJail,SandboxError> {
+        let jail_dir = match env::var_os(JAIL_ENV_VAR) {
+            Some(jail_dir) => PathBuf::from(jail_dir),
+            None => {
+                match JailDirectory::create(Path::new(DEFAULT_JAIL_ROOT)) {
+                    Ok(jail_dir) => jail_dir.path().to_path_buf(),
+                    Err(err) => return Err(SandboxError::ChrootFailed(err)),
+                }
             }
-        }
-        let jail_dir = PathBuf::from(OsStr::from_bytes(&prefix[..prefix.len() - 1]));
+        };
         let jail = ChrootJail {
             directory: jail_dir,
         };
 
-        let dest = CString::new(jail.directory
-                                    .as_os_str()
-                                    .to_str()
-                                    .unwrap()
-                                    .as_bytes()).unwrap();
+        let dest = CString::new(jail.directory.as_os_str().as_bytes()).map_err(|err| {
+            SandboxError::NamespaceFailed(io::Error::from(err))
+        })?;
         let tmpfs = CString::new("tmpfs").unwrap();
-        let result = unsafe {
+        let data = tmpfs_size.map(|size| CString::new(format!("size={}", size)).unwrap());
+        // Only a process with a mount namespace of its own may mount here.
+        let result = timings.time(Stage::TmpfsMount, || unsafe {
             libc::mount(tmpfs.as_ptr(),
                         dest.as_ptr(),
                         tmpfs.as_ptr(),
                         libc::MS_NOATIME | libc::MS_NODEV | libc::MS_NOEXEC | libc::MS_NOSUID,
-                        ptr::null())
-        };
+                        data.as_ref().map_or(ptr::null(), |data| data.as_ptr() as *const c_void))
+        });
         if result != 0 {
-            return Err(result)
+            return Err(SandboxError::NamespaceFailed(io::Error::last_os_error()))
         }
 
-        for operation in profile.allowed_operations().iter() {
-            match *operation {
-                Operation::FileReadAll(PathPattern::Literal(ref path)) |
-                Operation::FileReadAll(PathPattern::Subpath(ref path)) => {
-                    try!(jail.bind_mount(path));
+        let bind_mount = |path: &Path, flags| {
+            jail.bind_mount(path, flags).map_err(|error| {
+                SandboxError::MountFailed {
+                    path: path.to_path_buf(),
+                    error,
+                }
+            })
+        };
+        let hardening = hardening_flags(mount_options);
+        let mount_proc_operation = Operation::PlatformSpecific(PlatformOperation::MountProc);
+        let mount_proc = profile.allowed_operations().contains(&mount_proc_operation);
+        timings.time(Stage::BindMounts, || {
+            for mount in bind_mounts(profile, glob_matches) {
+                let mut flags = hardening;
+                if !mount.writable {
+                    flags |= libc::MS_RDONLY
+                }
+                if mount.executable {
+                    flags &= !libc::MS_NOEXEC
                 }
-                _ => {}
+                bind_mount(mount.path, flags)?;
+            }
+            for path in exec_dependencies(profile, glob_matches) {
+                bind_mount(&path, hardening & !libc::MS_NOEXEC | libc::MS_RDONLY)?;
+            }
+            // A `/proc` of the jail's own already has them.
+            if profile.allowed_operations().contains(&Operation::SystemInfoRead) && !mount_proc {
+                for path in SYSTEM_INFO_PATHS.iter() {
+                    bind_mount(Path::new(path), hardening | libc::MS_RDONLY)?;
+                }
+            }
+            // Not even the owner of a user namespace may create device nodes, so bind the host's.
+            if profile.allowed_operations().contains(&Operation::StandardDevices) {
+                for path in STANDARD_DEVICES.iter() {
+                    if Path::new(path).exists() {
+                        bind_mount(Path::new(path), hardening & !libc::MS_NODEV)?
+                    } else {
+                        warn!("{} doesn't exist, so it is left out of the jail", path)
+                    }
+                }
+            }
+            if let Some(core_dump_directory) = core_dump_directory {
+                bind_mount(core_dump_directory, hardening)?;
+            }
+            if mount_proc {
+                jail.mount_proc().map_err(|error| {
+                    SandboxError::MountFailed {
+                        path: PathBuf::from("/proc"),
+                        error,
+                    }
+                })?;
             }
-        }
 
+            // Now that the mount points exist, nothing else in the jail should be writable.
+            remount(&dest, libc::MS_RDONLY | libc::MS_NOATIME | libc::MS_NODEV | libc::MS_NOEXEC |
+                    libc::MS_NOSUID).map_err(SandboxError::ChrootFailed)
+        })?;
         Ok(jail)
     }
 
-    /// Enters the `chroot` jail.
-    fn enter(&self) -> Result<(),c_int> {
-        let directory = CString::new(self.directory
-                                         .as_os_str()
-                                         .to_str()
-                                         .unwrap()
-                                         .as_bytes()).unwrap();
+    /// Enters the jail with `pivot_root()` and detaches the old root, so that the rest of the
+    /// filesystem is gone from this process's mount namespace instead of just out of reach, as it
+    /// would be with `chroot()`.
+    ///
+    /// Once the root has been pivoted there is no way back, so if the old root can't be detached,
+    /// the process aborts rather than go on with the host's filesystem still mounted.
+    fn enter(&self) -> Result<(),SandboxError> {
+        // `pivot_root()` refuses to move mounts that propagate to or from other namespaces.
+        let root = CString::new("/").unwrap();
         let result = unsafe {
-            libc::chroot(directory.as_ptr())
+            libc::mount(ptr::null(),
+                        root.as_ptr(),
+                        ptr::null(),
+                        libc::MS_REC | libc::MS_PRIVATE,
+                        ptr::null())
         };
         if result != 0 {
-            return Err(result)
+            return Err(SandboxError::ChrootFailed(io::Error::last_os_error()))
         }
 
-        match env::set_current_dir(&Path::new(".")) {
-            Ok(_) => Ok(()),
-            Err(_) => Err(-1),
+        // Pivoting onto the current directory stacks the old root on top of the jail, where it
+        // can be detached without a directory in the jail to hold it.
+        env::set_current_dir(&self.directory).map_err(SandboxError::ChrootFailed)?;
+        let here = CString::new(".").unwrap();
+        let result = unsafe {
+            libc::syscall(libc::SYS_pivot_root, here.as_ptr(), here.as_ptr())
+        };
+        if result != 0 {
+            return Err(SandboxError::ChrootFailed(io::Error::last_os_error()))
+        }
+        if unsafe { libc::umount2(here.as_ptr(), libc::MNT_DETACH) } != 0 {
+            error!("couldn't detach the old root from the jail: {}", io::Error::last_os_error());
+            process::abort()
+        }
+        if let Err(err) = env::set_current_dir(Path::new("/")) {
+            error!("couldn't enter the jail after detaching the old root: {}", err);
+            process::abort()
         }
+        Ok(())
     }
 
-    /// Bind mounts a path into our chroot jail.
-    fn bind_mount(&self, source_path: &Path) -> Result<(),c_int> {
-        // Create all intermediate directories.
-        let mut destination_path = self.directory.clone();
-        let mut components: Vec<OsString> =
-            source_path.components().skip(1)
-                                    .map(|component| component.as_os_str().to_os_string())
-                                    .collect();
-        let last_component = components.pop();
-        for component in components.into_iter() {
-            destination_path.push(component);
-            if fs::create_dir(&destination_path).is_err() {
-                return Err(-1)
+    /// Mounts a new `proc` filesystem read-only at `/proc` in the jail. It shows this process's
+    /// PID namespace, not the host's, so only the processes started in the sandbox appear in it,
+    /// and each is hidden from other users (`hidepid=2`) unless the kernel doesn't know that
+    /// option. The host's `/proc` must be mounted and not partly covered up, or the kernel refuses
+    /// to mount another in a user namespace.
+    fn mount_proc(&self) -> io::Result<()> {
+        let destination_path = self.directory.join("proc");
+        match fs::create_dir(&destination_path) {
+            Ok(()) => {}
+            Err(ref err) if err.kind() == io::ErrorKind::AlreadyExists => {}
+            Err(err) => return Err(err),
+        }
+
+        let destination = CString::new(destination_path.as_os_str().as_bytes())?;
+        let proc_fs = CString::new("proc").unwrap();
+        let mount = |options: Option<&CString>| unsafe {
+            libc::mount(proc_fs.as_ptr(),
+                        destination.as_ptr(),
+                        proc_fs.as_ptr(),
+                        libc::MS_RDONLY | libc::MS_NODEV | libc::MS_NOEXEC | libc::MS_NOSUID,
+                        options.map_or(ptr::null(), |options| options.as_ptr() as *const c_void))
+        };
+        let hidepid = CString::new("hidepid=2").unwrap();
+        if mount(Some(&hidepid)) == 0 {
+            return Ok(())
+        }
+        let err = io::Error::last_os_error();
+        if err.raw_os_error() != Some(libc::EINVAL) {
+            return Err(err)
+        }
+        if mount(None) != 0 {
+            return Err(io::Error::last_os_error())
+        }
+        Ok(())
+    }
+
+    /// Bind mounts a path into our chroot jail, and remounts it with `flags`, which may include
+    /// `MS_RDONLY`, `MS_NOSUID`, `MS_NODEV`, and `MS_NOEXEC`.
+    ///
+    /// The mount is made where the symbolic links in the path lead, and the links are recreated
+    /// in the jail, so that paths resolve there as they do on the host and `readlink()` returns
+    /// the same.
+    fn bind_mount(&self, source_path: &Path, flags: c_ulong) -> io::Result<()> {
+        let (source_path, links) = match resolve_links(source_path) {
+            Ok(resolved) => resolved,
+            // The source didn't exist. Just don't create the bind mount.
+            Err(_) => return Ok(()),
+        };
+        let source_path = &*source_path;
+        for (link_path, target) in links {
+            let destination_path = self.create_parent_directories(&link_path)?;
+            // The host's link already shows through an enclosing bind mount.
+            if fs::symlink_metadata(&destination_path).is_err() {
+                symlink(&target, &destination_path)?
             }
         }
 
         // Create the mount file or directory.
-        if let Some(last_component) = last_component {
-            destination_path.push(last_component);
+        let destination_path = self.create_parent_directories(source_path)?;
+        if destination_path != self.directory {
             match fs::metadata(source_path) {
-                Ok(ref metadata) if metadata.is_dir() => {
-                    if fs::create_dir(&destination_path).is_err() {
-                        return Err(-1)
-                    }
-                }
-                Ok(_) => {
-                    if File::create(&destination_path).is_err() {
-                        return Err(-1)
-                    }
-                }
+                // An enclosing bind mount already provides it.
+                Ok(_) if fs::symlink_metadata(&destination_path).is_ok() => {}
+                Ok(ref metadata) if metadata.is_dir() => fs::create_dir(&destination_path)?,
+                Ok(_) => drop(File::create(&destination_path)?),
                 Err(_) => {
                     // The source directory didn't exist. Just don't create the bind mount.
                     return Ok(())
@@ -142,38 +294,338 @@ impl ChrootJail {
         }
 
         // Create the bind mount.
-        let source_path = CString::new(source_path.as_os_str()
-                                                  .to_str()
-                                                  .unwrap()
-                                                  .as_bytes()).unwrap();
-        let destination_path = CString::new(destination_path.as_os_str()
-                                                            .to_str()
-                                                            .unwrap()
-                                                            .as_bytes()).unwrap();
+        let source_path = CString::new(source_path.as_os_str().as_bytes())?;
+        let destination = CString::new(destination_path.as_os_str().as_bytes())?;
         let bind = CString::new("bind").unwrap();
         let result = unsafe {
             libc::mount(source_path.as_ptr(),
-                  destination_path.as_ptr(),
+                  destination.as_ptr(),
                   bind.as_ptr(),
                   libc::MS_MGC_VAL | libc::MS_BIND | libc::MS_REC,
                   ptr::null_mut())
         };
-        if result == 0 {
-            Ok(())
-        } else {
-            Err(result)
+        if result != 0 {
+            return Err(io::Error::last_os_error())
+        }
+        if flags == 0 {
+            return Ok(())
+        }
+        remount_tree(&destination_path, flags)
+    }
+
+    /// Creates the directories in the jail that lead to `path`, and returns where `path` is in
+    /// the jail.
+    fn create_parent_directories(&self, path: &Path) -> io::Result<PathBuf> {
+        let mut destination_path = self.directory.clone();
+        let mut components: Vec<OsString> =
+            path.components().skip(1)
+                             .map(|component| component.as_os_str().to_os_string())
+                             .collect();
+        let last_component = components.pop();
+        for component in components.into_iter() {
+            destination_path.push(component);
+            // Another bind mount may have created it already.
+            match fs::create_dir(&destination_path) {
+                Ok(()) => {}
+                Err(ref err) if err.kind() == io::ErrorKind::AlreadyExists => {}
+                Err(err) => return Err(err),
+            }
+        }
+        if let Some(last_component) = last_component {
+            destination_path.push(last_component)
+        }
+        Ok(destination_path)
+    }
+}
+
+/// The number of symbolic links `resolve_links()` follows before giving up, as Linux does.
+const MAX_SYMLINKS: usize = 40;
+
+/// Resolves the symbolic links in the absolute path `path`, returning the path they lead to and
+/// each link followed on the way, with its contents as they are.
+///
+/// This is `realpath()`, except that it says how it got there.
+fn resolve_links(path: &Path) -> io::Result<(PathBuf, Vec<(PathBuf, PathBuf)>)> {
+    let mut resolved = PathBuf::from("/");
+    let mut links = Vec::new();
+    let mut remaining: Vec<OsString> =
+        path.components().rev().map(|component| component.as_os_str().to_os_string()).collect();
+    while let Some(component) = remaining.pop() {
+        if component == *"/" {
+            resolved = PathBuf::from("/");
+            continue
+        }
+        if component == *"." {
+            continue
+        }
+        if component == *".." {
+            resolved.pop();
+            continue
+        }
+        let candidate = resolved.join(&component);
+        if !fs::symlink_metadata(&candidate)?.file_type().is_symlink() {
+            resolved = candidate;
+            continue
+        }
+        if links.len() == MAX_SYMLINKS {
+            return Err(io::Error::from_raw_os_error(libc::ELOOP))
+        }
+        let target = fs::read_link(&candidate)?;
+        remaining.extend(target.components()
+                               .rev()
+                               .map(|component| component.as_os_str().to_os_string()));
+        links.push((candidate, target));
+    }
+    Ok((resolved, links))
+}
+
+/// Returns the flags to remount bind mounts in the jail with for `mount_options`.
+fn hardening_flags(mount_options: MountOptions) -> c_ulong {
+    let mut flags = 0;
+    if mount_options.noexec {
+        flags |= libc::MS_NOEXEC
+    }
+    if mount_options.nosuid {
+        flags |= libc::MS_NOSUID
+    }
+    if mount_options.nodev {
+        flags |= libc::MS_NODEV
+    }
+    flags
+}
+
+/// Remounts the bind mount at `path` with `flags`, along with every mount under it, which the
+/// recursive bind brought along but a remount doesn't reach.
+///
+/// In a user namespace, a remount must keep the flags the original mount was locked with, so each
+/// keeps those of `ro`, `nosuid`, `nodev`, `noexec`, and the access time flags that
+/// `/proc/self/mountinfo` lists for it.
+fn remount_tree(path: &Path, flags: c_ulong) -> io::Result<()> {
+    // Mount points are whatever bytes their paths are, so this needn't be UTF-8.
+    let mut mountinfo = Vec::new();
+    File::open("/proc/self/mountinfo")?.read_to_end(&mut mountinfo)?;
+    for line in mountinfo.split(|&byte| byte == b'\n') {
+        // The fifth field is the mount point and the sixth its options.
+        let mut fields = line.split(|&byte| byte == b' ').skip(4);
+        let (mount_point, options) = match (fields.next(), fields.next()) {
+            (Some(mount_point), Some(options)) => (unescape_mount_point(mount_point), options),
+            _ => continue,
+        };
+        if !Path::new(OsStr::from_bytes(&mount_point)).starts_with(path) {
+            continue
+        }
+        let locked_flags = options.split(|&byte| byte == b',').fold(0, |locked_flags, option| {
+            locked_flags | match option {
+                b"ro" => libc::MS_RDONLY,
+                b"nosuid" => libc::MS_NOSUID,
+                b"nodev" => libc::MS_NODEV,
+                b"noexec" => libc::MS_NOEXEC,
+                b"noatime" => libc::MS_NOATIME,
+                b"nodiratime" => libc::MS_NODIRATIME,
+                b"relatime" => libc::MS_RELATIME,
+                _ => 0,
+            }
+        });
+        remount(&CString::new(mount_point)?, libc::MS_BIND | locked_flags | flags)?
+    }
+    Ok(())
+}
+
+/// Undoes the octal escapes `/proc/self/mountinfo` writes for spaces, tabs, newlines, and
+/// backslashes in mount points.
+fn unescape_mount_point(bytes: &[u8]) -> Vec<u8> {
+    let mut unescaped = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        let escape = bytes.get(index + 1..index + 4).filter(|digits| {
+            bytes[index] == b'\\' && (b'0'..=b'3').contains(&digits[0]) &&
+                digits[1..].iter().all(|digit| (b'0'..=b'7').contains(digit))
+        });
+        match escape {
+            Some(digits) => {
+                unescaped.push(digits.iter().fold(0, |byte, digit| byte * 8 + (digit - b'0')));
+                index += 4
+            }
+            None => {
+                unescaped.push(bytes[index]);
+                index += 1
+            }
+        }
+    }
+    unescaped
+}
+
+/// A path to bind into the jail.
+struct BindMount<'a> {
+    path: &'a Path,
+    writable: bool,
+    /// Whether programs may be run from it, so that it mustn't be mounted `noexec`.
+    executable: bool,
+}
+
+/// Returns the paths to bind into the jail for `profile`, in the order to bind them. Globs stand
+/// for the paths among `glob_matches` that they match.
+///
+/// Enclosing paths come first, so that the mounts for the paths inside them aren't hidden. A path
+/// is writable if any operation that allows writing or appending to it or its metadata covers it,
+/// which makes overlapping operations allow the union of what they allow. Programs that may be run
+/// are bound like files that may be read, but executable.
+fn bind_mounts<'a>(profile: &'a Profile, glob_matches: &'a [PathBuf]) -> Vec<BindMount<'a>> {
+    let operations = profile.allowed_operations();
+    let writable_patterns: Vec<&PathPattern> = operations.iter().filter_map(|operation| {
+        match *operation {
+            Operation::FileWriteAll(ref pattern) |
+            Operation::FileWriteMetadata(ref pattern) |
+            Operation::FileAppend(ref pattern) => Some(pattern),
+            _ => None,
+        }
+    }).collect();
+    let mut bind_mounts: Vec<BindMount> = Vec::new();
+    for operation in operations.iter() {
+        let executable = matches!(*operation, Operation::ProcessExec(_));
+        let pattern = match *operation {
+            Operation::FileReadAll(ref pattern) |
+            Operation::FileWriteAll(ref pattern) |
+            Operation::FileWriteMetadata(ref pattern) |
+            Operation::FileAppend(ref pattern) |
+            Operation::ProcessExec(ref pattern) => pattern,
+            _ => continue,
+        };
+        let paths: Vec<&Path> = match *pattern {
+            PathPattern::Literal(ref path) | PathPattern::Subpath(ref path) => vec![&**path],
+            PathPattern::Glob(_) => {
+                glob_matches.iter().filter(|path| pattern.matches(path)).map(|path| &**path)
+                                                                          .collect()
+            }
+        };
+        for path in paths {
+            let writable = writable_patterns.iter().any(|writable_pattern| {
+                match *pattern {
+                    PathPattern::Glob(_) => writable_pattern.matches(path),
+                    _ => writable_pattern.contains(pattern),
+                }
+            });
+            match bind_mounts.iter_mut().find(|bind_mount| bind_mount.path == path) {
+                Some(bind_mount) => {
+                    bind_mount.writable |= writable;
+                    bind_mount.executable |= executable
+                }
+                None => {
+                    bind_mounts.push(BindMount {
+                        path,
+                        writable,
+                        executable,
+                    })
+                }
+            }
+        }
+    }
+    bind_mounts.sort_by_key(|bind_mount| bind_mount.path.components().count());
+    bind_mounts
+}
+
+/// Returns the dynamic loaders and shared libraries that the programs `profile` allows running
+/// need, to bind read-only into the jail after everything else. Globs stand for the paths among
+/// `glob_matches` that they match. The programs under a `Subpath` aren't looked into, so their
+/// libraries must be allowed some other way.
+fn exec_dependencies(profile: &Profile, glob_matches: &[PathBuf]) -> Vec<PathBuf> {
+    let mut dependencies: Vec<PathBuf> = Vec::new();
+    for operation in profile.allowed_operations() {
+        let programs: Vec<&Path> = match *operation {
+            Operation::ProcessExec(PathPattern::Literal(ref path)) => vec![&**path],
+            Operation::ProcessExec(ref pattern @ PathPattern::Glob(_)) => {
+                glob_matches.iter().filter(|path| pattern.matches(path)).map(|path| &**path)
+                                                                          .collect()
+            }
+            _ => continue,
+        };
+        for program in programs {
+            for dependency in elf::dependencies(program) {
+                if !dependencies.contains(&dependency) {
+                    dependencies.push(dependency)
+                }
+            }
         }
     }
+    dependencies
+}
+
+/// Remounts the mount at `path` with `flags`.
+fn remount(path: &CString, flags: c_ulong) -> io::Result<()> {
+    let result = unsafe {
+        libc::mount(ptr::null(),
+                    path.as_ptr(),
+                    ptr::null(),
+                    libc::MS_REMOUNT | flags,
+                    ptr::null())
+    };
+    if result == 0 {
+        Ok(())
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
+
+/// Brings up the loopback interface, which a new network namespace starts with but leaves down,
+/// so that `127.0.0.1` and `::1` work in it. This takes `CAP_NET_ADMIN` over the namespace.
+pub(crate) fn setup_loopback() -> io::Result<()> {
+    unsafe {
+        let fd = libc::socket(libc::AF_INET, libc::SOCK_DGRAM | libc::SOCK_CLOEXEC, 0);
+        if fd < 0 {
+            return Err(io::Error::last_os_error())
+        }
+        let mut request: libc::ifreq = mem::zeroed();
+        for (dest, &src) in request.ifr_name.iter_mut().zip(b"lo".iter()) {
+            *dest = src as c_char
+        }
+        request.ifr_ifru.ifru_flags = (libc::IFF_UP | libc::IFF_LOOPBACK) as libc::c_short;
+        let result = libc::ioctl(fd, libc::SIOCSIFFLAGS, &request);
+        let error = io::Error::last_os_error();
+        libc::close(fd);
+        if result != 0 {
+            return Err(error)
+        }
+    }
+    Ok(())
+}
+
+/// Keeps `CAP_SYS_ADMIN` across `execve()`, which a process that isn't root in its user
+/// namespace would otherwise lose it at, by making it an ambient capability. This takes it
+/// being inheritable too. `drop_capabilities()` drops it along with the rest.
+unsafe fn keep_mount_capability() -> io::Result<()> {
+    const CAP_SYS_ADMIN: u32 = 21;
+    let header = __user_cap_header_struct {
+        version: _LINUX_CAPABILITY_VERSION_3,
+        pid: 0,
+    };
+    let mut capability_data = [__user_cap_data_struct {
+        effective: 0,
+        permitted: 0,
+        inheritable: 0,
+    }; _LINUX_CAPABILITY_U32S_3 as usize];
+    if capget(&header, capability_data.as_mut_ptr()) != 0 {
+        return Err(io::Error::last_os_error())
+    }
+    capability_data[0].inheritable |= 1 << CAP_SYS_ADMIN;
+    if capset(&header, capability_data.as_ptr()) != 0 ||
+            libc::prctl(libc::PR_CAP_AMBIENT,
+                        libc::PR_CAP_AMBIENT_RAISE,
+                        CAP_SYS_ADMIN as c_ulong,
+                        0,
+                        0) != 0 {
+        return Err(io::Error::last_os_error())
+    }
+    Ok(())
 }
 
 /// Removes fake-superuser capabilities. This removes our ability to mess with the filesystem view
 /// we've set up.
-fn drop_capabilities() -> Result<(),c_int> {
-    let capability_data: Vec<_> = iter::repeat(__user_cap_data_struct {
+fn drop_capabilities() -> Result<(),SandboxError> {
+    let capability_data = vec![__user_cap_data_struct {
         effective: 0,
         permitted: 0,
         inheritable: 0,
-    }).take(_LINUX_CAPABILITY_U32S_3 as usize).collect();
+    }; _LINUX_CAPABILITY_U32S_3 as usize];
     let result = unsafe {
         capset(&__user_cap_header_struct {
             version: _LINUX_CAPABILITY_VERSION_3,
@@ -183,70 +635,219 @@ fn drop_capabilities() -> Result<(),c_int> {
     if result == 0 {
         Ok(())
     } else {
-        Err(result)
+        Err(SandboxError::CapabilityDropFailed(io::Error::last_os_error()))
     }
 }
 
-/// Sets up the user and PID namespaces.
-unsafe fn prepare_user_and_pid_namespaces(parent_uid: uid_t, parent_gid: gid_t) -> io::Result<()> {
-    // Enter the main user and PID namespaces.
-    assert!(libc::unshare(libc::CLONE_NEWUSER | libc::CLONE_NEWPID) == 0);
+/// Sets up the user and PID namespaces, timing each step in `timings`.
+unsafe fn prepare_user_and_pid_namespaces(parent_uid: uid_t,
+                                          parent_gid: gid_t,
+                                          user_mapping: UserMapping,
+                                          isolate_time: bool,
+                                          timings: &mut StageTimings)
+                                          -> io::Result<()> {
+    // Enter the main user and PID namespaces, and the time namespace if asked to. Like the PID
+    // namespace, that only applies to our children.
+    let mut flags = libc::CLONE_NEWUSER | libc::CLONE_NEWPID;
+    if isolate_time {
+        flags |= libc::CLONE_NEWTIME
+    }
+    timings.time(Stage::Unshare, || {
+        assert!(libc::unshare(flags) == 0)
+    });
+    if isolate_time {
+        reset_clocks_for_children()?
+    }
 
-    // See http://crbug.com/457362 for more information on this.
-    try!(try!(File::create(&Path::new("/proc/self/setgroups"))).write_all(b"deny"));
+    timings.time(Stage::IdMaps, || {
+        // See http://crbug.com/457362 for more information on this.
+        File::create(Path::new("/proc/self/setgroups"))?.write_all(b"deny")?;
 
-    let gid_contents = format!("0 {} 1", parent_gid);
-    try!(try!(File::create(&Path::new("/proc/self/gid_map"))).write_all(gid_contents.as_bytes()));
-    let uid_contents = format!("0 {} 1", parent_uid);
-    try!(try!(File::create(&Path::new("/proc/self/uid_map"))).write_all(uid_contents.as_bytes()));
-    Ok(())
+        let (uid, gid) = match user_mapping {
+            UserMapping::Root => (0, 0),
+            UserMapping::Preserve => (parent_uid, parent_gid),
+        };
+        let gid_contents = format!("{} {} 1", gid, parent_gid);
+        File::create(Path::new("/proc/self/gid_map"))?.write_all(gid_contents.as_bytes())?;
+        let uid_contents = format!("{} {} 1", uid, parent_uid);
+        File::create(Path::new("/proc/self/uid_map"))?.write_all(uid_contents.as_bytes())
+    })
+}
+
+/// Offsets the monotonic and boot-time clocks of the time namespace our children will enter, so
+/// that they read zero now. This has to happen before any process enters the namespace.
+unsafe fn reset_clocks_for_children() -> io::Result<()> {
+    let mut offsets = String::new();
+    let clocks = [("monotonic", libc::CLOCK_MONOTONIC), ("boottime", libc::CLOCK_BOOTTIME)];
+    for &(name, clock) in clocks.iter() {
+        let mut now: libc::timespec = mem::zeroed();
+        assert!(libc::clock_gettime(clock, &mut now) == 0);
+        // The kernel wants the nanoseconds of the negated time between 0 and a second.
+        let (seconds, nanoseconds) = if now.tv_nsec == 0 {
+            (-now.tv_sec, 0)
+        } else {
+            (-now.tv_sec - 1, 1_000_000_000 - now.tv_nsec)
+        };
+        offsets.push_str(&format!("{} {} {}\n", name, seconds, nanoseconds));
+    }
+    File::create(Path::new("/proc/self/timens_offsets"))?.write_all(offsets.as_bytes())
+}
+
+/// Sends `timings` through the descriptor named by `TIMINGS_FD_ENV_VAR`. Each sender's timings
+/// are terminated with a comma, so that the parent can read them all as one list.
+pub(crate) fn send_timings(fd: c_int, timings: &StageTimings) {
+    let mut encoded = timings.encode();
+    encoded.push(',');
+    unsafe {
+        libc::write(fd, encoded.as_ptr() as *const c_void, encoded.len() as size_t);
+    }
+}
+
+/// How `start()` sets up the child's namespaces, beyond those every child gets.
+#[derive(Clone, Copy, Debug)]
+pub struct NamespaceOptions<'a> {
+    /// Whether the child gets a cgroup namespace of its own.
+    pub isolate_cgroups: bool,
+    /// Whether the child gets a time namespace of its own.
+    pub isolate_time: bool,
+    /// The IDs the child has in its user namespace.
+    pub user_mapping: UserMapping,
+    /// The hostname the child sees in its UTS namespace, if not the host's. It must be at most
+    /// `HOST_NAME_MAX` bytes long.
+    pub hostname: Option<&'a str>,
 }
 
-/// Spawns a child process in a new namespace.
+/// Spawns a child process in a new namespace, using the namespaces among `mechanisms`. The child
+/// joins `cgroup` if given one, and gets the cgroup and time namespaces, the IDs, and the
+/// hostname that `namespaces` asks for. If `configure_network` is given, the child waits after
+/// entering its network namespace until that has been called with its PID, and is killed if it
+/// fails.
+///
+/// If `report_timings` is true, this waits until the child has activated its sandbox (or exited)
+/// and fills in the timings of the stages of starting and activating it.
 ///
 /// This function is quite tricky. Hic sunt dracones!
-pub fn start(profile: &Profile, command: &mut Command) -> io::Result<Process> {
+pub fn start(mechanisms: &[Mechanism],
+             command: &mut Command,
+             cgroup: Option<&Cgroup>,
+             namespaces: NamespaceOptions<'_>,
+             configure_network: Option<&dyn Fn(pid_t) -> io::Result<()>>,
+             report_timings: bool)
+             -> io::Result<Process> {
     // Store our root namespace UID and GID because they're going to change once we enter a user
     // namespace.
     let (parent_uid, parent_gid) = unsafe {
         (libc::getuid(), libc::getgid())
     };
 
-    // Always create an IPC namespace, a mount namespace, and a UTS namespace. Additionally, if we
-    // aren't allowing network operations, create a network namespace.
+    // Always create an IPC namespace, a mount namespace, and a UTS namespace. Additionally, create
+    // a network namespace unless the profile allows network operations or it was disabled.
     let mut unshare_flags = libc::CLONE_NEWIPC | libc::CLONE_NEWNS | libc::CLONE_NEWUTS;
-    if !profile.allowed_operations().iter().any(|operation| {
-        match *operation {
-            Operation::NetworkOutbound(_) => true,
-            _ => false,
-        }
-    }) {
+    if mechanisms.contains(&Mechanism::NetworkNamespace) {
         unshare_flags |= libc::CLONE_NEWNET
     }
+    // In a cgroup namespace, the child sees the cgroup it is in, its own if it was given one, as
+    // the root of the hierarchy, instead of the host's layout.
+    if namespaces.isolate_cgroups {
+        unshare_flags |= libc::CLONE_NEWCGROUP
+    }
 
     unsafe {
         // Create a pipe so we can communicate the PID of our grandchild back.
         let mut pipe_fds = [0, 0];
         assert!(libc::pipe(&mut pipe_fds[0]) == 0);
 
+        // Create another one for the timings, close-on-exec so that children other threads
+        // start meanwhile don't hold it open. Our grandchild inherits the writing end.
+        let mut timings_fds = [-1, -1];
+        if report_timings {
+            assert!(libc::pipe2(&mut timings_fds[0], libc::O_CLOEXEC) == 0);
+        }
+        let mut timings = StageTimings::default();
+
+        // Create a socket pair for our grandchild to tell us it has entered its network namespace
+        // through, and for us to tell it that the namespace has been configured.
+        let mut network_fds = [-1, -1];
+        if configure_network.is_some() {
+            assert!(libc::socketpair(libc::AF_UNIX,
+                                     libc::SOCK_STREAM | libc::SOCK_CLOEXEC,
+                                     0,
+                                     network_fds.as_mut_ptr()) == 0);
+        }
+
         // Set this `prctl` flag so that we can wait on our grandchild. (Otherwise it'll be
         // reparented to init.)
         assert!(libc::prctl(libc::PR_SET_CHILD_SUBREAPER, 1, 0, 0, 0) == 0);
 
         // Fork so that we can unshare without removing our ability to create threads.
-        if libc::fork() == 0 {
+        let child_pid = libc::fork();
+        if child_pid == 0 {
             // Close the reading end of the pipe.
             libc::close(pipe_fds[0]);
 
+            // Join the cgroup before anything else starts, so that nothing escapes its limits.
+            // If that fails, send the negated error number up in place of a PID.
+            if let Some(cgroup) = cgroup {
+                if libc::write(cgroup.as_raw_fd(), b"0".as_ptr() as *const c_void, 1) != 1 {
+                    let errno: pid_t = -io::Error::last_os_error().raw_os_error().unwrap_or(0);
+                    libc::write(pipe_fds[1],
+                                &errno as *const pid_t as *const c_void,
+                                mem::size_of::<pid_t>() as size_t);
+                    libc::_exit(1)
+                }
+            }
+
             // Set up our user and PID namespaces. The PID namespace won't actually come into
             // effect until the next fork(), because PIDs are immutable.
-            prepare_user_and_pid_namespaces(parent_uid, parent_gid).unwrap();
+            prepare_user_and_pid_namespaces(parent_uid,
+                                            parent_gid,
+                                            namespaces.user_mapping,
+                                            namespaces.isolate_time,
+                                            &mut timings).unwrap();
 
             // Fork again, to enter the PID namespace.
             match libc::fork() {
                 0 => {
                     // Enter the auxiliary namespaces.
-                    assert!(libc::unshare(unshare_flags) == 0);
+                    timings.time(Stage::AuxiliaryNamespaces, || {
+                        assert!(libc::unshare(unshare_flags) == 0)
+                    });
+
+                    // Nothing outside can reach a new network namespace, but its loopback
+                    // interface still works for servers the command starts itself.
+                    if unshare_flags & libc::CLONE_NEWNET != 0 {
+                        setup_loopback().unwrap()
+                    }
+
+                    // We still have `CAP_SYS_ADMIN` over the UTS namespace we just made.
+                    if let Some(hostname) = namespaces.hostname {
+                        assert!(libc::sethostname(hostname.as_ptr() as *const c_char,
+                                                  hostname.len()) == 0)
+                    }
+
+                    // Wait for our grandparent to configure the network namespace.
+                    if configure_network.is_some() {
+                        let mut byte = 0u8;
+                        let byte_ptr = &mut byte as *mut u8 as *mut c_void;
+                        if libc::write(network_fds[1], byte_ptr, 1) != 1 ||
+                                libc::read(network_fds[1], byte_ptr, 1) != 1 {
+                            libc::_exit(1)
+                        }
+                        libc::close(network_fds[1]);
+                    }
+
+                    // Outside of root, the command would lose the capability to build its jail.
+                    if namespaces.user_mapping == UserMapping::Preserve {
+                        keep_mount_capability().unwrap()
+                    }
+
+                    // Send the timings so far, and let the command inherit the descriptor to
+                    // send its own.
+                    if report_timings {
+                        send_timings(timings_fds[1], &timings);
+                        assert!(libc::fcntl(timings_fds[1], libc::F_SETFD, 0) == 0);
+                        command.env(TIMINGS_FD_ENV_VAR, timings_fds[1].to_string());
+                    }
 
                     // Go ahead and start the command.
                     drop(unix::process::exec(command));
@@ -272,8 +873,74 @@ pub fn start(profile: &Profile, command: &mut Command) -> io::Result<Process> {
                            &mut grandchild_pid as *mut i32 as *mut c_void,
                            mem::size_of::<pid_t>() as size_t) ==
                 mem::size_of::<pid_t>() as ssize_t);
+        libc::close(pipe_fds[0]);
+        if grandchild_pid < 0 {
+            while libc::waitpid(child_pid, ptr::null_mut(), 0) < 0 &&
+                    io::Error::last_os_error().kind() == io::ErrorKind::Interrupted {}
+            if report_timings {
+                libc::close(timings_fds[0]);
+                libc::close(timings_fds[1]);
+            }
+            if configure_network.is_some() {
+                libc::close(network_fds[0]);
+                libc::close(network_fds[1]);
+            }
+            return Err(io::Error::from_raw_os_error(-grandchild_pid))
+        }
+
+        // Reap our child, which exits right after reporting the PID. Once it has, our grandchild
+        // has been reparented to us, and `Process::wait()` can wait for it alone.
+        while libc::waitpid(child_pid, ptr::null_mut(), 0) < 0 &&
+                io::Error::last_os_error().kind() == io::ErrorKind::Interrupted {}
+
+        // Configure our grandchild's network namespace once it is in it, and let it go on.
+        if let Some(configure_network) = configure_network {
+            libc::close(network_fds[1]);
+            let mut byte = 0u8;
+            let byte_ptr = &mut byte as *mut u8 as *mut c_void;
+            let result = if libc::read(network_fds[0], byte_ptr, 1) != 1 {
+                Err(io::Error::other("the child exited before entering its network namespace"))
+            } else {
+                configure_network(grandchild_pid)
+            };
+            let result = result.and_then(|()| {
+                if libc::write(network_fds[0], byte_ptr, 1) != 1 {
+                    Err(io::Error::last_os_error())
+                } else {
+                    Ok(())
+                }
+            });
+            libc::close(network_fds[0]);
+            if let Err(err) = result {
+                libc::kill(grandchild_pid, libc::SIGKILL);
+                while libc::waitpid(grandchild_pid, ptr::null_mut(), 0) < 0 &&
+                        io::Error::last_os_error().kind() == io::ErrorKind::Interrupted {}
+                if report_timings {
+                    libc::close(timings_fds[0]);
+                    libc::close(timings_fds[1]);
+                }
+                return Err(err)
+            }
+        }
+
+        // Wait for our grandchild to close its end of the timings pipe, which it does once it
+        // has activated its sandbox or exited.
+        if report_timings {
+            libc::close(timings_fds[1]);
+            let mut encoded = String::new();
+            drop(File::from_raw_fd(timings_fds[0]).read_to_string(&mut encoded));
+            timings = StageTimings::decode(&encoded);
+        }
+
         Ok(Process {
             pid: grandchild_pid,
+            events: EventSink::default(),
+            telemetry: TelemetrySink::default(),
+            timings,
+            mechanisms: Vec::new(),
+            jail: None,
+            cgroup: None,
+            deadline: None,
         })
     }
 }
@@ -299,8 +966,12 @@ type cap_user_header_t = *const __user_cap_header_struct;
 #[allow(non_camel_case_types)]
 type const_cap_user_data_t = *const __user_cap_data_struct;
 
+#[allow(non_camel_case_types)]
+type cap_user_data_t = *mut __user_cap_data_struct;
+
 const _LINUX_CAPABILITY_VERSION_3: u32 = 0x20080522;
 const _LINUX_CAPABILITY_U32S_3: u32 = 2;
-extern {
+extern "C" {
+    fn capget(hdrp: cap_user_header_t, datap: cap_user_data_t) -> c_int;
     fn capset(hdrp: cap_user_header_t, datap: const_cap_user_data_t) -> c_int;
 }
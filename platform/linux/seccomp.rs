@@ -16,17 +16,32 @@
 
 #![allow(non_upper_case_globals, unused_imports)]
 
-use profile::{Operation, Profile};
+use crate::error::SandboxError;
+use crate::platform;
+use crate::platform::capabilities::linux::probe_seccomp_action;
+use crate::platform::unix::cache::{self, ProfileCache};
+use crate::profile::{AddressPattern, Operation, Profile, ViolationAction};
 
-use libc::{self, CLONE_CHILD_CLEARTID, CLONE_FILES, CLONE_FS,
+use libc::{self, CLONE_CHILD_CLEARTID, CLONE_CHILD_SETTID, CLONE_FILES, CLONE_FS,
            CLONE_PARENT_SETTID, CLONE_SETTLS, CLONE_SIGHAND, CLONE_SYSVSEM,
-           CLONE_THREAD, CLONE_VM};
-use libc::{AF_INET, AF_INET6, AF_UNIX, AF_NETLINK};
+           CLONE_THREAD, CLONE_VFORK, CLONE_VM, SIGCHLD};
+use libc::{AF_INET, AF_INET6, AF_UNIX, AF_NETLINK, SOCK_CLOEXEC, SOCK_DGRAM, SOCK_NONBLOCK};
+use libc::{SOL_SOCKET, SO_REUSEADDR};
 use libc::{c_char, c_int, c_ulong, c_ushort, c_void};
 use libc::{O_NONBLOCK, O_RDONLY, O_NOCTTY, O_CLOEXEC, FIONREAD, FIOCLEX};
+use libc::{EFD_CLOEXEC, EFD_NONBLOCK, GRND_NONBLOCK};
+use libc::{O_APPEND, O_CREAT, O_RDWR, O_TRUNC, O_WRONLY};
 use libc::{MADV_NORMAL, MADV_RANDOM, MADV_SEQUENTIAL, MADV_WILLNEED, MADV_DONTNEED};
+use log::warn;
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::error::Error;
 use std::ffi::CString;
+use std::fmt;
+use std::io;
 use std::mem;
+use std::sync::{Arc, OnceLock};
 
 /// The architecture number for x86.
 #[cfg(target_arch="x86")]
@@ -46,8 +61,16 @@ const ARCH_NR: u32 = AUDIT_ARCH_PPC;
 const ARCH_NR: u32 = AUDIT_ARCH_PPC64;
 #[cfg(all(target_arch="powerpc64", target_endian="little"))]
 const ARCH_NR: u32 = AUDIT_ARCH_PPC64LE;
+/// The architecture number for RISC-V 64-bit.
+#[cfg(target_arch="riscv64")]
+const ARCH_NR: u32 = AUDIT_ARCH_RISCV64;
 
 const SECCOMP_RET_KILL: u32 = 0;
+const SECCOMP_RET_TRAP: u32 = 0x0003_0000;
+const SECCOMP_RET_ERRNO: u32 = 0x0005_0000;
+const SECCOMP_RET_USER_NOTIF: u32 = 0x7fc0_0000;
+const SECCOMP_RET_TRACE: u32 = 0x7ff0_0000;
+const SECCOMP_RET_LOG: u32 = 0x7ffc_0000;
 const SECCOMP_RET_ALLOW: u32 = 0x7fff_0000;
 
 const LD: u16 = 0x00;
@@ -57,92 +80,398 @@ const RET: u16 = 0x06;
 const W: u16 = 0;
 const ABS: u16 = 0x20;
 
+const JA: u16 = 0x00;
 const JEQ: u16 = 0x10;
+const JGE: u16 = 0x30;
 const JSET: u16 = 0x40;
 
 const K: u16 = 0x00;
 
+/// Masks out the instruction class and the jump operation of an instruction's code.
+const CLASS_MASK: u16 = 0x07;
+const OP_MASK: u16 = 0xf0;
+
+/// The most instructions the kernel accepts in one program, its `BPF_MAXINSNS`.
+pub(crate) const MAX_INSTRUCTIONS: usize = 4096;
+
 const SYSCALL_NR_OFFSET: u32 = 0;
 const ARCH_NR_OFFSET: u32 = 4;
-const ARG_0_OFFSET: u32 = 16;
-const ARG_1_OFFSET: u32 = 24;
-const ARG_2_OFFSET: u32 = 32;
+const ARGS_OFFSET: u32 = 16;
+
+/// Where the low 32 bits of each 64-bit argument are. A filter can only load 32 bits at a time,
+/// so every argument is compared a half at a time.
+#[cfg(target_endian="little")]
+const LOW_HALF_OFFSET: u32 = 0;
+#[cfg(target_endian="big")]
+const LOW_HALF_OFFSET: u32 = 4;
+const HIGH_HALF_OFFSET: u32 = 4 - LOW_HALF_OFFSET;
 
 const NETLINK_ROUTE: c_int = 0;
 
+// Only the numbers of the architecture being built for are used.
+#[cfg(target_arch="x86")]
 const EM_386: u32 = 3;
+#[cfg(target_arch="powerpc")]
 const EM_PPC: u32 = 20;
+#[cfg(target_arch="powerpc64")]
 const EM_PPC64: u32 = 21;
+#[cfg(target_arch="arm")]
 const EM_ARM: u32 = 40;
+#[cfg(target_arch="x86_64")]
 const EM_X86_64: u32 = 62;
+#[cfg(target_arch="aarch64")]
 const EM_AARCH64: u32 = 183;
+#[cfg(target_arch="riscv64")]
+const EM_RISCV: u32 = 243;
 
 /// A flag set in the architecture number for all 64-bit architectures.
 const __AUDIT_ARCH_64BIT: u32 = 0x8000_0000;
 /// A flag set in the architecture number for all little-endian architectures.
 const __AUDIT_ARCH_LE: u32 = 0x4000_0000;
 /// The architecture number for x86.
+#[cfg(target_arch="x86")]
 const AUDIT_ARCH_X86: u32 = EM_386 | __AUDIT_ARCH_LE;
 /// The architecture number for x86-64.
+#[cfg(target_arch="x86_64")]
 const AUDIT_ARCH_X86_64: u32 = EM_X86_64 | __AUDIT_ARCH_64BIT | __AUDIT_ARCH_LE;
 /// The architecture number for ARM.
+#[cfg(target_arch="arm")]
 const AUDIT_ARCH_ARM: u32 = EM_ARM | __AUDIT_ARCH_LE;
 /// The architecture number for ARM 64-bit.
+#[cfg(target_arch="aarch64")]
 const AUDIT_ARCH_AARCH64: u32 = EM_AARCH64 | __AUDIT_ARCH_64BIT | __AUDIT_ARCH_LE;
 /// The architecture number for ppc.
+#[cfg(target_arch="powerpc")]
 const AUDIT_ARCH_PPC: u32 = EM_PPC;
 /// The architecture number for ppc64.
+#[cfg(all(target_arch="powerpc64", target_endian="big"))]
 const AUDIT_ARCH_PPC64: u32 = EM_PPC64 | __AUDIT_ARCH_64BIT;
 /// The architecture number for ppc64le.
+#[cfg(all(target_arch="powerpc64", target_endian="little"))]
 const AUDIT_ARCH_PPC64LE: u32 = EM_PPC64 | __AUDIT_ARCH_64BIT | __AUDIT_ARCH_LE;
+/// The architecture number for RISC-V 64-bit.
+#[cfg(target_arch="riscv64")]
+const AUDIT_ARCH_RISCV64: u32 = EM_RISCV | __AUDIT_ARCH_64BIT | __AUDIT_ARCH_LE;
 
+const PR_GET_SECCOMP: c_int = 21;
 const PR_SET_SECCOMP: c_int = 22;
 const PR_SET_NO_NEW_PRIVS: c_int = 38;
+const PR_GET_NO_NEW_PRIVS: c_int = 39;
 
 const SECCOMP_MODE_FILTER: c_ulong = 2;
 
-static FILTER_PROLOGUE: [sock_filter; 3] = [
+/// The real-time signals the C library keeps for itself, for cancelling threads and for changing
+/// the credentials of every thread at once.
+const SIGCANCEL: c_int = 32;
+const SIGSETXID: c_int = 33;
+
+/// `poll()`. aarch64 and riscv64 only have `ppoll()`.
+#[cfg(not(any(target_arch="aarch64", target_arch="riscv64")))]
+const NR_poll: u32 = libc::SYS_poll as u32;
+#[cfg(any(target_arch="aarch64", target_arch="riscv64"))]
+const NR_poll: u32 = libc::SYS_ppoll as u32;
+
+/// What the C library's `rename()` uses where there is no `rename()`: `renameat()` on aarch64,
+/// which `libc` doesn't define, and `renameat2()` on riscv64, which has no `renameat()` either.
+#[cfg(target_arch="aarch64")]
+const NR_renameat: u32 = 38;
+#[cfg(target_arch="riscv64")]
+const NR_renameat: u32 = libc::SYS_renameat2 as u32;
+
+/// `getrlimit()`, which `libc` doesn't define on aarch64.
+#[cfg(not(target_arch="aarch64"))]
+const NR_getrlimit: u32 = libc::SYS_getrlimit as u32;
+#[cfg(target_arch="aarch64")]
+const NR_getrlimit: u32 = 163;
+
+static FILTER_PROLOGUE: [SockFilter; 3] = [
     VALIDATE_ARCHITECTURE_0,
     VALIDATE_ARCHITECTURE_1,
     VALIDATE_ARCHITECTURE_2,
 ];
 
-// A most untimely end...
-static FILTER_EPILOGUE: [sock_filter; 1] = [
-    KILL_PROCESS,
-];
+/// The instruction that ends the filter, handling every system call nothing above allowed as
+/// `violation_action` says. A most untimely end, by default...
+fn filter_epilogue(violation_action: ViolationAction) -> SockFilter {
+    let k = match violation_action {
+        ViolationAction::Kill => SECCOMP_RET_KILL,
+        ViolationAction::Errno(errno) => SECCOMP_RET_ERRNO | (errno as u32 & 0xffff),
+        ViolationAction::Log => SECCOMP_RET_LOG,
+        ViolationAction::Trap => SECCOMP_RET_TRAP,
+    };
+    SockFilter {
+        code: RET + K,
+        k,
+        jt: 0,
+        jf: 0,
+    }
+}
 
-/// Syscalls that are always allowed.
-pub static ALLOWED_SYSCALLS: [u32; 21] = [
+/// Syscalls that are always allowed. Besides what every program needs, these include what the C
+/// library and the Rust runtime do when they start a thread, such as registering it with
+/// `rseq()` and blocking signals around `clone()`, and reading the clock where the vDSO can't.
+pub static ALLOWED_SYSCALLS: [u32; 29] = [
     libc::SYS_brk as u32,
+    libc::SYS_capget as u32,
+    libc::SYS_clock_getres as u32,
+    libc::SYS_clock_gettime as u32,
+    libc::SYS_clock_nanosleep as u32,
     libc::SYS_close as u32,
     libc::SYS_exit as u32,
     libc::SYS_exit_group as u32,
     libc::SYS_futex as u32,
-    libc::SYS_getrandom as u32,
+    libc::SYS_gettid as u32,
+    libc::SYS_gettimeofday as u32,
     libc::SYS_getuid as u32,
     libc::SYS_mmap as u32,
     libc::SYS_mprotect as u32,
     libc::SYS_munmap as u32,
-    libc::SYS_poll as u32,
+    NR_poll,
     libc::SYS_read as u32,
     libc::SYS_recvfrom as u32,
     libc::SYS_recvmsg as u32,
+    libc::SYS_rseq as u32,
+    libc::SYS_rt_sigprocmask as u32,
     libc::SYS_rt_sigreturn as u32,
     libc::SYS_sched_getaffinity as u32,
     libc::SYS_sendmmsg as u32,
     libc::SYS_sendto as u32,
     libc::SYS_set_robust_list as u32,
+    libc::SYS_set_tid_address as u32,
     libc::SYS_sigaltstack as u32,
     libc::SYS_write as u32,
 ];
 
-static ALLOWED_SYSCALLS_FOR_FILE_READ: [u32; 5] = [
+/// What event loops such as mio's wait with, which works only on descriptors the process already
+/// has. `pipe2()` and `eventfd2()` are allowed separately, with only some flags.
+#[cfg(not(any(target_arch="aarch64", target_arch="riscv64")))]
+pub static ALLOWED_SYSCALLS_FOR_EVENT_LOOPS: [u32; 6] = [
+    libc::SYS_epoll_create1 as u32,
+    libc::SYS_epoll_ctl as u32,
+    libc::SYS_epoll_pwait as u32,
+    libc::SYS_epoll_wait as u32,
+    libc::SYS_timerfd_create as u32,
+    libc::SYS_timerfd_settime as u32,
+];
+
+/// aarch64 and riscv64 only have `epoll_pwait()`.
+#[cfg(any(target_arch="aarch64", target_arch="riscv64"))]
+pub static ALLOWED_SYSCALLS_FOR_EVENT_LOOPS: [u32; 5] = [
+    libc::SYS_epoll_create1 as u32,
+    libc::SYS_epoll_ctl as u32,
+    libc::SYS_epoll_pwait as u32,
+    libc::SYS_timerfd_create as u32,
+    libc::SYS_timerfd_settime as u32,
+];
+
+/// Current C libraries and the Rust standard library use the `*at()` forms of the path-based
+/// calls, and `statx()`, even where the older ones exist.
+#[cfg(not(any(target_arch="aarch64", target_arch="riscv64")))]
+static ALLOWED_SYSCALLS_FOR_FILE_READ: [u32; 12] = [
     libc::SYS_access as u32,
+    libc::SYS_faccessat as u32,
+    libc::SYS_faccessat2 as u32,
     libc::SYS_fstat as u32,
+    libc::SYS_getdents64 as u32,
     libc::SYS_lseek as u32,
+    libc::SYS_lstat as u32,
+    libc::SYS_newfstatat as u32,
     libc::SYS_readlink as u32,
+    libc::SYS_readlinkat as u32,
     libc::SYS_stat as u32,
+    libc::SYS_statx as u32,
+];
+
+/// aarch64 and riscv64 have only the `*at()` forms of the path-based calls.
+#[cfg(any(target_arch="aarch64", target_arch="riscv64"))]
+static ALLOWED_SYSCALLS_FOR_FILE_READ: [u32; 8] = [
+    libc::SYS_faccessat as u32,
+    libc::SYS_faccessat2 as u32,
+    libc::SYS_fstat as u32,
+    libc::SYS_getdents64 as u32,
+    libc::SYS_lseek as u32,
+    libc::SYS_newfstatat as u32,
+    libc::SYS_readlinkat as u32,
+    libc::SYS_statx as u32,
+];
+
+#[cfg(not(any(target_arch="aarch64", target_arch="riscv64")))]
+static ALLOWED_SYSCALLS_FOR_FILE_WRITE: [u32; 8] = [
+    libc::SYS_fallocate as u32,
+    libc::SYS_fsync as u32,
+    libc::SYS_ftruncate as u32,
+    libc::SYS_pwrite64 as u32,
+    libc::SYS_pwritev as u32,
+    libc::SYS_rename as u32,
+    libc::SYS_unlink as u32,
+    libc::SYS_writev as u32,
+];
+
+#[cfg(any(target_arch="aarch64", target_arch="riscv64"))]
+static ALLOWED_SYSCALLS_FOR_FILE_WRITE: [u32; 8] = [
+    libc::SYS_fallocate as u32,
+    libc::SYS_fsync as u32,
+    libc::SYS_ftruncate as u32,
+    libc::SYS_pwrite64 as u32,
+    libc::SYS_pwritev as u32,
+    NR_renameat,
+    libc::SYS_unlinkat as u32,
+    libc::SYS_writev as u32,
+];
+
+/// `write()` itself is always allowed; what a descriptor can be written to is decided when it is
+/// opened.
+static ALLOWED_SYSCALLS_FOR_FILE_APPEND: [u32; 1] = [
+    libc::SYS_writev as u32,
+];
+
+/// Running programs, waiting for them, and what the dynamic loader and C library do when a program
+/// starts.
+#[cfg(target_arch="x86_64")]
+static ALLOWED_SYSCALLS_FOR_PROCESS_EXEC: [u32; 9] = [
+    libc::SYS_arch_prctl as u32,
+    libc::SYS_execve as u32,
+    libc::SYS_execveat as u32,
+    libc::SYS_fork as u32,
+    libc::SYS_pread64 as u32,
+    libc::SYS_prlimit64 as u32,
+    libc::SYS_vfork as u32,
+    libc::SYS_wait4 as u32,
+    libc::SYS_waitid as u32,
+];
+
+/// aarch64 and riscv64 have no `fork()` or `vfork()`, only `clone()`, and no `arch_prctl()`.
+#[cfg(any(target_arch="aarch64", target_arch="riscv64"))]
+static ALLOWED_SYSCALLS_FOR_PROCESS_EXEC: [u32; 6] = [
+    libc::SYS_execve as u32,
+    libc::SYS_execveat as u32,
+    libc::SYS_pread64 as u32,
+    libc::SYS_prlimit64 as u32,
+    libc::SYS_wait4 as u32,
+    libc::SYS_waitid as u32,
+];
+
+#[cfg(not(any(target_arch="x86_64", target_arch="aarch64", target_arch="riscv64")))]
+static ALLOWED_SYSCALLS_FOR_PROCESS_EXEC: [u32; 8] = [
+    libc::SYS_execve as u32,
+    libc::SYS_execveat as u32,
+    libc::SYS_fork as u32,
+    libc::SYS_pread64 as u32,
+    libc::SYS_prlimit64 as u32,
+    libc::SYS_vfork as u32,
+    libc::SYS_wait4 as u32,
+    libc::SYS_waitid as u32,
+];
+
+static ALLOWED_SYSCALLS_FOR_SYSTEM_INFO_READ: [u32; 3] = [
+    NR_getrlimit,
+    libc::SYS_sysinfo as u32,
+    libc::SYS_uname as u32,
+];
+
+/// The `clone()` flags that the C library's `fork()` and `posix_spawn()` use, which create a
+/// process rather than a thread.
+static FORK_CLONE_FLAGS: [c_int; 3] = [
+    SIGCHLD,
+    CLONE_CHILD_SETTID | CLONE_CHILD_CLEARTID | SIGCHLD,
+    CLONE_VM | CLONE_VFORK | SIGCHLD,
+];
+
+/// `chown()` and friends are only allowed because the user namespace maps nobody but the process's
+/// own user and group, so it can't give files away.
+#[cfg(not(any(target_arch="aarch64", target_arch="riscv64")))]
+static ALLOWED_SYSCALLS_FOR_FILE_METADATA_WRITE: [u32; 8] = [
+    libc::SYS_chmod as u32,
+    libc::SYS_chown as u32,
+    libc::SYS_fchmod as u32,
+    libc::SYS_fchmodat as u32,
+    libc::SYS_fchown as u32,
+    libc::SYS_fchownat as u32,
+    libc::SYS_lchown as u32,
+    libc::SYS_utimensat as u32,
+];
+
+#[cfg(any(target_arch="aarch64", target_arch="riscv64"))]
+static ALLOWED_SYSCALLS_FOR_FILE_METADATA_WRITE: [u32; 5] = [
+    libc::SYS_fchmod as u32,
+    libc::SYS_fchmodat as u32,
+    libc::SYS_fchown as u32,
+    libc::SYS_fchownat as u32,
+    libc::SYS_utimensat as u32,
+];
+
+static ALLOWED_SYSCALLS_FOR_FD_ONLY: [u32; 5] = [
+    libc::SYS_fcntl as u32,
+    libc::SYS_fstat as u32,
+    libc::SYS_lseek as u32,
+    libc::SYS_pread64 as u32,
+    libc::SYS_pwrite64 as u32,
+];
+
+/// Syscalls that take a path, which fail with `ENOENT` under `Profile::fd_only()`.
+#[cfg(not(any(target_arch="aarch64", target_arch="riscv64")))]
+static PATH_SYSCALLS: [u32; 36] = [
+    libc::SYS_access as u32,
+    libc::SYS_chdir as u32,
+    libc::SYS_chmod as u32,
+    libc::SYS_chown as u32,
+    libc::SYS_creat as u32,
+    libc::SYS_execve as u32,
+    libc::SYS_execveat as u32,
+    libc::SYS_faccessat as u32,
+    libc::SYS_faccessat2 as u32,
+    libc::SYS_fchmodat as u32,
+    libc::SYS_fchownat as u32,
+    libc::SYS_lchown as u32,
+    libc::SYS_link as u32,
+    libc::SYS_linkat as u32,
+    libc::SYS_lstat as u32,
+    libc::SYS_mkdir as u32,
+    libc::SYS_mkdirat as u32,
+    libc::SYS_newfstatat as u32,
+    libc::SYS_open as u32,
+    libc::SYS_openat as u32,
+    libc::SYS_openat2 as u32,
+    libc::SYS_readlink as u32,
+    libc::SYS_readlinkat as u32,
+    libc::SYS_rename as u32,
+    libc::SYS_renameat as u32,
+    libc::SYS_renameat2 as u32,
+    libc::SYS_rmdir as u32,
+    libc::SYS_stat as u32,
+    libc::SYS_statfs as u32,
+    libc::SYS_statx as u32,
+    libc::SYS_symlink as u32,
+    libc::SYS_symlinkat as u32,
+    libc::SYS_truncate as u32,
+    libc::SYS_unlink as u32,
+    libc::SYS_unlinkat as u32,
+    libc::SYS_utimensat as u32,
+];
+
+#[cfg(any(target_arch="aarch64", target_arch="riscv64"))]
+static PATH_SYSCALLS: [u32; 21] = [
+    libc::SYS_chdir as u32,
+    libc::SYS_execve as u32,
+    libc::SYS_execveat as u32,
+    libc::SYS_faccessat as u32,
+    libc::SYS_faccessat2 as u32,
+    libc::SYS_fchmodat as u32,
+    libc::SYS_fchownat as u32,
+    libc::SYS_linkat as u32,
+    libc::SYS_mkdirat as u32,
+    libc::SYS_newfstatat as u32,
+    libc::SYS_openat as u32,
+    libc::SYS_openat2 as u32,
+    libc::SYS_readlinkat as u32,
+    NR_renameat,
+    libc::SYS_renameat2 as u32,
+    libc::SYS_statfs as u32,
+    libc::SYS_statx as u32,
+    libc::SYS_symlinkat as u32,
+    libc::SYS_truncate as u32,
+    libc::SYS_unlinkat as u32,
+    libc::SYS_utimensat as u32,
 ];
 
 static ALLOWED_SYSCALLS_FOR_NETWORK_OUTBOUND: [u32; 3] = [
@@ -151,87 +480,687 @@ static ALLOWED_SYSCALLS_FOR_NETWORK_OUTBOUND: [u32; 3] = [
     libc::SYS_getsockname as u32,
 ];
 
-const ALLOW_SYSCALL: sock_filter = sock_filter {
+/// `getsockopt()` only reads, and servers use it to check accepted connections.
+static ALLOWED_SYSCALLS_FOR_NETWORK_INBOUND: [u32; 4] = [
+    libc::SYS_accept as u32,
+    libc::SYS_accept4 as u32,
+    libc::SYS_getsockopt as u32,
+    libc::SYS_listen as u32,
+];
+
+const ALLOW_SYSCALL: SockFilter = SockFilter {
     code: RET + K,
     k: SECCOMP_RET_ALLOW,
     jt: 0,
     jf: 0,
 };
 
-const KILL_PROCESS: sock_filter = sock_filter {
+const KILL_PROCESS: SockFilter = SockFilter {
     code: RET + K,
     k: SECCOMP_RET_KILL,
     jt: 0,
     jf: 0,
 };
 
-const EXAMINE_SYSCALL: sock_filter = sock_filter {
-    code: LD + W + ABS,
-    k: SYSCALL_NR_OFFSET,
+/// Jumps ahead by `k` instructions, however many that is.
+const JUMP: SockFilter = SockFilter {
+    code: JMP + JA + K,
+    k: 0,
     jt: 0,
     jf: 0,
 };
 
-const EXAMINE_ARG_0: sock_filter = sock_filter {
+const EXAMINE_SYSCALL: SockFilter = SockFilter {
     code: LD + W + ABS,
-    k: ARG_0_OFFSET,
+    k: SYSCALL_NR_OFFSET,
     jt: 0,
     jf: 0,
 };
 
-const EXAMINE_ARG_1: sock_filter = sock_filter {
-    code: LD + W + ABS,
-    k: ARG_1_OFFSET,
-    jt: 0,
-    jf: 0,
-};
+/// Loads the low half of argument `index`.
+const fn examine_arg(index: u32) -> SockFilter {
+    SockFilter {
+        code: LD + W + ABS,
+        k: ARGS_OFFSET + index * 8 + LOW_HALF_OFFSET,
+        jt: 0,
+        jf: 0,
+    }
+}
 
-const EXAMINE_ARG_2: sock_filter = sock_filter {
-    code: LD + W + ABS,
-    k: ARG_2_OFFSET,
-    jt: 0,
-    jf: 0,
-};
+/// Loads the high half of argument `index`.
+const fn examine_arg_high(index: u32) -> SockFilter {
+    SockFilter {
+        code: LD + W + ABS,
+        k: ARGS_OFFSET + index * 8 + HIGH_HALF_OFFSET,
+        jt: 0,
+        jf: 0,
+    }
+}
 
-const VALIDATE_ARCHITECTURE_0: sock_filter = sock_filter {
+const VALIDATE_ARCHITECTURE_0: SockFilter = SockFilter {
     code: LD + W + ABS,
     k: ARCH_NR_OFFSET,
     jt: 0,
     jf: 0,
 };
 
-const VALIDATE_ARCHITECTURE_1: sock_filter = sock_filter {
+const VALIDATE_ARCHITECTURE_1: SockFilter = SockFilter {
     code: JMP + JEQ + K,
     k: ARCH_NR,
     jt: 1,
     jf: 0,
 };
 
-const VALIDATE_ARCHITECTURE_2: sock_filter = KILL_PROCESS;
+const VALIDATE_ARCHITECTURE_2: SockFilter = KILL_PROCESS;
+
+/// Returns the process-wide cache of compiled filters.
+///
+/// `ChildSandbox::activate()` and `Filter::cached()` consult this cache, so constructing sandboxes
+/// with equal profiles compiles the filter only once. Call `cache().set_enabled(false)` to turn
+/// caching off.
+pub fn cache() -> &'static ProfileCache<Filter> {
+    static CACHE: OnceLock<ProfileCache<Filter>> = OnceLock::new();
+    CACHE.get_or_init(|| ProfileCache::new(cache::DEFAULT_CAPACITY))
+}
 
 pub struct Filter {
-    program: Vec<sock_filter>,
+    program: Vec<SockFilter>,
 }
 
 impl Filter {
+    /// Returns the filter for the given profile, compiling it only if it isn't already in the
+    /// process-wide cache.
+    pub fn cached(profile: &Profile) -> Arc<Filter> {
+        cache().get_or_insert_with(profile, Filter::new)
+    }
+
+    /// Returns a filter that hands every system call to the process's tracer, for
+    /// `Sandbox::trace()`. It prohibits nothing.
+    pub fn trace_all() -> Filter {
+        Filter {
+            program: vec![SockFilter {
+                code: RET + K,
+                k: SECCOMP_RET_TRACE,
+                jt: 0,
+                jf: 0,
+            }],
+        }
+    }
+
+    /// Returns the filter for the given profile with its `ViolationAction` replaced by
+    /// `violation_action`.
+    pub fn new_with_action(profile: &Profile, violation_action: ViolationAction) -> Filter {
+        Filter::new(&profile.clone().with_violation_action(violation_action))
+    }
+
+    /// Returns a filter for the given profile that logs every system call instead of allowing
+    /// or prohibiting it, so that a program's whole system call surface shows up in the audit
+    /// log before its profile is tightened. It confines nothing. The architecture check still
+    /// kills.
+    pub fn audit_only(profile: &Profile) -> Filter {
+        let mut filter = Filter::new_with_action(profile, ViolationAction::Log);
+        for instruction in &mut filter.program {
+            if instruction.code == RET + K && instruction.k == SECCOMP_RET_ALLOW {
+                instruction.k = SECCOMP_RET_LOG
+            }
+        }
+        filter
+    }
+
+    /// Returns the filter for the given profile with `ViolationAction::Log` in place of its own,
+    /// for `AuditSandbox`. Where the kernel can't log, prohibited system calls are allowed
+    /// instead of failing with `EPERM`, so that auditing never changes what the program does.
+    pub fn auditing(profile: &Profile) -> Filter {
+        let mut filter = Filter::new_with_action(profile, ViolationAction::Log);
+        if probe_seccomp_action(SECCOMP_RET_LOG).is_err() {
+            filter.program.pop();
+            filter.program.push(ALLOW_SYSCALL)
+        }
+        filter
+    }
+
+    /// Returns a filter for the given profile that hands every system call the profile doesn't
+    /// allow to a supervisor, for `SupervisedSandbox`, instead of handling it as the profile's
+    /// `ViolationAction` says. It also allows `sendmsg` on `socket`, which the notification
+    /// descriptor is sent to the supervisor through.
+    pub fn supervised(profile: &Profile, socket: c_int) -> Filter {
+        let mut builder = FilterBuilder::for_profile(profile);
+        builder.if_syscall_is(libc::SYS_sendmsg as u32, |filter| {
+            filter.if_arg0_is(socket as u32, |filter| filter.allow_this_syscall())
+        });
+        Filter {
+            program: builder.binary_search(SockFilter {
+                code: RET + K,
+                k: SECCOMP_RET_USER_NOTIF,
+                jt: 0,
+                jf: 0,
+            }),
+        }
+    }
+
+    /// Returns a filter for the given profile that raises `SIGSYS` in the process for every
+    /// system call the profile doesn't allow, with `SECCOMP_RET_TRAP`, instead of handling it as
+    /// the profile's `ViolationAction` says, so that a handler can report it.
+    pub fn reporting(profile: &Profile) -> Filter {
+        Filter::new_with_action(profile, ViolationAction::Trap)
+    }
+
+    /// Reads a program in the format `as_bytes()` writes, such as one built by another tool, so
+    /// that it can be activated like gaol's own. Fails with `InvalidLength` unless `bytes` is a
+    /// whole number of instructions, between one and the kernel's 4096, and with
+    /// `JumpOutOfBounds` if an instruction can jump, or run on, past the end of the program.
+    ///
+    /// The program is taken as it is: it checks the architecture only if it was built to. Put
+    /// `architecture_check()` in front of it with `prepend()` if it wasn't.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Filter,FilterError> {
+        let size = mem::size_of::<SockFilter>();
+        let count = bytes.len() / size;
+        if count == 0 || count > MAX_INSTRUCTIONS || !bytes.len().is_multiple_of(size) {
+            return Err(FilterError::InvalidLength)
+        }
+        let program: Vec<_> = bytes.chunks_exact(size).map(|bytes| {
+            SockFilter {
+                code: u16::from_ne_bytes([bytes[0], bytes[1]]),
+                jt: bytes[2],
+                jf: bytes[3],
+                k: u32::from_ne_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]),
+            }
+        }).collect();
+        for (index, instruction) in program.iter().enumerate() {
+            let next = index + 1;
+            let furthest = match instruction.code & CLASS_MASK {
+                RET => continue,
+                JMP if instruction.code & OP_MASK == JA => next + instruction.k as usize,
+                JMP => next + instruction.jt.max(instruction.jf) as usize,
+                _ => next,
+            };
+            if furthest >= program.len() {
+                return Err(FilterError::JumpOutOfBounds(index))
+            }
+        }
+        Ok(Filter {
+            program,
+        })
+    }
+
+    /// Returns the instructions every filter gaol compiles starts with, which kill the process
+    /// if the system call wasn't made with this machine's native architecture, and go on to the
+    /// instruction after them if it was. It is no program by itself; `prepend()` it to one.
+    pub fn architecture_check() -> Filter {
+        Filter {
+            program: FILTER_PROLOGUE.to_vec(),
+        }
+    }
+
+    /// Puts the instructions of `prologue` in front of this filter's, so that it runs first and
+    /// this filter's program runs where it would run on past its end. Jumps are relative, so
+    /// neither program's change meaning.
+    pub fn prepend(&mut self, prologue: &Filter) {
+        self.program.splice(0..0, prologue.program.iter().cloned());
+    }
+
+    /// Puts the instructions of `epilogue` after this filter's, so that it runs where this
+    /// filter's program would run on past its end.
+    pub fn append(&mut self, epilogue: &Filter) {
+        self.program.extend_from_slice(&epilogue.program)
+    }
+
+    /// Returns the filter for the given profile, which finds each system call's rules by binary
+    /// search over the system call numbers.
     pub fn new(profile: &Profile) -> Filter {
-        let mut filter = Filter {
-            program: FILTER_PROLOGUE.iter().map(|x| *x).collect(),
+        Filter {
+            program: FilterBuilder::for_profile(profile)
+                .binary_search(filter_epilogue(profile.violation_action())),
+        }
+    }
+
+    /// Returns the filter for the given profile laid out as a chain of comparisons, one system
+    /// call after another, in the order its rules were added. It decides every system call as
+    /// the one `new()` returns does, but takes longer to reach the later ones.
+    pub fn linear(profile: &Profile) -> Filter {
+        Filter {
+            program: FilterBuilder::for_profile(profile)
+                .linear(filter_epilogue(profile.violation_action())),
+        }
+    }
+
+    /// Dumps this filter to a temporary file.
+    #[cfg(dump_bpf_sockets)]
+    pub fn dump(&self) {
+        let path = CString::new("/tmp/gaol-bpf.XXXXXX").unwrap();
+        let mut path = path.as_bytes_with_nul().to_vec();
+        let fd = unsafe {
+            libc::mkstemp(path.as_mut_ptr() as *mut c_char)
+        };
+        let bytes = self.as_bytes();
+        unsafe {
+            assert!(libc::write(fd, bytes.as_ptr() as *const c_void, bytes.len()) ==
+                    bytes.len() as isize);
+            libc::close(fd);
+        }
+    }
+
+    #[cfg(not(dump_bpf_sockets))]
+    pub fn dump(&self) {}
+
+    /// Returns the instructions of this filter's program, as the kernel gets them.
+    pub fn instructions(&self) -> &[SockFilter] {
+        &self.program
+    }
+
+    /// Returns how many instructions this filter's program has.
+    pub fn instruction_count(&self) -> usize {
+        self.program.len()
+    }
+
+    /// Returns this filter's program as the kernel gets it: each instruction as eight bytes, the
+    /// 16-bit code, the two jump offsets, and the 32-bit operand, in this machine's byte order.
+    /// Tools such as `bpf_dbg` and `seccomp-tools` read this format.
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(self.program.len() * mem::size_of::<SockFilter>());
+        for instruction in self.program.iter() {
+            bytes.extend_from_slice(&instruction.code.to_ne_bytes());
+            bytes.push(instruction.jt);
+            bytes.push(instruction.jf);
+            bytes.extend_from_slice(&instruction.k.to_ne_bytes());
+        }
+        bytes
+    }
+
+    /// Returns a human-readable listing of this filter's program, one instruction per line, in
+    /// the style of `tcpdump -d`. Jump targets are absolute instruction indices. Comparisons
+    /// with the system call number are followed by the name of the system call, if
+    /// `syscall_name()` knows it.
+    pub fn disassemble(&self) -> String {
+        let loaded = self.loaded_offsets();
+        let mut listing = String::new();
+        for (index, instruction) in self.program.iter().enumerate() {
+            listing.push_str(&format!("{:04}: {}", index, instruction.disassemble(index)));
+            let name = if instruction.code == JMP + JEQ + K && loaded[index] == Some(Some(0)) {
+                syscall_name(instruction.k as i64)
+            } else {
+                None
+            };
+            if let Some(name) = name {
+                listing.push_str(&format!("  ; {}", name))
+            }
+            listing.push('\n')
+        }
+        listing
+    }
+
+    /// Returns, for each instruction, the offset in `struct seccomp_data` of the word in the
+    /// accumulator when it runs: `None` if no path reaches it, and `Some(None)` if paths reaching
+    /// it loaded different words. BPF only jumps forward, so one pass finds them all.
+    fn loaded_offsets(&self) -> Vec<Option<Option<u32>>> {
+        let mut loaded: Vec<Option<Option<u32>>> = vec![None; self.program.len()];
+        let reach = |loaded: &mut Vec<Option<Option<u32>>>, index: usize, offset: Option<u32>| {
+            if let Some(reached) = loaded.get_mut(index) {
+                *reached = match *reached {
+                    None => Some(offset),
+                    Some(other) if other == offset => Some(offset),
+                    Some(_) => Some(None),
+                }
+            }
+        };
+        if !self.program.is_empty() {
+            loaded[0] = Some(None)
+        }
+        for (index, instruction) in self.program.iter().enumerate() {
+            let offset = match loaded[index] {
+                Some(offset) => offset,
+                None => continue,
+            };
+            match instruction.code {
+                code if code == LD + W + ABS => reach(&mut loaded, index + 1, Some(instruction.k)),
+                code if code == JMP + JA + K => {
+                    reach(&mut loaded, index + 1 + instruction.k as usize, offset)
+                }
+                code if code & 0x07 == JMP => {
+                    reach(&mut loaded, index + 1 + instruction.jt as usize, offset);
+                    reach(&mut loaded, index + 1 + instruction.jf as usize, offset)
+                }
+                code if code == RET + K => {}
+                _ => reach(&mut loaded, index + 1, offset),
+            }
+        }
+        loaded
+    }
+
+    /// Runs this filter's program the way the kernel would for a system call with the given
+    /// number and arguments, made from this architecture, and returns the resulting action.
+    ///
+    /// This lets callers find out whether a system call would be allowed without making it, which
+    /// under a filter that kills on violation is the only safe way to ask.
+    pub fn evaluate(&self, syscall: u32, args: &[u64; 6]) -> u32 {
+        let mut accumulator = 0;
+        let mut index = 0;
+        while let Some(instruction) = self.program.get(index) {
+            index += 1;
+            match instruction.code {
+                code if code == LD + W + ABS => {
+                    accumulator = load_seccomp_data_word(syscall, args, instruction.k)
+                }
+                code if code == JMP + JEQ + K => {
+                    index += if accumulator == instruction.k {
+                        instruction.jt as usize
+                    } else {
+                        instruction.jf as usize
+                    }
+                }
+                code if code == JMP + JGE + K => {
+                    index += if accumulator >= instruction.k {
+                        instruction.jt as usize
+                    } else {
+                        instruction.jf as usize
+                    }
+                }
+                code if code == JMP + JSET + K => {
+                    index += if accumulator & instruction.k != 0 {
+                        instruction.jt as usize
+                    } else {
+                        instruction.jf as usize
+                    }
+                }
+                code if code == JMP + JA + K => index += instruction.k as usize,
+                code if code == RET + K => return instruction.k,
+                _ => break,
+            }
+        }
+        SECCOMP_RET_KILL
+    }
+
+    /// Returns true if this filter allows the system call with the given number and arguments.
+    pub fn allows(&self, syscall: u32, args: &[u64; 6]) -> bool {
+        self.evaluate(syscall, args) == SECCOMP_RET_ALLOW
+    }
+
+    /// Activates this filter, applying all of its restrictions forevermore. This can only be done
+    /// once.
+    #[inline(never)]
+    pub fn activate(&self) -> Result<(),SandboxError> {
+        unsafe {
+            let result = libc::prctl(PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0);
+            if result != 0 {
+                return Err(seccomp_error())
+            }
+
+            let program = self.installable_program();
+            let program = fprog(&program);
+            let result = libc::prctl(PR_SET_SECCOMP,
+                                     SECCOMP_MODE_FILTER,
+                                     &program as *const sock_fprog as usize as c_ulong,
+                                     !0,
+                                     0);
+            if result == 0 {
+                Ok(())
+            } else {
+                Err(seccomp_error())
+            }
+        }
+    }
+
+    /// Like `activate()`, but applies the filter to every thread in the process at once, with
+    /// `SECCOMP_FILTER_FLAG_TSYNC`. This fails with `EINVAL` if the kernel doesn't support that
+    /// flag, in which case nothing is applied.
+    #[inline(never)]
+    pub fn activate_on_all_threads(&self) -> Result<(),SandboxError> {
+        unsafe {
+            let result = libc::prctl(PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0);
+            if result != 0 {
+                return Err(seccomp_error())
+            }
+
+            // On failure to synchronize, this returns the ID of a thread that couldn't be.
+            let program = self.installable_program();
+            let program = fprog(&program);
+            let result = libc::syscall(libc::SYS_seccomp,
+                                       libc::SECCOMP_SET_MODE_FILTER,
+                                       libc::SECCOMP_FILTER_FLAG_TSYNC,
+                                       &program as *const sock_fprog);
+            if result == 0 {
+                Ok(())
+            } else if result < 0 {
+                Err(seccomp_error())
+            } else {
+                let err = io::Error::other(format!("thread {} couldn't be synchronized", result));
+                Err(SandboxError::SeccompFailed(err))
+            }
+        }
+    }
+
+    /// Like `activate()`, but returns a descriptor that the system calls this filter hands to a
+    /// supervisor arrive on, with `SECCOMP_FILTER_FLAG_NEW_LISTENER`. With `all_threads`, it
+    /// applies the filter to every thread as `activate_on_all_threads()` does, which needs
+    /// `SECCOMP_FILTER_FLAG_TSYNC_ESRCH` (Linux 5.7+).
+    #[inline(never)]
+    pub fn activate_with_listener(&self, all_threads: bool) -> Result<c_int,SandboxError> {
+        unsafe {
+            let result = libc::prctl(PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0);
+            if result != 0 {
+                return Err(seccomp_error())
+            }
+
+            let mut flags = libc::SECCOMP_FILTER_FLAG_NEW_LISTENER;
+            if all_threads {
+                flags |= libc::SECCOMP_FILTER_FLAG_TSYNC | libc::SECCOMP_FILTER_FLAG_TSYNC_ESRCH
+            }
+            let program = self.installable_program();
+            let program = fprog(&program);
+            let result = libc::syscall(libc::SYS_seccomp,
+                                       libc::SECCOMP_SET_MODE_FILTER,
+                                       flags,
+                                       &program as *const sock_fprog);
+            if result >= 0 {
+                Ok(result as c_int)
+            } else {
+                Err(seccomp_error())
+            }
+        }
+    }
+
+    /// Returns this filter's program as this kernel can run it. Before Linux 4.14 there is no
+    /// `SECCOMP_RET_LOG`, so the final one, for violations, becomes a failure with `EPERM`, and
+    /// any others, which stand in for allowing, become `SECCOMP_RET_ALLOW`.
+    fn installable_program(&self) -> Cow<'_, [SockFilter]> {
+        let logs = self.program.iter().any(|instruction| {
+            instruction.code == RET + K && instruction.k == SECCOMP_RET_LOG
+        });
+        if !logs || probe_seccomp_action(SECCOMP_RET_LOG).is_ok() {
+            return Cow::Borrowed(&self.program)
+        }
+
+        warn!("this kernel can't log system calls; prohibited ones will fail with EPERM instead");
+        let last = self.program.len() - 1;
+        Cow::Owned(self.program.iter().enumerate().map(|(index, instruction)| {
+            if instruction.code != RET + K || instruction.k != SECCOMP_RET_LOG {
+                *instruction
+            } else if index == last {
+                SockFilter { k: SECCOMP_RET_ERRNO | libc::EPERM as u32, ..*instruction }
+            } else {
+                ALLOW_SYSCALL
+            }
+        }).collect())
+    }
+}
+
+/// Why `Filter::from_bytes()` couldn't read a program.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FilterError {
+    /// The program isn't a whole number of instructions, or it is empty, or it is longer than the
+    /// kernel accepts.
+    InvalidLength,
+    /// The instruction at this index can jump, or run on, past the end of the program.
+    JumpOutOfBounds(usize),
+}
+
+impl fmt::Display for FilterError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            FilterError::InvalidLength => {
+                write!(f, "the program must be between 1 and {} instructions of {} bytes each",
+                       MAX_INSTRUCTIONS,
+                       mem::size_of::<SockFilter>())
+            }
+            FilterError::JumpOutOfBounds(index) => {
+                write!(f, "instruction {} can go past the end of the program", index)
+            }
+        }
+    }
+}
+
+impl Error for FilterError {}
+
+/// Collects the rules of a filter, each the instructions that decide one system call, and then
+/// lays them out as a program.
+///
+/// A rule either returns or falls through, to the next rule for the same system call if there is
+/// one and to the violation action if not.
+struct FilterBuilder {
+    /// The instructions of the rule being collected.
+    program: Vec<SockFilter>,
+    /// The rules collected so far, in the order they were added.
+    rules: Vec<(u32, Vec<SockFilter>)>,
+}
+
+impl FilterBuilder {
+    /// Collects the rules for the given profile.
+    fn for_profile(profile: &Profile) -> FilterBuilder {
+        let mut filter = FilterBuilder {
+            program: Vec::new(),
+            rules: Vec::new(),
         };
         filter.allow_syscalls(&ALLOWED_SYSCALLS);
+        filter.allow_syscalls(&ALLOWED_SYSCALLS_FOR_EVENT_LOOPS);
 
-        if profile.allowed_operations().iter().any(|operation| {
-            match *operation {
-                Operation::FileReadAll(_) | Operation::FileReadMetadata(_) => true,
-                _ => false,
+        // System calls the profile names are allowed with any arguments, ahead of the rules below
+        // that would check them or make them fail.
+        for operation in profile.allowed_operations() {
+            if let Operation::PlatformSpecific(platform::Operation::Syscall(number)) = *operation {
+                filter.allow_syscalls(&[number])
             }
-        }) {
+        }
+
+        // Only allow pipes and event counters to be made close-on-exec or nonblocking, not, say,
+        // packet-mode pipes or semaphores.
+        for &(syscall, cloexec, nonblock) in [
+            (libc::SYS_pipe2, O_CLOEXEC, O_NONBLOCK),
+            (libc::SYS_eventfd2, EFD_CLOEXEC, EFD_NONBLOCK),
+        ].iter() {
+            filter.if_syscall_is(syscall as u32, |filter| {
+                for flags in [0, cloexec, nonblock, cloexec | nonblock].iter() {
+                    filter.if_arg1_is(*flags as u32, |filter| filter.allow_this_syscall())
+                }
+            })
+        }
+
+        // The Rust standard library checks that a descriptor is still open when it is dropped,
+        // and a sandboxed process may drop one it opened before activation, such as its end of a
+        // channel to its parent.
+        filter.if_syscall_is(libc::SYS_fcntl as u32, |filter| {
+            filter.if_arg1_is(libc::F_GETFD as u32, |filter| filter.allow_this_syscall())
+        });
+
+        // Random bytes may be read whether or not the pool is ready to give them, but not from the
+        // pool that `GRND_RANDOM` drains. Other flags fail the way they do on kernels that don't
+        // know them, so that, say, the Rust standard library falls back from `GRND_INSECURE` to
+        // `GRND_NONBLOCK`.
+        filter.if_syscall_is(libc::SYS_getrandom as u32, |filter| {
+            for flags in [0, GRND_NONBLOCK].iter() {
+                filter.if_arg2_is(*flags, |filter| filter.allow_this_syscall())
+            }
+            filter.fail_this_syscall(libc::EINVAL)
+        });
+
+        if profile.is_fd_only() {
+            filter.allow_syscalls(&ALLOWED_SYSCALLS_FOR_FD_ONLY);
+
+            // The C library's `fstat()` may be `newfstatat()` on the descriptor with an empty
+            // path. With nothing in the jail, no other path could be found anyway.
+            filter.if_syscall_is(libc::SYS_newfstatat as u32, |filter| {
+                filter.if_arg3_has_set(libc::AT_EMPTY_PATH as u32, |filter| {
+                    filter.allow_this_syscall()
+                })
+            });
+
+            // Make probing the filesystem fail as if it were empty, instead of violating the
+            // profile.
+            for &syscall in PATH_SYSCALLS.iter() {
+                filter.if_syscall_is(syscall, |filter| filter.fail_this_syscall(libc::ENOENT))
+            }
+        }
+
+        let file_write = profile.allowed_operations().iter().any(|operation| {
+            matches!(*operation, Operation::FileWriteAll(_))
+        });
+        let file_metadata_write = profile.allowed_operations().iter().any(|operation| {
+            matches!(*operation, Operation::FileWriteMetadata(_))
+        });
+        let file_append = profile.allowed_operations().iter().any(|operation| {
+            matches!(*operation, Operation::FileAppend(_))
+        });
+        let file_read = profile.allowed_operations().iter().any(|operation| {
+            matches!(*operation, Operation::FileReadAll(_) | Operation::FileReadMetadata(_))
+        });
+        // The dynamic loader reads the libraries of the programs it runs.
+        let process_exec = profile.allowed_operations().iter().any(|operation| {
+            matches!(*operation, Operation::ProcessExec(_))
+        });
+        // System information includes a few files in `/proc`.
+        let system_info_read = profile.allowed_operations().contains(&Operation::SystemInfoRead);
+        let standard_devices = profile.allowed_operations().contains(&Operation::StandardDevices);
+        let mount_proc_operation = Operation::PlatformSpecific(platform::Operation::MountProc);
+        let mount_proc = profile.allowed_operations().contains(&mount_proc_operation);
+        if file_read || file_write || file_metadata_write || file_append || process_exec ||
+                system_info_read || standard_devices || mount_proc {
             filter.allow_syscalls(&ALLOWED_SYSCALLS_FOR_FILE_READ);
+            if file_metadata_write {
+                filter.allow_syscalls(&ALLOWED_SYSCALLS_FOR_FILE_METADATA_WRITE);
+            }
+
+            // Only allow file reading, or writing if the profile allows it anywhere. The jail
+            // keeps writes to the paths the profile allows writing.
+            let mut open_flags = O_RDONLY | O_CLOEXEC | O_NOCTTY | O_NONBLOCK;
+            if file_write {
+                filter.allow_syscalls(&ALLOWED_SYSCALLS_FOR_FILE_WRITE);
+                open_flags |= O_WRONLY | O_RDWR | O_CREAT | O_TRUNC | O_APPEND;
+            } else if standard_devices {
+                // Shells open `/dev/null` with `O_CREAT | O_TRUNC` too, which do nothing to a
+                // device and can't create files in the read-only jail.
+                open_flags |= O_WRONLY | O_RDWR | O_CREAT | O_TRUNC | O_APPEND;
+            }
 
-            // Only allow file reading.
+            // Appending alone only allows opening for writing with `O_APPEND`, and makes cutting
+            // files short fail instead of violating the profile.
+            let append_only = file_append && !file_write;
+            let append_flags = open_flags | O_WRONLY | O_RDWR | O_CREAT | O_APPEND;
+            if append_only {
+                filter.allow_syscalls(&ALLOWED_SYSCALLS_FOR_FILE_APPEND);
+                for &syscall in [libc::SYS_fallocate, libc::SYS_ftruncate].iter() {
+                    filter.if_syscall_is(syscall as u32,
+                                         |filter| filter.fail_this_syscall(libc::EPERM))
+                }
+            }
+            #[cfg(not(any(target_arch="aarch64", target_arch="riscv64")))]
             filter.if_syscall_is(libc::SYS_open as u32, |filter| {
-                filter.if_arg1_hasnt_set(!(O_RDONLY | O_CLOEXEC | O_NOCTTY | O_NONBLOCK) as u32,
-                                         |filter| filter.allow_this_syscall())
+                filter.if_arg1_hasnt_set(!open_flags as u32, |filter| filter.allow_this_syscall());
+                if append_only {
+                    filter.if_arg1_has_set(O_APPEND as u32, |filter| {
+                        filter.if_arg1_hasnt_set(!append_flags as u32,
+                                                 |filter| filter.allow_this_syscall())
+                    })
+                }
+            });
+            // `openat()` takes the flags one argument later. aarch64 and riscv64 have no
+            // `open()` at all.
+            filter.if_syscall_is(libc::SYS_openat as u32, |filter| {
+                filter.if_arg2_hasnt_set(!open_flags as u32, |filter| filter.allow_this_syscall());
+                if append_only {
+                    filter.if_arg2_has_set(O_APPEND as u32, |filter| {
+                        filter.if_arg2_hasnt_set(!append_flags as u32,
+                                                 |filter| filter.allow_this_syscall())
+                    })
+                }
             });
 
             // Only allow the `FIONREAD` or `FIOCLEX` `ioctl`s to be performed.
@@ -241,14 +1170,37 @@ impl Filter {
             })
         }
 
-        if profile.allowed_operations().iter().any(|operation| {
-            match *operation {
-                Operation::NetworkOutbound(_) => true,
-                _ => false,
-            }
+        let address_patterns: Vec<&AddressPattern> =
+            profile.allowed_operations().iter().filter_map(|operation| {
+                match *operation {
+                    Operation::NetworkOutbound(ref address_pattern) |
+                    Operation::NetworkInbound(ref address_pattern) => Some(address_pattern),
+                    _ => None,
+                }
+            }).collect();
+        if address_patterns.is_empty() {
+            // No sockets at all.
+        } else if address_patterns.iter().all(|address_pattern| {
+            matches!(**address_pattern, AddressPattern::Udp(_))
         }) {
             filter.allow_syscalls(&ALLOWED_SYSCALLS_FOR_NETWORK_OUTBOUND);
 
+            // Only allow IPv4 and IPv6 datagram sockets to be created, so that allowing UDP
+            // doesn't allow TCP too.
+            filter.if_syscall_is(libc::SYS_socket as u32, |filter| {
+                for family in [AF_INET, AF_INET6].iter() {
+                    filter.if_arg0_is(*family as u32, |filter| {
+                        for flags in [0, SOCK_CLOEXEC, SOCK_NONBLOCK, SOCK_CLOEXEC | SOCK_NONBLOCK]
+                                .iter() {
+                            filter.if_arg1_is((SOCK_DGRAM | *flags) as u32,
+                                              |filter| filter.allow_this_syscall())
+                        }
+                    })
+                }
+            })
+        } else {
+            filter.allow_syscalls(&ALLOWED_SYSCALLS_FOR_NETWORK_OUTBOUND);
+
             // Only allow Unix, IPv4, IPv6, and netlink route sockets to be created.
             filter.if_syscall_is(libc::SYS_socket as u32, |filter| {
                 filter.if_arg0_is(AF_UNIX as u32, |filter| filter.allow_this_syscall());
@@ -260,7 +1212,44 @@ impl Filter {
             })
         }
 
-        // Only allow normal threads to be created.
+        if profile.allowed_operations().iter().any(|operation| {
+            matches!(*operation, Operation::NetworkInbound(_))
+        }) {
+            filter.allow_syscalls(&ALLOWED_SYSCALLS_FOR_NETWORK_INBOUND);
+
+            // Only allow `SO_REUSEADDR` to be set, so that listeners can be restarted.
+            filter.if_syscall_is(libc::SYS_setsockopt as u32, |filter| {
+                filter.if_arg1_is(SOL_SOCKET as u32, |filter| {
+                    filter.if_arg2_is(SO_REUSEADDR as u32, |filter| filter.allow_this_syscall())
+                })
+            })
+        }
+
+        if process_exec {
+            filter.allow_syscalls(&ALLOWED_SYSCALLS_FOR_PROCESS_EXEC);
+        }
+
+        if system_info_read {
+            filter.allow_syscalls(&ALLOWED_SYSCALLS_FOR_SYSTEM_INFO_READ);
+
+            // The C library's `getrlimit()` is `prlimit64()` on this process with no new limits.
+            // The pointer to the new limits is checked in full, so that one whose low half
+            // happens to be zero can't slip through.
+            filter.if_syscall_is(libc::SYS_prlimit64 as u32, |filter| {
+                filter.if_arg0_is(0, |filter| {
+                    filter.if_arg2_is_u64(0, |filter| filter.allow_this_syscall())
+                })
+            })
+        }
+
+        // `clone3()` passes its flags in memory, where the filter can't check them. Failing with
+        // `ENOSYS` makes the C library and the Rust runtime fall back to `clone()`, for threads
+        // as well as processes.
+        filter.if_syscall_is(libc::SYS_clone3 as u32,
+                             |filter| filter.fail_this_syscall(libc::ENOSYS));
+
+        // Only allow normal threads to be created, and processes forked if the profile allows
+        // running programs.
         filter.if_syscall_is(libc::SYS_clone as u32, |filter| {
             filter.if_arg0_is((CLONE_VM |
                                CLONE_FS |
@@ -271,7 +1260,25 @@ impl Filter {
                                CLONE_SETTLS |
                                CLONE_PARENT_SETTID |
                                CLONE_CHILD_CLEARTID) as u32,
-                              |filter| filter.allow_this_syscall())
+                              |filter| filter.allow_this_syscall());
+            if process_exec {
+                for flags in FORK_CLONE_FLAGS.iter() {
+                    filter.if_arg0_is(*flags as u32, |filter| filter.allow_this_syscall())
+                }
+            }
+        });
+
+        // Only allow handlers to be installed for the C library's own signals, which it does
+        // before starting the first thread.
+        filter.if_syscall_is(libc::SYS_rt_sigaction as u32, |filter| {
+            filter.if_arg0_is(SIGCANCEL as u32, |filter| filter.allow_this_syscall());
+            filter.if_arg0_is(SIGSETXID as u32, |filter| filter.allow_this_syscall())
+        });
+
+        // Only allow the read-only `prctl`s that `ChildSandbox::verify()` uses.
+        filter.if_syscall_is(libc::SYS_prctl as u32, |filter| {
+            filter.if_arg0_is(PR_GET_SECCOMP as u32, |filter| filter.allow_this_syscall());
+            filter.if_arg0_is(PR_GET_NO_NEW_PRIVS as u32, |filter| filter.allow_this_syscall())
         });
 
         // Only allow the POSIX values for `madvise`.
@@ -287,127 +1294,467 @@ impl Filter {
             }
         });
 
-        filter.program.extend_from_slice(&FILTER_EPILOGUE);
         filter
     }
 
-    /// Dumps this filter to a temporary file.
-    #[cfg(dump_bpf_sockets)]
-    pub fn dump(&self) {
-        let path = CString::from_slice(b"/tmp/gaol-bpf.XXXXXX");
-        let mut path = path.as_bytes_with_nul().to_vec();
-        let fd = unsafe {
-            libc::mkstemp(path.as_mut_ptr() as *mut c_char)
-        };
-        let nbytes = self.program.len() * mem::size_of::<sock_filter>();
-        unsafe {
-            assert!(libc::write(fd, self.program.as_ptr() as *const c_void, nbytes as u64) ==
-                    nbytes as i64);
-            libc::close(fd);
+    fn allow_this_syscall(&mut self) {
+        self.program.push(ALLOW_SYSCALL)
+    }
+
+    fn fail_this_syscall(&mut self, errno: c_int) {
+        self.program.push(SockFilter {
+            code: RET + K,
+            k: SECCOMP_RET_ERRNO | errno as u32,
+            jt: 0,
+            jf: 0,
+        })
+    }
+
+    fn allow_syscalls(&mut self, syscalls: &[u32]) {
+        for &syscall in syscalls.iter() {
+            self.if_syscall_is(syscall, |filter| filter.allow_this_syscall())
         }
     }
 
-    #[cfg(not(dump_bpf_sockets))]
-    pub fn dump(&self) {}
+    /// Adds a rule for the system call `number`, made of the instructions `then` adds.
+    fn if_syscall_is<F>(&mut self, number: u32, mut then: F)
+                        where F: FnMut(&mut FilterBuilder) {
+        let outer = mem::take(&mut self.program);
+        then(self);
+        let rule = mem::replace(&mut self.program, outer);
+        self.rules.push((number, rule))
+    }
 
-    /// Activates this filter, applying all of its restrictions forevermore. This can only be done
-    /// once.
-    pub fn activate(&self) -> Result<(),c_int> {
-        unsafe {
-            let result = libc::prctl(PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0);
-            if result != 0 {
-                return Err(result)
-            }
+    fn if_arg0_is<F>(&mut self, value: u32, then: F) where F: FnMut(&mut FilterBuilder) {
+        self.if_arg_is(0, value as u64, then)
+    }
 
-            let program = sock_fprog {
-                len: self.program.len() as c_ushort,
-                filter: self.program.as_ptr(),
-            };
-            let result = libc::prctl(PR_SET_SECCOMP,
-                                     SECCOMP_MODE_FILTER,
-                                     &program as *const sock_fprog as usize as c_ulong,
-                                     !0,
-                                     0);
-            if result == 0 {
-                Ok(())
-            } else {
-                Err(result)
-            }
-        }
+    fn if_arg1_is<F>(&mut self, value: u32, then: F) where F: FnMut(&mut FilterBuilder) {
+        self.if_arg_is(1, value as u64, then)
     }
 
-    fn allow_this_syscall(&mut self) {
-        self.program.push(ALLOW_SYSCALL)
+    #[cfg(not(any(target_arch="aarch64", target_arch="riscv64")))]
+    fn if_arg1_hasnt_set<F>(&mut self, value: u32, then: F) where F: FnMut(&mut FilterBuilder) {
+        self.if_arg_hasnt_set(1, value, then)
     }
 
-    fn allow_syscalls(&mut self, syscalls: &[u32]) {
-        for &syscall in syscalls.iter() {
-            self.if_syscall_is(syscall, |filter| filter.allow_this_syscall())
-        }
+    #[cfg(not(any(target_arch="aarch64", target_arch="riscv64")))]
+    fn if_arg1_has_set<F>(&mut self, value: u32, then: F) where F: FnMut(&mut FilterBuilder) {
+        self.if_arg_has_set(1, value, then)
+    }
+
+    fn if_arg2_is<F>(&mut self, value: u32, then: F) where F: FnMut(&mut FilterBuilder) {
+        self.if_arg_is(2, value as u64, then)
+    }
+
+    /// Like `if_arg2_is()`, for a value that may not fit in the low half.
+    fn if_arg2_is_u64<F>(&mut self, value: u64, then: F) where F: FnMut(&mut FilterBuilder) {
+        self.if_arg_is(2, value, then)
+    }
+
+    fn if_arg2_has_set<F>(&mut self, value: u32, then: F) where F: FnMut(&mut FilterBuilder) {
+        self.if_arg_has_set(2, value, then)
     }
 
-    fn if_syscall_is<F>(&mut self, number: u32, then: F) where F: FnMut(&mut Filter) {
-        self.program.push(EXAMINE_SYSCALL);
-        self.if_k_is(number, then)
+    fn if_arg2_hasnt_set<F>(&mut self, value: u32, then: F) where F: FnMut(&mut FilterBuilder) {
+        self.if_arg_hasnt_set(2, value, then)
     }
 
-    fn if_arg0_is<F>(&mut self, value: u32, then: F) where F: FnMut(&mut Filter) {
-        self.program.push(EXAMINE_ARG_0);
-        self.if_k_is(value, then)
+    fn if_arg3_has_set<F>(&mut self, value: u32, then: F) where F: FnMut(&mut FilterBuilder) {
+        self.if_arg_has_set(3, value, then)
     }
 
-    fn if_arg1_is<F>(&mut self, value: u32, then: F) where F: FnMut(&mut Filter) {
-        self.program.push(EXAMINE_ARG_1);
-        self.if_k_is(value, then)
+    /// Runs `then` if argument `index` is `value`: the low half, and then the high half.
+    fn if_arg_is<F>(&mut self, index: u32, value: u64, mut then: F)
+                    where F: FnMut(&mut FilterBuilder) {
+        self.program.push(examine_arg(index));
+        self.if_k_is(value as u32, |filter| {
+            filter.program.push(examine_arg_high(index));
+            filter.if_k_is((value >> 32) as u32, &mut then)
+        })
     }
 
-    fn if_arg1_hasnt_set<F>(&mut self, value: u32, then: F) where F: FnMut(&mut Filter) {
-        self.program.push(EXAMINE_ARG_1);
-        self.if_k_hasnt_set(value, then)
+    /// Runs `then` if argument `index` has any of the bits of `value` set. They are all in the
+    /// low half, so the high half doesn't matter.
+    fn if_arg_has_set<F>(&mut self, index: u32, value: u32, then: F)
+                         where F: FnMut(&mut FilterBuilder) {
+        self.program.push(examine_arg(index));
+        self.if_k_has_set(value, then)
     }
 
-    fn if_arg2_is<F>(&mut self, value: u32, then: F) where F: FnMut(&mut Filter) {
-        self.program.push(EXAMINE_ARG_2);
-        self.if_k_is(value, then)
+    /// Runs `then` if argument `index` has none of the bits of `value` set, nor any bit of the
+    /// high half, where no flag that could be allowed lives.
+    fn if_arg_hasnt_set<F>(&mut self, index: u32, value: u32, mut then: F)
+                           where F: FnMut(&mut FilterBuilder) {
+        self.program.push(examine_arg(index));
+        self.if_k_hasnt_set(value, |filter| {
+            filter.program.push(examine_arg_high(index));
+            filter.if_k_hasnt_set(!0, &mut then)
+        })
     }
 
-    fn if_k_is<F>(&mut self, value: u32, mut then: F) where F: FnMut(&mut Filter) {
+    fn if_k_is<F>(&mut self, value: u32, mut then: F) where F: FnMut(&mut FilterBuilder) {
         let index = self.program.len();
-        self.program.push(sock_filter {
+        self.program.push(SockFilter {
             code: JMP + JEQ + K,
             k: value,
             jt: 0,
             jf: 0,
         });
         then(self);
-        self.program[index].jf = (self.program.len() - index - 1) as u8;
+        self.program[index].jf = jump_offset(self.program.len() - index - 1);
     }
 
-    fn if_k_hasnt_set<F>(&mut self, value: u32, mut then: F) where F: FnMut(&mut Filter) {
+    fn if_k_has_set<F>(&mut self, value: u32, mut then: F) where F: FnMut(&mut FilterBuilder) {
         let index = self.program.len();
-        self.program.push(sock_filter {
+        self.program.push(SockFilter {
             code: JMP + JSET + K,
             k: value,
             jt: 0,
             jf: 0,
         });
         then(self);
-        self.program[index].jt = (self.program.len() - index - 1) as u8;
+        self.program[index].jf = jump_offset(self.program.len() - index - 1);
+    }
+
+    fn if_k_hasnt_set<F>(&mut self, value: u32, mut then: F) where F: FnMut(&mut FilterBuilder) {
+        let index = self.program.len();
+        self.program.push(SockFilter {
+            code: JMP + JSET + K,
+            k: value,
+            jt: 0,
+            jf: 0,
+        });
+        then(self);
+        self.program[index].jt = jump_offset(self.program.len() - index - 1);
+    }
+
+    /// Lays out the rules as a chain: each system call is compared against in turn, in the order
+    /// its rules were added, before `epilogue`.
+    fn linear(self, epilogue: SockFilter) -> Vec<SockFilter> {
+        let mut program = FILTER_PROLOGUE.to_vec();
+        for (syscall, rule) in self.rules {
+            program.push(EXAMINE_SYSCALL);
+            program.push(SockFilter {
+                code: JMP + JEQ + K,
+                k: syscall,
+                jt: 0,
+                jf: jump_offset(rule.len()),
+            });
+            program.extend(rule)
+        }
+        program.push(epilogue);
+        program
+    }
+
+    /// Lays out the rules as a binary search over the sorted system call numbers, so that every
+    /// system call is found in a logarithmic number of comparisons, with the rules for each
+    /// system call at the leaves, in the order they were added. Everything that falls through
+    /// jumps to `epilogue`, which is last.
+    ///
+    /// Conditional jumps only ever skip one instruction, and unconditional ones take 32-bit
+    /// offsets, so no program is too long to lay out this way.
+    fn binary_search(self, epilogue: SockFilter) -> Vec<SockFilter> {
+        let mut leaves: BTreeMap<u32, Vec<SockFilter>> = BTreeMap::new();
+        for (syscall, rule) in self.rules {
+            leaves.entry(syscall).or_default().extend(rule)
+        }
+        let leaves: Vec<(u32, Vec<SockFilter>)> = leaves.into_iter().collect();
+
+        let mut program = FILTER_PROLOGUE.to_vec();
+        program.push(EXAMINE_SYSCALL);
+        let mut violations = Vec::new();
+        search(&mut program, &leaves, &mut violations);
+        for index in violations {
+            program[index].k = (program.len() - index - 1) as u32
+        }
+        program.push(epilogue);
+        program
+    }
+}
+
+/// Lays out a binary search of `leaves`, sorted by system call number, for the system call
+/// number in the accumulator. The indices of the jumps to the violation action, whose offsets
+/// aren't known yet, are added to `violations`.
+fn search(program: &mut Vec<SockFilter>,
+          leaves: &[(u32, Vec<SockFilter>)],
+          violations: &mut Vec<usize>) {
+    match *leaves {
+        [] => {
+            violations.push(program.len());
+            program.push(JUMP)
+        }
+        [(syscall, ref rule)] => {
+            program.push(SockFilter {
+                code: JMP + JEQ + K,
+                k: syscall,
+                jt: 1,
+                jf: 0,
+            });
+            violations.push(program.len());
+            program.push(JUMP);
+            program.extend_from_slice(rule);
+            // A rule that is a lone `ret` never falls through.
+            if rule.len() != 1 || rule[0].code != RET + K {
+                violations.push(program.len());
+                program.push(JUMP)
+            }
+        }
+        _ => {
+            let middle = leaves.len() / 2;
+            program.push(SockFilter {
+                code: JMP + JGE + K,
+                k: leaves[middle].0,
+                jt: 0,
+                jf: 1,
+            });
+            let upper = program.len();
+            program.push(JUMP);
+            search(program, &leaves[..middle], violations);
+            program[upper].k = (program.len() - upper - 1) as u32;
+            search(program, &leaves[middle..], violations)
+        }
     }
 }
 
+/// Returns `length` as the offset of a conditional jump over that many instructions. Those only
+/// have eight bits, so a longer rule is a bug in the filter.
+fn jump_offset(length: usize) -> u8 {
+    u8::try_from(length).expect("seccomp rule too long for a conditional jump")
+}
+
+fn fprog(program: &[SockFilter]) -> sock_fprog {
+    sock_fprog {
+        len: program.len() as c_ushort,
+        filter: program.as_ptr(),
+    }
+}
+
+/// Returns the error the last failed system call left, as a failure to install the filter.
+fn seccomp_error() -> SandboxError {
+    SandboxError::SeccompFailed(io::Error::last_os_error())
+}
+
+/// Returns the 32-bit word at `offset` in the `seccomp_data` the kernel would pass to a filter
+/// for this system call. The instruction pointer is always zero.
+fn load_seccomp_data_word(syscall: u32, args: &[u64; 6], offset: u32) -> u32 {
+    match offset {
+        SYSCALL_NR_OFFSET => syscall,
+        ARCH_NR_OFFSET => ARCH_NR,
+        offset if (ARGS_OFFSET..ARGS_OFFSET + 48).contains(&offset) => {
+            let arg = args[((offset - ARGS_OFFSET) / 8) as usize];
+            if (offset - ARGS_OFFSET) % 8 == LOW_HALF_OFFSET {
+                arg as u32
+            } else {
+                (arg >> 32) as u32
+            }
+        }
+        _ => 0,
+    }
+}
+
+/// Returns the name of a system call that gaol's filters or tracer know about.
+pub fn syscall_name(number: i64) -> Option<&'static str> {
+    Some(match number {
+        libc::SYS_accept => "accept",
+        libc::SYS_accept4 => "accept4",
+        #[cfg(not(any(target_arch="aarch64", target_arch="riscv64")))]
+        libc::SYS_access => "access",
+        #[cfg(not(any(target_arch="aarch64", target_arch="riscv64")))]
+        libc::SYS_arch_prctl => "arch_prctl",
+        libc::SYS_bind => "bind",
+        libc::SYS_brk => "brk",
+        libc::SYS_capget => "capget",
+        libc::SYS_chdir => "chdir",
+        #[cfg(not(any(target_arch="aarch64", target_arch="riscv64")))]
+        libc::SYS_chmod => "chmod",
+        #[cfg(not(any(target_arch="aarch64", target_arch="riscv64")))]
+        libc::SYS_chown => "chown",
+        libc::SYS_clock_getres => "clock_getres",
+        libc::SYS_clock_gettime => "clock_gettime",
+        libc::SYS_clock_nanosleep => "clock_nanosleep",
+        libc::SYS_clone => "clone",
+        libc::SYS_clone3 => "clone3",
+        libc::SYS_close => "close",
+        libc::SYS_connect => "connect",
+        #[cfg(not(any(target_arch="aarch64", target_arch="riscv64")))]
+        libc::SYS_creat => "creat",
+        libc::SYS_epoll_create1 => "epoll_create1",
+        libc::SYS_epoll_ctl => "epoll_ctl",
+        libc::SYS_epoll_pwait => "epoll_pwait",
+        #[cfg(not(any(target_arch="aarch64", target_arch="riscv64")))]
+        libc::SYS_epoll_wait => "epoll_wait",
+        libc::SYS_eventfd2 => "eventfd2",
+        libc::SYS_execve => "execve",
+        libc::SYS_execveat => "execveat",
+        libc::SYS_exit => "exit",
+        libc::SYS_exit_group => "exit_group",
+        libc::SYS_faccessat => "faccessat",
+        libc::SYS_faccessat2 => "faccessat2",
+        libc::SYS_fallocate => "fallocate",
+        libc::SYS_fchmod => "fchmod",
+        libc::SYS_fchmodat => "fchmodat",
+        libc::SYS_fchown => "fchown",
+        libc::SYS_fchownat => "fchownat",
+        libc::SYS_fcntl => "fcntl",
+        #[cfg(not(any(target_arch="aarch64", target_arch="riscv64")))]
+        libc::SYS_fork => "fork",
+        libc::SYS_fstat => "fstat",
+        libc::SYS_fsync => "fsync",
+        libc::SYS_ftruncate => "ftruncate",
+        libc::SYS_futex => "futex",
+        libc::SYS_getcwd => "getcwd",
+        libc::SYS_getdents64 => "getdents64",
+        libc::SYS_getpid => "getpid",
+        libc::SYS_getrandom => "getrandom",
+        #[cfg(not(target_arch="aarch64"))]
+        libc::SYS_getrlimit => "getrlimit",
+        #[cfg(target_arch="aarch64")]
+        163 => "getrlimit",
+        libc::SYS_getsockname => "getsockname",
+        libc::SYS_getsockopt => "getsockopt",
+        libc::SYS_gettid => "gettid",
+        libc::SYS_gettimeofday => "gettimeofday",
+        libc::SYS_getuid => "getuid",
+        libc::SYS_ioctl => "ioctl",
+        libc::SYS_kill => "kill",
+        #[cfg(not(any(target_arch="aarch64", target_arch="riscv64")))]
+        libc::SYS_lchown => "lchown",
+        #[cfg(not(any(target_arch="aarch64", target_arch="riscv64")))]
+        libc::SYS_link => "link",
+        libc::SYS_linkat => "linkat",
+        libc::SYS_listen => "listen",
+        libc::SYS_lseek => "lseek",
+        #[cfg(not(any(target_arch="aarch64", target_arch="riscv64")))]
+        libc::SYS_lstat => "lstat",
+        libc::SYS_madvise => "madvise",
+        #[cfg(not(any(target_arch="aarch64", target_arch="riscv64")))]
+        libc::SYS_mkdir => "mkdir",
+        libc::SYS_mkdirat => "mkdirat",
+        libc::SYS_mmap => "mmap",
+        libc::SYS_mprotect => "mprotect",
+        libc::SYS_munmap => "munmap",
+        libc::SYS_newfstatat => "newfstatat",
+        #[cfg(not(any(target_arch="aarch64", target_arch="riscv64")))]
+        libc::SYS_open => "open",
+        libc::SYS_openat => "openat",
+        libc::SYS_openat2 => "openat2",
+        libc::SYS_pidfd_getfd => "pidfd_getfd",
+        libc::SYS_pidfd_open => "pidfd_open",
+        libc::SYS_pipe2 => "pipe2",
+        #[cfg(not(any(target_arch="aarch64", target_arch="riscv64")))]
+        libc::SYS_poll => "poll",
+        libc::SYS_ppoll => "ppoll",
+        libc::SYS_prctl => "prctl",
+        libc::SYS_pread64 => "pread64",
+        libc::SYS_prlimit64 => "prlimit64",
+        libc::SYS_pwrite64 => "pwrite64",
+        libc::SYS_pwritev => "pwritev",
+        libc::SYS_read => "read",
+        #[cfg(not(any(target_arch="aarch64", target_arch="riscv64")))]
+        libc::SYS_readlink => "readlink",
+        libc::SYS_readlinkat => "readlinkat",
+        libc::SYS_recvfrom => "recvfrom",
+        libc::SYS_recvmsg => "recvmsg",
+        #[cfg(not(any(target_arch="aarch64", target_arch="riscv64")))]
+        libc::SYS_rename => "rename",
+        #[cfg(not(any(target_arch="aarch64", target_arch="riscv64")))]
+        libc::SYS_renameat => "renameat",
+        #[cfg(target_arch="aarch64")]
+        38 => "renameat",
+        libc::SYS_renameat2 => "renameat2",
+        #[cfg(not(any(target_arch="aarch64", target_arch="riscv64")))]
+        libc::SYS_rmdir => "rmdir",
+        libc::SYS_rseq => "rseq",
+        libc::SYS_rt_sigaction => "rt_sigaction",
+        libc::SYS_rt_sigprocmask => "rt_sigprocmask",
+        libc::SYS_rt_sigreturn => "rt_sigreturn",
+        libc::SYS_sched_getaffinity => "sched_getaffinity",
+        libc::SYS_seccomp => "seccomp",
+        libc::SYS_sendmmsg => "sendmmsg",
+        libc::SYS_sendmsg => "sendmsg",
+        libc::SYS_sendto => "sendto",
+        libc::SYS_set_robust_list => "set_robust_list",
+        libc::SYS_set_tid_address => "set_tid_address",
+        libc::SYS_setsockopt => "setsockopt",
+        libc::SYS_sigaltstack => "sigaltstack",
+        libc::SYS_socket => "socket",
+        #[cfg(not(any(target_arch="aarch64", target_arch="riscv64")))]
+        libc::SYS_stat => "stat",
+        libc::SYS_statfs => "statfs",
+        libc::SYS_statx => "statx",
+        #[cfg(not(any(target_arch="aarch64", target_arch="riscv64")))]
+        libc::SYS_symlink => "symlink",
+        libc::SYS_symlinkat => "symlinkat",
+        libc::SYS_sysinfo => "sysinfo",
+        libc::SYS_timerfd_create => "timerfd_create",
+        libc::SYS_timerfd_settime => "timerfd_settime",
+        libc::SYS_truncate => "truncate",
+        libc::SYS_uname => "uname",
+        #[cfg(not(any(target_arch="aarch64", target_arch="riscv64")))]
+        libc::SYS_unlink => "unlink",
+        libc::SYS_unlinkat => "unlinkat",
+        libc::SYS_utimensat => "utimensat",
+        #[cfg(not(any(target_arch="aarch64", target_arch="riscv64")))]
+        libc::SYS_vfork => "vfork",
+        libc::SYS_wait4 => "wait4",
+        libc::SYS_waitid => "waitid",
+        libc::SYS_write => "write",
+        libc::SYS_writev => "writev",
+        _ => return None,
+    })
+}
+
+/// One instruction of a filter's program, laid out as the kernel's `struct sock_filter`.
 #[repr(C)]
-#[derive(Copy, Clone)]
-struct sock_filter {
-    code: u16,
-    jt: u8,
-    jf: u8,
-    k: u32,
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct SockFilter {
+    /// The operation, made of the BPF instruction class, size, mode, and source bits.
+    pub code: u16,
+    /// How many instructions a conditional jump skips if its comparison holds.
+    pub jt: u8,
+    /// How many instructions a conditional jump skips if its comparison fails.
+    pub jf: u8,
+    /// The operand: an offset to load from, a value to compare with, or a value to return.
+    pub k: u32,
+}
+
+impl SockFilter {
+    /// Returns a human-readable form of this instruction, which is at `index` in its program.
+    fn disassemble(&self, index: usize) -> String {
+        let target = |offset: u8| index + 1 + offset as usize;
+        match self.code {
+            code if code == LD + W + ABS => format!("ld   [{}]", self.k),
+            code if code == JMP + JEQ + K => {
+                format!("jeq  #{:#010x} jt {:04} jf {:04}",
+                        self.k,
+                        target(self.jt),
+                        target(self.jf))
+            }
+            code if code == JMP + JGE + K => {
+                format!("jge  #{:#010x} jt {:04} jf {:04}",
+                        self.k,
+                        target(self.jt),
+                        target(self.jf))
+            }
+            code if code == JMP + JA + K => format!("ja   {:04}", index + 1 + self.k as usize),
+            code if code == JMP + JSET + K => {
+                format!("jset #{:#010x} jt {:04} jf {:04}",
+                        self.k,
+                        target(self.jt),
+                        target(self.jf))
+            }
+            code if code == RET + K => format!("ret  #{:#010x}", self.k),
+            code => {
+                format!(".insn code={:#06x} jt={} jf={} k={:#010x}", code, self.jt, self.jf, self.k)
+            }
+        }
+    }
 }
 
 #[repr(C)]
 #[derive(Copy, Clone)]
 struct sock_fprog {
     len: c_ushort,
-    filter: *const sock_filter,
+    filter: *const SockFilter,
 }
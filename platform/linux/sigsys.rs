@@ -0,0 +1,246 @@
+// Copyright 2015 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Reporting system calls that a filter returning `SECCOMP_RET_TRAP` denied, for
+//! `ViolationAction::Trap`, `ChildSandbox::activate_with_violation_reporting()`, and
+//! `ChildSandbox::with_violation_handler()`.
+//!
+//! The handler runs in a process that is already under the filter, so it may only make system
+//! calls every filter allows, and must neither allocate nor take locks. It formats the report
+//! into a buffer on the stack and writes it, or passes it to the embedder's function, and then
+//! makes the denied system call again: `SIGSYS` is blocked while its handler runs, so the kernel
+//! delivers the second one with the default action, and the process dies of `SIGSYS` just as it
+//! would have without reporting.
+
+use libc::{self, c_int, c_uint, c_void};
+use std::io;
+use std::mem;
+use std::ptr;
+use std::sync::atomic::{AtomicI32, AtomicUsize, Ordering};
+
+/// The descriptor reports are written to.
+static REPORT_FD: AtomicI32 = AtomicI32::new(libc::STDERR_FILENO);
+
+/// The function reports are passed to instead, as an address, or zero if there is none.
+static HANDLER: AtomicUsize = AtomicUsize::new(0);
+
+/// A system call the filter denied, as `ChildSandbox::with_violation_handler()` reports it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SysViolationInfo {
+    syscall: i32,
+    arch: u32,
+    args: [u64; 6],
+    call_address: usize,
+}
+
+impl SysViolationInfo {
+    /// Returns the number of the system call, as `syscalls::syscall_nr()` gives it.
+    pub fn syscall(&self) -> i32 {
+        self.syscall
+    }
+
+    /// Returns the `AUDIT_ARCH_*` value of the system call convention it was made with.
+    pub fn arch(&self) -> u32 {
+        self.arch
+    }
+
+    /// Returns the arguments of the system call. They are all zero on architectures other than
+    /// x86-64, aarch64 and riscv64.
+    pub fn args(&self) -> [u64; 6] {
+        self.args
+    }
+
+    /// Returns the address the system call was made from.
+    pub fn call_address(&self) -> usize {
+        self.call_address
+    }
+}
+
+/// The start of a `siginfo_t` for `SIGSYS`, whose fields `libc` doesn't expose.
+#[repr(C)]
+struct SigsysInfo {
+    _signo: c_int,
+    _errno: c_int,
+    _code: c_int,
+    call_addr: *mut c_void,
+    syscall: c_int,
+    arch: c_uint,
+}
+
+/// Installs the handler, which will pass reports to `handler` if given one and write them to `fd`
+/// otherwise.
+pub fn install(fd: c_int, handler: Option<fn(&SysViolationInfo)>) -> io::Result<()> {
+    REPORT_FD.store(fd, Ordering::SeqCst);
+    HANDLER.store(handler.map_or(0, |handler| handler as usize), Ordering::SeqCst);
+    unsafe {
+        let mut action: libc::sigaction = mem::zeroed();
+        let handler: extern "C" fn(c_int, *mut libc::siginfo_t, *mut c_void) = handle_sigsys;
+        action.sa_sigaction = handler as libc::sighandler_t;
+        action.sa_flags = libc::SA_SIGINFO;
+        libc::sigemptyset(&mut action.sa_mask);
+        if libc::sigaction(libc::SIGSYS, &action, ptr::null_mut()) != 0 {
+            return Err(io::Error::last_os_error())
+        }
+    }
+    Ok(())
+}
+
+extern "C" fn handle_sigsys(_: c_int, info: *mut libc::siginfo_t, context: *mut c_void) {
+    let (syscall, arch, call_address) = unsafe {
+        let info = &*(info as *const SigsysInfo);
+        (info.syscall, info.arch, info.call_addr as usize)
+    };
+    let args = arguments(context);
+
+    match HANDLER.load(Ordering::SeqCst) {
+        0 => {
+            let mut report = Report::new();
+            report.push(b"gaol: the sandbox prohibited system call ");
+            report.push_decimal(syscall as u64);
+            report.push(b" (arch 0x");
+            report.push_hex(arch as u64);
+            report.push(b") with arguments 0x");
+            report.push_hex(args[0]);
+            report.push(b", 0x");
+            report.push_hex(args[1]);
+            report.push(b", 0x");
+            report.push_hex(args[2]);
+            report.push(b"\n");
+            report.write(REPORT_FD.load(Ordering::SeqCst));
+        }
+        handler => {
+            let handler: fn(&SysViolationInfo) = unsafe {
+                mem::transmute::<usize, fn(&SysViolationInfo)>(handler)
+            };
+            handler(&SysViolationInfo {
+                syscall,
+                arch,
+                args,
+                call_address,
+            })
+        }
+    }
+
+    unsafe {
+        libc::syscall(syscall as libc::c_long,
+                      args[0],
+                      args[1],
+                      args[2],
+                      args[3],
+                      args[4],
+                      args[5]);
+        // Only reached if the arguments couldn't be recovered and the filter allows the call
+        // with the ones above.
+        libc::_exit(128 + libc::SIGSYS)
+    }
+}
+
+/// The arguments of the denied system call, from the registers saved in `context`.
+#[cfg(target_arch="x86_64")]
+fn arguments(context: *mut c_void) -> [u64; 6] {
+    let registers = unsafe {
+        &(*(context as *const libc::ucontext_t)).uc_mcontext.gregs
+    };
+    [
+        registers[libc::REG_RDI as usize] as u64,
+        registers[libc::REG_RSI as usize] as u64,
+        registers[libc::REG_RDX as usize] as u64,
+        registers[libc::REG_R10 as usize] as u64,
+        registers[libc::REG_R8 as usize] as u64,
+        registers[libc::REG_R9 as usize] as u64,
+    ]
+}
+
+#[cfg(target_arch="aarch64")]
+fn arguments(context: *mut c_void) -> [u64; 6] {
+    let registers = unsafe {
+        &(*(context as *const libc::ucontext_t)).uc_mcontext.regs
+    };
+    [registers[0], registers[1], registers[2], registers[3], registers[4], registers[5]]
+}
+
+/// The arguments are in `a0` to `a5`, which are `x10` to `x15`.
+#[cfg(target_arch="riscv64")]
+fn arguments(context: *mut c_void) -> [u64; 6] {
+    let registers = unsafe {
+        &(*(context as *const libc::ucontext_t)).uc_mcontext.__gregs
+    };
+    [registers[10], registers[11], registers[12], registers[13], registers[14], registers[15]]
+}
+
+/// Elsewhere the arguments are reported as zero.
+#[cfg(not(any(target_arch="x86_64", target_arch="aarch64", target_arch="riscv64")))]
+fn arguments(_: *mut c_void) -> [u64; 6] {
+    [0; 6]
+}
+
+/// A line of text built on the stack.
+struct Report {
+    buffer: [u8; 160],
+    len: usize,
+}
+
+impl Report {
+    fn new() -> Report {
+        Report {
+            buffer: [0; 160],
+            len: 0,
+        }
+    }
+
+    fn push(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            if self.len < self.buffer.len() {
+                self.buffer[self.len] = byte;
+                self.len += 1
+            }
+        }
+    }
+
+    fn push_decimal(&mut self, value: u64) {
+        self.push_digits(value, 10)
+    }
+
+    fn push_hex(&mut self, value: u64) {
+        self.push_digits(value, 16)
+    }
+
+    fn push_digits(&mut self, mut value: u64, radix: u64) {
+        let mut digits = [0; 20];
+        let mut count = 0;
+        loop {
+            digits[count] = b"0123456789abcdef"[(value % radix) as usize];
+            count += 1;
+            value /= radix;
+            if value == 0 {
+                break
+            }
+        }
+        while count > 0 {
+            count -= 1;
+            self.push(&[digits[count]])
+        }
+    }
+
+    fn write(&self, fd: c_int) {
+        let mut written = 0;
+        while written < self.len {
+            let result = unsafe {
+                libc::write(fd,
+                            self.buffer[written..self.len].as_ptr() as *const c_void,
+                            self.len - written)
+            };
+            if result <= 0 {
+                return
+            }
+            written += result as usize
+        }
+    }
+}
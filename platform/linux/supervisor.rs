@@ -0,0 +1,333 @@
+// Copyright 2015 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Sandboxes whose prohibited system calls are decided in userspace, with
+//! `SECCOMP_RET_USER_NOTIF` (Linux 5.0+).
+//!
+//! The parent gives the child one end of a socket pair. When the child activates its sandbox it
+//! installs a filter that hands every system call the profile doesn't allow to a notification
+//! descriptor, and sends that descriptor to the parent over the socket. The parent then receives
+//! a notification for each such call, and the calling thread waits until the parent responds.
+//!
+//! The calling thread stays frozen until it gets a response, so a supervisor that stops reading
+//! notifications or responding hangs the child. The notification's arguments are only a snapshot:
+//! memory that a pointer argument refers to can change after the supervisor looks at it (another
+//! thread of the child can rewrite a path, for example), and the process the notification came
+//! from can die and its PID be reused. Check `SeccompNotifyFd::id_valid()` after reading the
+//! child's memory, and never respond with `SECCOMP_USER_NOTIF_FLAG_CONTINUE` on the strength of
+//! what a pointer argument pointed to.
+
+use crate::error::SandboxError;
+use crate::platform;
+use crate::platform::linux::Sandbox;
+use crate::platform::unix::process::Process;
+use crate::sandbox::{Command, SandboxMethods};
+use crate::telemetry::Mechanism;
+
+use libc::{self, c_int, c_void};
+use std::env;
+use std::io;
+use std::mem;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+use std::ptr;
+
+/// The environment variable through which the parent tells the child which descriptor to send
+/// its notification descriptor through.
+const NOTIFY_SOCKET_ENV_VAR: &str = "GAOL_NOTIFY_SOCKET_FD";
+
+/// A sandbox whose child hands the system calls its profile doesn't allow to this process to
+/// decide, instead of having them handled by the profile's `ViolationAction`.
+pub struct SupervisedSandbox {
+    sandbox: Sandbox,
+}
+
+impl SupervisedSandbox {
+    pub fn new(sandbox: Sandbox) -> SupervisedSandbox {
+        SupervisedSandbox {
+            sandbox,
+        }
+    }
+
+    /// Returns the sandbox that this one starts children in.
+    pub fn sandbox(&self) -> &Sandbox {
+        &self.sandbox
+    }
+
+    /// Starts `command` in the sandbox, returning it together with the descriptor its
+    /// notifications arrive on.
+    ///
+    /// This returns once the child has activated its sandbox, and fails if the child exits
+    /// without doing so. It fails with `Unsupported` if this kernel lacks
+    /// `SECCOMP_RET_USER_NOTIF`, and with `InvalidInput` if the sandbox's options disable the
+    /// seccomp filter.
+    pub fn start(&self, command: &mut Command) -> io::Result<(Process, SeccompNotifyFd)> {
+        if !platform::capabilities().seccomp_user_notif {
+            return Err(io::Error::new(io::ErrorKind::Unsupported,
+                                      "SECCOMP_RET_USER_NOTIF is unavailable"))
+        }
+        if self.sandbox.options.disabled_mechanisms()?.contains(&Mechanism::SeccompFilter) {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                      "supervision needs the seccomp filter"))
+        }
+
+        let (socket, child_socket) = socket_pair()?;
+        command.env(NOTIFY_SOCKET_ENV_VAR, child_socket.as_raw_fd().to_string())
+               .inherit_fd(&child_socket);
+        let process = self.sandbox.start(command)?;
+
+        // Close our copy of the child's end, so that we see the end of the stream if the child
+        // exits before sending its notification descriptor.
+        drop(child_socket);
+        let fd = receive_fd(&socket)?;
+        Ok((process, SeccompNotifyFd {
+            fd,
+        }))
+    }
+}
+
+/// A system call that a supervised child made and that its profile doesn't allow, as received
+/// from `SeccompNotifyFd::recv_notification()`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SeccompNotif {
+    /// Identifies this notification in `SeccompNotifyFd::send_response()`.
+    pub id: u64,
+    /// The PID of the calling thread, in this process's PID namespace.
+    pub pid: u32,
+    /// The system call number.
+    pub syscall: i32,
+    /// The `AUDIT_ARCH_*` value of the calling convention the system call was made with.
+    pub arch: u32,
+    /// The address of the system call instruction.
+    pub instruction_pointer: u64,
+    /// The system call's arguments.
+    pub args: [u64; 6],
+}
+
+/// The descriptor that a supervised child's notifications arrive on. Closing it makes every
+/// system call still waiting on it fail with `ENOSYS`.
+pub struct SeccompNotifyFd {
+    fd: OwnedFd,
+}
+
+impl SeccompNotifyFd {
+    /// Waits for the next notification. Fails with `ENOENT` if the calling thread was killed or
+    /// interrupted by a signal before the notification could be received.
+    pub fn recv_notification(&self) -> io::Result<SeccompNotif> {
+        // The kernel insists on a zeroed structure.
+        let mut notification: libc::seccomp_notif = unsafe {
+            mem::zeroed()
+        };
+        if unsafe {
+            libc::ioctl(self.fd.as_raw_fd(), libc::SECCOMP_IOCTL_NOTIF_RECV, &mut notification)
+        } < 0 {
+            return Err(io::Error::last_os_error())
+        }
+        Ok(SeccompNotif {
+            id: notification.id,
+            pid: notification.pid,
+            syscall: notification.data.nr,
+            arch: notification.data.arch,
+            instruction_pointer: notification.data.instruction_pointer,
+            args: notification.data.args,
+        })
+    }
+
+    /// Responds to the notification `id`, letting the waiting thread go on.
+    ///
+    /// The system call returns `val` if `error` is zero, and fails with the error number `-error`
+    /// otherwise. With `SECCOMP_USER_NOTIF_FLAG_CONTINUE` in `flags` (Linux 5.5+), `error` and
+    /// `val` must be zero and the kernel runs the system call as if it had been allowed. Fails
+    /// with `ENOENT` if the thread is no longer waiting.
+    pub fn send_response(&self, id: u64, error: i32, val: i64, flags: u32) -> io::Result<()> {
+        let mut response = libc::seccomp_notif_resp {
+            id,
+            val,
+            error,
+            flags,
+        };
+        if unsafe {
+            libc::ioctl(self.fd.as_raw_fd(), libc::SECCOMP_IOCTL_NOTIF_SEND, &mut response)
+        } < 0 {
+            return Err(io::Error::last_os_error())
+        }
+        Ok(())
+    }
+
+    /// Returns true if the thread that made notification `id` is still waiting for a response.
+    pub fn id_valid(&self, id: u64) -> bool {
+        unsafe {
+            libc::ioctl(self.fd.as_raw_fd(), libc::SECCOMP_IOCTL_NOTIF_ID_VALID, &id) == 0
+        }
+    }
+
+    /// Receives notifications and responds to each as `callback` decides, until the child and
+    /// every process it started have exited.
+    ///
+    /// Whether each notification is still valid is checked again after `callback` returns, and
+    /// notifications whose thread has stopped waiting, because it was killed or its PID reused
+    /// in the meantime, get no response. Decisions based on the child's memory are still only as
+    /// good as the notes in the module documentation say.
+    ///
+    /// Kernels before Linux 5.8 don't report that a filter has no processes left, so there this
+    /// only returns on an error.
+    pub fn handle_requests<F>(&self, mut callback: F) -> io::Result<()>
+                              where F: FnMut(&SeccompNotif) -> SupervisorResponse {
+        loop {
+            let mut poll_fd = libc::pollfd {
+                fd: self.fd.as_raw_fd(),
+                events: libc::POLLIN,
+                revents: 0,
+            };
+            if unsafe { libc::poll(&mut poll_fd, 1, -1) } < 0 {
+                let err = io::Error::last_os_error();
+                if err.kind() == io::ErrorKind::Interrupted {
+                    continue
+                }
+                return Err(err)
+            }
+            if poll_fd.revents & libc::POLLIN == 0 {
+                if poll_fd.revents & libc::POLLHUP != 0 {
+                    return Ok(())
+                }
+                continue
+            }
+
+            let notification = match self.recv_notification() {
+                Ok(notification) => notification,
+                Err(ref err) if err.raw_os_error() == Some(libc::ENOENT) => continue,
+                Err(err) => return Err(err),
+            };
+            let (error, val, flags) = match callback(&notification) {
+                SupervisorResponse::Continue => {
+                    (0, 0, libc::SECCOMP_USER_NOTIF_FLAG_CONTINUE as u32)
+                }
+                SupervisorResponse::Errno(errno) => (-errno, 0, 0),
+                SupervisorResponse::Return(val) => (0, val, 0),
+            };
+            if !self.id_valid(notification.id) {
+                continue
+            }
+            match self.send_response(notification.id, error, val, flags) {
+                Err(ref err) if err.raw_os_error() == Some(libc::ENOENT) => {}
+                result => result?,
+            }
+        }
+    }
+}
+
+/// How `SeccompNotifyFd::handle_requests()` responds to a notification.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SupervisorResponse {
+    /// The system call runs as if the filter had allowed it, with
+    /// `SECCOMP_USER_NOTIF_FLAG_CONTINUE` (Linux 5.5+). The kernel reads any pointer arguments
+    /// again when it runs the call, so never continue a call on the strength of what they
+    /// pointed to.
+    Continue,
+    /// The system call fails with this error number, for example `libc::EPERM` to deny it.
+    Errno(i32),
+    /// The system call returns this value without running.
+    Return(i64),
+}
+
+impl AsRawFd for SeccompNotifyFd {
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd.as_raw_fd()
+    }
+}
+
+/// Returns the descriptor the parent asked this child to send its notification descriptor
+/// through, if it is supervised.
+pub(crate) fn notify_socket() -> Option<c_int> {
+    let fd = env::var(NOTIFY_SOCKET_ENV_VAR).ok().and_then(|fd| fd.parse().ok());
+    env::remove_var(NOTIFY_SOCKET_ENV_VAR);
+    fd
+}
+
+/// Sends the notification descriptor `listener` to the parent through `socket`, and closes both.
+///
+/// This runs under the filter, so it allocates nothing and makes no system call but `sendmsg` on
+/// `socket` and `close`.
+pub(crate) fn send_listener(socket: c_int, listener: c_int) -> Result<(),SandboxError> {
+    unsafe {
+        // A `cmsghdr` followed by one descriptor, suitably aligned.
+        let mut control = [0u64; 4];
+        let mut byte = 0u8;
+        let mut iov = libc::iovec {
+            iov_base: &mut byte as *mut u8 as *mut c_void,
+            iov_len: 1,
+        };
+        let mut message: libc::msghdr = mem::zeroed();
+        message.msg_iov = &mut iov;
+        message.msg_iovlen = 1;
+        message.msg_control = control.as_mut_ptr() as *mut c_void;
+        message.msg_controllen = libc::CMSG_SPACE(mem::size_of::<c_int>() as u32) as _;
+        let header = libc::CMSG_FIRSTHDR(&message);
+        (*header).cmsg_level = libc::SOL_SOCKET;
+        (*header).cmsg_type = libc::SCM_RIGHTS;
+        (*header).cmsg_len = libc::CMSG_LEN(mem::size_of::<c_int>() as u32) as _;
+        ptr::write_unaligned(libc::CMSG_DATA(header) as *mut c_int, listener);
+
+        let result = libc::sendmsg(socket, &message, 0);
+        let err = io::Error::last_os_error();
+        libc::close(listener);
+        libc::close(socket);
+        if result == 1 {
+            Ok(())
+        } else {
+            Err(SandboxError::SeccompFailed(err))
+        }
+    }
+}
+
+/// Creates a connected pair of Unix sockets, both close-on-exec.
+fn socket_pair() -> io::Result<(OwnedFd, OwnedFd)> {
+    let mut fds = [-1; 2];
+    if unsafe {
+        libc::socketpair(libc::AF_UNIX, libc::SOCK_STREAM | libc::SOCK_CLOEXEC, 0, fds.as_mut_ptr())
+    } < 0 {
+        return Err(io::Error::last_os_error())
+    }
+    unsafe {
+        Ok((OwnedFd::from_raw_fd(fds[0]), OwnedFd::from_raw_fd(fds[1])))
+    }
+}
+
+/// Receives the descriptor that `send_listener()` sends through `socket`.
+fn receive_fd(socket: &OwnedFd) -> io::Result<OwnedFd> {
+    unsafe {
+        let mut control = [0u64; 4];
+        let mut byte = 0u8;
+        let mut iov = libc::iovec {
+            iov_base: &mut byte as *mut u8 as *mut c_void,
+            iov_len: 1,
+        };
+        let mut message: libc::msghdr = mem::zeroed();
+        message.msg_iov = &mut iov;
+        message.msg_iovlen = 1;
+        message.msg_control = control.as_mut_ptr() as *mut c_void;
+        message.msg_controllen = mem::size_of_val(&control) as _;
+        let result = loop {
+            let result = libc::recvmsg(socket.as_raw_fd(), &mut message, libc::MSG_CMSG_CLOEXEC);
+            if result >= 0 || io::Error::last_os_error().kind() != io::ErrorKind::Interrupted {
+                break result
+            }
+        };
+        if result < 0 {
+            return Err(io::Error::last_os_error())
+        }
+        let header = libc::CMSG_FIRSTHDR(&message);
+        if result == 0 || header.is_null() || (*header).cmsg_level != libc::SOL_SOCKET ||
+                (*header).cmsg_type != libc::SCM_RIGHTS {
+            return Err(io::Error::other("the child exited without activating its sandbox"))
+        }
+        let fd = ptr::read_unaligned(libc::CMSG_DATA(header) as *const c_int);
+        Ok(OwnedFd::from_raw_fd(fd))
+    }
+}
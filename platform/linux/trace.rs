@@ -0,0 +1,458 @@
+// Copyright 2015 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Recording the system calls of an unconfined child, to help author profiles for existing
+//! programs. See `Sandbox::trace()`.
+//!
+//! The child installs a seccomp filter that returns `SECCOMP_RET_TRACE` for every system call, so
+//! the kernel stops it at each one and the parent, attached with `ptrace`, reads the system call
+//! number and arguments with `PTRACE_GET_SYSCALL_INFO` (Linux 5.3+). Path arguments are read out
+//! of the child's memory with `process_vm_readv`. The child is stopped on entry to each system
+//! call, so calls are recorded whether or not they go on to succeed.
+
+use crate::platform::linux::seccomp::{syscall_name, Filter};
+use crate::platform::unix;
+use crate::profile::{AddressPattern, Operation, PathPattern};
+use crate::sandbox::Command;
+
+use libc::{self, c_int, c_long, c_uint, c_void, pid_t};
+use std::collections::HashSet;
+use std::ffi::OsStr;
+use std::fs;
+use std::io;
+use std::mem;
+use std::os::unix::prelude::OsStrExt;
+use std::path::{Path, PathBuf};
+
+/// `ptrace(PTRACE_GET_SYSCALL_INFO)`, which not every C library defines.
+const PTRACE_GET_SYSCALL_INFO: c_uint = 0x420e;
+
+/// The `op` of a `ptrace_syscall_info` describing a seccomp stop.
+const PTRACE_SYSCALL_INFO_SECCOMP: u8 = 3;
+
+/// The options the tracer sets on every traced process.
+const TRACE_OPTIONS: c_int = libc::PTRACE_O_TRACESECCOMP | libc::PTRACE_O_EXITKILL |
+    libc::PTRACE_O_TRACEFORK | libc::PTRACE_O_TRACEVFORK | libc::PTRACE_O_TRACECLONE |
+    libc::PTRACE_O_TRACEEXEC;
+
+/// The longest path argument that will be read.
+const MAX_PATH_LENGTH: usize = libc::PATH_MAX as usize;
+
+/// How many bytes of a path argument to read at a time.
+const READ_CHUNK_SIZE: usize = 256;
+
+/// `struct ptrace_syscall_info`, as filled in for a seccomp stop.
+#[repr(C)]
+#[derive(Default)]
+struct SyscallInfo {
+    op: u8,
+    pad: [u8; 3],
+    arch: u32,
+    instruction_pointer: u64,
+    stack_pointer: u64,
+    nr: u64,
+    args: [u64; 6],
+    ret_data: u32,
+}
+
+/// One system call made by a traced process.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TraceEvent {
+    /// The process (or thread) that made the call.
+    pub pid: pid_t,
+    /// The system call number.
+    pub number: i64,
+    /// The name of the system call, if it is one that gaol knows.
+    pub name: Option<String>,
+    /// The absolute path the call names, for calls that open, inspect, execute, or delete files.
+    pub path: Option<PathBuf>,
+    /// Whether the call writes to `path`, for example by opening it for writing.
+    pub writes: bool,
+    /// The address the call connects to, for `connect`.
+    pub address: Option<AddressPattern>,
+}
+
+/// The results of `Sandbox::trace()`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TraceReport {
+    /// Every system call the child and its descendants made, in order.
+    pub events: Vec<TraceEvent>,
+    /// Operations that a profile would need to allow for the child to do the same things.
+    ///
+    /// These are suggestions to review, not a profile to use blindly: they include every path
+    /// the child merely probed for, and on Linux some of them can't be allowed precisely.
+    pub suggestions: Vec<Operation>,
+    /// The exit code of the child, if it exited normally.
+    pub exit_code: Option<i32>,
+    /// The signal that terminated the child, if one did.
+    pub signal: Option<i32>,
+}
+
+/// Runs `command` under the tracer and returns what it did. Nothing is confined.
+///
+/// This waits for any child of the calling process, so it must not run while other children are
+/// being waited for.
+pub fn trace(command: &Command) -> io::Result<TraceReport> {
+    let filter = Filter::trace_all();
+    let pid = unsafe {
+        libc::fork()
+    };
+    if pid < 0 {
+        return Err(io::Error::last_os_error())
+    }
+    if pid == 0 {
+        unsafe {
+            if ptrace(libc::PTRACE_TRACEME, 0, 0, 0) != 0 {
+                libc::_exit(127)
+            }
+            libc::raise(libc::SIGSTOP);
+        }
+        if filter.activate().is_ok() {
+            drop(unix::process::exec(command));
+        }
+        unsafe {
+            libc::_exit(127)
+        }
+    }
+
+    let mut status = 0;
+    let result = unsafe {
+        libc::waitpid(pid, &mut status, libc::__WALL)
+    };
+    if result != pid || !libc::WIFSTOPPED(status) {
+        return Err(io::Error::other("the traced child didn't stop"))
+    }
+    unsafe {
+        if ptrace(libc::PTRACE_SETOPTIONS, pid, 0, TRACE_OPTIONS as usize) != 0 {
+            let err = io::Error::last_os_error();
+            libc::kill(pid, libc::SIGKILL);
+            return Err(err)
+        }
+        ptrace(libc::PTRACE_CONT, pid, 0, 0);
+    }
+
+    let mut report = TraceReport {
+        events: Vec::new(),
+        suggestions: Vec::new(),
+        exit_code: None,
+        signal: None,
+    };
+    let mut tracees = HashSet::new();
+    tracees.insert(pid);
+    while !tracees.is_empty() {
+        let tracee = unsafe {
+            libc::waitpid(-1, &mut status, libc::__WALL)
+        };
+        if tracee < 0 {
+            let err = io::Error::last_os_error();
+            if err.kind() == io::ErrorKind::Interrupted {
+                continue
+            }
+            return Err(err)
+        }
+
+        if libc::WIFEXITED(status) || libc::WIFSIGNALED(status) {
+            tracees.remove(&tracee);
+            if tracee == pid {
+                if libc::WIFEXITED(status) {
+                    report.exit_code = Some(libc::WEXITSTATUS(status))
+                } else {
+                    report.signal = Some(libc::WTERMSIG(status))
+                }
+            }
+            continue
+        }
+        if !libc::WIFSTOPPED(status) {
+            continue
+        }
+
+        // A new process may report its first stop before its parent reports creating it.
+        tracees.insert(tracee);
+        let signal = libc::WSTOPSIG(status);
+        let event = status >> 16;
+        let injected_signal = if signal != libc::SIGTRAP {
+            // The first stop of a new process, or a signal to deliver.
+            if signal == libc::SIGSTOP { 0 } else { signal }
+        } else {
+            match event {
+                libc::PTRACE_EVENT_SECCOMP => {
+                    if let Some(event) = read_event(tracee) {
+                        report.events.push(event)
+                    }
+                }
+                libc::PTRACE_EVENT_FORK | libc::PTRACE_EVENT_VFORK | libc::PTRACE_EVENT_CLONE => {
+                    let mut new_tracee: libc::c_ulong = 0;
+                    unsafe {
+                        ptrace(libc::PTRACE_GETEVENTMSG,
+                               tracee,
+                               0,
+                               &mut new_tracee as *mut libc::c_ulong as usize);
+                    }
+                    tracees.insert(new_tracee as pid_t);
+                }
+                _ => {}
+            }
+            0
+        };
+        unsafe {
+            ptrace(libc::PTRACE_CONT, tracee, 0, injected_signal as usize);
+        }
+    }
+
+    report.suggestions = suggestions(&report.events);
+    Ok(report)
+}
+
+/// Returns the operations a profile would need to allow for `events`, most specific last.
+pub fn suggestions(events: &[TraceEvent]) -> Vec<Operation> {
+    let mut suggestions: Vec<Operation> = Vec::new();
+    let mut suggest = |operation: Operation| {
+        if !suggestions.contains(&operation) {
+            suggestions.push(operation)
+        }
+    };
+    // The first program run is the command itself, which the sandbox starts before applying the
+    // profile. Any later one is a program the command runs.
+    let mut command_executed = false;
+    for event in events.iter() {
+        let executes = event.number == libc::SYS_execve || event.number == libc::SYS_execveat;
+        if let Some(ref path) = event.path {
+            let pattern = PathPattern::Literal(path.clone());
+            if executes && command_executed {
+                suggest(Operation::ProcessExec(pattern))
+            } else if event.writes {
+                suggest(Operation::FileWriteAll(pattern))
+            } else if reads_metadata_only(event.number) {
+                suggest(Operation::FileReadMetadata(pattern))
+            } else {
+                suggest(Operation::FileReadAll(pattern))
+            }
+        }
+        if let Some(ref address) = event.address {
+            suggest(Operation::NetworkOutbound(address.clone()))
+        }
+        command_executed |= executes;
+    }
+
+    // Reading a file includes reading its metadata.
+    let read_paths: Vec<PathPattern> = suggestions.iter().filter_map(|operation| {
+        match *operation {
+            Operation::FileReadAll(ref pattern) => Some(pattern.clone()),
+            _ => None,
+        }
+    }).collect();
+    suggestions.retain(|operation| {
+        match *operation {
+            Operation::FileReadMetadata(ref pattern) => !read_paths.contains(pattern),
+            _ => true,
+        }
+    });
+    suggestions
+}
+
+unsafe fn ptrace(request: c_uint, pid: pid_t, address: usize, data: usize) -> c_long {
+    libc::ptrace(request, pid, address as *mut c_void, data as *mut c_void)
+}
+
+/// Reads the system call that `pid` is stopped in.
+fn read_event(pid: pid_t) -> Option<TraceEvent> {
+    let mut info = SyscallInfo::default();
+    let result = unsafe {
+        ptrace(PTRACE_GET_SYSCALL_INFO,
+               pid,
+               mem::size_of::<SyscallInfo>(),
+               &mut info as *mut SyscallInfo as usize)
+    };
+    if result <= 0 || info.op != PTRACE_SYSCALL_INFO_SECCOMP {
+        return None
+    }
+
+    let number = info.nr as i64;
+    let path = path_argument(number).and_then(|(dirfd_index, path_index)| {
+        let path = read_c_string(pid, info.args[path_index])?;
+        let dirfd = dirfd_index.map(|index| info.args[index] as c_int);
+        resolve_path(pid, dirfd, Path::new(OsStr::from_bytes(&path)))
+    });
+    let address = if number == libc::SYS_connect {
+        connect_address(pid, &info.args)
+    } else {
+        None
+    };
+    Some(TraceEvent {
+        pid,
+        number,
+        name: syscall_name(number).map(|name| name.to_owned()),
+        path,
+        writes: writes_path(number, &info.args),
+        address,
+    })
+}
+
+/// Returns the index of the directory descriptor argument, if any, and of the path argument of
+/// the system calls that name files.
+fn path_argument(number: i64) -> Option<(Option<usize>, usize)> {
+    match number {
+        #[cfg(not(any(target_arch="aarch64", target_arch="riscv64")))]
+        libc::SYS_open | libc::SYS_stat | libc::SYS_lstat | libc::SYS_access |
+        libc::SYS_readlink | libc::SYS_creat | libc::SYS_unlink => Some((None, 0)),
+        libc::SYS_execve | libc::SYS_truncate => Some((None, 0)),
+        libc::SYS_openat | libc::SYS_newfstatat | libc::SYS_faccessat |
+        libc::SYS_faccessat2 | libc::SYS_readlinkat | libc::SYS_statx |
+        libc::SYS_execveat | libc::SYS_unlinkat => Some((Some(0), 1)),
+        _ => None,
+    }
+}
+
+/// Returns true if the system call writes to the file it names.
+fn writes_path(number: i64, args: &[u64; 6]) -> bool {
+    let flags = match number {
+        #[cfg(not(any(target_arch="aarch64", target_arch="riscv64")))]
+        libc::SYS_open => args[1] as c_int,
+        libc::SYS_openat => args[2] as c_int,
+        #[cfg(not(any(target_arch="aarch64", target_arch="riscv64")))]
+        libc::SYS_creat | libc::SYS_unlink => return true,
+        libc::SYS_truncate | libc::SYS_unlinkat => return true,
+        _ => return false,
+    };
+    flags & libc::O_ACCMODE != libc::O_RDONLY || flags & (libc::O_CREAT | libc::O_TRUNC) != 0
+}
+
+/// Returns true if the system call only reads metadata of the file it names.
+fn reads_metadata_only(number: i64) -> bool {
+    match number {
+        #[cfg(not(any(target_arch="aarch64", target_arch="riscv64")))]
+        libc::SYS_stat | libc::SYS_lstat | libc::SYS_access | libc::SYS_readlink => true,
+        libc::SYS_newfstatat | libc::SYS_faccessat | libc::SYS_faccessat2 |
+        libc::SYS_readlinkat | libc::SYS_statx => true,
+        _ => false,
+    }
+}
+
+/// Makes `path`, as passed by `pid` relative to `dirfd`, absolute. Returns `None` for an empty
+/// path, which refers to `dirfd` itself.
+fn resolve_path(pid: pid_t, dirfd: Option<c_int>, path: &Path) -> Option<PathBuf> {
+    if path.as_os_str().is_empty() {
+        return None
+    }
+    if path.is_absolute() {
+        return Some(path.to_path_buf())
+    }
+    let base = match dirfd {
+        Some(dirfd) if dirfd != libc::AT_FDCWD => format!("/proc/{}/fd/{}", pid, dirfd),
+        _ => format!("/proc/{}/cwd", pid),
+    };
+    Some(fs::read_link(base).ok()?.join(path))
+}
+
+/// Reads the socket address passed to `connect` and returns it as an address pattern.
+///
+/// Connections of TCP and Unix sockets are reported. Connected UDP sockets (which the C library's
+/// resolver uses) are left out, because no operation describes them.
+fn connect_address(pid: pid_t, args: &[u64; 6]) -> Option<AddressPattern> {
+    let length = (args[2] as usize).min(mem::size_of::<libc::sockaddr_storage>());
+    let mut address = vec![0; length];
+    if length < mem::size_of::<libc::sa_family_t>() ||
+            read_memory(pid, args[1], &mut address) != length {
+        return None
+    }
+    let family = libc::sa_family_t::from_ne_bytes([address[0], address[1]]) as c_int;
+    match family {
+        libc::AF_INET | libc::AF_INET6 if length >= 4 => {
+            if socket_type(pid, args[0] as c_int) == Some(libc::SOCK_DGRAM) {
+                return None
+            }
+            Some(AddressPattern::Tcp(u16::from_be_bytes([address[2], address[3]])))
+        }
+        libc::AF_UNIX => {
+            // Abstract sockets, whose names begin with a NUL, have no path.
+            let path = &address[2..];
+            let end = path.iter().position(|&byte| byte == 0).unwrap_or(path.len());
+            if end == 0 {
+                return None
+            }
+            let path = Path::new(OsStr::from_bytes(&path[..end]));
+            resolve_path(pid, None, path).map(AddressPattern::LocalSocket)
+        }
+        _ => None,
+    }
+}
+
+/// Returns the type of the socket `fd` in process `pid`, if it can be found out. This needs
+/// `pidfd_getfd` (Linux 5.6+).
+fn socket_type(pid: pid_t, fd: c_int) -> Option<c_int> {
+    unsafe {
+        let pidfd = libc::syscall(libc::SYS_pidfd_open, pid, 0) as c_int;
+        if pidfd < 0 {
+            return None
+        }
+        let local_fd = libc::syscall(libc::SYS_pidfd_getfd, pidfd, fd, 0) as c_int;
+        libc::close(pidfd);
+        if local_fd < 0 {
+            return None
+        }
+        let mut socket_type: c_int = 0;
+        let mut length = mem::size_of::<c_int>() as libc::socklen_t;
+        let result = libc::getsockopt(local_fd,
+                                      libc::SOL_SOCKET,
+                                      libc::SO_TYPE,
+                                      &mut socket_type as *mut c_int as *mut c_void,
+                                      &mut length);
+        libc::close(local_fd);
+        if result == 0 {
+            Some(socket_type)
+        } else {
+            None
+        }
+    }
+}
+
+/// Reads a NUL-terminated string from the memory of `pid`, without reading past the end of the
+/// page the terminator is on.
+fn read_c_string(pid: pid_t, mut address: u64) -> Option<Vec<u8>> {
+    let page_size = unsafe {
+        libc::sysconf(libc::_SC_PAGESIZE)
+    } as u64;
+    let mut string = Vec::new();
+    while string.len() < MAX_PATH_LENGTH {
+        let until_page_end = (page_size - address % page_size) as usize;
+        let mut chunk = [0; READ_CHUNK_SIZE];
+        let chunk = &mut chunk[..until_page_end.min(READ_CHUNK_SIZE)];
+        let length = read_memory(pid, address, chunk);
+        if length == 0 {
+            return None
+        }
+        if let Some(end) = chunk[..length].iter().position(|&byte| byte == 0) {
+            string.extend_from_slice(&chunk[..end]);
+            return Some(string)
+        }
+        string.extend_from_slice(&chunk[..length]);
+        address += length as u64;
+    }
+    None
+}
+
+/// Reads memory of `pid` at `address` into `buffer`, returning the number of bytes read.
+fn read_memory(pid: pid_t, address: u64, buffer: &mut [u8]) -> usize {
+    let local = libc::iovec {
+        iov_base: buffer.as_mut_ptr() as *mut c_void,
+        iov_len: buffer.len(),
+    };
+    let remote = libc::iovec {
+        iov_base: address as usize as *mut c_void,
+        iov_len: buffer.len(),
+    };
+    let result = unsafe {
+        libc::process_vm_readv(pid, &local, 1, &remote, 1, 0)
+    };
+    result.max(0) as usize
+}
+
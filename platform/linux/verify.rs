@@ -0,0 +1,173 @@
+// Copyright 2015 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Self-tests run from inside an activated sandbox on Linux.
+//!
+//! The seccomp filter kills the process on any violation, so probes that would make a prohibited
+//! system call first ask the installed filter, through `Filter::allows()`, whether the call would
+//! be let through. Only calls the filter allows are actually made, and those must then be stopped
+//! by the namespaces instead.
+
+use crate::platform::linux::seccomp::Filter;
+use crate::platform::unix::verify;
+use crate::profile::{Operation, Profile};
+use crate::sandbox::{ProbeStatus, VerificationReport};
+
+use libc::{self, c_int};
+use std::ffi::CString;
+use std::io;
+
+/// `prctl(PR_GET_SECCOMP)` reports this once a filter is installed.
+const SECCOMP_MODE_FILTER: c_int = 2;
+
+/// The capability ABI version that `capget` is asked to use.
+const LINUX_CAPABILITY_VERSION_3: u32 = 0x2008_0522;
+
+#[repr(C)]
+struct CapabilityHeader {
+    version: u32,
+    pid: c_int,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+struct CapabilityData {
+    effective: u32,
+    permitted: u32,
+    inheritable: u32,
+}
+
+pub fn verify(profile: &Profile) -> VerificationReport {
+    let filter = Filter::cached(profile);
+    let mut report = VerificationReport::default();
+    probe_file_access(profile, &filter, &mut report);
+    probe_network(profile, &filter, &mut report);
+    probe_capabilities(&mut report);
+    probe_no_new_privs(&mut report);
+    probe_seccomp(&mut report);
+    probe_uid(&mut report);
+    report
+}
+
+fn probe_file_access(profile: &Profile, filter: &Filter, report: &mut VerificationReport) {
+    if verify::profile_grants_probe_path(profile) {
+        return verify::skip_file_access(report)
+    }
+    let flags = libc::O_RDONLY | libc::O_CLOEXEC;
+    let openat_args = [libc::AT_FDCWD as u64, 0, flags as u64, 0, 0, 0];
+    // aarch64 and riscv64 have no `open`.
+    #[cfg(not(any(target_arch="aarch64", target_arch="riscv64")))]
+    if filter.allows(libc::SYS_open as u32, &[0, flags as u64, 0, 0, 0, 0]) {
+        // The jail has to hide the file. Make exactly the system call that was checked: the C
+        // library's `open` may use another one.
+        let path = CString::new(verify::PROBE_PATH).unwrap();
+        let fd = unsafe {
+            libc::syscall(libc::SYS_open, path.as_ptr(), flags)
+        };
+        return verify::record_file_access(report, fd as c_int)
+    }
+    if filter.allows(libc::SYS_openat as u32, &openat_args) {
+        let path = CString::new(verify::PROBE_PATH).unwrap();
+        let fd = unsafe {
+            libc::syscall(libc::SYS_openat,
+                          libc::AT_FDCWD,
+                          path.as_ptr(),
+                          flags)
+        };
+        verify::record_file_access(report, fd as c_int)
+    } else {
+        report.push("file-access",
+                    ProbeStatus::Passed,
+                    "the seccomp filter denies opening files".to_owned())
+    }
+}
+
+fn probe_network(profile: &Profile, filter: &Filter, report: &mut VerificationReport) {
+    if profile.allowed_operations().iter().any(|operation| {
+        matches!(*operation, Operation::NetworkOutbound(_) | Operation::NetworkInbound(_))
+    }) {
+        return verify::skip_network(report)
+    }
+    let socket_args = [libc::AF_INET as u64, libc::SOCK_STREAM as u64, 0, 0, 0, 0];
+    if filter.allows(libc::SYS_socket as u32, &socket_args) {
+        report.push("network",
+                    ProbeStatus::Failed,
+                    "the seccomp filter allows creating AF_INET sockets".to_owned())
+    } else {
+        report.push("network",
+                    ProbeStatus::Passed,
+                    "the seccomp filter denies creating AF_INET sockets".to_owned())
+    }
+}
+
+fn probe_capabilities(report: &mut VerificationReport) {
+    let mut header = CapabilityHeader {
+        version: LINUX_CAPABILITY_VERSION_3,
+        pid: 0,
+    };
+    let mut data = [CapabilityData::default(); 2];
+    let result = unsafe {
+        libc::syscall(libc::SYS_capget,
+                      &mut header as *mut CapabilityHeader,
+                      data.as_mut_ptr())
+    };
+    if result != 0 {
+        return report.push("capabilities",
+                           ProbeStatus::Failed,
+                           format!("capget failed: {}", io::Error::last_os_error()))
+    }
+    let effective = (data[1].effective as u64) << 32 | data[0].effective as u64;
+    let permitted = (data[1].permitted as u64) << 32 | data[0].permitted as u64;
+    let status = if effective == 0 && permitted == 0 {
+        ProbeStatus::Passed
+    } else {
+        ProbeStatus::Failed
+    };
+    report.push("capabilities",
+                status,
+                format!("effective {:#x}, permitted {:#x}", effective, permitted))
+}
+
+fn probe_no_new_privs(report: &mut VerificationReport) {
+    let result = unsafe {
+        libc::prctl(libc::PR_GET_NO_NEW_PRIVS, 0, 0, 0, 0)
+    };
+    let status = if result == 1 {
+        ProbeStatus::Passed
+    } else {
+        ProbeStatus::Failed
+    };
+    report.push("no-new-privs", status, format!("PR_GET_NO_NEW_PRIVS returned {}", result))
+}
+
+fn probe_seccomp(report: &mut VerificationReport) {
+    let result = unsafe {
+        libc::prctl(libc::PR_GET_SECCOMP, 0, 0, 0, 0)
+    };
+    let status = if result == SECCOMP_MODE_FILTER {
+        ProbeStatus::Passed
+    } else {
+        ProbeStatus::Failed
+    };
+    report.push("seccomp", status, format!("PR_GET_SECCOMP returned {}", result))
+}
+
+/// The user namespace maps the parent's user to root inside it.
+fn probe_uid(report: &mut VerificationReport) {
+    let uid = unsafe {
+        libc::getuid()
+    };
+    let status = if uid == 0 {
+        ProbeStatus::Passed
+    } else {
+        ProbeStatus::Failed
+    };
+    report.push("uid", status, format!("uid {} inside the user namespace", uid))
+}
@@ -10,18 +10,27 @@
 
 //! Sandboxing on Mac OS X via Seatbelt (`sandboxd`).
 
-use platform::unix::process::Process;
-use profile::{self, AddressPattern, OperationSupport, OperationSupportLevel, PathPattern, Profile};
-use sandbox::{ChildSandboxMethods, Command, SandboxMethods};
+use crate::error::SandboxError;
+use crate::platform::unix::cache::{self, ProfileCache};
+use crate::platform::unix::process::Process;
+use crate::platform::unix::verify;
+use crate::profile::{self, AddressPattern, OperationSupport, OperationSupportLevel, PathPattern};
+use crate::profile::{DecodeError, Decoder, Describe, Profile, STANDARD_DEVICES};
+use crate::sandbox::{self, ActivationError, ChildSandboxMethods, ChildSandboxOptions, Command};
+use crate::sandbox::{PreflightIssue, ProfilePayload, SandboxMethods, SandboxOptions};
+use crate::sandbox::{ResourceLimits, SandboxLimits, VerificationReport};
+use crate::telemetry::Mechanism;
 
 use libc::{c_char, c_int};
+use log::{debug, error};
 use std::ffi::{CStr, CString};
 use std::io::{self, Write};
 use std::path::Path;
 use std::ptr;
 use std::str;
+use std::sync::OnceLock;
 
-static SANDBOX_PROFILE_PROLOGUE: &'static [u8] = b"
+static SANDBOX_PROFILE_PROLOGUE: &[u8] = b"
 (version 1)
 (deny default)
 ";
@@ -31,34 +40,120 @@ impl OperationSupport for profile::Operation {
         match *self {
             profile::Operation::FileReadAll(_) |
             profile::Operation::FileReadMetadata(_) |
+            profile::Operation::FileWriteAll(_) |
+            profile::Operation::FileWriteMetadata(_) |
+            profile::Operation::ProcessExec(_) |
             profile::Operation::NetworkOutbound(AddressPattern::All) |
             profile::Operation::NetworkOutbound(AddressPattern::Tcp(_)) |
+            profile::Operation::NetworkOutbound(AddressPattern::Udp(_)) |
             profile::Operation::NetworkOutbound(AddressPattern::LocalSocket(_)) |
+            profile::Operation::NetworkInbound(AddressPattern::All) |
+            profile::Operation::NetworkInbound(AddressPattern::Tcp(_)) |
+            profile::Operation::NetworkInbound(AddressPattern::Udp(_)) |
+            profile::Operation::NetworkInbound(AddressPattern::LocalSocket(_)) |
             profile::Operation::SystemInfoRead |
+            profile::Operation::StandardDevices |
             profile::Operation::PlatformSpecific(Operation::MachLookup(_)) => {
                 OperationSupportLevel::CanBeAllowed
             }
+            // Seatbelt has no way to allow appending without allowing truncation too.
+            profile::Operation::FileAppend(_) => OperationSupportLevel::CannotBeAllowedPrecisely,
+            // Seatbelt only matches remote hosts by name, and only `localhost` at that.
+            profile::Operation::NetworkOutbound(AddressPattern::IpCidr { .. }) |
+            profile::Operation::NetworkInbound(AddressPattern::IpCidr { .. }) => {
+                OperationSupportLevel::CannotBeAllowedPrecisely
+            }
         }
     }
 }
 
 /// Mac OS X-specific operations.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Operation {
     /// Lookups to the given Mach service are allowed.
     MachLookup(Vec<u8>),
 }
 
+/// Identifies Mac OS X-specific operations in encoded profiles.
+pub(crate) const OPERATION_ENCODING_ID: u8 = 2;
+
+impl Operation {
+    pub(crate) fn encode(&self, bytes: &mut Vec<u8>) {
+        match *self {
+            Operation::MachLookup(ref service) => {
+                bytes.push(1);
+                profile::encode_bytes(bytes, service)
+            }
+        }
+    }
+
+    pub(crate) fn decode(decoder: &mut Decoder) -> Result<Operation,DecodeError> {
+        match decoder.u8()? {
+            1 => Ok(Operation::MachLookup(decoder.bytes()?.to_vec())),
+            _ => Err(DecodeError::Malformed),
+        }
+    }
+}
+
+impl Describe for Operation {
+    fn describe(&self) -> String {
+        match *self {
+            Operation::MachLookup(ref service) => {
+                format!("look up the Mach service {}", String::from_utf8_lossy(service))
+            }
+        }
+    }
+}
+
+/// Returns the mechanisms that confine a child started with a profile, and those of them that
+/// this machine lacks. None of the mechanisms that can be disabled are used here.
+pub(crate) fn mechanisms(_: &Profile, _: &[Mechanism]) -> (Vec<Mechanism>, Vec<Mechanism>) {
+    (vec![Mechanism::Seatbelt], Vec::new())
+}
+
+/// Returns the filesystem `path` is on if it can't be made available to a child. Nothing is
+/// bind-mounted here, so every filesystem can.
+pub(crate) fn unbindable_filesystem(_: &Path) -> Option<&'static str> {
+    None
+}
+
+/// Returns the kernel's core dump pattern if it would keep children from dumping core into a
+/// `CoreDumps::EnabledTo` directory. Core dumps aren't redirected here, so no pattern does.
+pub(crate) fn unsupported_core_pattern() -> Option<String> {
+    None
+}
+
 pub struct Sandbox {
     profile: Profile,
+    options: SandboxOptions,
 }
 
 impl Sandbox {
     pub fn new(profile: Profile) -> Sandbox {
+        Sandbox::with_options(profile, SandboxOptions::new())
+    }
+
+    pub fn with_options(profile: Profile, options: SandboxOptions) -> Sandbox {
         Sandbox {
-            profile: profile,
+            profile,
+            options,
         }
     }
+
+    /// Resource limits need cgroup v2, so children of a sandbox given them fail to start here,
+    /// with `Unsupported`.
+    pub fn with_limits(mut self, limits: SandboxLimits) -> Sandbox {
+        self.options.limits(limits);
+        self
+    }
+
+    /// Per-process resource limits are only set on Linux, so children of a sandbox given them
+    /// fail to start here, with `Unsupported`.
+    pub fn with_resource_limits(mut self, limits: ResourceLimits) -> Sandbox {
+        self.options.process_limits(limits);
+        self
+    }
 }
 
 impl SandboxMethods for Sandbox {
@@ -67,7 +162,31 @@ impl SandboxMethods for Sandbox {
     }
 
     fn start(&self, command: &mut Command) -> io::Result<Process> {
-        command.env("GAOL_CHILD_PROCESS", "1").spawn()
+        if self.options.resource_limits().is_some() {
+            let err = io::Error::new(io::ErrorKind::Unsupported, "resource limits need cgroup v2");
+            return self.options.record_start(&self.profile, Err(err))
+        }
+        if self.options.per_process_limits().is_some() {
+            let err = io::Error::new(io::ErrorKind::Unsupported,
+                                     "per-process resource limits are only set on Linux");
+            return self.options.record_start(&self.profile, Err(err))
+        }
+        if self.profile.resource_limits().is_some_and(|limits| {
+            limits.max_wall_clock_seconds.is_some()
+        }) {
+            let err = io::Error::new(io::ErrorKind::Unsupported,
+                                     "wall clock limits are only enforced on Linux");
+            return self.options.record_start(&self.profile, Err(err))
+        }
+        let result = ProfilePayload::new(&self.profile).and_then(|payload| {
+            payload.export(command);
+            command.env("GAOL_CHILD_PROCESS", "1").spawn()
+        });
+        self.options.record_start(&self.profile, result)
+    }
+
+    fn preflight(&self) -> Result<(), Vec<PreflightIssue>> {
+        self.options.preflight(&self.profile)
     }
 }
 
@@ -77,69 +196,210 @@ pub struct ChildSandbox {
 
 impl ChildSandbox {
     pub fn new(profile: Profile) -> ChildSandbox {
+        ChildSandbox::with_options(profile, ChildSandboxOptions::new())
+    }
+
+    /// Creates a sandbox for the profile that `Sandbox::start()` passed to this process, so that
+    /// the child needn't rebuild it. Fails with `InvalidData` if the profile was damaged in
+    /// transit, wrapping a `DecodeError`.
+    pub fn from_environment() -> io::Result<ChildSandbox> {
+        ChildSandbox::from_environment_with_options(ChildSandboxOptions::new())
+    }
+
+    /// Like `from_environment()`, with options.
+    pub fn from_environment_with_options(options: ChildSandboxOptions)
+                                         -> io::Result<ChildSandbox> {
+        Ok(ChildSandbox::with_options(sandbox::profile_from_environment()?, options))
+    }
+
+    /// Seatbelt confines the whole process, so none of the options make a difference here.
+    pub fn with_options(profile: Profile, _: ChildSandboxOptions) -> ChildSandbox {
         ChildSandbox {
-            profile: profile,
+            profile,
         }
     }
 }
 
 impl ChildSandboxMethods for ChildSandbox {
-    fn activate(&self) -> Result<(),()> {
-        let mut sandbox_profile = Vec::new();
-        sandbox_profile.write_all(SANDBOX_PROFILE_PROLOGUE).unwrap();
-        for operation in self.profile.allowed_operations().iter() {
-            match *operation {
-                profile::Operation::FileReadAll(ref file_pattern) => {
-                    sandbox_profile.write_all(b"(allow file-read* ").unwrap();
-                    write_file_pattern(&mut sandbox_profile, file_pattern);
-                    sandbox_profile.write_all(b")\n").unwrap();
-                }
-                profile::Operation::FileReadMetadata(ref file_pattern) => {
-                    sandbox_profile.write_all(b"(allow file-read-metadata ").unwrap();
-                    write_file_pattern(&mut sandbox_profile, file_pattern);
-                    sandbox_profile.write_all(b")\n").unwrap();
+    #[inline(never)]
+    fn activate_with_hook<F>(&self, hook: F) -> Result<(),ActivationError> where F: FnOnce() {
+        let sandbox_profile = cache().get_or_insert_with(&self.profile, compile_profile);
+        debug!("{}", str::from_utf8(&sandbox_profile).unwrap());
+
+        let profile = CString::new(&sandbox_profile[..]).unwrap();
+        if let Some(limits) = self.profile.resource_limits() {
+            set_resource_limits(limits).map_err(ActivationError::RestrictionFailed)?
+        }
+        sandbox::run_activation_hook(hook);
+        let mut err = ptr::null_mut();
+        unsafe {
+            if sandbox_init(profile.as_ptr(), 0, &mut err) == 0 {
+                Ok(())
+            } else {
+                let message = CStr::from_ptr(err).to_string_lossy().into_owned();
+                error!("Failed to init sandbox: {}", message);
+                sandbox_free_error(err);
+                Err(ActivationError::RestrictionFailed(SandboxError::PlatformError(message)))
+            }
+        }
+    }
+
+    fn verify(&self) -> VerificationReport {
+        let mut report = VerificationReport::default();
+        if verify::profile_grants_probe_path(&self.profile) {
+            verify::skip_file_access(&mut report)
+        } else {
+            verify::probe_file_access(&mut report)
+        }
+        if self.profile.allowed_operations().iter().any(|operation| {
+            matches!(*operation,
+                     profile::Operation::NetworkOutbound(_) |
+                     profile::Operation::NetworkInbound(_))
+        }) {
+            verify::skip_network(&mut report)
+        } else {
+            verify::probe_network_connect(&mut report)
+        }
+        report
+    }
+}
+
+/// Sets each of the profile's `limits` that is set as both the soft and the hard limit, as Linux
+/// does, with the hard CPU time limit a second past the soft one. Seatbelt doesn't stop the process
+/// from lowering them further, but nothing can raise a hard limit without privileges.
+fn set_resource_limits(limits: &ResourceLimits) -> Result<(), SandboxError> {
+    for &(resource, limit) in [
+        (libc::RLIMIT_AS, limits.max_memory_bytes),
+        (libc::RLIMIT_CPU, limits.max_cpu_seconds),
+        (libc::RLIMIT_NOFILE, limits.max_open_files),
+        (libc::RLIMIT_NPROC, limits.max_processes),
+        (libc::RLIMIT_FSIZE, limits.max_file_size_bytes),
+    ].iter() {
+        let limit = match limit {
+            Some(limit) => limit,
+            None => continue,
+        };
+        let rlimit = libc::rlimit {
+            rlim_cur: limit,
+            rlim_max: if resource == libc::RLIMIT_CPU { limit.saturating_add(1) } else { limit },
+        };
+        if unsafe { libc::setrlimit(resource, &rlimit) } != 0 {
+            return Err(SandboxError::ResourceLimitFailed(io::Error::last_os_error()))
+        }
+    }
+    Ok(())
+}
+
+/// Returns the process-wide cache of compiled Seatbelt profiles.
+///
+/// `ChildSandbox::activate()` consults this cache, so activating sandboxes with equal profiles
+/// generates the Seatbelt source only once. Call `cache().set_enabled(false)` to turn caching off.
+pub fn cache() -> &'static ProfileCache<Vec<u8>> {
+    static CACHE: OnceLock<ProfileCache<Vec<u8>>> = OnceLock::new();
+    CACHE.get_or_init(|| ProfileCache::new(cache::DEFAULT_CAPACITY))
+}
+
+/// Translates a profile into the Seatbelt profile language.
+fn compile_profile(profile: &Profile) -> Vec<u8> {
+    let mut sandbox_profile = Vec::new();
+    sandbox_profile.write_all(SANDBOX_PROFILE_PROLOGUE).unwrap();
+    for operation in profile.allowed_operations().iter() {
+        match *operation {
+            profile::Operation::FileReadAll(ref file_pattern) => {
+                sandbox_profile.write_all(b"(allow file-read* ").unwrap();
+                write_file_pattern(&mut sandbox_profile, file_pattern);
+                sandbox_profile.write_all(b")\n").unwrap();
+            }
+            profile::Operation::FileReadMetadata(ref file_pattern) => {
+                sandbox_profile.write_all(b"(allow file-read-metadata ").unwrap();
+                write_file_pattern(&mut sandbox_profile, file_pattern);
+                sandbox_profile.write_all(b")\n").unwrap();
+            }
+            profile::Operation::FileWriteAll(ref file_pattern) => {
+                sandbox_profile.write_all(b"(allow file-write* ").unwrap();
+                write_file_pattern(&mut sandbox_profile, file_pattern);
+                sandbox_profile.write_all(b")\n").unwrap();
+            }
+            profile::Operation::FileWriteMetadata(ref file_pattern) => {
+                sandbox_profile.write_all(b"(allow file-write-metadata ").unwrap();
+                write_file_pattern(&mut sandbox_profile, file_pattern);
+                sandbox_profile.write_all(b")\n").unwrap();
+            }
+            // `Profile::new()` rejects this on Mac OS X.
+            profile::Operation::FileAppend(_) => {}
+            profile::Operation::ProcessExec(ref file_pattern) => {
+                sandbox_profile.write_all(b"(allow process-fork)\n").unwrap();
+                sandbox_profile.write_all(b"(allow process-exec ").unwrap();
+                write_file_pattern(&mut sandbox_profile, file_pattern);
+                sandbox_profile.write_all(b")\n").unwrap();
+            }
+            profile::Operation::NetworkOutbound(ref address_pattern) => {
+                sandbox_profile.write_all(b"(allow system-socket)\n").unwrap();
+                sandbox_profile.write_all(b"(allow network-outbound").unwrap();
+                match *address_pattern {
+                    AddressPattern::All => {}
+                    AddressPattern::Tcp(port) => {
+                        write!(&mut sandbox_profile, " (remote tcp \"*:{}\")", port).unwrap()
+                    }
+                    AddressPattern::Udp(port) => {
+                        write!(&mut sandbox_profile, " (remote udp \"*:{}\")", port).unwrap()
+                    }
+                    AddressPattern::IpCidr { port: None, .. } => {}
+                    AddressPattern::IpCidr { port: Some(port), .. } => {
+                        write!(&mut sandbox_profile, " (remote ip \"*:{}\")", port).unwrap()
+                    }
+                    AddressPattern::LocalSocket(ref path) => {
+                        sandbox_profile.write_all(b"( literal ").unwrap();
+                        write_path(&mut sandbox_profile, path);
+                        sandbox_profile.write_all(b")").unwrap();
+                    }
                 }
-                profile::Operation::NetworkOutbound(ref address_pattern) => {
-                    sandbox_profile.write_all(b"(allow system-socket)\n").unwrap();
-                    sandbox_profile.write_all(b"(allow network-outbound").unwrap();
+                sandbox_profile.write_all(b")\n").unwrap();
+            }
+            profile::Operation::NetworkInbound(ref address_pattern) => {
+                sandbox_profile.write_all(b"(allow system-socket)\n").unwrap();
+                for rule in [&b"network-bind"[..], &b"network-inbound"[..]].iter() {
+                    sandbox_profile.write_all(b"(allow ").unwrap();
+                    sandbox_profile.write_all(rule).unwrap();
                     match *address_pattern {
                         AddressPattern::All => {}
                         AddressPattern::Tcp(port) => {
-                            write!(&mut sandbox_profile, " (remote tcp \"*:{}\")", port).unwrap()
+                            write!(&mut sandbox_profile, " (local tcp \"*:{}\")", port).unwrap()
+                        }
+                        AddressPattern::Udp(port) => {
+                            write!(&mut sandbox_profile, " (local udp \"*:{}\")", port).unwrap()
+                        }
+                        AddressPattern::IpCidr { port: None, .. } => {}
+                        AddressPattern::IpCidr { port: Some(port), .. } => {
+                            write!(&mut sandbox_profile, " (local ip \"*:{}\")", port).unwrap()
                         }
                         AddressPattern::LocalSocket(ref path) => {
-                            sandbox_profile.write_all(b"( literal ").unwrap();
+                            sandbox_profile.write_all(b" (literal ").unwrap();
                             write_path(&mut sandbox_profile, path);
                             sandbox_profile.write_all(b")").unwrap();
                         }
                     }
                     sandbox_profile.write_all(b")\n").unwrap();
                 }
-                profile::Operation::SystemInfoRead => {
-                    sandbox_profile.write_all(b"(allow sysctl-read)\n").unwrap()
-                }
-                profile::Operation::PlatformSpecific(Operation::MachLookup(ref service_name)) => {
-                    sandbox_profile.write_all(b"(allow mach-lookup (global-name ").unwrap();
-                    write_quoted_string(&mut sandbox_profile, service_name.as_slice());
+            }
+            profile::Operation::SystemInfoRead => {
+                sandbox_profile.write_all(b"(allow sysctl-read)\n").unwrap()
+            }
+            profile::Operation::StandardDevices => {
+                for device in STANDARD_DEVICES.iter() {
+                    sandbox_profile.write_all(b"(allow file-read* file-write* (literal ").unwrap();
+                    write_path(&mut sandbox_profile, Path::new(device));
                     sandbox_profile.write_all(b"))\n").unwrap();
                 }
             }
-        }
-
-        debug!("{}", str::from_utf8(&*sandbox_profile).unwrap());
-
-        let profile = CString::new(sandbox_profile).unwrap();
-        let mut err = ptr::null_mut();
-        unsafe {
-            if sandbox_init(profile.as_ptr(), 0, &mut err) == 0 {
-                Ok(())
-            } else {
-                error!("Failed to init sandbox: {:?}", CStr::from_ptr(err));
-                sandbox_free_error(err);
-                Err(())
+            profile::Operation::PlatformSpecific(Operation::MachLookup(ref service_name)) => {
+                sandbox_profile.write_all(b"(allow mach-lookup (global-name ").unwrap();
+                write_quoted_string(&mut sandbox_profile, service_name.as_slice());
+                sandbox_profile.write_all(b"))\n").unwrap();
             }
         }
     }
+    sandbox_profile
 }
 
 fn write_file_pattern(sandbox_profile: &mut Vec<u8>, path_pattern: &PathPattern) {
@@ -152,28 +412,57 @@ fn write_file_pattern(sandbox_profile: &mut Vec<u8>, path_pattern: &PathPattern)
             sandbox_profile.write_all(b"(subpath ").unwrap();
             write_path(sandbox_profile, path)
         }
+        PathPattern::Glob(ref glob) => {
+            sandbox_profile.write_all(b"(regex ").unwrap();
+            write_quoted_string(sandbox_profile, glob_to_regex(glob).as_bytes())
+        }
     }
     sandbox_profile.write_all(b")").unwrap()
 }
 
+/// Translates a `PathPattern::Glob` into a regular expression matching the same whole paths.
+fn glob_to_regex(glob: &str) -> String {
+    let mut regex = "^".to_owned();
+    let mut escaped = false;
+    for character in glob.chars() {
+        match character {
+            '*' if !escaped => regex.push_str("[^/]*"),
+            '?' if !escaped => regex.push_str("[^/]"),
+            '\\' if !escaped => {
+                escaped = true;
+                continue
+            }
+            '.' | '+' | '(' | ')' | '[' | ']' | '{' | '}' | '^' | '$' | '|' | '*' | '?' |
+            '\\' => {
+                regex.push('\\');
+                regex.push(character)
+            }
+            _ => regex.push(character),
+        }
+        escaped = false
+    }
+    regex.push('$');
+    regex
+}
+
 fn write_path(sandbox_profile: &mut Vec<u8>, path: &Path) {
     write_quoted_string(sandbox_profile, path.as_os_str().to_str().unwrap().as_bytes())
 }
 
 fn write_quoted_string(sandbox_profile: &mut Vec<u8>, string: &[u8]) {
-    sandbox_profile.write_all(&[b'"']).unwrap();
+    sandbox_profile.write_all(b"\"").unwrap();
     for &byte in string.iter() {
         // FIXME(pcwalton): Is this the right way to quote strings in TinyScheme?
         // FIXME(pcwalton): Any other special characters we need to worry about in TinyScheme?
         if byte == b'"' || byte == b'\\' {
-            sandbox_profile.write_all(&[b'\\']).unwrap()
+            sandbox_profile.write_all(b"\\").unwrap()
         }
         sandbox_profile.write_all(&[byte]).unwrap()
     }
-    sandbox_profile.write_all(&[b'"']).unwrap()
+    sandbox_profile.write_all(b"\"").unwrap()
 }
 
-extern {
+extern "C" {
     fn sandbox_init(profile: *const c_char, flags: u64, errorbuf: *mut *mut c_char) -> c_int;
     fn sandbox_free_error(errorbuf: *mut c_char);
 }
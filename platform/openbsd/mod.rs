@@ -0,0 +1,318 @@
+// Copyright 2015 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Sandboxing on OpenBSD via `unveil(2)` and `pledge(2)`.
+//!
+//! `unveil()` hides every path but those the profile names, and `pledge()` limits the process to
+//! the groups of system calls, or promises, that the profile's operations need. The promises
+//! carry over into programs the child runs. A child that breaks its promises is killed with
+//! `SIGABRT`, unless its profile's `ViolationAction` asks for an error, in which case the system
+//! call fails with `ENOSYS`, whatever error number the profile gives.
+
+use crate::error::SandboxError;
+use crate::platform::unix::process::Process;
+use crate::platform::unix::verify;
+use crate::profile::{self, AddressPattern, DecodeError, Decoder, Describe, OperationSupport};
+use crate::profile::{OperationSupportLevel, PathPattern, Profile, ProhibitionSupport};
+use crate::profile::ViolationAction;
+use crate::sandbox::{self, ActivationError, ChildSandboxMethods, ChildSandboxOptions, Command};
+use crate::sandbox::{PreflightIssue, ProbeStatus, ProfilePayload, SandboxMethods};
+use crate::sandbox::{ResourceLimits, SandboxLimits, SandboxOptions, VerificationReport};
+use crate::telemetry::Mechanism;
+
+use log::error;
+use std::ffi::CString;
+use std::io;
+use std::os::unix::prelude::OsStrExt;
+use std::path::Path;
+use std::ptr;
+
+impl OperationSupport for profile::Operation {
+    fn support(&self) -> OperationSupportLevel {
+        match *self {
+            profile::Operation::FileReadAll(PathPattern::Literal(_)) |
+            profile::Operation::FileReadAll(PathPattern::Subpath(_)) |
+            profile::Operation::FileWriteAll(PathPattern::Literal(_)) |
+            profile::Operation::FileWriteAll(PathPattern::Subpath(_)) |
+            profile::Operation::ProcessExec(PathPattern::Literal(_)) |
+            profile::Operation::ProcessExec(PathPattern::Subpath(_)) |
+            profile::Operation::NetworkOutbound(AddressPattern::All) |
+            profile::Operation::SystemInfoRead => OperationSupportLevel::CanBeAllowed,
+            // `unveil()` only takes paths.
+            profile::Operation::FileReadAll(PathPattern::Glob(_)) |
+            profile::Operation::FileWriteAll(PathPattern::Glob(_)) |
+            profile::Operation::ProcessExec(PathPattern::Glob(_)) => {
+                OperationSupportLevel::CannotBeAllowedPrecisely
+            }
+            _ => OperationSupportLevel::NeverAllowed
+        }
+    }
+
+    /// `stdio` allows `uname()` and the common `sysctl()`s, so system information can't be kept
+    /// from a child.
+    fn prohibition_support(&self) -> ProhibitionSupport {
+        match *self {
+            profile::Operation::SystemInfoRead => ProhibitionSupport::NotEnforced,
+            _ => ProhibitionSupport::from_support_level(self.support()),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Operation { }
+
+/// Identifies OpenBSD-specific operations in encoded profiles.
+pub(crate) const OPERATION_ENCODING_ID: u8 = 4;
+
+impl Operation {
+    pub(crate) fn encode(&self, _: &mut Vec<u8>) {
+        match *self {}
+    }
+
+    /// There are no OpenBSD-specific operations to decode.
+    pub(crate) fn decode(_: &mut Decoder) -> Result<Operation,DecodeError> {
+        Err(DecodeError::Malformed)
+    }
+}
+
+impl Describe for Operation {
+    fn describe(&self) -> String {
+        match *self {}
+    }
+}
+
+/// Returns the path that `operation` unveils and the permissions it unveils it with, or `None`
+/// if it unveils nothing.
+///
+/// Writing needs `c` as well as `w`, so that files can be created and removed.
+pub fn unveil_permissions(operation: &profile::Operation) -> Option<(&Path, &'static str)> {
+    let (pattern, permissions) = match *operation {
+        profile::Operation::FileReadAll(ref pattern) => (pattern, "r"),
+        profile::Operation::FileWriteAll(ref pattern) => (pattern, "rwc"),
+        profile::Operation::ProcessExec(ref pattern) => (pattern, "rx"),
+        _ => return None,
+    };
+    match *pattern {
+        PathPattern::Literal(ref path) | PathPattern::Subpath(ref path) => {
+            Some((path, permissions))
+        }
+        PathPattern::Glob(_) => None,
+    }
+}
+
+/// Returns the promises that `pledge()` is given for `profile`.
+///
+/// There is no promise for reading system information as such: `stdio` already allows `uname()`
+/// and the common `sysctl()`s, and `SystemInfoRead` adds `vminfo` for memory statistics.
+pub fn promises(profile: &Profile) -> String {
+    let mut promises = vec!["stdio"];
+    for operation in profile.allowed_operations() {
+        let needed: &[&str] = match *operation {
+            profile::Operation::FileReadAll(_) => &["rpath"],
+            profile::Operation::FileWriteAll(_) => &["rpath", "wpath", "cpath"],
+            profile::Operation::ProcessExec(_) => &["rpath", "proc", "exec"],
+            profile::Operation::NetworkOutbound(_) => &["inet"],
+            profile::Operation::SystemInfoRead => &["vminfo"],
+            _ => &[],
+        };
+        for promise in needed {
+            if !promises.contains(promise) {
+                promises.push(promise)
+            }
+        }
+    }
+    if profile.violation_action() != ViolationAction::Kill {
+        promises.push("error")
+    }
+    promises.join(" ")
+}
+
+/// Returns the mechanisms that confine a child started with a profile, and those of them that
+/// this machine lacks. None of the mechanisms that can be disabled are used here.
+pub(crate) fn mechanisms(_: &Profile, _: &[Mechanism]) -> (Vec<Mechanism>, Vec<Mechanism>) {
+    (vec![Mechanism::Unveil, Mechanism::Pledge], Vec::new())
+}
+
+/// Returns the filesystem `path` is on if it can't be made available to a child. Nothing is
+/// bind-mounted here, so every filesystem can.
+pub(crate) fn unbindable_filesystem(_: &Path) -> Option<&'static str> {
+    None
+}
+
+/// Returns the kernel's core dump pattern if it would keep children from dumping core into a
+/// `CoreDumps::EnabledTo` directory. Core dumps aren't redirected here, so no pattern does.
+pub(crate) fn unsupported_core_pattern() -> Option<String> {
+    None
+}
+
+pub struct Sandbox {
+    profile: Profile,
+    options: SandboxOptions,
+}
+
+impl Sandbox {
+    pub fn new(profile: Profile) -> Sandbox {
+        Sandbox::with_options(profile, SandboxOptions::new())
+    }
+
+    pub fn with_options(profile: Profile, options: SandboxOptions) -> Sandbox {
+        Sandbox {
+            profile,
+            options,
+        }
+    }
+
+    /// Resource limits need cgroup v2, so children of a sandbox given them fail to start here,
+    /// with `Unsupported`.
+    pub fn with_limits(mut self, limits: SandboxLimits) -> Sandbox {
+        self.options.limits(limits);
+        self
+    }
+
+    /// Per-process resource limits are only set on Linux, so children of a sandbox given them
+    /// fail to start here, with `Unsupported`.
+    pub fn with_resource_limits(mut self, limits: ResourceLimits) -> Sandbox {
+        self.options.process_limits(limits);
+        self
+    }
+}
+
+impl SandboxMethods for Sandbox {
+    fn profile(&self) -> &Profile {
+        &self.profile
+    }
+
+    /// The child is forked and runs `command` fresh, so that it pledges before running anything
+    /// of its own.
+    fn start(&self, command: &mut Command) -> io::Result<Process> {
+        if self.options.resource_limits().is_some() {
+            let err = io::Error::new(io::ErrorKind::Unsupported, "resource limits need cgroup v2");
+            return self.options.record_start(&self.profile, Err(err))
+        }
+        if self.options.per_process_limits().is_some() ||
+                self.profile.resource_limits().is_some() {
+            let err = io::Error::new(io::ErrorKind::Unsupported,
+                                     "per-process resource limits are only set on Linux");
+            return self.options.record_start(&self.profile, Err(err))
+        }
+        let result = ProfilePayload::new(&self.profile).and_then(|payload| {
+            payload.export(command);
+            command.env("GAOL_CHILD_PROCESS", "1").spawn()
+        });
+        self.options.record_start(&self.profile, result)
+    }
+
+    fn preflight(&self) -> Result<(), Vec<PreflightIssue>> {
+        self.options.preflight(&self.profile)
+    }
+}
+
+pub struct ChildSandbox {
+    profile: Profile,
+}
+
+impl ChildSandbox {
+    pub fn new(profile: Profile) -> ChildSandbox {
+        ChildSandbox::with_options(profile, ChildSandboxOptions::new())
+    }
+
+    /// Creates a sandbox for the profile that `Sandbox::start()` passed to this process, so that
+    /// the child needn't rebuild it. Fails with `InvalidData` if the profile was damaged in
+    /// transit, wrapping a `DecodeError`.
+    pub fn from_environment() -> io::Result<ChildSandbox> {
+        ChildSandbox::from_environment_with_options(ChildSandboxOptions::new())
+    }
+
+    /// Like `from_environment()`, with options.
+    pub fn from_environment_with_options(options: ChildSandboxOptions)
+                                         -> io::Result<ChildSandbox> {
+        Ok(ChildSandbox::with_options(sandbox::profile_from_environment()?, options))
+    }
+
+    /// `unveil()` and `pledge()` confine the whole process, so none of the options make a
+    /// difference here.
+    pub fn with_options(profile: Profile, _: ChildSandboxOptions) -> ChildSandbox {
+        ChildSandbox {
+            profile,
+        }
+    }
+
+    /// Unveils the paths the profile allows, skipping those that don't exist, and then hides
+    /// everything else for good.
+    fn unveil(&self) -> io::Result<()> {
+        for operation in self.profile.allowed_operations() {
+            let (path, permissions) = match unveil_permissions(operation) {
+                Some(unveiled) => unveiled,
+                None => continue,
+            };
+            let c_path = CString::new(path.as_os_str().as_bytes())?;
+            let c_permissions = CString::new(permissions).unwrap();
+            if unsafe { libc::unveil(c_path.as_ptr(), c_permissions.as_ptr()) } != 0 {
+                let err = io::Error::last_os_error();
+                if err.raw_os_error() != Some(libc::ENOENT) {
+                    return Err(err)
+                }
+            }
+        }
+        if unsafe { libc::unveil(ptr::null(), ptr::null()) } != 0 {
+            return Err(io::Error::last_os_error())
+        }
+        Ok(())
+    }
+
+    /// Pledges the profile's promises, for this process and the programs it runs.
+    fn pledge(&self) -> io::Result<()> {
+        let promises = CString::new(promises(&self.profile)).unwrap();
+        if unsafe { libc::pledge(promises.as_ptr(), promises.as_ptr()) } != 0 {
+            return Err(io::Error::last_os_error())
+        }
+        Ok(())
+    }
+}
+
+impl ChildSandboxMethods for ChildSandbox {
+    #[inline(never)]
+    fn activate_with_hook<F>(&self, hook: F) -> Result<(),ActivationError> where F: FnOnce() {
+        sandbox::run_activation_hook(hook);
+        match self.unveil().and_then(|()| self.pledge()) {
+            Ok(()) => Ok(()),
+            Err(err) => {
+                error!("Failed to init sandbox: {}", err);
+                Err(ActivationError::RestrictionFailed(SandboxError::from(err)))
+            }
+        }
+    }
+
+    /// Probes only what the pledge lets the child try: opening a file without `rpath`, or a
+    /// socket without `inet`, would end the process instead of failing.
+    fn verify(&self) -> VerificationReport {
+        let mut report = VerificationReport::default();
+        let promises = promises(&self.profile);
+        let promised = |promise: &str| promises.split(' ').any(|promised| promised == promise);
+        if verify::profile_grants_probe_path(&self.profile) {
+            verify::skip_file_access(&mut report)
+        } else if promised("rpath") {
+            verify::probe_file_access(&mut report)
+        } else {
+            report.push("file-access",
+                        ProbeStatus::Passed,
+                        "the pledge doesn't promise rpath".to_owned())
+        }
+        if promised("inet") {
+            verify::skip_network(&mut report)
+        } else {
+            report.push("network",
+                        ProbeStatus::Passed,
+                        "the pledge doesn't promise inet".to_owned())
+        }
+        report
+    }
+}
@@ -0,0 +1,108 @@
+// Copyright 2015 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! A bounded cache of values compiled from sandbox profiles.
+//!
+//! Compiling a profile into its platform representation (a BPF program on Linux, a Seatbelt
+//! profile on Mac OS X) is a pure function of the profile, so processes that create many sandboxes
+//! with equal profiles can reuse the result.
+
+use crate::profile::Profile;
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+
+/// The number of compiled profiles a cache holds before evicting the oldest one.
+pub const DEFAULT_CAPACITY: usize = 32;
+
+/// A thread-safe cache mapping profiles to values compiled from them.
+///
+/// Entries are keyed by the profile itself rather than only its hash, so distinct profiles never
+/// share an entry. When the cache is full, the least recently inserted entry is evicted.
+pub struct ProfileCache<T> {
+    state: Mutex<CacheState<T>>,
+}
+
+struct CacheState<T> {
+    entries: HashMap<Profile, Arc<T>>,
+    insertion_order: VecDeque<Profile>,
+    capacity: usize,
+    enabled: bool,
+}
+
+impl<T> ProfileCache<T> {
+    /// Creates a new, empty, enabled cache holding at most `capacity` entries.
+    pub fn new(capacity: usize) -> ProfileCache<T> {
+        ProfileCache {
+            state: Mutex::new(CacheState {
+                entries: HashMap::new(),
+                insertion_order: VecDeque::new(),
+                capacity,
+                enabled: true,
+            }),
+        }
+    }
+
+    /// Returns the value compiled from `profile`, calling `compile` only if it isn't cached.
+    ///
+    /// If the cache is disabled, `compile` is always called and its result isn't retained.
+    pub fn get_or_insert_with<F>(&self, profile: &Profile, compile: F) -> Arc<T>
+                                 where F: FnOnce(&Profile) -> T {
+        let mut state = self.state.lock().unwrap();
+        if !state.enabled || state.capacity == 0 {
+            return Arc::new(compile(profile))
+        }
+        if let Some(value) = state.entries.get(profile) {
+            return value.clone()
+        }
+
+        let value = Arc::new(compile(profile));
+        if state.entries.len() >= state.capacity {
+            if let Some(oldest) = state.insertion_order.pop_front() {
+                state.entries.remove(&oldest);
+            }
+        }
+        state.entries.insert(profile.clone(), value.clone());
+        state.insertion_order.push_back(profile.clone());
+        value
+    }
+
+    /// Enables or disables the cache. Disabling it also drops every cached entry.
+    pub fn set_enabled(&self, enabled: bool) {
+        let mut state = self.state.lock().unwrap();
+        state.enabled = enabled;
+        if !enabled {
+            state.entries.clear();
+            state.insertion_order.clear();
+        }
+    }
+
+    /// Returns true if the cache is currently enabled.
+    pub fn is_enabled(&self) -> bool {
+        self.state.lock().unwrap().enabled
+    }
+
+    /// Returns the number of cached entries.
+    pub fn len(&self) -> usize {
+        self.state.lock().unwrap().entries.len()
+    }
+
+    /// Returns true if nothing is cached.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Drops every cached entry.
+    pub fn clear(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.entries.clear();
+        state.insertion_order.clear();
+    }
+}
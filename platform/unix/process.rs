@@ -10,13 +10,23 @@
 
 //! Child process management on POSIX systems.
 
-use sandbox::Command;
+use crate::event_log::{EventRecord, EventSink};
+#[cfg(any(target_os="android", target_os="linux"))]
+use crate::platform::linux::cgroup::Cgroup;
+#[cfg(any(target_os="android", target_os="linux"))]
+use crate::platform::linux::deadline::Deadline;
+#[cfg(any(target_os="android", target_os="linux"))]
+use crate::platform::linux::jail::JailDirectory;
+use crate::sandbox::{ActivationError, Command};
+use crate::telemetry::{Mechanism, ResourceUsage, StageTimings, TelemetrySink};
 
-use libc::{execve, fork, pid_t, waitpid, WEXITSTATUS, WIFEXITED, WTERMSIG};
+use libc::{execve, fork, pid_t, wait4, WEXITSTATUS, WIFEXITED, WTERMSIG};
+#[cfg(any(target_os="android", target_os="linux"))]
+use log::error;
 use std::ffi::CString;
 use std::io;
+use std::mem;
 use std::ptr;
-use std::str;
 
 pub fn exec(command: &Command) -> io::Error {
     let mut args: Vec<_> = vec![command.module_path.as_ptr()];
@@ -29,17 +39,20 @@ pub fn exec(command: &Command) -> io::Error {
         .env
         .iter()
         .map(|(key, value)| {
-            let entry = format!(
-                "{}={}",
-                str::from_utf8(key.to_bytes()).unwrap(),
-                str::from_utf8(value.to_bytes()).unwrap()
-            );
+            let mut entry = key.to_bytes().to_vec();
+            entry.push(b'=');
+            entry.extend_from_slice(value.to_bytes());
             CString::new(entry).unwrap()
         }).collect();
     let mut env: Vec<_> = env.iter().map(|entry| entry.as_ptr()).collect();
     env.push(ptr::null());
 
     unsafe {
+        for &fd in command.inherited_fds.iter() {
+            if libc::fcntl(fd, libc::F_SETFD, 0) != 0 {
+                return io::Error::last_os_error()
+            }
+        }
         execve(command.module_path.as_ptr(), args.as_ptr(), env.as_ptr());
     }
 
@@ -53,7 +66,19 @@ pub fn spawn(command: &Command) -> io::Result<Process> {
                 drop(exec(command));
                 panic!()
             }
-            pid => Ok(Process { pid: pid }),
+            pid => Ok(Process {
+                pid,
+                events: EventSink::default(),
+                telemetry: TelemetrySink::default(),
+                timings: StageTimings::default(),
+                mechanisms: Vec::new(),
+                #[cfg(any(target_os="android", target_os="linux"))]
+                jail: None,
+                #[cfg(any(target_os="android", target_os="linux"))]
+                cgroup: None,
+                #[cfg(any(target_os="android", target_os="linux"))]
+                deadline: None,
+            }),
         }
     }
 }
@@ -61,42 +86,154 @@ pub fn spawn(command: &Command) -> io::Result<Process> {
 #[allow(missing_copy_implementations)]
 pub struct Process {
     pub pid: pid_t,
+    pub(crate) events: EventSink,
+    pub(crate) telemetry: TelemetrySink,
+    /// How long each stage of starting the process and activating its sandbox took.
+    pub(crate) timings: StageTimings,
+    /// The mechanisms confining the process.
+    pub(crate) mechanisms: Vec<Mechanism>,
+    /// The jail directory to remove once the process has exited.
+    #[cfg(any(target_os="android", target_os="linux"))]
+    pub(crate) jail: Option<JailDirectory>,
+    /// The cgroup to remove once the process has exited.
+    #[cfg(any(target_os="android", target_os="linux"))]
+    pub(crate) cgroup: Option<Cgroup>,
+    /// The wall-clock limit the process is killed at.
+    #[cfg(any(target_os="android", target_os="linux"))]
+    pub(crate) deadline: Option<Deadline>,
 }
 
 impl Process {
+    /// Returns the mechanisms confining the process, leaving out those that were disabled or that
+    /// this machine lacks.
+    pub fn mechanisms(&self) -> &[Mechanism] {
+        &self.mechanisms
+    }
+
     pub fn wait(&self) -> io::Result<ExitStatus> {
+        // Let the deadline's watcher know the process has exited before reaping it frees the
+        // process ID for reuse.
+        #[cfg(any(target_os="android", target_os="linux"))]
+        if let Some(ref deadline) = self.deadline {
+            loop {
+                let mut info: libc::siginfo_t = unsafe { mem::zeroed() };
+                let flags = libc::WEXITED | libc::WNOWAIT;
+                if unsafe { libc::waitid(libc::P_PID, self.pid as libc::id_t, &mut info, flags) } ==
+                        0 {
+                    break
+                }
+                let err = io::Error::last_os_error();
+                if err.kind() != io::ErrorKind::Interrupted {
+                    return Err(err)
+                }
+            }
+            deadline.child_exited()
+        }
+
         let mut stat = 0;
+        let mut rusage: libc::rusage = unsafe { mem::zeroed() };
         loop {
-            let pid = unsafe { waitpid(-1, &mut stat, 0) };
-            if pid < 0 {
-                return Err(io::Error::last_os_error());
-            }
+            let pid = unsafe { wait4(self.pid, &mut stat, 0, &mut rusage) };
             if pid == self.pid {
                 break;
             }
+            let err = io::Error::last_os_error();
+            if err.kind() != io::ErrorKind::Interrupted {
+                return Err(err);
+            }
         }
 
-        unsafe {
-            if WIFEXITED(stat) {
-                Ok(ExitStatus::Code(WEXITSTATUS(stat) as i32))
-            } else {
-                Ok(ExitStatus::Signal(WTERMSIG(stat) as i32))
+        #[cfg(any(target_os="android", target_os="linux"))]
+        if let Some(ref jail) = self.jail {
+            if let Err(err) = jail.remove() {
+                error!("failed to remove jail directory {}: {}", jail.path().display(), err)
+            }
+        }
+        #[cfg(any(target_os="android", target_os="linux"))]
+        if let Some(ref cgroup) = self.cgroup {
+            if let Err(err) = cgroup.remove() {
+                error!("failed to remove cgroup {}: {}", cgroup.path().display(), err)
             }
         }
+
+        let status = if WIFEXITED(stat) {
+            ExitStatus::Code(WEXITSTATUS(stat))
+        } else {
+            ExitStatus::Signal(WTERMSIG(stat))
+        };
+        #[cfg(any(target_os="android", target_os="linux"))]
+        let status = match (status, self.deadline.as_ref()) {
+            (ExitStatus::Signal(libc::SIGKILL), Some(deadline)) if deadline.expired() => {
+                ExitStatus::TimedOut
+            }
+            _ => status,
+        };
+        if self.events.is_enabled() {
+            self.events.record(EventRecord::for_exit(self.pid, status))
+        }
+        self.telemetry.exited(self.pid, status, Some(&ResourceUsage::from_rusage(&rusage)));
+        Ok(status)
     }
 }
 
+/// A child started by `SandboxMethods::start_async()`, which a Tokio runtime can wait for.
+///
+/// `Process::wait()` blocks until the child exits, so `wait()` runs it on the runtime's blocking
+/// threads. Dropping the future `wait()` returns doesn't stop the child, which is still reaped
+/// and cleaned up after; give it a `ResourceLimits::max_wall_clock_seconds` to bound how long it
+/// runs.
+#[cfg(feature = "tokio")]
+pub struct AsyncProcess {
+    process: Process,
+}
+
+#[cfg(feature = "tokio")]
+impl AsyncProcess {
+    /// Returns the process ID of the child.
+    pub fn pid(&self) -> pid_t {
+        self.process.pid
+    }
+
+    /// Waits for the child to exit without blocking the runtime.
+    pub async fn wait(self) -> io::Result<ExitStatus> {
+        let process = self.process;
+        tokio::task::spawn_blocking(move || process.wait()).await?
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl From<Process> for AsyncProcess {
+    fn from(process: Process) -> AsyncProcess {
+        AsyncProcess {
+            process,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum ExitStatus {
     Code(i32),
     Signal(i32),
+    /// The child ran past `ResourceLimits::max_wall_clock_seconds` and was killed.
+    TimedOut,
 }
 
 impl ExitStatus {
     #[inline]
     pub fn success(&self) -> bool {
+        matches!(*self, ExitStatus::Code(0))
+    }
+
+    /// Returns true if the child was killed for a system call its sandbox prohibits.
+    pub fn is_violation(&self) -> bool {
+        matches!(*self, ExitStatus::Signal(libc::SIGSYS))
+    }
+
+    /// Returns the activation error the child reported by exiting with its exit code, if any.
+    pub fn activation_error(&self) -> Option<ActivationError> {
         match *self {
-            ExitStatus::Code(0) => true,
-            _ => false,
+            ExitStatus::Code(code) => ActivationError::from_exit_code(code),
+            ExitStatus::Signal(_) | ExitStatus::TimedOut => None,
         }
     }
 }
@@ -0,0 +1,123 @@
+// Copyright 2015 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Probes shared by the `ChildSandboxMethods::verify()` implementations on POSIX systems.
+
+use crate::profile::{Operation, PathPattern, Profile};
+use crate::sandbox::{ProbeStatus, VerificationReport};
+
+use libc::c_int;
+use std::ffi::CString;
+use std::io;
+use std::mem;
+use std::net::Ipv4Addr;
+use std::path::PathBuf;
+
+/// The TCP port of the discard service, which the network probe tries to reach.
+const DISCARD_PORT: u16 = 9;
+
+/// A file that exists on every supported system and that no sandbox should expose by accident.
+pub const PROBE_PATH: &str = "/etc/passwd";
+
+/// Returns true if `profile` allows opening `PROBE_PATH`, in which case probing it proves nothing.
+pub fn profile_grants_probe_path(profile: &Profile) -> bool {
+    let probe_path = PathPattern::Literal(PathBuf::from(PROBE_PATH));
+    profile.allowed_operations().iter().any(|operation| {
+        match *operation {
+            // On Linux, allowing writes or running programs allows reading too.
+            Operation::FileReadAll(ref pattern) |
+            Operation::FileWriteAll(ref pattern) |
+            Operation::FileWriteMetadata(ref pattern) |
+            Operation::FileAppend(ref pattern) |
+            Operation::ProcessExec(ref pattern) => pattern.contains(&probe_path),
+            _ => false,
+        }
+    })
+}
+
+/// Tries to open `PROBE_PATH` for reading, which must fail.
+///
+/// Only call this where the sandbox reports denied opens as errors rather than by terminating the
+/// process.
+pub fn probe_file_access(report: &mut VerificationReport) {
+    let path = CString::new(PROBE_PATH).unwrap();
+    let fd = unsafe {
+        libc::open(path.as_ptr(), libc::O_RDONLY | libc::O_CLOEXEC)
+    };
+    record_file_access(report, fd)
+}
+
+/// Records the result of an attempt to open `PROBE_PATH`, closing the file if it succeeded.
+pub fn record_file_access(report: &mut VerificationReport, fd: c_int) {
+    if fd >= 0 {
+        unsafe {
+            libc::close(fd);
+        }
+        report.push("file-access", ProbeStatus::Failed, format!("opened {}", PROBE_PATH))
+    } else {
+        report.push("file-access",
+                    ProbeStatus::Passed,
+                    format!("opening {} failed: {}", PROBE_PATH, io::Error::last_os_error()))
+    }
+}
+
+/// Records that the file access probe doesn't apply to `profile`.
+pub fn skip_file_access(report: &mut VerificationReport) {
+    report.push("file-access",
+                ProbeStatus::Skipped,
+                format!("the profile allows reading {}", PROBE_PATH))
+}
+
+/// Tries to connect a TCP socket to the discard port on the loopback interface, which must fail
+/// with a permission error. (A refused connection means the attempt reached the network stack.)
+///
+/// Only call this where the sandbox reports denied connections as errors rather than by
+/// terminating the process.
+pub fn probe_network_connect(report: &mut VerificationReport) {
+    let fd = unsafe {
+        libc::socket(libc::AF_INET, libc::SOCK_STREAM, 0)
+    };
+    if fd < 0 {
+        return report.push("network",
+                           ProbeStatus::Passed,
+                           format!("creating a socket failed: {}", io::Error::last_os_error()))
+    }
+
+    let mut address: libc::sockaddr_in = unsafe { mem::zeroed() };
+    address.sin_family = libc::AF_INET as libc::sa_family_t;
+    address.sin_port = DISCARD_PORT.to_be();
+    address.sin_addr.s_addr = u32::from(Ipv4Addr::LOCALHOST).to_be();
+    let result = unsafe {
+        libc::connect(fd,
+                      &address as *const libc::sockaddr_in as *const libc::sockaddr,
+                      mem::size_of::<libc::sockaddr_in>() as libc::socklen_t)
+    };
+    let error = io::Error::last_os_error();
+    unsafe {
+        libc::close(fd);
+    }
+    if result == 0 {
+        return report.push("network", ProbeStatus::Failed, "connected to localhost".to_owned())
+    }
+    let status = match error.raw_os_error() {
+        Some(libc::EPERM) | Some(libc::EACCES) => ProbeStatus::Passed,
+        #[cfg(target_os="freebsd")]
+        Some(libc::ECAPMODE) => ProbeStatus::Passed,
+        _ => ProbeStatus::Failed,
+    };
+    report.push("network", status, format!("connecting to localhost failed: {}", error))
+}
+
+/// Records that the network probe doesn't apply to `profile`.
+pub fn skip_network(report: &mut VerificationReport) {
+    report.push("network",
+                ProbeStatus::Skipped,
+                "the profile allows outbound network connections".to_owned())
+}
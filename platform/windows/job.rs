@@ -0,0 +1,70 @@
+// Copyright 2015 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Job Objects, which hold a sandboxed child and keep it from starting other processes.
+
+use std::io;
+use std::mem;
+use std::os::windows::io::{AsRawHandle, FromRawHandle, OwnedHandle};
+use std::ptr;
+use windows_sys::Win32::System::JobObjects::{AssignProcessToJobObject, CreateJobObjectW};
+use windows_sys::Win32::System::JobObjects::{JobObjectExtendedLimitInformation};
+use windows_sys::Win32::System::JobObjects::{SetInformationJobObject};
+use windows_sys::Win32::System::JobObjects::{JOBOBJECT_EXTENDED_LIMIT_INFORMATION};
+use windows_sys::Win32::System::JobObjects::{JOB_OBJECT_LIMIT_ACTIVE_PROCESS};
+use windows_sys::Win32::System::JobObjects::{JOB_OBJECT_LIMIT_DIE_ON_UNHANDLED_EXCEPTION};
+use windows_sys::Win32::System::JobObjects::{JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE};
+
+/// A Job Object holding at most one process, which is killed when the job is closed.
+///
+/// The one-process limit is what stands in for `ProcessExec`: nothing in the job can start
+/// another process, so the child can neither run a program nor escape the job through one.
+pub struct Job {
+    handle: OwnedHandle,
+}
+
+impl Job {
+    pub fn new() -> io::Result<Job> {
+        let handle = unsafe { CreateJobObjectW(ptr::null(), ptr::null()) };
+        if handle.is_null() {
+            return Err(io::Error::last_os_error())
+        }
+        let job = Job {
+            handle: unsafe { OwnedHandle::from_raw_handle(handle) },
+        };
+
+        let mut limits: JOBOBJECT_EXTENDED_LIMIT_INFORMATION = unsafe { mem::zeroed() };
+        limits.BasicLimitInformation.LimitFlags = JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE |
+            JOB_OBJECT_LIMIT_DIE_ON_UNHANDLED_EXCEPTION |
+            JOB_OBJECT_LIMIT_ACTIVE_PROCESS;
+        limits.BasicLimitInformation.ActiveProcessLimit = 1;
+        let result = unsafe {
+            SetInformationJobObject(job.handle.as_raw_handle(),
+                                    JobObjectExtendedLimitInformation,
+                                    &limits as *const _ as *const _,
+                                    mem::size_of::<JOBOBJECT_EXTENDED_LIMIT_INFORMATION>() as u32)
+        };
+        if result == 0 {
+            return Err(io::Error::last_os_error())
+        }
+        Ok(job)
+    }
+
+    /// Puts `process` in the job.
+    pub fn assign(&self, process: &OwnedHandle) -> io::Result<()> {
+        let result = unsafe {
+            AssignProcessToJobObject(self.handle.as_raw_handle(), process.as_raw_handle())
+        };
+        if result == 0 {
+            return Err(io::Error::last_os_error())
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,401 @@
+// Copyright 2015 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Sandboxing on Windows via Job Objects, restricted tokens, and the Windows Filtering Platform.
+//!
+//! The child runs with a restricted token, which reaches only what every user of the machine
+//! can, plus the files the profile allows reading, whose ACLs grant the token access for as long
+//! as the child's `Process` lives. It runs in a Job Object that holds no other process, so it
+//! can't start programs, and that kills it when the `Process` is dropped. Unless the profile
+//! allows every outbound connection, WFP filters block those it doesn't allow. On activation the
+//! child forbids itself dynamic code and libraries not signed by Microsoft.
+
+pub mod job;
+pub mod process;
+pub mod token;
+pub mod wfp;
+
+use crate::error::SandboxError;
+use crate::platform::windows::job::Job;
+use crate::platform::windows::process::Process;
+use crate::platform::windows::token::{FileGrant, Sid};
+use crate::platform::windows::wfp::FilterEngine;
+use crate::profile::{self, AddressPattern, DecodeError, Decoder, Describe, OperationSupport};
+use crate::profile::{OperationSupportLevel, PathPattern, Profile, ProhibitionSupport};
+use crate::sandbox::{self, ActivationError, ChildSandboxMethods, ChildSandboxOptions, Command};
+use crate::sandbox::{PreflightIssue, ProbeStatus, ProfilePayload, SandboxMethods};
+use crate::sandbox::{ResourceLimits, SandboxLimits, SandboxOptions, VerificationReport};
+use crate::telemetry::Mechanism;
+
+use log::error;
+use std::ffi::OsStr;
+use std::io;
+use std::mem;
+use std::net::{Ipv4Addr, TcpStream};
+use std::os::windows::io::{AsRawHandle, FromRawHandle, OwnedHandle};
+use std::path::Path;
+use std::ptr;
+use std::sync::Arc;
+use windows_sys::Win32::Foundation::{ERROR_FILE_NOT_FOUND, ERROR_PATH_NOT_FOUND};
+use windows_sys::Win32::Security::{IsTokenRestricted, TOKEN_QUERY};
+use windows_sys::Win32::Storage::FileSystem::{FILE_GENERIC_EXECUTE, FILE_GENERIC_READ};
+use windows_sys::Win32::System::SystemServices::PROCESS_MITIGATION_BINARY_SIGNATURE_POLICY;
+use windows_sys::Win32::System::SystemServices::PROCESS_MITIGATION_DYNAMIC_CODE_POLICY;
+use windows_sys::Win32::System::Threading::{GetCurrentProcess, GetProcessMitigationPolicy};
+use windows_sys::Win32::System::Threading::{OpenProcessToken, PROCESS_MITIGATION_POLICY};
+use windows_sys::Win32::System::Threading::{ProcessDynamicCodePolicy, ProcessSignaturePolicy};
+use windows_sys::Win32::System::Threading::SetProcessMitigationPolicy;
+
+/// The port the network probe connects to.
+const DISCARD_PORT: u16 = 9;
+
+impl OperationSupport for profile::Operation {
+    fn support(&self) -> OperationSupportLevel {
+        match *self {
+            profile::Operation::FileReadAll(PathPattern::Literal(_)) |
+            profile::Operation::FileReadAll(PathPattern::Subpath(_)) |
+            profile::Operation::NetworkOutbound(AddressPattern::All) |
+            profile::Operation::NetworkOutbound(AddressPattern::Tcp(_)) => {
+                OperationSupportLevel::CanBeAllowed
+            }
+            // ACL entries only name paths.
+            profile::Operation::FileReadAll(PathPattern::Glob(_)) => {
+                OperationSupportLevel::CannotBeAllowedPrecisely
+            }
+            _ => OperationSupportLevel::NeverAllowed
+        }
+    }
+
+    /// Restricted tokens keep the right to traverse directories and read their attributes, the
+    /// filters only see connections going out, and nothing stops a process asking Windows about
+    /// the machine.
+    fn prohibition_support(&self) -> ProhibitionSupport {
+        match *self {
+            profile::Operation::FileReadMetadata(_) |
+            profile::Operation::NetworkInbound(_) |
+            profile::Operation::SystemInfoRead => ProhibitionSupport::NotEnforced,
+            _ => ProhibitionSupport::from_support_level(self.support()),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Operation { }
+
+/// Identifies Windows-specific operations in encoded profiles.
+pub(crate) const OPERATION_ENCODING_ID: u8 = 5;
+
+impl Operation {
+    pub(crate) fn encode(&self, _: &mut Vec<u8>) {
+        match *self {}
+    }
+
+    /// There are no Windows-specific operations to decode.
+    pub(crate) fn decode(_: &mut Decoder) -> Result<Operation,DecodeError> {
+        Err(DecodeError::Malformed)
+    }
+}
+
+impl Describe for Operation {
+    fn describe(&self) -> String {
+        match *self {}
+    }
+}
+
+/// Returns the path whose ACL `operation` grants a sandbox access to, and whether the entry is
+/// inherited by everything inside it, or `None` if it grants access to nothing.
+pub fn granted_path(operation: &profile::Operation) -> Option<(&Path, bool)> {
+    match *operation {
+        profile::Operation::FileReadAll(PathPattern::Literal(ref path)) => Some((path, false)),
+        profile::Operation::FileReadAll(PathPattern::Subpath(ref path)) => Some((path, true)),
+        _ => None,
+    }
+}
+
+/// Returns the TCP ports that `profile` allows connections to, or `None` if it allows every
+/// outbound connection and so needs no filters.
+fn allowed_ports(profile: &Profile) -> Option<Vec<u16>> {
+    let mut ports = Vec::new();
+    for operation in profile.allowed_operations() {
+        match *operation {
+            profile::Operation::NetworkOutbound(AddressPattern::All) => return None,
+            profile::Operation::NetworkOutbound(AddressPattern::Tcp(port)) => ports.push(port),
+            _ => {}
+        }
+    }
+    Some(ports)
+}
+
+/// Returns the mechanisms that confine a child started with a profile, and those of them that
+/// this machine lacks. None of the mechanisms that can be disabled are used here.
+pub(crate) fn mechanisms(profile: &Profile, _: &[Mechanism]) -> (Vec<Mechanism>, Vec<Mechanism>) {
+    let mut mechanisms = vec![Mechanism::JobObject, Mechanism::RestrictedToken];
+    if allowed_ports(profile).is_some() {
+        mechanisms.push(Mechanism::FilteringPlatform)
+    }
+    mechanisms.push(Mechanism::ProcessMitigations);
+    (mechanisms, Vec::new())
+}
+
+/// Returns the filesystem `path` is on if it can't be made available to a child. Nothing is
+/// bind-mounted here, so every filesystem can.
+pub(crate) fn unbindable_filesystem(_: &Path) -> Option<&'static str> {
+    None
+}
+
+/// Returns the kernel's core dump pattern if it would keep children from dumping core into a
+/// `CoreDumps::EnabledTo` directory. Core dumps aren't redirected here, so no pattern does.
+pub(crate) fn unsupported_core_pattern() -> Option<String> {
+    None
+}
+
+pub struct Sandbox {
+    profile: Profile,
+    options: SandboxOptions,
+}
+
+impl Sandbox {
+    pub fn new(profile: Profile) -> Sandbox {
+        Sandbox::with_options(profile, SandboxOptions::new())
+    }
+
+    pub fn with_options(profile: Profile, options: SandboxOptions) -> Sandbox {
+        Sandbox {
+            profile,
+            options,
+        }
+    }
+
+    /// Resource limits need cgroup v2, so children of a sandbox given them fail to start here,
+    /// with `Unsupported`.
+    pub fn with_limits(mut self, limits: SandboxLimits) -> Sandbox {
+        self.options.limits(limits);
+        self
+    }
+
+    /// Per-process resource limits are only set on Linux, so children of a sandbox given them
+    /// fail to start here, with `Unsupported`.
+    pub fn with_resource_limits(mut self, limits: ResourceLimits) -> Sandbox {
+        self.options.process_limits(limits);
+        self
+    }
+
+    /// Grants `sid` access to the child's program and to the files the profile allows reading,
+    /// skipping those that don't exist.
+    fn grant_files(&self, program: &Path, sid: &Arc<Sid>) -> io::Result<Vec<FileGrant>> {
+        let mut grants = vec![
+            FileGrant::new(program, sid, FILE_GENERIC_READ | FILE_GENERIC_EXECUTE, false)?
+        ];
+        for operation in self.profile.allowed_operations() {
+            let (path, subpath) = match granted_path(operation) {
+                Some(granted) => granted,
+                None => continue,
+            };
+            match FileGrant::new(path, sid, FILE_GENERIC_READ, subpath) {
+                Ok(grant) => grants.push(grant),
+                Err(ref err) if err.raw_os_error() == Some(ERROR_FILE_NOT_FOUND as i32) ||
+                    err.raw_os_error() == Some(ERROR_PATH_NOT_FOUND as i32) => {}
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(grants)
+    }
+
+    fn spawn(&self, command: &Command) -> io::Result<Process> {
+        let program = match command.module_path.to_str() {
+            Ok(program) => Path::new(OsStr::new(program)),
+            Err(_) => {
+                return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                          "program path is not valid Unicode"))
+            }
+        };
+        let sid = Arc::new(Sid::for_sandbox()?);
+        let grants = self.grant_files(program, &sid)?;
+        let token = token::restricted_token(&sid)?;
+        let job = Job::new()?;
+        let filters = match allowed_ports(&self.profile) {
+            Some(ports) => Some(FilterEngine::new(program, &ports)?),
+            None => None,
+        };
+        process::spawn_confined(command, &token, job, filters, grants)
+    }
+}
+
+impl SandboxMethods for Sandbox {
+    fn profile(&self) -> &Profile {
+        &self.profile
+    }
+
+    /// The child is created suspended with its restricted token and resumed once it is in its
+    /// job.
+    fn start(&self, command: &mut Command) -> io::Result<Process> {
+        if self.options.resource_limits().is_some() {
+            let err = io::Error::new(io::ErrorKind::Unsupported, "resource limits need cgroup v2");
+            return self.options.record_start(&self.profile, Err(err))
+        }
+        if self.options.per_process_limits().is_some() ||
+                self.profile.resource_limits().is_some() {
+            let err = io::Error::new(io::ErrorKind::Unsupported,
+                                     "per-process resource limits are only set on Linux");
+            return self.options.record_start(&self.profile, Err(err))
+        }
+        let result = ProfilePayload::new(&self.profile).and_then(|payload| {
+            payload.export(command);
+            command.env("GAOL_CHILD_PROCESS", "1");
+            self.spawn(command)
+        });
+        self.options.record_start(&self.profile, result)
+    }
+
+    fn preflight(&self) -> Result<(), Vec<PreflightIssue>> {
+        self.options.preflight(&self.profile)
+    }
+}
+
+pub struct ChildSandbox {
+    profile: Profile,
+}
+
+impl ChildSandbox {
+    pub fn new(profile: Profile) -> ChildSandbox {
+        ChildSandbox::with_options(profile, ChildSandboxOptions::new())
+    }
+
+    /// Creates a sandbox for the profile that `Sandbox::start()` passed to this process, so that
+    /// the child needn't rebuild it. Fails with `InvalidData` if the profile was damaged in
+    /// transit, wrapping a `DecodeError`.
+    pub fn from_environment() -> io::Result<ChildSandbox> {
+        ChildSandbox::from_environment_with_options(ChildSandboxOptions::new())
+    }
+
+    /// Like `from_environment()`, with options.
+    pub fn from_environment_with_options(options: ChildSandboxOptions)
+                                         -> io::Result<ChildSandbox> {
+        Ok(ChildSandbox::with_options(sandbox::profile_from_environment()?, options))
+    }
+
+    /// The token, the job, and the mitigation policies all cover the whole process, so none of
+    /// the options make a difference here.
+    pub fn with_options(profile: Profile, _: ChildSandboxOptions) -> ChildSandbox {
+        ChildSandbox {
+            profile,
+        }
+    }
+
+    /// Forbids this process to load libraries not signed by Microsoft and to make code of its
+    /// own. Libraries already loaded stay usable.
+    fn mitigate(&self) -> io::Result<()> {
+        let mut signature: PROCESS_MITIGATION_BINARY_SIGNATURE_POLICY = unsafe { mem::zeroed() };
+        // MicrosoftSignedOnly.
+        signature.Anonymous.Flags = 1;
+        set_mitigation_policy(ProcessSignaturePolicy, &signature)?;
+        let mut dynamic_code: PROCESS_MITIGATION_DYNAMIC_CODE_POLICY = unsafe { mem::zeroed() };
+        // ProhibitDynamicCode.
+        dynamic_code.Anonymous.Flags = 1;
+        set_mitigation_policy(ProcessDynamicCodePolicy, &dynamic_code)
+    }
+}
+
+fn set_mitigation_policy<T>(policy: PROCESS_MITIGATION_POLICY, value: &T) -> io::Result<()> {
+    let result = unsafe {
+        SetProcessMitigationPolicy(policy, value as *const T as *const _, mem::size_of::<T>())
+    };
+    if result == 0 {
+        return Err(io::Error::last_os_error())
+    }
+    Ok(())
+}
+
+impl ChildSandboxMethods for ChildSandbox {
+    #[inline(never)]
+    fn activate_with_hook<F>(&self, hook: F) -> Result<(),ActivationError> where F: FnOnce() {
+        sandbox::run_activation_hook(hook);
+        match self.mitigate() {
+            Ok(()) => Ok(()),
+            Err(err) => {
+                error!("Failed to init sandbox: {}", err);
+                Err(ActivationError::RestrictionFailed(SandboxError::from(err)))
+            }
+        }
+    }
+
+    /// Checks that this process runs with a restricted token, may not make code, and can't
+    /// connect to the discard port on localhost. What the token can reach depends on ACLs across
+    /// the machine, so file access isn't probed.
+    fn verify(&self) -> VerificationReport {
+        let mut report = VerificationReport::default();
+        let mut token = ptr::null_mut();
+        if unsafe { OpenProcessToken(GetCurrentProcess(), TOKEN_QUERY, &mut token) } == 0 {
+            report.push("restricted-token",
+                        ProbeStatus::Failed,
+                        format!("couldn't open the process token: {}", io::Error::last_os_error()))
+        } else {
+            let token = unsafe { OwnedHandle::from_raw_handle(token) };
+            if unsafe { IsTokenRestricted(token.as_raw_handle()) } != 0 {
+                report.push("restricted-token",
+                            ProbeStatus::Passed,
+                            "the process token is restricted".to_owned())
+            } else {
+                report.push("restricted-token",
+                            ProbeStatus::Failed,
+                            "the process token is not restricted".to_owned())
+            }
+        }
+
+        let mut dynamic_code: PROCESS_MITIGATION_DYNAMIC_CODE_POLICY = unsafe { mem::zeroed() };
+        let result = unsafe {
+            GetProcessMitigationPolicy(GetCurrentProcess(),
+                                       ProcessDynamicCodePolicy,
+                                       &mut dynamic_code as *mut _ as *mut _,
+                                       mem::size_of::<PROCESS_MITIGATION_DYNAMIC_CODE_POLICY>())
+        };
+        if result == 0 {
+            report.push("mitigations",
+                        ProbeStatus::Failed,
+                        format!("couldn't query the dynamic code policy: {}",
+                                io::Error::last_os_error()))
+        } else if unsafe { dynamic_code.Anonymous.Flags } & 1 != 0 {
+            report.push("mitigations",
+                        ProbeStatus::Passed,
+                        "dynamic code is prohibited".to_owned())
+        } else {
+            report.push("mitigations",
+                        ProbeStatus::Failed,
+                        "dynamic code is allowed".to_owned())
+        }
+
+        match allowed_ports(&self.profile) {
+            Some(ref ports) if !ports.contains(&DISCARD_PORT) => probe_network(&mut report),
+            _ => {
+                report.push("network",
+                            ProbeStatus::Skipped,
+                            "the profile allows outbound network connections".to_owned())
+            }
+        }
+        report
+    }
+}
+
+/// Tries to connect to the discard port on localhost, which the filters refuse with
+/// `WSAEACCES`.
+fn probe_network(report: &mut VerificationReport) {
+    match TcpStream::connect((Ipv4Addr::LOCALHOST, DISCARD_PORT)) {
+        Ok(_) => report.push("network", ProbeStatus::Failed, "connected to localhost".to_owned()),
+        Err(err) => {
+            let status = if err.kind() == io::ErrorKind::PermissionDenied {
+                ProbeStatus::Passed
+            } else {
+                ProbeStatus::Failed
+            };
+            report.push("network", status, format!("connecting to localhost failed: {}", err))
+        }
+    }
+}
@@ -0,0 +1,312 @@
+// Copyright 2015 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Child process management on Windows.
+
+use crate::event_log::{EventRecord, EventSink};
+use crate::platform::windows::job::Job;
+use crate::platform::windows::token::FileGrant;
+use crate::platform::windows::wfp::FilterEngine;
+use crate::sandbox::{ActivationError, Command};
+use crate::telemetry::{Mechanism, ResourceUsage, StageTimings, TelemetrySink};
+
+use std::io;
+use std::mem;
+use std::os::windows::io::{AsRawHandle, FromRawHandle, OwnedHandle};
+use std::ptr;
+use std::time::Duration;
+use windows_sys::Win32::Foundation::{FILETIME, WAIT_FAILED};
+use windows_sys::Win32::System::ProcessStatus::{K32GetProcessMemoryInfo, PROCESS_MEMORY_COUNTERS};
+use windows_sys::Win32::System::Threading::{CreateProcessAsUserW, CreateProcessW};
+use windows_sys::Win32::System::Threading::{GetExitCodeProcess, GetProcessTimes, INFINITE};
+use windows_sys::Win32::System::Threading::{PROCESS_INFORMATION, ResumeThread, STARTUPINFOW};
+use windows_sys::Win32::System::Threading::{TerminateProcess, WaitForSingleObject};
+use windows_sys::Win32::System::Threading::{CREATE_SUSPENDED, CREATE_UNICODE_ENVIRONMENT};
+
+/// A process ID. Windows process IDs are `DWORD`s.
+#[allow(non_camel_case_types)]
+pub type pid_t = u32;
+
+/// Starts `command` unconfined.
+pub fn spawn(command: &Command) -> io::Result<Process> {
+    let (process, thread, pid) = create(command, None)?;
+    resume(&process, &thread)?;
+    Ok(Process::new(pid, process))
+}
+
+/// Starts `command` with `token`, in `job`, and hands it what confines it besides, to be released
+/// once it has exited.
+pub(crate) fn spawn_confined(command: &Command,
+                             token: &OwnedHandle,
+                             job: Job,
+                             filters: Option<FilterEngine>,
+                             grants: Vec<FileGrant>)
+                             -> io::Result<Process> {
+    let (process, thread, pid) = create(command, Some(token))?;
+    // The child is suspended until it is in the job, so that nothing it starts escapes it.
+    if let Err(err) = job.assign(&process).and_then(|()| resume(&process, &thread)) {
+        unsafe {
+            TerminateProcess(process.as_raw_handle(), 1);
+        }
+        return Err(err)
+    }
+    let mut process = Process::new(pid, process);
+    process.job = Some(job);
+    process.filters = filters;
+    process.grants = grants;
+    Ok(process)
+}
+
+/// Creates the process for `command`, suspended, with `token` if given and the parent's token
+/// otherwise. Returns the process, its main thread, and its ID.
+fn create(command: &Command, token: Option<&OwnedHandle>)
+          -> io::Result<(OwnedHandle, OwnedHandle, pid_t)> {
+    let application = wide(command.module_path.to_str().map_err(invalid_input)?);
+    let mut command_line = command_line(command)?;
+    let environment = environment_block(command)?;
+    let mut startup_info: STARTUPINFOW = unsafe { mem::zeroed() };
+    startup_info.cb = mem::size_of::<STARTUPINFOW>() as u32;
+    let mut information: PROCESS_INFORMATION = unsafe { mem::zeroed() };
+    let flags = CREATE_SUSPENDED | CREATE_UNICODE_ENVIRONMENT;
+    let created = unsafe {
+        match token {
+            Some(token) => {
+                CreateProcessAsUserW(token.as_raw_handle(),
+                                     application.as_ptr(),
+                                     command_line.as_mut_ptr(),
+                                     ptr::null(),
+                                     ptr::null(),
+                                     0,
+                                     flags,
+                                     environment.as_ptr() as *const _,
+                                     ptr::null(),
+                                     &startup_info,
+                                     &mut information)
+            }
+            None => {
+                CreateProcessW(application.as_ptr(),
+                               command_line.as_mut_ptr(),
+                               ptr::null(),
+                               ptr::null(),
+                               0,
+                               flags,
+                               environment.as_ptr() as *const _,
+                               ptr::null(),
+                               &startup_info,
+                               &mut information)
+            }
+        }
+    };
+    if created == 0 {
+        return Err(io::Error::last_os_error())
+    }
+    unsafe {
+        Ok((OwnedHandle::from_raw_handle(information.hProcess),
+            OwnedHandle::from_raw_handle(information.hThread),
+            information.dwProcessId))
+    }
+}
+
+fn resume(process: &OwnedHandle, thread: &OwnedHandle) -> io::Result<()> {
+    if unsafe { ResumeThread(thread.as_raw_handle()) } == u32::MAX {
+        let err = io::Error::last_os_error();
+        unsafe {
+            TerminateProcess(process.as_raw_handle(), 1);
+        }
+        return Err(err)
+    }
+    Ok(())
+}
+
+/// Returns `string` as a NUL-terminated UTF-16 string.
+pub(crate) fn wide(string: &str) -> Vec<u16> {
+    string.encode_utf16().chain(Some(0)).collect()
+}
+
+fn invalid_input<E>(_: E) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, "command is not valid Unicode")
+}
+
+/// Returns the command line for `command`, quoted the way the C runtime splits it back up.
+fn command_line(command: &Command) -> io::Result<Vec<u16>> {
+    let mut line = String::new();
+    for argument in Some(&command.module_path).into_iter().chain(command.args.iter()) {
+        if !line.is_empty() {
+            line.push(' ')
+        }
+        quote(argument.to_str().map_err(invalid_input)?, &mut line)
+    }
+    Ok(wide(&line))
+}
+
+/// Appends `argument` to `line`, quoted if it is empty or has spaces, tabs, or quotes. Inside
+/// quotes, backslashes only escape quotes and the backslashes before them.
+fn quote(argument: &str, line: &mut String) {
+    if !argument.is_empty() && !argument.contains(&[' ', '\t', '"'][..]) {
+        line.push_str(argument);
+        return
+    }
+    line.push('"');
+    let mut backslashes = 0;
+    for character in argument.chars() {
+        if character == '\\' {
+            backslashes += 1
+        } else {
+            // The backslashes before a quote are doubled, and one more escapes the quote.
+            if character == '"' {
+                line.extend((0..=backslashes).map(|_| '\\'))
+            }
+            backslashes = 0
+        }
+        line.push(character)
+    }
+    line.extend((0..backslashes).map(|_| '\\'));
+    line.push('"')
+}
+
+/// Returns the environment block for `command`: its variables, sorted, as `NAME=value` strings
+/// each ending in NUL, followed by another NUL. Windows programs can't run without `SystemRoot`,
+/// so it is passed along unless the command sets it.
+fn environment_block(command: &Command) -> io::Result<Vec<u16>> {
+    let mut variables = Vec::new();
+    for (key, value) in command.env.iter() {
+        let key = key.to_str().map_err(invalid_input)?;
+        variables.push(format!("{}={}", key, value.to_str().map_err(invalid_input)?))
+    }
+    if !command.env.keys().any(|key| key.to_bytes().eq_ignore_ascii_case(b"SystemRoot")) {
+        if let Ok(system_root) = std::env::var("SystemRoot") {
+            variables.push(format!("SystemRoot={}", system_root))
+        }
+    }
+    variables.sort_by_key(|variable| variable.to_uppercase());
+    let mut block: Vec<u16> = variables.iter().flat_map(|variable| wide(variable)).collect();
+    if block.is_empty() {
+        block.push(0)
+    }
+    block.push(0);
+    Ok(block)
+}
+
+#[allow(missing_copy_implementations)]
+pub struct Process {
+    pub pid: pid_t,
+    handle: OwnedHandle,
+    pub(crate) events: EventSink,
+    pub(crate) telemetry: TelemetrySink,
+    /// How long each stage of starting the process and activating its sandbox took.
+    pub(crate) timings: StageTimings,
+    /// The mechanisms confining the process.
+    pub(crate) mechanisms: Vec<Mechanism>,
+    /// The Job Object the process is in. Closing it kills whatever is left of the process and its
+    /// descendants.
+    pub(crate) job: Option<Job>,
+    /// The filters on the process's connections, which go away with the engine session.
+    pub(crate) filters: Option<FilterEngine>,
+    /// The ACL entries letting the process's token reach the files its profile allows, which
+    /// are removed when the process is dropped.
+    pub(crate) grants: Vec<FileGrant>,
+}
+
+impl Process {
+    fn new(pid: pid_t, handle: OwnedHandle) -> Process {
+        Process {
+            pid,
+            handle,
+            events: EventSink::default(),
+            telemetry: TelemetrySink::default(),
+            timings: StageTimings::default(),
+            mechanisms: Vec::new(),
+            job: None,
+            filters: None,
+            grants: Vec::new(),
+        }
+    }
+
+    /// Returns the mechanisms confining the process, leaving out those that were disabled or that
+    /// this machine lacks.
+    pub fn mechanisms(&self) -> &[Mechanism] {
+        &self.mechanisms
+    }
+
+    pub fn wait(&self) -> io::Result<ExitStatus> {
+        if unsafe { WaitForSingleObject(self.handle.as_raw_handle(), INFINITE) } == WAIT_FAILED {
+            return Err(io::Error::last_os_error())
+        }
+        let mut code = 0;
+        if unsafe { GetExitCodeProcess(self.handle.as_raw_handle(), &mut code) } == 0 {
+            return Err(io::Error::last_os_error())
+        }
+
+        let status = ExitStatus::Code(code as i32);
+        if self.events.is_enabled() {
+            self.events.record(EventRecord::for_exit(self.pid, status))
+        }
+        self.telemetry.exited(self.pid, status, self.resource_usage().as_ref());
+        Ok(status)
+    }
+
+    /// Returns the resources the exited process used, if Windows will say.
+    fn resource_usage(&self) -> Option<ResourceUsage> {
+        let mut times: [FILETIME; 4] = unsafe { mem::zeroed() };
+        let mut counters: PROCESS_MEMORY_COUNTERS = unsafe { mem::zeroed() };
+        counters.cb = mem::size_of::<PROCESS_MEMORY_COUNTERS>() as u32;
+        unsafe {
+            let [ref mut creation, ref mut exit, ref mut kernel, ref mut user] = times;
+            if GetProcessTimes(self.handle.as_raw_handle(), creation, exit, kernel, user) == 0 ||
+                    K32GetProcessMemoryInfo(self.handle.as_raw_handle(),
+                                            &mut counters,
+                                            counters.cb) == 0 {
+                return None
+            }
+        }
+        Some(ResourceUsage {
+            user_time: filetime_to_duration(&times[3]),
+            system_time: filetime_to_duration(&times[2]),
+            max_resident_set_kb: counters.PeakWorkingSetSize as u64 / 1024,
+        })
+    }
+}
+
+/// Converts a `FILETIME` holding an interval, in units of 100 nanoseconds, to a `Duration`.
+fn filetime_to_duration(filetime: &FILETIME) -> Duration {
+    let ticks = (filetime.dwHighDateTime as u64) << 32 | filetime.dwLowDateTime as u64;
+    Duration::from_nanos(ticks * 100)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ExitStatus {
+    Code(i32),
+    /// Never reported on Windows, which has no signals.
+    Signal(i32),
+    /// Never reported on Windows, which doesn't enforce
+    /// `ResourceLimits::max_wall_clock_seconds`.
+    TimedOut,
+}
+
+impl ExitStatus {
+    #[inline]
+    pub fn success(&self) -> bool {
+        matches!(*self, ExitStatus::Code(0))
+    }
+
+    /// Returns true if the child was killed for an operation its sandbox prohibits. Nothing on
+    /// Windows kills for that: prohibited operations fail.
+    pub fn is_violation(&self) -> bool {
+        false
+    }
+
+    /// Returns the activation error the child reported by exiting with its exit code, if any.
+    pub fn activation_error(&self) -> Option<ActivationError> {
+        match *self {
+            ExitStatus::Code(code) => ActivationError::from_exit_code(code),
+            ExitStatus::Signal(_) | ExitStatus::TimedOut => None,
+        }
+    }
+}
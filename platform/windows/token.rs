@@ -0,0 +1,287 @@
+// Copyright 2015 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Restricted tokens, and the ACL entries that let them reach the files a profile allows.
+//!
+//! A restricted token passes an access check only if both its usual SIDs and its restricting
+//! SIDs are granted access. The restricting SIDs here are `Everyone`, `BUILTIN\Users`, and a SID
+//! made up for the one child, so the child keeps what the machine lets every user reach, such as
+//! the system's libraries, and loses the user's own files except where an ACL entry grants the
+//! made-up SID access to them.
+
+use crate::platform::windows::process::wide;
+
+use log::error;
+use std::io;
+use std::mem;
+use std::os::windows::io::{AsRawHandle, FromRawHandle, OwnedHandle};
+use std::path::Path;
+use std::process;
+use std::ptr;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+use windows_sys::Win32::Foundation::{GENERIC_ALL, HANDLE, LocalFree};
+use windows_sys::Win32::Security::{AllocateAndInitializeSid, CreateRestrictedToken, FreeSid};
+use windows_sys::Win32::Security::{GetTokenInformation, SetTokenInformation, ACE_FLAGS, ACL};
+use windows_sys::Win32::Security::{DACL_SECURITY_INFORMATION, DISABLE_MAX_PRIVILEGE, PSID};
+use windows_sys::Win32::Security::{NO_INHERITANCE, SUB_CONTAINERS_AND_OBJECTS_INHERIT};
+use windows_sys::Win32::Security::{SECURITY_NT_AUTHORITY, SECURITY_RESOURCE_MANAGER_AUTHORITY};
+use windows_sys::Win32::Security::{SECURITY_WORLD_SID_AUTHORITY, SID_AND_ATTRIBUTES};
+use windows_sys::Win32::Security::{SID_IDENTIFIER_AUTHORITY, TOKEN_DEFAULT_DACL};
+use windows_sys::Win32::Security::{TOKEN_ADJUST_DEFAULT, TOKEN_ASSIGN_PRIMARY, TOKEN_DUPLICATE};
+use windows_sys::Win32::Security::{TOKEN_QUERY, TokenDefaultDacl};
+use windows_sys::Win32::Security::Authorization::{GetNamedSecurityInfoW, SetEntriesInAclW};
+use windows_sys::Win32::Security::Authorization::{SetNamedSecurityInfoW, EXPLICIT_ACCESS_W};
+use windows_sys::Win32::Security::Authorization::{ACCESS_MODE, GRANT_ACCESS, REVOKE_ACCESS};
+use windows_sys::Win32::Security::Authorization::{NO_MULTIPLE_TRUSTEE, SE_FILE_OBJECT};
+use windows_sys::Win32::Security::Authorization::{TRUSTEE_IS_SID, TRUSTEE_IS_UNKNOWN, TRUSTEE_W};
+use windows_sys::Win32::System::SystemServices::{DOMAIN_ALIAS_RID_USERS, SECURITY_WORLD_RID};
+use windows_sys::Win32::System::SystemServices::SECURITY_BUILTIN_DOMAIN_RID;
+use windows_sys::Win32::System::Threading::{GetCurrentProcess, OpenProcessToken};
+
+/// A SID, freed when dropped.
+pub struct Sid {
+    sid: PSID,
+}
+
+// The SID is only ever read once it is made.
+unsafe impl Send for Sid {}
+unsafe impl Sync for Sid {}
+
+impl Sid {
+    fn new(authority: &SID_IDENTIFIER_AUTHORITY, subauthorities: &[u32]) -> io::Result<Sid> {
+        let mut all = [0; 8];
+        all[..subauthorities.len()].copy_from_slice(subauthorities);
+        let mut sid = ptr::null_mut();
+        let result = unsafe {
+            AllocateAndInitializeSid(authority,
+                                     subauthorities.len() as u8,
+                                     all[0], all[1], all[2], all[3],
+                                     all[4], all[5], all[6], all[7],
+                                     &mut sid)
+        };
+        if result == 0 {
+            return Err(io::Error::last_os_error())
+        }
+        Ok(Sid {
+            sid,
+        })
+    }
+
+    /// Makes up a SID for one sandboxed child, from this process's ID, a counter, and the time,
+    /// under the resource manager authority, which no account is in.
+    pub fn for_sandbox() -> io::Result<Sid> {
+        static NEXT: AtomicU32 = AtomicU32::new(0);
+        let nanos = SystemTime::now().duration_since(UNIX_EPOCH)
+                                     .map_or(0, |time| time.subsec_nanos());
+        Sid::new(&SECURITY_RESOURCE_MANAGER_AUTHORITY,
+                 &[process::id(), NEXT.fetch_add(1, Ordering::Relaxed), nanos])
+    }
+
+    fn as_psid(&self) -> PSID {
+        self.sid
+    }
+}
+
+impl Drop for Sid {
+    fn drop(&mut self) {
+        unsafe {
+            FreeSid(self.sid);
+        }
+    }
+}
+
+/// Returns an entry for `SetEntriesInAclW()` applying `mode` with `permissions` to `sid`.
+fn explicit_access(sid: &Sid, permissions: u32, mode: ACCESS_MODE, inheritance: ACE_FLAGS)
+                   -> EXPLICIT_ACCESS_W {
+    EXPLICIT_ACCESS_W {
+        grfAccessPermissions: permissions,
+        grfAccessMode: mode,
+        grfInheritance: inheritance,
+        Trustee: TRUSTEE_W {
+            pMultipleTrustee: ptr::null_mut(),
+            MultipleTrusteeOperation: NO_MULTIPLE_TRUSTEE,
+            TrusteeForm: TRUSTEE_IS_SID,
+            TrusteeType: TRUSTEE_IS_UNKNOWN,
+            ptstrName: sid.as_psid() as *mut u16,
+        },
+    }
+}
+
+/// Returns a primary token for a child, restricted to `Everyone`, `BUILTIN\Users`, and `sid`, with
+/// every privilege but the ability to traverse directories removed.
+///
+/// The objects the child makes, its own threads among them, get a default ACL that also grants
+/// `sid` access, without which the child couldn't open them.
+pub fn restricted_token(sid: &Sid) -> io::Result<OwnedHandle> {
+    let mut token: HANDLE = ptr::null_mut();
+    let access = TOKEN_DUPLICATE | TOKEN_QUERY | TOKEN_ASSIGN_PRIMARY | TOKEN_ADJUST_DEFAULT;
+    if unsafe { OpenProcessToken(GetCurrentProcess(), access, &mut token) } == 0 {
+        return Err(io::Error::last_os_error())
+    }
+    let token = unsafe { OwnedHandle::from_raw_handle(token) };
+
+    let everyone = Sid::new(&SECURITY_WORLD_SID_AUTHORITY, &[SECURITY_WORLD_RID as u32])?;
+    let users = Sid::new(&SECURITY_NT_AUTHORITY,
+                         &[SECURITY_BUILTIN_DOMAIN_RID as u32, DOMAIN_ALIAS_RID_USERS as u32])?;
+    let restricting: Vec<SID_AND_ATTRIBUTES> = [&everyone, &users, sid].iter().map(|sid| {
+        SID_AND_ATTRIBUTES {
+            Sid: sid.as_psid(),
+            Attributes: 0,
+        }
+    }).collect();
+    let mut restricted: HANDLE = ptr::null_mut();
+    let result = unsafe {
+        CreateRestrictedToken(token.as_raw_handle(),
+                              DISABLE_MAX_PRIVILEGE,
+                              0,
+                              ptr::null(),
+                              0,
+                              ptr::null(),
+                              restricting.len() as u32,
+                              restricting.as_ptr(),
+                              &mut restricted)
+    };
+    if result == 0 {
+        return Err(io::Error::last_os_error())
+    }
+    let restricted = unsafe { OwnedHandle::from_raw_handle(restricted) };
+    grant_default_acl(&restricted, sid)?;
+    Ok(restricted)
+}
+
+/// Adds an entry granting `sid` full access to the default ACL of `token`.
+fn grant_default_acl(token: &OwnedHandle, sid: &Sid) -> io::Result<()> {
+    let mut length = 0;
+    unsafe {
+        GetTokenInformation(token.as_raw_handle(),
+                            TokenDefaultDacl,
+                            ptr::null_mut(),
+                            0,
+                            &mut length);
+    }
+    // `u64`s, so that the `TOKEN_DEFAULT_DACL` at the start is aligned.
+    let mut buffer = vec![0u64; (length as usize).div_ceil(mem::size_of::<u64>())];
+    let result = unsafe {
+        GetTokenInformation(token.as_raw_handle(),
+                            TokenDefaultDacl,
+                            buffer.as_mut_ptr() as *mut _,
+                            length,
+                            &mut length)
+    };
+    if result == 0 {
+        return Err(io::Error::last_os_error())
+    }
+    let default_acl = unsafe { &*(buffer.as_ptr() as *const TOKEN_DEFAULT_DACL) };
+
+    let access = explicit_access(sid, GENERIC_ALL, GRANT_ACCESS, NO_INHERITANCE);
+    let mut acl: *mut ACL = ptr::null_mut();
+    let error = unsafe { SetEntriesInAclW(1, &access, default_acl.DefaultDacl, &mut acl) };
+    if error != 0 {
+        return Err(io::Error::from_raw_os_error(error as i32))
+    }
+    let information = TOKEN_DEFAULT_DACL {
+        DefaultDacl: acl,
+    };
+    let result = unsafe {
+        let result = SetTokenInformation(token.as_raw_handle(),
+                                         TokenDefaultDacl,
+                                         &information as *const TOKEN_DEFAULT_DACL as *const _,
+                                         mem::size_of::<TOKEN_DEFAULT_DACL>() as u32);
+        LocalFree(acl as *mut _);
+        result
+    };
+    if result == 0 {
+        return Err(io::Error::last_os_error())
+    }
+    Ok(())
+}
+
+/// An entry in the ACL of a file or directory granting a sandbox's SID access to it, removed
+/// when dropped. A directory's entry is inherited by everything inside it.
+pub struct FileGrant {
+    path: Vec<u16>,
+    sid: Arc<Sid>,
+}
+
+impl FileGrant {
+    /// Grants `sid` `permissions` on `path`, and on everything inside it if `subpath` is true.
+    pub fn new(path: &Path, sid: &Arc<Sid>, permissions: u32, subpath: bool)
+               -> io::Result<FileGrant> {
+        let path = match path.to_str() {
+            Some(path) => wide(path),
+            None => {
+                return Err(io::Error::new(io::ErrorKind::InvalidInput, "path is not valid Unicode"))
+            }
+        };
+        let inheritance = if subpath { SUB_CONTAINERS_AND_OBJECTS_INHERIT } else { NO_INHERITANCE };
+        update_acl(&path, &explicit_access(sid, permissions, GRANT_ACCESS, inheritance))?;
+        Ok(FileGrant {
+            path,
+            sid: sid.clone(),
+        })
+    }
+}
+
+impl Drop for FileGrant {
+    fn drop(&mut self) {
+        let access = explicit_access(&self.sid, 0, REVOKE_ACCESS, NO_INHERITANCE);
+        if let Err(err) = update_acl(&self.path, &access) {
+            error!("failed to remove a sandbox's ACL entry from {}: {}",
+                   String::from_utf16_lossy(&self.path[..self.path.len() - 1]),
+                   err)
+        }
+    }
+}
+
+/// Applies `access` to the ACL of the file or directory at `path`, a NUL-terminated UTF-16
+/// string.
+fn update_acl(path: &[u16], access: &EXPLICIT_ACCESS_W) -> io::Result<()> {
+    let mut old_acl: *mut ACL = ptr::null_mut();
+    let mut descriptor = ptr::null_mut();
+    let error = unsafe {
+        GetNamedSecurityInfoW(path.as_ptr(),
+                              SE_FILE_OBJECT,
+                              DACL_SECURITY_INFORMATION,
+                              ptr::null_mut(),
+                              ptr::null_mut(),
+                              &mut old_acl,
+                              ptr::null_mut(),
+                              &mut descriptor)
+    };
+    if error != 0 {
+        return Err(io::Error::from_raw_os_error(error as i32))
+    }
+    let mut new_acl: *mut ACL = ptr::null_mut();
+    let error = unsafe {
+        let error = SetEntriesInAclW(1, access, old_acl, &mut new_acl);
+        LocalFree(descriptor);
+        error
+    };
+    if error != 0 {
+        return Err(io::Error::from_raw_os_error(error as i32))
+    }
+    let error = unsafe {
+        let error = SetNamedSecurityInfoW(path.as_ptr(),
+                                          SE_FILE_OBJECT,
+                                          DACL_SECURITY_INFORMATION,
+                                          ptr::null_mut(),
+                                          ptr::null_mut(),
+                                          new_acl,
+                                          ptr::null());
+        LocalFree(new_acl as *mut _);
+        error
+    };
+    if error != 0 {
+        return Err(io::Error::from_raw_os_error(error as i32))
+    }
+    Ok(())
+}
@@ -0,0 +1,231 @@
+// Copyright 2015 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Filtering a child's outbound connections with the Windows Filtering Platform.
+//!
+//! WFP filters match connections by the program that makes them, not by process, so the filters
+//! here match the child's program run with a restricted token. Copies of the program run by
+//! other users, or by this one without a restricted token, connect as before; copies run in
+//! other sandboxes at the same time are filtered alike. The filters are added in a dynamic
+//! session, so they go away when the engine handle is closed or this process exits.
+
+use crate::platform::windows::process::wide;
+
+use std::io;
+use std::mem;
+use std::path::Path;
+use std::ptr;
+use windows_sys::Win32::Foundation::{HANDLE, LocalFree};
+use windows_sys::Win32::NetworkManagement::WindowsFilteringPlatform::{FwpmEngineClose0};
+use windows_sys::Win32::NetworkManagement::WindowsFilteringPlatform::{FwpmEngineOpen0};
+use windows_sys::Win32::NetworkManagement::WindowsFilteringPlatform::{FwpmFilterAdd0};
+use windows_sys::Win32::NetworkManagement::WindowsFilteringPlatform::{FwpmFreeMemory0};
+use windows_sys::Win32::NetworkManagement::WindowsFilteringPlatform::{FwpmGetAppIdFromFileName0};
+use windows_sys::Win32::NetworkManagement::WindowsFilteringPlatform::{FWPM_ACTION0};
+use windows_sys::Win32::NetworkManagement::WindowsFilteringPlatform::{FWPM_CONDITION_ALE_APP_ID};
+use windows_sys::Win32::NetworkManagement::WindowsFilteringPlatform::{FWPM_CONDITION_ALE_USER_ID};
+use windows_sys::Win32::NetworkManagement::WindowsFilteringPlatform::{FWPM_CONDITION_IP_PROTOCOL};
+use windows_sys::Win32::NetworkManagement::WindowsFilteringPlatform::FWPM_CONDITION_IP_REMOTE_PORT;
+use windows_sys::Win32::NetworkManagement::WindowsFilteringPlatform::{FWPM_FILTER0};
+use windows_sys::Win32::NetworkManagement::WindowsFilteringPlatform::{FWPM_FILTER_CONDITION0};
+use windows_sys::Win32::NetworkManagement::WindowsFilteringPlatform::FWPM_LAYER_ALE_AUTH_CONNECT_V4;
+use windows_sys::Win32::NetworkManagement::WindowsFilteringPlatform::FWPM_LAYER_ALE_AUTH_CONNECT_V6;
+use windows_sys::Win32::NetworkManagement::WindowsFilteringPlatform::{FWPM_SESSION0};
+use windows_sys::Win32::NetworkManagement::WindowsFilteringPlatform::{FWPM_SESSION_FLAG_DYNAMIC};
+use windows_sys::Win32::NetworkManagement::WindowsFilteringPlatform::{FWP_ACTION_BLOCK};
+use windows_sys::Win32::NetworkManagement::WindowsFilteringPlatform::{FWP_ACTION_PERMIT};
+use windows_sys::Win32::NetworkManagement::WindowsFilteringPlatform::{FWP_ACTION_TYPE};
+use windows_sys::Win32::NetworkManagement::WindowsFilteringPlatform::{FWP_BYTE_BLOB};
+use windows_sys::Win32::NetworkManagement::WindowsFilteringPlatform::{FWP_BYTE_BLOB_TYPE};
+use windows_sys::Win32::NetworkManagement::WindowsFilteringPlatform::{FWP_CONDITION_VALUE0};
+use windows_sys::Win32::NetworkManagement::WindowsFilteringPlatform::{FWP_CONDITION_VALUE0_0};
+use windows_sys::Win32::NetworkManagement::WindowsFilteringPlatform::{FWP_MATCH_EQUAL};
+use windows_sys::Win32::NetworkManagement::WindowsFilteringPlatform::{FWP_SECURITY_DESCRIPTOR_TYPE};
+use windows_sys::Win32::NetworkManagement::WindowsFilteringPlatform::{FWP_UINT16, FWP_UINT8};
+use windows_sys::Win32::NetworkManagement::WindowsFilteringPlatform::{FWP_VALUE0, FWP_VALUE0_0};
+use windows_sys::Win32::Security::{GetSecurityDescriptorLength, PSECURITY_DESCRIPTOR};
+use windows_sys::Win32::Security::Authorization::{self, SDDL_REVISION_1};
+use windows_sys::Win32::System::Rpc::RPC_C_AUTHN_WINNT;
+use windows_sys::core::GUID;
+
+/// A security descriptor granting Authenticated Users access. Restricted tokens fail the access
+/// check against it, because `NT AUTHORITY\Authenticated Users` isn't among their restricting
+/// SIDs, so it tells unrestricted copies of a program from sandboxed ones.
+const UNRESTRICTED_USERS: &str = "D:(A;;0x1;;;AU)";
+
+/// The IP protocol number of TCP.
+const IPPROTO_TCP: u8 = 6;
+
+/// An open session with the filter engine, holding the filters added through it.
+pub struct FilterEngine {
+    engine: HANDLE,
+}
+
+// The engine handle may be used and closed from any thread.
+unsafe impl Send for FilterEngine {}
+unsafe impl Sync for FilterEngine {}
+
+impl FilterEngine {
+    /// Blocks connections made by `program` with a restricted token, except TCP connections to
+    /// `ports`.
+    pub fn new(program: &Path, ports: &[u16]) -> io::Result<FilterEngine> {
+        let program = match program.to_str() {
+            Some(program) => wide(program),
+            None => {
+                return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                          "program path is not valid Unicode"))
+            }
+        };
+
+        let mut session: FWPM_SESSION0 = unsafe { mem::zeroed() };
+        session.flags = FWPM_SESSION_FLAG_DYNAMIC;
+        let mut engine: HANDLE = ptr::null_mut();
+        check(unsafe {
+            FwpmEngineOpen0(ptr::null(), RPC_C_AUTHN_WINNT, ptr::null(), &session, &mut engine)
+        })?;
+        let filters = FilterEngine {
+            engine,
+        };
+
+        let mut app_id: *mut FWP_BYTE_BLOB = ptr::null_mut();
+        check(unsafe { FwpmGetAppIdFromFileName0(program.as_ptr(), &mut app_id) })?;
+        let result = SecurityDescriptor::new(UNRESTRICTED_USERS).and_then(|unrestricted| {
+            for layer in &[FWPM_LAYER_ALE_AUTH_CONNECT_V4, FWPM_LAYER_ALE_AUTH_CONNECT_V6] {
+                filters.add_filters(layer, app_id, &unrestricted, ports)?
+            }
+            Ok(())
+        });
+        unsafe {
+            FwpmFreeMemory0(&mut app_id as *mut *mut FWP_BYTE_BLOB as *mut _);
+        }
+        result.map(|()| filters)
+    }
+
+    /// Adds the filters for `layer`. The heavier a filter, the sooner it is consulted, so
+    /// unrestricted copies of the program are let through first, then connections to the
+    /// allowed ports, and everything else is blocked.
+    fn add_filters(&self,
+                   layer: &GUID,
+                   app_id: *mut FWP_BYTE_BLOB,
+                   unrestricted: &SecurityDescriptor,
+                   ports: &[u16])
+                   -> io::Result<()> {
+        let application = condition(FWPM_CONDITION_ALE_APP_ID,
+                                    FWP_BYTE_BLOB_TYPE,
+                                    FWP_CONDITION_VALUE0_0 { byteBlob: app_id });
+        let mut blob = unrestricted.as_blob();
+        let user = condition(FWPM_CONDITION_ALE_USER_ID,
+                             FWP_SECURITY_DESCRIPTOR_TYPE,
+                             FWP_CONDITION_VALUE0_0 { sd: &mut blob });
+        self.add(layer, 3, FWP_ACTION_PERMIT, &mut [application, user])?;
+
+        let tcp = condition(FWPM_CONDITION_IP_PROTOCOL,
+                            FWP_UINT8,
+                            FWP_CONDITION_VALUE0_0 { uint8: IPPROTO_TCP });
+        for &port in ports {
+            let port = condition(FWPM_CONDITION_IP_REMOTE_PORT,
+                                 FWP_UINT16,
+                                 FWP_CONDITION_VALUE0_0 { uint16: port });
+            self.add(layer, 2, FWP_ACTION_PERMIT, &mut [application, tcp, port])?
+        }
+
+        self.add(layer, 1, FWP_ACTION_BLOCK, &mut [application])
+    }
+
+    fn add(&self,
+           layer: &GUID,
+           weight: u8,
+           action: FWP_ACTION_TYPE,
+           conditions: &mut [FWPM_FILTER_CONDITION0])
+           -> io::Result<()> {
+        let mut name = wide("gaol");
+        let mut filter: FWPM_FILTER0 = unsafe { mem::zeroed() };
+        filter.displayData.name = name.as_mut_ptr();
+        filter.layerKey = *layer;
+        filter.weight = FWP_VALUE0 {
+            r#type: FWP_UINT8,
+            Anonymous: FWP_VALUE0_0 { uint8: weight },
+        };
+        filter.numFilterConditions = conditions.len() as u32;
+        filter.filterCondition = conditions.as_mut_ptr();
+        filter.action = FWPM_ACTION0 {
+            r#type: action,
+            ..unsafe { mem::zeroed() }
+        };
+        check(unsafe { FwpmFilterAdd0(self.engine, &filter, ptr::null_mut(), ptr::null_mut()) })
+    }
+}
+
+impl Drop for FilterEngine {
+    fn drop(&mut self) {
+        unsafe {
+            FwpmEngineClose0(self.engine);
+        }
+    }
+}
+
+fn condition(field: GUID, kind: i32, value: FWP_CONDITION_VALUE0_0) -> FWPM_FILTER_CONDITION0 {
+    FWPM_FILTER_CONDITION0 {
+        fieldKey: field,
+        matchType: FWP_MATCH_EQUAL,
+        conditionValue: FWP_CONDITION_VALUE0 {
+            r#type: kind,
+            Anonymous: value,
+        },
+    }
+}
+
+/// A self-relative security descriptor made from SDDL, freed when dropped.
+struct SecurityDescriptor {
+    descriptor: PSECURITY_DESCRIPTOR,
+}
+
+impl SecurityDescriptor {
+    fn new(sddl: &str) -> io::Result<SecurityDescriptor> {
+        let sddl = wide(sddl);
+        let mut descriptor = ptr::null_mut();
+        let result = unsafe {
+            Authorization::ConvertStringSecurityDescriptorToSecurityDescriptorW(
+                sddl.as_ptr(),
+                SDDL_REVISION_1,
+                &mut descriptor,
+                ptr::null_mut())
+        };
+        if result == 0 {
+            return Err(io::Error::last_os_error())
+        }
+        Ok(SecurityDescriptor {
+            descriptor,
+        })
+    }
+
+    fn as_blob(&self) -> FWP_BYTE_BLOB {
+        FWP_BYTE_BLOB {
+            size: unsafe { GetSecurityDescriptorLength(self.descriptor) },
+            data: self.descriptor as *mut u8,
+        }
+    }
+}
+
+impl Drop for SecurityDescriptor {
+    fn drop(&mut self) {
+        unsafe {
+            LocalFree(self.descriptor);
+        }
+    }
+}
+
+/// Turns a status returned by the filter engine into a result.
+fn check(status: u32) -> io::Result<()> {
+    if status != 0 {
+        return Err(io::Error::from_raw_os_error(status as i32))
+    }
+    Ok(())
+}
@@ -10,9 +10,40 @@
 
 //! Sandbox profiles—lists of permitted operations.
 
-use platform;
+use crate::platform;
+#[cfg(any(target_os="android", target_os="linux"))]
+use crate::platform::linux::syscalls::{syscall_nr, UnknownSyscall};
+use crate::sandbox::ResourceLimits;
 
-use std::path::PathBuf;
+use std::convert::TryFrom;
+use std::error::Error;
+use std::ffi::OsStr;
+use std::fmt;
+use std::fs;
+use std::mem;
+use std::net::IpAddr;
+#[cfg(unix)]
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+
+/// Byte access to `OsStr`s, which are WTF-8 inside on Windows. Decoding only takes Unicode, so
+/// paths with unpaired surrogates come out empty.
+#[cfg(windows)]
+trait OsStrExt {
+    fn from_bytes(bytes: &[u8]) -> &Self;
+    fn as_bytes(&self) -> &[u8];
+}
+
+#[cfg(windows)]
+impl OsStrExt for OsStr {
+    fn from_bytes(bytes: &[u8]) -> &OsStr {
+        OsStr::new(std::str::from_utf8(bytes).unwrap_or(""))
+    }
+
+    fn as_bytes(&self) -> &[u8] {
+        self.as_encoded_bytes()
+    }
+}
 
 /// A sandbox profile, which specifies the set of operations that this process is allowed to
 /// perform. Operations not in the list are implicitly prohibited.
@@ -20,14 +51,20 @@ use std::path::PathBuf;
 /// If the process attempts to perform an operation in the list that this platform can prohibit
 /// after the sandbox is entered via `activate()`, the operation will either fail or the process
 /// will be immediately terminated. You can check whether an operation can be prohibited on this
-/// platform with `Operation::prohibition_support()`.
+/// platform with `OperationSupport::prohibition_support()`, and choose which of the two happens
+/// on Linux with `with_violation_action()`.
 ///
 /// All profiles implicitly prohibit *at least* the following operations. Future versions of `gaol`
 /// may add operations to selectively allow these.
 ///
-///    * Opening any file for writing.
+///    * Opening any file for writing, except as `Operation::FileWriteAll` and
+///      `Operation::FileAppend` allow.
 ///
-///    * Creating new processes.
+///    * Changing the permissions, ownership, or timestamps of any file, except as
+///      `Operation::FileWriteMetadata` allows.
+///
+///    * Creating new processes, or running other programs, except as `Operation::ProcessExec`
+///      allows.
 ///
 ///    * Opening named pipes or System V IPC resources.
 ///
@@ -75,78 +112,1777 @@ use std::path::PathBuf;
 ///
 ///    * Exiting the process.
 ///
-/// Because of platform limitations, patterns within one profile are not permitted to overlap; the
-/// behavior is undefined if they do. For example, you may not allow metadata reads of the subpath
-/// rooted at `/dev` while allowing full reads of `/dev/null`; you must instead allow full reads of
-/// `/dev` or make the profile more restrictive.
-#[derive(Clone, Debug)]
+/// Because of platform limitations, the patterns of different operations within one profile are
+/// not permitted to overlap, and `new()` rejects profiles where they do. For example, you may not
+/// allow metadata reads of the subpath rooted at `/dev` while allowing full reads of `/dev/null`;
+/// you must instead allow full reads of `/dev` or make the profile more restrictive.
+///
+/// The one exception is `FileReadAll` and `FileWriteMetadata`, whose patterns may overlap: a path
+/// matched by both can be read and have its metadata changed, and a path matched by only one
+/// allows only what that one does. For example, allowing full reads of `/srv` and metadata writes
+/// of `/srv/restore` lets the process read all of `/srv` but change metadata only under
+/// `/srv/restore`.
+///
+/// With the `serde` feature, profiles can be serialized, for example to JSON for a configuration
+/// file. Paths are serialized as strings, so a profile with a path that isn't valid UTF-8 fails to
+/// serialize; use `to_bytes()` for those. Deserializing checks the profile as `from_bytes()`
+/// does, and `violation_action`, `resource_limits`, and `fd_only` may be left out.
+/// Platform-specific operations are serialized as opaque strings that only the same platform can
+/// deserialize.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(try_from = "ProfileFields"))]
 pub struct Profile {
     allowed_operations: Vec<Operation>,
+    violation_action: ViolationAction,
+    resource_limits: Option<ResourceLimits>,
+    fd_only: bool,
 }
 
+/// What happens to a sandboxed process that makes a system call its profile prohibits, as set by
+/// `Profile::with_violation_action()`. Only Linux honors this fully. On OpenBSD anything but
+/// `Kill` makes prohibited system calls fail with `ENOSYS`; elsewhere prohibited operations fail
+/// or kill the process as the platform sees fit.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ViolationAction {
+    /// The process is killed with `SIGSYS` before the system call runs.
+    #[default]
+    Kill,
+    /// The system call fails with this error number instead of running, so that the process can
+    /// handle the denial. Only the low 16 bits are used.
+    Errno(i32),
+    /// The system call is allowed, and the kernel logs it to the audit log. This confines
+    /// nothing and is meant for finding out what a program needs while writing its profile.
+    /// Kernels older than Linux 4.14 can't log, so there the system call fails with `EPERM`
+    /// instead.
+    Log,
+    /// The process gets `SIGSYS` before the system call runs, with `SECCOMP_RET_TRAP`, and a
+    /// handler `ChildSandbox` installs reports the system call to standard error, or to the
+    /// function given to `ChildSandbox::with_violation_handler()`. The process then dies of
+    /// `SIGSYS` as under `Kill`. The process can replace the handler, so this is for debugging
+    /// profiles, not for confining code that might do that.
+    Trap,
+}
+
+/// The devices that `Operation::StandardDevices` allows.
+pub const STANDARD_DEVICES: [&str; 3] = ["/dev/null", "/dev/zero", "/dev/urandom"];
+
 /// An operation that this process is allowed to perform.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Operation {
     /// All file-related reading operations may be performed on this file.
     FileReadAll(PathPattern),
     /// Metadata (for example, `stat` or `readlink`) of this file may be read.
     FileReadMetadata(PathPattern),
+    /// All file-related writing operations may be performed on this file: creating, truncating,
+    /// writing, syncing, renaming, and deleting it. On Linux this also allows reading it.
+    FileWriteAll(PathPattern),
+    /// The permission bits, ownership, and timestamps of this file may be changed, but not its
+    /// contents. On Linux this also allows reading it, and ownership can only be changed to the
+    /// process's own user and group.
+    ///
+    /// On Linux the filter can't tell files apart, so in a profile that also has `FileWriteAll`,
+    /// the contents of these files can be written too.
+    FileWriteMetadata(PathPattern),
+    /// This file may be opened for appending, created, and appended to, but not truncated or
+    /// overwritten. On Linux this also allows reading it.
+    ///
+    /// On Linux the filter only allows files to be opened for writing with `O_APPEND`, and makes
+    /// `ftruncate` and `fallocate` fail with `EPERM`. The filter can't tell files apart, so in a
+    /// profile that also has `FileWriteAll`, these files can be truncated and overwritten too.
+    /// `fcntl` stays prohibited, so `O_APPEND` can't be cleared from an open file, but a file
+    /// already open for writing when the sandbox is activated, or one received from another
+    /// process, isn't limited to appending.
+    FileAppend(PathPattern),
+    /// The program at this path may be run, in a new process or in place of this one. It runs
+    /// under this same profile, so the profile must also allow whatever the program does.
+    ///
+    /// On Linux the filter can't check which program is run, so this allows `execve` and
+    /// `execveat` of any path, along with `fork`, `vfork`, and `clone` as they use it, and
+    /// waiting for children. What can actually be run is limited by the contents of the jail,
+    /// which gets these programs, read-only, together with the dynamic loader and shared
+    /// libraries they need. Anything else executable that the profile binds into the jail can be
+    /// run too, and so can a library, if it is executable. On macOS only these programs can be
+    /// run.
+    ///
+    /// Without it, running a program violates the profile, and is handled as its
+    /// `ViolationAction` says: with `ViolationAction::Errno(libc::EPERM)`, `execve` fails with
+    /// `EPERM`.
+    ProcessExec(PathPattern),
     /// Outbound network connections to the given address may be initiated.
     NetworkOutbound(AddressPattern),
+    /// Sockets may be bound to the given local address, and may listen for and accept inbound
+    /// connections on it.
+    NetworkInbound(AddressPattern),
     /// System information may be read (via `sysctl` on Unix).
+    ///
+    /// On Linux this allows `uname`, `sysinfo`, and `getrlimit`, and puts `/proc/cpuinfo` and
+    /// `/proc/meminfo` in the jail, read-only. `sched_getaffinity` is allowed regardless.
     SystemInfoRead,
+    /// The devices in `STANDARD_DEVICES`, `/dev/null`, `/dev/zero`, and `/dev/urandom`, may be
+    /// read and written.
+    ///
+    /// On Linux they are bound into the jail, writable, from the host, which a child can't create
+    /// device nodes for itself. One the host lacks is left out, with a warning. Opening files for
+    /// writing is allowed, and the jail keeps that to these devices and the paths the profile
+    /// allows writing.
+    StandardDevices,
     /// Platform-specific operations.
-    PlatformSpecific(platform::Operation),
+    PlatformSpecific(
+        #[cfg_attr(feature = "serde", serde(with = "platform_operation_serde"))]
+        platform::Operation
+    ),
 }
 
 /// Describes a path or paths on the filesystem.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum PathPattern {
     /// One specific path.
     Literal(PathBuf),
     /// A directory and all of its contents, recursively.
     Subpath(PathBuf),
+    /// Every path matching a shell-style glob, for example `/usr/lib/*.so` or `/tmp/servo-*`.
+    /// In each component, `*` matches any run of characters, `?` matches any one character, and
+    /// `\` makes the character after it match only itself. Neither wildcard matches `/`, and
+    /// each matching path is treated like a `Literal`.
+    ///
+    /// On Linux the glob is expanded once, by `Sandbox::start()`, into the paths that match it
+    /// then; paths created later that match it stay out of reach. A glob that matches nothing
+    /// allows nothing, which `SandboxMethods::preflight()` reports.
+    Glob(String),
 }
 
 /// Describes a network address.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum AddressPattern {
     /// All network addresses.
     All,
     /// TCP connections on the given port.
     Tcp(u16),
+    /// UDP datagrams sent to the given port. On Linux this allows UDP to every port, but not
+    /// TCP.
+    Udp(u16),
     /// A local socket at the given path (for example, a Unix socket).
     LocalSocket(PathBuf),
+    /// TCP connections and UDP datagrams to the network whose first `prefix_len` bits are those
+    /// of `addr`, on `port` or on any port.
+    ///
+    /// On Linux only outbound traffic can be restricted this way. The child gets a network
+    /// namespace whose only link leads to the host, with packet filter rules dropping everything
+    /// else; see `platform::linux::IpFilterBackend`. Setting that up needs `CAP_NET_ADMIN`.
+    IpCidr {
+        addr: IpAddr,
+        prefix_len: u8,
+        port: Option<u16>,
+    },
+}
+
+/// A path on the host that a sandbox needs in order to apply a profile, as returned by
+/// `Profile::required_host_paths()`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RequiredPath {
+    /// The path, as the profile names it.
+    pub path: PathBuf,
+    /// Whether the path is expected to be a file or a directory.
+    pub kind: PathKind,
+    /// The access the sandboxed process is granted to it.
+    pub access: PathAccess,
+}
+
+/// Whether a `RequiredPath` is expected to be a file or a directory.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum PathKind {
+    /// A single path, usually a file. A directory named this way is allowed but not its contents.
+    File,
+    /// A directory and everything under it.
+    Dir,
+}
+
+/// The access a sandboxed process is granted to a `RequiredPath`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum PathAccess {
+    /// The path may be read.
+    Read,
+    /// The path may be read and written, as `Operation::FileWriteAll` allows.
+    ReadWrite,
+    /// The path may be read and its metadata changed, as `Operation::FileWriteMetadata` allows.
+    ReadWriteMetadata,
+    /// The path may be read and appended to, as `Operation::FileAppend` allows.
+    ReadAppend,
+    /// The path may be run, as `Operation::ProcessExec` allows.
+    Execute,
 }
 
 impl Profile {
     /// Creates a new profile with the given set of allowed operations.
     ///
-    /// If the operations cannot be allowed precisely on this platform, this returns an error. You
-    /// can then inspect the operations via `OperationSupport::support()` to see which ones cannot
-    /// be allowed and modify the set of allowed operations as necessary. We are deliberately
-    /// strict here to reduce the probability of applications accidentally allowing operations due
-    /// to platform limitations.
-    pub fn new(allowed_operations: Vec<Operation>) -> Result<Profile,()> {
-        if allowed_operations.iter().all(|operation| {
+    /// If the operations cannot be allowed precisely on this platform, this returns
+    /// `ProfileError::UnsupportedOperation`. You can then inspect the operations via
+    /// `OperationSupport::support()` to see which ones cannot be allowed and modify the set of
+    /// allowed operations as necessary. We are deliberately strict here to reduce the probability
+    /// of applications accidentally allowing operations due to platform limitations.
+    ///
+    /// It also returns the first of the errors `validate()` would: for relative paths, repeated
+    /// operations, and overlapping patterns.
+    ///
+    /// `ProfileBuilder` builds the list of operations one at a time.
+    pub fn new(allowed_operations: Vec<Operation>) -> Result<Profile,ProfileError> {
+        if let Some(operation) = allowed_operations.iter().find(|operation| {
             match operation.support() {
-                OperationSupportLevel::NeverAllowed | OperationSupportLevel::CanBeAllowed => true,
+                OperationSupportLevel::NeverAllowed | OperationSupportLevel::CanBeAllowed => false,
                 OperationSupportLevel::CannotBeAllowedPrecisely |
-                OperationSupportLevel::AlwaysAllowed => false,
+                OperationSupportLevel::AlwaysAllowed => true,
             }
         }) {
-            Ok(Profile {
-                allowed_operations: allowed_operations,
-            })
-        } else {
-            Err(())
+            return Err(ProfileError::UnsupportedOperation(operation.clone()))
+        }
+        if let Some(error) = validation_errors(&allowed_operations).into_iter().next() {
+            return Err(error)
+        }
+        Ok(Profile {
+            allowed_operations,
+            violation_action: ViolationAction::Kill,
+            resource_limits: None,
+            fd_only: false,
+        })
+    }
+
+    /// Creates a profile that grants no view of the filesystem at all, for a process that does
+    /// all of its I/O on descriptors it inherits (see `Command::inherit_fd()`).
+    ///
+    /// It allows no operations. On Linux the jail is an empty `tmpfs` with nothing bound into
+    /// it, the inherited descriptors can be read, written, sought, mapped, `fstat`ed, and
+    /// `fcntl`ed, and every system call that takes a path fails with `ENOENT` rather than being
+    /// handled by the violation action, so that code probing the filesystem fails cleanly.
+    pub fn fd_only() -> Profile {
+        Profile {
+            allowed_operations: Vec::new(),
+            violation_action: ViolationAction::Kill,
+            resource_limits: None,
+            fd_only: true,
         }
     }
 
+    /// Returns true if this profile was created with `fd_only()`.
+    pub fn is_fd_only(&self) -> bool {
+        self.fd_only
+    }
+
     /// Returns the list of allowed operations.
     pub fn allowed_operations(&self) -> &[Operation] {
         self.allowed_operations.as_slice()
     }
+
+    /// Returns this profile with prohibited system calls handled by `violation_action` instead of
+    /// killing the process.
+    pub fn with_violation_action(mut self, violation_action: ViolationAction) -> Profile {
+        self.violation_action = violation_action;
+        self
+    }
+
+    /// Returns what happens to a process that makes a prohibited system call.
+    pub fn violation_action(&self) -> ViolationAction {
+        self.violation_action
+    }
+
+    /// Returns this profile with `limits` on what the process may use, which it sets while
+    /// activating the sandbox, before the seccomp filter. If the sandbox has limits of its own
+    /// (see `SandboxOptions::process_limits()`), the tighter of each applies.
+    pub fn with_resource_limits(mut self, limits: ResourceLimits) -> Profile {
+        self.resource_limits = Some(limits);
+        self
+    }
+
+    /// Returns the limits on what the process may use, if any were set.
+    pub fn resource_limits(&self) -> Option<&ResourceLimits> {
+        self.resource_limits.as_ref()
+    }
+
+    /// Describes how the operations allowed by `other` differ from those allowed by this profile.
+    ///
+    /// Operations that appear in both profiles are omitted. An operation in one profile that is
+    /// contained in, or contains, an operation in the other is reported as a narrowing or
+    /// widening respectively instead of as an addition and a removal.
+    pub fn diff(&self, other: &Profile) -> ProfileDiff {
+        let removed: Vec<&Operation> = self.allowed_operations.iter().filter(|operation| {
+            !other.allowed_operations.contains(operation)
+        }).collect();
+        let added: Vec<&Operation> = other.allowed_operations.iter().filter(|operation| {
+            !self.allowed_operations.contains(operation)
+        }).collect();
+
+        let mut diff = ProfileDiff::default();
+        let mut removed_paired = vec![false; removed.len()];
+        let mut added_paired = vec![false; added.len()];
+        for (removed_index, &before) in removed.iter().enumerate() {
+            for (added_index, &after) in added.iter().enumerate() {
+                if before.contains(after) {
+                    diff.narrowed.push((before.clone(), after.clone()))
+                } else if after.contains(before) {
+                    diff.widened.push((before.clone(), after.clone()))
+                } else {
+                    continue
+                }
+                removed_paired[removed_index] = true;
+                added_paired[added_index] = true;
+            }
+        }
+        for (&operation, &paired) in removed.iter().zip(removed_paired.iter()) {
+            if !paired {
+                diff.only_in_self.push(operation.clone())
+            }
+        }
+        for (&operation, &paired) in added.iter().zip(added_paired.iter()) {
+            if !paired {
+                diff.only_in_other.push(operation.clone())
+            }
+        }
+        diff
+    }
+
+    /// Returns a profile allowing every operation either this profile or `other` allows, each
+    /// once, with this profile's operations first and its violation action and resource limits.
+    /// It is fd-only only if both are.
+    ///
+    /// The union is checked as `new()` checks its operations, so this fails with
+    /// `ProfileError::OverlappingPatterns` if an operation of one profile overlaps one of the
+    /// other.
+    pub fn merge(&self, other: &Profile) -> Result<Profile,ProfileError> {
+        let mut allowed_operations = self.allowed_operations.clone();
+        for operation in &other.allowed_operations {
+            if !allowed_operations.contains(operation) {
+                allowed_operations.push(operation.clone())
+            }
+        }
+        let mut merged = Profile::new(allowed_operations)?;
+        merged.violation_action = self.violation_action;
+        merged.resource_limits = self.resource_limits;
+        merged.fd_only = self.fd_only && other.fd_only;
+        Ok(merged)
+    }
+
+    /// Returns a profile allowing only the operations that both this profile and `other` allow,
+    /// in this profile's order and with its violation action. Operations must be equal to be
+    /// kept: a `Subpath` in one profile and a `Literal` inside it in the other are dropped. It has
+    /// the tighter of the two profiles' resource limits, and is fd-only if either is.
+    pub fn intersection(&self, other: &Profile) -> Profile {
+        let resource_limits = match (self.resource_limits, other.resource_limits) {
+            (Some(this), Some(that)) => Some(this.combined_with(&that)),
+            (this, that) => this.or(that),
+        };
+        Profile {
+            allowed_operations: self.allowed_operations.iter().filter(|operation| {
+                other.allowed_operations.contains(operation)
+            }).cloned().collect(),
+            violation_action: self.violation_action,
+            resource_limits,
+            fd_only: self.fd_only || other.fd_only,
+        }
+    }
+
+    /// Returns true if every operation `other` allows is contained in one this profile allows
+    /// (see `Operation::contains()`).
+    pub fn is_superset_of(&self, other: &Profile) -> bool {
+        other.allowed_operations.iter().all(|that| {
+            self.allowed_operations.iter().any(|this| this.contains(that))
+        })
+    }
+
+    /// Checks this profile's operations, returning an error for every operation with a relative
+    /// path, every operation that appears more than once, and every pair of operations whose
+    /// patterns overlap (see `Operation::overlaps()`). `new()` already rejects profiles that fail
+    /// this check, so this only finds errors in profiles that were made some other way.
+    ///
+    /// Every pair of operations is compared, which is fine for profiles of the usual size.
+    pub fn validate(&self) -> Result<(), Vec<ProfileError>> {
+        let errors = validation_errors(&self.allowed_operations);
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Returns the host paths whose contents this profile makes available, which a sandbox
+    /// applying it needs to exist and be readable by the parent. On Linux these are the paths bound
+    /// into the `chroot` jail.
+    ///
+    /// Metadata-only operations are left out, since the metadata of a missing path can be read
+    /// just as well as that of one that exists, and so are globs, which may match nothing. Each
+    /// path is listed once, in profile order.
+    pub fn required_host_paths(&self) -> Vec<RequiredPath> {
+        let mut paths: Vec<RequiredPath> = Vec::new();
+        for operation in &self.allowed_operations {
+            let (pattern, access) = match *operation {
+                Operation::FileReadAll(ref pattern) => (pattern, PathAccess::Read),
+                Operation::FileWriteAll(ref pattern) => (pattern, PathAccess::ReadWrite),
+                Operation::FileWriteMetadata(ref pattern) => {
+                    (pattern, PathAccess::ReadWriteMetadata)
+                }
+                Operation::FileAppend(ref pattern) => (pattern, PathAccess::ReadAppend),
+                Operation::ProcessExec(ref pattern) => (pattern, PathAccess::Execute),
+                _ => continue,
+            };
+            let (path, kind) = match *pattern {
+                PathPattern::Literal(ref path) => (path, PathKind::File),
+                PathPattern::Subpath(ref path) => (path, PathKind::Dir),
+                PathPattern::Glob(_) => continue,
+            };
+            let required_path = RequiredPath {
+                path: path.clone(),
+                kind,
+                access,
+            };
+            if !paths.contains(&required_path) {
+                paths.push(required_path)
+            }
+        }
+        paths
+    }
+}
+
+impl fmt::Display for Profile {
+    /// Describes each allowed operation in plain language, one per line and in sorted order,
+    /// such that each line completes the sentence "The sandboxed process will be able to…".
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut descriptions: Vec<String> = self.allowed_operations.iter().map(|operation| {
+            operation.describe()
+        }).collect();
+        descriptions.sort();
+        descriptions.dedup();
+        if descriptions.is_empty() {
+            return write!(f, "perform only the operations every profile allows")
+        }
+        write!(f, "{}", descriptions.join("\n"))
+    }
+}
+
+/// Builds a `Profile` one operation at a time, for profiles whose operations depend on runtime
+/// conditions.
+///
+/// Paths are normalized as they are added: trailing slashes and `.` components are dropped, so
+/// `/usr/./lib/` becomes `/usr/lib`. Operations that are already present are skipped.
+/// `build()` checks the result as `Profile::new()` does, so relative paths are rejected there.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ProfileBuilder {
+    allowed_operations: Vec<Operation>,
+    violation_action: ViolationAction,
+    resource_limits: Option<ResourceLimits>,
+    fd_only: bool,
+}
+
+impl ProfileBuilder {
+    /// Constructs a builder that allows no operations.
+    pub fn new() -> ProfileBuilder {
+        ProfileBuilder::default()
+    }
+
+    /// Constructs a builder that starts from the operations, violation action, and resource limits
+    /// of `profile`, for making a changed copy of it. One started from `Profile::fd_only()` builds
+    /// fd-only profiles, and so can't be given any operations.
+    pub fn from_profile(profile: &Profile) -> ProfileBuilder {
+        let mut builder = ProfileBuilder::new();
+        for operation in &profile.allowed_operations {
+            builder.allow(operation.clone());
+        }
+        builder.violation_action(profile.violation_action);
+        builder.resource_limits = profile.resource_limits;
+        builder.fd_only = profile.fd_only;
+        builder
+    }
+
+    /// Allows `operation`.
+    pub fn allow(&mut self, operation: Operation) -> &mut ProfileBuilder {
+        let operation = operation.normalized();
+        if !self.allowed_operations.contains(&operation) {
+            self.allowed_operations.push(operation)
+        }
+        self
+    }
+
+    /// Allows `operation` if `condition` is true.
+    pub fn allow_if(&mut self, condition: bool, operation: Operation) -> &mut ProfileBuilder {
+        if condition {
+            self.allow(operation);
+        }
+        self
+    }
+
+    /// Allows every operation that `other` allows, after the ones already allowed.
+    pub fn extend(&mut self, other: &ProfileBuilder) -> &mut ProfileBuilder {
+        for operation in &other.allowed_operations {
+            self.allow(operation.clone());
+        }
+        self
+    }
+
+    /// Allows reading the file at `path`.
+    pub fn allow_file_read<P>(&mut self, path: P) -> &mut ProfileBuilder where P: AsRef<Path> {
+        self.allow(Operation::FileReadAll(PathPattern::Literal(path.as_ref().to_path_buf())))
+    }
+
+    /// Allows reading the directory `path` and everything under it.
+    pub fn allow_file_read_subpath<P>(&mut self, path: P) -> &mut ProfileBuilder
+                                      where P: AsRef<Path> {
+        self.allow(Operation::FileReadAll(PathPattern::Subpath(path.as_ref().to_path_buf())))
+    }
+
+    /// Allows reading the metadata of the file at `path`.
+    pub fn allow_file_metadata<P>(&mut self, path: P) -> &mut ProfileBuilder
+                                  where P: AsRef<Path> {
+        self.allow(Operation::FileReadMetadata(PathPattern::Literal(path.as_ref().to_path_buf())))
+    }
+
+    /// Allows writing the directory `path` and everything under it.
+    pub fn allow_file_write_subpath<P>(&mut self, path: P) -> &mut ProfileBuilder
+                                       where P: AsRef<Path> {
+        self.allow(Operation::FileWriteAll(PathPattern::Subpath(path.as_ref().to_path_buf())))
+    }
+
+    /// Allows outbound network connections to any address.
+    pub fn allow_network_outbound(&mut self) -> &mut ProfileBuilder {
+        self.allow(Operation::NetworkOutbound(AddressPattern::All))
+    }
+
+    /// Allows outbound TCP connections to `port`.
+    pub fn allow_tcp_outbound(&mut self, port: u16) -> &mut ProfileBuilder {
+        self.allow(Operation::NetworkOutbound(AddressPattern::Tcp(port)))
+    }
+
+    /// Allows sending UDP datagrams to `port`.
+    pub fn allow_udp_outbound(&mut self, port: u16) -> &mut ProfileBuilder {
+        self.allow(Operation::NetworkOutbound(AddressPattern::Udp(port)))
+    }
+
+    /// Allows outbound TCP connections and UDP datagrams to the network `addr`/`prefix_len`, on
+    /// `port` or on any port.
+    pub fn allow_cidr_outbound(&mut self, addr: IpAddr, prefix_len: u8, port: Option<u16>)
+                               -> &mut ProfileBuilder {
+        self.allow(Operation::NetworkOutbound(AddressPattern::IpCidr {
+            addr,
+            prefix_len,
+            port,
+        }))
+    }
+
+    /// Allows reading system information.
+    pub fn allow_system_info_read(&mut self) -> &mut ProfileBuilder {
+        self.allow(Operation::SystemInfoRead)
+    }
+
+    /// Allows reading and writing the standard devices.
+    pub fn allow_standard_devices(&mut self) -> &mut ProfileBuilder {
+        self.allow(Operation::StandardDevices)
+    }
+
+    /// Allows the system call named `name`, with any arguments, as
+    /// `platform::linux::Operation::Syscall` does. Fails if `syscall_nr()` doesn't know the name.
+    #[cfg(any(target_os="android", target_os="linux"))]
+    pub fn allow_raw_syscall(&mut self, name: &str)
+                             -> Result<&mut ProfileBuilder,UnknownSyscall> {
+        match syscall_nr(name) {
+            Some(number) => {
+                Ok(self.allow(Operation::PlatformSpecific(platform::Operation::Syscall(number))))
+            }
+            None => Err(UnknownSyscall(name.to_owned())),
+        }
+    }
+
+    /// Allows reading a `/proc` of the sandbox's own, as `platform::linux::Operation::MountProc`
+    /// does.
+    #[cfg(any(target_os="android", target_os="linux"))]
+    pub fn allow_proc(&mut self) -> &mut ProfileBuilder {
+        self.allow(Operation::PlatformSpecific(platform::Operation::MountProc))
+    }
+
+    /// Sets what happens to the sandboxed process when it violates the profile. See
+    /// `Profile::with_violation_action()`.
+    pub fn violation_action(&mut self, violation_action: ViolationAction)
+                            -> &mut ProfileBuilder {
+        self.violation_action = violation_action;
+        self
+    }
+
+    /// Sets the limits on what the sandboxed process may use. See
+    /// `Profile::with_resource_limits()`.
+    pub fn resource_limits(&mut self, limits: ResourceLimits) -> &mut ProfileBuilder {
+        self.resource_limits = Some(limits);
+        self
+    }
+
+    /// Returns the profile allowing the operations added so far, in the order they were first
+    /// added, or the first error `Profile::new()` finds in them. A builder started from an
+    /// fd-only profile fails with `ProfileError::FdOnlyOperation` if any were added.
+    pub fn build(&self) -> Result<Profile,ProfileError> {
+        let profile = if self.fd_only {
+            if let Some(operation) = self.allowed_operations.first() {
+                return Err(ProfileError::FdOnlyOperation(operation.clone()))
+            }
+            Profile::fd_only()
+        } else {
+            Profile::new(self.allowed_operations.clone())?
+        };
+        let mut profile = profile.with_violation_action(self.violation_action);
+        profile.resource_limits = self.resource_limits;
+        Ok(profile)
+    }
+}
+
+impl Operation {
+    /// Returns true if every action `other` allows is also allowed by `self`.
+    ///
+    /// Reading a file in full includes reading its metadata, so `FileReadAll` contains
+    /// `FileReadMetadata` of any path its pattern contains. Likewise, writing a file includes
+    /// appending to it.
+    pub fn contains(&self, other: &Operation) -> bool {
+        match (self, other) {
+            (Operation::FileReadAll(this), Operation::FileReadAll(that)) |
+            (Operation::FileReadAll(this), Operation::FileReadMetadata(that)) |
+            (Operation::FileReadMetadata(this), Operation::FileReadMetadata(that)) |
+            (Operation::FileWriteAll(this), Operation::FileWriteAll(that)) |
+            (Operation::FileWriteAll(this), Operation::FileAppend(that)) |
+            (Operation::FileWriteMetadata(this), Operation::FileWriteMetadata(that)) |
+            (Operation::FileAppend(this), Operation::FileAppend(that)) |
+            (Operation::ProcessExec(this), Operation::ProcessExec(that)) => {
+                this.contains(that)
+            }
+            (Operation::NetworkOutbound(this), Operation::NetworkOutbound(that)) |
+            (Operation::NetworkInbound(this), Operation::NetworkInbound(that)) => {
+                this.contains(that)
+            }
+            (Operation::SystemInfoRead, Operation::SystemInfoRead) |
+            (Operation::StandardDevices, Operation::StandardDevices) => true,
+            (Operation::PlatformSpecific(this), Operation::PlatformSpecific(that)) => {
+                this == that
+            }
+            _ => false,
+        }
+    }
+
+    /// Returns true if `self` and `other` are different kinds of file operation whose patterns
+    /// overlap, which `Profile::new()` rejects. Patterns of the same kind of operation may be
+    /// nested, and `FileReadAll` and `FileWriteMetadata` may overlap.
+    pub fn overlaps(&self, other: &Operation) -> bool {
+        if mem::discriminant(self) == mem::discriminant(other) {
+            return false
+        }
+        match (self, other) {
+            (Operation::FileReadAll(_), Operation::FileWriteMetadata(_)) |
+            (Operation::FileWriteMetadata(_), Operation::FileReadAll(_)) => false,
+            _ => match (self.path_pattern(), other.path_pattern()) {
+                (Some(this), Some(that)) => this.overlaps(that),
+                _ => false,
+            },
+        }
+    }
+
+    /// Returns this operation with its path, if any, normalized as `ProfileBuilder` does.
+    fn normalized(self) -> Operation {
+        match self {
+            Operation::FileReadAll(pattern) => Operation::FileReadAll(pattern.normalized()),
+            Operation::FileReadMetadata(pattern) => {
+                Operation::FileReadMetadata(pattern.normalized())
+            }
+            Operation::FileWriteAll(pattern) => Operation::FileWriteAll(pattern.normalized()),
+            Operation::FileWriteMetadata(pattern) => {
+                Operation::FileWriteMetadata(pattern.normalized())
+            }
+            Operation::FileAppend(pattern) => Operation::FileAppend(pattern.normalized()),
+            Operation::ProcessExec(pattern) => Operation::ProcessExec(pattern.normalized()),
+            Operation::NetworkOutbound(AddressPattern::LocalSocket(path)) => {
+                Operation::NetworkOutbound(AddressPattern::LocalSocket(normalize_path(&path)))
+            }
+            Operation::NetworkInbound(AddressPattern::LocalSocket(path)) => {
+                Operation::NetworkInbound(AddressPattern::LocalSocket(normalize_path(&path)))
+            }
+            operation => operation,
+        }
+    }
+
+    /// Returns the pattern of this operation if it is a file operation.
+    fn path_pattern(&self) -> Option<&PathPattern> {
+        match *self {
+            Operation::FileReadAll(ref pattern) |
+            Operation::FileReadMetadata(ref pattern) |
+            Operation::FileWriteAll(ref pattern) |
+            Operation::FileWriteMetadata(ref pattern) |
+            Operation::FileAppend(ref pattern) => Some(pattern),
+            _ => None,
+        }
+    }
+
+    /// Returns true if the path or glob in this operation is relative.
+    fn has_relative_path(&self) -> bool {
+        match *self {
+            Operation::FileReadAll(ref pattern) |
+            Operation::FileReadMetadata(ref pattern) |
+            Operation::FileWriteAll(ref pattern) |
+            Operation::FileWriteMetadata(ref pattern) |
+            Operation::FileAppend(ref pattern) |
+            Operation::ProcessExec(ref pattern) => {
+                match *pattern {
+                    PathPattern::Literal(ref path) | PathPattern::Subpath(ref path) => {
+                        path.is_relative()
+                    }
+                    PathPattern::Glob(ref glob) => !glob.starts_with('/'),
+                }
+            }
+            Operation::NetworkOutbound(AddressPattern::LocalSocket(ref path)) |
+            Operation::NetworkInbound(AddressPattern::LocalSocket(ref path)) => path.is_relative(),
+            _ => false,
+        }
+    }
+}
+
+impl PathPattern {
+    /// Returns true if every path matched by `other` is also matched by `self`.
+    ///
+    /// Paths are compared component-wise and without touching the filesystem, so symlinks are not
+    /// resolved.
+    ///
+    /// A glob is only known to contain the paths it matches and itself, and is contained in a
+    /// subpath that holds the directory its first wildcard is in.
+    pub fn contains(&self, other: &PathPattern) -> bool {
+        match (self, other) {
+            (PathPattern::Literal(this), PathPattern::Literal(that)) => this == that,
+            (PathPattern::Literal(_), PathPattern::Subpath(_)) |
+            (PathPattern::Literal(_), PathPattern::Glob(_)) |
+            (PathPattern::Glob(_), PathPattern::Subpath(_)) => false,
+            (PathPattern::Subpath(this), PathPattern::Literal(that)) |
+            (PathPattern::Subpath(this), PathPattern::Subpath(that)) => {
+                that.starts_with(this)
+            }
+            (PathPattern::Subpath(this), PathPattern::Glob(that)) => {
+                glob_directory(that).starts_with(this)
+            }
+            (PathPattern::Glob(this), PathPattern::Literal(that)) => glob_matches(this, that),
+            (PathPattern::Glob(this), PathPattern::Glob(that)) => this == that,
+        }
+    }
+
+    /// Returns true if some path could be matched by both `self` and `other`.
+    ///
+    /// Like `contains()`, this doesn't touch the filesystem. Two globs are taken to overlap unless
+    /// some pair of their components can't match the same name, so this may report overlaps
+    /// between globs that no path matches both of.
+    pub fn overlaps(&self, other: &PathPattern) -> bool {
+        match (self, other) {
+            (PathPattern::Literal(this), PathPattern::Literal(that)) => this == that,
+            (PathPattern::Literal(literal), PathPattern::Subpath(subpath)) |
+            (PathPattern::Subpath(subpath), PathPattern::Literal(literal)) => {
+                literal.starts_with(subpath)
+            }
+            (PathPattern::Subpath(this), PathPattern::Subpath(that)) => {
+                this.starts_with(that) || that.starts_with(this)
+            }
+            (PathPattern::Glob(glob), PathPattern::Literal(literal)) |
+            (PathPattern::Literal(literal), PathPattern::Glob(glob)) => glob_matches(glob, literal),
+            (PathPattern::Glob(glob), PathPattern::Subpath(subpath)) |
+            (PathPattern::Subpath(subpath), PathPattern::Glob(glob)) => {
+                glob_prefix_matches(glob, subpath)
+            }
+            (PathPattern::Glob(this), PathPattern::Glob(that)) => globs_overlap(this, that),
+        }
+    }
+
+    /// Returns this pattern without trailing slashes or `.` components. Globs only lose their
+    /// trailing slashes, since their components may be escaped.
+    fn normalized(self) -> PathPattern {
+        match self {
+            PathPattern::Literal(path) => PathPattern::Literal(normalize_path(&path)),
+            PathPattern::Subpath(path) => PathPattern::Subpath(normalize_path(&path)),
+            PathPattern::Glob(glob) => {
+                let trimmed = glob.trim_end_matches('/');
+                if trimmed.is_empty() || trimmed.len() == glob.len() {
+                    PathPattern::Glob(glob)
+                } else {
+                    PathPattern::Glob(trimmed.to_owned())
+                }
+            }
+        }
+    }
+
+    /// Returns true if this pattern matches `path`, without touching the filesystem.
+    pub fn matches(&self, path: &Path) -> bool {
+        match *self {
+            PathPattern::Literal(ref this) => this == path,
+            PathPattern::Subpath(ref this) => path.starts_with(this),
+            PathPattern::Glob(ref glob) => glob_matches(glob, path),
+        }
+    }
+
+    /// Returns the paths that currently exist and match this glob, in sorted order. Literal and
+    /// subpath patterns are returned as they are, whether or not they exist.
+    pub fn expand(&self) -> Vec<PathBuf> {
+        let glob = match *self {
+            PathPattern::Literal(ref path) | PathPattern::Subpath(ref path) => {
+                return vec![path.clone()]
+            }
+            PathPattern::Glob(ref glob) => glob,
+        };
+        let root = if glob.starts_with('/') { "/" } else { "" };
+        let mut paths = vec![PathBuf::from(root)];
+        for component in glob_components(glob) {
+            if !has_wildcard(component) {
+                let component = OsStr::from_bytes(&unescape(component)).to_os_string();
+                for path in paths.iter_mut() {
+                    path.push(&component)
+                }
+                continue
+            }
+            paths = paths.iter().flat_map(|directory| {
+                let entries = fs::read_dir(if directory.as_os_str().is_empty() {
+                    Path::new(".")
+                } else {
+                    directory
+                });
+                entries.into_iter().flatten().flatten().filter_map(move |entry| {
+                    let name = entry.file_name();
+                    if component_matches(component, name.as_bytes()) {
+                        Some(directory.join(name))
+                    } else {
+                        None
+                    }
+                })
+            }).collect();
+        }
+        paths.retain(|path| path.exists());
+        paths.sort();
+        paths.dedup();
+        paths
+    }
+}
+
+/// Returns `path` without trailing slashes or `.` components.
+fn normalize_path(path: &Path) -> PathBuf {
+    path.components().collect()
+}
+
+/// Returns the components of `glob`, without the empty ones that leading, trailing, and doubled
+/// slashes make.
+fn glob_components(glob: &str) -> impl Iterator<Item=&[u8]> {
+    glob.as_bytes().split(|&byte| byte == b'/').filter(|component| !component.is_empty())
+}
+
+/// Returns true if `glob` matches `path`, component by component.
+fn glob_matches(glob: &str, path: &Path) -> bool {
+    let path = path.as_os_str().as_bytes();
+    if glob.starts_with('/') != path.starts_with(b"/") {
+        return false
+    }
+    let mut path_components = path.split(|&byte| byte == b'/').filter(|name| !name.is_empty());
+    let mut glob_components = glob_components(glob);
+    loop {
+        match (glob_components.next(), path_components.next()) {
+            (Some(pattern), Some(name)) if component_matches(pattern, name) => {}
+            (None, None) => return true,
+            _ => return false,
+        }
+    }
+}
+
+/// Returns true if the components of `path` match the first components of `glob`, so that `glob`
+/// matches `path` or paths inside it.
+fn glob_prefix_matches(glob: &str, path: &Path) -> bool {
+    let path = path.as_os_str().as_bytes();
+    if glob.starts_with('/') != path.starts_with(b"/") {
+        return false
+    }
+    let mut glob_components = glob_components(glob);
+    path.split(|&byte| byte == b'/').filter(|name| !name.is_empty()).all(|name| {
+        glob_components.next().is_some_and(|pattern| component_matches(pattern, name))
+    })
+}
+
+/// Returns true if some path might be matched by both `this` and `that`: they have as many
+/// components, and each pair of components either has wildcards on both sides or can match the
+/// same name.
+fn globs_overlap(this: &str, that: &str) -> bool {
+    if this.starts_with('/') != that.starts_with('/') {
+        return false
+    }
+    let these: Vec<&[u8]> = glob_components(this).collect();
+    let those: Vec<&[u8]> = glob_components(that).collect();
+    these.len() == those.len() && these.iter().zip(those.iter()).all(|(&this, &that)| {
+        if !has_wildcard(this) {
+            component_matches(that, &unescape(this))
+        } else if !has_wildcard(that) {
+            component_matches(this, &unescape(that))
+        } else {
+            true
+        }
+    })
+}
+
+/// Returns true if `name`, one path component, matches `pattern`, one glob component.
+fn component_matches(pattern: &[u8], name: &[u8]) -> bool {
+    match pattern.split_first() {
+        None => name.is_empty(),
+        Some((&b'*', rest)) => (0..=name.len()).any(|skip| component_matches(rest, &name[skip..])),
+        Some((&b'?', rest)) => !name.is_empty() && component_matches(rest, &name[1..]),
+        Some((&b'\\', rest)) if !rest.is_empty() => {
+            name.first() == Some(&rest[0]) && component_matches(&rest[1..], &name[1..])
+        }
+        Some((&byte, rest)) => name.first() == Some(&byte) && component_matches(rest, &name[1..]),
+    }
+}
+
+/// Returns true if the glob component `pattern` has an unescaped wildcard.
+fn has_wildcard(pattern: &[u8]) -> bool {
+    let mut escaped = false;
+    for &byte in pattern {
+        match byte {
+            _ if escaped => escaped = false,
+            b'\\' => escaped = true,
+            b'*' | b'?' => return true,
+            _ => {}
+        }
+    }
+    false
+}
+
+/// Returns the glob component `pattern`, which has no wildcards, with its escapes removed.
+fn unescape(pattern: &[u8]) -> Vec<u8> {
+    let mut unescaped = Vec::new();
+    let mut escaped = false;
+    for &byte in pattern {
+        if byte == b'\\' && !escaped {
+            escaped = true
+        } else {
+            unescaped.push(byte);
+            escaped = false
+        }
+    }
+    unescaped
+}
+
+/// Returns the directory that every path `glob` matches is in: its components up to the first
+/// one with a wildcard.
+fn glob_directory(glob: &str) -> PathBuf {
+    let mut directory = PathBuf::from(if glob.starts_with('/') { "/" } else { "" });
+    for component in glob_components(glob) {
+        if has_wildcard(component) {
+            break
+        }
+        directory.push(OsStr::from_bytes(&unescape(component)))
+    }
+    directory
+}
+
+impl AddressPattern {
+    /// Returns true if every address matched by `other` is also matched by `self`.
+    pub fn contains(&self, other: &AddressPattern) -> bool {
+        match (self, other) {
+            (AddressPattern::All, _) => true,
+            (AddressPattern::Tcp(this), AddressPattern::Tcp(that)) => this == that,
+            (AddressPattern::Udp(this), AddressPattern::Udp(that)) => this == that,
+            (AddressPattern::LocalSocket(this), AddressPattern::LocalSocket(that)) => {
+                this == that
+            }
+            (AddressPattern::IpCidr { addr: this, prefix_len: this_len, port: this_port },
+             AddressPattern::IpCidr { addr: that, prefix_len: that_len, port: that_port }) => {
+                this_len <= that_len &&
+                    network_bits(*this, *this_len) == network_bits(*that, *this_len) &&
+                    (this_port.is_none() || this_port == that_port)
+            }
+            _ => false,
+        }
+    }
+
+    /// Returns true if this is an `IpCidr` whose prefix is longer than its address.
+    fn has_invalid_prefix(&self) -> bool {
+        match *self {
+            AddressPattern::IpCidr { addr: IpAddr::V4(_), prefix_len, .. } => prefix_len > 32,
+            AddressPattern::IpCidr { addr: IpAddr::V6(_), prefix_len, .. } => prefix_len > 128,
+            _ => false,
+        }
+    }
+}
+
+/// Returns the family of `addr` and its first `prefix_len` bits, the rest cleared.
+fn network_bits(addr: IpAddr, prefix_len: u8) -> (bool, u128) {
+    let (is_v4, bits, width) = match addr {
+        IpAddr::V4(addr) => (true, u32::from(addr) as u128, 32),
+        IpAddr::V6(addr) => (false, u128::from(addr), 128),
+    };
+    let host_bits = width - (prefix_len as u32).min(width);
+    let mask = if host_bits >= 128 { 0 } else { !0u128 << host_bits };
+    (is_v4, bits & mask)
+}
+
+/// The bytes every encoded profile begins with.
+const ENCODING_MAGIC: &[u8] = b"GAOL";
+
+/// The version of the encoding that `Profile::to_bytes()` produces.
+const ENCODING_VERSION: u8 = 3;
+
+/// The length of the checksum that ends every encoded profile.
+const CHECKSUM_LENGTH: usize = 8;
+
+/// Why `Profile::from_bytes()` rejected an encoded profile.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DecodeError {
+    /// The bytes don't begin like an encoded profile.
+    BadMagic,
+    /// The profile was encoded by an incompatible version of `gaol`.
+    UnsupportedVersion(u8),
+    /// The checksum doesn't match, so the bytes were corrupted or modified in transit.
+    ChecksumMismatch,
+    /// The bytes end partway through the profile.
+    Truncated,
+    /// The bytes pass the checksum but don't describe a valid profile.
+    Malformed,
+    /// The profile contains an operation specific to another platform.
+    ForeignPlatform,
+    /// The profile decoded, but `Profile::new()` rejects one of its operations on this platform.
+    Unsupported,
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            DecodeError::BadMagic => write!(f, "not an encoded profile"),
+            DecodeError::UnsupportedVersion(version) => {
+                write!(f, "unsupported profile encoding version {}", version)
+            }
+            DecodeError::ChecksumMismatch => write!(f, "profile checksum mismatch"),
+            DecodeError::Truncated => write!(f, "encoded profile is truncated"),
+            DecodeError::Malformed => write!(f, "encoded profile is malformed"),
+            DecodeError::ForeignPlatform => {
+                write!(f, "profile contains an operation specific to another platform")
+            }
+            DecodeError::Unsupported => write!(f, "profile is not supported on this platform"),
+        }
+    }
+}
+
+impl Error for DecodeError {}
+
+/// Returns the errors `Profile::validate()` reports for `operations`, in order.
+fn validation_errors(operations: &[Operation]) -> Vec<ProfileError> {
+    let mut errors: Vec<ProfileError> = operations.iter().filter(|operation| {
+        operation.has_relative_path()
+    }).map(|operation| ProfileError::RelativePath(operation.clone())).collect();
+    errors.extend(operations.iter().filter(|operation| {
+        match **operation {
+            Operation::NetworkOutbound(ref pattern) | Operation::NetworkInbound(ref pattern) => {
+                pattern.has_invalid_prefix()
+            }
+            _ => false,
+        }
+    }).map(|operation| ProfileError::InvalidPrefixLength(operation.clone())));
+    for (index, first) in operations.iter().enumerate() {
+        for second in &operations[index + 1..] {
+            if first == second {
+                errors.push(ProfileError::DuplicateOperation(first.clone()))
+            } else if first.overlaps(second) {
+                errors.push(ProfileError::OverlappingPatterns(first.clone(), second.clone()))
+            }
+        }
+    }
+    errors
+}
+
+/// Why `Profile::new()` or `Profile::validate()` rejected a profile.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ProfileError {
+    /// This operation can't be allowed precisely on this platform.
+    UnsupportedOperation(Operation),
+    /// The path or glob in this operation is relative.
+    RelativePath(Operation),
+    /// This operation appears more than once.
+    DuplicateOperation(Operation),
+    /// The patterns of these two operations overlap.
+    OverlappingPatterns(Operation, Operation),
+    /// The network prefix in this operation is longer than its address.
+    InvalidPrefixLength(Operation),
+    /// This operation was added to a `ProfileBuilder` started from an fd-only profile, which
+    /// allows none.
+    FdOnlyOperation(Operation),
+}
+
+impl fmt::Display for ProfileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ProfileError::UnsupportedOperation(ref operation) => {
+                write!(f, "the operation to {} can't be allowed precisely on this platform",
+                       operation.describe())
+            }
+            ProfileError::RelativePath(ref operation) => {
+                write!(f, "the operation to {} has a relative path", operation.describe())
+            }
+            ProfileError::DuplicateOperation(ref operation) => {
+                write!(f, "the operation to {} appears more than once", operation.describe())
+            }
+            ProfileError::OverlappingPatterns(ref first, ref second) => {
+                write!(f, "the operations to {} and to {} overlap", first.describe(),
+                       second.describe())
+            }
+            ProfileError::InvalidPrefixLength(ref operation) => {
+                write!(f, "the operation to {} has a prefix longer than its address",
+                       operation.describe())
+            }
+            ProfileError::FdOnlyOperation(ref operation) => {
+                write!(f, "the operation to {} can't be allowed in an fd-only profile",
+                       operation.describe())
+            }
+        }
+    }
+}
+
+impl Error for ProfileError {}
+
+impl Profile {
+    /// Encodes this profile in a compact binary form that `from_bytes()` can decode, for example
+    /// in another process.
+    ///
+    /// The encoding begins with a magic number and version and ends with a checksum. The checksum
+    /// detects corruption and accidental modification; it is not a signature, so anyone who can
+    /// write the bytes can forge a valid profile.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = ENCODING_MAGIC.to_vec();
+        bytes.push(ENCODING_VERSION);
+        bytes.extend_from_slice(&(self.allowed_operations.len() as u32).to_le_bytes());
+        for operation in &self.allowed_operations {
+            operation.encode(&mut bytes)
+        }
+        self.violation_action.encode(&mut bytes);
+        encode_resource_limits(self.resource_limits.as_ref(), &mut bytes);
+        bytes.push(self.fd_only as u8);
+        let checksum = checksum(&bytes);
+        bytes.extend_from_slice(&checksum.to_le_bytes());
+        bytes
+    }
+
+    /// Decodes a profile encoded by `to_bytes()`, checking it as `new()` does.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Profile,DecodeError> {
+        if bytes.len() < ENCODING_MAGIC.len() || &bytes[..ENCODING_MAGIC.len()] != ENCODING_MAGIC {
+            return Err(DecodeError::BadMagic)
+        }
+        match bytes.get(ENCODING_MAGIC.len()) {
+            Some(&ENCODING_VERSION) => {}
+            Some(&version) => return Err(DecodeError::UnsupportedVersion(version)),
+            None => return Err(DecodeError::Truncated),
+        }
+        if bytes.len() < ENCODING_MAGIC.len() + 1 + CHECKSUM_LENGTH {
+            return Err(DecodeError::Truncated)
+        }
+        let (body, expected) = bytes.split_at(bytes.len() - CHECKSUM_LENGTH);
+        let mut expected_bytes = [0; CHECKSUM_LENGTH];
+        expected_bytes.copy_from_slice(expected);
+        if checksum(body) != u64::from_le_bytes(expected_bytes) {
+            return Err(DecodeError::ChecksumMismatch)
+        }
+
+        let mut decoder = Decoder::new(&body[ENCODING_MAGIC.len() + 1..]);
+        let count = decoder.u32()?;
+        let mut allowed_operations = Vec::new();
+        for _ in 0..count {
+            allowed_operations.push(Operation::decode(&mut decoder)?)
+        }
+        let violation_action = ViolationAction::decode(&mut decoder)?;
+        let resource_limits = decode_resource_limits(&mut decoder)?;
+        let fd_only = match decoder.u8()? {
+            0 => false,
+            1 if allowed_operations.is_empty() => true,
+            _ => return Err(DecodeError::Malformed),
+        };
+        if !decoder.is_empty() {
+            return Err(DecodeError::Malformed)
+        }
+        Profile::from_fields(ProfileFields {
+            allowed_operations,
+            violation_action,
+            resource_limits,
+            fd_only,
+        })
+    }
+
+    /// Checks decoded fields as `new()` does and makes a profile of them.
+    fn from_fields(fields: ProfileFields) -> Result<Profile,DecodeError> {
+        let profile = if fields.fd_only {
+            if !fields.allowed_operations.is_empty() {
+                return Err(DecodeError::Malformed)
+            }
+            Profile::fd_only()
+        } else {
+            Profile::new(fields.allowed_operations).map_err(|error| {
+                match error {
+                    ProfileError::UnsupportedOperation(_) => DecodeError::Unsupported,
+                    _ => DecodeError::Malformed,
+                }
+            })?
+        };
+        let mut profile = profile.with_violation_action(fields.violation_action);
+        profile.resource_limits = fields.resource_limits;
+        Ok(profile)
+    }
+}
+
+/// The fields of a profile, as decoded before they are checked.
+#[cfg_attr(feature = "serde", derive(serde::Deserialize))]
+struct ProfileFields {
+    allowed_operations: Vec<Operation>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    violation_action: ViolationAction,
+    #[cfg_attr(feature = "serde", serde(default))]
+    resource_limits: Option<ResourceLimits>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    fd_only: bool,
+}
+
+impl TryFrom<ProfileFields> for Profile {
+    type Error = DecodeError;
+
+    fn try_from(fields: ProfileFields) -> Result<Profile,DecodeError> {
+        Profile::from_fields(fields)
+    }
+}
+
+/// Serializes platform-specific operations as their encoding in hexadecimal, preceded by the
+/// platform's `OPERATION_ENCODING_ID`, so that other platforms can tell them apart.
+#[cfg(feature = "serde")]
+mod platform_operation_serde {
+    use super::{DecodeError, Decoder};
+    use crate::platform;
+
+    use serde::de::{Deserialize, Deserializer, Error};
+    use serde::ser::Serializer;
+    use std::fmt::Write;
+
+    pub fn serialize<S>(operation: &platform::Operation, serializer: S)
+                        -> Result<S::Ok,S::Error> where S: Serializer {
+        let mut bytes = vec![platform::OPERATION_ENCODING_ID];
+        operation.encode(&mut bytes);
+        let mut string = String::new();
+        for byte in bytes {
+            write!(&mut string, "{:02x}", byte).unwrap();
+        }
+        serializer.serialize_str(&string)
+    }
+
+    pub fn deserialize<'de,D>(deserializer: D) -> Result<platform::Operation,D::Error>
+                              where D: Deserializer<'de> {
+        let string = String::deserialize(deserializer)?;
+        let bytes: Option<Vec<u8>> = (0..string.len()).step_by(2).map(|index| {
+            string.get(index..index + 2).and_then(|pair| u8::from_str_radix(pair, 16).ok())
+        }).collect();
+        let bytes = bytes.ok_or_else(|| D::Error::custom(DecodeError::Malformed))?;
+        match bytes.split_first() {
+            Some((&platform::OPERATION_ENCODING_ID, encoding)) => {
+                let mut decoder = Decoder::new(encoding);
+                let operation = platform::Operation::decode(&mut decoder)
+                    .map_err(D::Error::custom)?;
+                if !decoder.is_empty() {
+                    return Err(D::Error::custom(DecodeError::Malformed))
+                }
+                Ok(operation)
+            }
+            Some(_) => Err(D::Error::custom(DecodeError::ForeignPlatform)),
+            None => Err(D::Error::custom(DecodeError::Malformed)),
+        }
+    }
+}
+
+impl Operation {
+    fn encode(&self, bytes: &mut Vec<u8>) {
+        match *self {
+            Operation::FileReadAll(ref pattern) => {
+                bytes.push(1);
+                pattern.encode(bytes)
+            }
+            Operation::FileReadMetadata(ref pattern) => {
+                bytes.push(2);
+                pattern.encode(bytes)
+            }
+            Operation::NetworkOutbound(ref pattern) => {
+                bytes.push(3);
+                pattern.encode(bytes)
+            }
+            Operation::SystemInfoRead => bytes.push(4),
+            Operation::PlatformSpecific(ref operation) => {
+                bytes.push(5);
+                bytes.push(platform::OPERATION_ENCODING_ID);
+                operation.encode(bytes)
+            }
+            Operation::FileWriteAll(ref pattern) => {
+                bytes.push(6);
+                pattern.encode(bytes)
+            }
+            Operation::FileWriteMetadata(ref pattern) => {
+                bytes.push(7);
+                pattern.encode(bytes)
+            }
+            Operation::NetworkInbound(ref pattern) => {
+                bytes.push(8);
+                pattern.encode(bytes)
+            }
+            Operation::FileAppend(ref pattern) => {
+                bytes.push(9);
+                pattern.encode(bytes)
+            }
+            Operation::ProcessExec(ref pattern) => {
+                bytes.push(10);
+                pattern.encode(bytes)
+            }
+            Operation::StandardDevices => bytes.push(11),
+        }
+    }
+
+    fn decode(decoder: &mut Decoder) -> Result<Operation,DecodeError> {
+        match decoder.u8()? {
+            1 => Ok(Operation::FileReadAll(PathPattern::decode(decoder)?)),
+            2 => Ok(Operation::FileReadMetadata(PathPattern::decode(decoder)?)),
+            3 => Ok(Operation::NetworkOutbound(AddressPattern::decode(decoder)?)),
+            4 => Ok(Operation::SystemInfoRead),
+            5 => {
+                if decoder.u8()? != platform::OPERATION_ENCODING_ID {
+                    return Err(DecodeError::ForeignPlatform)
+                }
+                Ok(Operation::PlatformSpecific(platform::Operation::decode(decoder)?))
+            }
+            6 => Ok(Operation::FileWriteAll(PathPattern::decode(decoder)?)),
+            7 => Ok(Operation::FileWriteMetadata(PathPattern::decode(decoder)?)),
+            8 => Ok(Operation::NetworkInbound(AddressPattern::decode(decoder)?)),
+            9 => Ok(Operation::FileAppend(PathPattern::decode(decoder)?)),
+            10 => Ok(Operation::ProcessExec(PathPattern::decode(decoder)?)),
+            11 => Ok(Operation::StandardDevices),
+            _ => Err(DecodeError::Malformed),
+        }
+    }
+}
+
+impl PathPattern {
+    fn encode(&self, bytes: &mut Vec<u8>) {
+        let (tag, path) = match *self {
+            PathPattern::Literal(ref path) => (1, path),
+            PathPattern::Subpath(ref path) => (2, path),
+            PathPattern::Glob(ref glob) => {
+                bytes.push(3);
+                return encode_bytes(bytes, glob.as_bytes())
+            }
+        };
+        bytes.push(tag);
+        encode_bytes(bytes, path.as_os_str().as_bytes())
+    }
+
+    fn decode(decoder: &mut Decoder) -> Result<PathPattern,DecodeError> {
+        let tag = decoder.u8()?;
+        let bytes = decoder.bytes()?;
+        let path = PathBuf::from(OsStr::from_bytes(bytes));
+        match tag {
+            1 => Ok(PathPattern::Literal(path)),
+            2 => Ok(PathPattern::Subpath(path)),
+            3 => {
+                let glob = String::from_utf8(bytes.to_vec()).map_err(|_| DecodeError::Malformed)?;
+                Ok(PathPattern::Glob(glob))
+            }
+            _ => Err(DecodeError::Malformed),
+        }
+    }
+}
+
+impl AddressPattern {
+    fn encode(&self, bytes: &mut Vec<u8>) {
+        match *self {
+            AddressPattern::All => bytes.push(1),
+            AddressPattern::Tcp(port) => {
+                bytes.push(2);
+                bytes.extend_from_slice(&port.to_le_bytes())
+            }
+            AddressPattern::LocalSocket(ref path) => {
+                bytes.push(3);
+                encode_bytes(bytes, path.as_os_str().as_bytes())
+            }
+            AddressPattern::Udp(port) => {
+                bytes.push(4);
+                bytes.extend_from_slice(&port.to_le_bytes())
+            }
+            AddressPattern::IpCidr { addr, prefix_len, port } => {
+                bytes.push(5);
+                match addr {
+                    IpAddr::V4(addr) => {
+                        bytes.push(4);
+                        bytes.extend_from_slice(&addr.octets())
+                    }
+                    IpAddr::V6(addr) => {
+                        bytes.push(6);
+                        bytes.extend_from_slice(&addr.octets())
+                    }
+                }
+                bytes.push(prefix_len);
+                match port {
+                    None => bytes.push(0),
+                    Some(port) => {
+                        bytes.push(1);
+                        bytes.extend_from_slice(&port.to_le_bytes())
+                    }
+                }
+            }
+        }
+    }
+
+    fn decode(decoder: &mut Decoder) -> Result<AddressPattern,DecodeError> {
+        match decoder.u8()? {
+            1 => Ok(AddressPattern::All),
+            2 => Ok(AddressPattern::Tcp(decoder.u16()?)),
+            3 => {
+                let path = PathBuf::from(OsStr::from_bytes(decoder.bytes()?));
+                Ok(AddressPattern::LocalSocket(path))
+            }
+            4 => Ok(AddressPattern::Udp(decoder.u16()?)),
+            5 => {
+                let addr = match decoder.u8()? {
+                    4 => {
+                        let octets = <[u8; 4]>::try_from(decoder.take(4)?).unwrap();
+                        IpAddr::from(octets)
+                    }
+                    6 => {
+                        let octets = <[u8; 16]>::try_from(decoder.take(16)?).unwrap();
+                        IpAddr::from(octets)
+                    }
+                    _ => return Err(DecodeError::Malformed),
+                };
+                let prefix_len = decoder.u8()?;
+                let port = match decoder.u8()? {
+                    0 => None,
+                    1 => Some(decoder.u16()?),
+                    _ => return Err(DecodeError::Malformed),
+                };
+                Ok(AddressPattern::IpCidr {
+                    addr,
+                    prefix_len,
+                    port,
+                })
+            }
+            _ => Err(DecodeError::Malformed),
+        }
+    }
+}
+
+impl ViolationAction {
+    fn encode(&self, bytes: &mut Vec<u8>) {
+        match *self {
+            ViolationAction::Kill => bytes.push(1),
+            ViolationAction::Errno(errno) => {
+                bytes.push(2);
+                bytes.extend_from_slice(&errno.to_le_bytes())
+            }
+            ViolationAction::Log => bytes.push(3),
+            ViolationAction::Trap => bytes.push(4),
+        }
+    }
+
+    fn decode(decoder: &mut Decoder) -> Result<ViolationAction,DecodeError> {
+        match decoder.u8()? {
+            1 => Ok(ViolationAction::Kill),
+            2 => Ok(ViolationAction::Errno(decoder.u32()? as i32)),
+            3 => Ok(ViolationAction::Log),
+            4 => Ok(ViolationAction::Trap),
+            _ => Err(DecodeError::Malformed),
+        }
+    }
+}
+
+/// Appends `limits` to an encoded profile: a byte saying whether there are any, then each limit as
+/// a byte saying whether it is set followed by its value.
+fn encode_resource_limits(limits: Option<&ResourceLimits>, bytes: &mut Vec<u8>) {
+    let limits = match limits {
+        Some(limits) => limits,
+        None => return bytes.push(0),
+    };
+    bytes.push(1);
+    for limit in resource_limit_fields(limits).iter() {
+        match *limit {
+            Some(limit) => {
+                bytes.push(1);
+                bytes.extend_from_slice(&limit.to_le_bytes())
+            }
+            None => bytes.push(0),
+        }
+    }
+}
+
+/// Reads limits written by `encode_resource_limits()`.
+fn decode_resource_limits(decoder: &mut Decoder) -> Result<Option<ResourceLimits>,DecodeError> {
+    if decoder.u8()? == 0 {
+        return Ok(None)
+    }
+    let mut fields = [None; 6];
+    for field in fields.iter_mut() {
+        *field = match decoder.u8()? {
+            0 => None,
+            1 => Some(decoder.u64()?),
+            _ => return Err(DecodeError::Malformed),
+        }
+    }
+    Ok(Some(ResourceLimits {
+        max_memory_bytes: fields[0],
+        max_cpu_seconds: fields[1],
+        max_open_files: fields[2],
+        max_processes: fields[3],
+        max_file_size_bytes: fields[4],
+        max_wall_clock_seconds: fields[5],
+    }))
+}
+
+/// Returns the fields of `limits` in the order they are encoded.
+fn resource_limit_fields(limits: &ResourceLimits) -> [Option<u64>; 6] {
+    [
+        limits.max_memory_bytes,
+        limits.max_cpu_seconds,
+        limits.max_open_files,
+        limits.max_processes,
+        limits.max_file_size_bytes,
+        limits.max_wall_clock_seconds,
+    ]
+}
+
+/// Appends a length-prefixed byte string to an encoded profile.
+pub(crate) fn encode_bytes(bytes: &mut Vec<u8>, string: &[u8]) {
+    bytes.extend_from_slice(&(string.len() as u32).to_le_bytes());
+    bytes.extend_from_slice(string)
+}
+
+/// Reads the parts of an encoded profile in order.
+pub(crate) struct Decoder<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Decoder<'a> {
+    fn new(bytes: &'a [u8]) -> Decoder<'a> {
+        Decoder {
+            bytes,
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    fn take(&mut self, length: usize) -> Result<&'a [u8],DecodeError> {
+        if self.bytes.len() < length {
+            return Err(DecodeError::Truncated)
+        }
+        let (taken, rest) = self.bytes.split_at(length);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    pub fn u8(&mut self) -> Result<u8,DecodeError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16,DecodeError> {
+        let bytes = self.take(2)?;
+        Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+    }
+
+    pub fn u32(&mut self) -> Result<u32,DecodeError> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn u64(&mut self) -> Result<u64,DecodeError> {
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(self.take(8)?);
+        Ok(u64::from_le_bytes(bytes))
+    }
+
+    /// Reads a byte string written by `encode_bytes()`.
+    pub fn bytes(&mut self) -> Result<&'a [u8],DecodeError> {
+        let length = self.u32()? as usize;
+        self.take(length)
+    }
+}
+
+/// The 64-bit FNV-1a hash of `bytes`.
+fn checksum(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+/// Describes an operation in plain language, for audit logs and prompts shown to users.
+///
+/// Descriptions are verb phrases that complete the sentence "The sandboxed process will be able
+/// to…", for example "read files under /usr/share/fonts".
+pub trait Describe {
+    /// Returns a plain-language description of this operation.
+    fn describe(&self) -> String;
+}
+
+impl Describe for Operation {
+    fn describe(&self) -> String {
+        match *self {
+            Operation::FileReadAll(PathPattern::Literal(ref path)) => {
+                format!("read {}", path.display())
+            }
+            Operation::FileReadAll(PathPattern::Subpath(ref path)) => {
+                format!("read files under {}", path.display())
+            }
+            Operation::FileReadAll(PathPattern::Glob(ref glob)) => {
+                format!("read files matching {}", glob)
+            }
+            Operation::FileReadMetadata(PathPattern::Literal(ref path)) => {
+                format!("read metadata of {}", path.display())
+            }
+            Operation::FileReadMetadata(PathPattern::Subpath(ref path)) => {
+                format!("read metadata of files under {}", path.display())
+            }
+            Operation::FileReadMetadata(PathPattern::Glob(ref glob)) => {
+                format!("read metadata of files matching {}", glob)
+            }
+            Operation::FileWriteAll(PathPattern::Literal(ref path)) => {
+                format!("write {}", path.display())
+            }
+            Operation::FileWriteAll(PathPattern::Subpath(ref path)) => {
+                format!("write files under {}", path.display())
+            }
+            Operation::FileWriteAll(PathPattern::Glob(ref glob)) => {
+                format!("write files matching {}", glob)
+            }
+            Operation::FileWriteMetadata(PathPattern::Literal(ref path)) => {
+                format!("change the permissions, ownership, and timestamps of {}", path.display())
+            }
+            Operation::FileWriteMetadata(PathPattern::Subpath(ref path)) => {
+                format!("change the permissions, ownership, and timestamps of files under {}",
+                        path.display())
+            }
+            Operation::FileWriteMetadata(PathPattern::Glob(ref glob)) => {
+                format!("change the permissions, ownership, and timestamps of files matching {}",
+                        glob)
+            }
+            Operation::FileAppend(PathPattern::Literal(ref path)) => {
+                format!("append to {}", path.display())
+            }
+            Operation::FileAppend(PathPattern::Subpath(ref path)) => {
+                format!("append to files under {}", path.display())
+            }
+            Operation::FileAppend(PathPattern::Glob(ref glob)) => {
+                format!("append to files matching {}", glob)
+            }
+            Operation::ProcessExec(PathPattern::Literal(ref path)) => {
+                format!("run {}", path.display())
+            }
+            Operation::ProcessExec(PathPattern::Subpath(ref path)) => {
+                format!("run programs under {}", path.display())
+            }
+            Operation::ProcessExec(PathPattern::Glob(ref glob)) => {
+                format!("run programs matching {}", glob)
+            }
+            Operation::NetworkOutbound(AddressPattern::All) => {
+                "create sockets and make outbound network connections".to_owned()
+            }
+            Operation::NetworkOutbound(AddressPattern::Tcp(port)) => {
+                format!("make outbound TCP connections to port {}", port)
+            }
+            Operation::NetworkOutbound(AddressPattern::Udp(port)) => {
+                format!("send UDP datagrams to port {}", port)
+            }
+            Operation::NetworkOutbound(AddressPattern::LocalSocket(ref path)) => {
+                format!("connect to the local socket at {}", path.display())
+            }
+            Operation::NetworkOutbound(AddressPattern::IpCidr { addr, prefix_len, port: None }) => {
+                format!("make outbound network connections to {}/{}", addr, prefix_len)
+            }
+            Operation::NetworkOutbound(AddressPattern::IpCidr {
+                addr,
+                prefix_len,
+                port: Some(port),
+            }) => {
+                format!("make outbound network connections to port {} in {}/{}",
+                        port, addr, prefix_len)
+            }
+            Operation::NetworkInbound(AddressPattern::All) => {
+                "create sockets and accept inbound network connections".to_owned()
+            }
+            Operation::NetworkInbound(AddressPattern::Tcp(port)) => {
+                format!("accept inbound TCP connections on port {}", port)
+            }
+            Operation::NetworkInbound(AddressPattern::Udp(port)) => {
+                format!("receive UDP datagrams on port {}", port)
+            }
+            Operation::NetworkInbound(AddressPattern::LocalSocket(ref path)) => {
+                format!("accept connections on the local socket at {}", path.display())
+            }
+            Operation::NetworkInbound(AddressPattern::IpCidr { addr, prefix_len, port: None }) => {
+                format!("accept inbound network connections from {}/{}", addr, prefix_len)
+            }
+            Operation::NetworkInbound(AddressPattern::IpCidr {
+                addr,
+                prefix_len,
+                port: Some(port),
+            }) => {
+                format!("accept inbound network connections on port {} from {}/{}",
+                        port, addr, prefix_len)
+            }
+            Operation::SystemInfoRead => "read system information".to_owned(),
+            Operation::StandardDevices => {
+                "read and write /dev/null, /dev/zero, and /dev/urandom".to_owned()
+            }
+            Operation::PlatformSpecific(ref operation) => operation.describe(),
+        }
+    }
+}
+
+/// The differences between two profiles, as returned by `Profile::diff()`.
+///
+/// The diff reads from the profile `diff()` was called on ("self") to its argument ("other"):
+/// narrowing `Subpath("/usr")` to `Subpath("/usr/share")` means the other profile allows less.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ProfileDiff {
+    /// Operations allowed only by this profile, with nothing related in the other.
+    pub only_in_self: Vec<Operation>,
+    /// Operations allowed only by the other profile, with nothing related in this one.
+    pub only_in_other: Vec<Operation>,
+    /// Pairs of an operation in this profile and a narrower one that replaces it in the other.
+    pub narrowed: Vec<(Operation, Operation)>,
+    /// Pairs of an operation in this profile and a wider one that replaces it in the other.
+    pub widened: Vec<(Operation, Operation)>,
+}
+
+impl ProfileDiff {
+    /// Returns true if both profiles allow exactly the same operations.
+    pub fn is_empty(&self) -> bool {
+        self.only_in_self.is_empty() && self.only_in_other.is_empty() &&
+            self.narrowed.is_empty() && self.widened.is_empty()
+    }
+}
+
+impl fmt::Display for ProfileDiff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_empty() {
+            return write!(f, "profiles allow the same operations")
+        }
+        write!(f,
+               "{} removed, {} added, {} narrowed, {} widened",
+               self.only_in_self.len(),
+               self.only_in_other.len(),
+               self.narrowed.len(),
+               self.widened.len())?;
+        for operation in &self.only_in_self {
+            write!(f, "\n  - {:?}", operation)?;
+        }
+        for operation in &self.only_in_other {
+            write!(f, "\n  + {:?}", operation)?;
+        }
+        for (before, after) in &self.narrowed {
+            write!(f, "\n  < {:?} narrowed to {:?}", before, after)?;
+        }
+        for (before, after) in &self.widened {
+            write!(f, "\n  > {:?} widened to {:?}", before, after)?;
+        }
+        Ok(())
+    }
 }
 
 /// How precisely an operation can be allowed on this platform.
@@ -165,11 +1901,47 @@ pub enum OperationSupportLevel {
     AlwaysAllowed,
 }
 
+/// How well this platform prohibits an operation that a profile does not allow.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ProhibitionSupport {
+    /// Attempts to perform the operation fail or terminate the process.
+    Enforced,
+    /// The operation is prohibited, but only as part of a coarser class of operations, so
+    /// allowing it allows more than was asked for. For example, on Linux, allowing connections to
+    /// one TCP port would allow connections to every port.
+    EnforcedCoarsely,
+    /// The operation succeeds whether or not the profile allows it.
+    NotEnforced,
+}
+
+impl ProhibitionSupport {
+    /// Returns the prohibition support implied by how precisely an operation can be allowed.
+    ///
+    /// An operation that is never allowed is assumed to be prohibited. Backends that cannot
+    /// actually prohibit such an operation override `OperationSupport::prohibition_support()`.
+    pub fn from_support_level(level: OperationSupportLevel) -> ProhibitionSupport {
+        match level {
+            OperationSupportLevel::NeverAllowed |
+            OperationSupportLevel::CanBeAllowed => ProhibitionSupport::Enforced,
+            OperationSupportLevel::CannotBeAllowedPrecisely => {
+                ProhibitionSupport::EnforcedCoarsely
+            }
+            OperationSupportLevel::AlwaysAllowed => ProhibitionSupport::NotEnforced,
+        }
+    }
+}
+
 /// Allows operations to be queried to determine how precisely they can be allowed on this
 /// platform.
 pub trait OperationSupport {
     /// Returns an `OperationSupportLevel` describing how well this operation can be allowed on
     /// this platform.
     fn support(&self) -> OperationSupportLevel;
+
+    /// Returns a `ProhibitionSupport` describing how well this operation is prohibited on this
+    /// platform when a profile does not allow it.
+    fn prohibition_support(&self) -> ProhibitionSupport {
+        ProhibitionSupport::from_support_level(self.support())
+    }
 }
 
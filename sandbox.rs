@@ -10,16 +10,49 @@
 
 //! Creation and destruction of sandboxes.
 
-use platform::process::{self, Process};
-use profile::Profile;
+#[cfg(unix)]
+use crate::channel::{SandboxChannel, CHANNEL_FD_ENV_VAR};
+#[cfg(feature = "serde")]
+use crate::event_log::EventLog;
+use crate::error::SandboxError;
+use crate::event_log::{EventKind, EventRecord, EventSink};
+#[cfg(any(target_os="android", target_os="linux"))]
+use crate::platform::linux::{cgroup, jail};
+use crate::platform;
+use crate::platform::process::{self, Process};
+#[cfg(all(feature = "tokio", unix))]
+use crate::platform::process::AsyncProcess;
+#[cfg(windows)]
+use crate::profile::DecodeError;
+use crate::profile::{Operation, PathAccess, PathKind, PathPattern, Profile, RequiredPath};
+use crate::telemetry::{Mechanism, SandboxTelemetry, TelemetrySink};
 
+#[cfg(unix)]
+use libc::{R_OK, W_OK, X_OK};
 use std::collections::HashMap;
 use std::convert::AsRef;
 use std::env;
+use std::error::Error;
 use std::ffi::{CString, OsStr};
+use std::fmt;
+use std::fs;
+#[cfg(unix)]
+use std::fs::File;
 use std::io;
+#[cfg(unix)]
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::mem;
+#[cfg(all(feature = "serde", unix))]
+use std::os::fd::OwnedFd;
+#[cfg(unix)]
+use std::os::unix::io::{AsRawFd, FromRawFd, RawFd};
+#[cfg(all(feature = "serde", windows))]
+use std::os::windows::io::OwnedHandle;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
-pub use platform::{ChildSandbox, Sandbox};
+pub use crate::platform::{ChildSandbox, Sandbox};
 
 /// All platform-specific sandboxes implement this trait.
 ///
@@ -31,22 +64,1083 @@ pub trait SandboxMethods {
 
     /// Spawns a child process eligible for sandboxing.
     fn start(&self, command: &mut Command) -> io::Result<Process>;
+
+    /// Like `start()`, but also connects the child to this process. The child gets its end of
+    /// the channel with `ChildSandboxChannel::from_environment()`.
+    #[cfg(unix)]
+    fn start_with_channel(&self, command: &mut Command) -> io::Result<(Process, SandboxChannel)> {
+        let (channel, child_end) = SandboxChannel::pair()?;
+        command.env(CHANNEL_FD_ENV_VAR, child_end.as_raw_fd().to_string()).inherit_fd(&child_end);
+        let process = self.start(command)?;
+
+        // Close our copy of the child's end, so that we see the end of the stream once the child
+        // exits.
+        drop(child_end);
+        Ok((process, channel))
+    }
+
+    /// Like `start()`, but the child can be waited for from a Tokio runtime without blocking
+    /// it. The child activates its sandbox as it would after `start()`.
+    #[cfg(all(feature = "tokio", unix))]
+    fn start_async(&self, command: &mut Command) -> io::Result<AsyncProcess> {
+        self.start(command).map(AsyncProcess::from)
+    }
+
+    /// Checks, without starting anything, that a child could be started and confined as this
+    /// sandbox's profile and options ask, so that problems surface here rather than deep inside
+    /// activation. Every issue found is returned.
+    ///
+    /// This checks each of `Profile::required_host_paths()` and the mechanisms the platform
+    /// relies on. Passing is no guarantee: the host can change before the child starts.
+    fn preflight(&self) -> Result<(), Vec<PreflightIssue>>;
+}
+
+/// Configuration for a sandbox beyond its profile, passed to `Sandbox::with_options()`.
+#[derive(Clone, Default)]
+pub struct SandboxOptions {
+    events: EventSink,
+    telemetry: TelemetrySink,
+    jail_root: Option<PathBuf>,
+    disabled: Vec<Mechanism>,
+    weakening_acknowledged: bool,
+    core_dumps: CoreDumps,
+    limits: Option<SandboxLimits>,
+    cgroup_parent: Option<PathBuf>,
+    process_limits: Option<ResourceLimits>,
+    isolate_time: bool,
+    user_mapping: UserMapping,
+    hostname: Option<String>,
+    mount_options: MountOptions,
+    tmpfs_size: Option<u64>,
+}
+
+impl SandboxOptions {
+    /// Constructs the default options.
+    pub fn new() -> SandboxOptions {
+        SandboxOptions::default()
+    }
+
+    /// Writes a JSON-lines record of each lifecycle event and violation of the sandboxed child to
+    /// `fd`. See the `event_log` module for the format.
+    #[cfg(all(feature = "serde", unix))]
+    pub fn event_log(&mut self, fd: OwnedFd) -> &mut SandboxOptions {
+        self.events = EventSink::new(Some(Arc::new(EventLog::new(fd))));
+        self
+    }
+
+    /// Writes a JSON-lines record of each lifecycle event and violation of the sandboxed child to
+    /// `handle`. See the `event_log` module for the format.
+    #[cfg(all(feature = "serde", windows))]
+    pub fn event_log(&mut self, handle: OwnedHandle) -> &mut SandboxOptions {
+        self.events = EventSink::new(Some(Arc::new(EventLog::new(handle))));
+        self
+    }
+
+    /// Creates the directories that `chroot` jails are built in under `jail_root` instead of
+    /// `/tmp`. Only Linux uses jail directories.
+    pub fn jail_root<P>(&mut self, jail_root: P) -> &mut SandboxOptions where P: AsRef<Path> {
+        self.jail_root = Some(jail_root.as_ref().to_path_buf());
+        self
+    }
+
+    /// Returns the directory that `chroot` jails are built in.
+    #[cfg(any(target_os="android", target_os="linux"))]
+    pub(crate) fn jail_root_path(&self) -> &Path {
+        match self.jail_root {
+            Some(ref jail_root) => jail_root,
+            None => Path::new(jail::DEFAULT_JAIL_ROOT),
+        }
+    }
+
+    /// Starts children without a `chroot` jail, so that they can see the whole filesystem.
+    ///
+    /// This and the other `disable_` methods are for debugging and for environments that lack a
+    /// mechanism. They only affect Linux, and `Sandbox::start()` refuses to start children unless
+    /// `acknowledge_weakened_sandbox()` is called too.
+    pub fn disable_chroot(&mut self) -> &mut SandboxOptions {
+        self.disable(Mechanism::ChrootJail)
+    }
+
+    /// Starts children in the namespaces of the parent, and so without a `chroot` jail or a
+    /// network namespace either, confined by their seccomp filter (and Landlock, where there is
+    /// one) alone. Nothing keeps them from seeing the whole filesystem or other processes.
+    ///
+    /// This is the fallback for machines where `Sandbox::start()` fails with
+    /// `SandboxError::UserNamespacesUnavailable`. Sandbox limits and hostnames need namespaces.
+    pub fn disable_namespaces(&mut self) -> &mut SandboxOptions {
+        self.disable(Mechanism::Namespaces)
+            .disable(Mechanism::NetworkNamespace)
+            .disable(Mechanism::ChrootJail)
+    }
+
+    /// Starts children without a seccomp filter, so that they can make any system call.
+    pub fn disable_seccomp(&mut self) -> &mut SandboxOptions {
+        self.disable(Mechanism::SeccompFilter)
+    }
+
+    /// Starts children without a Landlock ruleset, even where the kernel has Landlock.
+    pub fn disable_landlock(&mut self) -> &mut SandboxOptions {
+        self.disable(Mechanism::Landlock)
+    }
+
+    /// Starts children in the network namespace of the parent, even if the profile doesn't allow
+    /// network access.
+    pub fn disable_network_namespace(&mut self) -> &mut SandboxOptions {
+        self.disable(Mechanism::NetworkNamespace)
+    }
+
+    /// Confirms that children should really be started with the mechanisms turned off by the
+    /// `disable_` methods, or, on Linux, with none at all by `Sandbox::trace()`.
+    pub fn acknowledge_weakened_sandbox(&mut self) -> &mut SandboxOptions {
+        self.weakening_acknowledged = true;
+        self
+    }
+
+    fn disable(&mut self, mechanism: Mechanism) -> &mut SandboxOptions {
+        if !self.disabled.contains(&mechanism) {
+            self.disabled.push(mechanism)
+        }
+        self
+    }
+
+    /// Returns the mechanisms turned off by the `disable_` methods, or an error if that wasn't
+    /// acknowledged.
+    pub(crate) fn disabled_mechanisms(&self) -> io::Result<&[Mechanism]> {
+        if !self.disabled.is_empty() && !self.weakening_acknowledged {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                      "sandbox mechanisms were disabled without \
+                                       acknowledge_weakened_sandbox()"))
+        }
+        Ok(&self.disabled)
+    }
+
+    /// Whether `acknowledge_weakened_sandbox()` was called.
+    pub(crate) fn weakening_acknowledged(&self) -> bool {
+        self.weakening_acknowledged
+    }
+
+    /// Sets what children do with core dumps. By default they never write them. Only Linux
+    /// honors this.
+    pub fn core_dumps(&mut self, core_dumps: CoreDumps) -> &mut SandboxOptions {
+        self.core_dumps = core_dumps;
+        self
+    }
+
+    /// Returns what children do with core dumps.
+    pub(crate) fn core_dump_behavior(&self) -> &CoreDumps {
+        &self.core_dumps
+    }
+
+    /// Confines children to `limits` on memory, CPU time, and process count. See
+    /// `SandboxLimits`.
+    pub fn limits(&mut self, limits: SandboxLimits) -> &mut SandboxOptions {
+        self.limits = Some(limits);
+        self
+    }
+
+    /// Returns the resource limits of children, if any were set.
+    pub(crate) fn resource_limits(&self) -> Option<&SandboxLimits> {
+        self.limits.as_ref()
+    }
+
+    /// Creates the cgroups that enforce `SandboxLimits` under the cgroup v2 group at `parent`
+    /// instead of `/sys/fs/cgroup`, such as a subtree that systemd delegates to this user. This
+    /// process must be allowed to create groups there, and the controllers the limits need must
+    /// be enabled in its `cgroup.subtree_control`. Only Linux uses cgroups.
+    pub fn cgroup_parent<P>(&mut self, parent: P) -> &mut SandboxOptions where P: AsRef<Path> {
+        self.cgroup_parent = Some(parent.as_ref().to_path_buf());
+        self
+    }
+
+    /// Returns the group that children's cgroups are created under.
+    #[cfg(any(target_os="android", target_os="linux"))]
+    pub(crate) fn cgroup_parent_path(&self) -> &Path {
+        match self.cgroup_parent {
+            Some(ref parent) => parent,
+            None => Path::new(cgroup::DEFAULT_CGROUP_PARENT),
+        }
+    }
+
+    /// Limits each child process to `limits` with `setrlimit()`. See `ResourceLimits`.
+    pub fn process_limits(&mut self, limits: ResourceLimits) -> &mut SandboxOptions {
+        self.process_limits = Some(limits);
+        self
+    }
+
+    /// Returns the per-process limits of children, if any were set.
+    pub(crate) fn per_process_limits(&self) -> Option<&ResourceLimits> {
+        self.process_limits.as_ref()
+    }
+
+    /// Gives children a time namespace of their own, in which `CLOCK_MONOTONIC` and
+    /// `CLOCK_BOOTTIME` start from zero, so that they can't tell how long the machine has been
+    /// up or match up readings taken in different sandboxes. The wall clock is unaffected. Only
+    /// Linux honors this, and kernels without time namespaces (before 5.6) start children with
+    /// the host's clocks after logging a warning. See `PlatformCapabilities::time_namespaces`.
+    pub fn isolate_time(&mut self, isolate_time: bool) -> &mut SandboxOptions {
+        self.isolate_time = isolate_time;
+        self
+    }
+
+    /// Returns true if children get a time namespace of their own.
+    pub(crate) fn isolates_time(&self) -> bool {
+        self.isolate_time
+    }
+
+    /// Sets which user and group IDs children have in their user namespace. By default they are
+    /// root there. Only Linux honors this.
+    pub fn user_mapping(&mut self, user_mapping: UserMapping) -> &mut SandboxOptions {
+        self.user_mapping = user_mapping;
+        self
+    }
+
+    /// Returns which user and group IDs children have in their user namespace.
+    pub(crate) fn user_mapping_mode(&self) -> UserMapping {
+        self.user_mapping
+    }
+
+    /// Sets the hostname children see in their UTS namespace to `hostname` instead of the
+    /// host's, so that nothing they produce can depend on which machine they ran on. It may be
+    /// at most 64 bytes long, and children fail to start if it is longer. Only Linux honors this.
+    pub fn hostname<S>(&mut self, hostname: S) -> &mut SandboxOptions where S: Into<String> {
+        self.hostname = Some(hostname.into());
+        self
+    }
+
+    /// Returns the hostname children see, if it was set.
+    pub(crate) fn child_hostname(&self) -> Option<&str> {
+        self.hostname.as_deref()
+    }
+
+    /// Sets the flags the paths in children's `chroot` jails are mounted with. By default they
+    /// get all of them. Only Linux honors this.
+    pub fn mount_options(&mut self, mount_options: MountOptions) -> &mut SandboxOptions {
+        self.mount_options = mount_options;
+        self
+    }
+
+    /// Returns the flags the paths in children's jails are mounted with.
+    pub(crate) fn jail_mount_options(&self) -> MountOptions {
+        self.mount_options
+    }
+
+    /// Limits the `tmpfs` that children's `chroot` jails are built on to `bytes`, rounded up to
+    /// whole pages, instead of the kernel's default of half of physical memory. Only Linux
+    /// honors this.
+    ///
+    /// The jail is read-only once its mount points are made, and what a child writes to a
+    /// writable path goes to the host's filesystem rather than the `tmpfs`, so this is a second
+    /// line of defense rather than the only thing between the child and the host's memory.
+    pub fn tmpfs_size_bytes(&mut self, bytes: u64) -> &mut SandboxOptions {
+        self.tmpfs_size = Some(bytes);
+        self
+    }
+
+    /// Returns the size limit of the `tmpfs` children's jails are built on, if one was set.
+    pub(crate) fn jail_tmpfs_size(&self) -> Option<u64> {
+        self.tmpfs_size
+    }
+
+    /// Reports the outcomes of this sandbox's children to `telemetry`. See the `telemetry` module
+    /// for when it is called.
+    ///
+    /// On Linux, with telemetry or an event log, `Sandbox::start()` waits for the child to
+    /// activate its sandbox (or exit) so that it can report how long that took.
+    pub fn telemetry(&mut self, telemetry: Arc<dyn SandboxTelemetry>) -> &mut SandboxOptions {
+        self.telemetry = TelemetrySink::new(telemetry);
+        self
+    }
+
+    /// Whether anything receives the outcomes of this sandbox's children, so that a platform can
+    /// skip collecting them.
+    pub(crate) fn reports_outcomes(&self) -> bool {
+        self.telemetry.is_enabled() || self.events.is_enabled()
+    }
+
+    /// Records the outcome of starting a sandboxed child with `profile` and hands the child the
+    /// event and telemetry sinks, so that it can record how it exits.
+    pub(crate) fn record_start(&self, profile: &Profile, result: io::Result<Process>)
+                               -> io::Result<Process> {
+        let disabled = if self.disabled.is_empty() {
+            None
+        } else {
+            Some(self.disabled.clone())
+        };
+        let (mechanisms, missing) = platform::mechanisms(profile, &self.disabled);
+        if self.telemetry.is_enabled() {
+            let (pid, errno, timings) = match result {
+                Ok(ref process) => (Some(process.pid), None, process.timings.as_slice()),
+                Err(ref err) => (None, err.raw_os_error(), &[][..]),
+            };
+            self.telemetry.started(pid,
+                                   errno,
+                                   mechanisms.clone(),
+                                   &self.disabled,
+                                   &missing,
+                                   timings);
+        }
+        let mut process = match result {
+            Ok(process) => process,
+            Err(err) => {
+                if self.events.is_enabled() {
+                    let mut record = EventRecord::new(EventKind::StartFailed);
+                    record.stage = Some("start".to_owned());
+                    record.errno = err.raw_os_error();
+                    record.disabled = disabled;
+                    self.events.record(record);
+                }
+                return Err(err)
+            }
+        };
+        process.mechanisms = mechanisms.into_iter().filter(|mechanism| {
+            !missing.contains(mechanism)
+        }).collect();
+        if self.events.is_enabled() {
+            let mut record = EventRecord::new(EventKind::Started);
+            record.pid = Some(process.pid);
+            record.stage = Some("start".to_owned());
+            record.disabled = disabled;
+            let timings = process.timings.as_slice();
+            if !timings.is_empty() {
+                record.timings_us = Some(timings.iter().map(|&(stage, duration)| {
+                    (stage, duration.as_micros() as u64)
+                }).collect());
+            }
+            self.events.record(record);
+            process.events = self.events.clone();
+        }
+        process.telemetry = self.telemetry.clone();
+        Ok(process)
+    }
+
+    /// Implements `SandboxMethods::preflight()` for a sandbox with `profile` and these options.
+    pub(crate) fn preflight(&self, profile: &Profile) -> Result<(), Vec<PreflightIssue>> {
+        let mut issues = Vec::new();
+        if self.disabled_mechanisms().is_err() {
+            issues.push(PreflightIssue::UnacknowledgedWeakening)
+        }
+        let core_dump_directory = match self.core_dumps {
+            CoreDumps::EnabledTo(ref directory) => {
+                if let Some(pattern) = platform::unsupported_core_pattern() {
+                    issues.push(PreflightIssue::UnsupportedCorePattern(pattern))
+                }
+                Some(RequiredPath {
+                    path: directory.clone(),
+                    kind: PathKind::Dir,
+                    access: PathAccess::ReadWrite,
+                })
+            }
+            CoreDumps::Disabled | CoreDumps::Default => None,
+        };
+        check_paths(profile.required_host_paths().into_iter().chain(core_dump_directory),
+                    &mut issues);
+        // The jail only holds the paths that globs match when the child starts.
+        if cfg!(any(target_os="android", target_os="linux")) {
+            for operation in profile.allowed_operations() {
+                let pattern = match *operation {
+                    Operation::FileReadAll(ref pattern) |
+                    Operation::FileWriteAll(ref pattern) |
+                    Operation::FileWriteMetadata(ref pattern) |
+                    Operation::FileAppend(ref pattern) |
+                    Operation::ProcessExec(ref pattern) => pattern,
+                    _ => continue,
+                };
+                if let PathPattern::Glob(ref glob) = *pattern {
+                    if pattern.expand().is_empty() {
+                        issues.push(PreflightIssue::UnmatchedGlob(glob.clone()))
+                    }
+                }
+            }
+        }
+        let (_, missing) = platform::mechanisms(profile, &self.disabled);
+        issues.extend(missing.into_iter().map(PreflightIssue::MechanismUnavailable));
+        if issues.is_empty() {
+            Ok(())
+        } else {
+            Err(issues)
+        }
+    }
+}
+
+/// Limits on the resources that sandboxed children and their descendants may use together. See
+/// `SandboxOptions::limits()` and `Sandbox::with_limits()`.
+///
+/// Only Linux enforces these, with a cgroup v2 group that `Sandbox::start()` creates for each
+/// child under `/sys/fs/cgroup`, or under `SandboxOptions::cgroup_parent()`, and
+/// `Process::wait()` removes. This needs the unified hierarchy mounted there, writable by this
+/// process, with the controllers for the limits set enabled. Without them, `Sandbox::start()`
+/// fails with `Unsupported`, as it does on other platforms. The groups of a parent that died
+/// before waiting for its children are left behind for
+/// `platform::linux::cleanup_stale_cgroups()`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SandboxLimits {
+    /// The most memory, in bytes, that the children may use before the kernel reclaims or kills.
+    /// It is written to `memory.max`.
+    pub memory_bytes: Option<u64>,
+    /// The children's share of CPU time relative to other groups, from 1 to 10000, where 100 is
+    /// the default. It is written to `cpu.weight`.
+    pub cpu_shares: Option<u32>,
+    /// The most processes and threads the children may have at once. It is written to
+    /// `pids.max`.
+    pub pids_max: Option<u32>,
+}
+
+/// Limits that each sandboxed process gets with `setrlimit()`, as both its soft and its hard
+/// limit, so that it can't raise them again. See `SandboxOptions::process_limits()`,
+/// `Sandbox::with_resource_limits()`, and `Profile::with_resource_limits()`.
+///
+/// Unlike `SandboxLimits`, these need no cgroup hierarchy, but they bind each process on its own
+/// rather than the children together, and a child that runs out reacts the way the limit makes
+/// it: allocations fail past the memory limit, writing past the file size limit raises
+/// `SIGXFSZ` and fails with `EFBIG`, and the kernel sends the child `SIGXCPU` once it has used up
+/// its CPU time and `SIGKILL` a second after that. A child that handles `SIGXCPU` has that second
+/// to wind down. On Linux, where the child is PID 1 of its PID namespace, the kernel doesn't let
+/// a signal it leaves unhandled kill it, so it dies of the `SIGKILL`; elsewhere `SIGXCPU` and
+/// `SIGXFSZ` kill it as usual. The child sets them while it activates its sandbox, before the
+/// activation hook runs and before the seccomp filter that keeps it from changing them, and
+/// activation fails with `SandboxError::ResourceLimitFailed` if it can't, as when a limit is above
+/// the hard limit it inherited.
+///
+/// Only Linux supports limits given to the sandbox; on other platforms its children fail to
+/// start, with `Unsupported`. Limits in the profile are also set on macOS, all but
+/// `max_wall_clock_seconds`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct ResourceLimits {
+    /// The most address space, in bytes, that the process may map (`RLIMIT_AS`).
+    pub max_memory_bytes: Option<u64>,
+    /// The most CPU time, in seconds, that the process may use (`RLIMIT_CPU`). The hard limit is
+    /// a second more, so that `SIGXCPU` comes first.
+    pub max_cpu_seconds: Option<u64>,
+    /// One more than the highest descriptor number the process may open (`RLIMIT_NOFILE`).
+    pub max_open_files: Option<u64>,
+    /// The most processes and threads that the user the process runs as may have
+    /// (`RLIMIT_NPROC`).
+    pub max_processes: Option<u64>,
+    /// The largest file, in bytes, that the process may write (`RLIMIT_FSIZE`). A profile that
+    /// allows no writing or appending limits this to zero on Linux regardless.
+    pub max_file_size_bytes: Option<u64>,
+    /// The most time, in seconds, that the child may run, however little CPU time it uses. The
+    /// parent kills the child and everything it started with `SIGKILL` when it is up, and
+    /// `Process::wait()` returns `ExitStatus::TimedOut`. Unlike the others, this isn't set with
+    /// `setrlimit()`.
+    pub max_wall_clock_seconds: Option<u64>,
+}
+
+impl ResourceLimits {
+    /// Returns the tighter of `self` and `other` for each limit, for a child given limits both by
+    /// its sandbox and by its profile.
+    pub(crate) fn combined_with(&self, other: &ResourceLimits) -> ResourceLimits {
+        let tighter = |this: Option<u64>, that: Option<u64>| {
+            match (this, that) {
+                (Some(this), Some(that)) => Some(this.min(that)),
+                (this, that) => this.or(that),
+            }
+        };
+        ResourceLimits {
+            max_memory_bytes: tighter(self.max_memory_bytes, other.max_memory_bytes),
+            max_cpu_seconds: tighter(self.max_cpu_seconds, other.max_cpu_seconds),
+            max_open_files: tighter(self.max_open_files, other.max_open_files),
+            max_processes: tighter(self.max_processes, other.max_processes),
+            max_file_size_bytes: tighter(self.max_file_size_bytes, other.max_file_size_bytes),
+            max_wall_clock_seconds: tighter(self.max_wall_clock_seconds,
+                                            other.max_wall_clock_seconds),
+        }
+    }
+}
+
+/// What sandboxed children do with core dumps. See `SandboxOptions::core_dumps()`.
+///
+/// Core dumps can hold anything the child had in memory, including untrusted input and secrets,
+/// so they are off unless asked for.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum CoreDumps {
+    /// Children never write core dumps. Their `RLIMIT_CORE` is zero and they are made
+    /// undumpable, which also keeps other processes from attaching to them with `ptrace`.
+    #[default]
+    Disabled,
+    /// Children keep the core dump limit and dumpability they inherit, and dump core wherever
+    /// the kernel's `core_pattern` puts it as seen from inside the jail.
+    Default,
+    /// Children dump core into this host directory, which must be absolute. It is bound writable
+    /// into the jail at the same path and becomes the child's working directory, their
+    /// `RLIMIT_CORE` is raised to the hard limit, and they stay dumpable.
+    ///
+    /// The kernel's `core_pattern` must be a plain file name such as `core` or `core.%p`. A
+    /// pattern that pipes dumps to a host helper or names an absolute path bypasses the
+    /// directory, so `Sandbox::start()` refuses to start children under one.
+    EnabledTo(PathBuf),
+}
+
+/// The flags the paths bound into a Linux child's `chroot` jail are mounted with, set with
+/// `SandboxOptions::mount_options()`. Each is on by default.
+///
+/// Whatever these say, the programs `Operation::ProcessExec` allows can be run, with the loader
+/// and libraries they need, and the devices `Operation::StandardDevices` allows can be used.
+/// Whether a path is read-only is up to the profile.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MountOptions {
+    /// Files can't be run or mapped executable (`noexec`). Turn this off for a child that loads
+    /// code from paths it may only read, with `dlopen()` for example.
+    pub noexec: bool,
+    /// Set-user-ID and set-group-ID bits and file capabilities are ignored (`nosuid`).
+    pub nosuid: bool,
+    /// Device files can't be opened (`nodev`).
+    pub nodev: bool,
+}
+
+impl Default for MountOptions {
+    fn default() -> MountOptions {
+        MountOptions {
+            noexec: true,
+            nosuid: true,
+            nodev: true,
+        }
+    }
+}
+
+/// The user and group IDs a child has in the user namespace `Sandbox::start()` gives it, set
+/// with `SandboxOptions::user_mapping()`. Either way, only the parent's own IDs are mapped, so
+/// files owned by anyone else appear to belong to the overflow IDs, usually 65534.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum UserMapping {
+    /// The child is root in its user namespace, and the parent's files appear to belong to it as
+    /// root.
+    #[default]
+    Root,
+    /// The child keeps the parent's user and group IDs, so that the parent's files appear to
+    /// belong to it as they do outside. Until it activates its sandbox, it holds
+    /// `CAP_SYS_ADMIN` in its user namespace as an ambient capability, which it needs to build
+    /// the jail.
+    Preserve,
+}
+
+/// The modes of the C runtime's `access()` on Windows, which has no `X_OK`: anything that can be
+/// read can be run.
+#[cfg(windows)]
+const R_OK: libc::c_int = 4;
+#[cfg(windows)]
+const W_OK: libc::c_int = 2;
+#[cfg(windows)]
+const X_OK: libc::c_int = 0;
+
+/// Adds an issue to `issues` for each of `required_paths` that the sandbox couldn't give access to.
+pub(crate) fn check_paths<I>(required_paths: I, issues: &mut Vec<PreflightIssue>)
+                             where I: IntoIterator<Item=RequiredPath> {
+    for required_path in required_paths {
+        let path = required_path.path;
+        let metadata = match fs::metadata(&path) {
+            Ok(metadata) => metadata,
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => {
+                issues.push(PreflightIssue::MissingPath(path));
+                continue
+            }
+            Err(err) => {
+                issues.push(PreflightIssue::Inaccessible {
+                    path,
+                    errno: err.raw_os_error().unwrap_or(0),
+                });
+                continue
+            }
+        };
+        if required_path.kind == PathKind::Dir && !metadata.is_dir() {
+            issues.push(PreflightIssue::NotADirectory(path));
+            continue
+        }
+        let mut mode = R_OK;
+        if matches!(required_path.access, PathAccess::ReadWrite | PathAccess::ReadAppend) {
+            mode |= W_OK
+        }
+        if metadata.is_dir() || required_path.access == PathAccess::Execute {
+            mode |= X_OK
+        }
+        if let Err(errno) = check_access(&path, mode) {
+            issues.push(PreflightIssue::Inaccessible {
+                path,
+                errno,
+            });
+            continue
+        }
+        if let Some(filesystem) = platform::unbindable_filesystem(&path) {
+            issues.push(PreflightIssue::UnsupportedFilesystem {
+                path,
+                filesystem: filesystem.to_owned(),
+            })
+        }
+    }
+}
+
+fn check_access(path: &Path, mode: libc::c_int) -> Result<(), i32> {
+    let c_path = cstring(path);
+    let result = unsafe {
+        libc::access(c_path.as_ptr(), mode)
+    };
+    if result == 0 {
+        Ok(())
+    } else {
+        Err(io::Error::last_os_error().raw_os_error().unwrap_or(0))
+    }
+}
+
+/// A problem found by `SandboxMethods::preflight()`, or by `ChildSandbox::preflight()` on Linux.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PreflightIssue {
+    /// A path the profile makes readable doesn't exist.
+    MissingPath(PathBuf),
+    /// A path the profile makes readable as a directory is something else.
+    NotADirectory(PathBuf),
+    /// A path the profile makes readable can't be accessed by this process.
+    Inaccessible {
+        /// The path.
+        path: PathBuf,
+        /// The error number from checking it.
+        errno: i32,
+    },
+    /// A path the profile makes readable is on a filesystem that can't be bound into the jail
+    /// (Linux).
+    UnsupportedFilesystem {
+        /// The path.
+        path: PathBuf,
+        /// The name of the filesystem, for example `proc`.
+        filesystem: String,
+    },
+    /// A mechanism the platform relies on is unavailable on this machine.
+    MechanismUnavailable(Mechanism),
+    /// Mechanisms were disabled without `SandboxOptions::acknowledge_weakened_sandbox()`.
+    UnacknowledgedWeakening,
+    /// Core dumps were enabled with `CoreDumps::EnabledTo`, but the kernel's `core_pattern`,
+    /// given here, would send them elsewhere (Linux).
+    UnsupportedCorePattern(String),
+    /// A glob the profile makes readable matches no path, so it would allow nothing (Linux).
+    UnmatchedGlob(String),
+    /// The process may not mount filesystems, so it can't build the `chroot` jail. It wasn't
+    /// started in namespaces of its own by `Sandbox::start()` (Linux).
+    CannotMount,
+    /// The profile's seccomp filter has this many instructions, more than the kernel takes
+    /// (Linux).
+    FilterTooLong(usize),
+    /// The process runs this many threads, and the `ChildSandboxOptions` don't synchronize them
+    /// (Linux).
+    MultithreadedProcess {
+        /// The number of threads.
+        thread_count: usize,
+    },
+}
+
+impl fmt::Display for PreflightIssue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            PreflightIssue::MissingPath(ref path) => write!(f, "{} does not exist", path.display()),
+            PreflightIssue::NotADirectory(ref path) => {
+                write!(f, "{} is not a directory", path.display())
+            }
+            PreflightIssue::Inaccessible { ref path, errno } => {
+                write!(f,
+                       "{} is not accessible: {}",
+                       path.display(),
+                       io::Error::from_raw_os_error(errno))
+            }
+            PreflightIssue::UnsupportedFilesystem { ref path, ref filesystem } => {
+                write!(f,
+                       "{} is on a {} filesystem, which can't be bound",
+                       path.display(),
+                       filesystem)
+            }
+            PreflightIssue::MechanismUnavailable(mechanism) => {
+                write!(f, "{} unavailable on this machine", mechanism)
+            }
+            PreflightIssue::UnacknowledgedWeakening => {
+                write!(f, "mechanisms were disabled without acknowledge_weakened_sandbox()")
+            }
+            PreflightIssue::UnsupportedCorePattern(ref pattern) if pattern.starts_with('|') => {
+                write!(f,
+                       "kernel.core_pattern pipes core dumps to a host helper ({}), so they \
+                        can't be written to the core dump directory; set it to a file name such \
+                        as core.%p",
+                       pattern)
+            }
+            PreflightIssue::UnsupportedCorePattern(ref pattern) => {
+                write!(f,
+                       "kernel.core_pattern names an absolute path ({}), which the jail hides; \
+                        set it to a file name such as core.%p",
+                       pattern)
+            }
+            PreflightIssue::UnmatchedGlob(ref glob) => write!(f, "{} matches nothing", glob),
+            PreflightIssue::CannotMount => {
+                write!(f, "this process may not mount filesystems to build the jail")
+            }
+            PreflightIssue::FilterTooLong(length) => {
+                write!(f, "the seccomp filter has {} instructions, more than the kernel takes",
+                       length)
+            }
+            PreflightIssue::MultithreadedProcess { thread_count } => {
+                write!(f, "this process runs {} threads, which activation can't confine",
+                       thread_count)
+            }
+        }
+    }
+}
+
+/// The environment variable through which `Sandbox::start()` tells the child which inherited file
+/// descriptor holds its encoded profile.
+#[cfg(unix)]
+const PROFILE_FD_ENV_VAR: &str = "GAOL_PROFILE_FD";
+
+/// A profile encoded with `Profile::to_bytes()` in an unlinked temporary file, for a child to
+/// inherit and decode with `ChildSandbox::from_environment()`. Dropping it closes the parent's
+/// descriptor.
+///
+/// The descriptor isn't close-on-exec, so children that other threads start at the same time
+/// inherit it too. That only reveals the profile, which is no secret.
+#[cfg(unix)]
+pub(crate) struct ProfilePayload {
+    file: File,
+}
+
+#[cfg(unix)]
+impl ProfilePayload {
+    pub fn new(profile: &Profile) -> io::Result<ProfilePayload> {
+        let template = cstring(env::temp_dir().join("gaol-profile.XXXXXX"));
+        let mut template = template.into_bytes_with_nul();
+        let fd = unsafe {
+            libc::mkstemp(template.as_mut_ptr() as *mut libc::c_char)
+        };
+        if fd < 0 {
+            return Err(io::Error::last_os_error())
+        }
+        let mut file = unsafe {
+            File::from_raw_fd(fd)
+        };
+        unsafe {
+            libc::unlink(template.as_ptr() as *const libc::c_char);
+        }
+        file.write_all(&profile.to_bytes())?;
+        Ok(ProfilePayload {
+            file,
+        })
+    }
+
+    /// Tells the child started with `command` where to find the profile.
+    pub fn export(&self, command: &mut Command) {
+        command.env(PROFILE_FD_ENV_VAR, self.file.as_raw_fd().to_string());
+    }
+}
+
+/// Reads and decodes the profile that `Sandbox::start()` passed to this process, closing the
+/// descriptor it came through.
+#[cfg(unix)]
+pub(crate) fn profile_from_environment() -> io::Result<Profile> {
+    let fd: RawFd = match env::var(PROFILE_FD_ENV_VAR).ok().and_then(|fd| fd.parse().ok()) {
+        Some(fd) => fd,
+        None => {
+            return Err(io::Error::new(io::ErrorKind::NotFound,
+                                      "no profile was passed to this process"))
+        }
+    };
+    // Make sure the descriptor is open before taking ownership of it, and don't use it twice.
+    env::remove_var(PROFILE_FD_ENV_VAR);
+    if unsafe { libc::fcntl(fd, libc::F_GETFD) } < 0 {
+        return Err(io::Error::last_os_error())
+    }
+    let mut file = unsafe {
+        File::from_raw_fd(fd)
+    };
+    let mut bytes = Vec::new();
+    file.seek(SeekFrom::Start(0))?;
+    file.read_to_end(&mut bytes)?;
+    Profile::from_bytes(&bytes).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+}
+
+/// The environment variable that holds the encoded profile on Windows, in hexadecimal.
+#[cfg(windows)]
+const PROFILE_ENV_VAR: &str = "GAOL_PROFILE";
+
+/// A profile encoded with `Profile::to_bytes()`, for a child to decode with
+/// `ChildSandbox::from_environment()`. Children on Windows inherit no handles, so the profile
+/// travels in their environment instead.
+#[cfg(windows)]
+pub(crate) struct ProfilePayload {
+    encoded: String,
+}
+
+#[cfg(windows)]
+impl ProfilePayload {
+    pub fn new(profile: &Profile) -> io::Result<ProfilePayload> {
+        let mut encoded = String::new();
+        for byte in profile.to_bytes() {
+            encoded.push_str(&format!("{:02x}", byte))
+        }
+        Ok(ProfilePayload {
+            encoded,
+        })
+    }
+
+    /// Tells the child started with `command` what the profile is.
+    pub fn export(&self, command: &mut Command) {
+        command.env(PROFILE_ENV_VAR, &self.encoded);
+    }
+}
+
+/// Reads and decodes the profile that `Sandbox::start()` passed to this process.
+#[cfg(windows)]
+pub(crate) fn profile_from_environment() -> io::Result<Profile> {
+    let encoded = match env::var(PROFILE_ENV_VAR) {
+        Ok(encoded) => encoded,
+        Err(_) => {
+            return Err(io::Error::new(io::ErrorKind::NotFound,
+                                      "no profile was passed to this process"))
+        }
+    };
+    env::remove_var(PROFILE_ENV_VAR);
+    let bytes: Option<Vec<u8>> = (0..encoded.len()).step_by(2).map(|index| {
+        encoded.get(index..index + 2).and_then(|pair| u8::from_str_radix(pair, 16).ok())
+    }).collect();
+    let bytes = bytes.ok_or_else(|| {
+        io::Error::new(io::ErrorKind::InvalidData, DecodeError::Malformed)
+    })?;
+    Profile::from_bytes(&bytes).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+}
+
+/// Configuration for a sandbox in the child process beyond its profile, passed to
+/// `ChildSandbox::with_options()`.
+#[derive(Clone, Debug, Default)]
+pub struct ChildSandboxOptions {
+    synchronize_threads: bool,
+}
+
+impl ChildSandboxOptions {
+    /// Constructs the default options.
+    pub fn new() -> ChildSandboxOptions {
+        ChildSandboxOptions::default()
+    }
+
+    /// Allows activation in a process that already has several threads.
+    ///
+    /// By default activation refuses with `ActivationError::MultithreadedProcess`, because on
+    /// Linux most restrictions bind only the calling thread. With this set, the seccomp filter is
+    /// installed with `SECCOMP_FILTER_FLAG_TSYNC` so that it binds every thread, but the other
+    /// threads keep their capabilities and are confined by the filter alone. Where the kernel
+    /// can't synchronize, the filter is applied to the calling thread only and activation fails
+    /// with `ActivationError::ThreadsNotSynchronized`.
+    pub fn synchronize_threads(&mut self, synchronize_threads: bool) -> &mut ChildSandboxOptions {
+        self.synchronize_threads = synchronize_threads;
+        self
+    }
+
+    /// Returns true if activation may synchronize the seccomp filter across threads.
+    pub fn synchronizes_threads(&self) -> bool {
+        self.synchronize_threads
+    }
 }
 
 /// All platform-specific sandboxes in the child process implement this trait.
+///
+/// Activation functions are never inlined, and nothing in them allocates, frees, or can panic
+/// once the final restriction has been applied, so a process that survives activation never has
+/// to unwind through half-applied restrictions.
 pub trait ChildSandboxMethods {
     /// Activates the restrictions in this child process from here on out. Be sure to check the
     /// return value!
-    fn activate(&self) -> Result<(),()>;
+    fn activate(&self) -> Result<(),ActivationError> {
+        self.activate_with_hook(|| {})
+    }
+
+    /// Like `activate()`, but runs `hook` partway through: after the restrictions that need
+    /// privileges to set up (on Linux, the namespaces and the `chroot` jail) but before the ones
+    /// that stop further changes (on Linux, the seccomp filter).
+    ///
+    /// If `hook` panics, the panic is not propagated: nothing can safely unwind through a
+    /// half-sandboxed process. Instead a note is written to standard error and the process exits
+    /// immediately with `ActivationError::HookPanicked.exit_code()`, which the parent can recognize
+    /// with `ExitStatus::activation_error()`.
+    fn activate_with_hook<F>(&self, hook: F) -> Result<(),ActivationError> where F: FnOnce();
+
+    /// Probes the restrictions from inside after `activate()` has succeeded, to prove that they
+    /// are really in effect before any untrusted data is touched.
+    ///
+    /// The probes never perform an operation that the sandbox would punish by terminating the
+    /// process. Check `VerificationReport::is_confined()` and refuse to continue if it is false.
+    fn verify(&self) -> VerificationReport;
+}
+
+/// The outcome of one probe run by `ChildSandboxMethods::verify()`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ProbeStatus {
+    /// The restriction the probe checks is in effect.
+    Passed,
+    /// The restriction the probe checks is not in effect.
+    Failed,
+    /// The probe doesn't apply, because the profile allows what it would check for.
+    Skipped,
+}
+
+/// One probe run by `ChildSandboxMethods::verify()`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Probe {
+    /// A short, stable name for the probe, for example `file-access`.
+    pub name: &'static str,
+    /// Whether the probe passed.
+    pub status: ProbeStatus,
+    /// What the probe observed, for humans.
+    pub detail: String,
+}
+
+/// The results of `ChildSandboxMethods::verify()`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct VerificationReport {
+    /// Every probe that was run, in order.
+    pub probes: Vec<Probe>,
+}
+
+impl VerificationReport {
+    /// Records the outcome of a probe.
+    pub fn push(&mut self, name: &'static str, status: ProbeStatus, detail: String) {
+        self.probes.push(Probe {
+            name,
+            status,
+            detail,
+        })
+    }
+
+    /// Returns true if no probe failed and at least one passed. A report in which nothing could
+    /// be checked does not count as confinement.
+    pub fn is_confined(&self) -> bool {
+        self.probes.iter().all(|probe| probe.status != ProbeStatus::Failed) &&
+            self.probes.iter().any(|probe| probe.status == ProbeStatus::Passed)
+    }
+
+    /// Returns the probes that failed.
+    pub fn failures(&self) -> Vec<&Probe> {
+        self.probes.iter().filter(|probe| probe.status == ProbeStatus::Failed).collect()
+    }
+}
+
+/// Why a sandbox couldn't be activated.
+#[derive(Debug)]
+pub enum ActivationError {
+    /// The operating system refused to apply one of the restrictions.
+    RestrictionFailed(SandboxError),
+    /// The process has more than one thread, so the restrictions could not bind all of them. See
+    /// `ChildSandboxOptions::synchronize_threads()`.
+    MultithreadedProcess {
+        /// The number of threads the process had.
+        thread_count: usize,
+    },
+    /// The kernel can't synchronize the seccomp filter across threads (`SECCOMP_FILTER_FLAG_TSYNC`
+    /// needs Linux 3.17), so with `ChildSandboxOptions::synchronize_threads()` the filter was
+    /// applied to the calling thread only. That thread is fully confined, but the others aren't
+    /// confined at all; treat this as a failure unless they can be trusted.
+    ThreadsNotSynchronized {
+        /// The number of threads the process had.
+        thread_count: usize,
+    },
+    /// The kernel can't give the child a cgroup namespace (that needs Linux 4.6), so it can read
+    /// the layout of the host's cgroup hierarchy through `/proc/self/cgroup`. Every restriction
+    /// was applied otherwise; treat this as a failure if that layout is sensitive. See
+    /// `PlatformCapabilities::cgroup_namespaces`.
+    NoCgroupNamespace,
+    /// The hook passed to `ChildSandboxMethods::activate_with_hook()` panicked.
+    ///
+    /// This is never returned in the child, which exits instead; the parent sees it through
+    /// `ExitStatus::activation_error()`.
+    HookPanicked,
+}
+
+/// The exit code of a child whose pre-activation hook panicked. Unusual enough that ordinary
+/// programs are unlikely to exit with it.
+const HOOK_PANICKED_EXIT_CODE: i32 = 121;
+
+/// The note a child writes to standard error before exiting because its hook panicked. It is
+/// static so that writing it doesn't allocate.
+static HOOK_PANICKED_MESSAGE: &[u8] =
+    b"gaol: the pre-activation hook panicked; exiting without unwinding\n";
+
+impl ActivationError {
+    /// Returns the exit code with which a child reports this error to its parent, if it is
+    /// reported that way.
+    pub fn exit_code(&self) -> Option<i32> {
+        match *self {
+            ActivationError::RestrictionFailed(_) |
+            ActivationError::MultithreadedProcess { .. } |
+            ActivationError::ThreadsNotSynchronized { .. } |
+            ActivationError::NoCgroupNamespace => None,
+            ActivationError::HookPanicked => Some(HOOK_PANICKED_EXIT_CODE),
+        }
+    }
+
+    /// Returns the error a child reported by exiting with `code`, if any.
+    pub fn from_exit_code(code: i32) -> Option<ActivationError> {
+        match code {
+            HOOK_PANICKED_EXIT_CODE => Some(ActivationError::HookPanicked),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for ActivationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ActivationError::RestrictionFailed(ref error) => error.fmt(f),
+            ActivationError::MultithreadedProcess { thread_count } => {
+                write!(f, "the process has {} threads, so the sandbox can't bind them all",
+                       thread_count)
+            }
+            ActivationError::ThreadsNotSynchronized { thread_count } => {
+                write!(f, "the seccomp filter binds only one of the process's {} threads",
+                       thread_count)
+            }
+            ActivationError::NoCgroupNamespace => {
+                f.write_str("the process has no cgroup namespace, so it can see the host's cgroups")
+            }
+            ActivationError::HookPanicked => f.write_str("the pre-activation hook panicked"),
+        }
+    }
+}
+
+impl Error for ActivationError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match *self {
+            ActivationError::RestrictionFailed(ref error) => Some(error),
+            ActivationError::MultithreadedProcess { .. } |
+            ActivationError::ThreadsNotSynchronized { .. } |
+            ActivationError::NoCgroupNamespace |
+            ActivationError::HookPanicked => None,
+        }
+    }
+}
+
+/// Runs a pre-activation hook for `ChildSandboxMethods::activate_with_hook()`, terminating the
+/// process without unwinding if it panics.
+#[inline(never)]
+pub(crate) fn run_activation_hook<F>(hook: F) where F: FnOnce() {
+    let payload = match panic::catch_unwind(AssertUnwindSafe(hook)) {
+        Ok(()) => return,
+        Err(payload) => payload,
+    };
+    // The payload's destructor is arbitrary code that could panic again.
+    mem::forget(payload);
+    unsafe {
+        #[cfg(unix)]
+        libc::write(libc::STDERR_FILENO,
+                    HOOK_PANICKED_MESSAGE.as_ptr() as *const libc::c_void,
+                    HOOK_PANICKED_MESSAGE.len());
+        #[cfg(windows)]
+        libc::write(2,
+                    HOOK_PANICKED_MESSAGE.as_ptr() as *const libc::c_void,
+                    HOOK_PANICKED_MESSAGE.len() as libc::c_uint);
+        libc::_exit(HOOK_PANICKED_EXIT_CODE)
+    }
 }
 
 fn cstring<T>(path: T) -> CString
     where T: AsRef<OsStr>
 {
     let path = path.as_ref();
-    let bytes = if cfg!(windows) {
-        path.to_str().unwrap().as_bytes()
-    } else {
+    #[cfg(windows)]
+    let bytes = path.to_str().unwrap().as_bytes();
+    #[cfg(unix)]
+    let bytes = {
         use std::os::unix::ffi::OsStrExt;
         path.as_bytes()
     };
@@ -60,6 +1154,9 @@ pub struct Command {
     pub args: Vec<CString>,
     /// The environment of the process.
     pub env: HashMap<CString,CString>,
+    /// Descriptors the process inherits even if they are close-on-exec.
+    #[cfg(unix)]
+    pub inherited_fds: Vec<RawFd>,
 }
 
 impl Command {
@@ -71,16 +1168,18 @@ impl Command {
             module_path: cstring(module_path),
             args: Vec::new(),
             env: HashMap::new(),
+            #[cfg(unix)]
+            inherited_fds: Vec::new(),
         }
     }
 
     /// Constructs a new `Command` for launching the current executable.
     pub fn me() -> io::Result<Command> {
-        Ok(Command::new(try!(env::current_exe())))
+        Ok(Command::new(env::current_exe()?))
     }
 
     /// Adds an argument to pass to the program.
-    pub fn arg<'a,T>(&'a mut self, arg: T) -> &'a mut Command where T: AsRef<OsStr> {
+    pub fn arg<T>(&mut self, arg: T) -> &mut Command where T: AsRef<OsStr> {
         self.args.push(cstring(arg));
         self
     }
@@ -92,12 +1191,23 @@ impl Command {
     }
 
     /// Inserts or updates an environment variable mapping.
-    pub fn env<'a,T,U>(&'a mut self, key: T, val: U) -> &'a mut Command
+    pub fn env<T,U>(&mut self, key: T, val: U) -> &mut Command
                        where T: AsRef<OsStr>, U: AsRef<OsStr> {
         self.env.insert(cstring(key), cstring(val));
         self
     }
 
+    /// Lets the process inherit `fd`, under the same number, even if it is close-on-exec. Tell
+    /// the process the number, for example in an argument or environment variable.
+    ///
+    /// Only the process inherits it: children that other threads start at the same time don't,
+    /// as long as `fd` is close-on-exec.
+    #[cfg(unix)]
+    pub fn inherit_fd<F>(&mut self, fd: &F) -> &mut Command where F: AsRawFd {
+        self.inherited_fds.push(fd.as_raw_fd());
+        self
+    }
+
     /// Executes the command as a child process, which is returned.
     pub fn spawn(&self) -> io::Result<Process> {
         process::spawn(self)
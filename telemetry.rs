@@ -0,0 +1,435 @@
+// Copyright 2015 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Reporting sandbox outcomes to an embedder's own telemetry.
+//!
+//! `SandboxOptions::telemetry()` installs a `SandboxTelemetry` that the parent side of a sandbox
+//! calls as the child progresses. For every child, the calls come in this order:
+//!
+//! 1. `degradation()`, only if some mechanism the platform normally uses is unavailable;
+//! 2. `activation()`, once the child has been started (or has failed to start), and on Linux
+//!    once it has activated its sandbox too, so that `ActivationStatus::timings()` can be filled
+//!    in;
+//! 3. `violation()`, only if the child was killed for performing a prohibited operation;
+//! 4. `child_exit()`, once the child has been waited for.
+//!
+//! The child applies its restrictions itself, after it starts, so `activation()` reports the
+//! mechanisms the child was started to use rather than proof that they are in effect. See
+//! `ChildSandboxMethods::verify()` for the latter.
+
+use crate::platform::process::ExitStatus;
+
+#[cfg(windows)]
+use crate::platform::process::pid_t;
+#[cfg(unix)]
+use libc::pid_t;
+use log::{debug, info, warn};
+use std::fmt;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// An operating-system mechanism that a sandbox uses to confine its child.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum Mechanism {
+    /// User, PID, IPC, mount, and UTS namespaces (Linux).
+    Namespaces,
+    /// A network namespace without interfaces, when the profile doesn't allow networking (Linux).
+    NetworkNamespace,
+    /// A `chroot` jail holding only the paths the profile allows reading (Linux).
+    ChrootJail,
+    /// A seccomp-bpf system call filter (Linux).
+    SeccompFilter,
+    /// A Landlock ruleset limiting the files the profile allows to what it allows doing with
+    /// them, where the kernel has Landlock and can express the profile (Linux).
+    Landlock,
+    /// A Seatbelt profile (Mac OS X).
+    Seatbelt,
+    /// Capability mode (FreeBSD).
+    Capsicum,
+    /// `unveil()`, hiding the paths the profile doesn't name (OpenBSD).
+    Unveil,
+    /// `pledge()`, limiting the system calls to those the profile's operations need (OpenBSD).
+    Pledge,
+    /// A Job Object that kills the child and its descendants when the sandbox goes away
+    /// (Windows).
+    JobObject,
+    /// A restricted token, which only reaches the user's own files where the profile grants them
+    /// (Windows).
+    RestrictedToken,
+    /// Windows Filtering Platform rules blocking the connections the profile doesn't allow
+    /// (Windows).
+    FilteringPlatform,
+    /// Process mitigation policies refusing unsigned code and code generated at run time
+    /// (Windows).
+    ProcessMitigations,
+}
+
+impl Mechanism {
+    /// Returns a short, stable name for this mechanism, for example `chroot_jail`.
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Mechanism::Namespaces => "namespaces",
+            Mechanism::NetworkNamespace => "network_namespace",
+            Mechanism::ChrootJail => "chroot_jail",
+            Mechanism::SeccompFilter => "seccomp_filter",
+            Mechanism::Landlock => "landlock",
+            Mechanism::Seatbelt => "seatbelt",
+            Mechanism::Capsicum => "capsicum",
+            Mechanism::Unveil => "unveil",
+            Mechanism::Pledge => "pledge",
+            Mechanism::JobObject => "job_object",
+            Mechanism::RestrictedToken => "restricted_token",
+            Mechanism::FilteringPlatform => "filtering_platform",
+            Mechanism::ProcessMitigations => "process_mitigations",
+        }
+    }
+
+    /// Returns the mechanism with the given `name()`.
+    pub fn from_name(name: &str) -> Option<Mechanism> {
+        [
+            Mechanism::Namespaces,
+            Mechanism::NetworkNamespace,
+            Mechanism::ChrootJail,
+            Mechanism::SeccompFilter,
+            Mechanism::Landlock,
+            Mechanism::Seatbelt,
+            Mechanism::Capsicum,
+            Mechanism::Unveil,
+            Mechanism::Pledge,
+            Mechanism::JobObject,
+            Mechanism::RestrictedToken,
+            Mechanism::FilteringPlatform,
+            Mechanism::ProcessMitigations,
+        ].iter().cloned().find(|mechanism| mechanism.name() == name)
+    }
+}
+
+impl fmt::Display for Mechanism {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            Mechanism::Namespaces => "namespaces",
+            Mechanism::NetworkNamespace => "network namespace",
+            Mechanism::ChrootJail => "chroot jail",
+            Mechanism::SeccompFilter => "seccomp filter",
+            Mechanism::Landlock => "Landlock",
+            Mechanism::Seatbelt => "Seatbelt",
+            Mechanism::Capsicum => "Capsicum",
+            Mechanism::Unveil => "unveil",
+            Mechanism::Pledge => "pledge",
+            Mechanism::JobObject => "Job Object",
+            Mechanism::RestrictedToken => "restricted token",
+            Mechanism::FilteringPlatform => "Windows Filtering Platform",
+            Mechanism::ProcessMitigations => "process mitigations",
+        })
+    }
+}
+
+/// A timed stage of starting a sandboxed child and activating its sandbox, in the order they
+/// happen. Only Linux times its stages.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum Stage {
+    /// Entering the user and PID namespaces.
+    Unshare,
+    /// Writing the user and group ID maps of the user namespace.
+    IdMaps,
+    /// Entering the IPC, mount, UTS, and network namespaces.
+    AuxiliaryNamespaces,
+    /// Mounting the `tmpfs` the jail is built on.
+    TmpfsMount,
+    /// Bind mounting the paths the profile allows into the jail.
+    BindMounts,
+    /// Entering the jail.
+    Chroot,
+    /// Installing the seccomp filter.
+    FilterInstall,
+}
+
+impl Stage {
+    /// Returns a short, stable name for this stage, for example `bind_mounts`.
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Stage::Unshare => "unshare",
+            Stage::IdMaps => "id_maps",
+            Stage::AuxiliaryNamespaces => "auxiliary_namespaces",
+            Stage::TmpfsMount => "tmpfs_mount",
+            Stage::BindMounts => "bind_mounts",
+            Stage::Chroot => "chroot",
+            Stage::FilterInstall => "filter_install",
+        }
+    }
+
+    /// Returns the stage with the given `name()`.
+    pub fn from_name(name: &str) -> Option<Stage> {
+        [
+            Stage::Unshare,
+            Stage::IdMaps,
+            Stage::AuxiliaryNamespaces,
+            Stage::TmpfsMount,
+            Stage::BindMounts,
+            Stage::Chroot,
+            Stage::FilterInstall,
+        ].iter().cloned().find(|stage| stage.name() == name)
+    }
+}
+
+/// The durations of the stages a child went through, in order. Timing a stage costs two reads of
+/// the monotonic clock, so it is done whether or not anyone will look.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub(crate) struct StageTimings {
+    timings: Vec<(Stage, Duration)>,
+}
+
+impl StageTimings {
+    /// Decodes timings encoded with `encode()`, skipping anything unrecognized.
+    pub fn decode(encoded: &str) -> StageTimings {
+        let timings = encoded.split(',').filter_map(|timing| {
+            let mut parts = timing.splitn(2, '=');
+            let stage = Stage::from_name(parts.next()?)?;
+            let nanos = parts.next()?.parse().ok()?;
+            Some((stage, Duration::from_nanos(nanos)))
+        }).collect();
+        StageTimings {
+            timings,
+        }
+    }
+
+    /// Runs `stage` and records how long it took.
+    pub fn time<T,F>(&mut self, stage: Stage, stage_fn: F) -> T where F: FnOnce() -> T {
+        let start = Instant::now();
+        let result = stage_fn();
+        self.timings.push((stage, start.elapsed()));
+        result
+    }
+
+    /// Encodes these timings for passing between processes, as `name=nanoseconds` pairs
+    /// separated by commas.
+    pub fn encode(&self) -> String {
+        let timings: Vec<String> = self.timings.iter().map(|&(stage, duration)| {
+            format!("{}={}", stage.name(), duration.as_nanos())
+        }).collect();
+        timings.join(",")
+    }
+
+    /// Returns the durations of the stages, in order.
+    pub fn as_slice(&self) -> &[(Stage, Duration)] {
+        &self.timings
+    }
+}
+
+/// How starting a sandboxed child went.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ActivationStatus {
+    /// The process ID of the child, if it was started.
+    pub pid: Option<pid_t>,
+    /// The error number, if the child couldn't be started.
+    pub errno: Option<i32>,
+    /// The mechanisms the child was started to use.
+    pub mechanisms: Vec<Mechanism>,
+    /// The mechanisms the child would have used but for `SandboxOptions`, for example
+    /// `SandboxOptions::disable_chroot()`.
+    pub disabled: Vec<Mechanism>,
+    timings: Vec<(Stage, Duration)>,
+}
+
+impl ActivationStatus {
+    /// Returns how long each stage of starting the child and activating its sandbox took, in the
+    /// order they ran. Stages that were skipped, for example because a mechanism was disabled,
+    /// are left out, as is everything the child didn't get to if it never activated.
+    pub fn timings(&self) -> &[(Stage, Duration)] {
+        &self.timings
+    }
+}
+
+/// A sandboxed child killed for performing a prohibited operation.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Violation {
+    /// The process ID of the child.
+    pub pid: pid_t,
+    /// The signal that killed it.
+    pub signal: i32,
+    /// The name of the system call responsible, when it is known.
+    pub syscall: Option<String>,
+}
+
+/// The resources a child used over its lifetime, as reported by `wait4` (on Windows,
+/// `GetProcessTimes()` and `GetProcessMemoryInfo()`).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ResourceUsage {
+    /// Time spent executing in user mode.
+    pub user_time: Duration,
+    /// Time spent executing in the kernel.
+    pub system_time: Duration,
+    /// The peak resident set size, in kilobytes.
+    pub max_resident_set_kb: u64,
+}
+
+#[cfg(unix)]
+impl ResourceUsage {
+    pub(crate) fn from_rusage(rusage: &libc::rusage) -> ResourceUsage {
+        ResourceUsage {
+            user_time: timeval_to_duration(&rusage.ru_utime),
+            system_time: timeval_to_duration(&rusage.ru_stime),
+            // Mac OS X reports bytes rather than kilobytes.
+            max_resident_set_kb: if cfg!(target_os="macos") {
+                rusage.ru_maxrss as u64 / 1024
+            } else {
+                rusage.ru_maxrss as u64
+            },
+        }
+    }
+}
+
+#[cfg(unix)]
+fn timeval_to_duration(timeval: &libc::timeval) -> Duration {
+    Duration::new(timeval.tv_sec as u64, timeval.tv_usec as u32 * 1000)
+}
+
+/// Receives sandbox outcomes. See the module documentation for when each method is called.
+///
+/// Methods are called on whichever thread starts or waits for the child, so they should return
+/// quickly.
+pub trait SandboxTelemetry: Send + Sync {
+    /// A sandboxed child was started, or failed to start.
+    fn activation(&self, status: &ActivationStatus);
+
+    /// Mechanisms the platform normally uses are unavailable on this machine, so the child will
+    /// be less confined than usual, if it can be started at all.
+    fn degradation(&self, missing: &[Mechanism]);
+
+    /// A sandboxed child was killed for performing a prohibited operation.
+    fn violation(&self, violation: &Violation);
+
+    /// A sandboxed child exited, for whatever reason.
+    fn child_exit(&self, status: &ExitStatus, rusage: Option<&ResourceUsage>);
+}
+
+/// A `SandboxTelemetry` that writes every outcome to the `log` crate.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct LogTelemetry;
+
+impl SandboxTelemetry for LogTelemetry {
+    fn activation(&self, status: &ActivationStatus) {
+        let mechanisms: Vec<String> = status.mechanisms.iter().map(|mechanism| {
+            mechanism.to_string()
+        }).collect();
+        if !status.disabled.is_empty() {
+            let disabled: Vec<String> = status.disabled.iter().map(|mechanism| {
+                mechanism.to_string()
+            }).collect();
+            warn!("sandbox weakened on request: {} disabled", disabled.join(", "))
+        }
+        match (status.pid, status.errno) {
+            (Some(pid), _) => {
+                info!("started sandboxed child {} with {}", pid, mechanisms.join(", "))
+            }
+            (None, errno) => warn!("failed to start sandboxed child (errno {:?})", errno),
+        }
+        if !status.timings.is_empty() {
+            let timings: Vec<String> = status.timings.iter().map(|&(stage, duration)| {
+                format!("{} {:?}", stage.name(), duration)
+            }).collect();
+            debug!("sandbox activation took {}", timings.join(", "))
+        }
+    }
+
+    fn degradation(&self, missing: &[Mechanism]) {
+        let missing: Vec<String> = missing.iter().map(|mechanism| mechanism.to_string()).collect();
+        warn!("sandbox degraded: {} unavailable", missing.join(", "))
+    }
+
+    fn violation(&self, violation: &Violation) {
+        match violation.syscall {
+            Some(ref syscall) => {
+                warn!("sandboxed child {} was killed for calling {}", violation.pid, syscall)
+            }
+            None => {
+                warn!("sandboxed child {} was killed by signal {} for a violation",
+                      violation.pid,
+                      violation.signal)
+            }
+        }
+    }
+
+    fn child_exit(&self, status: &ExitStatus, rusage: Option<&ResourceUsage>) {
+        match rusage {
+            Some(rusage) => {
+                info!("sandboxed child exited with {:?} after {:?} user and {:?} system time",
+                      status,
+                      rusage.user_time,
+                      rusage.system_time)
+            }
+            None => info!("sandboxed child exited with {:?}", status),
+        }
+    }
+}
+
+/// Where a sandbox and the processes it starts report outcomes. Does nothing unless telemetry was
+/// configured.
+#[derive(Clone, Default)]
+pub(crate) struct TelemetrySink {
+    telemetry: Option<Arc<dyn SandboxTelemetry>>,
+}
+
+impl TelemetrySink {
+    pub fn new(telemetry: Arc<dyn SandboxTelemetry>) -> TelemetrySink {
+        TelemetrySink {
+            telemetry: Some(telemetry),
+        }
+    }
+
+    /// Whether outcomes are reported anywhere, so that callers can skip computing them.
+    pub fn is_enabled(&self) -> bool {
+        self.telemetry.is_some()
+    }
+
+    /// Reports the start of a child that was meant to use `mechanisms`, of which `missing` are
+    /// unavailable. The options turned off the `disabled` ones.
+    pub fn started(&self,
+                   pid: Option<pid_t>,
+                   errno: Option<i32>,
+                   mut mechanisms: Vec<Mechanism>,
+                   disabled: &[Mechanism],
+                   missing: &[Mechanism],
+                   timings: &[(Stage, Duration)]) {
+        if let Some(ref telemetry) = self.telemetry {
+            if !missing.is_empty() {
+                telemetry.degradation(missing);
+                mechanisms.retain(|mechanism| !missing.contains(mechanism));
+            }
+            telemetry.activation(&ActivationStatus {
+                pid,
+                errno,
+                mechanisms,
+                disabled: disabled.to_vec(),
+                timings: timings.to_vec(),
+            })
+        }
+    }
+
+    /// Reports how a child exited.
+    pub fn exited(&self, pid: pid_t, status: ExitStatus, rusage: Option<&ResourceUsage>) {
+        if let Some(ref telemetry) = self.telemetry {
+            if let ExitStatus::Signal(signal) = status {
+                if status.is_violation() {
+                    telemetry.violation(&Violation {
+                        pid,
+                        signal,
+                        syscall: None,
+                    })
+                }
+            }
+            telemetry.child_exit(&status, rusage)
+        }
+    }
+}
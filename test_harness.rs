@@ -0,0 +1,248 @@
+// Copyright 2015 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Support code for integration tests that re-execute themselves inside a sandbox.
+//!
+//! Every sandbox test follows the same pattern: the parent spawns the current executable inside
+//! a sandbox, the child activates the profile the parent passed it and performs some operation,
+//! and the parent checks whether the child exited successfully. This module handles the re-exec
+//! plumbing. A test calls `run_in_sandbox()` once per scenario from `main()`; in the parent that
+//! spawns a child and returns its exit status, and in the re-executed child it runs the matching
+//! closure under the sandbox and exits. Only the parent's profile is used, so the child's copy
+//! of the policy can't drift from it.
+//!
+//! Because the child runs `main()` from the top, anything the child needs that the parent
+//! computed at runtime (for example, the name of a temporary file) must be obtained through
+//! `shared_value()` or `temp_file()` so that both processes agree on it.
+
+use crate::platform::capabilities::{self, ProbeResults};
+use crate::platform::process::ExitStatus;
+use crate::profile::Profile;
+use crate::sandbox::{ChildSandbox, ChildSandboxMethods, Command, Sandbox, SandboxMethods};
+use crate::sandbox::SandboxOptions;
+
+use std::cell::RefCell;
+use std::env;
+use std::ffi::OsString;
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The environment variable naming the entry point the re-executed child should run.
+const CHILD_ENV_VAR: &str = "GAOL_TEST_HARNESS_CHILD";
+
+/// The prefix of environment variables that carry shared values from the parent to the child.
+const SHARED_ENV_PREFIX: &str = "GAOL_TEST_HARNESS_SHARED_";
+
+/// The exit code with which a child reports that its closure ran to completion.
+const CHILD_SUCCESS_CODE: i32 = 0;
+
+thread_local! {
+    static SHARED_VALUES: RefCell<Vec<(OsString, OsString)>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Returns the name of the entry point this process was re-executed to run, if this is a child.
+pub fn child_name() -> Option<String> {
+    env::var(CHILD_ENV_VAR).ok()
+}
+
+/// Returns true if this process is a re-executed child rather than the test's parent process.
+pub fn in_child() -> bool {
+    child_name().is_some()
+}
+
+/// Makes `platform::capabilities()` report what `probes` says instead of probing the machine, for
+/// testing how sandboxes cope without a feature. Call it first thing in `main()`, so that the
+/// parent and its children agree; it panics if the capabilities were already probed.
+pub fn fake_probes(probes: ProbeResults) {
+    assert!(capabilities::set_probe_results(probes), "the capabilities were already probed")
+}
+
+/// Returns a value computed once in the parent and passed to every child it spawns.
+///
+/// In the parent, `init` is called and its result is recorded so that subsequent calls to
+/// `run_in_sandbox()` export it. In a child, `init` is not called; the value is read back from
+/// the environment instead.
+pub fn shared_value<F>(key: &str, init: F) -> OsString where F: FnOnce() -> OsString {
+    let env_key = format!("{}{}", SHARED_ENV_PREFIX, key);
+    if in_child() {
+        return env::var_os(&env_key).expect("shared value was not passed to the child")
+    }
+    let value = init();
+    SHARED_VALUES.with(|shared_values| {
+        shared_values.borrow_mut().push((OsString::from(env_key), value.clone()))
+    });
+    value
+}
+
+/// Creates a temporary file with the given contents in the parent and returns its canonical
+/// path. In a child, the path of the parent's file is returned and nothing is created.
+///
+/// The path is canonicalized because the temporary directory is often a symlink (for example, on
+/// Mac OS X), and sandbox profiles match against resolved paths.
+pub fn temp_file(key: &str, contents: &[u8]) -> PathBuf {
+    PathBuf::from(shared_value(key, || {
+        let path = unique_temp_path("gaoltest");
+        File::create(&path).unwrap().write_all(contents).unwrap();
+        path.into_os_string()
+    }))
+}
+
+/// Creates an empty temporary directory in the parent and returns its canonical path. In a
+/// child, the path of the parent's directory is returned and nothing is created.
+pub fn temp_dir(key: &str) -> PathBuf {
+    PathBuf::from(shared_value(key, || {
+        let path = unique_temp_path("gaoltest-dir");
+        fs::create_dir(&path).unwrap();
+        path.into_os_string()
+    }))
+}
+
+fn unique_temp_path(prefix: &str) -> PathBuf {
+    let mut path = fs::canonicalize(env::temp_dir()).unwrap();
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().subsec_nanos();
+    path.push(format!("{}.{}.{}", prefix, process::id(), nanos));
+    path
+}
+
+/// Compares `actual` against the contents of the golden file at `path`, panicking with both
+/// versions if they differ.
+///
+/// If the `GAOL_BLESS` environment variable is set to `1`, the golden file is (re)written with
+/// `actual` instead. Use this to accept an intentional change, then review the resulting diff.
+pub fn assert_golden(path: &Path, actual: &str) {
+    if env::var("GAOL_BLESS").ok().as_ref().map(|value| &value[..]) == Some("1") {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        File::create(path).unwrap().write_all(actual.as_bytes()).unwrap();
+        return
+    }
+
+    let mut expected = String::new();
+    if File::open(path).and_then(|mut file| file.read_to_string(&mut expected)).is_err() {
+        panic!("missing golden file {}; rerun with GAOL_BLESS=1 to create it", path.display())
+    }
+    if expected != actual {
+        panic!("{} is out of date; rerun with GAOL_BLESS=1 if this change is intended\n\
+                --- expected\n{}\n--- actual\n{}",
+               path.display(),
+               expected,
+               actual)
+    }
+}
+
+/// Runs `child_fn` inside a sandbox with the given profile and returns the exit status.
+///
+/// In the parent, this spawns the current executable with `Sandbox::start()`, passing along the
+/// entry point name and every shared value, and waits for it. In the child whose entry point is
+/// `name`, this activates a `ChildSandbox` with the profile the parent passed it (see
+/// `ChildSandbox::from_environment()`), runs `child_fn`, and exits with status zero if it
+/// returns; this function never returns in that case. A panic in `child_fn` (or a failed
+/// activation) makes the child exit unsuccessfully.
+///
+/// In a child whose entry point is a different name, this does nothing and returns a successful
+/// status so that the child can proceed to its own entry point. The `assert_allowed!` and
+/// `assert_violation!` macros ignore statuses in children.
+pub fn run_in_sandbox<F>(profile: Profile, name: &str, child_fn: F) -> ExitStatus
+                         where F: FnOnce() {
+    run_in_sandbox_with_hook(profile, name, || {}, child_fn)
+}
+
+/// Like `run_in_sandbox()`, but the child activates its sandbox with
+/// `ChildSandboxMethods::activate_with_hook()`, running `hook` partway through.
+pub fn run_in_sandbox_with_hook<H,F>(profile: Profile, name: &str, hook: H, child_fn: F)
+                                     -> ExitStatus
+                                     where H: FnOnce(), F: FnOnce() {
+    run_unactivated_in_sandbox(profile, name, || {
+        ChildSandbox::from_environment().unwrap().activate_with_hook(hook).unwrap();
+        child_fn()
+    })
+}
+
+/// Like `run_in_sandbox()`, but the parent starts the child with a sandbox configured by
+/// `options`.
+pub fn run_in_sandbox_with_options<F>(profile: Profile,
+                                      options: SandboxOptions,
+                                      name: &str,
+                                      child_fn: F)
+                                      -> ExitStatus
+                                      where F: FnOnce() {
+    run_in_given_sandbox(Sandbox::with_options(profile, options), name, child_fn)
+}
+
+/// Like `run_in_sandbox()`, but the parent starts the child in `sandbox`.
+pub fn run_in_given_sandbox<F>(sandbox: Sandbox, name: &str, child_fn: F) -> ExitStatus
+                               where F: FnOnce() {
+    spawn_in_sandbox(sandbox, name, || {
+        ChildSandbox::from_environment().unwrap().activate().unwrap();
+        child_fn()
+    })
+}
+
+/// Like `run_in_sandbox()`, but the child doesn't activate a `ChildSandbox` before running
+/// `child_fn`. Use this to test activation itself.
+pub fn run_unactivated_in_sandbox<F>(profile: Profile, name: &str, child_fn: F) -> ExitStatus
+                                     where F: FnOnce() {
+    spawn_in_sandbox(Sandbox::new(profile), name, child_fn)
+}
+
+fn spawn_in_sandbox<F>(sandbox: Sandbox, name: &str, child_fn: F) -> ExitStatus
+                       where F: FnOnce() {
+    match child_name() {
+        Some(ref child_name) if child_name == name => {
+            child_fn();
+            process::exit(CHILD_SUCCESS_CODE)
+        }
+        Some(_) => return ExitStatus::Code(CHILD_SUCCESS_CODE),
+        None => {}
+    }
+
+    let mut command = Command::me().unwrap();
+    command.env(CHILD_ENV_VAR, name).env("RUST_BACKTRACE", "1");
+    SHARED_VALUES.with(|shared_values| {
+        for (key, value) in shared_values.borrow().iter() {
+            command.env(key, value);
+        }
+    });
+    sandbox.start(&mut command).unwrap().wait().unwrap()
+}
+
+/// Asserts that a child run via `run_in_sandbox()` exited successfully, meaning that every
+/// operation it attempted was allowed.
+#[macro_export]
+macro_rules! assert_allowed {
+    ($status:expr) => {{
+        let status: $crate::platform::process::ExitStatus = $status;
+        if !$crate::test_harness::in_child() {
+            assert!(status.success(), "expected the child to succeed, but it exited with {:?}",
+                    status);
+        }
+    }}
+}
+
+/// Asserts that a child run via `run_in_sandbox()` failed, meaning that the sandbox prohibited
+/// an operation it attempted, either by making it fail or by terminating the child. If an
+/// expected `ExitStatus` is supplied, the child must have exited with exactly that status.
+#[macro_export]
+macro_rules! assert_violation {
+    ($status:expr) => {{
+        let status: $crate::platform::process::ExitStatus = $status;
+        if !$crate::test_harness::in_child() {
+            assert!(!status.success(), "expected the sandbox to stop the child, but it succeeded");
+        }
+    }};
+    ($status:expr, $expected:expr) => {{
+        let status: $crate::platform::process::ExitStatus = $status;
+        if !$crate::test_harness::in_child() {
+            assert_eq!(status, $expected, "the child was not stopped in the expected way");
+        }
+    }}
+}
@@ -0,0 +1,44 @@
+// Any copyright is dedicated to the Public Domain.
+// http://creativecommons.org/publicdomain/zero/1.0/
+
+#[macro_use]
+extern crate gaol;
+extern crate libc;
+
+use gaol::platform::process::ExitStatus;
+use gaol::profile::Profile;
+use gaol::sandbox::ActivationError;
+use gaol::test_harness;
+use std::cell::Cell;
+use std::io;
+use std::ptr;
+
+fn profile() -> Profile {
+    Profile::new(Vec::new()).unwrap()
+}
+
+fn assert_no_children_remain() {
+    let result = unsafe {
+        libc::waitpid(-1, ptr::null_mut(), libc::WNOHANG)
+    };
+    assert_eq!(result, -1, "an unreaped child remains");
+    assert_eq!(io::Error::last_os_error().raw_os_error(), Some(libc::ECHILD));
+}
+
+pub fn main() {
+    let hook_ran = Cell::new(false);
+    assert_allowed!(test_harness::run_in_sandbox_with_hook(profile(), "hook", || {
+        hook_ran.set(true)
+    }, || {
+        assert!(hook_ran.get())
+    }));
+
+    let status = test_harness::run_in_sandbox_with_hook(profile(), "panicking_hook", || {
+        panic!("deliberate panic in a pre-activation hook")
+    }, || {});
+    if !test_harness::in_child() {
+        assert!(matches!(status.activation_error(), Some(ActivationError::HookPanicked)));
+        assert_eq!(ActivationError::HookPanicked.exit_code().map(ExitStatus::Code), Some(status));
+        assert_no_children_remain();
+    }
+}
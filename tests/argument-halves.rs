@@ -0,0 +1,74 @@
+// Any copyright is dedicated to the Public Domain.
+// http://creativecommons.org/publicdomain/zero/1.0/
+
+//! The filter compares both 32-bit halves of the arguments it inspects, so that bits set in the
+//! high half can't slip a call past a check on the low one.
+
+#[cfg(target_os="linux")]
+#[macro_use]
+extern crate gaol;
+#[cfg(target_os="linux")]
+extern crate libc;
+
+#[cfg(target_os="linux")]
+use gaol::platform::linux::seccomp::Filter;
+#[cfg(target_os="linux")]
+use gaol::profile::{AddressPattern, Operation, PathPattern, Profile};
+#[cfg(target_os="linux")]
+use gaol::test_harness;
+#[cfg(target_os="linux")]
+use std::path::PathBuf;
+
+#[cfg(target_os="linux")]
+const HIGH_BIT: u64 = 1 << 32;
+
+#[cfg(target_os="linux")]
+fn read_profile() -> Profile {
+    Profile::new(vec![
+        Operation::FileReadAll(PathPattern::Subpath(PathBuf::from("/usr"))),
+    ]).unwrap()
+}
+
+#[cfg(target_os="linux")]
+fn test_generated_program() {
+    let read = Filter::new(&read_profile());
+    let openat = |flags: u64| [libc::AT_FDCWD as u64, 0, flags, 0, 0, 0];
+    let openat_syscall = libc::SYS_openat as u32;
+    assert!(read.allows(openat_syscall, &openat(libc::O_RDONLY as u64)));
+    assert!(!read.allows(openat_syscall, &openat(libc::O_RDONLY as u64 | HIGH_BIT)));
+
+    let ioctl = |request: u64| [0, request, 0, 0, 0, 0];
+    assert!(read.allows(libc::SYS_ioctl as u32, &ioctl(libc::FIONREAD)));
+    assert!(!read.allows(libc::SYS_ioctl as u32, &ioctl(libc::FIONREAD | HIGH_BIT)));
+
+    let network = Filter::new(&Profile::new(vec![
+        Operation::NetworkOutbound(AddressPattern::All),
+    ]).unwrap());
+    let socket = |family: u64| [family, libc::SOCK_STREAM as u64, 0, 0, 0, 0];
+    assert!(network.allows(libc::SYS_socket as u32, &socket(libc::AF_INET as u64)));
+    assert!(!network.allows(libc::SYS_socket as u32, &socket(libc::AF_INET as u64 | HIGH_BIT)));
+}
+
+/// The kernel reads `fcntl()`'s command as 32 bits, so without the check on the high half this
+/// would be an allowed `F_GETFD`.
+#[cfg(target_os="linux")]
+fn fcntl(command: u64) {
+    unsafe {
+        libc::syscall(libc::SYS_fcntl, 0, command);
+    }
+}
+
+#[cfg(target_os="linux")]
+pub fn main() {
+    test_generated_program();
+
+    assert_allowed!(test_harness::run_in_sandbox(read_profile(), "low_half_test", || {
+        fcntl(libc::F_GETFD as u64)
+    }));
+    assert_violation!(test_harness::run_in_sandbox(read_profile(), "high_half_test", || {
+        fcntl(libc::F_GETFD as u64 | HIGH_BIT)
+    }));
+}
+
+#[cfg(not(target_os="linux"))]
+pub fn main() {}
@@ -0,0 +1,71 @@
+// Any copyright is dedicated to the Public Domain.
+// http://creativecommons.org/publicdomain/zero/1.0/
+
+//! Children started with `start_async()` are confined like any other, and a Tokio runtime can
+//! wait for several of them at once.
+
+#[cfg(target_os="linux")]
+extern crate libc;
+
+#[cfg(target_os="linux")]
+use gaol::platform::process::ExitStatus;
+#[cfg(target_os="linux")]
+use gaol::profile::{Operation, PathPattern, Profile};
+#[cfg(target_os="linux")]
+use gaol::sandbox::{ChildSandbox, ChildSandboxMethods, Command, Sandbox, SandboxMethods};
+#[cfg(target_os="linux")]
+use gaol::test_harness;
+#[cfg(target_os="linux")]
+use std::env;
+#[cfg(target_os="linux")]
+use std::fs;
+#[cfg(target_os="linux")]
+use std::path::PathBuf;
+#[cfg(target_os="linux")]
+use std::process;
+
+/// Names what the re-executed child should do.
+#[cfg(target_os="linux")]
+const CHILD_ENV_VAR: &str = "GAOL_ASYNC_CHILD";
+/// The file the child may read.
+#[cfg(target_os="linux")]
+const PATH_ENV_VAR: &str = "GAOL_ASYNC_PATH";
+
+#[cfg(target_os="linux")]
+pub fn main() {
+    if let Ok(action) = env::var(CHILD_ENV_VAR) {
+        let path = PathBuf::from(env::var_os(PATH_ENV_VAR).unwrap());
+        ChildSandbox::from_environment().unwrap().activate().unwrap();
+        match &*action {
+            "read" => assert_eq!(fs::read(&path).unwrap(), b"super secret\n"),
+            _ => unsafe {
+                libc::socket(libc::AF_INET, libc::SOCK_STREAM, 0);
+            },
+        }
+        process::exit(0)
+    }
+
+    let path = test_harness::temp_file("GAOL_TEMP_FILE", b"super secret\n");
+    let sandbox = Sandbox::new(Profile::new(vec![
+        Operation::FileReadAll(PathPattern::Literal(path.clone())),
+    ]).unwrap());
+    let start = |action: &str| {
+        let mut command = Command::me().unwrap();
+        command.env(CHILD_ENV_VAR, action).env(PATH_ENV_VAR, &path);
+        sandbox.start_async(&mut command).unwrap()
+    };
+    let reader = start("read");
+    let connector = start("connect");
+
+    let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+    let (reader, connector) = runtime.block_on(async {
+        let reader = tokio::spawn(reader.wait());
+        let connector = tokio::spawn(connector.wait());
+        (reader.await.unwrap().unwrap(), connector.await.unwrap().unwrap())
+    });
+    assert_eq!(reader, ExitStatus::Code(0));
+    assert_eq!(connector, ExitStatus::Signal(libc::SIGSYS));
+}
+
+#[cfg(not(target_os="linux"))]
+pub fn main() {}
@@ -0,0 +1,88 @@
+// Any copyright is dedicated to the Public Domain.
+// http://creativecommons.org/publicdomain/zero/1.0/
+
+#[cfg(target_os="linux")]
+extern crate gaol;
+#[cfg(target_os="linux")]
+extern crate libc;
+
+#[cfg(target_os="linux")]
+use gaol::platform::linux::seccomp::Filter;
+#[cfg(target_os="linux")]
+use gaol::platform::linux::{AuditSandbox, Sandbox};
+#[cfg(target_os="linux")]
+use gaol::platform::process::ExitStatus;
+#[cfg(target_os="linux")]
+use gaol::platform::capabilities;
+#[cfg(target_os="linux")]
+use gaol::profile::Profile;
+#[cfg(target_os="linux")]
+use gaol::sandbox::{ChildSandbox, ChildSandboxMethods, Command, SandboxMethods};
+#[cfg(target_os="linux")]
+use gaol::sandbox::SandboxOptions;
+#[cfg(target_os="linux")]
+use std::env;
+#[cfg(target_os="linux")]
+use std::io;
+
+/// Set in the child's environment.
+#[cfg(target_os="linux")]
+const AUDIT_CHILD_VAR: &str = "GAOL_AUDIT_CHILD";
+
+/// Creates a socket, which the profile prohibits.
+#[cfg(target_os="linux")]
+fn child() {
+    ChildSandbox::from_environment().unwrap().activate().unwrap();
+    let fd = unsafe {
+        libc::socket(libc::AF_INET, libc::SOCK_STREAM, 0)
+    };
+    assert!(fd >= 0);
+}
+
+#[cfg(target_os="linux")]
+fn profile() -> Profile {
+    Profile::new(Vec::new()).unwrap()
+}
+
+#[cfg(target_os="linux")]
+pub fn main() {
+    if env::var_os(AUDIT_CHILD_VAR).is_some() {
+        return child()
+    }
+
+    // Audited, the violation is let through.
+    let mut command = Command::me().unwrap();
+    command.env(AUDIT_CHILD_VAR, "1");
+    let sandbox = AuditSandbox::new(Sandbox::new(profile()));
+    assert_eq!(sandbox.start(&mut command).unwrap().wait().unwrap(), ExitStatus::Code(0));
+
+    // Enforced, the same child is killed.
+    let mut command = Command::me().unwrap();
+    command.env(AUDIT_CHILD_VAR, "1");
+    let sandbox = Sandbox::new(profile());
+    assert_eq!(sandbox.start(&mut command).unwrap().wait().unwrap(),
+               ExitStatus::Signal(libc::SIGSYS));
+
+    // Auditing without the filter would audit nothing.
+    let mut options = SandboxOptions::new();
+    options.disable_seccomp().acknowledge_weakened_sandbox();
+    let sandbox = AuditSandbox::new(Sandbox::with_options(profile(), options));
+    let mut command = Command::me().unwrap();
+    assert_eq!(sandbox.start(&mut command).err().map(|error| error.kind()),
+               Some(io::ErrorKind::InvalidInput));
+
+    const SECCOMP_RET_ALLOW: u32 = 0x7fff_0000;
+    const SECCOMP_RET_LOG: u32 = 0x7ffc_0000;
+    let auditing = Filter::auditing(&profile());
+    let socket_args = [libc::AF_INET as u64, libc::SOCK_STREAM as u64, 0, 0, 0, 0];
+    let expected = if capabilities().seccomp_ret_log {
+        SECCOMP_RET_LOG
+    } else {
+        SECCOMP_RET_ALLOW
+    };
+    assert_eq!(auditing.evaluate(libc::SYS_socket as u32, &socket_args), expected);
+    assert_eq!(auditing.evaluate(libc::SYS_read as u32, &[0; 6]), SECCOMP_RET_ALLOW);
+}
+
+#[cfg(not(target_os="linux"))]
+pub fn main() {}
@@ -0,0 +1,96 @@
+// Any copyright is dedicated to the Public Domain.
+// http://creativecommons.org/publicdomain/zero/1.0/
+
+//! The filter finds system calls by binary search, and must decide every one of them as the
+//! chain of comparisons it replaced does.
+
+#[cfg(target_os="linux")]
+extern crate gaol;
+#[cfg(target_os="linux")]
+extern crate libc;
+
+#[cfg(target_os="linux")]
+use gaol::platform::linux::seccomp::Filter;
+#[cfg(target_os="linux")]
+use gaol::profile::{AddressPattern, Operation, PathPattern, Profile, ViolationAction};
+#[cfg(target_os="linux")]
+use std::path::PathBuf;
+
+/// Values that the rules inspect arguments for, and a few that they don't.
+#[cfg(target_os="linux")]
+fn argument_values() -> Vec<u64> {
+    vec![
+        0,
+        1,
+        libc::AF_INET as u64,
+        libc::AF_NETLINK as u64,
+        libc::SOCK_DGRAM as u64,
+        libc::SOL_SOCKET as u64,
+        libc::O_APPEND as u64,
+        libc::O_WRONLY as u64 | libc::O_APPEND as u64,
+        libc::O_CREAT as u64,
+        libc::AT_EMPTY_PATH as u64,
+        libc::FIONREAD,
+        libc::F_GETFD as u64,
+        libc::MADV_DONTNEED as u64,
+        libc::MADV_FREE as u64,
+        (libc::CLONE_VM | libc::CLONE_FS | libc::CLONE_FILES | libc::CLONE_SIGHAND |
+         libc::CLONE_THREAD | libc::CLONE_SYSVSEM | libc::CLONE_SETTLS |
+         libc::CLONE_PARENT_SETTID | libc::CLONE_CHILD_CLEARTID) as u64,
+        1 << 32,
+        !0,
+    ]
+}
+
+#[cfg(target_os="linux")]
+fn profiles() -> Vec<Profile> {
+    vec![
+        Profile::new(Vec::new()).unwrap(),
+        Profile::new(vec![
+            Operation::FileReadAll(PathPattern::Subpath(PathBuf::from("/usr"))),
+            Operation::FileAppend(PathPattern::Subpath(PathBuf::from("/var/log"))),
+            Operation::SystemInfoRead,
+        ]).unwrap(),
+        Profile::new(vec![
+            Operation::FileReadAll(PathPattern::Subpath(PathBuf::from("/usr"))),
+            Operation::FileWriteAll(PathPattern::Subpath(PathBuf::from("/var/tmp"))),
+            Operation::ProcessExec(PathPattern::Literal(PathBuf::from("/usr/bin/ffprobe"))),
+            Operation::NetworkInbound(AddressPattern::All),
+        ]).unwrap().with_violation_action(ViolationAction::Errno(libc::EACCES)),
+        Profile::new(vec![
+            Operation::NetworkOutbound(AddressPattern::Udp(53)),
+        ]).unwrap(),
+    ]
+}
+
+#[cfg(target_os="linux")]
+pub fn main() {
+    let values = argument_values();
+    for profile in profiles() {
+        let tree = Filter::new(&profile);
+        let chain = Filter::linear(&profile);
+        for syscall in 0..512 {
+            // Each pair of inspected arguments is tried with each pair of values, with the
+            // others zero.
+            for first in 0..4 {
+                for second in first + 1..4 {
+                    for &first_value in &values {
+                        for &second_value in &values {
+                            let mut args = [0; 6];
+                            args[first] = first_value;
+                            args[second] = second_value;
+                            assert_eq!(tree.evaluate(syscall, &args),
+                                       chain.evaluate(syscall, &args),
+                                       "system call {} with {:?}",
+                                       syscall,
+                                       args);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(not(target_os="linux"))]
+pub fn main() {}
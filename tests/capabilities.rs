@@ -0,0 +1,203 @@
+// Any copyright is dedicated to the Public Domain.
+// http://creativecommons.org/publicdomain/zero/1.0/
+
+extern crate gaol;
+extern crate libc;
+
+use gaol::platform::capabilities::{PlatformCapabilities, ProbeResults};
+use std::io;
+
+/// Probe results from a recent kernel with every feature enabled.
+fn everything_probes() -> ProbeResults {
+    ProbeResults {
+        unprivileged_userns_clone: Some("1\n".to_owned()),
+        max_user_namespaces: Some("63400\n".to_owned()),
+        user_namespace: Some(Ok(())),
+        mount_in_user_namespace: Some(Ok(())),
+        time_namespace: true,
+        cgroup_namespace: true,
+        seccomp_mode: Ok(0),
+        seccomp_tsync: Err(libc::EFAULT),
+        seccomp_ret_log: Ok(()),
+        seccomp_user_notif: Ok(()),
+        landlock_abi: Ok(3),
+        macos_version: None,
+    }
+}
+
+fn test_derivation() {
+    let everything = PlatformCapabilities::from_probes(&everything_probes());
+    assert_eq!(everything, PlatformCapabilities {
+        user_namespaces: true,
+        mount_in_user_namespace: true,
+        seccomp_filter: true,
+        time_namespaces: true,
+        cgroup_namespaces: true,
+        seccomp_tsync: true,
+        seccomp_ret_log: true,
+        seccomp_user_notif: true,
+        landlock_abi: Some(3),
+        macos_version: None,
+    });
+
+    // The sysctl disables user namespaces even if the probe child somehow got one.
+    let capabilities = PlatformCapabilities::from_probes(&ProbeResults {
+        unprivileged_userns_clone: Some("0\n".to_owned()),
+        ..everything_probes()
+    });
+    assert!(!capabilities.user_namespaces);
+    assert!(!capabilities.mount_in_user_namespace);
+
+    let capabilities = PlatformCapabilities::from_probes(&ProbeResults {
+        max_user_namespaces: Some("0\n".to_owned()),
+        ..everything_probes()
+    });
+    assert!(!capabilities.user_namespaces);
+
+    // Kernels without the sysctl rely on the probe child alone.
+    let capabilities = PlatformCapabilities::from_probes(&ProbeResults {
+        unprivileged_userns_clone: None,
+        ..everything_probes()
+    });
+    assert!(capabilities.user_namespaces);
+
+    let capabilities = PlatformCapabilities::from_probes(&ProbeResults {
+        user_namespace: Some(Err(libc::EPERM)),
+        mount_in_user_namespace: None,
+        ..everything_probes()
+    });
+    assert!(!capabilities.user_namespaces);
+    assert!(!capabilities.mount_in_user_namespace);
+    assert!(!capabilities.time_namespaces);
+    assert!(!capabilities.cgroup_namespaces);
+
+    let capabilities = PlatformCapabilities::from_probes(&ProbeResults {
+        time_namespace: false,
+        ..everything_probes()
+    });
+    assert!(capabilities.user_namespaces);
+    assert!(!capabilities.time_namespaces);
+
+    let capabilities = PlatformCapabilities::from_probes(&ProbeResults {
+        cgroup_namespace: false,
+        ..everything_probes()
+    });
+    assert!(capabilities.user_namespaces);
+    assert!(!capabilities.cgroup_namespaces);
+
+    let capabilities = PlatformCapabilities::from_probes(&ProbeResults {
+        mount_in_user_namespace: Some(Err(libc::EPERM)),
+        ..everything_probes()
+    });
+    assert!(capabilities.user_namespaces);
+    assert!(!capabilities.mount_in_user_namespace);
+
+    // `EINVAL` means the kernel doesn't know the TSYNC flag.
+    let capabilities = PlatformCapabilities::from_probes(&ProbeResults {
+        seccomp_tsync: Err(libc::EINVAL),
+        ..everything_probes()
+    });
+    assert!(capabilities.seccomp_filter);
+    assert!(!capabilities.seccomp_tsync);
+
+    // Without seccomp nothing seccomp-related is reported, whatever the other probes said.
+    let capabilities = PlatformCapabilities::from_probes(&ProbeResults {
+        seccomp_mode: Err(libc::EINVAL),
+        ..everything_probes()
+    });
+    assert!(!capabilities.seccomp_filter);
+    assert!(!capabilities.seccomp_tsync);
+    assert!(!capabilities.seccomp_ret_log);
+    assert!(!capabilities.seccomp_user_notif);
+
+    let capabilities = PlatformCapabilities::from_probes(&ProbeResults {
+        seccomp_ret_log: Err(libc::EOPNOTSUPP),
+        seccomp_user_notif: Err(libc::EOPNOTSUPP),
+        ..everything_probes()
+    });
+    assert!(!capabilities.seccomp_ret_log);
+    assert!(!capabilities.seccomp_user_notif);
+
+    // Landlock may be compiled in but disabled at boot.
+    for &result in &[Err(libc::ENOSYS), Err(libc::EOPNOTSUPP), Ok(0)] {
+        let capabilities = PlatformCapabilities::from_probes(&ProbeResults {
+            landlock_abi: result,
+            ..everything_probes()
+        });
+        assert_eq!(capabilities.landlock_abi, None);
+    }
+
+    let capabilities = PlatformCapabilities::from_probes(&ProbeResults {
+        macos_version: Some("14.2.1".to_owned()),
+        ..ProbeResults::default()
+    });
+    assert_eq!(capabilities.macos_version, Some("14.2.1".to_owned()));
+}
+
+#[cfg(target_os="linux")]
+fn namespaces() -> Vec<std::path::PathBuf> {
+    ["user", "mnt", "pid", "net", "ipc", "uts"].iter().map(|name| {
+        std::fs::read_link(format!("/proc/self/ns/{}", name)).unwrap()
+    }).collect()
+}
+
+#[cfg(target_os="linux")]
+fn seccomp_status() -> String {
+    let status = std::fs::read_to_string("/proc/self/status").unwrap();
+    status.lines().find(|line| line.starts_with("Seccomp:")).unwrap().to_owned()
+}
+
+/// Probing must leave the calling process exactly as it found it.
+#[cfg(target_os="linux")]
+fn test_probe_is_side_effect_free() {
+    let namespaces_before = namespaces();
+    let seccomp_before = seccomp_status();
+    gaol::platform::capabilities::probe();
+    gaol::platform::capabilities();
+    assert_eq!(namespaces(), namespaces_before);
+    assert_eq!(seccomp_status(), seccomp_before);
+    assert_eq!(seccomp_status().split_whitespace().nth(1), Some("0"));
+}
+
+#[cfg(not(target_os="linux"))]
+fn test_probe_is_side_effect_free() {
+    gaol::platform::capabilities::probe();
+    gaol::platform::capabilities();
+}
+
+/// The reason user namespaces are unavailable names the sysctl that forbids them, or else the
+/// probe child's `errno`.
+fn test_user_namespace_error() {
+    assert!(everything_probes().user_namespace_error().is_none());
+
+    let error = ProbeResults {
+        unprivileged_userns_clone: Some("0\n".to_owned()),
+        ..everything_probes()
+    }.user_namespace_error().unwrap();
+    assert_eq!(error.kind(), io::ErrorKind::PermissionDenied);
+    assert!(error.to_string().contains("kernel.unprivileged_userns_clone"), "{}", error);
+
+    let error = ProbeResults {
+        max_user_namespaces: Some("0\n".to_owned()),
+        ..everything_probes()
+    }.user_namespace_error().unwrap();
+    assert!(error.to_string().contains("user.max_user_namespaces"), "{}", error);
+
+    let error = ProbeResults {
+        user_namespace: Some(Err(libc::EACCES)),
+        mount_in_user_namespace: None,
+        ..everything_probes()
+    }.user_namespace_error().unwrap();
+    assert_eq!(error.raw_os_error(), Some(libc::EACCES));
+
+    assert!(ProbeResults::default().user_namespace_error().is_some());
+}
+
+pub fn main() {
+    test_derivation();
+    test_user_namespace_error();
+    test_probe_is_side_effect_free();
+
+    // The cached report is computed once.
+    assert!(std::ptr::eq(gaol::platform::capabilities(), gaol::platform::capabilities()));
+}
@@ -0,0 +1,81 @@
+// Any copyright is dedicated to the Public Domain.
+// http://creativecommons.org/publicdomain/zero/1.0/
+
+#[cfg(target_os="freebsd")]
+extern crate gaol;
+#[cfg(target_os="freebsd")]
+extern crate libc;
+
+#[cfg(target_os="freebsd")]
+use gaol::platform::process::ExitStatus;
+#[cfg(target_os="freebsd")]
+use gaol::profile::{Operation, PathPattern, Profile};
+#[cfg(target_os="freebsd")]
+use gaol::sandbox::{ChildSandbox, ChildSandboxMethods, Command, Sandbox, SandboxMethods};
+#[cfg(target_os="freebsd")]
+use gaol::test_harness;
+#[cfg(target_os="freebsd")]
+use std::env;
+#[cfg(target_os="freebsd")]
+use std::ffi::CString;
+#[cfg(target_os="freebsd")]
+use std::io::{self, Read, Write};
+#[cfg(target_os="freebsd")]
+use std::os::unix::ffi::OsStrExt;
+#[cfg(target_os="freebsd")]
+use std::path::{Path, PathBuf};
+
+/// The environment variables through which the parent tells the child which file it may read
+/// and which it may not.
+#[cfg(target_os="freebsd")]
+const ALLOWED_VAR: &str = "GAOL_CAPSICUM_ALLOWED";
+#[cfg(target_os="freebsd")]
+const FORBIDDEN_VAR: &str = "GAOL_CAPSICUM_FORBIDDEN";
+
+#[cfg(target_os="freebsd")]
+fn profile(path: &Path) -> Profile {
+    Profile::new(vec![Operation::FileReadAll(PathPattern::Literal(path.to_path_buf()))]).unwrap()
+}
+
+#[cfg(target_os="freebsd")]
+fn errno_of_open(path: &Path) -> i32 {
+    let c_path = CString::new(path.as_os_str().as_bytes()).unwrap();
+    assert_eq!(unsafe { libc::open(c_path.as_ptr(), libc::O_RDONLY) }, -1);
+    io::Error::last_os_error().raw_os_error().unwrap()
+}
+
+/// Reads the allowed file through a descriptor opened before activation, and checks that it
+/// can't be written to and that the other file can't be opened.
+#[cfg(target_os="freebsd")]
+fn child() {
+    let allowed = PathBuf::from(env::var_os(ALLOWED_VAR).unwrap());
+    let forbidden = PathBuf::from(env::var_os(FORBIDDEN_VAR).unwrap());
+    let sandbox = ChildSandbox::from_environment().unwrap();
+    assert_eq!(sandbox.open_path(&forbidden).unwrap_err().kind(), io::ErrorKind::PermissionDenied);
+    let mut file = sandbox.open_path(&allowed).unwrap();
+    sandbox.activate().unwrap();
+
+    let mut contents = String::new();
+    file.read_to_string(&mut contents).unwrap();
+    assert_eq!(contents, "super secret\n");
+    assert_eq!(file.write(b"x").unwrap_err().raw_os_error(), Some(libc::ENOTCAPABLE));
+    assert_eq!(errno_of_open(&allowed), libc::ECAPMODE);
+    assert_eq!(errno_of_open(&forbidden), libc::ECAPMODE);
+}
+
+#[cfg(target_os="freebsd")]
+pub fn main() {
+    if env::var_os(ALLOWED_VAR).is_some() {
+        return child()
+    }
+
+    let allowed = test_harness::temp_file("GAOL_CAPSICUM_ALLOWED_FILE", b"super secret\n");
+    let forbidden = test_harness::temp_file("GAOL_CAPSICUM_FORBIDDEN_FILE", b"top secret\n");
+    let mut command = Command::me().unwrap();
+    command.env(ALLOWED_VAR, &allowed).env(FORBIDDEN_VAR, &forbidden);
+    let process = Sandbox::new(profile(&allowed)).start(&mut command).unwrap();
+    assert_eq!(process.wait().unwrap(), ExitStatus::Code(0));
+}
+
+#[cfg(not(target_os="freebsd"))]
+pub fn main() {}
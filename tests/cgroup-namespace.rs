@@ -0,0 +1,45 @@
+// Any copyright is dedicated to the Public Domain.
+// http://creativecommons.org/publicdomain/zero/1.0/
+
+//! A sandboxed child sees the cgroup it was started in as the root of the hierarchy, not where
+//! that cgroup is on the host.
+
+#[cfg(target_os="linux")]
+#[macro_use]
+extern crate gaol;
+
+#[cfg(target_os="linux")]
+use gaol::platform::capabilities;
+#[cfg(target_os="linux")]
+use gaol::profile::Profile;
+#[cfg(target_os="linux")]
+use gaol::sandbox::{ActivationError, ChildSandbox, ChildSandboxMethods};
+#[cfg(target_os="linux")]
+use gaol::test_harness;
+#[cfg(target_os="linux")]
+use std::fs;
+
+#[cfg(target_os="linux")]
+pub fn main() {
+    let profile = Profile::new(Vec::new()).unwrap();
+    assert_allowed!(test_harness::run_unactivated_in_sandbox(profile, "cgroups", || {
+        // Each line is `hierarchy-ID:controllers:path`, with `0::/` for the unified hierarchy.
+        let cgroups = fs::read_to_string("/proc/self/cgroup").unwrap();
+        let namespaced = cgroups.lines().all(|line| line.ends_with(":/")) &&
+            cgroups.lines().any(|line| line == "0::/");
+        assert_eq!(namespaced, capabilities().cgroup_namespaces, "{}", cgroups);
+
+        // Activation says whether the child got the namespace.
+        let result = ChildSandbox::from_environment().unwrap().activate();
+        if namespaced {
+            result.unwrap()
+        } else {
+            assert!(matches!(result, Err(ActivationError::NoCgroupNamespace)))
+        }
+    }));
+}
+
+#[cfg(not(target_os="linux"))]
+pub fn main() {
+    // Currently unsupported on other platforms.
+}
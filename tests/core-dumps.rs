@@ -0,0 +1,119 @@
+// Any copyright is dedicated to the Public Domain.
+// http://creativecommons.org/publicdomain/zero/1.0/
+
+#[cfg(target_os="linux")]
+#[macro_use]
+extern crate gaol;
+#[cfg(target_os="linux")]
+extern crate libc;
+
+#[cfg(target_os="linux")]
+use gaol::platform::process::ExitStatus;
+#[cfg(target_os="linux")]
+use gaol::profile::Profile;
+#[cfg(target_os="linux")]
+use gaol::sandbox::{Command, CoreDumps, Sandbox, SandboxMethods, SandboxOptions};
+#[cfg(target_os="linux")]
+use gaol::test_harness;
+#[cfg(target_os="linux")]
+use std::env;
+#[cfg(target_os="linux")]
+use std::fs;
+#[cfg(target_os="linux")]
+use std::io;
+#[cfg(target_os="linux")]
+use std::path::{Path, PathBuf};
+#[cfg(target_os="linux")]
+use std::ptr;
+
+/// Returns true if a child could dump core into a directory here: the kernel writes dumps to a
+/// plain file name and the hard limit allows them.
+#[cfg(target_os="linux")]
+fn core_dumps_possible() -> bool {
+    let pattern = fs::read_to_string("/proc/sys/kernel/core_pattern").unwrap_or_default();
+    let mut rlimit = libc::rlimit {
+        rlim_cur: 0,
+        rlim_max: 0,
+    };
+    unsafe {
+        libc::getrlimit(libc::RLIMIT_CORE, &mut rlimit);
+    }
+    !pattern.starts_with('|') && !pattern.starts_with('/') && rlimit.rlim_max != 0
+}
+
+/// Crashes with `SIGSEGV` without making a system call the filter would stop.
+#[cfg(target_os="linux")]
+fn crash() {
+    unsafe {
+        ptr::write_volatile(8 as *mut u8, 0);
+    }
+}
+
+#[cfg(target_os="linux")]
+fn entries(directory: &Path) -> usize {
+    fs::read_dir(directory).unwrap().count()
+}
+
+#[cfg(target_os="linux")]
+fn test_relative_directory_rejected() {
+    let mut options = SandboxOptions::new();
+    options.core_dumps(CoreDumps::EnabledTo(PathBuf::from("cores")));
+    let sandbox = Sandbox::with_options(Profile::new(Vec::new()).unwrap(), options);
+    let err = sandbox.start(&mut Command::new("/bin/true")).err().unwrap();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+}
+
+#[cfg(target_os="linux")]
+fn test_disabled(working_directory: &Path) {
+    // The jail leaves the child in its parent's working directory, where a relative pattern
+    // would put the dump.
+    if !test_harness::in_child() {
+        env::set_current_dir(working_directory).unwrap();
+    }
+    assert_violation!(test_harness::run_in_sandbox(Profile::new(Vec::new()).unwrap(),
+                                                   "disabled",
+                                                   crash),
+                      ExitStatus::Signal(libc::SIGSEGV));
+    if !test_harness::in_child() {
+        assert_eq!(entries(working_directory), 0);
+    }
+}
+
+#[cfg(target_os="linux")]
+fn test_enabled_to(dump_directory: &Path) {
+    let mut options = SandboxOptions::new();
+    options.core_dumps(CoreDumps::EnabledTo(dump_directory.to_path_buf()));
+    assert_violation!(test_harness::run_in_sandbox_with_options(Profile::new(Vec::new()).unwrap(),
+                                                                options,
+                                                                "enabled_to",
+                                                                crash),
+                      ExitStatus::Signal(libc::SIGSEGV));
+    if !test_harness::in_child() {
+        assert_eq!(entries(dump_directory), 1);
+        fs::remove_dir_all(dump_directory).unwrap();
+    }
+}
+
+#[cfg(target_os="linux")]
+pub fn main() {
+    if !core_dumps_possible() {
+        return
+    }
+    // Let the crash take the default action instead of the runtime's stack overflow handler,
+    // which would make system calls the filter doesn't allow.
+    if test_harness::in_child() {
+        unsafe {
+            libc::signal(libc::SIGSEGV, libc::SIG_DFL);
+        }
+    }
+    let working_directory = test_harness::temp_dir("GAOL_WORKING_DIRECTORY");
+    let dump_directory = test_harness::temp_dir("GAOL_DUMP_DIRECTORY");
+    if !test_harness::in_child() {
+        test_relative_directory_rejected();
+    }
+    test_disabled(&working_directory);
+    test_enabled_to(&dump_directory);
+}
+
+#[cfg(not(target_os="linux"))]
+fn main() {}
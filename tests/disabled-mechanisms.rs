@@ -0,0 +1,229 @@
+// Any copyright is dedicated to the Public Domain.
+// http://creativecommons.org/publicdomain/zero/1.0/
+
+#[cfg(target_os="linux")]
+#[macro_use]
+extern crate gaol;
+#[cfg(target_os="linux")]
+extern crate libc;
+
+#[cfg(target_os="linux")]
+use gaol::platform::capabilities;
+#[cfg(target_os="linux")]
+use gaol::platform::process::ExitStatus;
+#[cfg(target_os="linux")]
+use gaol::profile::{Operation, PathPattern, Profile};
+#[cfg(target_os="linux")]
+use gaol::sandbox::{Command, Sandbox, SandboxMethods, SandboxOptions};
+#[cfg(target_os="linux")]
+use gaol::telemetry::{ActivationStatus, Mechanism, ResourceUsage, SandboxTelemetry, Violation};
+#[cfg(target_os="linux")]
+use gaol::test_harness;
+#[cfg(target_os="linux")]
+use std::ffi::CString;
+#[cfg(target_os="linux")]
+use std::io;
+#[cfg(target_os="linux")]
+use std::os::unix::ffi::OsStrExt;
+#[cfg(target_os="linux")]
+use std::path::{Path, PathBuf};
+#[cfg(target_os="linux")]
+use std::sync::{Arc, Mutex};
+
+/// Remembers the last activation status it was told about.
+#[cfg(target_os="linux")]
+#[derive(Default)]
+struct ActivationRecorder {
+    status: Mutex<Option<ActivationStatus>>,
+}
+
+#[cfg(target_os="linux")]
+impl SandboxTelemetry for ActivationRecorder {
+    fn activation(&self, status: &ActivationStatus) {
+        *self.status.lock().unwrap() = Some(status.clone())
+    }
+
+    fn degradation(&self, _: &[Mechanism]) {}
+    fn violation(&self, _: &Violation) {}
+    fn child_exit(&self, _: &ExitStatus, _: Option<&ResourceUsage>) {}
+}
+
+/// Allows reading a directory that doesn't exist, so that the filter allows opening files but
+/// the jail holds nothing.
+#[cfg(target_os="linux")]
+fn file_read_profile() -> Profile {
+    Profile::new(vec![
+        Operation::FileReadAll(PathPattern::Subpath(PathBuf::from("/bogus")))
+    ]).unwrap()
+}
+
+#[cfg(target_os="linux")]
+fn empty_profile() -> Profile {
+    Profile::new(Vec::new()).unwrap()
+}
+
+/// Opens and reads `path` with system calls the filter allows for file reading.
+#[cfg(target_os="linux")]
+fn read_file(path: &Path) -> io::Result<Vec<u8>> {
+    let path = CString::new(path.as_os_str().as_bytes()).unwrap();
+    #[cfg(not(any(target_arch="aarch64", target_arch="riscv64")))]
+    let fd = unsafe {
+        libc::syscall(libc::SYS_open, path.as_ptr(), libc::O_RDONLY | libc::O_CLOEXEC)
+    };
+    #[cfg(any(target_arch="aarch64", target_arch="riscv64"))]
+    let fd = unsafe {
+        libc::syscall(libc::SYS_openat,
+                      libc::AT_FDCWD,
+                      path.as_ptr(),
+                      libc::O_RDONLY | libc::O_CLOEXEC)
+    };
+    if fd < 0 {
+        return Err(io::Error::last_os_error())
+    }
+    let mut buffer = vec![0; 64];
+    let length = unsafe {
+        libc::read(fd as libc::c_int, buffer.as_mut_ptr() as *mut libc::c_void, buffer.len())
+    };
+    unsafe {
+        libc::close(fd as libc::c_int);
+    }
+    buffer.truncate(length.max(0) as usize);
+    Ok(buffer)
+}
+
+#[cfg(target_os="linux")]
+fn test_acknowledgement_required() {
+    let mut options = SandboxOptions::new();
+    options.disable_seccomp();
+    let sandbox = Sandbox::with_options(empty_profile(), options);
+    let err = sandbox.start(&mut Command::new("/bin/true")).err().unwrap();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+}
+
+#[cfg(target_os="linux")]
+fn test_chroot_disabled(path: &Path) {
+    assert_violation!(test_harness::run_in_sandbox(file_read_profile(), "chroot_enabled", || {
+        read_file(path).unwrap();
+    }));
+
+    let recorder = Arc::new(ActivationRecorder::default());
+    let mut options = SandboxOptions::new();
+    options.disable_chroot()
+           .disable_landlock()
+           .acknowledge_weakened_sandbox()
+           .telemetry(recorder.clone());
+    assert_allowed!(test_harness::run_in_sandbox_with_options(file_read_profile(),
+                                                              options,
+                                                              "chroot_disabled",
+                                                              || {
+        assert_eq!(read_file(path).unwrap(), b"super secret\n")
+    }));
+    if !test_harness::in_child() {
+        let status = recorder.status.lock().unwrap().clone().unwrap();
+        assert_eq!(status.disabled, vec![Mechanism::ChrootJail, Mechanism::Landlock]);
+        assert!(!status.mechanisms.contains(&Mechanism::ChrootJail));
+        assert!(!status.mechanisms.contains(&Mechanism::Landlock));
+        assert!(status.mechanisms.contains(&Mechanism::SeccompFilter));
+    }
+}
+
+/// Without the jail, Landlock still keeps the child from reading what the profile doesn't allow,
+/// and lets it read what the profile does.
+#[cfg(target_os="linux")]
+fn test_landlock_without_chroot(path: &Path) {
+    if capabilities().landlock_abi.is_none() {
+        return
+    }
+    let recorder = Arc::new(ActivationRecorder::default());
+    let mut options = SandboxOptions::new();
+    options.disable_chroot().acknowledge_weakened_sandbox().telemetry(recorder.clone());
+    assert_allowed!(test_harness::run_in_sandbox_with_options(file_read_profile(),
+                                                              options.clone(),
+                                                              "landlock_only",
+                                                              || {
+        assert_eq!(read_file(path).unwrap_err().raw_os_error(), Some(libc::EACCES))
+    }));
+    if !test_harness::in_child() {
+        let status = recorder.status.lock().unwrap().clone().unwrap();
+        assert!(status.mechanisms.contains(&Mechanism::Landlock));
+    }
+
+    let profile = Profile::new(vec![
+        Operation::FileReadAll(PathPattern::Literal(path.to_path_buf()))
+    ]).unwrap();
+    assert_allowed!(test_harness::run_in_sandbox_with_options(profile,
+                                                              options,
+                                                              "landlock_only_allowed",
+                                                              || {
+        assert_eq!(read_file(path).unwrap(), b"super secret\n")
+    }));
+}
+
+#[cfg(target_os="linux")]
+fn test_seccomp_disabled() {
+    let create_socket = || {
+        let fd = unsafe {
+            libc::socket(libc::AF_INET, libc::SOCK_STREAM, 0)
+        };
+        assert!(fd >= 0, "{}", io::Error::last_os_error());
+    };
+    assert_violation!(test_harness::run_in_sandbox(empty_profile(),
+                                                   "seccomp_enabled",
+                                                   create_socket),
+                      ExitStatus::Signal(libc::SIGSYS));
+
+    let mut options = SandboxOptions::new();
+    options.disable_seccomp().acknowledge_weakened_sandbox();
+    assert_allowed!(test_harness::run_in_sandbox_with_options(empty_profile(),
+                                                              options,
+                                                              "seccomp_disabled",
+                                                              create_socket));
+}
+
+/// Without namespaces, the child shares the parent's view of the system, and only its filter
+/// confines it.
+#[cfg(target_os="linux")]
+fn test_namespaces_disabled(path: &Path) {
+    let recorder = Arc::new(ActivationRecorder::default());
+    let mut options = SandboxOptions::new();
+    options.disable_namespaces()
+           .disable_landlock()
+           .acknowledge_weakened_sandbox()
+           .telemetry(recorder.clone());
+    assert_allowed!(test_harness::run_in_sandbox_with_options(file_read_profile(),
+                                                              options.clone(),
+                                                              "namespaces_disabled",
+                                                              || {
+        assert_eq!(read_file(path).unwrap(), b"super secret\n")
+    }));
+    if !test_harness::in_child() {
+        let status = recorder.status.lock().unwrap().clone().unwrap();
+        assert!(!status.mechanisms.contains(&Mechanism::Namespaces));
+        assert!(!status.mechanisms.contains(&Mechanism::ChrootJail));
+        assert!(status.mechanisms.contains(&Mechanism::SeccompFilter));
+    }
+
+    assert_violation!(test_harness::run_in_sandbox_with_options(empty_profile(),
+                                                                options,
+                                                                "namespaces_disabled_socket",
+                                                                || {
+        unsafe {
+            libc::socket(libc::AF_INET, libc::SOCK_STREAM, 0);
+        }
+    }), ExitStatus::Signal(libc::SIGSYS));
+}
+
+#[cfg(target_os="linux")]
+pub fn main() {
+    let path = test_harness::temp_file("GAOL_TEMP_FILE", b"super secret\n");
+    if !test_harness::in_child() {
+        test_acknowledgement_required();
+    }
+    test_chroot_disabled(&path);
+    test_landlock_without_chroot(&path);
+    test_seccomp_disabled();
+    test_namespaces_disabled(&path);
+}
+
+#[cfg(not(target_os="linux"))]
+fn main() {}
@@ -0,0 +1,122 @@
+// Any copyright is dedicated to the Public Domain.
+// http://creativecommons.org/publicdomain/zero/1.0/
+
+extern crate gaol;
+extern crate libc;
+extern crate serde_json;
+
+use gaol::event_log::{EventKind, EventRecord, MAX_RECORD_SIZE};
+use gaol::platform::process::ExitStatus;
+use gaol::profile::Profile;
+use gaol::sandbox::{ActivationError, Command, Sandbox, SandboxMethods, SandboxOptions};
+use gaol::telemetry::Mechanism;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::os::fd::{FromRawFd, OwnedFd};
+
+fn record(event: EventKind) -> EventRecord {
+    EventRecord {
+        timestamp_ms: 1_700_000_000_000,
+        ..EventRecord::new(event)
+    }
+}
+
+fn test_schema() {
+    let started = EventRecord {
+        pid: Some(42),
+        stage: Some("start".to_owned()),
+        ..record(EventKind::Started)
+    };
+    assert_eq!(String::from_utf8(started.to_line().unwrap()).unwrap(),
+               "{\"timestamp_ms\":1700000000000,\"pid\":42,\"event\":\"started\",\
+                \"syscall\":null,\"stage\":\"start\",\"errno\":null,\"exit_code\":null,\
+                \"signal\":null,\"disabled\":null,\"timings_us\":null}\n");
+
+    let start_failed = EventRecord {
+        stage: Some("start".to_owned()),
+        errno: Some(libc::EPERM),
+        ..record(EventKind::StartFailed)
+    };
+    assert_eq!(String::from_utf8(start_failed.to_line().unwrap()).unwrap(),
+               "{\"timestamp_ms\":1700000000000,\"pid\":null,\"event\":\"start_failed\",\
+                \"syscall\":null,\"stage\":\"start\",\"errno\":1,\"exit_code\":null,\
+                \"signal\":null,\"disabled\":null,\"timings_us\":null}\n");
+
+    let violation = EventRecord {
+        syscall: Some("ptrace".to_owned()),
+        ..EventRecord::for_exit(42, ExitStatus::Signal(libc::SIGSYS))
+    };
+    assert_eq!(violation.event, EventKind::Violation);
+    assert_eq!(violation.signal, Some(libc::SIGSYS));
+    assert_eq!(violation.stage, Some("wait".to_owned()));
+
+    let exited = EventRecord::for_exit(42, ExitStatus::Code(3));
+    assert_eq!(exited.event, EventKind::Exited);
+    assert_eq!(exited.exit_code, Some(3));
+    assert_eq!(EventRecord::for_exit(42, ExitStatus::Signal(libc::SIGKILL)).event,
+               EventKind::Exited);
+    let hook_panicked = ExitStatus::Code(ActivationError::HookPanicked.exit_code().unwrap());
+    assert_eq!(EventRecord::for_exit(42, hook_panicked).event, EventKind::HookPanicked);
+
+    let weakened = EventRecord {
+        pid: Some(42),
+        disabled: Some(vec![Mechanism::ChrootJail, Mechanism::SeccompFilter]),
+        ..record(EventKind::Started)
+    };
+    assert!(String::from_utf8(weakened.to_line().unwrap()).unwrap()
+                .contains("\"disabled\":[\"chroot_jail\",\"seccomp_filter\"]"));
+
+    // Records round-trip.
+    let line = violation.to_line().unwrap();
+    let parsed: EventRecord = serde_json::from_slice(&line).unwrap();
+    assert_eq!(parsed, violation);
+}
+
+fn test_bounded_size() {
+    let oversized = EventRecord {
+        syscall: Some("x".repeat(MAX_RECORD_SIZE)),
+        stage: Some("wait".to_owned()),
+        ..record(EventKind::Violation)
+    };
+    let line = oversized.to_line().unwrap();
+    assert!(line.len() <= MAX_RECORD_SIZE);
+    assert_eq!(*line.last().unwrap(), b'\n');
+    let parsed: EventRecord = serde_json::from_slice(&line).unwrap();
+    assert_eq!(parsed.syscall, None);
+    assert_eq!(parsed.stage, None);
+    assert_eq!(parsed.event, EventKind::Violation);
+}
+
+fn test_lifecycle() {
+    let mut fds = [0, 0];
+    assert_eq!(unsafe { libc::pipe(fds.as_mut_ptr()) }, 0);
+    let reader = unsafe { File::from_raw_fd(fds[0]) };
+    let writer = unsafe { OwnedFd::from_raw_fd(fds[1]) };
+
+    let mut options = SandboxOptions::new();
+    options.event_log(writer);
+    let sandbox = Sandbox::with_options(Profile::new(Vec::new()).unwrap(), options);
+    let process = sandbox.start(&mut Command::new("/usr/bin/true")).unwrap();
+    let pid = process.pid;
+    assert_eq!(process.wait().unwrap(), ExitStatus::Code(0));
+
+    // Dropping the sandbox closes the last copy of the write end.
+    drop(process);
+    drop(sandbox);
+    let records: Vec<EventRecord> = BufReader::new(reader).lines().map(|line| {
+        serde_json::from_str(&line.unwrap()).unwrap()
+    }).collect();
+    assert_eq!(records.len(), 2);
+    assert_eq!(records[0].event, EventKind::Started);
+    assert_eq!(records[0].pid, Some(pid));
+    assert_eq!(records[1].event, EventKind::Exited);
+    assert_eq!(records[1].pid, Some(pid));
+    assert_eq!(records[1].exit_code, Some(0));
+    assert!(records[0].timestamp_ms <= records[1].timestamp_ms);
+}
+
+pub fn main() {
+    test_schema();
+    test_bounded_size();
+    test_lifecycle();
+}
@@ -0,0 +1,96 @@
+// Any copyright is dedicated to the Public Domain.
+// http://creativecommons.org/publicdomain/zero/1.0/
+
+//! An epoll loop like an async runtime's reactor runs under a profile that allows nothing.
+
+#[cfg(target_os="linux")]
+#[macro_use]
+extern crate gaol;
+#[cfg(target_os="linux")]
+extern crate libc;
+
+#[cfg(target_os="linux")]
+use gaol::profile::Profile;
+#[cfg(target_os="linux")]
+use gaol::test_harness;
+#[cfg(target_os="linux")]
+use libc::c_void;
+#[cfg(target_os="linux")]
+use std::mem;
+#[cfg(target_os="linux")]
+use std::ptr;
+
+#[cfg(target_os="linux")]
+fn profile() -> Profile {
+    Profile::new(Vec::new()).unwrap()
+}
+
+/// Waits on a pipe, an event counter and a timer, and returns the tokens of the ones that are
+/// ready, in order.
+#[cfg(target_os="linux")]
+fn run_event_loop() -> Vec<u64> {
+    unsafe {
+        let epoll = libc::epoll_create1(libc::EPOLL_CLOEXEC);
+        assert!(epoll >= 0);
+        let mut pipe = [-1; 2];
+        assert_eq!(libc::pipe2(pipe.as_mut_ptr(), libc::O_CLOEXEC | libc::O_NONBLOCK), 0);
+        let eventfd = libc::eventfd(0, libc::EFD_CLOEXEC | libc::EFD_NONBLOCK);
+        assert!(eventfd >= 0);
+        let timer = libc::timerfd_create(libc::CLOCK_MONOTONIC, libc::TFD_CLOEXEC);
+        assert!(timer >= 0);
+        for (token, &fd) in [pipe[0], eventfd, timer].iter().enumerate() {
+            let mut event = libc::epoll_event {
+                events: libc::EPOLLIN as u32,
+                u64: token as u64,
+            };
+            assert_eq!(libc::epoll_ctl(epoll, libc::EPOLL_CTL_ADD, fd, &mut event), 0);
+        }
+
+        let mut tokens = Vec::new();
+        let mut events: [libc::epoll_event; 3] = mem::zeroed();
+        assert_eq!(libc::write(pipe[1], b"x".as_ptr() as *const c_void, 1), 1);
+        assert_eq!(libc::epoll_wait(epoll, events.as_mut_ptr(), 3, -1), 1);
+        tokens.push(events[0].u64);
+
+        let mut byte = 0u8;
+        assert_eq!(libc::read(pipe[0], &mut byte as *mut u8 as *mut c_void, 1), 1);
+        let one = 1u64;
+        assert_eq!(libc::write(eventfd, &one as *const u64 as *const c_void, 8), 8);
+        assert_eq!(libc::epoll_pwait(epoll, events.as_mut_ptr(), 3, -1, ptr::null()), 1);
+        tokens.push(events[0].u64);
+
+        let mut count = 0u64;
+        assert_eq!(libc::read(eventfd, &mut count as *mut u64 as *mut c_void, 8), 8);
+        let mut timeout: libc::itimerspec = mem::zeroed();
+        timeout.it_value.tv_nsec = 1_000_000;
+        assert_eq!(libc::timerfd_settime(timer, 0, &timeout, ptr::null_mut()), 0);
+        assert_eq!(libc::epoll_wait(epoll, events.as_mut_ptr(), 3, -1), 1);
+        tokens.push(events[0].u64);
+        tokens
+    }
+}
+
+#[cfg(target_os="linux")]
+pub fn main() {
+    assert_allowed!(test_harness::run_in_sandbox(profile(), "event_loop", || {
+        assert_eq!(run_event_loop(), [0, 1, 2])
+    }));
+
+    // Flags other than close-on-exec and nonblocking are still prohibited.
+    assert_violation!(test_harness::run_in_sandbox(profile(), "packet_pipe", || {
+        let mut pipe = [-1; 2];
+        unsafe {
+            libc::pipe2(pipe.as_mut_ptr(), libc::O_CLOEXEC | libc::O_DIRECT);
+        }
+    }));
+    assert_violation!(test_harness::run_in_sandbox(profile(), "semaphore", || {
+        unsafe {
+            libc::eventfd(0, libc::EFD_SEMAPHORE);
+        }
+    }));
+}
+
+#[cfg(not(target_os="linux"))]
+pub fn main() {
+    // Currently unsupported on other platforms.
+}
@@ -0,0 +1,85 @@
+// Any copyright is dedicated to the Public Domain.
+// http://creativecommons.org/publicdomain/zero/1.0/
+
+#[cfg(target_os="linux")]
+extern crate gaol;
+#[cfg(target_os="linux")]
+extern crate libc;
+
+#[cfg(target_os="linux")]
+use gaol::platform::process::ExitStatus;
+#[cfg(target_os="linux")]
+use gaol::profile::Profile;
+#[cfg(target_os="linux")]
+use gaol::sandbox::{ChildSandbox, ChildSandboxMethods, Command, Sandbox, SandboxMethods};
+#[cfg(target_os="linux")]
+use gaol::test_harness;
+#[cfg(target_os="linux")]
+use std::env;
+#[cfg(target_os="linux")]
+use std::fs::File;
+#[cfg(target_os="linux")]
+use std::io::{self, Read, Write};
+#[cfg(target_os="linux")]
+use std::os::unix::io::{AsRawFd, FromRawFd, RawFd};
+
+/// The environment variables through which the parent tells the child which descriptors to read
+/// its data from and write its results to.
+#[cfg(target_os="linux")]
+const DATA_FD_VAR: &str = "GAOL_FD_ONLY_DATA_FD";
+#[cfg(target_os="linux")]
+const RESULT_FD_VAR: &str = "GAOL_FD_ONLY_RESULT_FD";
+
+#[cfg(target_os="linux")]
+fn fd_from_environment(name: &str) -> RawFd {
+    env::var(name).unwrap().parse().unwrap()
+}
+
+/// Counts the words it is given and tries to open the root directory, reporting both.
+#[cfg(target_os="linux")]
+fn child() {
+    let data_fd = fd_from_environment(DATA_FD_VAR);
+    let result_fd = fd_from_environment(RESULT_FD_VAR);
+    ChildSandbox::from_environment().unwrap().activate().unwrap();
+
+    let mut data = String::new();
+    unsafe { File::from_raw_fd(data_fd) }.read_to_string(&mut data).unwrap();
+    let root = unsafe {
+        libc::open(b"/\0".as_ptr() as *const libc::c_char, libc::O_RDONLY)
+    };
+    assert_eq!(root, -1);
+    let errno = io::Error::last_os_error().raw_os_error().unwrap();
+    let mut results = unsafe { File::from_raw_fd(result_fd) };
+    write!(results, "{} words, errno {}", data.split_whitespace().count(), errno).unwrap();
+}
+
+#[cfg(target_os="linux")]
+pub fn main() {
+    if env::var_os(RESULT_FD_VAR).is_some() {
+        return child()
+    }
+
+    let path = test_harness::temp_file("GAOL_TEMP_FILE", b"the quick brown fox\n");
+    let data = File::open(&path).unwrap();
+    let mut pipe_fds = [0; 2];
+    assert_eq!(unsafe { libc::pipe2(pipe_fds.as_mut_ptr(), libc::O_CLOEXEC) }, 0);
+    let (mut results, results_writer) = unsafe {
+        (File::from_raw_fd(pipe_fds[0]), File::from_raw_fd(pipe_fds[1]))
+    };
+
+    let mut command = Command::me().unwrap();
+    command.env(DATA_FD_VAR, data.as_raw_fd().to_string())
+           .env(RESULT_FD_VAR, results_writer.as_raw_fd().to_string())
+           .inherit_fd(&data)
+           .inherit_fd(&results_writer);
+    let process = Sandbox::new(Profile::fd_only()).start(&mut command).unwrap();
+    drop(results_writer);
+
+    let mut output = String::new();
+    results.read_to_string(&mut output).unwrap();
+    assert_eq!(process.wait().unwrap(), ExitStatus::Code(0));
+    assert_eq!(output, format!("4 words, errno {}", libc::ENOENT));
+}
+
+#[cfg(not(target_os="linux"))]
+pub fn main() {}
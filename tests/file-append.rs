@@ -0,0 +1,85 @@
+// Any copyright is dedicated to the Public Domain.
+// http://creativecommons.org/publicdomain/zero/1.0/
+
+#[macro_use]
+extern crate gaol;
+extern crate libc;
+
+use gaol::profile::{Operation, PathPattern, Profile, ProfileError};
+use gaol::test_harness;
+use std::ffi::CString;
+use std::fs;
+use std::io;
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
+
+fn append_profile(dir: &Path) -> Result<Profile,ProfileError> {
+    Profile::new(vec![
+        Operation::FileAppend(PathPattern::Subpath(dir.to_path_buf())),
+    ])
+}
+
+/// Opens `path` with `flags`, without the `openat` that the standard library uses, which the
+/// Linux filter doesn't allow.
+fn open(path: &Path, flags: libc::c_int) -> libc::c_int {
+    let c_path = CString::new(path.as_os_str().as_bytes()).unwrap();
+    unsafe {
+        #[cfg(all(any(target_os="android", target_os="linux"),
+                  not(any(target_arch="aarch64", target_arch="riscv64"))))]
+        let fd = libc::syscall(libc::SYS_open, c_path.as_ptr(), flags, 0o600) as libc::c_int;
+        #[cfg(all(any(target_os="android", target_os="linux"),
+                  any(target_arch="aarch64", target_arch="riscv64")))]
+        let fd = libc::syscall(libc::SYS_openat, libc::AT_FDCWD, c_path.as_ptr(), flags, 0o600)
+            as libc::c_int;
+        #[cfg(not(any(target_os="android", target_os="linux")))]
+        let fd = libc::open(c_path.as_ptr(), flags, 0o600);
+        assert!(fd >= 0, "couldn't open {}: {}", path.display(), io::Error::last_os_error());
+        fd
+    }
+}
+
+/// Appends to `path` in the usual ways, and checks that it can't be cut short.
+fn append_to_file(path: &Path) {
+    let fd = open(path, libc::O_WRONLY | libc::O_CREAT | libc::O_APPEND);
+    unsafe {
+        assert_eq!(libc::write(fd, b"second\n".as_ptr() as *const libc::c_void, 7), 7);
+        let iovec = libc::iovec {
+            iov_base: b"third\n".as_ptr() as *mut libc::c_void,
+            iov_len: 6,
+        };
+        assert_eq!(libc::writev(fd, &iovec, 1), 6);
+        assert_eq!(libc::ftruncate(fd, 0), -1);
+        assert_eq!(io::Error::last_os_error().raw_os_error(), Some(libc::EPERM));
+        libc::close(fd);
+    }
+}
+
+pub fn main() {
+    let dir = test_harness::temp_dir("GAOL_TEMP_DIR");
+    let log = dir.join("log");
+    if !test_harness::in_child() {
+        fs::write(&log, b"first\n").unwrap();
+    }
+
+    if let Ok(profile) = append_profile(&dir) {
+        assert_allowed!(test_harness::run_in_sandbox(profile, "allowance_test", || {
+            append_to_file(&log)
+        }));
+    }
+
+    if let Ok(profile) = append_profile(&dir) {
+        assert_violation!(test_harness::run_in_sandbox(profile, "truncation_test", || {
+            open(&log, libc::O_WRONLY | libc::O_TRUNC);
+        }));
+    }
+
+    if let Ok(profile) = append_profile(&dir) {
+        assert_violation!(test_harness::run_in_sandbox(profile, "overwrite_test", || {
+            open(&log, libc::O_WRONLY);
+        }));
+    }
+
+    if !test_harness::in_child() {
+        assert_eq!(fs::read(&log).unwrap(), b"first\nsecond\nthird\n");
+    }
+}
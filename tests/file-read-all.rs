@@ -1,28 +1,21 @@
 // Any copyright is dedicated to the Public Domain.
 // http://creativecommons.org/publicdomain/zero/1.0/
 
+#[macro_use]
 extern crate gaol;
+#[cfg(target_os="linux")]
 extern crate libc;
-extern crate rand;
 
+#[cfg(target_os="linux")]
+use gaol::platform::linux::seccomp::Filter;
 use gaol::profile::{Operation, PathPattern, Profile};
-use gaol::sandbox::{ChildSandbox, ChildSandboxMethods, Command, Sandbox, SandboxMethods};
-use libc::c_char;
-use rand::Rng;
-use rand::distributions::Alphanumeric;
-use std::env;
-use std::ffi::{CString, OsStr};
+use gaol::test_harness;
 use std::fs::File;
-use std::io::Write;
-use std::os::unix::prelude::OsStrExt;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
-// A conservative overapproximation of `PATH_MAX` on all platforms.
-const PATH_MAX: usize = 4096;
-
-fn allowance_profile(path: &PathBuf) -> Profile {
+fn allowance_profile(path: &Path) -> Profile {
     Profile::new(vec![
-        Operation::FileReadAll(PathPattern::Literal(path.clone())),
+        Operation::FileReadAll(PathPattern::Literal(path.to_path_buf())),
     ]).unwrap()
 }
 
@@ -32,65 +25,29 @@ fn prohibition_profile() -> Profile {
     ]).unwrap()
 }
 
-fn allowance_test() {
-    let path = PathBuf::from(env::var("GAOL_TEMP_FILE").unwrap());
-    ChildSandbox::new(allowance_profile(&path)).activate().unwrap();
-    drop(File::open(&path).unwrap())
-}
-
-fn prohibition_test() {
-    let path = PathBuf::from(env::var("GAOL_TEMP_FILE").unwrap());
-    ChildSandbox::new(prohibition_profile()).activate().unwrap();
-    drop(File::open(&path).unwrap())
-}
-
 pub fn main() {
-    match env::args().skip(1).next() {
-        Some(ref arg) if arg == "allowance_test" => return allowance_test(),
-        Some(ref arg) if arg == "prohibition_test" => return prohibition_test(),
-        _ => {}
-    }
-
-    // Need to use `realpath` here for Mac OS X, because the temporary directory is usually a
-    // symlink.
-    let mut temp_path = env::temp_dir();
-    unsafe {
-        let c_temp_path =
-            CString::new(temp_path.as_os_str().to_str().unwrap().as_bytes()).unwrap();
-        let mut new_temp_path = [0u8; PATH_MAX];
-        drop(realpath(c_temp_path.as_ptr(), new_temp_path.as_mut_ptr() as *mut c_char));
-        let pos = new_temp_path.iter().position(|&x| x == 0).unwrap();
-        temp_path = PathBuf::from(OsStr::from_bytes(&new_temp_path[..pos]));
+    let path = test_harness::temp_file("GAOL_TEMP_FILE", b"super secret\n");
+
+    assert_allowed!(test_harness::run_in_sandbox(allowance_profile(&path), "allowance_test", || {
+        drop(File::open(&path).unwrap())
+    }));
+
+    assert_violation!(test_harness::run_in_sandbox(prohibition_profile(), "prohibition_test", || {
+        drop(File::open(&path).unwrap())
+    }));
+
+    // `openat()` takes its flags one argument later than `open()`, and is held to the same ones.
+    #[cfg(target_os="linux")]
+    {
+        let filter = Filter::new(&allowance_profile(&path));
+        let openat = |flags: libc::c_int| [libc::AT_FDCWD as u64, 0, flags as u64, 0, 0, 0];
+        let openat_syscall = libc::SYS_openat as u32;
+        assert!(filter.allows(openat_syscall, &openat(libc::O_RDONLY | libc::O_CLOEXEC)));
+        assert!(!filter.allows(openat_syscall, &openat(libc::O_WRONLY | libc::O_CLOEXEC)));
+        assert!(!filter.allows(openat_syscall, &openat(libc::O_RDWR | libc::O_CREAT)));
+        for &syscall in [libc::SYS_newfstatat, libc::SYS_statx, libc::SYS_faccessat,
+                         libc::SYS_readlinkat, libc::SYS_getdents64].iter() {
+            assert!(filter.allows(syscall as u32, &[0; 6]), "{}", syscall);
+        }
     }
-
-    let mut rng = rand::thread_rng();
-    let suffix: String = std::iter::repeat(())
-        .map(|()| rng.sample(Alphanumeric))
-        .take(6).collect();
-
-    temp_path.push(format!("gaoltest.{}", suffix));
-    File::create(&temp_path).unwrap().write_all(b"super secret\n").unwrap();
-
-    let allowance_status = Sandbox::new(allowance_profile(
-            &temp_path)).start(&mut Command::me().unwrap()
-                                                 .arg("allowance_test")
-                                                 .env("GAOL_TEMP_FILE", temp_path.clone())
-                                                 .env("RUST_BACKTRACE", "1"))
-                        .unwrap()
-                        .wait()
-                        .unwrap();
-    assert!(allowance_status.success());
-
-    let prohibition_status = Sandbox::new(prohibition_profile()).start(
-        Command::me().unwrap().arg("prohibition_test").env("GAOL_TEMP_FILE", temp_path.clone())
-                                                      .env("RUST_BACKTRACE", "1"))
-                                                                .unwrap()
-                                                                .wait()
-                                                                .unwrap();
-    assert!(!prohibition_status.success());
 }
-
-extern {
-    fn realpath(file_name: *const c_char, resolved_name: *mut c_char) -> *mut c_char;
-}
-
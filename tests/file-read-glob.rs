@@ -0,0 +1,76 @@
+// Any copyright is dedicated to the Public Domain.
+// http://creativecommons.org/publicdomain/zero/1.0/
+
+#[macro_use]
+extern crate gaol;
+extern crate libc;
+
+use gaol::profile::{Operation, PathPattern, Profile};
+use gaol::test_harness;
+use std::ffi::CString;
+use std::fs::{self, File};
+use std::io::Write;
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
+
+fn profile(glob: String) -> Profile {
+    Profile::new(vec![
+        Operation::FileReadAll(PathPattern::Glob(glob)),
+    ]).unwrap()
+}
+
+/// Returns true if `path` can be opened for reading, without the `openat` that the standard
+/// library uses, which the Linux filter doesn't allow.
+fn can_open(path: &Path) -> bool {
+    let c_path = CString::new(path.as_os_str().as_bytes()).unwrap();
+    unsafe {
+        #[cfg(all(any(target_os="android", target_os="linux"),
+                  not(any(target_arch="aarch64", target_arch="riscv64"))))]
+        let fd = libc::syscall(libc::SYS_open, c_path.as_ptr(), libc::O_RDONLY) as libc::c_int;
+        #[cfg(all(any(target_os="android", target_os="linux"),
+                  any(target_arch="aarch64", target_arch="riscv64")))]
+        let fd = libc::syscall(libc::SYS_openat, libc::AT_FDCWD, c_path.as_ptr(), libc::O_RDONLY)
+            as libc::c_int;
+        #[cfg(not(any(target_os="android", target_os="linux")))]
+        let fd = libc::open(c_path.as_ptr(), libc::O_RDONLY);
+        if fd < 0 {
+            return false
+        }
+        libc::close(fd);
+    }
+    true
+}
+
+/// Fills `dir` with files for the globs to match, among them some with glob characters in their
+/// names.
+fn build_tree(dir: &Path) {
+    if test_harness::in_child() {
+        return
+    }
+    fs::create_dir(dir.join("special")).unwrap();
+    for name in &["a.so", "b.so", "c.txt", "special/we*ird [1].so", "special/weXird [1].so"] {
+        File::create(dir.join(name)).unwrap().write_all(b"contents\n").unwrap();
+    }
+}
+
+pub fn main() {
+    let dir = test_harness::temp_dir("GAOL_TEMP_DIR");
+    build_tree(&dir);
+    let glob = |pattern: &str| format!("{}/{}", dir.display(), pattern);
+
+    assert_allowed!(test_harness::run_in_sandbox(profile(glob("*.so")), "multiple_test", || {
+        assert!(can_open(&dir.join("a.so")));
+        assert!(can_open(&dir.join("b.so")));
+        assert!(!can_open(&dir.join("c.txt")));
+    }));
+
+    assert_allowed!(test_harness::run_in_sandbox(profile(glob("*.none")), "no_match_test", || {
+        assert!(!can_open(&dir.join("a.so")));
+    }));
+
+    let special = glob("special/we\\*ird [1].so");
+    assert_allowed!(test_harness::run_in_sandbox(profile(special), "special_test", || {
+        assert!(can_open(&dir.join("special/we*ird [1].so")));
+        assert!(!can_open(&dir.join("special/weXird [1].so")));
+    }));
+}
@@ -0,0 +1,92 @@
+// Any copyright is dedicated to the Public Domain.
+// http://creativecommons.org/publicdomain/zero/1.0/
+
+#[macro_use]
+extern crate gaol;
+
+use gaol::profile::{Operation, PathPattern, Profile};
+use gaol::test_harness;
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::os::unix::fs::symlink;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How long the child waits for the parent to create a file after the sandbox has started.
+const LATE_FILE_TIMEOUT: Duration = Duration::from_secs(10);
+
+fn profile(root: &Path) -> Profile {
+    Profile::new(vec![
+        Operation::FileReadAll(PathPattern::Subpath(root.to_path_buf())),
+    ]).unwrap()
+}
+
+fn read_to_string(path: &Path) -> String {
+    let mut contents = String::new();
+    File::open(path).unwrap().read_to_string(&mut contents).unwrap();
+    contents
+}
+
+fn write_file(path: &Path, contents: &[u8]) {
+    File::create(path).unwrap().write_all(contents).unwrap();
+}
+
+/// Builds `root/outer/inner/nested.txt` plus a relative symlink `root/link` pointing at it, and
+/// a sibling tree outside `root`.
+fn build_trees(root: &Path, sibling: &Path) {
+    if test_harness::in_child() {
+        return
+    }
+    let inner = root.join("outer").join("inner");
+    fs::create_dir_all(&inner).unwrap();
+    write_file(&inner.join("nested.txt"), b"nested\n");
+    symlink("outer/inner/nested.txt", root.join("link")).unwrap();
+    write_file(&sibling.join("outside.txt"), b"outside\n");
+}
+
+/// Waits for a file that the parent creates after the child has started.
+fn wait_for_file(path: &Path) -> String {
+    let start = Instant::now();
+    while !path.exists() {
+        assert!(start.elapsed() < LATE_FILE_TIMEOUT, "late file never became visible");
+        thread::sleep(Duration::from_millis(10));
+    }
+    read_to_string(path)
+}
+
+pub fn main() {
+    let root = test_harness::temp_dir("GAOL_TEMP_ROOT");
+    let sibling = test_harness::temp_dir("GAOL_TEMP_SIBLING");
+    build_trees(&root, &sibling);
+
+    let nested: PathBuf = root.join("outer").join("inner").join("nested.txt");
+    assert_allowed!(test_harness::run_in_sandbox(profile(&root), "nested_test", || {
+        assert_eq!(read_to_string(&nested), "nested\n")
+    }));
+
+    let link = root.join("link");
+    assert_allowed!(test_harness::run_in_sandbox(profile(&root), "symlink_test", || {
+        assert_eq!(read_to_string(&link), "nested\n")
+    }));
+
+    let outside = sibling.join("outside.txt");
+    assert_violation!(test_harness::run_in_sandbox(profile(&root), "sibling_test", || {
+        drop(read_to_string(&outside))
+    }));
+
+    // The grant covers the directory, not a snapshot of it: on Linux the jail bind mounts the
+    // directory itself, so files created in it after the sandbox starts are visible, and on Mac
+    // OS X the `subpath` rule matches them as well.
+    let late = root.join("outer").join("late.txt");
+    if !test_harness::in_child() {
+        let late = late.clone();
+        thread::spawn(move || {
+            thread::sleep(Duration::from_millis(500));
+            write_file(&late, b"late\n");
+        });
+    }
+    assert_allowed!(test_harness::run_in_sandbox(profile(&root), "late_file_test", || {
+        assert_eq!(wait_for_file(&late), "late\n")
+    }));
+}
@@ -0,0 +1,80 @@
+// Any copyright is dedicated to the Public Domain.
+// http://creativecommons.org/publicdomain/zero/1.0/
+
+#[macro_use]
+extern crate gaol;
+extern crate libc;
+
+use gaol::profile::{Operation, PathPattern, Profile, ProfileError};
+use gaol::test_harness;
+use std::ffi::CString;
+use std::io;
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
+
+fn write_profile(dir: &Path) -> Result<Profile,ProfileError> {
+    Profile::new(vec![
+        Operation::FileWriteAll(PathPattern::Subpath(dir.to_path_buf())),
+    ])
+}
+
+/// Creates `path` and writes to it in the usual ways, without the `openat` that the standard
+/// library uses, which the Linux filter doesn't allow.
+fn write_file(path: &Path) {
+    let c_path = CString::new(path.as_os_str().as_bytes()).unwrap();
+    let flags = libc::O_WRONLY | libc::O_CREAT | libc::O_TRUNC;
+    unsafe {
+        #[cfg(all(any(target_os="android", target_os="linux"),
+                  not(any(target_arch="aarch64", target_arch="riscv64"))))]
+        let fd = libc::syscall(libc::SYS_open, c_path.as_ptr(), flags, 0o600) as libc::c_int;
+        #[cfg(all(any(target_os="android", target_os="linux"),
+                  any(target_arch="aarch64", target_arch="riscv64")))]
+        let fd = libc::syscall(libc::SYS_openat, libc::AT_FDCWD, c_path.as_ptr(), flags, 0o600)
+            as libc::c_int;
+        #[cfg(not(any(target_os="android", target_os="linux")))]
+        let fd = libc::open(c_path.as_ptr(), flags, 0o600);
+        assert!(fd >= 0, "couldn't open {}: {}", path.display(), io::Error::last_os_error());
+        assert_eq!(libc::write(fd, b"output\n".as_ptr() as *const libc::c_void, 7), 7);
+        let iovec = libc::iovec {
+            iov_base: b"more\n".as_ptr() as *mut libc::c_void,
+            iov_len: 5,
+        };
+        assert_eq!(libc::writev(fd, &iovec, 1), 5);
+        assert_eq!(libc::pwrite(fd, b"O".as_ptr() as *const libc::c_void, 1, 0), 1);
+        #[cfg(any(target_os="android", target_os="linux"))]
+        assert_eq!(libc::fallocate(fd, 0, 0, 4096), 0);
+        assert_eq!(libc::ftruncate(fd, 12), 0);
+        libc::close(fd);
+    }
+}
+
+pub fn main() {
+    let dir = test_harness::temp_dir("GAOL_TEMP_DIR");
+    let outside = test_harness::temp_file("GAOL_TEMP_FILE", b"super secret\n");
+
+    if let Ok(profile) = write_profile(&dir) {
+        assert_allowed!(test_harness::run_in_sandbox(profile, "allowance_test", || {
+            write_file(&dir.join("output"))
+        }));
+    }
+
+    if let Ok(profile) = write_profile(&dir) {
+        assert_violation!(test_harness::run_in_sandbox(profile, "prohibition_test", || {
+            write_file(&outside)
+        }));
+    }
+
+    let read_profile = Profile::new(vec![
+        Operation::FileReadAll(PathPattern::Literal(outside.clone())),
+        Operation::FileWriteAll(PathPattern::Subpath(dir.clone())),
+    ]);
+    if let Ok(profile) = read_profile {
+        assert_violation!(test_harness::run_in_sandbox(profile, "read_only_test", || {
+            write_file(&outside)
+        }));
+    }
+
+    if !test_harness::in_child() {
+        assert!(dir.join("output").exists());
+    }
+}
@@ -0,0 +1,117 @@
+// Any copyright is dedicated to the Public Domain.
+// http://creativecommons.org/publicdomain/zero/1.0/
+
+#[macro_use]
+extern crate gaol;
+extern crate libc;
+
+use gaol::profile::{Operation, PathPattern, Profile};
+use gaol::test_harness;
+use std::ffi::CString;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+use std::ptr;
+
+fn c_path(path: &Path) -> CString {
+    CString::new(path.as_os_str().as_bytes()).unwrap()
+}
+
+/// Changes the permission bits and timestamps of `path`.
+fn change_metadata(path: &Path) -> io::Result<()> {
+    let path = c_path(path);
+    unsafe {
+        if libc::chmod(path.as_ptr(), 0o640) != 0 ||
+                libc::utimensat(libc::AT_FDCWD, path.as_ptr(), ptr::null(), 0) != 0 {
+            return Err(io::Error::last_os_error())
+        }
+    }
+    Ok(())
+}
+
+/// Opens `path` with the system call the Linux filter checks, rather than the `openat` that the
+/// standard library uses.
+fn open(path: &Path, flags: libc::c_int) -> libc::c_int {
+    let path = c_path(path);
+    unsafe {
+        #[cfg(all(any(target_os="android", target_os="linux"),
+                  not(any(target_arch="aarch64", target_arch="riscv64"))))]
+        let fd = libc::syscall(libc::SYS_open, path.as_ptr(), flags) as libc::c_int;
+        #[cfg(all(any(target_os="android", target_os="linux"),
+                  any(target_arch="aarch64", target_arch="riscv64")))]
+        let fd = libc::syscall(libc::SYS_openat, libc::AT_FDCWD, path.as_ptr(), flags)
+            as libc::c_int;
+        #[cfg(not(any(target_os="android", target_os="linux")))]
+        let fd = libc::open(path.as_ptr(), flags);
+        fd
+    }
+}
+
+fn read_file(path: &Path) -> Vec<u8> {
+    let fd = open(path, libc::O_RDONLY);
+    assert!(fd >= 0, "couldn't open {}: {}", path.display(), io::Error::last_os_error());
+    let mut buffer = vec![0; 64];
+    let length = unsafe {
+        libc::read(fd, buffer.as_mut_ptr() as *mut libc::c_void, buffer.len())
+    };
+    unsafe {
+        libc::close(fd);
+    }
+    buffer.truncate(length.max(0) as usize);
+    buffer
+}
+
+fn mode(path: &Path) -> u32 {
+    fs::metadata(path).unwrap().permissions().mode() & 0o777
+}
+
+pub fn main() {
+    let dir = test_harness::temp_dir("GAOL_TEMP_DIR");
+    let restore_dir = dir.join("restore");
+    let restored = restore_dir.join("restored");
+    let other = dir.join("other");
+    if !test_harness::in_child() {
+        fs::create_dir(&restore_dir).unwrap();
+        for path in &[&restored, &other] {
+            File::create(path).unwrap().write_all(b"archived\n").unwrap();
+            fs::set_permissions(path, fs::Permissions::from_mode(0o600)).unwrap();
+        }
+    }
+
+    let metadata_profile = Profile::new(vec![
+        Operation::FileWriteMetadata(PathPattern::Literal(restored.clone())),
+    ]);
+    if let Ok(profile) = metadata_profile.clone() {
+        assert_allowed!(test_harness::run_in_sandbox(profile, "allowance_test", || {
+            change_metadata(&restored).unwrap()
+        }));
+    }
+
+    if let Ok(profile) = metadata_profile {
+        assert_violation!(test_harness::run_in_sandbox(profile, "prohibition_test", || {
+            assert!(open(&restored, libc::O_WRONLY) >= 0, "{}", io::Error::last_os_error())
+        }));
+    }
+
+    // Reads of the whole directory overlapping metadata writes of part of it allow each where its
+    // pattern applies.
+    let composed_profile = Profile::new(vec![
+        Operation::FileReadAll(PathPattern::Subpath(dir.clone())),
+        Operation::FileWriteMetadata(PathPattern::Subpath(restore_dir.clone())),
+    ]);
+    if let Ok(profile) = composed_profile {
+        assert_allowed!(test_harness::run_in_sandbox(profile, "composed_test", || {
+            assert_eq!(read_file(&other), b"archived\n");
+            assert_eq!(read_file(&restored), b"archived\n");
+            change_metadata(&restored).unwrap();
+            assert!(change_metadata(&other).is_err());
+        }));
+    }
+
+    if !test_harness::in_child() {
+        assert_eq!(mode(&restored), 0o640);
+        assert_eq!(mode(&other), 0o600);
+    }
+}
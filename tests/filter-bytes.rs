@@ -0,0 +1,48 @@
+// Any copyright is dedicated to the Public Domain.
+// http://creativecommons.org/publicdomain/zero/1.0/
+
+//! A compiled filter can be taken out as the bytes the kernel gets, and read as text.
+
+#[cfg(target_os="linux")]
+extern crate gaol;
+
+#[cfg(target_os="linux")]
+use gaol::platform::linux::seccomp::{Filter, SockFilter};
+#[cfg(target_os="linux")]
+use gaol::profile::{AddressPattern, Operation, Profile};
+#[cfg(target_os="linux")]
+use std::mem;
+#[cfg(target_os="linux")]
+use std::slice;
+
+#[cfg(target_os="linux")]
+pub fn main() {
+    let profile = Profile::new(vec![Operation::NetworkOutbound(AddressPattern::All)]).unwrap();
+    let filter = Filter::new(&profile);
+    let bytes = filter.as_bytes();
+    assert_eq!(filter.instruction_count(), filter.instructions().len());
+    assert_eq!(bytes.len(), filter.instruction_count() * 8);
+    assert_eq!(mem::size_of::<SockFilter>(), 8);
+
+    // The bytes are the program laid out in memory for the kernel.
+    let program = unsafe {
+        slice::from_raw_parts(filter.instructions().as_ptr() as *const u8, bytes.len())
+    };
+    assert_eq!(bytes, program);
+
+    // The first instruction loads the architecture, at offset 4 of `struct seccomp_data`.
+    assert_eq!(u16::from_ne_bytes([bytes[0], bytes[1]]), 0x20);
+    assert_eq!(u32::from_ne_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]), 4);
+
+    let listing = filter.disassemble();
+    assert_eq!(listing.lines().count(), filter.instruction_count());
+    assert!(listing.starts_with("0000: ld   [4]\n"), "{}", listing);
+    for opcode in &["jeq  #0x", "jge  #0x", "ja   ", "ret  #0x7fff0000", "  ; socket"] {
+        assert!(listing.contains(opcode), "no {:?} in {}", opcode, listing);
+    }
+}
+
+#[cfg(not(target_os="linux"))]
+pub fn main() {
+    // seccomp-bpf only exists on Linux.
+}
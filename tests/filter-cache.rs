@@ -0,0 +1,56 @@
+// Any copyright is dedicated to the Public Domain.
+// http://creativecommons.org/publicdomain/zero/1.0/
+
+#[cfg(target_os="linux")]
+extern crate gaol;
+
+#[cfg(target_os="linux")]
+use gaol::platform::linux::seccomp::{self, Filter};
+#[cfg(target_os="linux")]
+use gaol::profile::{AddressPattern, Operation, PathPattern, Profile};
+#[cfg(target_os="linux")]
+use std::path::PathBuf;
+#[cfg(target_os="linux")]
+use std::sync::Arc;
+
+#[cfg(target_os="linux")]
+fn read_profile(path: &str) -> Profile {
+    Profile::new(vec![
+        Operation::FileReadAll(PathPattern::Subpath(PathBuf::from(path))),
+    ]).unwrap()
+}
+
+#[cfg(target_os="linux")]
+fn network_profile() -> Profile {
+    Profile::new(vec![Operation::NetworkOutbound(AddressPattern::All)]).unwrap()
+}
+
+#[cfg(target_os="linux")]
+pub fn main() {
+    // Equal profiles share one compiled filter.
+    let first = Filter::cached(&read_profile("/usr"));
+    let second = Filter::cached(&read_profile("/usr"));
+    assert!(Arc::ptr_eq(&first, &second));
+
+    // Different profiles get different filters, even when the programs happen to be identical.
+    let other_path = Filter::cached(&read_profile("/opt"));
+    assert!(!Arc::ptr_eq(&first, &other_path));
+    assert_eq!(first.disassemble(), other_path.disassemble());
+    let network = Filter::cached(&network_profile());
+    assert!(!Arc::ptr_eq(&first, &network));
+    assert!(first.disassemble() != network.disassemble());
+
+    // A disabled cache compiles afresh every time and retains nothing.
+    seccomp::cache().set_enabled(false);
+    assert!(seccomp::cache().is_empty());
+    let uncached = Filter::cached(&read_profile("/usr"));
+    assert!(!Arc::ptr_eq(&first, &uncached));
+    assert!(!Arc::ptr_eq(&uncached, &Filter::cached(&read_profile("/usr"))));
+    assert!(seccomp::cache().is_empty());
+    seccomp::cache().set_enabled(true);
+}
+
+#[cfg(not(target_os="linux"))]
+pub fn main() {
+    // seccomp-bpf only exists on Linux.
+}
@@ -0,0 +1,96 @@
+// Any copyright is dedicated to the Public Domain.
+// http://creativecommons.org/publicdomain/zero/1.0/
+
+//! A program built elsewhere can be read in as a filter, checked, combined with gaol's
+//! architecture check, and activated.
+
+#[cfg(target_os="linux")]
+#[macro_use]
+extern crate gaol;
+#[cfg(target_os="linux")]
+extern crate libc;
+
+#[cfg(target_os="linux")]
+use gaol::platform::linux::seccomp::{Filter, FilterError, SockFilter};
+#[cfg(target_os="linux")]
+use gaol::profile::{AddressPattern, Operation, Profile};
+#[cfg(target_os="linux")]
+use gaol::test_harness;
+#[cfg(target_os="linux")]
+use std::io;
+
+#[cfg(target_os="linux")]
+const SECCOMP_RET_ERRNO: u32 = 0x0005_0000;
+#[cfg(target_os="linux")]
+const SECCOMP_RET_ALLOW: u32 = 0x7fff_0000;
+
+#[cfg(target_os="linux")]
+fn bytes(program: &[SockFilter]) -> Vec<u8> {
+    program.iter().flat_map(|instruction| {
+        let mut bytes = instruction.code.to_ne_bytes().to_vec();
+        bytes.extend_from_slice(&[instruction.jt, instruction.jf]);
+        bytes.extend_from_slice(&instruction.k.to_ne_bytes());
+        bytes
+    }).collect()
+}
+
+/// A program that makes `getppid()` fail with `EPERM` and allows everything else, as a tool
+/// other than gaol might build it.
+#[cfg(target_os="linux")]
+fn deny_getppid() -> Vec<SockFilter> {
+    vec![
+        SockFilter { code: 0x20, jt: 0, jf: 0, k: 0 },
+        SockFilter { code: 0x15, jt: 0, jf: 1, k: libc::SYS_getppid as u32 },
+        SockFilter { code: 0x06, jt: 0, jf: 0, k: SECCOMP_RET_ERRNO | libc::EPERM as u32 },
+        SockFilter { code: 0x06, jt: 0, jf: 0, k: SECCOMP_RET_ALLOW },
+    ]
+}
+
+#[cfg(target_os="linux")]
+pub fn main() {
+    // gaol's own filters survive the trip out and back in.
+    let profile = Profile::new(vec![Operation::NetworkOutbound(AddressPattern::All)]).unwrap();
+    let filter = Filter::new(&profile);
+    let imported = Filter::from_bytes(&filter.as_bytes()).unwrap();
+    assert_eq!(imported.instructions(), filter.instructions());
+    assert_eq!(imported.disassemble(), filter.disassemble());
+
+    // Malformed programs are refused.
+    let program = deny_getppid();
+    assert_eq!(Filter::from_bytes(&[]).err(), Some(FilterError::InvalidLength));
+    assert_eq!(Filter::from_bytes(&bytes(&program)[..7]).err(), Some(FilterError::InvalidLength));
+    assert_eq!(Filter::from_bytes(&bytes(&program).repeat(1025)).err(),
+               Some(FilterError::InvalidLength));
+    let mut far_jump = program.clone();
+    far_jump[1].jf = 2;
+    assert_eq!(Filter::from_bytes(&bytes(&far_jump)).err(), Some(FilterError::JumpOutOfBounds(1)));
+    assert_eq!(Filter::from_bytes(&bytes(&program[..2])).err(),
+               Some(FilterError::JumpOutOfBounds(1)));
+    assert_eq!(Filter::from_bytes(&bytes(&program[..1])).err(),
+               Some(FilterError::JumpOutOfBounds(0)));
+    assert!(FilterError::JumpOutOfBounds(1).to_string().contains("instruction 1"));
+
+    // The architecture check goes in front, and runs on into the imported program.
+    let mut combined = Filter::from_bytes(&bytes(&program)).unwrap();
+    combined.prepend(&Filter::architecture_check());
+    assert_eq!(combined.instruction_count(), Filter::architecture_check().instruction_count() + 4);
+    assert_eq!(&combined.instructions()[3..], &program[..]);
+    assert!(!combined.allows(libc::SYS_getppid as u32, &[0; 6]));
+    assert!(combined.allows(libc::SYS_getpid as u32, &[0; 6]));
+
+    let mut appended = Filter::architecture_check();
+    appended.append(&Filter::from_bytes(&bytes(&program)).unwrap());
+    assert_eq!(appended.instructions(), combined.instructions());
+
+    assert_allowed!(test_harness::run_unactivated_in_sandbox(profile, "activate", || {
+        combined.activate().unwrap();
+        assert_eq!(unsafe { libc::syscall(libc::SYS_getppid) }, -1);
+        assert_eq!(io::Error::last_os_error().raw_os_error(), Some(libc::EPERM));
+        assert!(unsafe { libc::getpid() } > 0);
+    }));
+}
+
+#[cfg(not(target_os="linux"))]
+pub fn main() {
+    // seccomp-bpf only exists on Linux.
+}
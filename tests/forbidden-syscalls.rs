@@ -1,55 +1,45 @@
 // Any copyright is dedicated to the Public Domain.
 // http://creativecommons.org/publicdomain/zero/1.0/
 
+#[cfg(target_os="linux")]
+#[macro_use]
 extern crate gaol;
+#[cfg(target_os="linux")]
 extern crate libc;
 
+#[cfg(target_os="linux")]
+use gaol::platform::linux::seccomp::{ALLOWED_SYSCALLS, ALLOWED_SYSCALLS_FOR_EVENT_LOOPS};
+#[cfg(target_os="linux")]
 use gaol::profile::Profile;
-use gaol::sandbox::{ChildSandbox, ChildSandboxMethods, Command, Sandbox, SandboxMethods};
-use libc::c_int;
-use std::env;
-
 #[cfg(target_os="linux")]
-use gaol::platform::linux::seccomp::ALLOWED_SYSCALLS;
+use gaol::test_harness;
+#[cfg(target_os="linux")]
+use libc::c_long;
 
+#[cfg(target_os="linux")]
 const MAX_SYSCALL: u32 = 400;
 
+#[cfg(target_os="linux")]
 fn profile() -> Profile {
     Profile::new(Vec::new()).unwrap()
 }
 
-#[cfg(target_os="linux")]
-fn test_syscall(number: c_int) {
-    ChildSandbox::new(profile()).activate().unwrap();
-    unsafe {
-        syscall(number, -1, -1, -1, -1, -1, -1);
-    }
-}
-
 #[cfg(target_os="linux")]
 pub fn main() {
-    if let Some(arg) = env::args().skip(1).next() {
-        return test_syscall(arg.parse().unwrap())
-    }
-
     for syscall in 0..MAX_SYSCALL {
-        if ALLOWED_SYSCALLS.iter().any(|number| *number == syscall) {
+        // `getrandom()` with flags it doesn't allow fails instead.
+        if ALLOWED_SYSCALLS.contains(&syscall) ||
+                ALLOWED_SYSCALLS_FOR_EVENT_LOOPS.contains(&syscall) ||
+                syscall == libc::SYS_getrandom as u32 {
             continue
         }
-        let arg = format!("{}", syscall);
-        let status = Sandbox::new(profile()).start(&mut Command::me().unwrap().arg(&arg[..]))
-                                            .unwrap()
-                                            .wait()
-                                            .unwrap();
-        assert!(!status.success());
+        assert_violation!(test_harness::run_in_sandbox(profile(), &syscall.to_string(), || {
+            unsafe {
+                libc::syscall(syscall as c_long, -1, -1, -1, -1, -1, -1);
+            }
+        }));
     }
 }
 
 #[cfg(not(target_os="linux"))]
 fn main() {}
-
-#[cfg(target_os="linux")]
-extern {
-    fn syscall(number: c_int, ...) -> c_int;
-}
-
@@ -0,0 +1,62 @@
+// Any copyright is dedicated to the Public Domain.
+// http://creativecommons.org/publicdomain/zero/1.0/
+
+//! Children see the hostname their sandbox gives them, or else the host's.
+
+#[cfg(target_os="linux")]
+#[macro_use]
+extern crate gaol;
+
+#[cfg(target_os="linux")]
+use gaol::platform::linux::Operation as PlatformOperation;
+#[cfg(target_os="linux")]
+use gaol::profile::{Operation, Profile};
+#[cfg(target_os="linux")]
+use gaol::sandbox::{Command, Sandbox, SandboxMethods, SandboxOptions};
+#[cfg(target_os="linux")]
+use gaol::test_harness;
+#[cfg(target_os="linux")]
+use std::ffi::OsString;
+#[cfg(target_os="linux")]
+use std::fs;
+#[cfg(target_os="linux")]
+use std::io;
+
+#[cfg(target_os="linux")]
+const HOSTNAME_PATH: &str = "/proc/sys/kernel/hostname";
+
+#[cfg(target_os="linux")]
+fn profile() -> Profile {
+    Profile::new(vec![Operation::PlatformSpecific(PlatformOperation::MountProc)]).unwrap()
+}
+
+#[cfg(target_os="linux")]
+pub fn main() {
+    let mut options = SandboxOptions::new();
+    options.hostname("sandbox");
+    assert_allowed!(test_harness::run_in_sandbox_with_options(profile(),
+                                                              options,
+                                                              "fixed",
+                                                              || {
+        assert_eq!(fs::read_to_string(HOSTNAME_PATH).unwrap(), "sandbox\n")
+    }));
+
+    let host = test_harness::shared_value("GAOL_HOST_HOSTNAME", || {
+        OsString::from(fs::read_to_string(HOSTNAME_PATH).unwrap())
+    });
+    assert_allowed!(test_harness::run_in_sandbox(profile(), "inherited", || {
+        assert_eq!(OsString::from(fs::read_to_string(HOSTNAME_PATH).unwrap()), host)
+    }));
+
+    if test_harness::in_child() {
+        return
+    }
+    let mut options = SandboxOptions::new();
+    options.hostname("x".repeat(65));
+    let sandbox = Sandbox::with_options(profile(), options);
+    let err = sandbox.start(&mut Command::new("/bin/true")).err().unwrap();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+}
+
+#[cfg(not(target_os="linux"))]
+pub fn main() {}
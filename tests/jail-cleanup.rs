@@ -0,0 +1,104 @@
+// Any copyright is dedicated to the Public Domain.
+// http://creativecommons.org/publicdomain/zero/1.0/
+
+#[cfg(target_os="linux")]
+extern crate gaol;
+#[cfg(target_os="linux")]
+extern crate libc;
+
+#[cfg(target_os="linux")]
+use gaol::platform::linux::cleanup_stale_jails;
+#[cfg(target_os="linux")]
+use gaol::platform::process::{ExitStatus, Process};
+#[cfg(target_os="linux")]
+use gaol::profile::Profile;
+#[cfg(target_os="linux")]
+use gaol::sandbox::{Command, Sandbox, SandboxMethods, SandboxOptions};
+#[cfg(target_os="linux")]
+use gaol::test_harness;
+#[cfg(target_os="linux")]
+use std::fs;
+#[cfg(target_os="linux")]
+use std::path::{Path, PathBuf};
+#[cfg(target_os="linux")]
+use std::thread;
+
+#[cfg(target_os="linux")]
+const THREADS: usize = 4;
+#[cfg(target_os="linux")]
+const SANDBOXES_PER_THREAD: usize = 25;
+
+#[cfg(target_os="linux")]
+fn sandbox(jail_root: &Path) -> Sandbox {
+    let mut options = SandboxOptions::new();
+    options.jail_root(jail_root);
+    Sandbox::with_options(Profile::new(Vec::new()).unwrap(), options)
+}
+
+#[cfg(target_os="linux")]
+fn jails(jail_root: &Path) -> Vec<PathBuf> {
+    fs::read_dir(jail_root).unwrap().map(|entry| entry.unwrap().path()).collect()
+}
+
+/// Starts many sandboxes at once from several threads. Every one gets its own jail, and waiting
+/// for a child removes its jail.
+#[cfg(target_os="linux")]
+fn test_concurrent_sandboxes(jail_root: &Path) {
+    let starters: Vec<_> = (0..THREADS).map(|_| {
+        let jail_root = jail_root.to_path_buf();
+        thread::spawn(move || {
+            (0..SANDBOXES_PER_THREAD).map(|_| {
+                sandbox(&jail_root).start(&mut Command::new("/bin/true")).unwrap()
+            }).collect::<Vec<Process>>()
+        })
+    }).collect();
+    let processes: Vec<Process> = starters.into_iter().flat_map(|starter| {
+        starter.join().unwrap()
+    }).collect();
+    assert_eq!(jails(jail_root).len(), THREADS * SANDBOXES_PER_THREAD);
+
+    // Nothing is stale while the sandboxes' owner is alive.
+    assert_eq!(cleanup_stale_jails(jail_root).unwrap(), 0);
+
+    for process in processes.iter() {
+        assert_eq!(process.wait().unwrap(), ExitStatus::Code(0));
+    }
+    assert_eq!(jails(jail_root), Vec::<PathBuf>::new());
+}
+
+/// Kills a process that started a sandbox before it can wait for it, leaving its jail behind for
+/// `cleanup_stale_jails()`.
+#[cfg(target_os="linux")]
+fn test_crash_cleanup(jail_root: &Path) {
+    let owner = unsafe {
+        libc::fork()
+    };
+    if owner == 0 {
+        drop(sandbox(jail_root).start(&mut Command::new("/bin/true")));
+        unsafe {
+            libc::kill(libc::getpid(), libc::SIGKILL);
+        }
+    }
+    let mut status = 0;
+    assert_eq!(unsafe { libc::waitpid(owner, &mut status, 0) }, owner);
+    assert!(libc::WIFSIGNALED(status) && libc::WTERMSIG(status) == libc::SIGKILL);
+    assert_eq!(jails(jail_root).len(), 1);
+
+    // A live sandbox's jail survives cleanup.
+    let live = sandbox(jail_root).start(&mut Command::new("/bin/true")).unwrap();
+    assert_eq!(cleanup_stale_jails(jail_root).unwrap(), 1);
+    assert_eq!(jails(jail_root).len(), 1);
+    live.wait().unwrap();
+    assert_eq!(jails(jail_root), Vec::<PathBuf>::new());
+}
+
+#[cfg(target_os="linux")]
+pub fn main() {
+    let jail_root = test_harness::temp_dir("GAOL_JAIL_ROOT");
+    test_concurrent_sandboxes(&jail_root);
+    test_crash_cleanup(&jail_root);
+    fs::remove_dir(&jail_root).unwrap();
+}
+
+#[cfg(not(target_os="linux"))]
+fn main() {}
@@ -0,0 +1,44 @@
+// Any copyright is dedicated to the Public Domain.
+// http://creativecommons.org/publicdomain/zero/1.0/
+
+//! The devices and `/proc` that programs expect are in the jail when the profile asks for them,
+//! without allowing file writes for them.
+
+#[cfg(target_os="linux")]
+#[macro_use]
+extern crate gaol;
+
+#[cfg(target_os="linux")]
+use gaol::profile::ProfileBuilder;
+#[cfg(target_os="linux")]
+use gaol::test_harness;
+#[cfg(target_os="linux")]
+use std::fs::{self, OpenOptions};
+#[cfg(target_os="linux")]
+use std::io::{Read, Write};
+#[cfg(target_os="linux")]
+use std::os::unix::io::AsRawFd;
+#[cfg(target_os="linux")]
+use std::path::Path;
+
+#[cfg(target_os="linux")]
+pub fn main() {
+    let profile = ProfileBuilder::new().allow_standard_devices().allow_proc().build().unwrap();
+    assert_allowed!(test_harness::run_in_sandbox(profile, "jail_devices", || {
+        let mut null = OpenOptions::new().write(true).open("/dev/null").unwrap();
+        null.write_all(b"discarded\n").unwrap();
+
+        let mut random = [0; 16];
+        fs::File::open("/dev/urandom").unwrap().read_exact(&mut random).unwrap();
+        let mut zeros = [1; 16];
+        fs::File::open("/dev/zero").unwrap().read_exact(&mut zeros).unwrap();
+        assert_eq!(zeros, [0; 16]);
+
+        // As programs that find their own files by descriptor do.
+        let fd = format!("/proc/self/fd/{}", null.as_raw_fd());
+        assert_eq!(fs::read_link(fd).unwrap(), Path::new("/dev/null"));
+    }));
+}
+
+#[cfg(not(target_os="linux"))]
+pub fn main() {}
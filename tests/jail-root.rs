@@ -0,0 +1,71 @@
+// Any copyright is dedicated to the Public Domain.
+// http://creativecommons.org/publicdomain/zero/1.0/
+
+//! The jail becomes the root of the child's mount namespace, and the host's root is detached
+//! from it instead of just being out of reach.
+
+#[cfg(target_os="linux")]
+extern crate gaol;
+
+#[cfg(target_os="linux")]
+use gaol::channel::ChildSandboxChannel;
+#[cfg(target_os="linux")]
+use gaol::platform::process::ExitStatus;
+#[cfg(target_os="linux")]
+use gaol::profile::{Operation, PathPattern, Profile};
+#[cfg(target_os="linux")]
+use gaol::sandbox::{ChildSandbox, ChildSandboxMethods, Command, Sandbox, SandboxMethods};
+#[cfg(target_os="linux")]
+use std::env;
+#[cfg(target_os="linux")]
+use std::fs;
+#[cfg(target_os="linux")]
+use std::path::{Path, PathBuf};
+
+/// Set in the child's environment.
+#[cfg(target_os="linux")]
+const JAIL_ROOT_CHILD_VAR: &str = "GAOL_JAIL_ROOT_CHILD";
+
+#[cfg(target_os="linux")]
+fn profile() -> Profile {
+    Profile::new(vec![Operation::FileReadAll(PathPattern::Subpath(PathBuf::from("/proc")))])
+        .unwrap()
+}
+
+/// Checks that only the jail and what the profile allows are mounted, then waits for the parent
+/// to look at its root.
+#[cfg(target_os="linux")]
+fn child() {
+    let mut channel = ChildSandboxChannel::from_environment().unwrap();
+    ChildSandbox::from_environment().unwrap().activate().unwrap();
+    let mounts = fs::read_to_string("/proc/self/mounts").unwrap();
+    let mount_points: Vec<(&str, &str)> = mounts.lines().map(|line| {
+        let fields: Vec<&str> = line.split(' ').collect();
+        (fields[1], fields[2])
+    }).collect();
+    assert_eq!(mount_points, [("/", "tmpfs"), ("/proc", "proc")], "{}", mounts);
+    channel.send_message(b"activated").unwrap();
+    channel.recv_message().unwrap();
+}
+
+#[cfg(target_os="linux")]
+pub fn main() {
+    if env::var(JAIL_ROOT_CHILD_VAR).is_ok() {
+        return child()
+    }
+
+    let mut command = Command::me().unwrap();
+    command.env(JAIL_ROOT_CHILD_VAR, "1");
+    let (process, mut channel) = Sandbox::new(profile()).start_with_channel(&mut command).unwrap();
+    channel.recv_message().unwrap();
+
+    // Seen from here, a `chroot` jail would be the directory it was built in. A pivoted root is
+    // the root of its own namespace.
+    let root = fs::read_link(format!("/proc/{}/root", process.pid)).unwrap();
+    assert_eq!(root, Path::new("/"));
+    channel.send_message(b"done").unwrap();
+    assert_eq!(process.wait().unwrap(), ExitStatus::Code(0));
+}
+
+#[cfg(not(target_os="linux"))]
+pub fn main() {}
@@ -0,0 +1,43 @@
+// Any copyright is dedicated to the Public Domain.
+// http://creativecommons.org/publicdomain/zero/1.0/
+
+//! A child in a network namespace of its own can still reach servers it starts on the loopback
+//! interface there.
+
+#[cfg(target_os="linux")]
+#[macro_use]
+extern crate gaol;
+
+#[cfg(target_os="linux")]
+use gaol::profile::Profile;
+#[cfg(target_os="linux")]
+use gaol::sandbox::SandboxOptions;
+#[cfg(target_os="linux")]
+use gaol::test_harness;
+#[cfg(target_os="linux")]
+use std::io::{Read, Write};
+#[cfg(target_os="linux")]
+use std::net::{Ipv4Addr, TcpListener, TcpStream};
+
+#[cfg(target_os="linux")]
+pub fn main() {
+    // Without network operations, the child gets a network namespace. The filter would keep it
+    // from making sockets at all, so only the namespace is left to test.
+    let mut options = SandboxOptions::new();
+    options.disable_seccomp().disable_landlock().acknowledge_weakened_sandbox();
+    assert_allowed!(test_harness::run_in_sandbox_with_options(Profile::new(Vec::new()).unwrap(),
+                                                              options,
+                                                              "loopback",
+                                                              || {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let mut client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (mut server, _) = listener.accept().unwrap();
+        client.write_all(b"ping").unwrap();
+        let mut buffer = [0; 4];
+        server.read_exact(&mut buffer).unwrap();
+        assert_eq!(&buffer, b"ping")
+    }));
+}
+
+#[cfg(not(target_os="linux"))]
+pub fn main() {}
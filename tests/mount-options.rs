@@ -0,0 +1,73 @@
+// Any copyright is dedicated to the Public Domain.
+// http://creativecommons.org/publicdomain/zero/1.0/
+
+#[cfg(target_os="linux")]
+#[macro_use]
+extern crate gaol;
+#[cfg(target_os="linux")]
+extern crate libc;
+
+#[cfg(target_os="linux")]
+use gaol::profile::{Operation, PathPattern, Profile};
+#[cfg(target_os="linux")]
+use gaol::sandbox::{MountOptions, SandboxOptions};
+#[cfg(target_os="linux")]
+use gaol::test_harness;
+#[cfg(target_os="linux")]
+use std::fs::{self, File};
+#[cfg(target_os="linux")]
+use std::io;
+#[cfg(target_os="linux")]
+use std::os::unix::io::AsRawFd;
+#[cfg(target_os="linux")]
+use std::path::Path;
+#[cfg(target_os="linux")]
+use std::ptr;
+
+/// Maps `path` executable, as the dynamic loader does with a library, and returns the error if
+/// the mount it's on doesn't allow that.
+#[cfg(target_os="linux")]
+fn map_executable(path: &Path) -> io::Result<()> {
+    let file = File::open(path)?;
+    unsafe {
+        let address = libc::mmap(ptr::null_mut(),
+                                 1,
+                                 libc::PROT_READ | libc::PROT_EXEC,
+                                 libc::MAP_PRIVATE,
+                                 file.as_raw_fd(),
+                                 0);
+        if address == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error())
+        }
+        libc::munmap(address, 1);
+    }
+    Ok(())
+}
+
+#[cfg(target_os="linux")]
+pub fn main() {
+    let path = test_harness::temp_file("GAOL_TEMP_FILE", b"\xc3");
+    let profile = || {
+        Profile::new(vec![Operation::FileReadAll(PathPattern::Literal(path.clone()))]).unwrap()
+    };
+
+    assert_allowed!(test_harness::run_in_sandbox(profile(), "noexec_test", || {
+        assert_eq!(map_executable(&path).unwrap_err().raw_os_error(), Some(libc::EPERM))
+    }));
+
+    let mut options = SandboxOptions::new();
+    options.mount_options(MountOptions { noexec: false, ..MountOptions::default() });
+    assert_allowed!(test_harness::run_in_sandbox_with_options(profile(),
+                                                              options,
+                                                              "exec_test",
+                                                              || {
+        map_executable(&path).unwrap()
+    }));
+
+    if !test_harness::in_child() {
+        fs::remove_file(&path).unwrap();
+    }
+}
+
+#[cfg(not(target_os="linux"))]
+fn main() {}
@@ -0,0 +1,54 @@
+// Any copyright is dedicated to the Public Domain.
+// http://creativecommons.org/publicdomain/zero/1.0/
+
+#[cfg(target_os="linux")]
+#[macro_use]
+extern crate gaol;
+#[cfg(target_os="linux")]
+extern crate libc;
+
+#[cfg(target_os="linux")]
+use gaol::profile::{Operation, PathPattern, Profile, ProfileBuilder};
+#[cfg(target_os="linux")]
+use gaol::test_harness;
+#[cfg(target_os="linux")]
+use std::fs;
+#[cfg(target_os="linux")]
+use std::path::Path;
+
+#[cfg(target_os="linux")]
+pub fn main() {
+    let profile = ProfileBuilder::new().allow_proc().build().unwrap();
+    assert_allowed!(test_harness::run_in_sandbox(profile, "allowance_test", || {
+        let status = fs::read_to_string("/proc/self/status").unwrap();
+        assert!(status.contains("Seccomp:"), "{}", status);
+        assert!(!fs::read_to_string("/proc/cpuinfo").unwrap().is_empty());
+        assert!(fs::read_to_string("/proc/self/maps").unwrap().contains("[stack]"));
+
+        // It shows the sandbox's PID namespace, so its PID 1 is never the host's init.
+        let pid: u32 = fs::read_link("/proc/self").unwrap().to_str().unwrap().parse().unwrap();
+        assert!(pid <= 2, "the child is process {}", pid);
+        if let Ok(environ) = fs::read("/proc/1/environ") {
+            let environ = String::from_utf8_lossy(&environ);
+            assert!(environ.contains("GAOL_TEST_HARNESS_CHILD="), "{}", environ)
+        }
+    }));
+
+    // Without it, there is no `/proc` in the jail, though the profile allows looking.
+    let path = test_harness::temp_file("GAOL_TEMP_FILE", b"");
+    let profile = Profile::new(vec![
+        Operation::FileReadAll(PathPattern::Literal(path.clone())),
+    ]).unwrap();
+    assert_allowed!(test_harness::run_in_sandbox(profile, "absence_test", || {
+        assert!(fs::metadata(&path).is_ok());
+        assert_eq!(fs::metadata(Path::new("/proc")).unwrap_err().raw_os_error(),
+                   Some(libc::ENOENT))
+    }));
+
+    if !test_harness::in_child() {
+        fs::remove_file(&path).unwrap();
+    }
+}
+
+#[cfg(not(target_os="linux"))]
+fn main() {}
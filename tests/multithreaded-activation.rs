@@ -0,0 +1,74 @@
+// Any copyright is dedicated to the Public Domain.
+// http://creativecommons.org/publicdomain/zero/1.0/
+
+#[macro_use]
+extern crate gaol;
+extern crate libc;
+
+use gaol::profile::Profile;
+use gaol::sandbox::{ActivationError, ChildSandbox, ChildSandboxMethods, ChildSandboxOptions};
+use gaol::test_harness;
+use std::sync::mpsc;
+use std::thread;
+
+fn profile() -> Profile {
+    Profile::new(Vec::new()).unwrap()
+}
+
+/// Starts a thread that waits for a message and then tries to create a socket, which the profile
+/// doesn't allow. Dropping the returned sender instead makes the thread exit quietly.
+fn spawn_socket_thread() -> (mpsc::Sender<()>, thread::JoinHandle<()>) {
+    let (sender, receiver) = mpsc::channel();
+    let thread = thread::spawn(move || {
+        if receiver.recv().is_ok() {
+            unsafe {
+                libc::socket(libc::AF_INET, libc::SOCK_STREAM, 0);
+            }
+        }
+    });
+    (sender, thread)
+}
+
+pub fn main() {
+    assert_allowed!(test_harness::run_unactivated_in_sandbox(profile(), "refused", || {
+        let sandbox = ChildSandbox::from_environment().unwrap();
+        let (sender, thread) = spawn_socket_thread();
+        let result = sandbox.activate_with_hook(|| {});
+        drop(sender);
+        thread.join().unwrap();
+        assert!(matches!(result, Err(ActivationError::MultithreadedProcess { thread_count: 2 })),
+                "unexpected result {:?}", result);
+    }));
+
+    // With the filter synchronized, the other thread is confined too.
+    #[cfg(any(target_os="android", target_os="linux"))]
+    let tsync = gaol::platform::capabilities().seccomp_tsync;
+    #[cfg(not(any(target_os="android", target_os="linux")))]
+    let tsync = true;
+    if tsync {
+        assert_violation!(test_harness::run_unactivated_in_sandbox(profile(), "synchronized", || {
+            let mut options = ChildSandboxOptions::new();
+            options.synchronize_threads(true);
+            let sandbox = ChildSandbox::from_environment_with_options(options).unwrap();
+            let (sender, thread) = spawn_socket_thread();
+            sandbox.activate().unwrap();
+            sender.send(()).unwrap();
+            thread.join().unwrap();
+        }));
+    } else {
+        // Without synchronization, only the calling thread is confined, and activation says so.
+        assert_violation!(test_harness::run_unactivated_in_sandbox(profile(), "unsynchronized", || {
+            let mut options = ChildSandboxOptions::new();
+            options.synchronize_threads(true);
+            let sandbox = ChildSandbox::from_environment_with_options(options).unwrap();
+            let (sender, thread) = spawn_socket_thread();
+            assert!(matches!(sandbox.activate_with_hook(|| {}),
+                             Err(ActivationError::ThreadsNotSynchronized { thread_count: 2 })));
+            sender.send(()).unwrap();
+            thread.join().unwrap();
+            unsafe {
+                libc::socket(libc::AF_INET, libc::SOCK_STREAM, 0);
+            }
+        }));
+    }
+}
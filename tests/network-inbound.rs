@@ -0,0 +1,112 @@
+// Any copyright is dedicated to the Public Domain.
+// http://creativecommons.org/publicdomain/zero/1.0/
+
+#[macro_use]
+extern crate gaol;
+extern crate libc;
+
+use gaol::profile::{AddressPattern, Operation, Profile};
+use gaol::test_harness;
+use std::ffi::OsString;
+use std::io::{Read, Write};
+use std::mem;
+use std::net::{TcpListener, TcpStream};
+use std::thread;
+use std::time::{Duration, Instant};
+
+fn inbound_profile() -> Profile {
+    Profile::new(vec![Operation::NetworkInbound(AddressPattern::All)]).unwrap()
+}
+
+fn outbound_profile() -> Profile {
+    Profile::new(vec![Operation::NetworkOutbound(AddressPattern::All)]).unwrap()
+}
+
+/// Creates a TCP socket bound to `port` on the loopback interface and listens on it.
+///
+/// This uses `libc` directly because the standard library checks descriptors with `fcntl` in
+/// debug builds, which the sandbox doesn't allow.
+fn listen(port: u16) -> libc::c_int {
+    unsafe {
+        let fd = libc::socket(libc::AF_INET, libc::SOCK_STREAM, 0);
+        assert!(fd >= 0);
+        let reuse: libc::c_int = 1;
+        assert_eq!(libc::setsockopt(fd,
+                                    libc::SOL_SOCKET,
+                                    libc::SO_REUSEADDR,
+                                    &reuse as *const libc::c_int as *const libc::c_void,
+                                    mem::size_of::<libc::c_int>() as libc::socklen_t), 0);
+        let mut address: libc::sockaddr_in = mem::zeroed();
+        address.sin_family = libc::AF_INET as libc::sa_family_t;
+        address.sin_port = port.to_be();
+        address.sin_addr.s_addr = u32::from_ne_bytes([127, 0, 0, 1]);
+        assert_eq!(libc::bind(fd,
+                              &address as *const libc::sockaddr_in as *const libc::sockaddr,
+                              mem::size_of::<libc::sockaddr_in>() as libc::socklen_t), 0);
+        assert_eq!(libc::listen(fd, 1), 0);
+        fd
+    }
+}
+
+/// Accepts one connection on `port`, checks it for errors, and echoes one byte back on it.
+fn serve_one_connection(port: u16) {
+    let listener = listen(port);
+    unsafe {
+        let connection = libc::accept(listener, std::ptr::null_mut(), std::ptr::null_mut());
+        assert!(connection >= 0);
+        let mut error: libc::c_int = -1;
+        let mut length = mem::size_of::<libc::c_int>() as libc::socklen_t;
+        assert_eq!(libc::getsockopt(connection,
+                                    libc::SOL_SOCKET,
+                                    libc::SO_ERROR,
+                                    &mut error as *mut libc::c_int as *mut libc::c_void,
+                                    &mut length), 0);
+        assert_eq!(error, 0);
+        let mut byte = 0u8;
+        assert_eq!(libc::read(connection, &mut byte as *mut u8 as *mut libc::c_void, 1), 1);
+        assert_eq!(libc::write(connection, &byte as *const u8 as *const libc::c_void, 1), 1);
+        libc::close(connection);
+        libc::close(listener);
+    }
+}
+
+/// Connects to the child's listener once it is up, and exchanges one byte with it.
+fn exchange_byte(port: u16) {
+    let deadline = Instant::now() + Duration::from_secs(10);
+    let mut stream = loop {
+        match TcpStream::connect(("127.0.0.1", port)) {
+            Ok(stream) => break stream,
+            Err(_) if Instant::now() < deadline => thread::sleep(Duration::from_millis(10)),
+            Err(error) => panic!("couldn't connect to the child: {}", error),
+        }
+    };
+    stream.write_all(b"g").unwrap();
+    let mut reply = [0];
+    stream.read_exact(&mut reply).unwrap();
+    assert_eq!(&reply, b"g");
+}
+
+pub fn main() {
+    let port = test_harness::shared_value("GAOL_TEST_PORT", || {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        OsString::from(listener.local_addr().unwrap().port().to_string())
+    });
+    let port: u16 = port.to_str().unwrap().parse().unwrap();
+
+    let client = if test_harness::in_child() {
+        None
+    } else {
+        Some(thread::spawn(move || exchange_byte(port)))
+    };
+    assert_allowed!(test_harness::run_in_sandbox(inbound_profile(), "inbound_allowance_test", || {
+        serve_one_connection(port)
+    }));
+    if let Some(client) = client {
+        client.join().unwrap();
+    }
+
+    // Outbound connections don't allow listening.
+    assert_violation!(test_harness::run_in_sandbox(outbound_profile(),
+                                                   "inbound_prohibition_test",
+                                                   || { listen(port); }));
+}
@@ -0,0 +1,172 @@
+// Any copyright is dedicated to the Public Domain.
+// http://creativecommons.org/publicdomain/zero/1.0/
+
+#[cfg(target_os="linux")]
+#[macro_use]
+extern crate gaol;
+#[cfg(target_os="linux")]
+extern crate libc;
+
+#[cfg(target_os="linux")]
+use gaol::platform::linux::ip_filter::{self, IpFilterBackend};
+#[cfg(target_os="linux")]
+use gaol::profile::{AddressPattern, Operation, Profile};
+#[cfg(target_os="linux")]
+use gaol::sandbox::{Command, Sandbox, SandboxMethods};
+#[cfg(target_os="linux")]
+use gaol::test_harness;
+#[cfg(target_os="linux")]
+use std::fs::{self, File};
+#[cfg(target_os="linux")]
+use std::io;
+#[cfg(target_os="linux")]
+use std::mem;
+#[cfg(target_os="linux")]
+use std::net::{IpAddr, Ipv4Addr, SocketAddrV4};
+#[cfg(target_os="linux")]
+use std::os::unix::fs::MetadataExt;
+#[cfg(target_os="linux")]
+use std::path::PathBuf;
+#[cfg(target_os="linux")]
+use std::process;
+#[cfg(target_os="linux")]
+use std::sync::{Arc, Mutex};
+
+/// An address in the range the profile allows (TEST-NET-1).
+#[cfg(target_os="linux")]
+const ALLOWED_ADDRESS: Ipv4Addr = Ipv4Addr::new(192, 0, 2, 1);
+/// An address outside it (TEST-NET-2).
+#[cfg(target_os="linux")]
+const FORBIDDEN_ADDRESS: Ipv4Addr = Ipv4Addr::new(198, 51, 100, 1);
+
+#[cfg(target_os="linux")]
+fn allowed() -> Vec<AddressPattern> {
+    vec![
+        AddressPattern::IpCidr {
+            addr: IpAddr::V4(Ipv4Addr::new(192, 0, 2, 7)),
+            prefix_len: 24,
+            port: None,
+        },
+        AddressPattern::IpCidr {
+            addr: "2001:db8::".parse().unwrap(),
+            prefix_len: 32,
+            port: Some(443),
+        },
+        AddressPattern::Udp(53),
+    ]
+}
+
+#[cfg(target_os="linux")]
+fn profile() -> Profile {
+    Profile::new(allowed().into_iter().map(Operation::NetworkOutbound).collect()).unwrap()
+}
+
+#[cfg(target_os="linux")]
+fn golden_path(name: &str) -> PathBuf {
+    [env!("CARGO_MANIFEST_DIR"), "tests", "golden", "ip-filter", name].iter().collect()
+}
+
+/// Starts connecting to port 80 at `address` without waiting for the connection, returning the
+/// error that tells whether the first packet could be sent.
+#[cfg(target_os="linux")]
+fn start_connecting(address: Ipv4Addr) -> io::Error {
+    let address = SocketAddrV4::new(address, 80);
+    unsafe {
+        let fd = libc::socket(libc::AF_INET, libc::SOCK_STREAM | libc::SOCK_NONBLOCK, 0);
+        assert!(fd >= 0, "couldn't create a socket: {}", io::Error::last_os_error());
+        let mut sockaddr: libc::sockaddr_in = mem::zeroed();
+        sockaddr.sin_family = libc::AF_INET as libc::sa_family_t;
+        sockaddr.sin_port = address.port().to_be();
+        sockaddr.sin_addr.s_addr = u32::from(*address.ip()).to_be();
+        assert_eq!(libc::connect(fd,
+                                 &sockaddr as *const libc::sockaddr_in as *const libc::sockaddr,
+                                 mem::size_of::<libc::sockaddr_in>() as libc::socklen_t),
+                   -1);
+        let err = io::Error::last_os_error();
+        libc::close(fd);
+        err
+    }
+}
+
+/// A backend that installs no rules, and records what it was asked to allow and where.
+#[cfg(target_os="linux")]
+#[derive(Default)]
+struct RecordingBackend {
+    installs: Mutex<Vec<(u64, Vec<AddressPattern>)>>,
+}
+
+#[cfg(target_os="linux")]
+impl IpFilterBackend for RecordingBackend {
+    fn name(&self) -> &str {
+        "recording"
+    }
+
+    fn is_available(&self) -> bool {
+        true
+    }
+
+    fn install(&self, namespace: &File, allowed: &[AddressPattern]) -> io::Result<()> {
+        let inode = namespace.metadata()?.ino();
+        self.installs.lock().unwrap().push((inode, allowed.to_vec()));
+        Ok(())
+    }
+}
+
+/// Returns true if this process can create links between network namespaces with `ip`.
+#[cfg(target_os="linux")]
+fn can_link_namespaces() -> bool {
+    let euid = unsafe {
+        libc::geteuid()
+    };
+    euid == 0 && process::Command::new("ip").arg("-V").output().is_ok_and(|output| {
+        output.status.success()
+    })
+}
+
+#[cfg(target_os="linux")]
+pub fn main() {
+    test_harness::assert_golden(&golden_path("nftables.txt"),
+                                &ip_filter::nftables_ruleset(&allowed()));
+    let rules = ip_filter::iptables_rules(&allowed());
+    let iptables: Vec<String> = rules.into_iter().flat_map(|(program, rules)| {
+        rules.into_iter().map(move |rule| format!("{} {}\n", program, rule.join(" ")))
+    }).collect();
+    test_harness::assert_golden(&golden_path("iptables.txt"), &iptables.concat());
+
+    // Without a backend, starting fails before anything is created.
+    if ip_filter::default_backend().is_none() && !test_harness::in_child() {
+        let err = Sandbox::new(profile()).start(&mut Command::new("/bin/true")).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::Unsupported);
+    }
+
+    if !can_link_namespaces() {
+        return
+    }
+
+    // The link routes everything to the host, so that only the rules decide.
+    let backend = Arc::new(RecordingBackend::default());
+    let sandbox = Sandbox::new(profile()).with_ip_filter_backend(backend.clone());
+    assert_allowed!(test_harness::run_in_given_sandbox(sandbox, "link_test", || {
+        for &address in &[ALLOWED_ADDRESS, FORBIDDEN_ADDRESS] {
+            assert_eq!(start_connecting(address).raw_os_error(), Some(libc::EINPROGRESS))
+        }
+    }));
+    if !test_harness::in_child() {
+        let installs = backend.installs.lock().unwrap();
+        assert_eq!(installs.len(), 1);
+        assert_ne!(installs[0].0, fs::metadata("/proc/self/ns/net").unwrap().ino());
+        assert_eq!(installs[0].1, allowed());
+    }
+
+    // The real backends drop what the profile doesn't allow.
+    if ip_filter::default_backend().is_some() {
+        assert_allowed!(test_harness::run_in_sandbox(profile(), "filter_test", || {
+            assert_eq!(start_connecting(ALLOWED_ADDRESS).raw_os_error(),
+                       Some(libc::EINPROGRESS));
+            assert_eq!(start_connecting(FORBIDDEN_ADDRESS).raw_os_error(), Some(libc::EPERM));
+        }));
+    }
+}
+
+#[cfg(not(target_os="linux"))]
+fn main() {}
@@ -0,0 +1,77 @@
+// Any copyright is dedicated to the Public Domain.
+// http://creativecommons.org/publicdomain/zero/1.0/
+
+#[macro_use]
+extern crate gaol;
+extern crate libc;
+
+use gaol::profile::{AddressPattern, Operation, Profile};
+use gaol::test_harness;
+use std::ffi::OsString;
+use std::mem;
+use std::net::UdpSocket;
+use std::time::Duration;
+
+fn udp_profile(port: u16) -> Profile {
+    Profile::new(vec![Operation::NetworkOutbound(AddressPattern::Udp(port))]).unwrap()
+}
+
+/// Sends one datagram to the parent's socket on the loopback interface.
+///
+/// This uses `libc` directly because the standard library checks descriptors with `fcntl` in
+/// debug builds, which the sandbox doesn't allow.
+fn send_datagram(port: u16) {
+    unsafe {
+        let fd = libc::socket(libc::AF_INET, libc::SOCK_DGRAM | libc::SOCK_CLOEXEC, 0);
+        assert!(fd >= 0);
+        let mut address: libc::sockaddr_in = mem::zeroed();
+        address.sin_family = libc::AF_INET as libc::sa_family_t;
+        address.sin_port = port.to_be();
+        address.sin_addr.s_addr = u32::from_ne_bytes([127, 0, 0, 1]);
+        let sent = libc::sendto(fd,
+                                b"gaol".as_ptr() as *const libc::c_void,
+                                4,
+                                0,
+                                &address as *const libc::sockaddr_in as *const libc::sockaddr,
+                                mem::size_of::<libc::sockaddr_in>() as libc::socklen_t);
+        assert_eq!(sent, 4);
+        libc::close(fd);
+    }
+}
+
+/// Creates a TCP socket without connecting it to anything.
+fn create_tcp_socket() {
+    let fd = unsafe {
+        libc::socket(libc::AF_INET, libc::SOCK_STREAM, 0)
+    };
+    assert!(fd >= 0);
+}
+
+pub fn main() {
+    let mut receiver = None;
+    let port = test_harness::shared_value("GAOL_TEST_UDP_PORT", || {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        socket.set_read_timeout(Some(Duration::from_secs(10))).unwrap();
+        let port = socket.local_addr().unwrap().port();
+        receiver = Some(socket);
+        OsString::from(port.to_string())
+    });
+    let port: u16 = port.to_str().unwrap().parse().unwrap();
+
+    assert_allowed!(test_harness::run_in_sandbox(udp_profile(port), "udp_allowance_test", || {
+        send_datagram(port)
+    }));
+    if let Some(receiver) = receiver {
+        let mut datagram = [0; 16];
+        let (length, _) = receiver.recv_from(&mut datagram).unwrap();
+        assert_eq!(&datagram[..length], b"gaol");
+    }
+
+    // Allowing UDP doesn't allow TCP.
+    if cfg!(target_os="linux") {
+        assert_violation!(test_harness::run_in_sandbox(udp_profile(port),
+                                                       "tcp_prohibition_test",
+                                                       create_tcp_socket),
+                          gaol::platform::process::ExitStatus::Signal(libc::SIGSYS));
+    }
+}
@@ -1,55 +1,128 @@
 // Any copyright is dedicated to the Public Domain.
 // http://creativecommons.org/publicdomain/zero/1.0/
 
+#[macro_use]
 extern crate gaol;
+extern crate libc;
 
-use gaol::profile::{AddressPattern, Operation, Profile};
-use gaol::sandbox::{ChildSandbox, ChildSandboxMethods, Command, Sandbox, SandboxMethods};
-use std::env;
+use gaol::profile::{AddressPattern, Operation, Profile, ProfileError};
+use gaol::test_harness;
+use std::ffi::OsString;
+use std::io::{ErrorKind, Read, Write};
 use std::net::{TcpListener, TcpStream};
+use std::process;
+use std::thread;
 
-static ADDRESS: &'static str = "127.0.0.1:7357";
+/// The exit code a prohibition child uses when `connect` returned an error instead of the child
+/// being terminated by the sandbox.
+const CONNECT_FAILED: i32 = 2;
 
-fn allowance_profile() -> Profile {
+fn all_profile() -> Profile {
     Profile::new(vec![Operation::NetworkOutbound(AddressPattern::All)]).unwrap()
 }
 
+fn port_profile(port: u16) -> Result<Profile,ProfileError> {
+    Profile::new(vec![Operation::NetworkOutbound(AddressPattern::Tcp(port))])
+}
+
 fn prohibition_profile() -> Profile {
     Profile::new(Vec::new()).unwrap()
 }
 
-fn allowance_test() {
-    ChildSandbox::new(allowance_profile()).activate().unwrap();
-    drop(TcpStream::connect(ADDRESS).unwrap())
+/// Connects to the parent's listener and exchanges one byte with it.
+fn exchange_byte(port: u16) {
+    let mut stream = TcpStream::connect(("127.0.0.1", port)).unwrap();
+    stream.write_all(b"g").unwrap();
+    let mut reply = [0];
+    stream.read_exact(&mut reply).unwrap();
+    assert_eq!(&reply, b"g");
 }
 
-fn prohibition_test() {
-    ChildSandbox::new(prohibition_profile()).activate().unwrap();
-    drop(TcpStream::connect(ADDRESS).unwrap())
+/// Attempts to connect to the parent's listener, which the sandbox should prevent.
+///
+/// On Linux the seccomp filter terminates the child as soon as it creates the socket, so the
+/// network namespace (which has no route to the parent's loopback interface) is never reached.
+/// Elsewhere, `connect` is expected to fail with a permission error.
+fn attempt_connect(port: u16) {
+    match TcpStream::connect(("127.0.0.1", port)) {
+        Ok(_) => {}
+        Err(error) => {
+            assert_eq!(error.kind(), ErrorKind::PermissionDenied);
+            process::exit(CONNECT_FAILED)
+        }
+    }
 }
 
-pub fn main() {
-    match env::args().skip(1).next() {
-        Some(ref arg) if arg == "allowance_test" => return allowance_test(),
-        Some(ref arg) if arg == "prohibition_test" => return prohibition_test(),
-        _ => {}
+/// Creates a socket without connecting it to anything.
+fn create_socket() {
+    let fd = unsafe {
+        libc::socket(libc::AF_INET, libc::SOCK_STREAM, 0)
+    };
+    assert!(fd >= 0);
+    unsafe {
+        libc::close(fd);
     }
+}
 
-    let listener = TcpListener::bind(ADDRESS).unwrap();
-    let _acceptor = listener.incoming();
+/// Echoes one byte back on every connection accepted by `listener`.
+fn serve(listener: TcpListener) {
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = match stream {
+                Ok(stream) => stream,
+                Err(_) => continue,
+            };
+            let mut byte = [0];
+            if stream.read_exact(&mut byte).is_ok() {
+                drop(stream.write_all(&byte));
+            }
+        }
+    });
+}
 
-    let allowance_status =
-        Sandbox::new(allowance_profile()).start(Command::me().unwrap().arg("allowance_test"))
-                                         .unwrap()
-                                         .wait()
-                                         .unwrap();
-    assert!(allowance_status.success());
+#[cfg(target_os="linux")]
+fn expected_prohibition_status() -> gaol::platform::process::ExitStatus {
+    gaol::platform::process::ExitStatus::Signal(libc::SIGSYS)
+}
 
-    let prohibition_status =
-        Sandbox::new(prohibition_profile()).start(Command::me().unwrap().arg("prohibition_test"))
-                                           .unwrap()
-                                           .wait()
-                                           .unwrap();
-    assert!(!prohibition_status.success());
+#[cfg(not(target_os="linux"))]
+fn expected_prohibition_status() -> gaol::platform::process::ExitStatus {
+    gaol::platform::process::ExitStatus::Code(CONNECT_FAILED)
 }
 
+pub fn main() {
+    let port = test_harness::shared_value("GAOL_TEST_PORT", || {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        serve(listener);
+        OsString::from(port.to_string())
+    });
+    let port: u16 = port.to_str().unwrap().parse().unwrap();
+
+    assert_allowed!(test_harness::run_in_sandbox(all_profile(), "all_allowance_test", || {
+        exchange_byte(port)
+    }));
+
+    // Not every platform can restrict connections by port.
+    if let Ok(profile) = port_profile(port) {
+        assert_allowed!(test_harness::run_in_sandbox(profile, "port_allowance_test", || {
+            exchange_byte(port)
+        }));
+    }
+
+    assert_violation!(test_harness::run_in_sandbox(prohibition_profile(),
+                                                   "prohibition_test",
+                                                   || attempt_connect(port)),
+                      expected_prohibition_status());
+
+    // Creating a socket is gated separately from connecting it.
+    assert_allowed!(test_harness::run_in_sandbox(all_profile(), "socket_allowance_test", || {
+        create_socket()
+    }));
+    if cfg!(target_os="linux") {
+        assert_violation!(test_harness::run_in_sandbox(prohibition_profile(),
+                                                       "socket_prohibition_test",
+                                                       create_socket),
+                          expected_prohibition_status());
+    }
+}
@@ -0,0 +1,54 @@
+// Any copyright is dedicated to the Public Domain.
+// http://creativecommons.org/publicdomain/zero/1.0/
+
+//! Paths that aren't UTF-8, which Linux allows, can be bound into the jail like any other.
+
+#[cfg(target_os="linux")]
+#[macro_use]
+extern crate gaol;
+
+#[cfg(target_os="linux")]
+use gaol::profile::{Operation, PathPattern, Profile};
+#[cfg(target_os="linux")]
+use gaol::test_harness;
+#[cfg(target_os="linux")]
+use std::ffi::OsStr;
+#[cfg(target_os="linux")]
+use std::fs;
+#[cfg(target_os="linux")]
+use std::os::unix::ffi::OsStrExt;
+
+#[cfg(target_os="linux")]
+pub fn main() {
+    let directory = test_harness::temp_dir("GAOL_TEMP_DIR");
+    // Latin-1 "café" and a byte that is never UTF-8.
+    let path = directory.join(OsStr::from_bytes(b"caf\xe9-\xff"));
+    if !test_harness::in_child() {
+        fs::write(&path, b"super secret\n").unwrap();
+    }
+
+    let profile = Profile::new(vec![
+        Operation::FileReadAll(PathPattern::Literal(path.clone())),
+    ]).unwrap();
+    assert_allowed!(test_harness::run_in_sandbox(profile, "literal", || {
+        assert_eq!(fs::read(&path).unwrap(), b"super secret\n")
+    }));
+
+    let profile = Profile::new(vec![
+        Operation::FileReadAll(PathPattern::Subpath(directory.clone())),
+    ]).unwrap();
+    assert_allowed!(test_harness::run_in_sandbox(profile, "subpath", || {
+        assert_eq!(fs::read(&path).unwrap(), b"super secret\n")
+    }));
+
+    // The parent hands the glob's matches to the child through its environment.
+    let profile = Profile::new(vec![
+        Operation::FileReadAll(PathPattern::Glob(format!("{}/caf*", directory.display()))),
+    ]).unwrap();
+    assert_allowed!(test_harness::run_in_sandbox(profile, "glob", || {
+        assert_eq!(fs::read(&path).unwrap(), b"super secret\n")
+    }));
+}
+
+#[cfg(not(target_os="linux"))]
+pub fn main() {}
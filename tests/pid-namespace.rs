@@ -0,0 +1,66 @@
+// Any copyright is dedicated to the Public Domain.
+// http://creativecommons.org/publicdomain/zero/1.0/
+
+#[cfg(target_os="linux")]
+#[macro_use]
+extern crate gaol;
+#[cfg(target_os="linux")]
+extern crate libc;
+
+#[cfg(target_os="linux")]
+use gaol::platform::process::ExitStatus;
+#[cfg(target_os="linux")]
+use gaol::profile::Profile;
+#[cfg(target_os="linux")]
+use gaol::test_harness;
+#[cfg(target_os="linux")]
+use std::ffi::OsString;
+#[cfg(target_os="linux")]
+use std::io;
+
+/// Forks a process, which is the second in the namespace, and reaps it, as the first has to.
+/// Exits with the status the forked process exited with.
+#[cfg(target_os="linux")]
+fn fork_and_reap() {
+    unsafe {
+        match libc::fork() {
+            0 => {
+                let status = if libc::getpid() == 2 && libc::getppid() == 1 { 42 } else { 1 };
+                libc::_exit(status)
+            }
+            pid => {
+                let mut status = 0;
+                assert_eq!(libc::waitpid(pid, &mut status, 0), pid);
+                assert!(libc::WIFEXITED(status));
+                libc::_exit(libc::WEXITSTATUS(status))
+            }
+        }
+    }
+}
+
+/// The child is the first process in its own PID namespace, so it can't name its parent, or
+/// signal any process outside the namespace, by PID.
+#[cfg(target_os="linux")]
+pub fn main() {
+    let parent_pid = test_harness::shared_value("GAOL_PARENT_PID", || {
+        OsString::from(unsafe { libc::getpid() }.to_string())
+    });
+    let parent_pid: libc::pid_t = parent_pid.to_str().unwrap().parse().unwrap();
+    let profile = Profile::new(Vec::new()).unwrap();
+    assert_allowed!(test_harness::run_unactivated_in_sandbox(profile, "pid_test", || {
+        unsafe {
+            assert_eq!(libc::getpid(), 1);
+            assert_eq!(libc::getppid(), 0);
+            assert_eq!(libc::kill(parent_pid, 0), -1);
+            assert_eq!(io::Error::last_os_error().raw_os_error(), Some(libc::ESRCH));
+        }
+    }));
+
+    // Exit statuses make it out of the namespace.
+    let profile = Profile::new(Vec::new()).unwrap();
+    let status = test_harness::run_unactivated_in_sandbox(profile, "reap_test", fork_and_reap);
+    assert_eq!(status, ExitStatus::Code(42));
+}
+
+#[cfg(not(target_os="linux"))]
+fn main() {}
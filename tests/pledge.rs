@@ -0,0 +1,59 @@
+// Any copyright is dedicated to the Public Domain.
+// http://creativecommons.org/publicdomain/zero/1.0/
+
+#[cfg(target_os="openbsd")]
+#[macro_use]
+extern crate gaol;
+#[cfg(target_os="openbsd")]
+extern crate libc;
+
+#[cfg(target_os="openbsd")]
+use gaol::platform::openbsd::{promises, unveil_permissions};
+#[cfg(target_os="openbsd")]
+use gaol::profile::{AddressPattern, Operation, PathPattern, Profile, ViolationAction};
+#[cfg(target_os="openbsd")]
+use gaol::test_harness;
+#[cfg(target_os="openbsd")]
+use std::fs;
+#[cfg(target_os="openbsd")]
+use std::path::{Path, PathBuf};
+
+#[cfg(target_os="openbsd")]
+fn profile(path: &Path) -> Profile {
+    Profile::new(vec![Operation::FileReadAll(PathPattern::Literal(path.to_path_buf()))]).unwrap()
+}
+
+#[cfg(target_os="openbsd")]
+fn test_translation() {
+    let read = Operation::FileReadAll(PathPattern::Subpath(PathBuf::from("/usr/share")));
+    let write = Operation::FileWriteAll(PathPattern::Literal(PathBuf::from("/tmp/out")));
+    assert_eq!(unveil_permissions(&read), Some((Path::new("/usr/share"), "r")));
+    assert_eq!(unveil_permissions(&write), Some((Path::new("/tmp/out"), "rwc")));
+    assert_eq!(unveil_permissions(&Operation::SystemInfoRead), None);
+
+    let profile = Profile::new(vec![
+        read,
+        write,
+        Operation::NetworkOutbound(AddressPattern::All),
+        Operation::SystemInfoRead,
+    ]).unwrap();
+    assert_eq!(promises(&profile), "stdio rpath wpath cpath inet vminfo");
+    assert_eq!(promises(&Profile::new(Vec::new()).unwrap()), "stdio");
+    let erring = Profile::new(Vec::new()).unwrap().with_violation_action(ViolationAction::Errno(1));
+    assert_eq!(promises(&erring), "stdio error");
+}
+
+#[cfg(target_os="openbsd")]
+pub fn main() {
+    test_translation();
+
+    let allowed = test_harness::temp_file("GAOL_PLEDGE_ALLOWED_FILE", b"super secret\n");
+    let hidden = test_harness::temp_file("GAOL_PLEDGE_HIDDEN_FILE", b"top secret\n");
+    assert_allowed!(test_harness::run_in_sandbox(profile(&allowed), "unveil_test", || {
+        assert_eq!(fs::read(&allowed).unwrap(), b"super secret\n");
+        assert_eq!(fs::read(&hidden).unwrap_err().raw_os_error(), Some(libc::ENOENT));
+    }));
+}
+
+#[cfg(not(target_os="openbsd"))]
+pub fn main() {}
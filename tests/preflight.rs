@@ -0,0 +1,135 @@
+// Any copyright is dedicated to the Public Domain.
+// http://creativecommons.org/publicdomain/zero/1.0/
+
+#[macro_use]
+extern crate gaol;
+
+#[cfg(any(target_os="android", target_os="linux"))]
+use gaol::platform::ChildSandbox;
+use gaol::profile::{AddressPattern, Operation, PathAccess, PathKind, PathPattern, Profile};
+use gaol::profile::RequiredPath;
+use gaol::sandbox::{PreflightIssue, Sandbox, SandboxMethods};
+use gaol::test_harness;
+use std::path::PathBuf;
+#[cfg(any(target_os="android", target_os="linux"))]
+use std::sync::mpsc;
+#[cfg(any(target_os="android", target_os="linux"))]
+use std::thread;
+
+fn required(path: &str, kind: PathKind) -> RequiredPath {
+    RequiredPath {
+        path: PathBuf::from(path),
+        kind,
+        access: PathAccess::Read,
+    }
+}
+
+fn test_required_host_paths() {
+    let empty = Profile::new(Vec::new()).unwrap();
+    assert!(empty.required_host_paths().is_empty());
+
+    let profile = Profile::new(vec![
+        Operation::FileReadAll(PathPattern::Subpath(PathBuf::from("/usr/lib"))),
+        Operation::FileReadAll(PathPattern::Literal(PathBuf::from("/etc/hosts"))),
+        Operation::NetworkOutbound(AddressPattern::All),
+    ]).unwrap();
+    assert_eq!(profile.required_host_paths(), vec![
+        required("/usr/lib", PathKind::Dir),
+        required("/etc/hosts", PathKind::File),
+    ]);
+}
+
+fn test_missing_path() {
+    let present = test_harness::temp_file("GAOL_TEMP_FILE", b"present\n");
+    let missing = present.with_extension("missing");
+    let profile = Profile::new(vec![
+        Operation::FileReadAll(PathPattern::Literal(present.clone())),
+        Operation::FileReadAll(PathPattern::Literal(missing.clone())),
+        Operation::FileReadAll(PathPattern::Subpath(present.clone())),
+    ]).unwrap();
+    let issues = match Sandbox::new(profile).preflight() {
+        Ok(()) => panic!("preflight passed despite a missing path"),
+        Err(issues) => issues,
+    };
+    let path_issues: Vec<&PreflightIssue> = issues.iter().filter(|issue| {
+        !matches!(**issue, PreflightIssue::MechanismUnavailable(_))
+    }).collect();
+    assert_eq!(path_issues, vec![
+        &PreflightIssue::MissingPath(missing),
+        &PreflightIssue::NotADirectory(present),
+    ]);
+}
+
+#[cfg(any(target_os="android", target_os="linux"))]
+fn test_unmatched_glob() {
+    let dir = test_harness::temp_dir("GAOL_TEMP_DIR");
+    let matched = format!("{}/*.present", dir.display());
+    let unmatched = format!("{}/*.missing", dir.display());
+    std::fs::write(dir.join("file.present"), b"present\n").unwrap();
+    let profile = Profile::new(vec![
+        Operation::FileReadAll(PathPattern::Glob(matched)),
+        Operation::FileReadAll(PathPattern::Glob(unmatched.clone())),
+    ]).unwrap();
+    let issues = Sandbox::new(profile).preflight().unwrap_err();
+    let glob_issues: Vec<&PreflightIssue> = issues.iter().filter(|issue| {
+        matches!(**issue, PreflightIssue::UnmatchedGlob(_))
+    }).collect();
+    assert_eq!(glob_issues, vec![&PreflightIssue::UnmatchedGlob(unmatched)]);
+}
+
+#[cfg(not(any(target_os="android", target_os="linux")))]
+fn test_unmatched_glob() {}
+
+#[cfg(any(target_os="android", target_os="linux"))]
+fn test_unsupported_filesystem() {
+    let profile = Profile::new(vec![
+        Operation::FileReadAll(PathPattern::Subpath(PathBuf::from("/proc/self"))),
+    ]).unwrap();
+    let issues = Sandbox::new(profile).preflight().unwrap_err();
+    assert!(issues.contains(&PreflightIssue::UnsupportedFilesystem {
+        path: PathBuf::from("/proc/self"),
+        filesystem: "proc".to_owned(),
+    }), "{:?}", issues);
+}
+
+#[cfg(not(any(target_os="android", target_os="linux")))]
+fn test_unsupported_filesystem() {}
+
+#[cfg(any(target_os="android", target_os="linux"))]
+fn test_child_preflight() {
+    let missing = PathBuf::from("/nonexistent/gaol-preflight");
+    let sandbox = ChildSandbox::new(Profile::new(vec![
+        Operation::FileReadAll(PathPattern::Literal(missing.clone())),
+    ]).unwrap());
+    let issues = sandbox.preflight().unwrap_err();
+    assert!(issues.contains(&PreflightIssue::MissingPath(missing)), "{:?}", issues);
+    // Checking changes nothing, so checking again finds the same.
+    assert_eq!(sandbox.preflight().unwrap_err(), issues);
+
+    // Activation can't confine a thread the options don't synchronize.
+    let (sender, receiver) = mpsc::channel::<()>();
+    let thread = thread::spawn(move || receiver.recv().unwrap_err());
+    let issues = sandbox.preflight().unwrap_err();
+    assert!(issues.iter().any(|issue| {
+        matches!(*issue, PreflightIssue::MultithreadedProcess { thread_count: 2 })
+    }), "{:?}", issues);
+    drop(sender);
+    thread.join().unwrap();
+
+    // A child of `Sandbox::start()` has all it needs.
+    let profile = Profile::new(Vec::new()).unwrap();
+    assert_allowed!(test_harness::run_unactivated_in_sandbox(profile, "child", || {
+        assert_eq!(ChildSandbox::from_environment().unwrap().preflight(), Ok(()));
+    }));
+}
+
+#[cfg(not(any(target_os="android", target_os="linux")))]
+fn test_child_preflight() {}
+
+pub fn main() {
+    test_required_host_paths();
+    test_missing_path();
+    test_unmatched_glob();
+    test_unsupported_filesystem();
+    test_child_preflight();
+}
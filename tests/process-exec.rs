@@ -0,0 +1,174 @@
+// Any copyright is dedicated to the Public Domain.
+// http://creativecommons.org/publicdomain/zero/1.0/
+
+#[cfg(target_os="linux")]
+#[macro_use]
+extern crate gaol;
+#[cfg(target_os="linux")]
+extern crate libc;
+
+#[cfg(target_os="linux")]
+use gaol::platform::capabilities;
+#[cfg(target_os="linux")]
+use gaol::profile::{Operation, PathPattern, Profile, ViolationAction};
+#[cfg(target_os="linux")]
+use gaol::test_harness;
+#[cfg(target_os="linux")]
+use std::ffi::CString;
+#[cfg(target_os="linux")]
+use std::fs::{self, Permissions};
+#[cfg(target_os="linux")]
+use std::io;
+#[cfg(target_os="linux")]
+use std::os::unix::ffi::OsStrExt;
+#[cfg(target_os="linux")]
+use std::os::unix::fs::PermissionsExt;
+#[cfg(target_os="linux")]
+use std::path::{Path, PathBuf};
+#[cfg(target_os="linux")]
+use std::ptr;
+
+/// The status the helper program exits with.
+#[cfg(target_os="linux")]
+const HELPER_EXIT_CODE: u8 = 42;
+
+/// Machine code that exits with `HELPER_EXIT_CODE`, making no other system call.
+#[cfg(all(target_os="linux", target_arch="x86_64"))]
+const HELPER_CODE: [u8; 12] = [
+    0xbf, HELPER_EXIT_CODE, 0, 0, 0,    // mov edi, HELPER_EXIT_CODE
+    0xb8, 60, 0, 0, 0,                  // mov eax, SYS_exit
+    0x0f, 0x05,                         // syscall
+];
+#[cfg(all(target_os="linux", target_arch="x86_64"))]
+const EM_NATIVE: u16 = 62;
+
+#[cfg(all(target_os="linux", target_arch="aarch64"))]
+const HELPER_CODE: [u8; 12] = [
+    0x40, 0x05, 0x80, 0xd2,             // mov x0, #HELPER_EXIT_CODE
+    0xa8, 0x0b, 0x80, 0xd2,             // mov x8, #SYS_exit
+    0x01, 0x00, 0x00, 0xd4,             // svc #0
+];
+#[cfg(all(target_os="linux", target_arch="aarch64"))]
+const EM_NATIVE: u16 = 183;
+
+#[cfg(all(target_os="linux", target_arch="riscv64"))]
+const HELPER_CODE: [u8; 12] = [
+    0x13, 0x05, 0xa0, 0x02,             // li a0, HELPER_EXIT_CODE
+    0x93, 0x08, 0xd0, 0x05,             // li a7, SYS_exit
+    0x73, 0x00, 0x00, 0x00,             // ecall
+];
+#[cfg(all(target_os="linux", target_arch="riscv64"))]
+const EM_NATIVE: u16 = 243;
+
+/// Returns a statically linked executable holding just `HELPER_CODE`, which needs nothing in the
+/// jail but itself.
+#[cfg(target_os="linux")]
+fn helper_program() -> Vec<u8> {
+    const BASE_ADDRESS: u64 = 0x40_0000;
+    const HEADERS_SIZE: u64 = 64 + 56;
+    let size = HEADERS_SIZE + HELPER_CODE.len() as u64;
+
+    let mut program = b"\x7fELF\x02\x01\x01\0\0\0\0\0\0\0\0\0".to_vec();
+    program.extend_from_slice(&2u16.to_le_bytes());                         // ET_EXEC
+    program.extend_from_slice(&EM_NATIVE.to_le_bytes());
+    program.extend_from_slice(&1u32.to_le_bytes());                         // EV_CURRENT
+    program.extend_from_slice(&(BASE_ADDRESS + HEADERS_SIZE).to_le_bytes()); // entry point
+    program.extend_from_slice(&64u64.to_le_bytes());                        // program headers
+    program.extend_from_slice(&0u64.to_le_bytes());                         // section headers
+    program.extend_from_slice(&0u32.to_le_bytes());                         // flags
+    for &field in &[64u16, 56, 1, 64, 0, 0] {
+        program.extend_from_slice(&field.to_le_bytes());
+    }
+
+    program.extend_from_slice(&1u32.to_le_bytes());                         // PT_LOAD
+    program.extend_from_slice(&5u32.to_le_bytes());                         // PF_R | PF_X
+    for &field in &[0, BASE_ADDRESS, BASE_ADDRESS, size, size, 0x1000] {
+        program.extend_from_slice(&field.to_le_bytes());
+    }
+
+    program.extend_from_slice(&HELPER_CODE);
+    program
+}
+
+#[cfg(target_os="linux")]
+fn exec_profile(program: &Path) -> Profile {
+    Profile::new(vec![
+        Operation::ProcessExec(PathPattern::Literal(program.to_path_buf())),
+    ]).unwrap()
+}
+
+/// Runs `program` in place of this process, returning only if that fails.
+#[cfg(target_os="linux")]
+fn exec(program: &Path) -> io::Error {
+    let c_program = CString::new(program.as_os_str().as_bytes()).unwrap();
+    let argv = [c_program.as_ptr(), ptr::null()];
+    let envp: [*const libc::c_char; 1] = [ptr::null()];
+    unsafe {
+        libc::execve(c_program.as_ptr(), argv.as_ptr(), envp.as_ptr());
+    }
+    io::Error::last_os_error()
+}
+
+/// Runs `program` in a new process and returns the status it exits with.
+#[cfg(target_os="linux")]
+fn run(program: &Path) -> libc::c_int {
+    unsafe {
+        let pid = libc::fork();
+        assert!(pid >= 0, "couldn't fork: {}", io::Error::last_os_error());
+        if pid == 0 {
+            exec(program);
+            libc::_exit(127)
+        }
+        let mut status = 0;
+        assert_eq!(libc::waitpid(pid, &mut status, 0), pid);
+        assert!(libc::WIFEXITED(status), "{} didn't exit: {}", program.display(), status);
+        libc::WEXITSTATUS(status)
+    }
+}
+
+#[cfg(target_os="linux")]
+pub fn main() {
+    let helper = test_harness::temp_file("GAOL_HELPER", &helper_program());
+    if !test_harness::in_child() {
+        fs::set_permissions(&helper, Permissions::from_mode(0o755)).unwrap();
+    }
+
+    assert_allowed!(test_harness::run_in_sandbox(exec_profile(&helper), "allowance_test", || {
+        assert_eq!(run(&helper), HELPER_EXIT_CODE as libc::c_int)
+    }));
+
+    // The filter allows running anything, but the jail only holds the helper.
+    assert_allowed!(test_harness::run_in_sandbox(exec_profile(&helper), "shell_test", || {
+        assert_eq!(exec(Path::new("/bin/sh")).raw_os_error(), Some(libc::ENOENT))
+    }));
+
+    assert_violation!(test_harness::run_in_sandbox(Profile::new(Vec::new()).unwrap(),
+                                                   "prohibition_test",
+                                                   || {
+        exec(&helper);
+    }));
+
+    // Unless the profile says to fail prohibited system calls instead.
+    let failing = Profile::new(Vec::new()).unwrap();
+    let failing = failing.with_violation_action(ViolationAction::Errno(libc::EPERM));
+    assert_allowed!(test_harness::run_in_sandbox(failing, "failure_test", || {
+        assert_eq!(exec(&helper).raw_os_error(), Some(libc::EPERM))
+    }));
+
+    // A dynamically linked program needs its loader and libraries in the jail. What they do to
+    // start it isn't all allowed, so this only logs it.
+    if capabilities().seccomp_ret_log {
+        let program = PathBuf::from("/bin/true");
+        let profile = exec_profile(&program).with_violation_action(ViolationAction::Log);
+        assert_allowed!(test_harness::run_in_sandbox(profile, "dynamic_test", || {
+            assert_eq!(run(&program), 0)
+        }));
+    }
+
+    if !test_harness::in_child() {
+        fs::remove_file(&helper).unwrap();
+    }
+}
+
+#[cfg(not(target_os="linux"))]
+fn main() {}
@@ -0,0 +1,99 @@
+// Any copyright is dedicated to the Public Domain.
+// http://creativecommons.org/publicdomain/zero/1.0/
+
+extern crate gaol;
+
+use gaol::profile::{AddressPattern, Operation, PathPattern, Profile, ProfileBuilder};
+use gaol::profile::{ProfileError, ViolationAction};
+use std::path::PathBuf;
+
+fn test_matches_hand_built() {
+    let built = ProfileBuilder::new()
+        .allow_file_read_subpath("/usr")
+        .allow_file_read("/etc/hosts")
+        .allow_file_write_subpath("/var/tmp")
+        .allow_network_outbound()
+        .violation_action(ViolationAction::Errno(1))
+        .build()
+        .unwrap();
+    let expected = Profile::new(vec![
+        Operation::FileReadAll(PathPattern::Subpath(PathBuf::from("/usr"))),
+        Operation::FileReadAll(PathPattern::Literal(PathBuf::from("/etc/hosts"))),
+        Operation::FileWriteAll(PathPattern::Subpath(PathBuf::from("/var/tmp"))),
+        Operation::NetworkOutbound(AddressPattern::All),
+    ]).unwrap().with_violation_action(ViolationAction::Errno(1));
+    assert_eq!(built, expected);
+}
+
+fn test_conditions_and_extend() {
+    let mut network = ProfileBuilder::new();
+    network.allow_udp_outbound(53);
+    let mut builder = ProfileBuilder::new();
+    builder.allow_file_read_subpath("/usr")
+           .allow_if(false, Operation::FileWriteAll(PathPattern::Subpath(PathBuf::from("/tmp"))))
+           .allow_if(true, Operation::FileReadAll(PathPattern::Subpath(PathBuf::from("/lib"))))
+           .extend(&network);
+    assert_eq!(builder.build().unwrap(), Profile::new(vec![
+        Operation::FileReadAll(PathPattern::Subpath(PathBuf::from("/usr"))),
+        Operation::FileReadAll(PathPattern::Subpath(PathBuf::from("/lib"))),
+        Operation::NetworkOutbound(AddressPattern::Udp(53)),
+    ]).unwrap());
+}
+
+fn test_normalization() {
+    let built = ProfileBuilder::new()
+        .allow_file_read_subpath("/usr/./lib/")
+        .allow_file_read_subpath("/usr/lib")
+        .allow(Operation::FileReadAll(PathPattern::Glob("/opt/*/".to_owned())))
+        .build()
+        .unwrap();
+    assert_eq!(built.allowed_operations(), &[
+        Operation::FileReadAll(PathPattern::Subpath(PathBuf::from("/usr/lib"))),
+        Operation::FileReadAll(PathPattern::Glob("/opt/*".to_owned())),
+    ][..]);
+
+    let relative = ProfileBuilder::new().allow_file_read_subpath("usr/lib/").build();
+    assert_eq!(relative, Err(ProfileError::RelativePath(
+        Operation::FileReadAll(PathPattern::Subpath(PathBuf::from("usr/lib"))))));
+}
+
+fn test_from_profile() {
+    let profile = Profile::new(vec![
+        Operation::FileReadAll(PathPattern::Subpath(PathBuf::from("/usr"))),
+        Operation::SystemInfoRead,
+    ]).unwrap().with_violation_action(ViolationAction::Errno(1));
+    assert_eq!(ProfileBuilder::from_profile(&profile).build().unwrap(), profile);
+
+    let widened = ProfileBuilder::from_profile(&profile)
+        .allow_network_outbound()
+        .build()
+        .unwrap();
+    assert_eq!(widened.allowed_operations(), &[
+        Operation::FileReadAll(PathPattern::Subpath(PathBuf::from("/usr"))),
+        Operation::SystemInfoRead,
+        Operation::NetworkOutbound(AddressPattern::All),
+    ][..]);
+    assert_eq!(widened.violation_action(), ViolationAction::Errno(1));
+    assert_eq!(profile.allowed_operations().len(), 2);
+}
+
+/// A builder can't turn an fd-only profile into a looser one with a jail.
+fn test_from_fd_only_profile() {
+    let rebuilt = ProfileBuilder::from_profile(&Profile::fd_only()).build().unwrap();
+    assert!(rebuilt.is_fd_only());
+    assert_eq!(rebuilt, Profile::fd_only());
+
+    let widened = ProfileBuilder::from_profile(&Profile::fd_only())
+        .allow_network_outbound()
+        .build();
+    assert_eq!(widened.unwrap_err(),
+               ProfileError::FdOnlyOperation(Operation::NetworkOutbound(AddressPattern::All)));
+}
+
+pub fn main() {
+    test_matches_hand_built();
+    test_conditions_and_extend();
+    test_normalization();
+    test_from_profile();
+    test_from_fd_only_profile();
+}
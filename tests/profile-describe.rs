@@ -0,0 +1,113 @@
+// Any copyright is dedicated to the Public Domain.
+// http://creativecommons.org/publicdomain/zero/1.0/
+
+extern crate gaol;
+
+use gaol::profile::{AddressPattern, Describe, Operation, PathPattern, Profile};
+use std::net::IpAddr;
+use std::path::PathBuf;
+
+fn test_operation_descriptions() {
+    let cases = vec![
+        (Operation::FileReadAll(PathPattern::Literal(PathBuf::from("/etc/hostname"))),
+         "read /etc/hostname"),
+        (Operation::FileReadAll(PathPattern::Subpath(PathBuf::from("/usr/share/fonts"))),
+         "read files under /usr/share/fonts"),
+        (Operation::FileReadAll(PathPattern::Glob("/usr/lib/*.so".to_owned())),
+         "read files matching /usr/lib/*.so"),
+        (Operation::FileReadMetadata(PathPattern::Literal(PathBuf::from("/etc/hostname"))),
+         "read metadata of /etc/hostname"),
+        (Operation::FileReadMetadata(PathPattern::Subpath(PathBuf::from("/etc"))),
+         "read metadata of files under /etc"),
+        (Operation::FileWriteMetadata(PathPattern::Literal(PathBuf::from("/srv/archive"))),
+         "change the permissions, ownership, and timestamps of /srv/archive"),
+        (Operation::FileAppend(PathPattern::Literal(PathBuf::from("/var/log/app.log"))),
+         "append to /var/log/app.log"),
+        (Operation::FileAppend(PathPattern::Subpath(PathBuf::from("/var/log"))),
+         "append to files under /var/log"),
+        (Operation::ProcessExec(PathPattern::Literal(PathBuf::from("/usr/bin/ffprobe"))),
+         "run /usr/bin/ffprobe"),
+        (Operation::ProcessExec(PathPattern::Subpath(PathBuf::from("/usr/libexec/app"))),
+         "run programs under /usr/libexec/app"),
+        (Operation::NetworkOutbound(AddressPattern::All),
+         "create sockets and make outbound network connections"),
+        (Operation::NetworkOutbound(AddressPattern::Tcp(443)),
+         "make outbound TCP connections to port 443"),
+        (Operation::NetworkOutbound(AddressPattern::Udp(53)), "send UDP datagrams to port 53"),
+        (Operation::NetworkOutbound(AddressPattern::IpCidr {
+            addr: IpAddr::from([10, 0, 0, 0]),
+            prefix_len: 8,
+            port: None,
+        }), "make outbound network connections to 10.0.0.0/8"),
+        (Operation::NetworkOutbound(AddressPattern::IpCidr {
+            addr: "2001:db8::".parse().unwrap(),
+            prefix_len: 32,
+            port: Some(443),
+        }), "make outbound network connections to port 443 in 2001:db8::/32"),
+        (Operation::NetworkInbound(AddressPattern::All),
+         "create sockets and accept inbound network connections"),
+        (Operation::NetworkInbound(AddressPattern::Tcp(8080)),
+         "accept inbound TCP connections on port 8080"),
+        (Operation::NetworkOutbound(AddressPattern::LocalSocket(PathBuf::from("/run/socket"))),
+         "connect to the local socket at /run/socket"),
+        (Operation::SystemInfoRead, "read system information"),
+        (Operation::StandardDevices, "read and write /dev/null, /dev/zero, and /dev/urandom"),
+    ];
+    for (operation, expected) in cases {
+        assert_eq!(operation.describe(), expected);
+    }
+}
+
+#[cfg(target_os="linux")]
+fn test_platform_descriptions() {
+    let read = gaol::platform::linux::syscalls::syscall_nr("read").unwrap();
+    assert_eq!(Operation::PlatformSpecific(gaol::platform::Operation::Syscall(read)).describe(),
+               "make the system call read");
+    assert_eq!(Operation::PlatformSpecific(gaol::platform::Operation::Syscall(100_000)).describe(),
+               "make system call number 100000");
+    assert_eq!(Operation::PlatformSpecific(gaol::platform::Operation::MountProc).describe(),
+               "read a /proc of its own");
+}
+
+#[cfg(target_os="macos")]
+fn test_platform_descriptions() {
+    let operation = gaol::platform::Operation::MachLookup(b"com.apple.FontServer".to_vec());
+    assert_eq!(Operation::PlatformSpecific(operation).describe(),
+               "look up the Mach service com.apple.FontServer");
+}
+
+#[cfg(not(any(target_os="linux", target_os="macos")))]
+fn test_platform_descriptions() {}
+
+fn test_profile_display() {
+    assert_eq!(Profile::new(Vec::new()).unwrap().to_string(),
+               "perform only the operations every profile allows");
+
+    // Descriptions are sorted and deduplicated regardless of the order of the operations.
+    let operations = vec![
+        Operation::SystemInfoRead,
+        Operation::NetworkOutbound(AddressPattern::All),
+        Operation::FileReadAll(PathPattern::Subpath(PathBuf::from("/usr/share/fonts"))),
+        Operation::FileReadAll(PathPattern::Literal(PathBuf::from("/etc/hostname"))),
+        Operation::SystemInfoRead,
+    ];
+    let mut reversed = operations.clone();
+    reversed.reverse();
+    if let (Ok(profile), Ok(reversed)) = (Profile::new(operations), Profile::new(reversed)) {
+        let expected = "create sockets and make outbound network connections\n\
+                        read /etc/hostname\n\
+                        read files under /usr/share/fonts\n\
+                        read system information";
+        assert_eq!(profile.to_string(), expected);
+        assert_eq!(reversed.to_string(), expected);
+
+        // `Debug` stays structural.
+        assert!(format!("{:?}", profile).starts_with("Profile { allowed_operations: ["));
+    }
+}
+
+pub fn main() {
+    test_operation_descriptions();
+    test_platform_descriptions();
+    test_profile_display();
+}
@@ -0,0 +1,241 @@
+// Any copyright is dedicated to the Public Domain.
+// http://creativecommons.org/publicdomain/zero/1.0/
+
+extern crate gaol;
+
+use gaol::profile::{AddressPattern, Operation, PathPattern, Profile, ProfileDiff};
+use std::net::IpAddr;
+use std::path::PathBuf;
+
+fn literal(path: &str) -> PathPattern {
+    PathPattern::Literal(PathBuf::from(path))
+}
+
+fn subpath(path: &str) -> PathPattern {
+    PathPattern::Subpath(PathBuf::from(path))
+}
+
+fn glob(glob: &str) -> PathPattern {
+    PathPattern::Glob(glob.to_owned())
+}
+
+fn local_socket(path: &str) -> AddressPattern {
+    AddressPattern::LocalSocket(PathBuf::from(path))
+}
+
+fn cidr(addr: &str, prefix_len: u8, port: Option<u16>) -> AddressPattern {
+    AddressPattern::IpCidr { addr: addr.parse::<IpAddr>().unwrap(), prefix_len, port }
+}
+
+/// `(a, b, a contains b)` for every interesting pair of path patterns.
+fn path_matrix() -> Vec<(PathPattern, PathPattern, bool)> {
+    vec![
+        (literal("/usr"), literal("/usr"), true),
+        (literal("/usr"), literal("/usr/share"), false),
+        (literal("/usr"), subpath("/usr"), false),
+        (literal("/usr/share"), subpath("/usr"), false),
+        (subpath("/usr"), subpath("/usr"), true),
+        (subpath("/usr"), subpath("/usr/share"), true),
+        (subpath("/usr/share"), subpath("/usr"), false),
+        (subpath("/usr"), literal("/usr"), true),
+        (subpath("/usr"), literal("/usr/share/dict/words"), true),
+        (subpath("/usr"), literal("/etc"), false),
+        // Containment is by component, not by string prefix.
+        (subpath("/usr"), subpath("/usrlocal"), false),
+        (subpath("/usr"), literal("/usrlocal"), false),
+        (subpath("/"), subpath("/usr"), true),
+        (glob("/usr/lib/*.so"), literal("/usr/lib/libc.so"), true),
+        (glob("/usr/lib/*.so"), literal("/usr/lib/sub/libc.so"), false),
+        (glob("/usr/lib/*.so"), literal("/usr/lib/libc.so.6"), false),
+        (glob("/tmp/servo-*"), literal("/tmp/servo-1234"), true),
+        (glob("/tmp/a\\*b"), literal("/tmp/a*b"), true),
+        (glob("/tmp/a\\*b"), literal("/tmp/axb"), false),
+        (glob("/usr/lib/*.so"), glob("/usr/lib/*.so"), true),
+        (glob("/usr/*"), subpath("/usr/lib"), false),
+        (subpath("/usr"), glob("/usr/lib/*.so"), true),
+        (subpath("/usr/lib/x"), glob("/usr/lib/*/x"), false),
+        (literal("/usr/lib/libc.so"), glob("/usr/lib/*.so"), false),
+    ]
+}
+
+/// `(a, b, a contains b)` for every interesting pair of address patterns.
+fn address_matrix() -> Vec<(AddressPattern, AddressPattern, bool)> {
+    vec![
+        (AddressPattern::All, AddressPattern::All, true),
+        (AddressPattern::All, AddressPattern::Tcp(443), true),
+        (AddressPattern::All, local_socket("/run/socket"), true),
+        (AddressPattern::Tcp(443), AddressPattern::All, false),
+        (AddressPattern::Tcp(443), AddressPattern::Tcp(443), true),
+        (AddressPattern::Tcp(443), AddressPattern::Tcp(80), false),
+        (AddressPattern::Tcp(443), local_socket("/run/socket"), false),
+        (AddressPattern::All, AddressPattern::Udp(53), true),
+        (AddressPattern::Udp(53), AddressPattern::Udp(53), true),
+        (AddressPattern::Udp(53), AddressPattern::Udp(443), false),
+        (AddressPattern::Udp(443), AddressPattern::Tcp(443), false),
+        (AddressPattern::Tcp(443), AddressPattern::Udp(443), false),
+        (local_socket("/run/socket"), local_socket("/run/socket"), true),
+        (local_socket("/run/socket"), local_socket("/run/other"), false),
+        (local_socket("/run/socket"), AddressPattern::All, false),
+        (AddressPattern::All, cidr("10.0.0.0", 8, None), true),
+        (cidr("10.0.0.0", 8, None), cidr("10.1.0.0", 16, None), true),
+        (cidr("10.1.0.0", 16, None), cidr("10.0.0.0", 8, None), false),
+        (cidr("10.0.0.0", 8, None), cidr("11.0.0.0", 8, None), false),
+        (cidr("10.0.0.0", 8, None), cidr("::", 8, None), false),
+        (cidr("10.0.0.0", 8, None), cidr("10.0.0.0", 8, Some(443)), true),
+        (cidr("10.0.0.0", 8, Some(443)), cidr("10.0.0.0", 8, None), false),
+        (cidr("2001:db8::", 32, None), cidr("2001:db8:1::", 48, None), true),
+        (cidr("10.0.0.0", 8, None), AddressPattern::Tcp(443), false),
+    ]
+}
+
+/// `(a, b, a contains b)` for every interesting pair of operations.
+fn operation_matrix() -> Vec<(Operation, Operation, bool)> {
+    vec![
+        (Operation::FileReadAll(subpath("/usr")),
+         Operation::FileReadAll(literal("/usr/bin/env")),
+         true),
+        (Operation::FileReadAll(subpath("/usr")),
+         Operation::FileReadMetadata(subpath("/usr/share")),
+         true),
+        (Operation::FileReadMetadata(subpath("/usr")),
+         Operation::FileReadAll(subpath("/usr")),
+         false),
+        (Operation::FileReadMetadata(subpath("/usr")),
+         Operation::FileReadMetadata(literal("/usr/lib")),
+         true),
+        (Operation::FileReadAll(literal("/etc/hostname")),
+         Operation::FileReadMetadata(subpath("/etc")),
+         false),
+        (Operation::FileWriteAll(subpath("/var/log")),
+         Operation::FileAppend(literal("/var/log/app.log")),
+         true),
+        (Operation::FileAppend(subpath("/var/log")),
+         Operation::FileWriteAll(literal("/var/log/app.log")),
+         false),
+        (Operation::NetworkOutbound(AddressPattern::All),
+         Operation::NetworkOutbound(AddressPattern::Tcp(443)),
+         true),
+        (Operation::NetworkOutbound(AddressPattern::Tcp(443)),
+         Operation::NetworkOutbound(AddressPattern::All),
+         false),
+        (Operation::NetworkInbound(AddressPattern::All),
+         Operation::NetworkInbound(AddressPattern::Tcp(8080)),
+         true),
+        (Operation::NetworkOutbound(AddressPattern::All),
+         Operation::NetworkInbound(AddressPattern::Tcp(8080)),
+         false),
+        (Operation::SystemInfoRead, Operation::SystemInfoRead, true),
+        (Operation::SystemInfoRead, Operation::FileReadAll(subpath("/")), false),
+        (Operation::FileReadAll(subpath("/")), Operation::SystemInfoRead, false),
+        (Operation::NetworkOutbound(AddressPattern::All),
+         Operation::FileReadAll(subpath("/")),
+         false),
+    ]
+}
+
+fn test_containment_matrix() {
+    for (this, that, expected) in path_matrix() {
+        assert_eq!(this.contains(&that), expected, "{:?} contains {:?}", this, that);
+    }
+    for (this, that, expected) in address_matrix() {
+        assert_eq!(this.contains(&that), expected, "{:?} contains {:?}", this, that);
+    }
+    for (this, that, expected) in operation_matrix() {
+        assert_eq!(this.contains(&that), expected, "{:?} contains {:?}", this, that);
+    }
+}
+
+/// `(self, other, expected diff)` for profiles that can be built on this platform. Cases whose
+/// profiles can't be built here are skipped.
+fn diff_table() -> Vec<(Vec<Operation>, Vec<Operation>, ProfileDiff)> {
+    vec![
+        (vec![], vec![], ProfileDiff::default()),
+        (vec![Operation::FileReadAll(subpath("/usr")), Operation::SystemInfoRead],
+         vec![Operation::SystemInfoRead, Operation::FileReadAll(subpath("/usr"))],
+         ProfileDiff::default()),
+        (vec![Operation::FileReadAll(subpath("/usr"))],
+         vec![Operation::FileReadAll(subpath("/usr/share"))],
+         ProfileDiff {
+             narrowed: vec![(Operation::FileReadAll(subpath("/usr")),
+                             Operation::FileReadAll(subpath("/usr/share")))],
+             ..ProfileDiff::default()
+         }),
+        (vec![Operation::FileReadAll(literal("/usr/bin/env"))],
+         vec![Operation::FileReadAll(subpath("/usr"))],
+         ProfileDiff {
+             widened: vec![(Operation::FileReadAll(literal("/usr/bin/env")),
+                            Operation::FileReadAll(subpath("/usr")))],
+             ..ProfileDiff::default()
+         }),
+        (vec![Operation::NetworkOutbound(AddressPattern::Tcp(443))],
+         vec![Operation::NetworkOutbound(AddressPattern::All)],
+         ProfileDiff {
+             widened: vec![(Operation::NetworkOutbound(AddressPattern::Tcp(443)),
+                            Operation::NetworkOutbound(AddressPattern::All))],
+             ..ProfileDiff::default()
+         }),
+        (vec![Operation::FileReadAll(subpath("/usr")), Operation::SystemInfoRead],
+         vec![Operation::FileReadAll(subpath("/etc"))],
+         ProfileDiff {
+             only_in_self: vec![Operation::FileReadAll(subpath("/usr")),
+                                Operation::SystemInfoRead],
+             only_in_other: vec![Operation::FileReadAll(subpath("/etc"))],
+             ..ProfileDiff::default()
+         }),
+        // One operation can be narrowed into several.
+        (vec![Operation::FileReadAll(subpath("/usr"))],
+         vec![Operation::FileReadAll(subpath("/usr/lib")),
+              Operation::FileReadAll(subpath("/usr/share"))],
+         ProfileDiff {
+             narrowed: vec![(Operation::FileReadAll(subpath("/usr")),
+                             Operation::FileReadAll(subpath("/usr/lib"))),
+                            (Operation::FileReadAll(subpath("/usr")),
+                             Operation::FileReadAll(subpath("/usr/share")))],
+             ..ProfileDiff::default()
+         }),
+    ]
+}
+
+fn test_diff_table() {
+    for (before, after, expected) in diff_table() {
+        let (before, after) = match (Profile::new(before), Profile::new(after)) {
+            (Ok(before), Ok(after)) => (before, after),
+            _ => continue,
+        };
+        let diff = before.diff(&after);
+        assert_eq!(diff, expected, "{:?} -> {:?}", before, after);
+        assert_eq!(diff.is_empty(), expected == ProfileDiff::default());
+
+        // Diffing the other way round swaps the sides.
+        let reverse = after.diff(&before);
+        assert_eq!(reverse.only_in_self, expected.only_in_other);
+        assert_eq!(reverse.only_in_other, expected.only_in_self);
+        assert_eq!(reverse.narrowed.len(), expected.widened.len());
+        assert_eq!(reverse.widened.len(), expected.narrowed.len());
+    }
+}
+
+fn test_display() {
+    assert_eq!(ProfileDiff::default().to_string(), "profiles allow the same operations");
+
+    let diff = ProfileDiff {
+        only_in_self: vec![Operation::SystemInfoRead],
+        only_in_other: vec![],
+        narrowed: vec![(Operation::FileReadAll(subpath("/usr")),
+                        Operation::FileReadAll(subpath("/usr/share")))],
+        widened: vec![(Operation::NetworkOutbound(AddressPattern::Tcp(443)),
+                       Operation::NetworkOutbound(AddressPattern::All))],
+    };
+    assert_eq!(diff.to_string(),
+               "1 removed, 0 added, 1 narrowed, 1 widened\n\
+                \x20 - SystemInfoRead\n\
+                \x20 < FileReadAll(Subpath(\"/usr\")) narrowed to \
+                FileReadAll(Subpath(\"/usr/share\"))\n\
+                \x20 > NetworkOutbound(Tcp(443)) widened to NetworkOutbound(All)");
+}
+
+pub fn main() {
+    test_containment_matrix();
+    test_diff_table();
+    test_display();
+}
@@ -0,0 +1,139 @@
+// Any copyright is dedicated to the Public Domain.
+// http://creativecommons.org/publicdomain/zero/1.0/
+
+extern crate gaol;
+
+use gaol::profile::{AddressPattern, DecodeError, Operation, OperationSupport};
+use gaol::profile::{OperationSupportLevel, PathPattern, Profile, ViolationAction};
+use gaol::sandbox::{ChildSandbox, ResourceLimits};
+use gaol::test_harness;
+use std::env;
+use std::error::Error;
+use std::ffi::OsStr;
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+use std::os::unix::ffi::OsStrExt;
+use std::net::IpAddr;
+use std::os::unix::io::IntoRawFd;
+use std::path::PathBuf;
+
+/// A profile with every operation this platform can allow.
+fn profile() -> Profile {
+    let mut operations = vec![
+        Operation::FileReadAll(PathPattern::Literal(PathBuf::from("/etc/hosts"))),
+        Operation::FileReadAll(PathPattern::Subpath(PathBuf::from("/usr/share"))),
+        Operation::FileReadAll(PathPattern::Glob("/usr/lib/*.so".to_owned())),
+        Operation::FileReadMetadata(PathPattern::Subpath(PathBuf::from("/var"))),
+        Operation::FileWriteAll(PathPattern::Subpath(PathBuf::from("/var/tmp"))),
+        Operation::FileWriteMetadata(PathPattern::Subpath(PathBuf::from("/srv/restore"))),
+        Operation::FileAppend(PathPattern::Subpath(PathBuf::from("/srv/log"))),
+        Operation::ProcessExec(PathPattern::Literal(PathBuf::from("/usr/bin/ffprobe"))),
+        Operation::NetworkOutbound(AddressPattern::Tcp(443)),
+        Operation::NetworkOutbound(AddressPattern::Udp(53)),
+        Operation::NetworkOutbound(AddressPattern::IpCidr {
+            addr: IpAddr::from([10, 0, 0, 0]),
+            prefix_len: 8,
+            port: Some(443),
+        }),
+        Operation::NetworkOutbound(AddressPattern::IpCidr {
+            addr: "2001:db8::".parse().unwrap(),
+            prefix_len: 32,
+            port: None,
+        }),
+        Operation::NetworkOutbound(AddressPattern::LocalSocket(PathBuf::from("/tmp/socket"))),
+        Operation::NetworkOutbound(AddressPattern::All),
+        Operation::NetworkInbound(AddressPattern::Tcp(8080)),
+        Operation::NetworkInbound(AddressPattern::All),
+        Operation::SystemInfoRead,
+        Operation::StandardDevices,
+    ];
+    #[cfg(any(target_os="android", target_os="linux"))]
+    operations.push(Operation::PlatformSpecific(gaol::platform::Operation::Syscall(0)));
+    #[cfg(any(target_os="android", target_os="linux"))]
+    operations.push(Operation::PlatformSpecific(gaol::platform::Operation::MountProc));
+    #[cfg(target_os="macos")]
+    operations.push(Operation::PlatformSpecific(
+        gaol::platform::Operation::MachLookup(b"com.apple.FontServer".to_vec())));
+    operations.retain(|operation| {
+        matches!(operation.support(),
+                 OperationSupportLevel::NeverAllowed | OperationSupportLevel::CanBeAllowed)
+    });
+    Profile::new(operations).unwrap()
+}
+
+fn test_round_trip() {
+    for profile in &[
+        Profile::new(Vec::new()).unwrap(),
+        profile(),
+        profile().with_violation_action(ViolationAction::Errno(1)),
+        profile().with_violation_action(ViolationAction::Log),
+        profile().with_resource_limits(ResourceLimits {
+            max_memory_bytes: Some(1 << 40),
+            max_file_size_bytes: Some(0),
+            ..ResourceLimits::default()
+        }),
+        Profile::fd_only(),
+    ] {
+        assert_eq!(Profile::from_bytes(&profile.to_bytes()).as_ref(), Ok(profile));
+    }
+}
+
+fn test_non_utf8_path() {
+    // Latin-1 "café", which isn't valid UTF-8.
+    let path = PathBuf::from(OsStr::from_bytes(b"/srv/caf\xe9"));
+    let profile = Profile::new(vec![
+        Operation::FileReadAll(PathPattern::Subpath(path.clone())),
+    ]).unwrap();
+    let decoded = Profile::from_bytes(&profile.to_bytes()).unwrap();
+    assert_eq!(decoded.allowed_operations(),
+               &[Operation::FileReadAll(PathPattern::Subpath(path))][..]);
+}
+
+fn test_damage_detected() {
+    let bytes = profile().to_bytes();
+    for index in 5..bytes.len() {
+        let mut tampered = bytes.clone();
+        tampered[index] ^= 0x20;
+        assert_eq!(Profile::from_bytes(&tampered), Err(DecodeError::ChecksumMismatch));
+    }
+
+    let mut bad_magic = bytes.clone();
+    bad_magic[0] = b'X';
+    assert_eq!(Profile::from_bytes(&bad_magic), Err(DecodeError::BadMagic));
+    let mut bad_version = bytes.clone();
+    bad_version[4] = 99;
+    assert_eq!(Profile::from_bytes(&bad_version), Err(DecodeError::UnsupportedVersion(99)));
+    assert_eq!(Profile::from_bytes(&bytes[..6]), Err(DecodeError::Truncated));
+}
+
+/// Passes `bytes` to `ChildSandbox::from_environment()` the way `Sandbox::start()` would.
+fn child_sandbox_from(bytes: &[u8]) -> io::Result<ChildSandbox> {
+    let path = test_harness::temp_file("GAOL_TEMP_PROFILE", b"");
+    let mut file = OpenOptions::new().read(true).write(true).truncate(true).open(&path).unwrap();
+    file.write_all(bytes).unwrap();
+    env::set_var("GAOL_PROFILE_FD", file.into_raw_fd().to_string());
+    ChildSandbox::from_environment()
+}
+
+fn test_from_environment() {
+    let bytes = profile().to_bytes();
+    assert!(child_sandbox_from(&bytes).is_ok());
+
+    let mut tampered = bytes;
+    let last = tampered.len() - 1;
+    tampered[last] ^= 1;
+    let err = child_sandbox_from(&tampered).err().unwrap();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    let source = err.get_ref().and_then(|err| err.downcast_ref::<DecodeError>());
+    assert_eq!(source, Some(&DecodeError::ChecksumMismatch), "{:?}", err.source());
+
+    let err = ChildSandbox::from_environment().err().unwrap();
+    assert_eq!(err.kind(), io::ErrorKind::NotFound);
+}
+
+pub fn main() {
+    test_round_trip();
+    test_non_utf8_path();
+    test_damage_detected();
+    test_from_environment();
+}
@@ -0,0 +1,120 @@
+// Any copyright is dedicated to the Public Domain.
+// http://creativecommons.org/publicdomain/zero/1.0/
+
+extern crate gaol;
+
+use gaol::profile::{AddressPattern, Operation, PathPattern, Profile, ProfileError};
+use gaol::profile::ViolationAction;
+use gaol::sandbox::ResourceLimits;
+use std::path::PathBuf;
+
+fn read_subpath(path: &str) -> Operation {
+    Operation::FileReadAll(PathPattern::Subpath(PathBuf::from(path)))
+}
+
+fn read_metadata(path: &str) -> Operation {
+    Operation::FileReadMetadata(PathPattern::Literal(PathBuf::from(path)))
+}
+
+fn write_literal(path: &str) -> Operation {
+    Operation::FileWriteAll(PathPattern::Literal(PathBuf::from(path)))
+}
+
+fn test_deduplication() {
+    let base = Profile::new(vec![read_subpath("/tmp"), Operation::SystemInfoRead]).unwrap()
+        .with_violation_action(ViolationAction::Errno(1));
+    let plugin = Profile::new(vec![
+        Operation::NetworkOutbound(AddressPattern::All),
+        read_subpath("/tmp"),
+    ]).unwrap();
+    let merged = base.merge(&plugin).unwrap();
+    assert_eq!(merged.allowed_operations(), &[
+        read_subpath("/tmp"),
+        Operation::SystemInfoRead,
+        Operation::NetworkOutbound(AddressPattern::All),
+    ][..]);
+    assert_eq!(merged.violation_action(), ViolationAction::Errno(1));
+    assert!(merged.is_superset_of(&base));
+    assert!(merged.is_superset_of(&plugin));
+    assert!(!base.is_superset_of(&merged));
+
+    // Neither profile is changed.
+    assert_eq!(base.allowed_operations().len(), 2);
+    assert_eq!(plugin.allowed_operations().len(), 2);
+    assert_eq!(base.merge(&base).unwrap().allowed_operations(), base.allowed_operations());
+}
+
+fn test_conflict() {
+    let base = Profile::new(vec![read_subpath("/tmp")]).unwrap();
+    let plugin = Profile::new(vec![write_literal("/tmp/foo")]).unwrap();
+    assert_eq!(base.merge(&plugin).unwrap_err(),
+               ProfileError::OverlappingPatterns(read_subpath("/tmp"), write_literal("/tmp/foo")));
+
+    // Reading a file's metadata can't be allowed precisely everywhere.
+    if let Ok(plugin) = Profile::new(vec![read_metadata("/tmp/foo")]) {
+        assert_eq!(base.merge(&plugin).unwrap_err(),
+                   ProfileError::OverlappingPatterns(read_subpath("/tmp"),
+                                                     read_metadata("/tmp/foo")));
+    }
+}
+
+fn test_intersection() {
+    let first = Profile::new(vec![
+        read_subpath("/usr"),
+        Operation::NetworkOutbound(AddressPattern::All),
+        Operation::SystemInfoRead,
+    ]).unwrap();
+    let second = Profile::new(vec![
+        Operation::SystemInfoRead,
+        read_subpath("/usr/share"),
+        Operation::NetworkOutbound(AddressPattern::All),
+    ]).unwrap();
+    let common = first.intersection(&second);
+    assert_eq!(common.allowed_operations(), &[
+        Operation::NetworkOutbound(AddressPattern::All),
+        Operation::SystemInfoRead,
+    ][..]);
+    assert!(first.is_superset_of(&common));
+    assert!(second.is_superset_of(&common));
+    assert!(common.validate().is_ok());
+
+    // `/usr` contains `/usr/share`, so the first profile covers the second, but not the reverse.
+    assert!(first.is_superset_of(&second));
+    assert!(!second.is_superset_of(&first));
+
+    assert!(first.intersection(&Profile::fd_only()).is_fd_only());
+    assert!(first.intersection(&Profile::fd_only()).allowed_operations().is_empty());
+    assert!(!first.merge(&Profile::fd_only()).unwrap().is_fd_only());
+}
+
+/// The intersection allows no more than either profile, so it keeps the limits of both.
+fn test_intersection_resource_limits() {
+    let unlimited = Profile::new(vec![Operation::SystemInfoRead]).unwrap();
+    let limited = Profile::new(vec![Operation::SystemInfoRead]).unwrap()
+        .with_resource_limits(ResourceLimits {
+            max_file_size_bytes: Some(1 << 20),
+            ..ResourceLimits::default()
+        });
+    assert_eq!(unlimited.intersection(&limited).resource_limits(), limited.resource_limits());
+    assert_eq!(limited.intersection(&unlimited).resource_limits(), limited.resource_limits());
+    assert_eq!(unlimited.intersection(&unlimited).resource_limits(), None);
+
+    let also_limited = Profile::new(vec![Operation::SystemInfoRead]).unwrap()
+        .with_resource_limits(ResourceLimits {
+            max_file_size_bytes: Some(1 << 30),
+            max_open_files: Some(64),
+            ..ResourceLimits::default()
+        });
+    assert_eq!(limited.intersection(&also_limited).resource_limits(), Some(&ResourceLimits {
+        max_file_size_bytes: Some(1 << 20),
+        max_open_files: Some(64),
+        ..ResourceLimits::default()
+    }));
+}
+
+pub fn main() {
+    test_deduplication();
+    test_conflict();
+    test_intersection();
+    test_intersection_resource_limits();
+}
@@ -0,0 +1,103 @@
+// Any copyright is dedicated to the Public Domain.
+// http://creativecommons.org/publicdomain/zero/1.0/
+
+//! Children get the `ResourceLimits` of their profile, alongside those of their sandbox.
+
+#[cfg(target_os="linux")]
+extern crate gaol;
+#[cfg(target_os="linux")]
+extern crate libc;
+
+#[cfg(target_os="linux")]
+use gaol::platform::process::ExitStatus;
+#[cfg(target_os="linux")]
+use gaol::profile::Profile;
+#[cfg(target_os="linux")]
+use gaol::sandbox::{ChildSandbox, ChildSandboxMethods, Command};
+#[cfg(target_os="linux")]
+use gaol::sandbox::{ResourceLimits, Sandbox, SandboxMethods};
+#[cfg(target_os="linux")]
+use std::env;
+#[cfg(target_os="linux")]
+use std::hint;
+#[cfg(target_os="linux")]
+use std::time::{Duration, Instant};
+
+/// Set in the child's environment to what the child should do.
+#[cfg(target_os="linux")]
+const PROFILE_LIMITS_CHILD_VAR: &str = "GAOL_PROFILE_LIMITS_CHILD";
+
+#[cfg(target_os="linux")]
+const MEMORY_LIMIT: u64 = 256 << 20;
+
+/// The code a child that got `SIGXCPU` exits with.
+#[cfg(target_os="linux")]
+const GOT_SIGXCPU_CODE: i32 = 24;
+
+#[cfg(target_os="linux")]
+extern "C" fn handle_sigxcpu(_: libc::c_int) {
+    unsafe {
+        libc::_exit(GOT_SIGXCPU_CODE)
+    }
+}
+
+#[cfg(target_os="linux")]
+fn child(mode: &str) {
+    if mode == "cpu-handled" {
+        unsafe {
+            libc::signal(libc::SIGXCPU, handle_sigxcpu as *const () as libc::sighandler_t);
+        }
+    }
+    ChildSandbox::from_environment().unwrap().activate().unwrap();
+    match mode {
+        "memory" => {
+            let mut memory: Vec<u8> = Vec::new();
+            assert!(memory.try_reserve_exact(MEMORY_LIMIT as usize).is_err());
+        }
+        "cpu" | "cpu-handled" => loop {
+            hint::black_box(0);
+        },
+        "compliant" => {
+            // A little memory and CPU time, well within the limits.
+            let memory = vec![1u8; 1 << 20];
+            let start = Instant::now();
+            while start.elapsed() < Duration::from_millis(100) {
+                hint::black_box(&memory);
+            }
+        }
+        _ => panic!("unexpected mode {}", mode),
+    }
+}
+
+#[cfg(target_os="linux")]
+fn run(mode: &str, limits: ResourceLimits) -> ExitStatus {
+    let mut command = Command::me().unwrap();
+    command.env(PROFILE_LIMITS_CHILD_VAR, mode);
+    let profile = Profile::new(Vec::new()).unwrap().with_resource_limits(limits);
+    Sandbox::new(profile).start(&mut command).unwrap().wait().unwrap()
+}
+
+#[cfg(target_os="linux")]
+pub fn main() {
+    if let Ok(mode) = env::var(PROFILE_LIMITS_CHILD_VAR) {
+        return child(&mode)
+    }
+
+    let limits = ResourceLimits {
+        max_memory_bytes: Some(MEMORY_LIMIT),
+        max_cpu_seconds: Some(1),
+        max_open_files: Some(64),
+        max_file_size_bytes: Some(0),
+        ..ResourceLimits::default()
+    };
+    assert_eq!(run("memory", limits), ExitStatus::Code(0));
+    assert_eq!(run("cpu-handled", limits), ExitStatus::Code(GOT_SIGXCPU_CODE));
+
+    // As PID 1 of its PID namespace, a child that doesn't handle `SIGXCPU` lives on until the
+    // hard limit a second later.
+    assert_eq!(run("cpu", limits), ExitStatus::Signal(libc::SIGKILL));
+    assert_eq!(run("compliant", limits), ExitStatus::Code(0));
+}
+
+#[cfg(not(target_os="linux"))]
+pub fn main() {}
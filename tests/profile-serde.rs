@@ -0,0 +1,96 @@
+// Any copyright is dedicated to the Public Domain.
+// http://creativecommons.org/publicdomain/zero/1.0/
+
+extern crate gaol;
+extern crate serde_json;
+
+use gaol::profile::{AddressPattern, Operation, OperationSupport, OperationSupportLevel};
+use gaol::profile::{PathPattern, Profile, ViolationAction};
+use std::path::PathBuf;
+
+/// One operation of every variant.
+fn operations() -> Vec<Operation> {
+    let mut operations = vec![
+        Operation::FileReadAll(PathPattern::Literal(PathBuf::from("/etc/hosts"))),
+        Operation::FileReadMetadata(PathPattern::Subpath(PathBuf::from("/var"))),
+        Operation::FileWriteAll(PathPattern::Glob("/var/tmp/*.log".to_owned())),
+        Operation::FileWriteMetadata(PathPattern::Subpath(PathBuf::from("/srv/restore"))),
+        Operation::FileAppend(PathPattern::Subpath(PathBuf::from("/srv/log"))),
+        Operation::NetworkOutbound(AddressPattern::All),
+        Operation::NetworkOutbound(AddressPattern::Tcp(443)),
+        Operation::NetworkOutbound(AddressPattern::Udp(53)),
+        Operation::NetworkOutbound(AddressPattern::LocalSocket(PathBuf::from("/tmp/socket"))),
+        Operation::NetworkInbound(AddressPattern::Tcp(8080)),
+        Operation::SystemInfoRead,
+        Operation::StandardDevices,
+    ];
+    #[cfg(any(target_os="android", target_os="linux"))]
+    operations.push(Operation::PlatformSpecific(gaol::platform::Operation::Syscall(0)));
+    #[cfg(any(target_os="android", target_os="linux"))]
+    operations.push(Operation::PlatformSpecific(gaol::platform::Operation::MountProc));
+    #[cfg(target_os="macos")]
+    operations.push(Operation::PlatformSpecific(
+        gaol::platform::Operation::MachLookup(b"com.apple.FontServer".to_vec())));
+    operations
+}
+
+fn test_operations_round_trip() {
+    for operation in operations() {
+        let json = serde_json::to_string(&operation).unwrap();
+        assert_eq!(serde_json::from_str::<Operation>(&json).unwrap(), operation, "{}", json);
+    }
+}
+
+fn test_profile_round_trip() {
+    let mut operations = operations();
+    operations.retain(|operation| {
+        matches!(operation.support(),
+                 OperationSupportLevel::NeverAllowed | OperationSupportLevel::CanBeAllowed)
+    });
+    for profile in &[
+        Profile::new(operations).unwrap(),
+        Profile::new(Vec::new()).unwrap().with_violation_action(ViolationAction::Errno(1)),
+        Profile::fd_only(),
+    ] {
+        let json = serde_json::to_string(profile).unwrap();
+        assert_eq!(&serde_json::from_str::<Profile>(&json).unwrap(), profile, "{}", json);
+    }
+}
+
+fn test_hand_written() {
+    let profile: Profile = serde_json::from_str(r#"{
+        "allowed_operations": [
+            {"FileReadAll": {"Subpath": "/usr"}},
+            {"NetworkOutbound": "All"}
+        ]
+    }"#).unwrap();
+    assert_eq!(profile, Profile::new(vec![
+        Operation::FileReadAll(PathPattern::Subpath(PathBuf::from("/usr"))),
+        Operation::NetworkOutbound(AddressPattern::All),
+    ]).unwrap());
+}
+
+fn test_rejected() {
+    // Overlapping patterns are rejected as `Profile::new()` rejects them.
+    let overlapping = serde_json::from_str::<Profile>(r#"{
+        "allowed_operations": [
+            {"FileReadAll": {"Subpath": "/usr"}},
+            {"FileWriteAll": {"Subpath": "/usr/share"}}
+        ]
+    }"#);
+    assert!(overlapping.is_err());
+
+    let foreign = serde_json::from_str::<Operation>(r#"{"PlatformSpecific": "ff"}"#);
+    let message = foreign.unwrap_err().to_string();
+    assert!(message.contains("specific to another platform"), "{}", message);
+
+    let malformed = serde_json::from_str::<Operation>(r#"{"PlatformSpecific": "0"}"#);
+    assert!(malformed.is_err());
+}
+
+pub fn main() {
+    test_operations_round_trip();
+    test_profile_round_trip();
+    test_hand_written();
+    test_rejected();
+}
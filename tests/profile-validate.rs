@@ -0,0 +1,152 @@
+// Any copyright is dedicated to the Public Domain.
+// http://creativecommons.org/publicdomain/zero/1.0/
+
+extern crate gaol;
+
+use gaol::profile::{AddressPattern, Operation, PathPattern, Profile, ProfileError};
+use std::net::IpAddr;
+use std::path::PathBuf;
+
+fn literal(path: &str) -> PathPattern {
+    PathPattern::Literal(PathBuf::from(path))
+}
+
+fn subpath(path: &str) -> PathPattern {
+    PathPattern::Subpath(PathBuf::from(path))
+}
+
+fn glob(glob: &str) -> PathPattern {
+    PathPattern::Glob(glob.to_owned())
+}
+
+/// `(a, b, a overlaps b)` for every interesting pair of path patterns.
+fn path_matrix() -> Vec<(PathPattern, PathPattern, bool)> {
+    vec![
+        (literal("/usr"), literal("/usr"), true),
+        (literal("/usr"), literal("/etc"), false),
+        (literal("/usr/bin/env"), subpath("/usr"), true),
+        (subpath("/usr"), literal("/usr/bin/env"), true),
+        (subpath("/usr"), literal("/usr"), true),
+        (literal("/usr"), subpath("/usr/bin"), false),
+        (subpath("/usr"), subpath("/usr/share"), true),
+        (subpath("/usr/share"), subpath("/usr"), true),
+        (subpath("/usr"), subpath("/etc"), false),
+        // Overlap is by component, not by string prefix.
+        (subpath("/usr"), subpath("/usrlocal"), false),
+        (subpath("/usr"), literal("/usrlocal"), false),
+        (glob("/usr/lib/*.so"), literal("/usr/lib/libc.so"), true),
+        (literal("/usr/lib/libc.so.6"), glob("/usr/lib/*.so"), false),
+        (glob("/usr/lib/*.so"), subpath("/usr"), true),
+        (subpath("/usr/lib/libc.so"), glob("/usr/lib/*.so"), true),
+        (glob("/usr/lib/*.so"), subpath("/usr/lib/sub"), false),
+        (glob("/usr/lib/*.so"), subpath("/etc"), false),
+        (glob("/usr/lib/*.so"), glob("/usr/lib/libc.*"), true),
+        (glob("/usr/lib/*.so"), glob("/usr/lib/*.a"), true),
+        (glob("/usr/lib/*.so"), glob("/usr/lib/sub/*.so"), false),
+        (glob("/usr/lib/*.so"), glob("/usr/share/*.so"), false),
+    ]
+}
+
+fn test_overlap_matrix() {
+    for (a, b, expected) in path_matrix() {
+        assert_eq!(a.overlaps(&b), expected, "{:?} overlaps {:?}", a, b);
+    }
+}
+
+fn test_operation_overlaps() {
+    let read_all = Operation::FileReadAll(subpath("/foo"));
+    assert!(read_all.overlaps(&Operation::FileReadMetadata(subpath("/foo/bar"))));
+    assert!(Operation::FileReadMetadata(subpath("/foo/bar")).overlaps(&read_all));
+    assert!(read_all.overlaps(&Operation::FileWriteAll(subpath("/"))));
+    assert!(!read_all.overlaps(&Operation::FileReadMetadata(subpath("/bar"))));
+    assert!(!read_all.overlaps(&Operation::SystemInfoRead));
+
+    // Patterns of the same kind of operation may be nested.
+    assert!(!read_all.overlaps(&Operation::FileReadAll(literal("/foo/bar"))));
+    assert!(!read_all.overlaps(&Operation::FileReadAll(subpath("/"))));
+
+    // Reads of a directory may overlap metadata writes of part of it.
+    assert!(!read_all.overlaps(&Operation::FileWriteMetadata(subpath("/foo/bar"))));
+    assert!(!Operation::FileWriteMetadata(subpath("/foo")).overlaps(&read_all));
+    assert!(Operation::FileWriteMetadata(subpath("/foo"))
+                .overlaps(&Operation::FileWriteAll(literal("/foo/bar"))));
+}
+
+fn test_new_accepts_compatible_nesting() {
+    let profile = Profile::new(vec![
+        Operation::FileReadAll(subpath("/usr")),
+        Operation::FileReadAll(literal("/usr/bin/env")),
+        Operation::FileReadAll(literal("/etc/hosts")),
+        Operation::FileWriteAll(subpath("/var/tmp")),
+        Operation::FileWriteMetadata(subpath("/usr/local")),
+    ]).unwrap();
+    assert_eq!(profile.validate(), Ok(()));
+}
+
+fn test_new_rejects_literal_in_subpath() {
+    assert_eq!(Profile::new(vec![
+        Operation::FileReadAll(subpath("/usr")),
+        Operation::FileWriteAll(literal("/usr/bin/env")),
+    ]).err(), Some(ProfileError::OverlappingPatterns(
+        Operation::FileReadAll(subpath("/usr")),
+        Operation::FileWriteAll(literal("/usr/bin/env")))));
+}
+
+fn test_new_rejects_nested_subpaths() {
+    assert_eq!(Profile::new(vec![
+        Operation::FileReadAll(subpath("/usr")),
+        Operation::FileWriteAll(subpath("/usr/share")),
+    ]).err(), Some(ProfileError::OverlappingPatterns(
+        Operation::FileReadAll(subpath("/usr")),
+        Operation::FileWriteAll(subpath("/usr/share")))));
+}
+
+fn test_new_rejects_duplicates() {
+    assert_eq!(Profile::new(vec![
+        Operation::FileReadAll(literal("/etc/hosts")),
+        Operation::FileReadAll(literal("/etc/hosts")),
+    ]).err(), Some(ProfileError::DuplicateOperation(
+        Operation::FileReadAll(literal("/etc/hosts")))));
+}
+
+fn test_new_rejects_relative_paths() {
+    for pattern in [literal("etc/hosts"), subpath("usr"), glob("*.so")] {
+        assert_eq!(Profile::new(vec![Operation::FileReadAll(pattern.clone())]).err(),
+                   Some(ProfileError::RelativePath(Operation::FileReadAll(pattern))));
+    }
+}
+
+fn test_new_rejects_long_prefixes() {
+    for (addr, prefix_len) in [("10.0.0.0", 33), ("2001:db8::", 129)] {
+        let operation = Operation::NetworkOutbound(AddressPattern::IpCidr {
+            addr: addr.parse::<IpAddr>().unwrap(),
+            prefix_len,
+            port: None,
+        });
+        assert_eq!(Profile::new(vec![operation.clone()]).err(),
+                   Some(ProfileError::InvalidPrefixLength(operation)));
+    }
+}
+
+fn test_display() {
+    let error = ProfileError::OverlappingPatterns(Operation::FileReadAll(subpath("/usr")),
+                                                  Operation::FileWriteAll(literal("/usr/bin/env")));
+    assert_eq!(error.to_string(),
+               "the operations to read files under /usr and to write /usr/bin/env overlap");
+    let error = ProfileError::DuplicateOperation(Operation::FileReadAll(literal("/etc/hosts")));
+    assert_eq!(error.to_string(), "the operation to read /etc/hosts appears more than once");
+    let error = ProfileError::RelativePath(Operation::FileReadAll(subpath("usr")));
+    assert_eq!(error.to_string(), "the operation to read files under usr has a relative path");
+}
+
+pub fn main() {
+    test_overlap_matrix();
+    test_operation_overlaps();
+    test_new_accepts_compatible_nesting();
+    test_new_rejects_literal_in_subpath();
+    test_new_rejects_nested_subpaths();
+    test_new_rejects_duplicates();
+    test_new_rejects_relative_paths();
+    test_new_rejects_long_prefixes();
+    test_display();
+}
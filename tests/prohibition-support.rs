@@ -0,0 +1,105 @@
+// Any copyright is dedicated to the Public Domain.
+// http://creativecommons.org/publicdomain/zero/1.0/
+
+extern crate gaol;
+
+use gaol::profile::{AddressPattern, Operation, OperationSupport, PathPattern};
+use gaol::profile::ProhibitionSupport;
+use std::path::PathBuf;
+
+/// One operation of every variant, without the platform-specific ones.
+fn operations() -> Vec<Operation> {
+    vec![
+        Operation::FileReadAll(PathPattern::Subpath(PathBuf::from("/usr"))),
+        Operation::FileReadMetadata(PathPattern::Literal(PathBuf::from("/etc/hostname"))),
+        Operation::FileWriteAll(PathPattern::Subpath(PathBuf::from("/var/tmp"))),
+        Operation::FileWriteMetadata(PathPattern::Subpath(PathBuf::from("/srv/restore"))),
+        Operation::FileAppend(PathPattern::Literal(PathBuf::from("/var/log/app.log"))),
+        Operation::ProcessExec(PathPattern::Literal(PathBuf::from("/usr/bin/ffprobe"))),
+        Operation::NetworkOutbound(AddressPattern::All),
+        Operation::NetworkOutbound(AddressPattern::Tcp(443)),
+        Operation::NetworkOutbound(AddressPattern::Udp(53)),
+        Operation::NetworkOutbound(AddressPattern::LocalSocket(PathBuf::from("/run/socket"))),
+        Operation::NetworkInbound(AddressPattern::All),
+        Operation::NetworkInbound(AddressPattern::Tcp(8080)),
+        Operation::SystemInfoRead,
+        Operation::StandardDevices,
+    ]
+}
+
+#[cfg(any(target_os="android", target_os="linux"))]
+fn expected() -> Vec<(Operation, ProhibitionSupport)> {
+    let supports = [
+        ProhibitionSupport::Enforced,
+        ProhibitionSupport::EnforcedCoarsely,
+        ProhibitionSupport::Enforced,
+        ProhibitionSupport::Enforced,
+        ProhibitionSupport::Enforced,
+        ProhibitionSupport::Enforced,
+        ProhibitionSupport::Enforced,
+        ProhibitionSupport::EnforcedCoarsely,
+        ProhibitionSupport::Enforced,
+        ProhibitionSupport::EnforcedCoarsely,
+        ProhibitionSupport::Enforced,
+        ProhibitionSupport::EnforcedCoarsely,
+        ProhibitionSupport::Enforced,
+        ProhibitionSupport::Enforced,
+    ];
+    let mut expected: Vec<_> = operations().into_iter().zip(supports.iter().cloned()).collect();
+    expected.push((Operation::PlatformSpecific(gaol::platform::Operation::Syscall(0)),
+                   ProhibitionSupport::Enforced));
+    expected.push((Operation::PlatformSpecific(gaol::platform::Operation::MountProc),
+                   ProhibitionSupport::Enforced));
+    expected
+}
+
+#[cfg(target_os="macos")]
+fn expected() -> Vec<(Operation, ProhibitionSupport)> {
+    let mut expected: Vec<_> = operations().into_iter().map(|operation| {
+        let support = match operation {
+            Operation::FileAppend(_) => ProhibitionSupport::EnforcedCoarsely,
+            _ => ProhibitionSupport::Enforced,
+        };
+        (operation, support)
+    }).collect();
+    expected.push((Operation::PlatformSpecific(gaol::platform::Operation::MachLookup(
+                       b"com.apple.FontServer".to_vec())),
+                   ProhibitionSupport::Enforced));
+    expected
+}
+
+#[cfg(any(target_os="freebsd", target_os="openbsd"))]
+fn expected() -> Vec<(Operation, ProhibitionSupport)> {
+    operations().into_iter().map(|operation| {
+        let support = match operation {
+            Operation::SystemInfoRead => ProhibitionSupport::NotEnforced,
+            _ => ProhibitionSupport::Enforced,
+        };
+        (operation, support)
+    }).collect()
+}
+
+#[cfg(target_os="windows")]
+fn expected() -> Vec<(Operation, ProhibitionSupport)> {
+    operations().into_iter().map(|operation| {
+        let support = match operation {
+            Operation::FileReadMetadata(_) |
+            Operation::NetworkInbound(_) |
+            Operation::SystemInfoRead => ProhibitionSupport::NotEnforced,
+            _ => ProhibitionSupport::Enforced,
+        };
+        (operation, support)
+    }).collect()
+}
+
+pub fn main() {
+    for (operation, support) in expected() {
+        assert_eq!(operation.prohibition_support(), support, "{:?}", operation);
+
+        // Operations a profile could contain must agree with how precisely they can be allowed.
+        if support == ProhibitionSupport::EnforcedCoarsely {
+            assert_eq!(operation.support(),
+                       gaol::profile::OperationSupportLevel::CannotBeAllowedPrecisely);
+        }
+    }
+}
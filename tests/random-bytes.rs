@@ -0,0 +1,62 @@
+// Any copyright is dedicated to the Public Domain.
+// http://creativecommons.org/publicdomain/zero/1.0/
+
+//! Seeding a `HashMap` and reading random bytes works under a profile that allows nothing, with
+//! no `/dev/urandom` to fall back to.
+
+#[cfg(target_os="linux")]
+#[macro_use]
+extern crate gaol;
+#[cfg(target_os="linux")]
+extern crate libc;
+
+#[cfg(target_os="linux")]
+use gaol::platform::linux::seccomp::Filter;
+#[cfg(target_os="linux")]
+use gaol::profile::Profile;
+#[cfg(target_os="linux")]
+use gaol::test_harness;
+#[cfg(target_os="linux")]
+use libc::c_void;
+#[cfg(target_os="linux")]
+use std::collections::HashMap;
+
+#[cfg(target_os="linux")]
+const SECCOMP_RET_ERRNO: u32 = 0x0005_0000;
+
+#[cfg(target_os="linux")]
+fn profile() -> Profile {
+    Profile::new(Vec::new()).unwrap()
+}
+
+#[cfg(target_os="linux")]
+fn read_random_bytes() {
+    let mut map = HashMap::new();
+    map.insert("gaol", 1);
+    assert_eq!(map["gaol"], 1);
+
+    for &flags in &[0, libc::GRND_NONBLOCK] {
+        let mut bytes = [0u8; 16];
+        let count = unsafe {
+            libc::getrandom(bytes.as_mut_ptr() as *mut c_void, bytes.len(), flags)
+        };
+        assert_eq!(count, 16);
+    }
+}
+
+#[cfg(target_os="linux")]
+pub fn main() {
+    assert_allowed!(test_harness::run_in_sandbox(profile(), "random_bytes", read_random_bytes));
+
+    // Other flags fail rather than violating the profile.
+    let filter = Filter::new(&profile());
+    for &flags in &[libc::GRND_RANDOM, libc::GRND_INSECURE] {
+        assert_eq!(filter.evaluate(libc::SYS_getrandom as u32, &[0, 16, flags as u64, 0, 0, 0]),
+                   SECCOMP_RET_ERRNO | libc::EINVAL as u32);
+    }
+}
+
+#[cfg(not(target_os="linux"))]
+pub fn main() {
+    // Currently unsupported on other platforms.
+}
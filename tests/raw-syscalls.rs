@@ -0,0 +1,50 @@
+// Any copyright is dedicated to the Public Domain.
+// http://creativecommons.org/publicdomain/zero/1.0/
+
+//! A profile can allow a system call gaol knows nothing else about, by name.
+
+#[cfg(target_os="linux")]
+#[macro_use]
+extern crate gaol;
+#[cfg(target_os="linux")]
+extern crate libc;
+
+#[cfg(target_os="linux")]
+use gaol::platform::linux::syscalls::{syscall_nr, UnknownSyscall};
+#[cfg(target_os="linux")]
+use gaol::profile::{Profile, ProfileBuilder};
+#[cfg(target_os="linux")]
+use gaol::test_harness;
+
+#[cfg(target_os="linux")]
+fn getppid() {
+    assert!(unsafe { libc::syscall(libc::SYS_getppid) } >= 0)
+}
+
+#[cfg(target_os="linux")]
+pub fn main() {
+    #[cfg(target_arch="x86_64")]
+    assert_eq!(syscall_nr("read"), Some(0));
+    #[cfg(any(target_arch="aarch64", target_arch="riscv64"))]
+    assert_eq!(syscall_nr("read"), Some(63));
+    assert_eq!(syscall_nr("getppid"), Some(libc::SYS_getppid as u32));
+    assert_eq!(syscall_nr("openat"), Some(libc::SYS_openat as u32));
+    assert_eq!(syscall_nr("sys_read"), None);
+    assert_eq!(syscall_nr(""), None);
+
+    let mut builder = ProfileBuilder::new();
+    assert_eq!(builder.allow_raw_syscall("no_such_call").err(),
+               Some(UnknownSyscall("no_such_call".to_owned())));
+    builder.allow_raw_syscall("getppid").unwrap();
+    let profile = builder.build().unwrap();
+
+    assert_allowed!(test_harness::run_in_sandbox(profile, "allowed", getppid));
+    assert_violation!(test_harness::run_in_sandbox(Profile::new(Vec::new()).unwrap(),
+                                                   "prohibited",
+                                                   getppid));
+}
+
+#[cfg(not(target_os="linux"))]
+pub fn main() {
+    // Only Linux profiles name system calls.
+}
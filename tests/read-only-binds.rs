@@ -0,0 +1,73 @@
+// Any copyright is dedicated to the Public Domain.
+// http://creativecommons.org/publicdomain/zero/1.0/
+
+//! Paths a profile only allows reading are bound into the jail read-only, mounts under them
+//! included, so writing them fails even without the seccomp filter.
+
+#[cfg(target_os="linux")]
+#[macro_use]
+extern crate gaol;
+#[cfg(target_os="linux")]
+extern crate libc;
+
+#[cfg(target_os="linux")]
+use gaol::profile::{Operation, PathPattern, Profile};
+#[cfg(target_os="linux")]
+use gaol::sandbox::SandboxOptions;
+#[cfg(target_os="linux")]
+use gaol::test_harness;
+#[cfg(target_os="linux")]
+use std::fs::{self, File, OpenOptions};
+#[cfg(target_os="linux")]
+use std::os::unix::fs::MetadataExt;
+#[cfg(target_os="linux")]
+use std::path::{Path, PathBuf};
+
+/// Whether `/dev/shm` is a mount of its own under `/dev`, as it usually is.
+#[cfg(target_os="linux")]
+fn shm_is_submount() -> bool {
+    match (fs::metadata("/dev"), fs::metadata("/dev/shm")) {
+        (Ok(dev), Ok(shm)) => dev.dev() != shm.dev(),
+        _ => false,
+    }
+}
+
+#[cfg(target_os="linux")]
+fn file() -> PathBuf {
+    test_harness::temp_file("file", b"original")
+}
+
+#[cfg(target_os="linux")]
+fn profile() -> Profile {
+    let mut operations = vec![Operation::FileReadAll(PathPattern::Literal(file()))];
+    if shm_is_submount() {
+        operations.push(Operation::FileReadAll(PathPattern::Subpath(PathBuf::from("/dev"))))
+    }
+    Profile::new(operations).unwrap()
+}
+
+#[cfg(target_os="linux")]
+fn assert_read_only(result: std::io::Result<File>) {
+    assert_eq!(result.err().and_then(|err| err.raw_os_error()), Some(libc::EROFS))
+}
+
+#[cfg(target_os="linux")]
+pub fn main() {
+    let file = file();
+    let shm_is_submount = shm_is_submount();
+    let mut options = SandboxOptions::new();
+    options.disable_seccomp().acknowledge_weakened_sandbox();
+    assert_allowed!(test_harness::run_in_sandbox_with_options(profile(), options, "write", || {
+        assert_read_only(OpenOptions::new().read(true).write(true).open(&file));
+        assert_eq!(fs::read(&file).unwrap(), b"original");
+        if shm_is_submount {
+            assert_read_only(File::create(Path::new("/dev/shm/gaol-read-only-binds")));
+        }
+    }));
+    assert_eq!(fs::read(&file).unwrap(), b"original");
+}
+
+#[cfg(not(target_os="linux"))]
+pub fn main() {
+    // Currently unsupported on other platforms.
+}
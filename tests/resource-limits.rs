@@ -0,0 +1,116 @@
+// Any copyright is dedicated to the Public Domain.
+// http://creativecommons.org/publicdomain/zero/1.0/
+
+//! Children get the `ResourceLimits` of their sandbox, and can't get past them.
+
+#[cfg(target_os="linux")]
+extern crate gaol;
+#[cfg(target_os="linux")]
+extern crate libc;
+
+#[cfg(target_os="linux")]
+use gaol::error::SandboxError;
+#[cfg(target_os="linux")]
+use gaol::platform::process::ExitStatus;
+#[cfg(target_os="linux")]
+use gaol::profile::{Operation, Profile};
+#[cfg(target_os="linux")]
+use gaol::sandbox::{ActivationError, ChildSandbox, ChildSandboxMethods, Command};
+#[cfg(target_os="linux")]
+use gaol::sandbox::{ResourceLimits, Sandbox, SandboxMethods};
+#[cfg(target_os="linux")]
+use std::env;
+#[cfg(target_os="linux")]
+use std::hint;
+#[cfg(target_os="linux")]
+use std::mem;
+
+/// Set in the child's environment to what the child should do.
+#[cfg(target_os="linux")]
+const RESOURCE_LIMITS_CHILD_VAR: &str = "GAOL_RESOURCE_LIMITS_CHILD";
+
+#[cfg(target_os="linux")]
+const MEMORY_LIMIT: u64 = 256 << 20;
+
+#[cfg(target_os="linux")]
+fn getrlimit(resource: libc::__rlimit_resource_t) -> (u64, u64) {
+    let mut rlimit: libc::rlimit = unsafe {
+        mem::zeroed()
+    };
+    assert_eq!(unsafe { libc::getrlimit(resource, &mut rlimit) }, 0);
+    (rlimit.rlim_cur as u64, rlimit.rlim_max as u64)
+}
+
+#[cfg(target_os="linux")]
+fn child(mode: &str) {
+    let result = ChildSandbox::from_environment().unwrap().activate();
+    if mode == "raise" {
+        match result {
+            Err(ActivationError::RestrictionFailed(SandboxError::ResourceLimitFailed(_))) => {
+                return
+            }
+            _ => panic!("unexpected result {:?}", result),
+        }
+    }
+    result.unwrap();
+    match mode {
+        "read" => {
+            assert_eq!(getrlimit(libc::RLIMIT_NOFILE), (64, 64));
+            assert_eq!(getrlimit(libc::RLIMIT_NPROC), (1024, 1024));
+            assert_eq!(getrlimit(libc::RLIMIT_AS), (MEMORY_LIMIT, MEMORY_LIMIT));
+        }
+        "memory" => {
+            let mut memory: Vec<u8> = Vec::new();
+            assert!(memory.try_reserve_exact(MEMORY_LIMIT as usize).is_err());
+
+            // Allocating it anyway aborts the child.
+            memory.resize(MEMORY_LIMIT as usize, 1);
+            hint::black_box(memory);
+        }
+        "cpu" => loop {
+            hint::black_box(0);
+        },
+        _ => panic!("unexpected mode {}", mode),
+    }
+}
+
+#[cfg(target_os="linux")]
+fn run(mode: &str, operations: Vec<Operation>, limits: ResourceLimits) -> ExitStatus {
+    let mut command = Command::me().unwrap();
+    command.env(RESOURCE_LIMITS_CHILD_VAR, mode);
+    let sandbox = Sandbox::new(Profile::new(operations).unwrap()).with_resource_limits(limits);
+    sandbox.start(&mut command).unwrap().wait().unwrap()
+}
+
+#[cfg(target_os="linux")]
+pub fn main() {
+    if let Ok(mode) = env::var(RESOURCE_LIMITS_CHILD_VAR) {
+        return child(&mode)
+    }
+
+    let limits = ResourceLimits {
+        max_memory_bytes: Some(MEMORY_LIMIT),
+        max_open_files: Some(64),
+        max_processes: Some(1024),
+        ..ResourceLimits::default()
+    };
+    assert_eq!(run("read", vec![Operation::SystemInfoRead], limits), ExitStatus::Code(0));
+    let status = run("memory", Vec::new(), limits);
+    assert!(matches!(status, ExitStatus::Signal(_)), "unexpected status {:?}", status);
+
+    let limits = ResourceLimits {
+        max_cpu_seconds: Some(1),
+        ..ResourceLimits::default()
+    };
+    assert_eq!(run("cpu", Vec::new(), limits), ExitStatus::Signal(libc::SIGKILL));
+
+    // Raising a hard limit needs privileges that the child doesn't have.
+    let limits = ResourceLimits {
+        max_open_files: Some(1 << 40),
+        ..ResourceLimits::default()
+    };
+    assert_eq!(run("raise", Vec::new(), limits), ExitStatus::Code(0));
+}
+
+#[cfg(not(target_os="linux"))]
+pub fn main() {}
@@ -0,0 +1,52 @@
+// Any copyright is dedicated to the Public Domain.
+// http://creativecommons.org/publicdomain/zero/1.0/
+
+#[cfg(target_os="windows")]
+#[macro_use]
+extern crate gaol;
+
+#[cfg(target_os="windows")]
+use gaol::platform::windows::granted_path;
+#[cfg(target_os="windows")]
+use gaol::profile::{Operation, PathPattern, Profile};
+#[cfg(target_os="windows")]
+use gaol::sandbox::{ChildSandbox, ChildSandboxMethods};
+#[cfg(target_os="windows")]
+use gaol::test_harness;
+#[cfg(target_os="windows")]
+use std::fs;
+#[cfg(target_os="windows")]
+use std::io;
+#[cfg(target_os="windows")]
+use std::path::{Path, PathBuf};
+
+#[cfg(target_os="windows")]
+fn profile(path: &Path) -> Profile {
+    Profile::new(vec![Operation::FileReadAll(PathPattern::Literal(path.to_path_buf()))]).unwrap()
+}
+
+#[cfg(target_os="windows")]
+fn test_translation() {
+    let subpath = Operation::FileReadAll(PathPattern::Subpath(PathBuf::from(r"C:\Data")));
+    let literal = Operation::FileReadAll(PathPattern::Literal(PathBuf::from(r"C:\Data\a.txt")));
+    assert_eq!(granted_path(&subpath), Some((Path::new(r"C:\Data"), true)));
+    assert_eq!(granted_path(&literal), Some((Path::new(r"C:\Data\a.txt"), false)));
+    assert_eq!(granted_path(&Operation::SystemInfoRead), None);
+}
+
+#[cfg(target_os="windows")]
+pub fn main() {
+    test_translation();
+
+    let allowed = test_harness::temp_file("GAOL_TOKEN_ALLOWED_FILE", b"super secret\n");
+    let hidden = test_harness::temp_file("GAOL_TOKEN_HIDDEN_FILE", b"top secret\n");
+    assert_allowed!(test_harness::run_in_sandbox(profile(&allowed), "restricted_token_test", || {
+        assert_eq!(fs::read(&allowed).unwrap(), b"super secret\n");
+        assert_eq!(fs::read(&hidden).unwrap_err().kind(), io::ErrorKind::PermissionDenied);
+        let report = ChildSandbox::new(profile(&allowed)).verify();
+        assert!(report.is_confined(), "{:?}", report.failures());
+    }));
+}
+
+#[cfg(not(target_os="windows"))]
+pub fn main() {}
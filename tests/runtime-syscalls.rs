@@ -0,0 +1,71 @@
+// Any copyright is dedicated to the Public Domain.
+// http://creativecommons.org/publicdomain/zero/1.0/
+
+//! What the C library and the Rust runtime do on their own, starting threads and reading the
+//! clock, works under a profile that allows nothing.
+
+#[cfg(target_os="linux")]
+#[macro_use]
+extern crate gaol;
+#[cfg(target_os="linux")]
+extern crate libc;
+
+#[cfg(target_os="linux")]
+use gaol::platform::linux::seccomp::Filter;
+#[cfg(target_os="linux")]
+use gaol::profile::Profile;
+#[cfg(target_os="linux")]
+use gaol::test_harness;
+#[cfg(target_os="linux")]
+use std::mem;
+#[cfg(target_os="linux")]
+use std::ptr;
+#[cfg(target_os="linux")]
+use std::thread;
+#[cfg(target_os="linux")]
+use std::time::{Instant, SystemTime};
+
+#[cfg(target_os="linux")]
+const SECCOMP_RET_ERRNO: u32 = 0x0005_0000;
+
+#[cfg(target_os="linux")]
+fn read_the_clock() {
+    assert!(SystemTime::now() > SystemTime::UNIX_EPOCH);
+    let start = Instant::now();
+    assert!(Instant::now() >= start);
+    unsafe {
+        // The system calls themselves, for when the vDSO can't answer.
+        let mut timeval: libc::timeval = mem::zeroed();
+        assert_eq!(libc::syscall(libc::SYS_gettimeofday, &mut timeval, ptr::null_mut::<u8>()), 0);
+        let mut timespec: libc::timespec = mem::zeroed();
+        assert_eq!(libc::syscall(libc::SYS_clock_gettime, libc::CLOCK_MONOTONIC, &mut timespec),
+                   0);
+        assert_eq!(libc::syscall(libc::SYS_clock_getres, libc::CLOCK_MONOTONIC, &mut timespec),
+                   0);
+    }
+}
+
+#[cfg(target_os="linux")]
+pub fn main() {
+    assert_allowed!(test_harness::run_in_sandbox(Profile::new(Vec::new()).unwrap(),
+                                                 "thread_test",
+                                                 || {
+        assert_eq!(thread::spawn(|| 42).join().unwrap(), 42)
+    }));
+    assert_allowed!(test_harness::run_in_sandbox(Profile::new(Vec::new()).unwrap(),
+                                                 "clock_test",
+                                                 read_the_clock));
+
+    // `clone3()` fails rather than violating the profile, so the caller falls back to `clone()`.
+    let filter = Filter::new(&Profile::new(Vec::new()).unwrap());
+    assert_eq!(filter.evaluate(libc::SYS_clone3 as u32, &[0; 6]),
+               SECCOMP_RET_ERRNO | libc::ENOSYS as u32);
+    for &syscall in &[libc::SYS_rseq, libc::SYS_set_tid_address, libc::SYS_set_robust_list] {
+        assert!(filter.allows(syscall as u32, &[0; 6]));
+    }
+}
+
+#[cfg(not(target_os="linux"))]
+pub fn main() {
+    // Currently unsupported on other platforms.
+}
@@ -0,0 +1,72 @@
+// Any copyright is dedicated to the Public Domain.
+// http://creativecommons.org/publicdomain/zero/1.0/
+
+//! A sandboxed child and its parent talk over the channel they were started with.
+
+#[cfg(target_os="linux")]
+extern crate gaol;
+
+#[cfg(target_os="linux")]
+use gaol::channel::ChildSandboxChannel;
+#[cfg(target_os="linux")]
+use gaol::platform::process::ExitStatus;
+#[cfg(target_os="linux")]
+use gaol::profile::Profile;
+#[cfg(target_os="linux")]
+use gaol::sandbox::{ChildSandbox, ChildSandboxMethods, Command, Sandbox, SandboxMethods};
+#[cfg(target_os="linux")]
+use std::env;
+#[cfg(target_os="linux")]
+use std::io::{ErrorKind, Read, Write};
+
+/// Set in the child's environment.
+#[cfg(target_os="linux")]
+const CHANNEL_CHILD_VAR: &str = "GAOL_CHANNEL_CHILD";
+
+/// Answers each message from the parent in upper case from inside the sandbox, then writes the
+/// raw bytes it's sent back reversed.
+#[cfg(target_os="linux")]
+fn child() {
+    let mut channel = ChildSandboxChannel::from_environment().unwrap();
+    ChildSandbox::from_environment().unwrap().activate().unwrap();
+    for _ in 0..2 {
+        let message = channel.recv_message().unwrap();
+        channel.send_message(&message.to_ascii_uppercase()).unwrap();
+    }
+    let mut bytes = [0; 4];
+    channel.read_exact(&mut bytes).unwrap();
+    bytes.reverse();
+    channel.write_all(&bytes).unwrap();
+}
+
+#[cfg(target_os="linux")]
+pub fn main() {
+    if env::var(CHANNEL_CHILD_VAR).is_ok() {
+        return child()
+    }
+
+    let error = ChildSandboxChannel::from_environment().err().unwrap();
+    assert_eq!(error.kind(), ErrorKind::NotFound);
+
+    let mut command = Command::me().unwrap();
+    command.env(CHANNEL_CHILD_VAR, "1");
+    let sandbox = Sandbox::new(Profile::new(Vec::new()).unwrap());
+    let (process, mut channel) = sandbox.start_with_channel(&mut command).unwrap();
+
+    channel.send_message(b"hello").unwrap();
+    assert_eq!(channel.recv_message().unwrap(), b"HELLO");
+    channel.send_message(b"").unwrap();
+    assert_eq!(channel.recv_message().unwrap(), b"");
+
+    channel.write_all(b"gaol").unwrap();
+    let mut bytes = [0; 4];
+    channel.read_exact(&mut bytes).unwrap();
+    assert_eq!(&bytes, b"loag");
+
+    // Once the child exits, the channel reports the end of the stream rather than blocking.
+    assert_eq!(process.wait().unwrap(), ExitStatus::Code(0));
+    assert_eq!(channel.recv_message().err().unwrap().kind(), ErrorKind::UnexpectedEof);
+}
+
+#[cfg(not(target_os="linux"))]
+pub fn main() {}
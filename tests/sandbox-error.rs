@@ -0,0 +1,74 @@
+// Any copyright is dedicated to the Public Domain.
+// http://creativecommons.org/publicdomain/zero/1.0/
+
+//! Callers can tell which restriction failed to apply, and why.
+
+extern crate gaol;
+
+use gaol::error::SandboxError;
+use gaol::sandbox::ActivationError;
+use std::error::Error;
+use std::io;
+use std::path::{Path, PathBuf};
+
+const EACCES: i32 = 13;
+const EINVAL: i32 = 22;
+const EPERM: i32 = 1;
+
+/// What a caller might do about each failure.
+fn advice(error: &ActivationError) -> &'static str {
+    match *error {
+        ActivationError::RestrictionFailed(SandboxError::NamespaceFailed(_)) => {
+            "start the process with Sandbox::start()"
+        }
+        ActivationError::RestrictionFailed(SandboxError::SeccompFailed(ref err))
+                if err.raw_os_error() == Some(EINVAL) => "upgrade the kernel",
+        ActivationError::RestrictionFailed(SandboxError::MountFailed { ref path, ref error })
+                if error.raw_os_error() == Some(EACCES) && path == Path::new("/srv") => {
+            "make /srv readable"
+        }
+        ActivationError::RestrictionFailed(_) => "give up",
+        _ => "try again",
+    }
+}
+
+pub fn main() {
+    let namespace = ActivationError::RestrictionFailed(SandboxError::NamespaceFailed(
+        io::Error::from_raw_os_error(EPERM)));
+    let seccomp = ActivationError::RestrictionFailed(SandboxError::SeccompFailed(
+        io::Error::from_raw_os_error(EINVAL)));
+    assert_eq!(advice(&namespace), "start the process with Sandbox::start()");
+    assert_eq!(advice(&seccomp), "upgrade the kernel");
+    assert_eq!(advice(&ActivationError::HookPanicked), "try again");
+    let mount = ActivationError::RestrictionFailed(SandboxError::MountFailed {
+        path: PathBuf::from("/srv"),
+        error: io::Error::from_raw_os_error(EACCES),
+    });
+    assert_eq!(advice(&mount), "make /srv readable");
+
+    // The operating system's error survives, as the source of the failure.
+    let source = seccomp.source().unwrap();
+    assert!(matches!(source.downcast_ref::<SandboxError>(), Some(SandboxError::SeccompFailed(_))));
+    let cause = source.source().unwrap().downcast_ref::<io::Error>().unwrap();
+    assert_eq!(cause.raw_os_error(), Some(EINVAL));
+    match namespace {
+        ActivationError::RestrictionFailed(ref error) => {
+            assert_eq!(error.raw_os_error(), Some(EPERM))
+        }
+        _ => panic!("unexpected error {:?}", namespace),
+    }
+
+    // Each names the restriction that failed.
+    assert!(namespace.to_string().contains("namespace"), "{}", namespace);
+    assert!(seccomp.to_string().contains("seccomp"), "{}", seccomp);
+    assert!(mount.to_string().contains("/srv couldn't be bound"), "{}", mount);
+    let cause = mount.source().unwrap().source().unwrap().downcast_ref::<io::Error>().unwrap();
+    assert_eq!(cause.raw_os_error(), Some(EACCES));
+
+    // Other errors are the platform's, without a number to match on.
+    let other = SandboxError::from(io::Error::other("no such sandbox"));
+    assert!(matches!(other, SandboxError::PlatformError(ref message)
+                            if message == "no such sandbox"));
+    assert_eq!(other.raw_os_error(), None);
+    assert!(other.source().is_none());
+}
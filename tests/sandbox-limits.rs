@@ -0,0 +1,117 @@
+// Any copyright is dedicated to the Public Domain.
+// http://creativecommons.org/publicdomain/zero/1.0/
+
+#[cfg(target_os="linux")]
+extern crate gaol;
+#[cfg(target_os="linux")]
+extern crate libc;
+
+#[cfg(target_os="linux")]
+use gaol::platform::linux::cleanup_stale_cgroups;
+#[cfg(target_os="linux")]
+use gaol::platform::process::ExitStatus;
+#[cfg(target_os="linux")]
+use gaol::profile::Profile;
+#[cfg(target_os="linux")]
+use gaol::sandbox::{Command, Sandbox, SandboxLimits, SandboxMethods, SandboxOptions};
+#[cfg(target_os="linux")]
+use gaol::test_harness;
+#[cfg(target_os="linux")]
+use std::fs;
+#[cfg(target_os="linux")]
+use std::io;
+#[cfg(target_os="linux")]
+use std::path::Path;
+
+#[cfg(target_os="linux")]
+const CGROUP_ROOT: &str = "/sys/fs/cgroup";
+
+#[cfg(target_os="linux")]
+const LIMITS: SandboxLimits = SandboxLimits {
+    memory_bytes: Some(256 << 20),
+    cpu_shares: Some(50),
+    pids_max: Some(16),
+};
+
+/// Returns how many groups this process has created and not removed.
+#[cfg(target_os="linux")]
+fn our_cgroups() -> usize {
+    let prefix = format!("gaol.{}.", std::process::id());
+    fs::read_dir(CGROUP_ROOT).unwrap().filter(|entry| {
+        entry.as_ref().unwrap().file_name().to_string_lossy().starts_with(&prefix)
+    }).count()
+}
+
+#[cfg(target_os="linux")]
+fn test_limits() {
+    let sandbox = Sandbox::new(Profile::new(Vec::new()).unwrap()).with_limits(LIMITS);
+    let result = sandbox.start(&mut Command::new("/bin/true"));
+    if !Path::new(CGROUP_ROOT).join("cgroup.controllers").exists() {
+        assert_eq!(result.err().map(|err| err.kind()), Some(io::ErrorKind::Unsupported));
+        return
+    }
+
+    match result {
+        Ok(process) => {
+            assert_eq!(our_cgroups(), 1);
+            assert_eq!(process.wait().unwrap(), ExitStatus::Code(0));
+        }
+        // Without the right to create groups, or with a controller the limits need disabled,
+        // starting fails rather than run the child without its limits.
+        Err(err) => assert_eq!(err.kind(), io::ErrorKind::Unsupported, "{}", err),
+    }
+    assert_eq!(our_cgroups(), 0);
+}
+
+/// A parent that isn't a cgroup v2 group can't hold the children's groups.
+#[cfg(target_os="linux")]
+fn test_unsupported_parent(directory: &Path) {
+    let mut options = SandboxOptions::new();
+    options.limits(LIMITS).cgroup_parent(directory);
+    let sandbox = Sandbox::with_options(Profile::new(Vec::new()).unwrap(), options);
+    let err = sandbox.start(&mut Command::new("/bin/true")).err().unwrap();
+    assert_eq!(err.kind(), io::ErrorKind::Unsupported);
+    assert_eq!(fs::read_dir(directory).unwrap().count(), 0);
+}
+
+/// Groups whose creator has died are removed, and no others.
+#[cfg(target_os="linux")]
+fn test_stale_cleanup(directory: &Path) {
+    let dead = unsafe {
+        libc::fork()
+    };
+    if dead == 0 {
+        unsafe {
+            libc::_exit(0)
+        }
+    }
+    let mut status = 0;
+    assert_eq!(unsafe { libc::waitpid(dead, &mut status, 0) }, dead);
+
+    let names = [
+        format!("gaol.{}.0123456789abcdef", dead),
+        format!("gaol.{}.0123456789abcdef", std::process::id()),
+        format!("other.{}", dead),
+    ];
+    for name in names.iter() {
+        fs::create_dir(directory.join(name)).unwrap();
+    }
+    assert_eq!(cleanup_stale_cgroups(directory).unwrap(), 1);
+    assert!(!directory.join(&names[0]).exists());
+    assert!(directory.join(&names[1]).exists() && directory.join(&names[2]).exists());
+    for name in names[1..].iter() {
+        fs::remove_dir(directory.join(name)).unwrap();
+    }
+}
+
+#[cfg(target_os="linux")]
+pub fn main() {
+    test_limits();
+    let directory = test_harness::temp_dir("GAOL_CGROUP_PARENT");
+    test_unsupported_parent(&directory);
+    test_stale_cleanup(&directory);
+    fs::remove_dir(&directory).unwrap();
+}
+
+#[cfg(not(target_os="linux"))]
+fn main() {}
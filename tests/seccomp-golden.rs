@@ -0,0 +1,100 @@
+// Any copyright is dedicated to the Public Domain.
+// http://creativecommons.org/publicdomain/zero/1.0/
+
+//! Regression tests pinning the seccomp-bpf program generated for a catalogue of profiles.
+//!
+//! Each profile's disassembled filter is compared against a checked-in golden file under
+//! `tests/golden/seccomp/<arch>/`, so any change to `Filter::new` shows up as a diff of BPF text.
+//! Comparisons with system call numbers carry the names of the system calls.
+//! Run with `GAOL_BLESS=1` to regenerate the golden files after an intentional change.
+
+#[cfg(target_os="linux")]
+extern crate gaol;
+
+#[cfg(target_os="linux")]
+use gaol::platform::linux::seccomp::Filter;
+#[cfg(target_os="linux")]
+use gaol::profile::{AddressPattern, Operation, PathPattern, Profile, ProfileError,
+                   ViolationAction};
+#[cfg(target_os="linux")]
+use gaol::test_harness;
+#[cfg(target_os="linux")]
+use std::env;
+#[cfg(target_os="linux")]
+use std::path::PathBuf;
+
+/// The profiles whose filters are pinned. Profiles that cannot be constructed on this platform
+/// are skipped.
+#[cfg(target_os="linux")]
+fn catalogue() -> Vec<(&'static str, Result<Profile,ProfileError>)> {
+    vec![
+        ("empty", Profile::new(Vec::new())),
+        ("read-only", Profile::new(vec![
+            Operation::FileReadAll(PathPattern::Subpath(PathBuf::from("/usr"))),
+        ])),
+        ("metadata-only", Profile::new(vec![
+            Operation::FileReadMetadata(PathPattern::Subpath(PathBuf::from("/etc"))),
+        ])),
+        ("read-write", Profile::new(vec![
+            Operation::FileReadAll(PathPattern::Subpath(PathBuf::from("/usr"))),
+            Operation::FileWriteAll(PathPattern::Subpath(PathBuf::from("/var/tmp"))),
+        ])),
+        ("write-metadata", Profile::new(vec![
+            Operation::FileReadAll(PathPattern::Subpath(PathBuf::from("/srv"))),
+            Operation::FileWriteMetadata(PathPattern::Subpath(PathBuf::from("/srv/restore"))),
+        ])),
+        ("append", Profile::new(vec![
+            Operation::FileReadAll(PathPattern::Subpath(PathBuf::from("/usr"))),
+            Operation::FileAppend(PathPattern::Subpath(PathBuf::from("/var/log"))),
+        ])),
+        ("process-exec", Profile::new(vec![
+            Operation::ProcessExec(PathPattern::Literal(PathBuf::from("/usr/bin/ffprobe"))),
+        ])),
+        ("errno", Profile::new(Vec::new()).map(|profile| {
+            profile.with_violation_action(ViolationAction::Errno(1))
+        })),
+        ("network", Profile::new(vec![
+            Operation::NetworkOutbound(AddressPattern::All),
+        ])),
+        ("udp", Profile::new(vec![
+            Operation::NetworkOutbound(AddressPattern::Udp(53)),
+        ])),
+        ("inbound", Profile::new(vec![
+            Operation::NetworkInbound(AddressPattern::All),
+        ])),
+        ("kitchen-sink", Profile::new(vec![
+            Operation::FileReadAll(PathPattern::Subpath(PathBuf::from("/usr"))),
+            Operation::FileReadAll(PathPattern::Literal(PathBuf::from("/etc/hostname"))),
+            Operation::NetworkOutbound(AddressPattern::All),
+            Operation::SystemInfoRead,
+        ])),
+    ]
+}
+
+#[cfg(target_os="linux")]
+fn golden_path(name: &str) -> PathBuf {
+    let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    path.push("tests");
+    path.push("golden");
+    path.push("seccomp");
+    path.push(env::consts::ARCH);
+    path.push(format!("{}.txt", name));
+    path
+}
+
+#[cfg(target_os="linux")]
+pub fn main() {
+    for (name, profile) in catalogue() {
+        if let Ok(profile) = profile {
+            let filter = Filter::new(&profile);
+            let listing = filter.disassemble();
+            assert_eq!(listing.lines().count(), filter.instructions().len(), "{}", name);
+            test_harness::assert_golden(&golden_path(name), &listing)
+        }
+    }
+}
+
+#[cfg(not(target_os="linux"))]
+pub fn main() {
+    // seccomp-bpf only exists on Linux.
+}
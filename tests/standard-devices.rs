@@ -0,0 +1,56 @@
+// Any copyright is dedicated to the Public Domain.
+// http://creativecommons.org/publicdomain/zero/1.0/
+
+#[cfg(any(target_os="linux", target_os="macos"))]
+#[macro_use]
+extern crate gaol;
+#[cfg(target_os="linux")]
+extern crate libc;
+
+#[cfg(target_os="linux")]
+use gaol::profile::PathPattern;
+#[cfg(any(target_os="linux", target_os="macos"))]
+use gaol::profile::{Operation, Profile};
+#[cfg(any(target_os="linux", target_os="macos"))]
+use gaol::test_harness;
+#[cfg(any(target_os="linux", target_os="macos"))]
+use std::fs::File;
+#[cfg(any(target_os="linux", target_os="macos"))]
+use std::io::{Read, Write};
+
+#[cfg(any(target_os="linux", target_os="macos"))]
+fn use_devices() {
+    // As a shell redirecting output to `/dev/null` does.
+    File::create("/dev/null").unwrap().write_all(b"discarded\n").unwrap();
+
+    let mut random = [0; 16];
+    File::open("/dev/urandom").unwrap().read_exact(&mut random).unwrap();
+    let mut zeros = [1; 16];
+    File::open("/dev/zero").unwrap().read_exact(&mut zeros).unwrap();
+    assert_eq!(zeros, [0; 16]);
+}
+
+#[cfg(any(target_os="linux", target_os="macos"))]
+pub fn main() {
+    let profile = Profile::new(vec![Operation::StandardDevices]).unwrap();
+    assert_allowed!(test_harness::run_in_sandbox(profile, "allowance_test", use_devices));
+    assert_violation!(test_harness::run_in_sandbox(Profile::new(Vec::new()).unwrap(),
+                                                   "prohibition_test",
+                                                   use_devices));
+
+    // Without the operation, the devices aren't in the jail even when other files can be read.
+    #[cfg(target_os="linux")]
+    {
+        let path = test_harness::temp_file("GAOL_TEMP_FILE", b"super secret\n");
+        let profile = Profile::new(vec![
+            Operation::FileReadAll(PathPattern::Literal(path)),
+        ]).unwrap();
+        assert_allowed!(test_harness::run_in_sandbox(profile, "invisibility_test", || {
+            let error = File::open("/dev/null").unwrap_err();
+            assert_eq!(error.raw_os_error(), Some(libc::ENOENT))
+        }));
+    }
+}
+
+#[cfg(not(any(target_os="linux", target_os="macos")))]
+pub fn main() {}
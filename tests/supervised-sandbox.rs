@@ -0,0 +1,121 @@
+// Any copyright is dedicated to the Public Domain.
+// http://creativecommons.org/publicdomain/zero/1.0/
+
+#[cfg(target_os="linux")]
+extern crate gaol;
+#[cfg(target_os="linux")]
+extern crate libc;
+
+#[cfg(target_os="linux")]
+use gaol::platform::capabilities;
+#[cfg(target_os="linux")]
+use gaol::platform::linux::{Sandbox, SupervisedSandbox, SupervisorResponse};
+#[cfg(target_os="linux")]
+use gaol::platform::process::ExitStatus;
+#[cfg(target_os="linux")]
+use gaol::profile::Profile;
+#[cfg(target_os="linux")]
+use gaol::sandbox::{ChildSandbox, ChildSandboxMethods, Command};
+#[cfg(target_os="linux")]
+use std::env;
+#[cfg(target_os="linux")]
+use std::io;
+#[cfg(target_os="linux")]
+use std::mem;
+
+/// Set in the child's environment.
+#[cfg(target_os="linux")]
+const SUPERVISED_CHILD_VAR: &str = "GAOL_SUPERVISED_CHILD";
+
+/// Makes two prohibited system calls, expecting the supervisor to fail the first with `EACCES`
+/// and to return 42 from the second.
+#[cfg(target_os="linux")]
+fn child() {
+    ChildSandbox::from_environment().unwrap().activate().unwrap();
+    let fd = unsafe {
+        libc::socket(libc::AF_INET, libc::SOCK_STREAM, 0)
+    };
+    assert_eq!(fd, -1);
+    assert_eq!(io::Error::last_os_error().raw_os_error(), Some(libc::EACCES));
+    let fd = unsafe {
+        libc::socket(libc::AF_INET6, libc::SOCK_DGRAM, 0)
+    };
+    assert_eq!(fd, 42);
+}
+
+/// Makes three prohibited system calls, expecting `handle_requests()` to fail `connect` with
+/// `ECONNREFUSED`, to return 7 from `socket`, and to let `uname` run.
+#[cfg(target_os="linux")]
+fn looping_child() {
+    ChildSandbox::from_environment().unwrap().activate().unwrap();
+    let address: libc::sockaddr_in = unsafe {
+        mem::zeroed()
+    };
+    let result = unsafe {
+        libc::connect(0,
+                      &address as *const libc::sockaddr_in as *const libc::sockaddr,
+                      mem::size_of::<libc::sockaddr_in>() as libc::socklen_t)
+    };
+    assert_eq!(result, -1);
+    assert_eq!(io::Error::last_os_error().raw_os_error(), Some(libc::ECONNREFUSED));
+    assert_eq!(unsafe { libc::socket(libc::AF_INET, libc::SOCK_STREAM, 0) }, 7);
+    let mut name: libc::utsname = unsafe {
+        mem::zeroed()
+    };
+    assert_eq!(unsafe { libc::uname(&mut name) }, 0);
+}
+
+/// Supervises a child with `handle_requests()`, which returns once the child has exited.
+#[cfg(target_os="linux")]
+fn test_handle_requests() {
+    let mut command = Command::me().unwrap();
+    command.env(SUPERVISED_CHILD_VAR, "loop");
+    let sandbox = SupervisedSandbox::new(Sandbox::new(Profile::new(Vec::new()).unwrap()));
+    let (process, notify) = sandbox.start(&mut command).unwrap();
+    let mut syscalls = Vec::new();
+    notify.handle_requests(|notification| {
+        syscalls.push(notification.syscall as libc::c_long);
+        match notification.syscall as libc::c_long {
+            libc::SYS_connect => SupervisorResponse::Errno(libc::ECONNREFUSED),
+            libc::SYS_socket => SupervisorResponse::Return(7),
+            _ => SupervisorResponse::Continue,
+        }
+    }).unwrap();
+    assert_eq!(&syscalls[..3], &[libc::SYS_connect, libc::SYS_socket, libc::SYS_uname][..]);
+    assert_eq!(process.wait().unwrap(), ExitStatus::Code(0));
+}
+
+#[cfg(target_os="linux")]
+pub fn main() {
+    match env::var(SUPERVISED_CHILD_VAR).as_ref().map(|mode| &mode[..]) {
+        Ok("loop") => return looping_child(),
+        Ok(_) => return child(),
+        Err(_) => {}
+    }
+    if !capabilities().seccomp_user_notif {
+        return
+    }
+    test_handle_requests();
+
+    let mut command = Command::me().unwrap();
+    command.env(SUPERVISED_CHILD_VAR, "1");
+    let sandbox = SupervisedSandbox::new(Sandbox::new(Profile::new(Vec::new()).unwrap()));
+    let (process, notify) = sandbox.start(&mut command).unwrap();
+
+    let notification = notify.recv_notification().unwrap();
+    assert_eq!(notification.syscall as libc::c_long, libc::SYS_socket);
+    assert_eq!(notification.args[0], libc::AF_INET as u64);
+    assert!(notify.id_valid(notification.id));
+    notify.send_response(notification.id, -libc::EACCES, 0, 0).unwrap();
+
+    let notification = notify.recv_notification().unwrap();
+    assert_eq!(notification.args[0], libc::AF_INET6 as u64);
+    notify.send_response(notification.id, 0, 42, 0).unwrap();
+
+    // Stop supervising, so that a failing child isn't left waiting on a panic.
+    drop(notify);
+    assert_eq!(process.wait().unwrap(), ExitStatus::Code(0));
+}
+
+#[cfg(not(target_os="linux"))]
+pub fn main() {}
@@ -0,0 +1,91 @@
+// Any copyright is dedicated to the Public Domain.
+// http://creativecommons.org/publicdomain/zero/1.0/
+
+//! Symbolic links in the profile's paths are recreated in the jail, leading to what they lead to
+//! on the host.
+
+#[cfg(target_os="linux")]
+#[macro_use]
+extern crate gaol;
+
+#[cfg(target_os="linux")]
+use gaol::profile::{Operation, PathPattern, Profile};
+#[cfg(target_os="linux")]
+use gaol::sandbox::{Command, Sandbox, SandboxMethods};
+#[cfg(target_os="linux")]
+use gaol::test_harness;
+#[cfg(target_os="linux")]
+use std::fs;
+#[cfg(target_os="linux")]
+use std::io;
+#[cfg(target_os="linux")]
+use std::os::unix::fs::symlink;
+#[cfg(target_os="linux")]
+use std::path::Path;
+
+#[cfg(target_os="linux")]
+fn read_profile(path: &Path) -> Profile {
+    Profile::new(vec![
+        Operation::FileReadAll(PathPattern::Literal(path.to_path_buf())),
+    ]).unwrap()
+}
+
+#[cfg(target_os="linux")]
+fn test_relative_symlink(directory: &Path) {
+    let link = directory.join("relative");
+    assert_allowed!(test_harness::run_in_sandbox(read_profile(&link), "relative", || {
+        assert_eq!(fs::read(&link).unwrap(), b"super secret\n");
+        assert_eq!(fs::read_link(&link).unwrap(), Path::new("real/file"))
+    }));
+}
+
+#[cfg(target_os="linux")]
+fn test_absolute_symlink(directory: &Path) {
+    let link = directory.join("absolute");
+    assert_allowed!(test_harness::run_in_sandbox(read_profile(&link), "absolute", || {
+        assert_eq!(fs::read(&link).unwrap(), b"super secret\n");
+        assert_eq!(fs::read_link(&link).unwrap(), directory.join("real/file"))
+    }));
+}
+
+#[cfg(target_os="linux")]
+fn test_symlinked_parent_directory(directory: &Path) {
+    let path = directory.join("linked-dir/file");
+    assert_allowed!(test_harness::run_in_sandbox(read_profile(&path), "parent", || {
+        assert_eq!(fs::read(&path).unwrap(), b"super secret\n");
+        assert_eq!(fs::read_link(directory.join("linked-dir")).unwrap(), Path::new("real"));
+        assert_eq!(fs::read(directory.join("real/file")).unwrap(), b"super secret\n")
+    }));
+}
+
+#[cfg(target_os="linux")]
+fn test_dangling_symlink(directory: &Path) {
+    let link = directory.join("dangling");
+    let sandbox = Sandbox::new(read_profile(&link));
+    let err = sandbox.start(&mut Command::new("/bin/true")).err().unwrap();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    assert!(err.to_string().contains("nowhere"), "{}", err);
+}
+
+#[cfg(target_os="linux")]
+pub fn main() {
+    let directory = test_harness::temp_dir("GAOL_TEMP_DIR");
+    if !test_harness::in_child() {
+        fs::create_dir(directory.join("real")).unwrap();
+        fs::write(directory.join("real/file"), b"super secret\n").unwrap();
+        symlink("real/file", directory.join("relative")).unwrap();
+        symlink(directory.join("real/file"), directory.join("absolute")).unwrap();
+        symlink("real", directory.join("linked-dir")).unwrap();
+        symlink("nowhere", directory.join("dangling")).unwrap();
+    }
+
+    test_relative_symlink(&directory);
+    test_absolute_symlink(&directory);
+    test_symlinked_parent_directory(&directory);
+    if !test_harness::in_child() {
+        test_dangling_symlink(&directory);
+    }
+}
+
+#[cfg(not(target_os="linux"))]
+pub fn main() {}
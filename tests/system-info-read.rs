@@ -1,26 +1,41 @@
 // Any copyright is dedicated to the Public Domain.
 // http://creativecommons.org/publicdomain/zero/1.0/
 
+#[cfg(any(target_os="linux", target_os="macos"))]
+#[macro_use]
 extern crate gaol;
+#[cfg(any(target_os="linux", target_os="macos"))]
 extern crate libc;
 
+#[cfg(target_os="linux")]
+use gaol::platform::linux::seccomp::Filter;
+#[cfg(target_os="linux")]
+use gaol::profile::PathPattern;
+#[cfg(any(target_os="linux", target_os="macos"))]
 use gaol::profile::{Operation, Profile};
-use gaol::sandbox::{ChildSandbox, ChildSandboxMethods, Command, Sandbox, SandboxMethods};
+#[cfg(any(target_os="linux", target_os="macos"))]
+use gaol::test_harness;
+#[cfg(target_os="macos")]
 use libc::{c_char, c_int, c_void, size_t};
-use std::env;
+#[cfg(any(target_os="linux", target_os="macos"))]
 use std::ffi::CString;
-use std::iter;
+#[cfg(target_os="linux")]
+use std::io;
+#[cfg(target_os="linux")]
+use std::mem;
+#[cfg(target_os="macos")]
 use std::ptr;
 
-static SYSCTL_NAME: &'static str = "hw.ncpu";
+#[cfg(target_os="macos")]
+static SYSCTL_NAME: &str = "hw.ncpu";
 
 #[cfg(target_os="macos")]
-fn look_at_sysctl() {
+fn look_at_system_info() {
     let sysctl_name = CString::new(SYSCTL_NAME.as_bytes().to_vec()).unwrap();
     let mut length = 0;
     unsafe {
         sysctlbyname(sysctl_name.as_ptr(), ptr::null_mut(), &mut length, ptr::null_mut(), 0);
-        let mut value: Vec<_> = iter::repeat(0).take(length as usize).collect();
+        let mut value = vec![0u8; length];
         assert!(sysctlbyname(sysctl_name.as_ptr(),
                              value.as_mut_ptr() as *mut c_void,
                              &mut length,
@@ -29,57 +44,90 @@ fn look_at_sysctl() {
     }
 }
 
-fn allowance_profile() -> Profile {
-    Profile::new(vec![Operation::SystemInfoRead]).unwrap()
+/// Opens `path` for reading, without the `openat` that the standard library uses, which the
+/// filter doesn't allow.
+#[cfg(target_os="linux")]
+fn open(path: &str) -> io::Result<libc::c_int> {
+    let c_path = CString::new(path).unwrap();
+    let fd = unsafe {
+        #[cfg(not(any(target_arch="aarch64", target_arch="riscv64")))]
+        let fd = libc::syscall(libc::SYS_open, c_path.as_ptr(), libc::O_RDONLY);
+        #[cfg(any(target_arch="aarch64", target_arch="riscv64"))]
+        let fd = libc::syscall(libc::SYS_openat, libc::AT_FDCWD, c_path.as_ptr(), libc::O_RDONLY);
+        fd as libc::c_int
+    };
+    if fd < 0 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(fd)
+    }
 }
 
-fn prohibition_profile() -> Profile {
-    Profile::new(Vec::new()).unwrap()
+#[cfg(target_os="linux")]
+fn look_at_system_info() {
+    unsafe {
+        let mut utsname: libc::utsname = mem::zeroed();
+        assert_eq!(libc::uname(&mut utsname), 0);
+        let mut sysinfo: libc::sysinfo = mem::zeroed();
+        assert_eq!(libc::sysinfo(&mut sysinfo), 0);
+        let mut rlimit: libc::rlimit = mem::zeroed();
+        assert_eq!(libc::getrlimit(libc::RLIMIT_NOFILE, &mut rlimit), 0);
+        for path in &["/proc/cpuinfo", "/proc/meminfo"] {
+            let fd = open(path).unwrap();
+            let mut buffer = [0u8; 64];
+            assert!(libc::read(fd, buffer.as_mut_ptr() as *mut libc::c_void, buffer.len()) > 0);
+            libc::close(fd);
+        }
+    }
 }
 
-#[cfg(target_os="macos")]
-pub fn allowance_test() {
-    ChildSandbox::new(allowance_profile()).activate().unwrap();
-    look_at_sysctl();
+#[cfg(any(target_os="linux", target_os="macos"))]
+fn allowance_profile() -> Profile {
+    Profile::new(vec![Operation::SystemInfoRead]).unwrap()
 }
 
-#[cfg(target_os="macos")]
-pub fn prohibition_test() {
-    ChildSandbox::new(prohibition_profile()).activate().unwrap();
-    look_at_sysctl();
+#[cfg(any(target_os="linux", target_os="macos"))]
+fn prohibition_profile() -> Profile {
+    Profile::new(Vec::new()).unwrap()
 }
 
-#[cfg(target_os="macos")]
+#[cfg(any(target_os="linux", target_os="macos"))]
 pub fn main() {
-    match env::args().skip(1).next() {
-        Some(ref arg) if arg == "allowance_test" => return allowance_test(),
-        Some(ref arg) if arg == "prohibition_test" => return prohibition_test(),
-        _ => {}
-    }
+    assert_allowed!(test_harness::run_in_sandbox(allowance_profile(),
+                                                 "allowance_test",
+                                                 look_at_system_info));
+    assert_violation!(test_harness::run_in_sandbox(prohibition_profile(),
+                                                   "prohibition_test",
+                                                   look_at_system_info));
 
-    let allowance_status =
-        Sandbox::new(allowance_profile()).start(&mut Command::me().unwrap().arg("allowance_test"))
-                                         .unwrap()
-                                         .wait()
-                                         .unwrap();
-    assert!(allowance_status.success());
+    // Without the operation, the files aren't in the jail even when other files can be read.
+    #[cfg(target_os="linux")]
+    {
+        let path = test_harness::temp_file("GAOL_TEMP_FILE", b"super secret\n");
+        let profile = Profile::new(vec![
+            Operation::FileReadAll(PathPattern::Literal(path)),
+        ]).unwrap();
+        assert_allowed!(test_harness::run_in_sandbox(profile, "invisibility_test", || {
+            assert_eq!(open("/proc/cpuinfo").unwrap_err().raw_os_error(), Some(libc::ENOENT))
+        }));
 
-    let prohibition_status =
-        Sandbox::new(prohibition_profile()).start(&mut Command::me().unwrap()
-                                                                    .arg("prohibition_test"))
-                                           .unwrap()
-                                           .wait()
-                                           .unwrap();
-    assert!(!prohibition_status.success());
+        // Setting limits is prohibited even through a pointer whose low half is zero.
+        let filter = Filter::new(&allowance_profile());
+        let resource = libc::RLIMIT_NOFILE as u64;
+        let prlimit64 = libc::SYS_prlimit64 as u32;
+        assert!(filter.allows(prlimit64, &[0, resource, 0, 0x1000, 0, 0]));
+        assert!(!filter.allows(prlimit64, &[0, resource, 0x1000, 0x1000, 0, 0]));
+        assert!(!filter.allows(prlimit64, &[0, resource, 1 << 32, 0x1000, 0, 0]));
+    }
 }
 
-#[cfg(not(target_os="macos"))]
+#[cfg(not(any(target_os="linux", target_os="macos")))]
 pub fn main() {
-    // Currently unsupported on non-Mac platforms.
+    // Currently unsupported on other platforms.
 }
 
 #[cfg(target_os="macos")]
-extern {
+extern "C" {
     fn sysctlbyname(name: *const c_char,
                     oldp: *mut c_void,
                     oldlenp: *mut size_t,
@@ -87,4 +135,3 @@ extern {
                     newlen: size_t)
                     -> c_int;
 }
-
@@ -0,0 +1,172 @@
+// Any copyright is dedicated to the Public Domain.
+// http://creativecommons.org/publicdomain/zero/1.0/
+
+extern crate gaol;
+extern crate libc;
+
+use gaol::platform::process::ExitStatus;
+use gaol::profile::Profile;
+use gaol::sandbox::{ChildSandbox, ChildSandboxMethods, Command, Sandbox, SandboxMethods};
+use gaol::sandbox::SandboxOptions;
+use gaol::telemetry::{ActivationStatus, Mechanism, ResourceUsage, SandboxTelemetry, Stage};
+use gaol::telemetry::Violation;
+use std::env;
+use std::sync::{Arc, Mutex};
+
+/// The environment variable that makes this test act as a child that violates its sandbox.
+const VIOLATING_CHILD_VAR: &str = "GAOL_TELEMETRY_VIOLATING_CHILD";
+
+/// The environment variable that makes this test act as a child that activates its sandbox and
+/// exits.
+const ACTIVATING_CHILD_VAR: &str = "GAOL_TELEMETRY_ACTIVATING_CHILD";
+
+#[derive(Clone, Debug, PartialEq)]
+enum Call {
+    Activation(ActivationStatus),
+    Degradation(Vec<Mechanism>),
+    Violation(Violation),
+    ChildExit(ExitStatus, bool),
+}
+
+#[derive(Default)]
+struct RecordingTelemetry {
+    calls: Mutex<Vec<Call>>,
+}
+
+impl RecordingTelemetry {
+    /// Returns the calls so far, apart from degradations, which depend on the machine.
+    fn take_calls(&self) -> Vec<Call> {
+        let mut calls = self.calls.lock().unwrap();
+        let result = calls.iter().filter(|call| !matches!(**call, Call::Degradation(_))).cloned();
+        let result = result.collect();
+        calls.clear();
+        result
+    }
+}
+
+impl SandboxTelemetry for RecordingTelemetry {
+    fn activation(&self, status: &ActivationStatus) {
+        self.calls.lock().unwrap().push(Call::Activation(status.clone()))
+    }
+
+    fn degradation(&self, missing: &[Mechanism]) {
+        let mut calls = self.calls.lock().unwrap();
+        assert!(calls.is_empty(), "degradation must be reported first");
+        calls.push(Call::Degradation(missing.to_vec()))
+    }
+
+    fn violation(&self, violation: &Violation) {
+        self.calls.lock().unwrap().push(Call::Violation(violation.clone()))
+    }
+
+    fn child_exit(&self, status: &ExitStatus, rusage: Option<&ResourceUsage>) {
+        self.calls.lock().unwrap().push(Call::ChildExit(*status, rusage.is_some()))
+    }
+}
+
+fn profile() -> Profile {
+    Profile::new(Vec::new()).unwrap()
+}
+
+fn sandbox(telemetry: &Arc<RecordingTelemetry>) -> Sandbox {
+    let mut options = SandboxOptions::new();
+    options.telemetry(telemetry.clone());
+    Sandbox::with_options(profile(), options)
+}
+
+fn assert_activation(call: &Call, pid: i32) {
+    match *call {
+        Call::Activation(ref status) => {
+            assert_eq!(status.pid, Some(pid));
+            assert_eq!(status.errno, None);
+        }
+        ref call => panic!("expected an activation, got {:?}", call),
+    }
+}
+
+fn test_normal_run(telemetry: &Arc<RecordingTelemetry>) {
+    let process = sandbox(telemetry).start(&mut Command::new("/bin/true")).unwrap();
+    assert_eq!(process.wait().unwrap(), ExitStatus::Code(0));
+
+    let calls = telemetry.take_calls();
+    assert_eq!(calls.len(), 2, "{:?}", calls);
+    assert_activation(&calls[0], process.pid);
+    assert_eq!(calls[1], Call::ChildExit(ExitStatus::Code(0), true));
+}
+
+fn test_violation_run(telemetry: &Arc<RecordingTelemetry>) {
+    let mut command = Command::me().unwrap();
+    command.env(VIOLATING_CHILD_VAR, "1");
+    let process = sandbox(telemetry).start(&mut command).unwrap();
+    let status = process.wait().unwrap();
+    assert_eq!(status, ExitStatus::Signal(libc::SIGSYS));
+
+    let calls = telemetry.take_calls();
+    assert_eq!(calls.len(), 3, "{:?}", calls);
+    assert_activation(&calls[0], process.pid);
+    assert_eq!(calls[1], Call::Violation(Violation {
+        pid: process.pid,
+        signal: libc::SIGSYS,
+        syscall: None,
+    }));
+    assert_eq!(calls[2], Call::ChildExit(status, true));
+}
+
+fn assert_timings(telemetry: &Arc<RecordingTelemetry>,
+                  mut options: SandboxOptions,
+                  expected: &[Stage]) {
+    options.telemetry(telemetry.clone());
+    let mut command = Command::me().unwrap();
+    command.env(ACTIVATING_CHILD_VAR, "1");
+    let process = Sandbox::with_options(profile(), options).start(&mut command).unwrap();
+    assert_eq!(process.wait().unwrap(), ExitStatus::Code(0));
+
+    match telemetry.take_calls()[0] {
+        Call::Activation(ref status) => {
+            let stages: Vec<Stage> = status.timings().iter().map(|timing| timing.0).collect();
+            assert_eq!(stages, expected);
+        }
+        ref call => panic!("expected an activation, got {:?}", call),
+    }
+}
+
+fn test_activation_timings(telemetry: &Arc<RecordingTelemetry>) {
+    assert_timings(telemetry, SandboxOptions::new(), &[
+        Stage::Unshare,
+        Stage::IdMaps,
+        Stage::AuxiliaryNamespaces,
+        Stage::TmpfsMount,
+        Stage::BindMounts,
+        Stage::Chroot,
+        Stage::FilterInstall,
+    ]);
+    let mut options = SandboxOptions::new();
+    options.disable_chroot().acknowledge_weakened_sandbox();
+    assert_timings(telemetry, options, &[
+        Stage::Unshare,
+        Stage::IdMaps,
+        Stage::AuxiliaryNamespaces,
+        Stage::FilterInstall,
+    ]);
+}
+
+pub fn main() {
+    if env::var_os(ACTIVATING_CHILD_VAR).is_some() {
+        ChildSandbox::from_environment().unwrap().activate().unwrap();
+        return
+    }
+    if env::var_os(VIOLATING_CHILD_VAR).is_some() {
+        ChildSandbox::from_environment().unwrap().activate().unwrap();
+        unsafe {
+            libc::socket(libc::AF_INET, libc::SOCK_STREAM, 0);
+        }
+        return
+    }
+
+    let telemetry = Arc::new(RecordingTelemetry::default());
+    test_normal_run(&telemetry);
+    if cfg!(any(target_os="android", target_os="linux")) {
+        test_violation_run(&telemetry);
+        test_activation_timings(&telemetry);
+    }
+}
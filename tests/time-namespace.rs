@@ -0,0 +1,60 @@
+// Any copyright is dedicated to the Public Domain.
+// http://creativecommons.org/publicdomain/zero/1.0/
+
+#[cfg(target_os="linux")]
+#[macro_use]
+extern crate gaol;
+#[cfg(target_os="linux")]
+extern crate libc;
+
+#[cfg(target_os="linux")]
+use gaol::platform::capabilities;
+#[cfg(target_os="linux")]
+use gaol::profile::Profile;
+#[cfg(target_os="linux")]
+use gaol::sandbox::SandboxOptions;
+#[cfg(target_os="linux")]
+use gaol::test_harness;
+#[cfg(target_os="linux")]
+use std::mem;
+
+/// How many seconds a child may take to start and read its clocks.
+#[cfg(target_os="linux")]
+const STARTUP_SECONDS: libc::time_t = 30;
+
+#[cfg(target_os="linux")]
+fn seconds(clock: libc::clockid_t) -> libc::time_t {
+    unsafe {
+        let mut now: libc::timespec = mem::zeroed();
+        assert_eq!(libc::clock_gettime(clock, &mut now), 0);
+        now.tv_sec
+    }
+}
+
+#[cfg(target_os="linux")]
+pub fn main() {
+    if !capabilities().time_namespaces {
+        return
+    }
+
+    let mut options = SandboxOptions::new();
+    options.isolate_time(true);
+    assert_allowed!(test_harness::run_in_sandbox_with_options(Profile::new(Vec::new()).unwrap(),
+                                                              options,
+                                                              "isolated_test",
+                                                              || {
+        assert!(seconds(libc::CLOCK_MONOTONIC) < STARTUP_SECONDS);
+        assert!(seconds(libc::CLOCK_BOOTTIME) < STARTUP_SECONDS);
+    }));
+
+    // Otherwise the child shares the host's clocks, which started counting at boot.
+    let host_seconds = seconds(libc::CLOCK_MONOTONIC);
+    assert_allowed!(test_harness::run_in_sandbox(Profile::new(Vec::new()).unwrap(),
+                                                 "shared_test",
+                                                 || {
+        assert!(seconds(libc::CLOCK_MONOTONIC) >= host_seconds);
+    }));
+}
+
+#[cfg(not(target_os="linux"))]
+fn main() {}
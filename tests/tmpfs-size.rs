@@ -0,0 +1,56 @@
+// Any copyright is dedicated to the Public Domain.
+// http://creativecommons.org/publicdomain/zero/1.0/
+
+//! The `tmpfs` the jail is built on gets the size limit the sandbox asks for.
+
+#[cfg(target_os="linux")]
+#[macro_use]
+extern crate gaol;
+
+#[cfg(target_os="linux")]
+use gaol::profile::{Operation, PathPattern, Profile};
+#[cfg(target_os="linux")]
+use gaol::sandbox::SandboxOptions;
+#[cfg(target_os="linux")]
+use gaol::test_harness;
+#[cfg(target_os="linux")]
+use std::fs;
+#[cfg(target_os="linux")]
+use std::path::PathBuf;
+
+#[cfg(target_os="linux")]
+fn profile() -> Profile {
+    Profile::new(vec![Operation::FileReadAll(PathPattern::Subpath(PathBuf::from("/proc")))])
+        .unwrap()
+}
+
+/// Returns the mount options of the jail's root, as `/proc/self/mounts` lists them.
+#[cfg(target_os="linux")]
+fn root_options() -> Vec<String> {
+    let mounts = fs::read_to_string("/proc/self/mounts").unwrap();
+    let root = mounts.lines().find(|line| line.split(' ').nth(1) == Some("/")).unwrap();
+    root.split(' ').nth(3).unwrap().split(',').map(str::to_owned).collect()
+}
+
+#[cfg(target_os="linux")]
+pub fn main() {
+    let mut options = SandboxOptions::new();
+    options.tmpfs_size_bytes(1 << 20);
+    assert_allowed!(test_harness::run_in_sandbox_with_options(profile(),
+                                                              options,
+                                                              "limited",
+                                                              || {
+        let options = root_options();
+        assert!(options.contains(&"size=1024k".to_owned()), "{:?}", options);
+        // Nothing in the jail can fill it, since it is read-only by the time the child runs.
+        assert!(options.contains(&"ro".to_owned()), "{:?}", options);
+    }));
+
+    assert_allowed!(test_harness::run_in_sandbox(profile(), "default", || {
+        let options = root_options();
+        assert!(!options.contains(&"size=1024k".to_owned()), "{:?}", options);
+    }));
+}
+
+#[cfg(not(target_os="linux"))]
+pub fn main() {}
@@ -0,0 +1,97 @@
+// Any copyright is dedicated to the Public Domain.
+// http://creativecommons.org/publicdomain/zero/1.0/
+
+#[cfg(target_os="linux")]
+extern crate gaol;
+
+#[cfg(target_os="linux")]
+use gaol::profile::{AddressPattern, Operation, PathPattern, Profile};
+#[cfg(target_os="linux")]
+use gaol::sandbox::{Command, Sandbox, SandboxOptions};
+#[cfg(target_os="linux")]
+use std::env;
+#[cfg(target_os="linux")]
+use std::fs::{self, File};
+#[cfg(target_os="linux")]
+use std::io::{self, Read, Write};
+#[cfg(target_os="linux")]
+use std::net::{TcpListener, TcpStream};
+#[cfg(target_os="linux")]
+use std::path::PathBuf;
+#[cfg(target_os="linux")]
+use std::process;
+
+/// The environment variables that make this test act as the traced child, naming the files to
+/// read and the port to connect to.
+#[cfg(target_os="linux")]
+const FIRST_FILE_VAR: &str = "GAOL_TRACE_FIRST_FILE";
+#[cfg(target_os="linux")]
+const SECOND_FILE_VAR: &str = "GAOL_TRACE_SECOND_FILE";
+#[cfg(target_os="linux")]
+const PORT_VAR: &str = "GAOL_TRACE_PORT";
+
+#[cfg(target_os="linux")]
+fn traced_child() {
+    for var in &[FIRST_FILE_VAR, SECOND_FILE_VAR] {
+        let mut contents = String::new();
+        File::open(env::var(var).unwrap()).unwrap().read_to_string(&mut contents).unwrap();
+    }
+    let port: u16 = env::var(PORT_VAR).unwrap().parse().unwrap();
+    TcpStream::connect(("127.0.0.1", port)).unwrap();
+}
+
+#[cfg(target_os="linux")]
+fn temp_file(name: &str) -> PathBuf {
+    let mut path = fs::canonicalize(env::temp_dir()).unwrap();
+    path.push(format!("gaoltest-trace.{}.{}", process::id(), name));
+    File::create(&path).unwrap().write_all(b"traced\n").unwrap();
+    path
+}
+
+#[cfg(target_os="linux")]
+fn test_acknowledgement_required() {
+    let sandbox = Sandbox::new(Profile::new(Vec::new()).unwrap());
+    let err = sandbox.trace(&mut Command::new("/bin/true")).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+}
+
+#[cfg(target_os="linux")]
+fn test_suggestions() {
+    let first = temp_file("first");
+    let second = temp_file("second");
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+
+    let mut options = SandboxOptions::new();
+    options.acknowledge_weakened_sandbox();
+    let sandbox = Sandbox::with_options(Profile::new(Vec::new()).unwrap(), options);
+    let mut command = Command::me().unwrap();
+    command.env(FIRST_FILE_VAR, &first)
+           .env(SECOND_FILE_VAR, &second)
+           .env(PORT_VAR, port.to_string());
+    let report = sandbox.trace(&mut command).unwrap();
+    drop(fs::remove_file(&first));
+    drop(fs::remove_file(&second));
+
+    assert_eq!(report.exit_code, Some(0), "{:?}", report);
+    assert_eq!(report.signal, None);
+    for path in &[first, second] {
+        let operation = Operation::FileReadAll(PathPattern::Literal(path.clone()));
+        assert!(report.suggestions.contains(&operation), "{:?}", report.suggestions);
+    }
+    let connect = Operation::NetworkOutbound(AddressPattern::Tcp(port));
+    assert!(report.suggestions.contains(&connect), "{:?}", report.suggestions);
+    assert!(report.events.iter().any(|event| event.name.as_deref() == Some("connect")));
+}
+
+#[cfg(target_os="linux")]
+pub fn main() {
+    if env::var_os(FIRST_FILE_VAR).is_some() {
+        return traced_child()
+    }
+    test_acknowledgement_required();
+    test_suggestions();
+}
+
+#[cfg(not(target_os="linux"))]
+fn main() {}
@@ -0,0 +1,50 @@
+// Any copyright is dedicated to the Public Domain.
+// http://creativecommons.org/publicdomain/zero/1.0/
+
+#[cfg(target_os="linux")]
+#[macro_use]
+extern crate gaol;
+#[cfg(target_os="linux")]
+extern crate libc;
+
+#[cfg(target_os="linux")]
+use gaol::profile::{Operation, PathPattern, Profile};
+#[cfg(target_os="linux")]
+use gaol::sandbox::{SandboxOptions, UserMapping};
+#[cfg(target_os="linux")]
+use gaol::test_harness;
+#[cfg(target_os="linux")]
+use std::fs;
+#[cfg(target_os="linux")]
+use std::os::unix::fs::MetadataExt;
+
+#[cfg(target_os="linux")]
+pub fn main() {
+    // Children see the IDs of the parent as they are mapped in their user namespace.
+    let ids = test_harness::shared_value("GAOL_PARENT_IDS", || unsafe {
+        format!("{} {}", libc::getuid(), libc::getgid()).into()
+    });
+    let ids: Vec<u32> = ids.to_str().unwrap().split(' ').map(|id| id.parse().unwrap()).collect();
+    let path = test_harness::temp_file("GAOL_TEMP_FILE", b"owned\n");
+    let profile = Profile::new(vec![
+        Operation::FileReadAll(PathPattern::Literal(path.clone())),
+    ]).unwrap();
+
+    for &(user_mapping, uid, gid) in &[(UserMapping::Root, 0, 0),
+                                       (UserMapping::Preserve, ids[0], ids[1])] {
+        let mut options = SandboxOptions::new();
+        options.user_mapping(user_mapping);
+        let name = format!("{:?}", user_mapping);
+        assert_allowed!(test_harness::run_in_sandbox_with_options(profile.clone(),
+                                                                 options,
+                                                                 &name,
+                                                                 || {
+            let metadata = fs::metadata(&path).unwrap();
+            assert_eq!((metadata.uid(), metadata.gid()), (uid, gid));
+            assert_eq!(unsafe { libc::getuid() }, uid);
+        }));
+    }
+}
+
+#[cfg(not(target_os="linux"))]
+pub fn main() {}
@@ -0,0 +1,99 @@
+// Any copyright is dedicated to the Public Domain.
+// http://creativecommons.org/publicdomain/zero/1.0/
+
+//! Where user namespaces are turned off, starting a sandbox says why, and children can still be
+//! started confined by their seccomp filter alone.
+
+#[cfg(target_os="linux")]
+#[macro_use]
+extern crate gaol;
+#[cfg(target_os="linux")]
+extern crate libc;
+
+#[cfg(target_os="linux")]
+use gaol::error::SandboxError;
+#[cfg(target_os="linux")]
+use gaol::platform::capabilities::{self, ProbeResults};
+#[cfg(target_os="linux")]
+use gaol::platform::process::ExitStatus;
+#[cfg(target_os="linux")]
+use gaol::profile::Profile;
+#[cfg(target_os="linux")]
+use gaol::sandbox::{Command, Sandbox, SandboxMethods, SandboxOptions};
+#[cfg(target_os="linux")]
+use gaol::telemetry::Mechanism;
+#[cfg(target_os="linux")]
+use gaol::test_harness;
+#[cfg(target_os="linux")]
+use std::io;
+
+/// The probes of this machine, as if `kernel.unprivileged_userns_clone` were 0.
+#[cfg(target_os="linux")]
+fn probes_without_user_namespaces() -> ProbeResults {
+    ProbeResults {
+        unprivileged_userns_clone: Some("0\n".to_owned()),
+        user_namespace: Some(Err(libc::EPERM)),
+        mount_in_user_namespace: None,
+        ..capabilities::probe()
+    }
+}
+
+#[cfg(target_os="linux")]
+fn empty_profile() -> Profile {
+    Profile::new(Vec::new()).unwrap()
+}
+
+#[cfg(target_os="linux")]
+fn test_start_fails() {
+    let sandbox = Sandbox::new(empty_profile());
+    let err = sandbox.start(&mut Command::new("/bin/true")).err().unwrap();
+    assert_eq!(err.kind(), io::ErrorKind::Unsupported);
+    match err.get_ref().and_then(|err| err.downcast_ref::<SandboxError>()) {
+        Some(SandboxError::UserNamespacesUnavailable(reason)) => {
+            assert!(reason.to_string().contains("kernel.unprivileged_userns_clone"), "{}", reason)
+        }
+        _ => panic!("unexpected error: {}", err),
+    }
+}
+
+/// The process says what confines it, without any telemetry.
+#[cfg(target_os="linux")]
+fn test_seccomp_only_fallback() {
+    let mut options = SandboxOptions::new();
+    options.disable_namespaces().acknowledge_weakened_sandbox();
+    let sandbox = Sandbox::with_options(empty_profile(), options.clone());
+    let process = sandbox.start(&mut Command::new("/bin/true")).unwrap();
+    assert!(!process.mechanisms().contains(&Mechanism::Namespaces));
+    assert!(!process.mechanisms().contains(&Mechanism::NetworkNamespace));
+    assert!(!process.mechanisms().contains(&Mechanism::ChrootJail));
+    assert!(process.mechanisms().contains(&Mechanism::SeccompFilter));
+    assert_eq!(process.wait().unwrap(), ExitStatus::Code(0));
+}
+
+#[cfg(target_os="linux")]
+fn test_fallback_is_confined() {
+    let mut options = SandboxOptions::new();
+    options.disable_namespaces().acknowledge_weakened_sandbox();
+    assert_violation!(test_harness::run_in_sandbox_with_options(empty_profile(),
+                                                                options,
+                                                                "fallback_socket",
+                                                                || {
+        unsafe {
+            libc::socket(libc::AF_INET, libc::SOCK_STREAM, 0);
+        }
+    }), ExitStatus::Signal(libc::SIGSYS));
+}
+
+#[cfg(target_os="linux")]
+pub fn main() {
+    test_harness::fake_probes(probes_without_user_namespaces());
+    assert!(!gaol::platform::capabilities().user_namespaces);
+    if !test_harness::in_child() {
+        test_start_fails();
+        test_seccomp_only_fallback();
+    }
+    test_fallback_is_confined();
+}
+
+#[cfg(not(target_os="linux"))]
+pub fn main() {}
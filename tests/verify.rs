@@ -0,0 +1,80 @@
+// Any copyright is dedicated to the Public Domain.
+// http://creativecommons.org/publicdomain/zero/1.0/
+
+#[macro_use]
+extern crate gaol;
+extern crate libc;
+
+use gaol::profile::{AddressPattern, Operation, PathPattern, Profile};
+use gaol::sandbox::{ChildSandbox, ChildSandboxMethods, ProbeStatus, VerificationReport};
+use gaol::test_harness;
+use std::path::PathBuf;
+
+fn empty_profile() -> Profile {
+    Profile::new(Vec::new()).unwrap()
+}
+
+fn read_only_profile() -> Profile {
+    Profile::new(vec![
+        Operation::FileReadAll(PathPattern::Subpath(PathBuf::from("/etc"))),
+    ]).unwrap()
+}
+
+fn network_profile() -> Profile {
+    Profile::new(vec![Operation::NetworkOutbound(AddressPattern::All)]).unwrap()
+}
+
+fn verify_confined(profile: Profile) {
+    let report = ChildSandbox::new(profile).verify();
+    if !report.is_confined() {
+        eprintln!("{:?}", report);
+        std::process::exit(1)
+    }
+}
+
+fn check_report_semantics() {
+    assert!(!VerificationReport::default().is_confined());
+
+    let mut report = VerificationReport::default();
+    report.push("file-access", ProbeStatus::Skipped, String::new());
+    assert!(!report.is_confined());
+    report.push("seccomp", ProbeStatus::Passed, String::new());
+    assert!(report.is_confined());
+    report.push("uid", ProbeStatus::Failed, String::new());
+    assert!(!report.is_confined());
+    assert_eq!(report.failures().len(), 1);
+    assert_eq!(report.failures()[0].name, "uid");
+}
+
+#[cfg(target_os="linux")]
+fn check_filter_evaluation() {
+    use gaol::platform::linux::seccomp::Filter;
+
+    let empty = Filter::new(&empty_profile());
+    assert!(empty.allows(libc::SYS_read as u32, &[0; 6]));
+    assert!(!empty.allows(libc::SYS_socket as u32, &[libc::AF_INET as u64, 0, 0, 0, 0, 0]));
+    assert!(!empty.allows(libc::SYS_ptrace as u32, &[0; 6]));
+
+    let network = Filter::new(&network_profile());
+    assert!(network.allows(libc::SYS_socket as u32, &[libc::AF_INET as u64, 0, 0, 0, 0, 0]));
+}
+
+#[cfg(not(target_os="linux"))]
+fn check_filter_evaluation() {}
+
+pub fn main() {
+    if !test_harness::in_child() {
+        check_report_semantics();
+        check_filter_evaluation();
+    }
+
+    assert_allowed!(test_harness::run_in_sandbox(empty_profile(), "empty", || {
+        verify_confined(empty_profile())
+    }));
+    assert_allowed!(test_harness::run_in_sandbox(read_only_profile(), "read_only", || {
+        verify_confined(read_only_profile())
+    }));
+    assert_allowed!(test_harness::run_in_sandbox(network_profile(), "network", || {
+        verify_confined(network_profile())
+    }));
+}
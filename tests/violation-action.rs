@@ -0,0 +1,86 @@
+// Any copyright is dedicated to the Public Domain.
+// http://creativecommons.org/publicdomain/zero/1.0/
+
+#[cfg(target_os="linux")]
+#[macro_use]
+extern crate gaol;
+#[cfg(target_os="linux")]
+extern crate libc;
+
+#[cfg(target_os="linux")]
+use gaol::platform::capabilities;
+#[cfg(target_os="linux")]
+use gaol::platform::linux::seccomp::Filter;
+#[cfg(target_os="linux")]
+use gaol::profile::{Profile, ViolationAction};
+#[cfg(target_os="linux")]
+use gaol::test_harness;
+#[cfg(target_os="linux")]
+use std::io;
+
+#[cfg(target_os="linux")]
+fn profile(violation_action: ViolationAction) -> Profile {
+    Profile::new(Vec::new()).unwrap().with_violation_action(violation_action)
+}
+
+#[cfg(target_os="linux")]
+fn create_socket() -> io::Result<()> {
+    let fd = unsafe {
+        libc::socket(libc::AF_INET, libc::SOCK_STREAM, 0)
+    };
+    if fd < 0 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(())
+    }
+}
+
+#[cfg(target_os="linux")]
+pub fn main() {
+    assert_violation!(test_harness::run_in_sandbox(profile(ViolationAction::Kill), "kill", || {
+        drop(create_socket())
+    }));
+
+    let errno = profile(ViolationAction::Errno(libc::EPERM));
+    assert_allowed!(test_harness::run_in_sandbox(errno, "errno", || {
+        assert_eq!(create_socket().unwrap_err().raw_os_error(), Some(libc::EPERM));
+        // Allowed system calls still run after a denial.
+        assert_eq!(unsafe { libc::getuid() }, 0);
+        assert_eq!(unsafe { libc::write(2, b"".as_ptr() as *const libc::c_void, 0) }, 0);
+    }));
+
+    let errno = profile(ViolationAction::Errno(libc::ENOSYS));
+    assert_allowed!(test_harness::run_in_sandbox(errno, "enosys", || {
+        assert_eq!(create_socket().unwrap_err().raw_os_error(), Some(libc::ENOSYS))
+    }));
+
+    // Without kernel support for logging, prohibited system calls fail with `EPERM` instead.
+    let log_supported = capabilities().seccomp_ret_log;
+    assert_allowed!(test_harness::run_in_sandbox(profile(ViolationAction::Log), "log", || {
+        match create_socket() {
+            Ok(()) => assert!(log_supported),
+            Err(error) => {
+                assert!(!log_supported);
+                assert_eq!(error.raw_os_error(), Some(libc::EPERM))
+            }
+        }
+    }));
+
+    const SECCOMP_RET_ALLOW: u32 = 0x7fff_0000;
+    const SECCOMP_RET_ERRNO: u32 = 0x0005_0000;
+    let socket_args = [libc::AF_INET as u64, 0, 0, 0, 0, 0];
+    let errno = Filter::new_with_action(&profile(ViolationAction::Kill),
+                                        ViolationAction::Errno(libc::EPERM));
+    assert_eq!(errno.evaluate(libc::SYS_socket as u32, &socket_args),
+               SECCOMP_RET_ERRNO | libc::EPERM as u32);
+    assert_eq!(errno.evaluate(libc::SYS_read as u32, &[0; 6]), SECCOMP_RET_ALLOW);
+
+    const SECCOMP_RET_LOG: u32 = 0x7ffc_0000;
+    let audit = Filter::audit_only(&profile(ViolationAction::Kill));
+    assert_eq!(audit.evaluate(libc::SYS_read as u32, &[0; 6]), SECCOMP_RET_LOG);
+    assert_eq!(audit.evaluate(libc::SYS_socket as u32, &socket_args), SECCOMP_RET_LOG);
+    assert!(!audit.allows(libc::SYS_read as u32, &[0; 6]));
+}
+
+#[cfg(not(target_os="linux"))]
+pub fn main() {}
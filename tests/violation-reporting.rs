@@ -0,0 +1,104 @@
+// Any copyright is dedicated to the Public Domain.
+// http://creativecommons.org/publicdomain/zero/1.0/
+
+#[cfg(target_os="linux")]
+#[macro_use]
+extern crate gaol;
+#[cfg(target_os="linux")]
+extern crate libc;
+
+#[cfg(target_os="linux")]
+use gaol::platform::linux::seccomp::Filter;
+#[cfg(target_os="linux")]
+use gaol::platform::process::ExitStatus;
+#[cfg(target_os="linux")]
+use gaol::platform::linux::SysViolationInfo;
+#[cfg(target_os="linux")]
+use gaol::platform::ChildSandbox;
+#[cfg(target_os="linux")]
+use gaol::profile::{Operation, PathPattern, Profile, ViolationAction};
+#[cfg(target_os="linux")]
+use gaol::sandbox::ChildSandboxMethods;
+#[cfg(target_os="linux")]
+use gaol::test_harness;
+#[cfg(target_os="linux")]
+use std::fs::{self, OpenOptions};
+#[cfg(target_os="linux")]
+use std::os::unix::io::IntoRawFd;
+#[cfg(target_os="linux")]
+use std::path::Path;
+
+/// Without anything to write, the file size limit would keep the report out of the file.
+#[cfg(target_os="linux")]
+fn profile(report_path: &Path) -> Profile {
+    Profile::new(vec![Operation::FileAppend(PathPattern::Literal(report_path.to_owned()))])
+        .unwrap()
+}
+
+/// Exits with 42 if the prohibited system call was `socket(AF_INET, SOCK_STREAM, 0)`, and 43 if
+/// it was something else.
+#[cfg(target_os="linux")]
+fn exit_if_socket(info: &SysViolationInfo) {
+    let socket = info.syscall() == libc::SYS_socket as i32 &&
+        info.args()[..3] == [libc::AF_INET as u64, libc::SOCK_STREAM as u64, 0] &&
+        info.call_address() != 0;
+    unsafe {
+        libc::_exit(if socket { 42 } else { 43 })
+    }
+}
+
+#[cfg(target_os="linux")]
+fn make_socket() {
+    unsafe {
+        libc::socket(libc::AF_INET, libc::SOCK_STREAM, 0);
+    }
+}
+
+#[cfg(target_os="linux")]
+pub fn main() {
+    let report_path = test_harness::temp_file("report", b"");
+    let status = test_harness::run_unactivated_in_sandbox(profile(&report_path), "report", || {
+        let fd = OpenOptions::new().append(true).open(&report_path).unwrap().into_raw_fd();
+        let sandbox = ChildSandbox::from_environment().unwrap();
+        sandbox.activate_with_violation_reporting(fd).unwrap();
+        unsafe {
+            libc::socket(libc::AF_INET, libc::SOCK_STREAM, 0);
+        }
+    });
+    assert_violation!(status, ExitStatus::Signal(libc::SIGSYS));
+    if !test_harness::in_child() {
+        let report = fs::read_to_string(&report_path).unwrap();
+        drop(fs::remove_file(&report_path));
+        let expected = format!("gaol: the sandbox prohibited system call {} (arch 0x",
+                               libc::SYS_socket);
+        assert!(report.starts_with(&expected), "unexpected report {:?}", report);
+        assert!(report.ends_with(") with arguments 0x2, 0x1, 0x0\n"),
+                "unexpected report {:?}", report);
+    }
+
+    // A handler of the embedder's sees the system call instead.
+    let status = test_harness::run_unactivated_in_sandbox(profile(&report_path), "handler", || {
+        let sandbox = ChildSandbox::from_environment().unwrap();
+        sandbox.with_violation_handler(exit_if_socket).activate().unwrap();
+        make_socket()
+    });
+    assert_violation!(status, ExitStatus::Code(42));
+
+    // With `ViolationAction::Trap`, plain activation reports to standard error.
+    let trapping = profile(&report_path).with_violation_action(ViolationAction::Trap);
+    assert_violation!(test_harness::run_in_sandbox(trapping.clone(), "trap", make_socket),
+                      ExitStatus::Signal(libc::SIGSYS));
+
+    const SECCOMP_RET_ALLOW: u32 = 0x7fff_0000;
+    const SECCOMP_RET_TRAP: u32 = 0x0003_0000;
+    let socket_args = [libc::AF_INET as u64, libc::SOCK_STREAM as u64, 0, 0, 0, 0];
+    let reporting = Filter::reporting(&profile(&report_path));
+    assert_eq!(reporting.evaluate(libc::SYS_socket as u32, &socket_args), SECCOMP_RET_TRAP);
+    assert_eq!(reporting.evaluate(libc::SYS_read as u32, &[0; 6]), SECCOMP_RET_ALLOW);
+    let trapping = Filter::new(&trapping);
+    assert_eq!(trapping.evaluate(libc::SYS_socket as u32, &socket_args), SECCOMP_RET_TRAP);
+    assert_eq!(trapping.instructions(), reporting.instructions());
+}
+
+#[cfg(not(target_os="linux"))]
+pub fn main() {}
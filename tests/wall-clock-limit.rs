@@ -0,0 +1,43 @@
+// Any copyright is dedicated to the Public Domain.
+// http://creativecommons.org/publicdomain/zero/1.0/
+
+//! Children that run past their wall-clock limit are killed, and the parent can tell.
+
+#[cfg(target_os="linux")]
+extern crate gaol;
+
+#[cfg(target_os="linux")]
+use gaol::platform::process::ExitStatus;
+#[cfg(target_os="linux")]
+use gaol::profile::Profile;
+#[cfg(target_os="linux")]
+use gaol::sandbox::{Command, ResourceLimits, Sandbox, SandboxMethods};
+#[cfg(target_os="linux")]
+use std::time::{Duration, Instant};
+
+#[cfg(target_os="linux")]
+fn sandbox() -> Sandbox {
+    Sandbox::new(Profile::new(Vec::new()).unwrap()).with_resource_limits(ResourceLimits {
+        max_wall_clock_seconds: Some(1),
+        ..ResourceLimits::default()
+    })
+}
+
+#[cfg(target_os="linux")]
+pub fn main() {
+    let start = Instant::now();
+    let mut command = Command::new("/bin/sleep");
+    command.arg("100");
+    let status = sandbox().start(&mut command).unwrap().wait().unwrap();
+    assert_eq!(status, ExitStatus::TimedOut);
+    let elapsed = start.elapsed();
+    assert!(elapsed >= Duration::from_secs(1) && elapsed < Duration::from_secs(10),
+            "killed after {:?}", elapsed);
+
+    // Children that finish in time exit as usual.
+    let status = sandbox().start(&mut Command::new("/bin/true")).unwrap().wait().unwrap();
+    assert_eq!(status, ExitStatus::Code(0));
+}
+
+#[cfg(not(target_os="linux"))]
+pub fn main() {}